@@ -0,0 +1,96 @@
+/// Which side of an insertion at the anchor's position the anchor sticks to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bias {
+  /// Stay before text inserted at the anchor's position
+  Left,
+  /// Move after text inserted at the anchor's position
+  Right,
+}
+
+/// A position in the buffer that follows edits made after its creation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Anchor {
+  pub(crate) offset: usize,
+  pub(crate) version: usize,
+  pub(crate) bias: Bias,
+}
+
+impl Anchor {
+  pub fn bias(&self) -> Bias {
+    self.bias
+  }
+}
+
+/// A single applied edit, in chars, used to move anchors forward
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Edit {
+  pub start: usize,
+  pub old_len: usize,
+  pub new_len: usize,
+}
+
+impl Edit {
+  /// Map an offset from before this edit to after it
+  pub fn transform(&self, offset: usize, bias: Bias) -> usize {
+    let old_end = self.start + self.old_len;
+
+    if offset < self.start {
+      offset
+    } else if offset > old_end || (offset == old_end && self.old_len > 0) {
+      offset - self.old_len + self.new_len
+    } else if bias == Bias::Left || (offset == self.start && self.old_len > 0) {
+      self.start
+    } else {
+      self.start + self.new_len
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_transform_before_edit() {
+    let edit = Edit {
+      start: 5,
+      old_len: 2,
+      new_len: 4,
+    };
+    assert_eq!(edit.transform(3, Bias::Left), 3);
+    assert_eq!(edit.transform(3, Bias::Right), 3);
+  }
+
+  #[test]
+  fn test_transform_after_edit() {
+    let edit = Edit {
+      start: 5,
+      old_len: 2,
+      new_len: 4,
+    };
+    assert_eq!(edit.transform(7, Bias::Left), 9);
+    assert_eq!(edit.transform(10, Bias::Right), 12);
+  }
+
+  #[test]
+  fn test_transform_insertion_at_anchor() {
+    let edit = Edit {
+      start: 5,
+      old_len: 0,
+      new_len: 3,
+    };
+    assert_eq!(edit.transform(5, Bias::Left), 5);
+    assert_eq!(edit.transform(5, Bias::Right), 8);
+  }
+
+  #[test]
+  fn test_transform_inside_deletion() {
+    let edit = Edit {
+      start: 5,
+      old_len: 4,
+      new_len: 0,
+    };
+    assert_eq!(edit.transform(7, Bias::Left), 5);
+    assert_eq!(edit.transform(7, Bias::Right), 5);
+  }
+}
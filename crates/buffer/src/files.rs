@@ -0,0 +1,25 @@
+//! Writing files without leaving them half written
+
+use std::path::Path;
+
+/// Write `contents` next to `path` and rename it over the file, so a failed
+/// write never leaves it half written. A symlink is followed, and the file
+/// keeps its permissions.
+pub fn write_atomically(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+  let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+  let file_name = path
+    .file_name()
+    .ok_or_else(|| std::io::Error::other("not a file"))?
+    .to_string_lossy();
+  let temp_path = path.with_file_name(format!(".{}.save", file_name));
+  let permissions = std::fs::metadata(&path).map(|metadata| metadata.permissions());
+  std::fs::write(&temp_path, contents)
+    .and_then(|_| match permissions {
+      Ok(permissions) => std::fs::set_permissions(&temp_path, permissions),
+      Err(_) => Ok(()),
+    })
+    .and_then(|_| std::fs::rename(&temp_path, &path))
+    .inspect_err(|_| {
+      let _ = std::fs::remove_file(&temp_path);
+    })
+}
@@ -0,0 +1,211 @@
+//! Write-ahead journal of buffer edits
+//!
+//! Every edit applied to the text is appended to the journal file as it is
+//! committed. After a crash, replaying the journal over the last saved file
+//! restores the unsaved edits; the journal is truncated on save.
+//!
+//! Entries are `<start> <removed chars> <inserted bytes>\n<inserted text>\n`.
+
+use std::{
+  fs::{File, OpenOptions},
+  io::{Read, Write},
+  path::{Path, PathBuf},
+};
+
+use crate::files::write_atomically;
+
+/// One edit: remove `removed` chars at `start`, then insert `inserted` there
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct JournalEntry {
+  pub start: usize,
+  pub removed: usize,
+  pub inserted: String,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Journal {
+  path: PathBuf,
+  len: u64,
+  // Buffer version after each append and the journal length then, oldest first
+  marks: Vec<(usize, u64)>,
+}
+
+impl Journal {
+  /// Append to `path`; what it already holds predates buffer `version`
+  pub fn new(path: PathBuf, version: usize) -> Self {
+    let len = std::fs::metadata(&path).map_or(0, |metadata| metadata.len());
+    Self {
+      path,
+      len,
+      marks: vec![(version, len)],
+    }
+  }
+
+  /// Append the entries that took the buffer to `version`
+  pub fn append(&mut self, version: usize, entries: &[JournalEntry]) -> Result<(), String> {
+    let mut bytes = Vec::new();
+    // Empty removals recorded by replace leave the text unchanged
+    for entry in entries
+      .iter()
+      .filter(|entry| entry.removed > 0 || !entry.inserted.is_empty())
+    {
+      bytes.extend_from_slice(
+        format!(
+          "{} {} {}\n",
+          entry.start,
+          entry.removed,
+          entry.inserted.len()
+        )
+        .as_bytes(),
+      );
+      bytes.extend_from_slice(entry.inserted.as_bytes());
+      bytes.push(b'\n');
+    }
+    if bytes.is_empty() {
+      self.skip_to(version);
+      return Ok(());
+    }
+
+    let mut file = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&self.path)
+      .map_err(|err| err.to_string())?;
+    // A single write keeps a crash from splitting entries of one transaction
+    file.write_all(&bytes).map_err(|err| err.to_string())?;
+    self.len += bytes.len() as u64;
+    self.marks.push((version, self.len));
+    Ok(())
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Buffer version the journal has been written up to
+  pub fn version(&self) -> usize {
+    self.marks.last().map_or(0, |&(version, _)| version)
+  }
+
+  /// Note that the edits up to buffer `version` need no entries of their own
+  pub fn skip_to(&mut self, version: usize) {
+    match self.marks.last_mut() {
+      Some(mark) if mark.1 == self.len => mark.0 = version,
+      _ => self.marks.push((version, self.len)),
+    }
+  }
+
+  /// Drop the entries up to buffer `version`, keeping those of later edits
+  pub fn truncate_to(&mut self, version: usize) -> Result<(), String> {
+    let saved = self.marks.partition_point(|&(mark, _)| mark <= version);
+    let offset = match saved {
+      0 => 0,
+      saved => self.marks[saved - 1].1,
+    };
+    // Rewritten aside and renamed over, so a crash keeps the later entries
+    if offset == self.len {
+      write_atomically(&self.path, []).map_err(|err| err.to_string())?;
+    } else if offset > 0 {
+      let bytes = std::fs::read(&self.path).map_err(|err| err.to_string())?;
+      let rest = bytes.get(offset as usize..).unwrap_or_default();
+      write_atomically(&self.path, rest).map_err(|err| err.to_string())?;
+    }
+    self.len -= offset;
+    self.marks.drain(..saved);
+    for (_, len) in &mut self.marks {
+      *len -= offset;
+    }
+    self.marks.insert(0, (version, 0));
+    Ok(())
+  }
+}
+
+/// Read the complete entries of a journal, a torn last entry is ignored
+pub(crate) fn read_entries(path: &Path) -> Result<Vec<JournalEntry>, String> {
+  let mut bytes = Vec::new();
+  File::open(path)
+    .and_then(|mut file| file.read_to_end(&mut bytes))
+    .map_err(|err| err.to_string())?;
+
+  let mut entries = Vec::new();
+  let mut rest = bytes.as_slice();
+  while let Some(header_end) = rest.iter().position(|&byte| byte == b'\n') {
+    let Some(entry) = parse_header(&rest[..header_end]) else {
+      break;
+    };
+    let (start, removed, inserted_len) = entry;
+
+    let body = &rest[header_end + 1..];
+    if body.len() <= inserted_len || body[inserted_len] != b'\n' {
+      break;
+    }
+    let Ok(inserted) = String::from_utf8(body[..inserted_len].to_vec()) else {
+      break;
+    };
+
+    entries.push(JournalEntry {
+      start,
+      removed,
+      inserted,
+    });
+    rest = &body[inserted_len + 1..];
+  }
+  Ok(entries)
+}
+
+fn parse_header(line: &[u8]) -> Option<(usize, usize, usize)> {
+  let line = std::str::from_utf8(line).ok()?;
+  let mut fields = line.split(' ').map(|field| field.parse::<usize>().ok());
+  let header = (fields.next()??, fields.next()??, fields.next()??);
+  fields.next().is_none().then_some(header)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_roundtrip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("journal");
+    std::fs::write(&path, "0 0 5\nstale\n").unwrap();
+    let mut journal = Journal::new(path.clone(), 0);
+    journal.truncate_to(0).unwrap();
+
+    let entries = vec![
+      JournalEntry {
+        start: 0,
+        removed: 0,
+        inserted: "héllo\nworld".to_string(),
+      },
+      JournalEntry {
+        start: 3,
+        removed: 2,
+        inserted: String::new(),
+      },
+    ];
+    journal.append(1, &entries[..1]).unwrap();
+    journal.append(2, &entries[1..]).unwrap();
+
+    assert_eq!(read_entries(&path).unwrap(), entries);
+
+    // Edits made after the saved version stay in the journal
+    journal.truncate_to(1).unwrap();
+    assert_eq!(read_entries(&path).unwrap(), &entries[1..]);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+    journal.truncate_to(2).unwrap();
+    assert!(read_entries(&path).unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_torn_entry_is_ignored() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("journal");
+    std::fs::write(&path, b"0 0 3\nabc\n3 0 10\nabc").unwrap();
+
+    let entries = read_entries(&path).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].inserted, "abc");
+  }
+}
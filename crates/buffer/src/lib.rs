@@ -1,3 +1,12 @@
+mod anchor;
+mod files;
+mod journal;
+mod snapshot;
 mod text_buffer;
 
-pub use text_buffer::{TextBuffer, TextOperation, TransactionContext, TransactionId};
+pub use anchor::{Anchor, Bias, Edit};
+pub use files::write_atomically;
+pub use snapshot::BufferSnapshot;
+pub use text_buffer::{
+  TextBuffer, TextOperation, TransactionContext, TransactionId, TransactionSelection,
+};
@@ -0,0 +1,93 @@
+use std::{borrow::Cow, ops::Range};
+
+use ropey::Rope;
+
+use crate::text_buffer::line_content;
+
+/// An immutable copy of a buffer's text at one version
+///
+/// Cloning the rope shares its chunks with the buffer, so taking a snapshot
+/// is cheap and later edits don't change it. Background tasks read from a
+/// snapshot instead of copying the whole text up front.
+#[derive(Clone)]
+pub struct BufferSnapshot {
+  pub(crate) text: Rope,
+  pub(crate) version: usize,
+}
+
+impl BufferSnapshot {
+  /// Version of the buffer the snapshot was taken at
+  pub fn version(&self) -> usize {
+    self.version
+  }
+
+  pub fn len(&self) -> usize {
+    self.text.len_chars()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.text.len_chars() == 0
+  }
+
+  pub fn len_bytes(&self) -> usize {
+    self.text.len_bytes()
+  }
+
+  pub fn len_lines(&self) -> usize {
+    self.text.len_lines()
+  }
+
+  pub fn chars(&self) -> impl Iterator<Item = char> {
+    self.text.chars()
+  }
+
+  pub fn char_to_line(&self, char_idx: usize) -> usize {
+    self.text.char_to_line(char_idx)
+  }
+
+  pub fn line_to_char(&self, line_idx: usize) -> usize {
+    self.text.line_to_char(line_idx)
+  }
+
+  pub fn byte_to_char(&self, byte_idx: usize) -> usize {
+    self.text.byte_to_char(byte_idx)
+  }
+
+  pub fn char_to_byte(&self, char_idx: usize) -> usize {
+    self.text.char_to_byte(char_idx)
+  }
+
+  pub fn slice_to_string(&self, range: Range<usize>) -> String {
+    self.text.slice(range).to_string()
+  }
+
+  /// Get line content without trailing newlines
+  pub fn line_content(&self, line_idx: usize) -> Option<Cow<'_, str>> {
+    line_content(&self.text, line_idx)
+  }
+
+  /// Bytes from `byte_idx` to the end of the rope chunk holding it, empty at
+  /// the end of the text
+  pub fn chunk_at_byte(&self, byte_idx: usize) -> &[u8] {
+    if byte_idx >= self.text.len_bytes() {
+      return &[];
+    }
+    let (chunk, chunk_start, _, _) = self.text.chunk_at_byte(byte_idx);
+    &chunk.as_bytes()[byte_idx - chunk_start..]
+  }
+
+  /// Rope chunks holding the bytes in `byte_range`, cut to it
+  pub fn byte_chunks(&self, byte_range: Range<usize>) -> impl Iterator<Item = &str> {
+    self.text.byte_slice(byte_range).chunks()
+  }
+
+  /// The whole text, for work that needs it in one piece
+  pub fn text(&self) -> String {
+    self.text.to_string()
+  }
+
+  /// Whether the text equals `text`, without copying it
+  pub fn text_eq(&self, text: &str) -> bool {
+    self.text == text
+  }
+}
@@ -2,11 +2,18 @@ use std::{
   borrow::Cow,
   collections::VecDeque,
   ops::Range,
+  path::{Path, PathBuf},
   time::{Duration, Instant},
 };
 
 use ropey::Rope;
 
+use crate::{
+  anchor::{Anchor, Bias, Edit},
+  journal::{self, Journal, JournalEntry},
+  snapshot::BufferSnapshot,
+};
+
 const DEFAULT_GROUP_INTERVAL_MS: u64 = 300;
 
 pub type TransactionId = usize;
@@ -33,21 +40,44 @@ pub struct TextOperation {
 }
 
 impl TextOperation {
+  fn journal_entry(&self) -> JournalEntry {
+    JournalEntry {
+      start: self.range.start,
+      removed: if self.before.is_empty() {
+        0
+      } else {
+        self.range.len()
+      },
+      inserted: self.after.clone(),
+    }
+  }
+
   pub fn undo(&self) -> Self {
     TextOperation {
-      range: self.range.start..(self.range.start + self.after.len()),
+      range: self.range.start..(self.range.start + self.after.chars().count()),
       before: self.after.clone(),
       after: self.before.clone(),
     }
   }
 }
 
+/// Selections around a transaction, restored by undo and redo, and what it
+/// did, as named in an Edit menu's undo and redo items
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransactionSelection {
+  pub before: Range<usize>,
+  pub after: Range<usize>,
+  pub description: &'static str,
+}
+
 /// A transaction groups one or more text operations
 #[derive(Clone, Debug)]
 struct Transaction {
   id: TransactionId,
   timestamp: Instant,
   operations: Vec<TextOperation>,
+  // Left unset by edits made without a selection, like save hooks
+  selection: Option<TransactionSelection>,
 }
 
 #[derive(Clone, Debug)]
@@ -57,6 +87,12 @@ pub struct TextBuffer {
   undo_stack: VecDeque<Transaction>,
   redo_stack: VecDeque<Transaction>,
   group_interval: Duration,
+  // Every edit applied to the rope, in order, so anchors can be moved forward
+  edits: Vec<Edit>,
+  // The text each of `edits` removed and inserted
+  operations: Vec<TextOperation>,
+  // Crash recovery journal, written as transactions are committed
+  journal: Option<Journal>,
 }
 
 impl Default for TextBuffer {
@@ -73,6 +109,9 @@ impl TextBuffer {
       undo_stack: VecDeque::new(),
       redo_stack: VecDeque::new(),
       group_interval: Duration::from_millis(DEFAULT_GROUP_INTERVAL_MS),
+      edits: Vec::new(),
+      operations: Vec::new(),
+      journal: None,
     }
   }
 
@@ -85,6 +124,9 @@ impl TextBuffer {
       undo_stack: VecDeque::new(),
       redo_stack: VecDeque::new(),
       group_interval: Duration::from_millis(DEFAULT_GROUP_INTERVAL_MS),
+      edits: Vec::new(),
+      operations: Vec::new(),
+      journal: None,
     }
   }
 
@@ -96,6 +138,15 @@ impl TextBuffer {
     self.text.len_chars() == 0
   }
 
+  pub fn len_bytes(&self) -> usize {
+    self.text.len_bytes()
+  }
+
+  pub fn ends_with_newline(&self) -> bool {
+    let len = self.text.len_chars();
+    len > 0 && self.text.char(len - 1) == '\n'
+  }
+
   pub fn chars(&self) -> impl Iterator<Item = char> {
     self.text.chars()
   }
@@ -108,39 +159,35 @@ impl TextBuffer {
     self.text.line_to_char(line_idx)
   }
 
+  pub fn byte_to_char(&self, byte_idx: usize) -> usize {
+    self.text.byte_to_char(byte_idx)
+  }
+
+  pub fn char_to_byte(&self, char_idx: usize) -> usize {
+    self.text.char_to_byte(char_idx)
+  }
+
   pub fn slice_to_string(&self, range: Range<usize>) -> String {
     self.text.slice(range).to_string()
   }
 
+  /// UTF-8 bytes in a byte range, clamped to the end of the text
+  pub fn bytes_in_range(&self, range: Range<usize>) -> Vec<u8> {
+    let start = range.start.min(self.text.len_bytes());
+    self
+      .text
+      .bytes_at(start)
+      .take(range.end.saturating_sub(start))
+      .collect()
+  }
+
   pub fn len_lines(&self) -> usize {
     self.text.len_lines()
   }
 
   /// Get line content without trailing newlines
   pub fn line_content(&self, line_idx: usize) -> Option<Cow<'_, str>> {
-    if line_idx < self.len_lines() {
-      let line_slice = self.text.line(line_idx);
-
-      // Try fast path: borrow if line is contiguous in memory and has no newlines
-      if let Some(line_str) = line_slice.as_str()
-        && !line_str.ends_with('\n')
-        && !line_str.ends_with('\r')
-      {
-        return Some(Cow::Borrowed(line_str));
-      }
-
-      // Slow path: line crosses chunk boundaries or has newlines, must allocate
-      let mut owned = line_slice.to_string();
-      if owned.ends_with('\n') {
-        owned.pop();
-        if owned.ends_with('\r') {
-          owned.pop();
-        }
-      }
-      Some(Cow::Owned(owned))
-    } else {
-      None
-    }
+    line_content(&self.text, line_idx)
   }
 
   pub fn line_range(&self, line_idx: usize) -> Option<Range<usize>> {
@@ -159,26 +206,57 @@ impl TextBuffer {
 
   /// Insert text with transaction context
   pub fn insert(&mut self, tx: &mut TransactionContext, offset: usize, text: &str) {
-    tx.operations.push(TextOperation {
+    let operation = TextOperation {
       range: offset..offset,
       before: String::new(),
       after: text.to_string(),
-    });
+    };
+    tx.operations.push(operation.clone());
 
     self.text.insert(offset, text);
+    self.record_edit(operation);
   }
 
   /// Remove text with transaction context
   pub fn remove(&mut self, tx: &mut TransactionContext, range: Range<usize>) {
-    let before = self.slice_to_string(range.clone());
-
-    tx.operations.push(TextOperation {
+    let operation = TextOperation {
       range: range.clone(),
-      before,
+      before: self.slice_to_string(range.clone()),
       after: String::new(),
-    });
+    };
+    tx.operations.push(operation.clone());
 
     self.text.remove(range);
+    self.record_edit(operation);
+  }
+
+  /// Append text at the end outside of any transaction, so it can't be undone
+  ///
+  /// Undo history stays valid because every recorded operation lies before the end.
+  pub fn append(&mut self, text: &str) {
+    let len = self.text.len_chars();
+    self.text.insert(len, text);
+    self.record_edit(TextOperation {
+      range: len..len,
+      before: String::new(),
+      after: text.to_string(),
+    });
+    self.sync_journal();
+  }
+
+  /// Replace the whole text outside of any transaction
+  ///
+  /// Undo history no longer applies to the new text, so it is dropped.
+  pub fn reset(&mut self, text: &str) {
+    let before = std::mem::replace(&mut self.text, Rope::from_str(text));
+    self.record_edit(TextOperation {
+      range: 0..before.len_chars(),
+      before: before.to_string(),
+      after: text.to_string(),
+    });
+    self.undo_stack.clear();
+    self.redo_stack.clear();
+    self.sync_journal();
   }
 
   /// Replace text with transaction context
@@ -205,6 +283,8 @@ impl TextBuffer {
   }
 
   fn commit_transaction(&mut self, operations: Vec<TextOperation>, now: Instant) -> TransactionId {
+    self.sync_journal();
+
     let transaction_id = self.next_transaction_id;
     self.next_transaction_id += 1;
 
@@ -224,24 +304,188 @@ impl TextBuffer {
       id: transaction_id,
       timestamp: now,
       operations,
+      selection: None,
     });
     self.redo_stack.clear();
     transaction_id
   }
 
   fn exec_operation(&mut self, operation: &TextOperation) {
+    let start = operation.range.start;
     if operation.before.is_empty() && !operation.after.is_empty() {
       // Insert
-      self.text.insert(operation.range.start, &operation.after);
+      self.text.insert(start, &operation.after);
     } else if !operation.before.is_empty() && operation.after.is_empty() {
       // Delete
       self.text.remove(operation.range.clone());
     } else if !operation.before.is_empty() && !operation.after.is_empty() {
       // Replace
       self.text.remove(operation.range.clone());
-      self.text.insert(operation.range.start, &operation.after);
+      self.text.insert(start, &operation.after);
+    } else {
+      // If both empty, do nothing
+      return;
+    }
+    self.record_edit(operation.clone());
+  }
+
+  fn record_edit(&mut self, operation: TextOperation) {
+    self.edits.push(Edit {
+      start: operation.range.start,
+      old_len: operation.range.len(),
+      new_len: operation.after.chars().count(),
+    });
+    self.operations.push(operation);
+  }
+
+  /// Number of edits applied so far, bumped by every insert, remove, undo and redo
+  pub fn version(&self) -> usize {
+    self.edits.len()
+  }
+
+  /// Immutable copy of the current text, cheap to take and to send to
+  /// another thread
+  pub fn snapshot(&self) -> BufferSnapshot {
+    BufferSnapshot {
+      text: self.text.clone(),
+      version: self.version(),
+    }
+  }
+
+  /// Edits applied since the text was at `version`, oldest first
+  pub fn edits_since(&self, version: usize) -> &[Edit] {
+    self.edits.get(version..).unwrap_or_default()
+  }
+
+  /// Operations taking the text from `version` to now, oldest first
+  ///
+  /// Every change counts, made in a transaction or not, undone or redone, so
+  /// edits grouped into a transaction older than `version` are still in it.
+  pub fn diff_since(&self, version: usize) -> &[TextOperation] {
+    self.operations.get(version..).unwrap_or_default()
+  }
+
+  /// Create an anchor that stays before text inserted at `offset`
+  pub fn anchor_before(&self, offset: usize) -> Anchor {
+    self.anchor_at(offset, Bias::Left)
+  }
+
+  /// Create an anchor that moves after text inserted at `offset`
+  pub fn anchor_after(&self, offset: usize) -> Anchor {
+    self.anchor_at(offset, Bias::Right)
+  }
+
+  pub fn anchor_at(&self, offset: usize, bias: Bias) -> Anchor {
+    Anchor {
+      offset: offset.min(self.len()),
+      version: self.version(),
+      bias,
+    }
+  }
+
+  /// Resolve an anchor to its current char offset
+  pub fn resolve_anchor(&self, anchor: &Anchor) -> usize {
+    let edits = self.edits.get(anchor.version..).unwrap_or_default();
+    let offset = edits.iter().fold(anchor.offset, |offset, edit| {
+      edit.transform(offset, anchor.bias)
+    });
+    offset.min(self.len())
+  }
+
+  /// The same position as `anchor`, created at the current version
+  ///
+  /// Long-lived anchors are rebased so resolving them only walks the edits made
+  /// since they were last rebased, instead of every edit since their creation.
+  pub fn rebase_anchor(&self, anchor: &Anchor) -> Anchor {
+    self.anchor_at(self.resolve_anchor(anchor), anchor.bias)
+  }
+
+  /// Create an anchor range that doesn't grow when text is inserted at either end
+  pub fn anchor_range(&self, range: Range<usize>) -> Range<Anchor> {
+    self.anchor_after(range.start)..self.anchor_before(range.end)
+  }
+
+  pub fn resolve_anchor_range(&self, range: &Range<Anchor>) -> Range<usize> {
+    let start = self.resolve_anchor(&range.start);
+    let end = self.resolve_anchor(&range.end);
+    start..end.max(start)
+  }
+
+  /// Start or stop appending committed edits to a journal file
+  pub fn set_journal(&mut self, path: Option<PathBuf>) {
+    match path {
+      Some(path) => self.start_journal(path, self.version()),
+      None => self.journal = None,
+    }
+  }
+
+  /// Journal the edits made since `version` to `path`, those made already first
+  pub fn start_journal(&mut self, path: PathBuf, version: usize) {
+    self.journal = Some(Journal::new(path, version));
+    self.sync_journal();
+  }
+
+  /// File edits are journaled to
+  pub fn journal(&self) -> Option<&Path> {
+    self.journal.as_ref().map(Journal::path)
+  }
+
+  /// Drop the journal entries of edits up to `version` once the text at that
+  /// version has been saved, keeping those of edits made since
+  pub fn truncate_journal(&mut self, version: usize) -> Result<(), String> {
+    match &mut self.journal {
+      Some(journal) => journal.truncate_to(version),
+      None => Ok(()),
+    }
+  }
+
+  /// Apply the edits of a journal over the text, returning how many were applied
+  ///
+  /// Replayed edits are not journaled again and can't be undone. A journal
+  /// that doesn't fit the text is not applied at all.
+  pub fn replay_journal(&mut self, path: &Path) -> Result<usize, String> {
+    let entries = journal::read_entries(path)?;
+    let mut len = self.text.len_chars();
+    for (idx, entry) in entries.iter().enumerate() {
+      if entry.start + entry.removed > len {
+        return Err(format!("journal entry {} is out of bounds", idx + 1));
+      }
+      len = len - entry.removed + entry.inserted.chars().count();
+    }
+    for entry in &entries {
+      let end = entry.start + entry.removed;
+      let before = self.slice_to_string(entry.start..end);
+      self.text.remove(entry.start..end);
+      self.text.insert(entry.start, &entry.inserted);
+      self.record_edit(TextOperation {
+        range: entry.start..end,
+        before,
+        after: entry.inserted.clone(),
+      });
+    }
+    let version = self.version();
+    if let Some(journal) = &mut self.journal {
+      journal.skip_to(version);
+    }
+    Ok(entries.len())
+  }
+
+  /// Append the edits made since the journal was last written to it
+  fn sync_journal(&mut self) {
+    let Some(journal) = &self.journal else {
+      return;
+    };
+    let entries: Vec<JournalEntry> = self
+      .diff_since(journal.version())
+      .iter()
+      .map(TextOperation::journal_entry)
+      .collect();
+    let version = self.version();
+    if let Some(journal) = &mut self.journal
+      && let Err(err) = journal.append(version, &entries)
+    {
+      eprintln!("Failed to write buffer journal: {}", err);
     }
-    // If both empty, do nothing
   }
 
   pub fn undo(&mut self) -> Option<TransactionId> {
@@ -251,6 +495,7 @@ impl TextBuffer {
     for operation in tx.operations.iter().rev() {
       self.exec_operation(&operation.undo());
     }
+    self.sync_journal();
 
     let id = tx.id;
     self.redo_stack.push_back(tx);
@@ -264,12 +509,64 @@ impl TextBuffer {
     for operation in &tx.operations {
       self.exec_operation(operation);
     }
+    self.sync_journal();
 
     let id = tx.id;
     self.undo_stack.push_back(tx);
     Some(id)
   }
 
+  /// Record the selections around transaction `id` and what it did; a
+  /// transaction that already has them, because later edits were grouped
+  /// into it, only takes the new selection after
+  pub fn set_selection(
+    &mut self,
+    id: TransactionId,
+    before: Range<usize>,
+    after: Range<usize>,
+    description: &'static str,
+  ) {
+    let Some(tx) = self.undo_stack.iter_mut().rev().find(|tx| tx.id == id) else {
+      return;
+    };
+    match &mut tx.selection {
+      Some(selection) => selection.after = after,
+      None => {
+        tx.selection = Some(TransactionSelection {
+          before,
+          after,
+          description,
+        })
+      }
+    }
+  }
+
+  /// Selections around transaction `id`, if it is still in the history
+  pub fn selection(&self, id: TransactionId) -> Option<&TransactionSelection> {
+    self
+      .undo_stack
+      .iter()
+      .chain(&self.redo_stack)
+      .find(|tx| tx.id == id)?
+      .selection
+      .as_ref()
+  }
+
+  /// Selections around the transaction undo would revert
+  pub fn undo_selection(&self) -> Option<&TransactionSelection> {
+    self.undo_stack.back()?.selection.as_ref()
+  }
+
+  /// Selections around the transaction redo would reapply
+  pub fn redo_selection(&self) -> Option<&TransactionSelection> {
+    self.redo_stack.back()?.selection.as_ref()
+  }
+
+  /// Number of transactions undo can revert
+  pub fn undo_depth(&self) -> usize {
+    self.undo_stack.len()
+  }
+
   pub fn can_undo(&self) -> bool {
     !self.undo_stack.is_empty()
   }
@@ -281,6 +578,66 @@ impl TextBuffer {
   pub fn set_group_interval(&mut self, interval: Duration) {
     self.group_interval = interval;
   }
+
+  /// Fold the transactions committed after `id` into it, so one undo
+  /// reverts them all, whatever the time between them
+  pub fn group_since(&mut self, id: TransactionId) {
+    let Some(ix) = self.undo_stack.iter().position(|tx| tx.id == id) else {
+      return;
+    };
+    let later: Vec<_> = self.undo_stack.drain(ix + 1..).collect();
+    let tx = &mut self.undo_stack[ix];
+    for later in later {
+      tx.operations.extend(later.operations);
+      tx.timestamp = later.timestamp;
+      match (&mut tx.selection, later.selection) {
+        (Some(selection), Some(later)) => selection.after = later.after,
+        (selection @ None, later) => *selection = later,
+        (Some(_), None) => {}
+      }
+    }
+  }
+
+  /// Fold the transactions committed past the first `depth` of the undo
+  /// history into one step, which restores `before` when undone and `after`
+  /// when redone
+  pub fn group_past(&mut self, depth: usize, before: Range<usize>, after: Range<usize>) {
+    let Some(id) = self.undo_stack.get(depth).map(|tx| tx.id) else {
+      return;
+    };
+    self.group_since(id);
+    if let Some(selection) = &mut self.undo_stack[depth].selection {
+      selection.before = before;
+      selection.after = after;
+    }
+  }
+}
+
+/// Content of a line of `text` without its trailing newline
+pub(crate) fn line_content(text: &Rope, line_idx: usize) -> Option<Cow<'_, str>> {
+  if line_idx < text.len_lines() {
+    let line_slice = text.line(line_idx);
+
+    // Try fast path: borrow if line is contiguous in memory and has no newlines
+    if let Some(line_str) = line_slice.as_str()
+      && !line_str.ends_with('\n')
+      && !line_str.ends_with('\r')
+    {
+      return Some(Cow::Borrowed(line_str));
+    }
+
+    // Slow path: line crosses chunk boundaries or has newlines, must allocate
+    let mut owned = line_slice.to_string();
+    if owned.ends_with('\n') {
+      owned.pop();
+      if owned.ends_with('\r') {
+        owned.pop();
+      }
+    }
+    Some(Cow::Owned(owned))
+  } else {
+    None
+  }
 }
 
 #[cfg(test)]
@@ -295,6 +652,135 @@ mod tests {
     assert_eq!(buffer.len_lines(), 1);
   }
 
+  #[test]
+  fn test_ends_with_newline() {
+    assert!(!TextBuffer::new().ends_with_newline());
+    assert!(!TextBuffer::from_text("a\nb").ends_with_newline());
+    assert!(TextBuffer::from_text("a\nb\n").ends_with_newline());
+  }
+
+  #[test]
+  fn test_bytes_in_range() {
+    let buffer = TextBuffer::from_text("aé\nb");
+
+    assert_eq!(buffer.len_bytes(), 5);
+    assert_eq!(buffer.char_to_byte(2), 3);
+    assert_eq!(buffer.bytes_in_range(1..3), vec![0xc3, 0xa9]);
+    assert_eq!(buffer.bytes_in_range(3..16), b"\nb".to_vec());
+    assert!(buffer.bytes_in_range(8..16).is_empty());
+  }
+
+  #[test]
+  fn test_snapshot_is_unchanged_by_edits() {
+    let mut buffer = TextBuffer::from_text("one\ntwo");
+    let snapshot = buffer.snapshot();
+    buffer.transaction(Instant::now(), |buf, tx| {
+      buf.replace(tx, 0..3, "three");
+    });
+
+    assert_eq!(snapshot.version(), 0);
+    assert_ne!(buffer.version(), snapshot.version());
+    assert!(snapshot.text_eq("one\ntwo"));
+    assert_eq!(snapshot.line_content(1).as_deref(), Some("two"));
+    assert_eq!(buffer.snapshot().text(), "three\ntwo");
+  }
+
+  #[test]
+  fn test_snapshot_chunks() {
+    let text = "é".repeat(3000);
+    let snapshot = TextBuffer::from_text(&text).snapshot();
+    let mut read = Vec::new();
+    while read.len() < snapshot.len_bytes() {
+      read.extend_from_slice(snapshot.chunk_at_byte(read.len()));
+    }
+    assert_eq!(read, text.as_bytes());
+    assert!(snapshot.chunk_at_byte(text.len()).is_empty());
+    assert_eq!(snapshot.byte_chunks(2..6).collect::<String>(), "éé");
+  }
+
+  #[test]
+  fn test_append_is_not_undoable() {
+    let mut buffer = TextBuffer::from_text("a");
+    buffer.transaction(Instant::now(), |buf, tx| {
+      buf.insert(tx, 0, "b");
+    });
+    let anchor = buffer.anchor_before(2);
+
+    buffer.append("\nlog");
+    assert_eq!(buffer.slice_to_string(0..buffer.len()), "ba\nlog");
+    assert_eq!(buffer.resolve_anchor(&anchor), 2);
+
+    buffer.undo();
+    assert_eq!(buffer.slice_to_string(0..buffer.len()), "a\nlog");
+  }
+
+  #[test]
+  fn test_reset_drops_history() {
+    let mut buffer = TextBuffer::from_text("a");
+    buffer.transaction(Instant::now(), |buf, tx| {
+      buf.insert(tx, 1, "b");
+    });
+    let anchor = buffer.anchor_after(2);
+
+    buffer.reset("new\n");
+    assert_eq!(buffer.slice_to_string(0..buffer.len()), "new\n");
+    assert_eq!(buffer.resolve_anchor(&anchor), 4);
+    assert_eq!(buffer.undo(), None);
+  }
+
+  #[test]
+  fn test_journal_replay() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("journal");
+    let saved = "fn main() {}\n";
+
+    let mut buffer = TextBuffer::from_text(saved);
+    buffer.set_journal(Some(path.clone()));
+    buffer.truncate_journal(buffer.version()).unwrap();
+    buffer.set_group_interval(Duration::ZERO);
+    buffer.transaction(Instant::now(), |buf, tx| {
+      buf.replace(tx, 3..7, "start");
+    });
+    buffer.transaction(Instant::now(), |buf, tx| {
+      buf.insert(tx, 0, "// é\n");
+    });
+    buffer.undo();
+    buffer.append("// log\n");
+    let expected = "fn start() {}\n// log\n";
+    assert_eq!(buffer.slice_to_string(0..buffer.len()), expected);
+
+    let mut recovered = TextBuffer::from_text(saved);
+    assert_eq!(recovered.replay_journal(&path).unwrap(), 5);
+    assert_eq!(recovered.slice_to_string(0..recovered.len()), expected);
+
+    // Over a file changed since, nothing is applied
+    let mut changed = TextBuffer::from_text("fn a");
+    assert!(changed.replay_journal(&path).is_err());
+    assert_eq!(changed.slice_to_string(0..changed.len()), "fn a");
+    assert_eq!(changed.version(), 0);
+
+    buffer.truncate_journal(buffer.version()).unwrap();
+    let mut recovered = TextBuffer::from_text(saved);
+    assert_eq!(recovered.replay_journal(&path).unwrap(), 0);
+  }
+
+  #[test]
+  fn test_start_journal_at_earlier_version() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("journal");
+    let mut buffer = TextBuffer::from_text("a");
+    let saved = buffer.version();
+    buffer.append("b");
+
+    buffer.start_journal(path.clone(), saved);
+    buffer.append("c");
+    assert_eq!(buffer.journal(), Some(path.as_path()));
+
+    let mut recovered = TextBuffer::from_text("a");
+    assert_eq!(recovered.replay_journal(&path).unwrap(), 2);
+    assert_eq!(recovered.slice_to_string(0..recovered.len()), "abc");
+  }
+
   #[test]
   fn test_transaction_insert() {
     let mut buffer = TextBuffer::new();
@@ -399,6 +885,65 @@ mod tests {
     assert!(!buffer.can_undo());
   }
 
+  #[test]
+  fn test_group_since() {
+    let mut buffer = TextBuffer::new();
+    buffer.set_group_interval(Duration::from_millis(0));
+
+    buffer.transaction(Instant::now(), |buf, tx| {
+      buf.insert(tx, 0, "a");
+    });
+    let id = buffer.transaction(Instant::now(), |buf, tx| {
+      buf.insert(tx, 1, "b");
+    });
+    buffer.transaction(Instant::now(), |buf, tx| {
+      buf.insert(tx, 2, "c");
+    });
+    buffer.group_since(id);
+
+    assert!(buffer.undo().is_some());
+    assert_eq!(buffer.slice_to_string(0..buffer.len()), "a");
+    assert!(buffer.redo().is_some());
+    assert_eq!(buffer.slice_to_string(0..buffer.len()), "abc");
+  }
+
+  #[test]
+  fn test_transaction_selection() {
+    let mut buffer = TextBuffer::new();
+    buffer.set_group_interval(Duration::from_secs(60));
+    let now = Instant::now();
+
+    let first = buffer.transaction(now, |buf, tx| buf.insert(tx, 0, "a"));
+    buffer.set_selection(first, 0..0, 1..1, "Typing");
+    // Grouped into the first, which keeps its selection before
+    let grouped = buffer.transaction(now, |buf, tx| buf.insert(tx, 1, "b"));
+    assert_eq!(grouped, first);
+    buffer.set_selection(grouped, 1..1, 2..2, "Paste");
+    let expected = TransactionSelection {
+      before: 0..0,
+      after: 2..2,
+      description: "Typing",
+    };
+    assert_eq!(buffer.undo_selection(), Some(&expected));
+
+    buffer.set_group_interval(Duration::ZERO);
+    let second = buffer.transaction(now, |buf, tx| buf.insert(tx, 2, "c"));
+    buffer.set_selection(second, 2..2, 3..3, "Typing");
+    buffer.group_past(0, 0..1, 3..3);
+    assert_eq!(buffer.undo_depth(), 1);
+    assert_eq!(buffer.undo(), Some(first));
+    assert_eq!(
+      buffer.selection(first),
+      Some(&TransactionSelection {
+        before: 0..1,
+        after: 3..3,
+        description: "Typing",
+      })
+    );
+    assert_eq!(buffer.redo_selection(), buffer.selection(first));
+    assert_eq!(buffer.undo_selection(), None);
+  }
+
   #[test]
   fn test_replace_undo() {
     let mut buffer = TextBuffer::new();
@@ -559,6 +1104,136 @@ mod tests {
     assert_eq!(buffer.slice_to_string(6..8), "世界");
   }
 
+  #[test]
+  fn test_anchor_follows_insert_before() {
+    let mut buffer = TextBuffer::from_text("hello world");
+    let anchor = buffer.anchor_before(6);
+
+    buffer.transaction(Instant::now(), |buf, tx| {
+      buf.insert(tx, 0, ">> ");
+    });
+
+    assert_eq!(buffer.resolve_anchor(&anchor), 9);
+  }
+
+  #[test]
+  fn test_anchor_bias_on_insert_at_position() {
+    let mut buffer = TextBuffer::from_text("hello world");
+    let before = buffer.anchor_before(5);
+    let after = buffer.anchor_after(5);
+
+    buffer.transaction(Instant::now(), |buf, tx| {
+      buf.insert(tx, 5, ",");
+    });
+
+    assert_eq!(buffer.resolve_anchor(&before), 5);
+    assert_eq!(buffer.resolve_anchor(&after), 6);
+  }
+
+  #[test]
+  fn test_anchor_range_does_not_grow() {
+    let mut buffer = TextBuffer::from_text("abc def ghi");
+    let range = buffer.anchor_range(4..7);
+
+    buffer.transaction(Instant::now(), |buf, tx| {
+      buf.insert(tx, 7, "!");
+      buf.insert(tx, 4, "_");
+    });
+
+    assert_eq!(buffer.resolve_anchor_range(&range), 5..8);
+    assert_eq!(buffer.slice_to_string(5..8), "def");
+  }
+
+  #[test]
+  fn test_anchor_collapses_on_delete() {
+    let mut buffer = TextBuffer::from_text("abc def ghi");
+    let range = buffer.anchor_range(4..7);
+
+    buffer.transaction(Instant::now(), |buf, tx| {
+      buf.remove(tx, 2..9);
+    });
+
+    assert_eq!(buffer.resolve_anchor_range(&range), 2..2);
+  }
+
+  #[test]
+  fn test_anchor_follows_undo_redo() {
+    let mut buffer = TextBuffer::from_text("world");
+    buffer.set_group_interval(Duration::from_millis(0));
+    let anchor = buffer.anchor_before(0);
+
+    buffer.transaction(Instant::now(), |buf, tx| {
+      buf.insert(tx, 0, "hello ");
+    });
+    let after_insert = buffer.anchor_before(6);
+    assert_eq!(buffer.resolve_anchor(&anchor), 0);
+
+    buffer.undo();
+    assert_eq!(buffer.resolve_anchor(&after_insert), 0);
+
+    buffer.redo();
+    assert_eq!(buffer.resolve_anchor(&after_insert), 0);
+    assert_eq!(buffer.version(), 3);
+  }
+
+  #[test]
+  fn test_rebase_anchor() {
+    let mut buffer = TextBuffer::from_text("hello world");
+    let anchor = buffer.anchor_after(5);
+
+    buffer.transaction(Instant::now(), |buf, tx| {
+      buf.insert(tx, 0, ">> ");
+    });
+    let rebased = buffer.rebase_anchor(&anchor);
+    assert_eq!(rebased.version, buffer.version());
+    assert_eq!(rebased.bias(), Bias::Right);
+
+    buffer.transaction(Instant::now(), |buf, tx| {
+      buf.insert(tx, 8, ",");
+    });
+    assert_eq!(buffer.resolve_anchor(&rebased), 9);
+    assert_eq!(buffer.resolve_anchor(&anchor), 9);
+  }
+
+  #[test]
+  fn test_diff_since() {
+    let mut buffer = TextBuffer::from_text("ab");
+    let inserted =
+      |ops: &[TextOperation]| -> Vec<String> { ops.iter().map(|op| op.after.clone()).collect() };
+
+    // Edits grouped into one transaction still split at a version
+    buffer.transaction(Instant::now(), |buf, tx| buf.insert(tx, 2, "c"));
+    let version = buffer.version();
+    buffer.transaction(Instant::now(), |buf, tx| buf.insert(tx, 3, "d"));
+    buffer.transaction(Instant::now(), |buf, tx| buf.replace(tx, 0..1, "A"));
+    assert_eq!(buffer.undo_depth(), 1);
+    assert_eq!(inserted(buffer.diff_since(version)), ["d", "", "A"]);
+    assert_eq!(buffer.diff_since(version)[1].before, "a");
+    assert!(buffer.diff_since(buffer.version()).is_empty());
+    assert!(buffer.diff_since(99).is_empty());
+
+    // Undo, redo and appends are changes like any other
+    let version = buffer.version();
+    buffer.undo();
+    buffer.append("!");
+    let ops = buffer.diff_since(version);
+    assert_eq!(
+      ops.iter().map(|op| op.before.as_str()).collect::<Vec<_>>(),
+      ["A", "", "d", "c", ""]
+    );
+    assert_eq!(inserted(ops), ["", "a", "", "", "!"]);
+
+    // Replaying the operations over the old text gives the new one
+    let mut replayed = TextBuffer::from_text("ab");
+    for op in buffer.diff_since(0) {
+      replayed.exec_operation(op);
+    }
+    assert_eq!(
+      replayed.slice_to_string(0..replayed.len()),
+      buffer.slice_to_string(0..buffer.len())
+    );
+  }
+
   #[test]
   fn test_invert_operation() {
     let op = TextOperation {
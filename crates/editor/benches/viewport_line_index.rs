@@ -0,0 +1,53 @@
+//! Offset and line lookups in the viewport, through the rope and through the
+//! viewport line index
+//!
+//! Run with `cargo bench -p editor --bench viewport_line_index`.
+
+use std::{hint::black_box, ops::Range, time::Instant};
+
+use editor::{Document, ViewportLineIndex};
+use gpui::{AppContext, TestAppContext};
+
+const ROUNDS: usize = 2_000;
+
+fn main() {
+  let mut cx = TestAppContext::single();
+  let text = "let value = some_function(argument);\n".repeat(200_000);
+  let doc = cx.new(|cx| Document::new(&text, None, cx));
+  let lines: Range<usize> = 100_000..100_060;
+
+  doc.read_with(&cx, |doc, _| {
+    let offsets: Vec<usize> = lines
+      .clone()
+      .map(|line| doc.line_to_char(line) + 7)
+      .collect();
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+      for (line, &offset) in lines.clone().zip(&offsets) {
+        black_box(doc.char_to_line(black_box(offset)) + doc.line_to_char(black_box(line)));
+      }
+    }
+    let rope = start.elapsed();
+
+    let start = Instant::now();
+    let index = ViewportLineIndex::new(doc, lines.clone());
+    for _ in 0..ROUNDS {
+      for (line, &offset) in lines.clone().zip(&offsets) {
+        black_box(
+          index.char_to_line(doc, black_box(offset)) + index.line_to_char(doc, black_box(line)),
+        );
+      }
+    }
+    let indexed = start.elapsed();
+
+    let lookups = ROUNDS * lines.len() * 2;
+    println!(
+      "{} lookups: rope {:?}, viewport index {:?} ({:.1}x)",
+      lookups,
+      rope,
+      indexed,
+      rope.as_secs_f64() / indexed.as_secs_f64()
+    );
+  });
+}
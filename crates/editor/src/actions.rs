@@ -3,22 +3,44 @@
 //! This module contains all the action handlers for the editor,
 //! including text editing, cursor movement, and selection operations.
 
-use std::time::Instant;
-
-use gpui::{ClipboardItem, Context, EntityInputHandler, Window, actions};
-
-use crate::{boundaries, editor::Editor};
+use std::{ops::Range, time::Instant};
+
+use gpui::{
+  App, ClipboardEntry, ClipboardItem, Context, EntityInputHandler, PromptLevel, Window, actions,
+};
+
+use crate::{
+  alternate, appearance, boundaries, code_cells, command_palette, comment, conflicts, dap,
+  describe,
+  diff::{self, DiffAlgorithm},
+  document::Document,
+  editor::Editor,
+  escape, files, find, focus_mode, folds, generators, indent,
+  invisibles::{UnicodeWarning, escape_char, unicode_warnings_in_line},
+  json, jump,
+  markdown::{self, ListEnter, SmartPaste},
+  motion::{self, Motion},
+  multi_cursor, paste_format, path_completion, permalink, preview, reflow, ruler, scratch,
+  selection_lock, shortcuts,
+  transform::{self, Transform},
+  trust, unicode_input, word_diff, workspace,
+};
 
 actions!(
   editor,
   [
     Enter,
+    Indent,
+    Outdent,
+    ToggleComment,
     Backspace,
     BackspaceWord,
     BackspaceAll,
     Delete,
     Up,
     Down,
+    PageUp,
+    PageDown,
     Left,
     AltLeft,
     CmdLeft,
@@ -29,6 +51,8 @@ actions!(
     CmdDown,
     SelectUp,
     SelectDown,
+    SelectPageUp,
+    SelectPageDown,
     SelectLeft,
     SelectRight,
     SelectCmdLeft,
@@ -38,39 +62,172 @@ actions!(
     SelectWordLeft,
     SelectWordRight,
     SelectAll,
+    SelectNextOccurrence,
     Home,
     End,
+    SelectHome,
+    SelectEnd,
     ShowCharacterPalette,
     Paste,
     Cut,
     Copy,
+    CopyAppend,
+    CutAppend,
     Undo,
     Redo,
+    ReflowParagraph,
+    DuplicateLine,
+    DeleteLine,
+    MoveLineUp,
+    MoveLineDown,
+    ToggleCoverageOverlay,
+    ToggleHexMode,
+    ToggleSoftWrap,
+    ToggleElasticTabstops,
+    EscapeSuspiciousUnicode,
+    RemoveSuspiciousUnicode,
+    StartPerformanceTrace,
+    StopPerformanceTrace,
+    SwitchToAlternateFile,
+    TrustWorkspace,
+    DescribeCharacter,
+    InsertUnicode,
+    ZoomIn,
+    ZoomOut,
+    ZoomToFit,
+    StartDebugging,
+    StopDebugging,
+    StepOver,
+    StepInto,
+    StepOut,
+    ToggleCodeCells,
+    RunCell,
+    StopCell,
+    ClearCellOutputs,
+    JumpToWord,
+    ToggleDarkMode,
+    UseSystemTheme,
+    ToggleFocusMode,
+    ToggleWordDiff,
+    AddFolderToWorkspace,
+    RemoveFolder,
+    NextConflict,
+    PreviousConflict,
+    CopyWithLineNumbers,
+    CopyPermalink,
+    ToggleFormatOnPaste,
+    FocusNextPanel,
+    FocusEditor,
+    FocusFileTree,
+    FocusTerminal,
+    RetryHighlighting,
+    ShowKeyboardShortcuts,
+    ShowCommandPalette,
+    FoldBracketRegion,
+    ToggleSelectionLock,
+    SwapSelectionEnds,
+    NewScratchBuffer,
+    Cancel,
+    FormatJson,
+    MinifyJson,
+    Base64Encode,
+    Base64Decode,
+    UrlEncode,
+    UrlDecode,
+    JsonEscape,
+    JsonUnescape,
+    InsertUuid,
+    InsertTimestamp,
+    InsertLoremIpsum,
+    Open,
+    Save,
+    SaveAs,
+    Find,
+    Replace,
+    FindNext,
+    FindPrevious,
     Quit,
   ]
 );
 
 pub fn enter(editor: &mut Editor, _: &Enter, window: &mut Window, cx: &mut Context<Editor>) {
-  editor.target_column = None;
-  let cursor = editor.cursor_offset();
-  let current_line = editor.document.read(cx).char_to_line(cursor);
-  let selection_before = editor.selected_range.clone();
-
-  let transaction_id = editor.document.update(cx, |doc, cx| {
-    let id = doc.buffer.transaction(Instant::now(), |buffer, tx| {
-      buffer.insert(tx, cursor, "\n");
+  if path_completion::accept(editor, cx) {
+    return;
+  }
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    let cursor = editor.cursor_offset();
+    let current_line = editor.document.read(cx).char_to_line(cursor);
+    let selection_before = editor.selected_range.clone();
+    let (range, text) = enter_edit(editor, cursor, cx);
+
+    let transaction_id = editor.document.update(cx, |doc, cx| {
+      let id = doc.buffer.transaction(Instant::now(), |buffer, tx| {
+        buffer.replace(tx, range.clone(), &text);
+      });
+      doc.schedule_recompute_highlights(cx);
+      cx.notify();
+      id
     });
-    cx.notify();
-    id
+
+    editor.move_to(range.start + text.chars().count(), cx);
+    let selection_after = editor.selected_range.clone();
+
+    editor.record_transaction(
+      transaction_id,
+      selection_before,
+      selection_after,
+      "Typing",
+      cx,
+    );
+
+    editor.invalidate_lines_from(current_line);
   });
 
-  editor.move_to(cursor + 1, cx);
-  let selection_after = editor.selected_range.clone();
+  editor.ensure_cursor_visible(window, cx);
+}
 
-  editor.record_transaction(transaction_id, selection_before, selection_after);
+/// Range replaced and text inserted by Enter at `cursor`, depending on the language
+fn enter_edit(editor: &Editor, cursor: usize, cx: &App) -> (Range<usize>, String) {
+  let document = editor.document.read(cx);
+  let line = document.char_to_line(cursor);
+  let line_start = document.line_to_char(line);
+  let content = document.line_content(line).unwrap_or_default();
+  let at_line_end = cursor == line_start + content.chars().count();
+
+  if at_line_end && markdown::is_markdown(document.file_extension()) {
+    match markdown::list_enter(&content) {
+      Some(ListEnter::Continue(prefix)) => return (cursor..cursor, format!("\n{}", prefix)),
+      Some(ListEnter::EndList) => return (line_start..cursor, String::new()),
+      None => {}
+    }
+  }
+  (cursor..cursor, "\n".to_string())
+}
 
-  editor.invalidate_lines_from(current_line);
+/// Indent at the cursor, or the selected lines
+pub fn indent(editor: &mut Editor, _: &Indent, window: &mut Window, cx: &mut Context<Editor>) {
+  if path_completion::accept(editor, cx) {
+    return;
+  }
+  multi_cursor::for_each(editor, cx, indent::indent);
+  editor.ensure_cursor_visible(window, cx);
+}
+
+/// Take a level of indentation off the lines under the selection
+pub fn outdent(editor: &mut Editor, _: &Outdent, window: &mut Window, cx: &mut Context<Editor>) {
+  multi_cursor::for_each(editor, cx, indent::outdent);
+  editor.ensure_cursor_visible(window, cx);
+}
 
+/// Comment out the lines under the selection, or uncomment them
+pub fn toggle_comment(
+  editor: &mut Editor,
+  _: &ToggleComment,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  multi_cursor::for_each(editor, cx, comment::toggle_comment);
   editor.ensure_cursor_visible(window, cx);
 }
 
@@ -80,14 +237,17 @@ pub fn backspace(
   window: &mut Window,
   cx: &mut Context<Editor>,
 ) {
-  editor.target_column = None;
-  if editor.selected_range.is_empty() {
-    editor.select_to(
-      boundaries::previous_boundary(editor, editor.cursor_offset(), cx),
-      cx,
-    )
-  }
-  editor.replace_text_in_range(None, "", window, cx)
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    if editor.selected_range.is_empty() {
+      editor.select_to(
+        boundaries::previous_boundary(editor, editor.cursor_offset(), cx),
+        cx,
+      )
+    }
+    editor.describe_next_edit("Delete");
+    editor.replace_text_in_range(None, "", window, cx)
+  });
 }
 
 pub fn backspace_word(
@@ -96,21 +256,24 @@ pub fn backspace_word(
   window: &mut Window,
   cx: &mut Context<Editor>,
 ) {
-  editor.target_column = None;
-  if editor.selected_range.is_empty() {
-    let document = editor.document.read(cx);
-    let cursor = editor.cursor_offset();
-    let line = document.char_to_line(cursor);
-    let line_start = document.line_to_char(line);
-
-    // If we're at the beginning of an empty line, behave like simple backspace
-    if cursor == line_start && document.line_content(line).unwrap_or_default().is_empty() {
-      editor.select_to(boundaries::previous_boundary(editor, cursor, cx), cx);
-    } else {
-      editor.select_to(boundaries::previous_word_boundary(editor, cursor, cx), cx);
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    if editor.selected_range.is_empty() {
+      let document = editor.document.read(cx);
+      let cursor = editor.cursor_offset();
+      let line = document.char_to_line(cursor);
+      let line_start = document.line_to_char(line);
+
+      // If we're at the beginning of an empty line, behave like simple backspace
+      if cursor == line_start && document.line_content(line).unwrap_or_default().is_empty() {
+        editor.select_to(boundaries::previous_boundary(editor, cursor, cx), cx);
+      } else {
+        editor.select_to(boundaries::previous_word_boundary(editor, cursor, cx), cx);
+      }
     }
-  }
-  editor.replace_text_in_range(None, "", window, cx)
+    editor.describe_next_edit("Delete");
+    editor.replace_text_in_range(None, "", window, cx)
+  });
 }
 
 pub fn backspace_all(
@@ -119,244 +282,221 @@ pub fn backspace_all(
   window: &mut Window,
   cx: &mut Context<Editor>,
 ) {
-  editor.target_column = None;
-  if editor.selected_range.is_empty() {
-    let document = editor.document.read(cx);
-    let cursor = editor.cursor_offset();
-    let line = document.char_to_line(cursor);
-    let line_start = document.line_to_char(line);
-
-    // If we're at the beginning of an empty line, behave like simple backspace
-    if cursor == line_start && document.line_content(line).unwrap_or_default().is_empty() {
-      editor.select_to(boundaries::previous_boundary(editor, cursor, cx), cx);
-    } else {
-      // Delete from start of current line to cursor
-      editor.select_to(line_start, cx);
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    if editor.selected_range.is_empty() {
+      let document = editor.document.read(cx);
+      let cursor = editor.cursor_offset();
+      let line = document.char_to_line(cursor);
+      let line_start = document.line_to_char(line);
+
+      // If we're at the beginning of an empty line, behave like simple backspace
+      if cursor == line_start && document.line_content(line).unwrap_or_default().is_empty() {
+        editor.select_to(boundaries::previous_boundary(editor, cursor, cx), cx);
+      } else {
+        // Delete from start of current line to cursor
+        editor.select_to(line_start, cx);
+      }
     }
-  }
-  editor.replace_text_in_range(None, "", window, cx)
+    editor.describe_next_edit("Delete");
+    editor.replace_text_in_range(None, "", window, cx)
+  });
 }
 
 pub fn delete(editor: &mut Editor, _: &Delete, window: &mut Window, cx: &mut Context<Editor>) {
-  editor.target_column = None;
-  if editor.selected_range.is_empty() {
-    editor.select_to(
-      boundaries::next_boundary(editor, editor.cursor_offset(), cx),
-      cx,
-    )
-  }
-  editor.replace_text_in_range(None, "", window, cx)
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    if editor.selected_range.is_empty() {
+      editor.select_to(
+        boundaries::next_boundary(editor, editor.cursor_offset(), cx),
+        cx,
+      )
+    }
+    editor.describe_next_edit("Delete");
+    editor.replace_text_in_range(None, "", window, cx)
+  });
 }
 
 pub fn up(editor: &mut Editor, _: &Up, window: &mut Window, cx: &mut Context<Editor>) {
-  let new_cursor = {
-    let document = editor.document.read(cx);
-    let cursor_offset = editor.cursor_offset();
-    let current_line = document.char_to_line(cursor_offset);
-
-    if current_line > 0 {
-      if editor.target_column.is_none() {
-        let line_start = document.line_to_char(current_line);
-        editor.target_column = Some(cursor_offset - line_start);
-      }
-
-      let target_column = editor.target_column.unwrap();
-
-      // Calculate new position in target line
-      let target_line = current_line - 1;
-      let target_start = document.line_to_char(target_line);
-      let target_len = document.line_content(target_line).unwrap_or_default().len();
-
-      Some(target_start + target_column.min(target_len))
-    } else {
-      // On first line, go to beginning of buffer
-      editor.target_column = None;
-      Some(0)
-    }
-  };
-
-  if let Some(cursor) = new_cursor {
-    editor.move_to(cursor, cx);
-    editor.ensure_cursor_visible(window, cx);
+  if path_completion::select(editor, -1, cx) {
+    return;
   }
+  motion::queue(editor, Motion::Up, window, cx);
 }
 
 pub fn down(editor: &mut Editor, _: &Down, window: &mut Window, cx: &mut Context<Editor>) {
-  let new_cursor = {
-    let document = editor.document.read(cx);
-    let cursor_offset = editor.cursor_offset();
-    let current_line = document.char_to_line(cursor_offset);
-
-    if current_line < document.len_lines().saturating_sub(1) {
-      if editor.target_column.is_none() {
-        let line_start = document.line_to_char(current_line);
-        editor.target_column = Some(cursor_offset - line_start);
-      }
-
-      let target_column = editor.target_column.unwrap();
-
-      let target_line = current_line + 1;
-      let target_start = document.line_to_char(target_line);
-      let target_len = document.line_content(target_line).unwrap_or_default().len();
+  if path_completion::select(editor, 1, cx) {
+    return;
+  }
+  motion::queue(editor, Motion::Down, window, cx);
+}
 
-      Some(target_start + target_column.min(target_len))
-    } else {
-      editor.target_column = None;
-      Some(document.len())
-    }
-  };
+pub fn page_up(editor: &mut Editor, _: &PageUp, window: &mut Window, cx: &mut Context<Editor>) {
+  motion::page(editor, true, false, window, cx);
+}
 
-  if let Some(cursor) = new_cursor {
-    editor.move_to(cursor, cx);
-    editor.ensure_cursor_visible(window, cx);
-  }
+pub fn page_down(editor: &mut Editor, _: &PageDown, window: &mut Window, cx: &mut Context<Editor>) {
+  motion::page(editor, false, false, window, cx);
 }
 
 pub fn left(editor: &mut Editor, _: &Left, window: &mut Window, cx: &mut Context<Editor>) {
-  editor.target_column = None;
-  if editor.selected_range.is_empty() {
-    editor.move_to(
-      boundaries::previous_boundary(editor, editor.cursor_offset(), cx),
-      cx,
-    );
-  } else {
-    editor.move_to(editor.selected_range.start, cx)
-  }
-  editor.ensure_cursor_visible(window, cx);
+  motion::queue(editor, Motion::Left, window, cx);
 }
 
 pub fn alt_left(editor: &mut Editor, _: &AltLeft, window: &mut Window, cx: &mut Context<Editor>) {
-  editor.target_column = None;
-  if editor.selected_range.is_empty() {
-    editor.move_to(
-      boundaries::previous_word_boundary(editor, editor.cursor_offset(), cx),
-      cx,
-    );
-  } else {
-    editor.move_to(editor.selected_range.start, cx)
+  if selection_lock::is_locked(editor, cx) {
+    return select_word_left(editor, &SelectWordLeft, window, cx);
   }
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    if editor.selected_range.is_empty() {
+      editor.move_to(
+        boundaries::previous_word_boundary(editor, editor.cursor_offset(), cx),
+        cx,
+      );
+    } else {
+      editor.move_to(editor.selected_range.start, cx)
+    }
+  });
   editor.ensure_cursor_visible(window, cx);
 }
 
 pub fn cmd_left(editor: &mut Editor, _: &CmdLeft, window: &mut Window, cx: &mut Context<Editor>) {
-  editor.target_column = None;
-  let document = editor.document.read(cx);
-  let cursor = editor.cursor_offset();
-  let line = document.char_to_line(cursor);
-  let line_start = document.line_to_char(line);
-  editor.move_to(line_start, cx);
+  if selection_lock::is_locked(editor, cx) {
+    return select_cmd_left(editor, &SelectCmdLeft, window, cx);
+  }
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    let document = editor.document.read(cx);
+    let cursor = editor.cursor_offset();
+    let line = document.char_to_line(cursor);
+    let line_start = document.line_to_char(line);
+    editor.move_to(line_start, cx);
+  });
   editor.ensure_cursor_visible(window, cx);
 }
 
 pub fn right(editor: &mut Editor, _: &Right, window: &mut Window, cx: &mut Context<Editor>) {
-  editor.target_column = None;
-  if editor.selected_range.is_empty() {
-    editor.move_to(
-      boundaries::next_boundary(editor, editor.selected_range.end, cx),
-      cx,
-    );
-  } else {
-    editor.move_to(editor.selected_range.end, cx)
-  }
-  editor.ensure_cursor_visible(window, cx);
+  motion::queue(editor, Motion::Right, window, cx);
 }
 
 pub fn alt_right(editor: &mut Editor, _: &AltRight, window: &mut Window, cx: &mut Context<Editor>) {
-  editor.target_column = None;
-  if editor.selected_range.is_empty() {
-    editor.move_to(
-      boundaries::next_word_boundary(editor, editor.selected_range.end, cx),
-      cx,
-    );
-  } else {
-    editor.move_to(editor.selected_range.end, cx)
+  if selection_lock::is_locked(editor, cx) {
+    return select_word_right(editor, &SelectWordRight, window, cx);
   }
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    if editor.selected_range.is_empty() {
+      editor.move_to(
+        boundaries::next_word_boundary(editor, editor.selected_range.end, cx),
+        cx,
+      );
+    } else {
+      editor.move_to(editor.selected_range.end, cx)
+    }
+  });
   editor.ensure_cursor_visible(window, cx);
 }
 
 pub fn cmd_right(editor: &mut Editor, _: &CmdRight, window: &mut Window, cx: &mut Context<Editor>) {
-  editor.target_column = None;
-  let document = editor.document.read(cx);
-  let cursor = editor.cursor_offset();
-  let line = document.char_to_line(cursor);
-  let line_range = document.line_range(line).unwrap_or(0..0);
-  // Go to end of line content (before the newline)
-  let line_content = document.line_content(line).unwrap_or_default();
-  let line_end = line_range.start + line_content.len();
-  editor.move_to(line_end, cx);
+  if selection_lock::is_locked(editor, cx) {
+    return select_cmd_right(editor, &SelectCmdRight, window, cx);
+  }
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    let document = editor.document.read(cx);
+    let cursor = editor.cursor_offset();
+    let line = document.char_to_line(cursor);
+    let line_range = document.line_range(line).unwrap_or(0..0);
+    // Go to end of line content (before the newline)
+    let line_content = document.line_content(line).unwrap_or_default();
+    let line_end = line_range.start + line_content.len();
+    editor.move_to(line_end, cx);
+  });
   editor.ensure_cursor_visible(window, cx);
 }
 
 pub fn cmd_up(editor: &mut Editor, _: &CmdUp, window: &mut Window, cx: &mut Context<Editor>) {
-  editor.target_column = None;
-  editor.move_to(0, cx);
+  if selection_lock::is_locked(editor, cx) {
+    return select_cmd_up(editor, &SelectCmdUp, window, cx);
+  }
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    editor.move_to(0, cx);
+  });
   editor.ensure_cursor_visible(window, cx);
 }
 
 pub fn cmd_down(editor: &mut Editor, _: &CmdDown, window: &mut Window, cx: &mut Context<Editor>) {
-  editor.target_column = None;
-  let document = editor.document.read(cx);
-  editor.move_to(document.len(), cx);
+  if selection_lock::is_locked(editor, cx) {
+    return select_cmd_down(editor, &SelectCmdDown, window, cx);
+  }
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    let document = editor.document.read(cx);
+    editor.move_to(document.len(), cx);
+  });
   editor.ensure_cursor_visible(window, cx);
 }
 
+/// Move to the first non-blank character of the line, or to its start when
+/// already there
 pub fn home(editor: &mut Editor, _: &Home, window: &mut Window, cx: &mut Context<Editor>) {
-  editor.target_column = None;
-  editor.move_to(0, cx);
+  if selection_lock::is_locked(editor, cx) {
+    return select_home(editor, &SelectHome, window, cx);
+  }
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    editor.move_to(
+      boundaries::smart_home(editor, editor.cursor_offset(), cx),
+      cx,
+    );
+  });
   editor.ensure_cursor_visible(window, cx);
 }
 
 pub fn end(editor: &mut Editor, _: &End, window: &mut Window, cx: &mut Context<Editor>) {
-  editor.target_column = None;
-  let doc_len = editor.document.read(cx).len();
-  editor.move_to(doc_len, cx);
+  if selection_lock::is_locked(editor, cx) {
+    return select_end(editor, &SelectEnd, window, cx);
+  }
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    editor.move_to(boundaries::line_end(editor, editor.cursor_offset(), cx), cx);
+  });
   editor.ensure_cursor_visible(window, cx);
 }
 
-pub fn select_up(editor: &mut Editor, _: &SelectUp, window: &mut Window, cx: &mut Context<Editor>) {
-  // Keep the anchor point of the selection
-  let anchor = if editor.selection_reversed {
-    editor.selected_range.end
-  } else {
-    editor.selected_range.start
-  };
-
-  // Calculate new cursor position (same logic as up())
-  let new_cursor = {
-    let document = editor.document.read(cx);
-    let cursor_offset = editor.cursor_offset();
-    let current_line = document.char_to_line(cursor_offset);
-
-    if current_line > 0 {
-      if editor.target_column.is_none() {
-        let line_start = document.line_to_char(current_line);
-        editor.target_column = Some(cursor_offset - line_start);
-      }
-
-      let target_column = editor.target_column.unwrap();
-
-      let target_line = current_line - 1;
-      let target_start = document.line_to_char(target_line);
-      let target_len = document.line_content(target_line).unwrap_or_default().len();
-
-      Some(target_start + target_column.min(target_len))
-    } else {
-      editor.target_column = None;
-      Some(0)
-    }
-  };
+pub fn select_home(
+  editor: &mut Editor,
+  _: &SelectHome,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    editor.select_to(
+      boundaries::smart_home(editor, editor.cursor_offset(), cx),
+      cx,
+    );
+  });
+  editor.ensure_cursor_visible(window, cx);
+}
 
-  // Move cursor and extend selection
-  let cursor = new_cursor.unwrap();
-  if anchor <= cursor {
-    editor.selected_range = anchor..cursor;
-    editor.selection_reversed = false;
-  } else {
-    editor.selected_range = cursor..anchor;
-    editor.selection_reversed = true;
-  }
+pub fn select_end(
+  editor: &mut Editor,
+  _: &SelectEnd,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    editor.select_to(boundaries::line_end(editor, editor.cursor_offset(), cx), cx);
+  });
   editor.ensure_cursor_visible(window, cx);
-  cx.notify();
+}
+
+pub fn select_up(editor: &mut Editor, _: &SelectUp, window: &mut Window, cx: &mut Context<Editor>) {
+  motion::queue(editor, Motion::SelectUp, window, cx);
 }
 
 pub fn select_down(
@@ -365,59 +505,34 @@ pub fn select_down(
   window: &mut Window,
   cx: &mut Context<Editor>,
 ) {
-  // Keep the anchor point of the selection
-  let anchor = if editor.selection_reversed {
-    editor.selected_range.end
-  } else {
-    editor.selected_range.start
-  };
-
-  // Calculate new cursor position (same logic as down())
-  let new_cursor = {
-    let document = editor.document.read(cx);
-    let cursor_offset = editor.cursor_offset();
-    let current_line = document.char_to_line(cursor_offset);
-    let total_lines = document.len_lines();
-
-    if current_line + 1 < total_lines {
-      if editor.target_column.is_none() {
-        let line_start = document.line_to_char(current_line);
-        editor.target_column = Some(cursor_offset - line_start);
-      }
-
-      let target_column = editor.target_column.unwrap();
-
-      let target_line = current_line + 1;
-      let target_start = document.line_to_char(target_line);
-      let target_len = document.line_content(target_line).unwrap_or_default().len();
+  motion::queue(editor, Motion::SelectDown, window, cx);
+}
 
-      Some(target_start + target_column.min(target_len))
-    } else {
-      // On last line, go to end of buffer
-      editor.target_column = None;
-      Some(document.len())
-    }
-  };
+pub fn select_page_up(
+  editor: &mut Editor,
+  _: &SelectPageUp,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  motion::page(editor, true, true, window, cx);
+}
 
-  // Move cursor and extend selection
-  let cursor = new_cursor.unwrap();
-  if anchor <= cursor {
-    editor.selected_range = anchor..cursor;
-    editor.selection_reversed = false;
-  } else {
-    editor.selected_range = cursor..anchor;
-    editor.selection_reversed = true;
-  }
-  editor.ensure_cursor_visible(window, cx);
-  cx.notify();
+pub fn select_page_down(
+  editor: &mut Editor,
+  _: &SelectPageDown,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  motion::page(editor, false, true, window, cx);
 }
 
-pub fn select_left(editor: &mut Editor, _: &SelectLeft, _: &mut Window, cx: &mut Context<Editor>) {
-  editor.target_column = None;
-  editor.select_to(
-    boundaries::previous_boundary(editor, editor.cursor_offset(), cx),
-    cx,
-  );
+pub fn select_left(
+  editor: &mut Editor,
+  _: &SelectLeft,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  motion::queue(editor, Motion::SelectLeft, window, cx);
 }
 
 pub fn select_word_left(
@@ -426,11 +541,13 @@ pub fn select_word_left(
   window: &mut Window,
   cx: &mut Context<Editor>,
 ) {
-  editor.target_column = None;
-  editor.select_to(
-    boundaries::previous_word_boundary(editor, editor.cursor_offset(), cx),
-    cx,
-  );
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    editor.select_to(
+      boundaries::previous_word_boundary(editor, editor.cursor_offset(), cx),
+      cx,
+    );
+  });
   editor.ensure_cursor_visible(window, cx);
 }
 
@@ -440,12 +557,7 @@ pub fn select_right(
   window: &mut Window,
   cx: &mut Context<Editor>,
 ) {
-  editor.target_column = None;
-  editor.select_to(
-    boundaries::next_boundary(editor, editor.cursor_offset(), cx),
-    cx,
-  );
-  editor.ensure_cursor_visible(window, cx);
+  motion::queue(editor, Motion::SelectRight, window, cx);
 }
 
 pub fn select_word_right(
@@ -454,11 +566,13 @@ pub fn select_word_right(
   window: &mut Window,
   cx: &mut Context<Editor>,
 ) {
-  editor.target_column = None;
-  editor.select_to(
-    boundaries::next_word_boundary(editor, editor.cursor_offset(), cx),
-    cx,
-  );
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    editor.select_to(
+      boundaries::next_word_boundary(editor, editor.cursor_offset(), cx),
+      cx,
+    );
+  });
   editor.ensure_cursor_visible(window, cx);
 }
 
@@ -468,11 +582,13 @@ pub fn select_cmd_left(
   window: &mut Window,
   cx: &mut Context<Editor>,
 ) {
-  let document = editor.document.read(cx);
-  let cursor = editor.cursor_offset();
-  let line = document.char_to_line(cursor);
-  let line_start = document.line_to_char(line);
-  editor.select_to(line_start, cx);
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    let document = editor.document.read(cx);
+    let cursor = editor.cursor_offset();
+    let line = document.char_to_line(cursor);
+    let line_start = document.line_to_char(line);
+    editor.select_to(line_start, cx);
+  });
   editor.ensure_cursor_visible(window, cx);
 }
 
@@ -482,13 +598,15 @@ pub fn select_cmd_right(
   window: &mut Window,
   cx: &mut Context<Editor>,
 ) {
-  let document = editor.document.read(cx);
-  let cursor = editor.cursor_offset();
-  let line = document.char_to_line(cursor);
-  let line_range = document.line_range(line).unwrap_or(0..0);
-  let line_content = document.line_content(line).unwrap_or_default();
-  let line_end = line_range.start + line_content.len();
-  editor.select_to(line_end, cx);
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    let document = editor.document.read(cx);
+    let cursor = editor.cursor_offset();
+    let line = document.char_to_line(cursor);
+    let line_range = document.line_range(line).unwrap_or(0..0);
+    let line_content = document.line_content(line).unwrap_or_default();
+    let line_end = line_range.start + line_content.len();
+    editor.select_to(line_end, cx);
+  });
   editor.ensure_cursor_visible(window, cx);
 }
 
@@ -498,7 +616,9 @@ pub fn select_cmd_up(
   window: &mut Window,
   cx: &mut Context<Editor>,
 ) {
-  editor.select_to(0, cx);
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.select_to(0, cx);
+  });
   editor.ensure_cursor_visible(window, cx);
 }
 
@@ -508,12 +628,15 @@ pub fn select_cmd_down(
   window: &mut Window,
   cx: &mut Context<Editor>,
 ) {
-  let document = editor.document.read(cx);
-  editor.select_to(document.len(), cx);
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    let document = editor.document.read(cx);
+    editor.select_to(document.len(), cx);
+  });
   editor.ensure_cursor_visible(window, cx);
 }
 
 pub fn select_all(editor: &mut Editor, _: &SelectAll, _: &mut Window, cx: &mut Context<Editor>) {
+  multi_cursor::clear(editor, cx);
   editor.target_column = None;
   let doc_len = editor.document.read(cx).len();
 
@@ -521,43 +644,235 @@ pub fn select_all(editor: &mut Editor, _: &SelectAll, _: &mut Window, cx: &mut C
   editor.select_to(doc_len, cx);
 }
 
+pub fn select_next_occurrence(
+  editor: &mut Editor,
+  _: &SelectNextOccurrence,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  multi_cursor::select_next_occurrence(editor, window, cx);
+}
+
 // === Clipboard Actions ===
 
 pub fn paste(editor: &mut Editor, _: &Paste, window: &mut Window, cx: &mut Context<Editor>) {
   editor.target_column = None;
-  if let Some(text) = cx.read_from_clipboard().and_then(|item| item.text()) {
-    let cursor = editor.cursor_offset();
-    let current_line = editor.document.read(cx).char_to_line(cursor);
-    editor.replace_text_in_range(None, &text, window, cx);
-    // Invalidate cache from current line onwards since paste may add multiple lines
-    editor.invalidate_lines_from(current_line);
+  let Some(item) = cx.read_from_clipboard() else {
+    return;
+  };
+  let Some(text) = item.text() else {
+    return;
+  };
+  let lengths = match item.entries() {
+    [ClipboardEntry::String(string)] => string.metadata_json::<Vec<usize>>(),
+    _ => None,
+  };
+  let selections = editor.extra_selections.len() + 1;
+  if selections > 1
+    && let Some(pieces) = split_clipboard(&text, lengths.as_deref(), selections)
+  {
+    return paste_pieces(editor, &pieces, window, cx);
+  }
+
+  let smart_paste = {
+    let document = editor.document.read(cx);
+    markdown::is_markdown(document.file_extension())
+      .then(|| {
+        let selection = document.slice_to_string(editor.selected_range.clone());
+        markdown::smart_paste(&selection, &text)
+      })
+      .flatten()
+  };
+  match smart_paste {
+    Some(SmartPaste::Link(link)) => paste_text(editor, &link, window, cx),
+    Some(SmartPaste::Image(image_link)) => {
+      let answer = window.prompt(
+        PromptLevel::Info,
+        "Insert an image link?",
+        Some(&image_link),
+        &["Image Link", "Plain Text"],
+        cx,
+      );
+      cx.spawn_in(window, async move |this, cx| {
+        let text = match answer.await {
+          Ok(0) => image_link,
+          Ok(_) => text,
+          Err(_) => return,
+        };
+        let _ = this.update_in(cx, |editor, window, cx| {
+          paste_text(editor, &text, window, cx)
+        });
+      })
+      .detach();
+    }
+    None => paste_format::paste(editor, text, window, cx),
+  }
+}
+
+pub(crate) fn paste_text(
+  editor: &mut Editor,
+  text: &str,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  let cursor = editor.cursor_offset();
+  let current_line = editor.document.read(cx).char_to_line(cursor);
+  editor.describe_next_edit("Paste");
+  editor.replace_text_in_range(None, text, window, cx);
+  // Invalidate cache from current line onwards since paste may add multiple lines
+  editor.invalidate_lines_from(current_line);
+}
+
+/// `text` split into `count` pieces: the ones it was copied from, by their
+/// char `lengths`, or else its lines
+pub(crate) fn split_clipboard(
+  text: &str,
+  lengths: Option<&[usize]>,
+  count: usize,
+) -> Option<Vec<String>> {
+  let Some(lengths) = lengths else {
+    let lines: Vec<String> = text.lines().map(String::from).collect();
+    return (lines.len() == count).then_some(lines);
+  };
+  if lengths.len() != count {
+    return None;
+  }
+  let mut chars = text.chars();
+  let mut pieces = Vec::with_capacity(count);
+  for (ix, &len) in lengths.iter().enumerate() {
+    if ix > 0 && chars.next() != Some('\n') {
+      return None;
+    }
+    let piece: String = chars.by_ref().take(len).collect();
+    if piece.chars().count() != len {
+      return None;
+    }
+    pieces.push(piece);
+  }
+  chars.next().is_none().then_some(pieces)
+}
+
+/// Paste one of `pieces` at every selection, in document order
+fn paste_pieces(
+  editor: &mut Editor,
+  pieces: &[String],
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  let order = multi_cursor::document_order(editor, cx);
+  let mut visited = 0;
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    paste_text(editor, &pieces[order[visited]], window, cx);
+    visited += 1;
+  });
+}
+
+/// Text of every non-empty selection, in document order
+fn selected_texts(editor: &mut Editor, cx: &mut Context<Editor>) -> Vec<String> {
+  let mut texts = Vec::new();
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    if !editor.selected_range.is_empty() {
+      let range = editor.selected_range.clone();
+      texts.push((range.start, editor.document.read(cx).slice_to_string(range)));
+    }
+  });
+  texts.sort_by_key(|(start, _)| *start);
+  texts.into_iter().map(|(_, text)| text).collect()
+}
+
+/// The selections' texts one per line, with their lengths so paste can hand
+/// them back out one per cursor
+fn clipboard_item(texts: &[String]) -> ClipboardItem {
+  let text = texts.join("\n");
+  if texts.len() < 2 {
+    return ClipboardItem::new_string(text);
   }
+  let lengths: Vec<usize> = texts.iter().map(|text| text.chars().count()).collect();
+  ClipboardItem::new_string_with_json_metadata(text, lengths)
 }
 
+/// Copy every selection, one per line
 pub fn copy(editor: &mut Editor, _: &Copy, _: &mut Window, cx: &mut Context<Editor>) {
-  if !editor.selected_range.is_empty() {
-    cx.write_to_clipboard(ClipboardItem::new_string(
-      editor
-        .document
-        .read(cx)
-        .slice_to_string(editor.selected_range.clone()),
-    ));
+  let texts = selected_texts(editor, cx);
+  if !texts.is_empty() {
+    cx.write_to_clipboard(clipboard_item(&texts));
   }
 }
 
+/// Copy every selection, one per line, and remove them
 pub fn cut(editor: &mut Editor, _: &Cut, window: &mut Window, cx: &mut Context<Editor>) {
-  editor.target_column = None;
-  if !editor.selected_range.is_empty() {
+  let texts = selected_texts(editor, cx);
+  if texts.is_empty() {
+    return;
+  }
+  cx.write_to_clipboard(clipboard_item(&texts));
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    editor.target_column = None;
+    if !editor.selected_range.is_empty() {
+      let cursor = editor.cursor_offset();
+      let current_line = editor.document.read(cx).char_to_line(cursor);
+      editor.describe_next_edit("Cut");
+      editor.replace_text_in_range(None, "", window, cx);
+      // Invalidate cache from current line onwards since cut may affect multiple lines
+      editor.invalidate_lines_from(current_line);
+    }
+  });
+}
+
+/// `existing` clipboard text followed by `fragment` on a line of its own
+pub(crate) fn append_clipboard_text(existing: Option<&str>, fragment: &str) -> String {
+  match existing {
+    Some(existing) if !existing.is_empty() => {
+      let separator = if existing.ends_with('\n') { "" } else { "\n" };
+      format!("{}{}{}", existing, separator, fragment)
+    }
+    _ => fragment.to_string(),
+  }
+}
+
+/// Append the selection, or the cursor's line without its break, to the
+/// clipboard; returns the range to remove when cutting
+pub(crate) fn copy_append_fragment(editor: &Editor, cx: &mut Context<Editor>) -> Range<usize> {
+  let document = editor.document.read(cx);
+  let (range, fragment) = if editor.selected_range.is_empty() {
     let cursor = editor.cursor_offset();
-    let current_line = editor.document.read(cx).char_to_line(cursor);
-    cx.write_to_clipboard(ClipboardItem::new_string(
-      editor
-        .document
-        .read(cx)
-        .slice_to_string(editor.selected_range.clone()),
-    ));
+    let line = document.char_to_line(cursor);
+    (
+      document.line_range(line).unwrap_or(cursor..cursor),
+      document.line_content(line).unwrap_or_default().into_owned(),
+    )
+  } else {
+    (
+      editor.selected_range.clone(),
+      document.slice_to_string(editor.selected_range.clone()),
+    )
+  };
+
+  let existing = cx.read_from_clipboard().and_then(|item| item.text());
+  cx.write_to_clipboard(ClipboardItem::new_string(append_clipboard_text(
+    existing.as_deref(),
+    &fragment,
+  )));
+  range
+}
+
+pub fn copy_append(editor: &mut Editor, _: &CopyAppend, _: &mut Window, cx: &mut Context<Editor>) {
+  copy_append_fragment(editor, cx);
+}
+
+pub fn cut_append(
+  editor: &mut Editor,
+  _: &CutAppend,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  editor.target_column = None;
+  let range = copy_append_fragment(editor, cx);
+  if !range.is_empty() {
+    let current_line = editor.document.read(cx).char_to_line(range.start);
+    editor.selected_range = range;
+    editor.describe_next_edit("Cut");
     editor.replace_text_in_range(None, "", window, cx);
-    // Invalidate cache from current line onwards since cut may affect multiple lines
     editor.invalidate_lines_from(current_line);
   }
 }
@@ -565,82 +880,1258 @@ pub fn cut(editor: &mut Editor, _: &Cut, window: &mut Window, cx: &mut Context<E
 // === Undo/Redo Actions ===
 
 pub fn undo(editor: &mut Editor, _: &Undo, _window: &mut Window, cx: &mut Context<Editor>) {
-  if let Some(transaction) = editor.undo_stack.pop_back() {
-    let buffer_tx_id = editor.document.update(cx, |doc, cx| {
-      let result = doc.undo(cx);
+  multi_cursor::clear(editor, cx);
+  let document = editor.document.read(cx);
+  let (version, line_count) = (document.version(), document.len_lines());
+  let Some(transaction_id) = editor.document.update(cx, |doc, cx| {
+    let id = doc.undo(cx)?;
+    // Trigger async syntax re-highlighting after undo
+    doc.schedule_recompute_highlights(cx);
+    Some(id)
+  }) else {
+    return;
+  };
 
-      // Trigger async syntax re-highlighting after undo
-      if result.is_some() {
-        doc.schedule_recompute_highlights(cx);
-      }
+  // Restore cursor position from before the transaction
+  let selection = editor
+    .document
+    .read(cx)
+    .buffer
+    .selection(transaction_id)
+    .map(|selection| selection.before.clone());
+  restore_selection(editor, selection, cx);
+  editor.invalidate_edits_since(version, line_count, cx);
+  cx.notify();
+}
 
-      result
-    });
+pub fn redo(editor: &mut Editor, _: &Redo, _window: &mut Window, cx: &mut Context<Editor>) {
+  multi_cursor::clear(editor, cx);
+  let document = editor.document.read(cx);
+  let (version, line_count) = (document.version(), document.len_lines());
+  let Some(transaction_id) = editor.document.update(cx, |doc, cx| {
+    let id = doc.redo(cx)?;
+    // Trigger async syntax re-highlighting after redo
+    doc.schedule_recompute_highlights(cx);
+    Some(id)
+  }) else {
+    return;
+  };
 
-    // Only restore selection if buffer undo succeeded
-    if buffer_tx_id.is_some() {
-      // Restore cursor position from before the transaction
-      editor.selected_range = transaction.selection_before.clone();
-      editor.selection_reversed = false;
+  // Restore cursor position from after the transaction
+  let selection = editor
+    .document
+    .read(cx)
+    .buffer
+    .selection(transaction_id)
+    .map(|selection| selection.after.clone());
+  restore_selection(editor, selection, cx);
+  editor.invalidate_edits_since(version, line_count, cx);
+  cx.notify();
+}
 
-      // Invalidate cache (content may have changed significantly)
-      editor.line_layouts.clear();
+/// Select what undo or redo recorded, or keep the selection inside the text
+/// for an edit that recorded none
+fn restore_selection(editor: &mut Editor, selection: Option<Range<usize>>, cx: &App) {
+  let len = editor.document.read(cx).len();
+  let selection = selection.unwrap_or_else(|| editor.selected_range.clone());
+  editor.selected_range = selection.start.min(len)..selection.end.min(len);
+  editor.selection_reversed = false;
+}
 
-      // Move transaction to redo stack
-      editor.redo_stack.push_back(transaction);
+/// Rewrap the comments of the selected lines, or the comment paragraph under
+/// the cursor, to the wrap column; plain text documents are rewrapped as prose
+pub fn reflow_paragraph(
+  editor: &mut Editor,
+  _: &ReflowParagraph,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  editor.target_column = None;
+  let selection_before = editor.selected_range.clone();
+  let document = editor.document.read(cx);
+  let mode = match document.language_config() {
+    Some(config) => reflow::ReflowMode::Comments(config.comments),
+    None => reflow::ReflowMode::Prose,
+  };
+  let line_text = |line_idx: usize| {
+    document
+      .line_content(line_idx)
+      .map(|line| line.into_owned())
+      .unwrap_or_default()
+  };
 
-      cx.notify();
-    } else {
-      // Buffer undo failed, push transaction back
-      editor.undo_stack.push_back(transaction);
+  let mut start_line = document.char_to_line(selection_before.start);
+  let mut end_line = document.char_to_line(selection_before.end);
+  if selection_before.is_empty() {
+    let current = line_text(start_line);
+    if !reflow::is_text(&current, mode) || reflow::is_blank(&current, mode) {
+      return;
+    }
+    while start_line > 0 && reflow::in_same_paragraph(&current, &line_text(start_line - 1), mode) {
+      start_line -= 1;
     }
+    while end_line + 1 < document.len_lines()
+      && reflow::in_same_paragraph(&current, &line_text(end_line + 1), mode)
+    {
+      end_line += 1;
+    }
+  } else if end_line > start_line && document.line_to_char(end_line) == selection_before.end {
+    // A selection ending at a line start doesn't include that line
+    end_line -= 1;
   }
-}
 
-pub fn redo(editor: &mut Editor, _: &Redo, _window: &mut Window, cx: &mut Context<Editor>) {
-  if let Some(transaction) = editor.redo_stack.pop_back() {
-    let buffer_tx_id = editor.document.update(cx, |doc, cx| {
-      let result = doc.redo(cx);
+  let lines: Vec<String> = (start_line..=end_line).map(line_text).collect();
+  let line_refs: Vec<&str> = lines.iter().map(String::as_str).collect();
+  // Keep the line ending of the paragraph, or of the document for a last line
+  let ending_line = if start_line < end_line || start_line + 1 < document.len_lines() {
+    start_line
+  } else {
+    0
+  };
+  let line_ending = match document.line_range(ending_line) {
+    Some(range) if document.slice_to_string(range).ends_with("\r\n") => "\r\n",
+    _ => "\n",
+  };
+  let new_text =
+    reflow::reflow_lines(&line_refs, mode, ruler::wrap_column(editor, cx)).join(line_ending);
 
-      // Trigger async syntax re-highlighting after redo
-      if result.is_some() {
-        doc.schedule_recompute_highlights(cx);
-      }
+  let range_start = document.line_to_char(start_line);
+  let range = range_start..document.line_to_char(end_line) + lines[lines.len() - 1].chars().count();
+  if document.slice_to_string(range.clone()) == new_text {
+    return;
+  }
 
-      result
+  let transaction_id = editor.document.update(cx, |doc, cx| {
+    let id = doc.buffer.transaction(Instant::now(), |buffer, tx| {
+      buffer.replace(tx, range.clone(), &new_text);
     });
+    doc.schedule_recompute_highlights(cx);
+    cx.notify();
+    id
+  });
+
+  editor.move_to(range_start + new_text.chars().count(), cx);
+  let selection_after = editor.selected_range.clone();
+  editor.record_transaction(
+    transaction_id,
+    selection_before,
+    selection_after,
+    "Reflow Paragraph",
+    cx,
+  );
 
-    // Only restore selection if buffer redo succeeded
-    if buffer_tx_id.is_some() {
-      // Restore cursor position from after the transaction
-      editor.selected_range = transaction.selection_after.clone();
-      editor.selection_reversed = false;
+  editor.invalidate_lines_from(start_line);
+  editor.ensure_cursor_visible(window, cx);
+}
 
-      // Invalidate cache
-      editor.line_layouts.clear();
+// === Line Actions ===
 
-      // Move transaction to undo stack
-      editor.undo_stack.push_back(transaction);
+/// Copy the lines under the selection below them, selecting the copy
+pub fn duplicate_line(
+  editor: &mut Editor,
+  _: &DuplicateLine,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  multi_cursor::for_each(editor, cx, duplicate_lines);
+  editor.ensure_cursor_visible(window, cx);
+}
 
-      cx.notify();
+pub fn delete_line(
+  editor: &mut Editor,
+  _: &DeleteLine,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  multi_cursor::for_each(editor, cx, delete_lines);
+  editor.ensure_cursor_visible(window, cx);
+}
+
+pub fn move_line_up(
+  editor: &mut Editor,
+  _: &MoveLineUp,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  multi_cursor::for_each(editor, cx, |editor, cx| move_lines(editor, true, cx));
+  editor.ensure_cursor_visible(window, cx);
+}
+
+pub fn move_line_down(
+  editor: &mut Editor,
+  _: &MoveLineDown,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  multi_cursor::for_each(editor, cx, |editor, cx| move_lines(editor, false, cx));
+  editor.ensure_cursor_visible(window, cx);
+}
+
+/// Texts of `lines`, without line breaks
+fn line_texts(document: &Document, lines: Range<usize>) -> Vec<String> {
+  lines
+    .map(|line| document.line_content(line).unwrap_or_default().into_owned())
+    .collect()
+}
+
+fn duplicate_lines(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let document = editor.document.read(cx);
+  let selection = editor.selected_range.clone();
+  let lines = indent::selected_lines(document, &selection);
+  let start = document.line_to_char(lines.start);
+  let copy = line_texts(document, lines).join("\n") + "\n";
+  // The copy goes above, so the selection ends up on the lower lines
+  let shift = copy.chars().count();
+  let selection = selection.start + shift..selection.end + shift;
+  replace_lines(editor, start..start, &copy, selection, "Duplicate Line", cx);
+}
+
+fn delete_lines(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let document = editor.document.read(cx);
+  let cursor = editor.cursor_offset();
+  let column = cursor - document.line_to_char(document.char_to_line(cursor));
+  let lines = indent::selected_lines(document, &editor.selected_range);
+  let mut range = document.line_to_char(lines.start)..document.len();
+  let mut next_line = lines.start;
+  if lines.end < document.len_lines() {
+    range.end = document.line_to_char(lines.end);
+  } else if lines.start > 0 {
+    // The last line takes the line break before it along
+    range.start -= 1;
+    next_line -= 1;
+  }
+  if range.is_empty() {
+    return;
+  }
+
+  // The cursor stays in its column on the line that takes their place
+  let next_line_start = document.line_to_char(next_line);
+  let next_line_len = document
+    .line_content(if next_line < lines.start {
+      next_line
     } else {
-      // Buffer redo failed, push transaction back
-      editor.redo_stack.push_back(transaction);
+      lines.end
+    })
+    .map_or(0, |content| content.chars().count());
+  let cursor = next_line_start + column.min(next_line_len);
+  replace_lines(editor, range, "", cursor..cursor, "Delete Line", cx);
+}
+
+/// Swap the lines under the selection with the line above or below them
+fn move_lines(editor: &mut Editor, up: bool, cx: &mut Context<Editor>) {
+  let document = editor.document.read(cx);
+  let selection = editor.selected_range.clone();
+  let lines = indent::selected_lines(document, &selection);
+  let other = if up {
+    let Some(above) = lines.start.checked_sub(1) else {
+      return;
+    };
+    above
+  } else {
+    if lines.end >= document.len_lines() {
+      return;
+    }
+    lines.end
+  };
+
+  let block = line_texts(document, lines.clone()).join("\n");
+  let other_text = line_texts(document, other..other + 1).remove(0);
+  let swapped = lines.start.min(other)..lines.end.max(other + 1);
+  let start = document.line_to_char(swapped.start);
+  let end = start
+    + line_texts(document, swapped)
+      .iter()
+      .map(|line| line.chars().count() + 1)
+      .sum::<usize>()
+    - 1;
+  let (text, shift) = if up {
+    (
+      format!("{block}\n{other_text}"),
+      -(other_text.chars().count() as isize + 1),
+    )
+  } else {
+    (
+      format!("{other_text}\n{block}"),
+      other_text.chars().count() as isize + 1,
+    )
+  };
+  let moved = |offset: usize| offset.saturating_add_signed(shift);
+  let selection = moved(selection.start)..moved(selection.end);
+  let description = if up { "Move Line Up" } else { "Move Line Down" };
+  replace_lines(editor, start..end, &text, selection, description, cx);
+}
+
+/// Replace `range` with `text` as one undoable edit, selecting `selection`
+/// after
+fn replace_lines(
+  editor: &mut Editor,
+  range: Range<usize>,
+  text: &str,
+  selection: Range<usize>,
+  description: &'static str,
+  cx: &mut Context<Editor>,
+) {
+  let selection_before = editor.selected_range.clone();
+  let start_line = editor.document.read(cx).char_to_line(range.start);
+  let transaction_id = editor.document.update(cx, |doc, cx| {
+    let id = doc.buffer.transaction(Instant::now(), |buffer, tx| {
+      buffer.replace(tx, range.clone(), text);
+    });
+    doc.schedule_recompute_highlights(cx);
+    cx.notify();
+    id
+  });
+
+  editor.selected_range = selection;
+  let selection_after = editor.selected_range.clone();
+  editor.record_transaction(
+    transaction_id,
+    selection_before,
+    selection_after,
+    description,
+    cx,
+  );
+  editor.invalidate_lines_from(start_line);
+  cx.notify();
+}
+
+// === Unicode Safety Actions ===
+
+/// Replace bidi controls and confusable characters with `\u{..}` escapes
+pub fn escape_suspicious_unicode(
+  editor: &mut Editor,
+  _: &EscapeSuspiciousUnicode,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  replace_suspicious_unicode(editor, |ch, _| escape_char(ch), cx);
+}
+
+/// Delete bidi controls and invisible characters, and replace confusables with ASCII
+pub fn remove_suspicious_unicode(
+  editor: &mut Editor,
+  _: &RemoveSuspiciousUnicode,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  replace_suspicious_unicode(
+    editor,
+    |_, warning| warning.replacement().map(String::from).unwrap_or_default(),
+    cx,
+  );
+}
+
+/// Rewrite the characters flagged as suspicious in the selection, or anywhere in
+/// the document without one; the text around them is left as is
+fn replace_suspicious_unicode(
+  editor: &mut Editor,
+  replacement: impl Fn(char, UnicodeWarning) -> String,
+  cx: &mut Context<Editor>,
+) {
+  let selection_before = editor.selected_range.clone();
+  let range = if selection_before.is_empty() {
+    0..editor.document.read(cx).len()
+  } else {
+    selection_before.clone()
+  };
+
+  let mut edits: Vec<(usize, String)> = Vec::new();
+  let document = editor.document.read(cx);
+  let lines = document.char_to_line(range.start)..document.char_to_line(range.end) + 1;
+  for line_idx in lines {
+    let (Some(line), Some(line_range)) = (
+      document.line_content(line_idx),
+      document.line_range(line_idx),
+    ) else {
+      continue;
+    };
+    // Flags depend on the characters around, so whole lines are checked
+    let mut chars = line.char_indices().enumerate();
+    for (offset, warning) in unicode_warnings_in_line(&line) {
+      let Some((index, (_, ch))) = chars.find(|(_, (byte, _))| *byte == offset) else {
+        continue;
+      };
+      let char_offset = line_range.start + index;
+      if range.contains(&char_offset) {
+        edits.push((char_offset, replacement(ch, warning)));
+      }
     }
   }
+  if edits.is_empty() {
+    return;
+  }
+
+  let transaction_id = editor.document.update(cx, |doc, cx| {
+    let id = doc.buffer.transaction(Instant::now(), |buffer, tx| {
+      // Back to front so earlier offsets stay valid
+      for (offset, text) in edits.iter().rev() {
+        buffer.replace(tx, *offset..*offset + 1, text);
+      }
+    });
+    doc.schedule_recompute_highlights(cx);
+    cx.notify();
+    id
+  });
+
+  let map_offset = |offset: usize| {
+    let shift: isize = edits
+      .iter()
+      .take_while(|(edit_offset, _)| *edit_offset < offset)
+      .map(|(_, text)| text.chars().count() as isize - 1)
+      .sum();
+    offset.saturating_add_signed(shift)
+  };
+  editor.selected_range = map_offset(selection_before.start)..map_offset(selection_before.end);
+  let selection_after = editor.selected_range.clone();
+  editor.record_transaction(
+    transaction_id,
+    selection_before,
+    selection_after,
+    "Replace Suspicious Characters",
+    cx,
+  );
+
+  editor.line_layouts.clear();
+  cx.notify();
 }
 
-// === System Actions ===
+// === JSON Actions ===
 
-pub fn show_character_palette(
-  _editor: &mut Editor,
-  _: &ShowCharacterPalette,
+/// Pretty-print the JSON document with two-space indentation
+pub fn format_json(
+  editor: &mut Editor,
+  _: &FormatJson,
   window: &mut Window,
-  _: &mut Context<Editor>,
+  cx: &mut Context<Editor>,
 ) {
-  window.show_character_palette();
+  rewrite_json(editor, json::format, "Format JSON", window, cx);
 }
 
-pub fn quit(_editor: &mut Editor, _: &Quit, _: &mut Window, cx: &mut Context<Editor>) {
-  cx.quit();
+/// Remove the whitespace between the tokens of the JSON document
+pub fn minify_json(
+  editor: &mut Editor,
+  _: &MinifyJson,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  rewrite_json(editor, json::minify, "Minify JSON", window, cx);
+}
+
+fn rewrite_json(
+  editor: &mut Editor,
+  rewrite: fn(&str) -> Result<String, String>,
+  description: &'static str,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  let document = editor.document.read(cx);
+  if !json::is_json(document.file_extension()) {
+    return;
+  }
+  let text = document.text();
+  let new_text = match rewrite(&text) {
+    Ok(new_text) => new_text,
+    Err(err) => {
+      eprintln!("{}", err);
+      return;
+    }
+  };
+  if new_text == text {
+    return;
+  }
+
+  // Replacing only the lines that changed keeps anchors on the others
+  let algorithm = cx
+    .try_global::<DiffAlgorithm>()
+    .copied()
+    .unwrap_or_default();
+  let edits: Vec<_> = diff::line_edits(&text, &new_text, algorithm)
+    .into_iter()
+    .map(|(range, lines)| {
+      let range = document.byte_to_char(range.start)..document.byte_to_char(range.end);
+      (range, lines)
+    })
+    .collect();
+
+  let selection_before = editor.selected_range.clone();
+  let transaction_id = editor.document.update(cx, |doc, cx| {
+    let id = doc.buffer.transaction(Instant::now(), |buffer, tx| {
+      // Back to front, so the ranges before each edit stay valid
+      for (range, lines) in edits.iter().rev() {
+        buffer.replace(tx, range.clone(), lines);
+      }
+    });
+    doc.schedule_recompute_highlights(cx);
+    cx.notify();
+    id
+  });
+
+  // Only whitespace changed, so the cursor stays on its token
+  editor.move_to(
+    json::map_offset(&text, &new_text, editor.cursor_offset()),
+    cx,
+  );
+  let selection_after = editor.selected_range.clone();
+  editor.record_transaction(
+    transaction_id,
+    selection_before,
+    selection_after,
+    description,
+    cx,
+  );
+
+  editor.invalidate_lines_from(0);
+  editor.ensure_cursor_visible(window, cx);
+}
+
+// === Transform Actions ===
+
+/// Base64-encode the selection, or the text at the cursor
+pub fn base64_encode(
+  editor: &mut Editor,
+  _: &Base64Encode,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  transform::transform_selection(editor, Transform::Base64Encode, window, cx);
+}
+
+/// Decode Base64 in the selection, or the text at the cursor
+pub fn base64_decode(
+  editor: &mut Editor,
+  _: &Base64Decode,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  transform::transform_selection(editor, Transform::Base64Decode, window, cx);
+}
+
+/// Percent-encode the selection, or the text at the cursor
+pub fn url_encode(
+  editor: &mut Editor,
+  _: &UrlEncode,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  transform::transform_selection(editor, Transform::UrlEncode, window, cx);
+}
+
+/// Decode percent escapes in the selection, or the text at the cursor
+pub fn url_decode(
+  editor: &mut Editor,
+  _: &UrlDecode,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  transform::transform_selection(editor, Transform::UrlDecode, window, cx);
+}
+
+/// Escape the selection, or the text at the cursor, as JSON string contents
+pub fn json_escape(
+  editor: &mut Editor,
+  _: &JsonEscape,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  transform::transform_selection(editor, Transform::JsonEscape, window, cx);
+}
+
+/// Unescape JSON string contents in the selection, or the text at the cursor
+pub fn json_unescape(
+  editor: &mut Editor,
+  _: &JsonUnescape,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  transform::transform_selection(editor, Transform::JsonUnescape, window, cx);
+}
+
+// === Generator Actions ===
+
+/// Insert a random version 4 UUID
+pub fn insert_uuid(
+  editor: &mut Editor,
+  _: &InsertUuid,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  generators::insert_uuid(editor, cx);
+  editor.ensure_cursor_visible(window, cx);
+}
+
+/// Insert the current time in the global TimestampFormat
+pub fn insert_timestamp(
+  editor: &mut Editor,
+  _: &InsertTimestamp,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  generators::insert_timestamp(editor, cx);
+  editor.ensure_cursor_visible(window, cx);
+}
+
+pub fn insert_lorem_ipsum(
+  editor: &mut Editor,
+  _: &InsertLoremIpsum,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  generators::insert_lorem_ipsum(editor, cx);
+  editor.ensure_cursor_visible(window, cx);
+}
+
+// === View Actions ===
+
+pub fn toggle_coverage_overlay(
+  editor: &mut Editor,
+  _: &ToggleCoverageOverlay,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  editor.toggle_coverage_overlay(cx);
+}
+
+pub fn start_performance_trace(
+  editor: &mut Editor,
+  _: &StartPerformanceTrace,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  editor.start_performance_trace(cx);
+}
+
+pub fn stop_performance_trace(
+  editor: &mut Editor,
+  _: &StopPerformanceTrace,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  match editor.stop_performance_trace(cx) {
+    Ok(Some(path)) => eprintln!("Performance trace written to {}", path.display()),
+    Ok(None) => {}
+    Err(err) => eprintln!("Failed to write performance trace: {}", err),
+  }
+}
+
+pub fn switch_to_alternate_file(
+  editor: &mut Editor,
+  _: &SwitchToAlternateFile,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  alternate::switch(editor, window, cx);
+}
+
+pub fn trust_workspace(
+  editor: &mut Editor,
+  _: &TrustWorkspace,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  trust::trust_workspace(editor, cx);
+}
+
+pub fn describe_character(
+  editor: &mut Editor,
+  _: &DescribeCharacter,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  describe::show(editor, cx);
+}
+
+pub fn insert_unicode(
+  editor: &mut Editor,
+  _: &InsertUnicode,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  unicode_input::open(editor, window, cx);
+}
+
+pub fn zoom_in(editor: &mut Editor, _: &ZoomIn, _window: &mut Window, cx: &mut Context<Editor>) {
+  preview::zoom_in(editor, cx);
+}
+
+pub fn zoom_out(editor: &mut Editor, _: &ZoomOut, _window: &mut Window, cx: &mut Context<Editor>) {
+  preview::zoom_out(editor, cx);
+}
+
+pub fn zoom_to_fit(
+  editor: &mut Editor,
+  _: &ZoomToFit,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  preview::zoom(editor, None, cx);
+}
+
+/// Start debugging the open file, or continue when paused
+pub fn start_debugging(
+  editor: &mut Editor,
+  _: &StartDebugging,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  dap::start(editor, window, cx);
+}
+
+pub fn stop_debugging(
+  editor: &mut Editor,
+  _: &StopDebugging,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  dap::stop(editor, cx);
+}
+
+pub fn step_over(
+  editor: &mut Editor,
+  _: &StepOver,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  dap::resume(editor, "next", cx);
+}
+
+pub fn step_into(
+  editor: &mut Editor,
+  _: &StepInto,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  dap::resume(editor, "stepIn", cx);
+}
+
+pub fn step_out(editor: &mut Editor, _: &StepOut, _window: &mut Window, cx: &mut Context<Editor>) {
+  dap::resume(editor, "stepOut", cx);
+}
+
+pub fn toggle_code_cells(
+  editor: &mut Editor,
+  _: &ToggleCodeCells,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  code_cells::toggle(editor, cx);
+}
+
+/// Run, or run again, the code cell containing the cursor
+pub fn run_cell(editor: &mut Editor, _: &RunCell, window: &mut Window, cx: &mut Context<Editor>) {
+  if trust::is_restricted(editor, cx) {
+    trust::request_trust(editor, window, cx);
+    return;
+  }
+  let line = editor
+    .document
+    .read(cx)
+    .char_to_line(editor.cursor_offset());
+  code_cells::run_cell_at_line(editor, line, cx);
+}
+
+/// Kill the interpreter of the running cell under the cursor
+pub fn stop_cell(
+  editor: &mut Editor,
+  _: &StopCell,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  let line = editor
+    .document
+    .read(cx)
+    .char_to_line(editor.cursor_offset());
+  code_cells::stop_cell_at_line(editor, line, cx);
+}
+
+pub fn clear_cell_outputs(
+  editor: &mut Editor,
+  _: &ClearCellOutputs,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  code_cells::clear_outputs(editor, cx);
+}
+
+/// Label the words on screen; typing a label moves there, with shift selects to it
+pub fn jump_to_word(
+  editor: &mut Editor,
+  _: &JumpToWord,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  jump::start(editor, cx);
+}
+
+/// Switch to the other theme, which then stays when the OS appearance changes
+pub fn toggle_dark_mode(
+  editor: &mut Editor,
+  _: &ToggleDarkMode,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  editor.toggle_dark_mode(cx);
+}
+
+pub fn use_system_theme(
+  editor: &mut Editor,
+  _: &UseSystemTheme,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  appearance::follow_window(editor, window, cx);
+}
+
+/// Dim the code outside the function or item under the cursor
+pub fn toggle_focus_mode(
+  editor: &mut Editor,
+  _: &ToggleFocusMode,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  focus_mode::toggle(editor, cx);
+}
+
+/// Underline the words changed since the last commit on modified lines
+pub fn toggle_word_diff(
+  editor: &mut Editor,
+  _: &ToggleWordDiff,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  word_diff::toggle(editor, cx);
+}
+
+pub fn add_folder_to_workspace(
+  _editor: &mut Editor,
+  _: &AddFolderToWorkspace,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  workspace::add_folders(window, cx);
+}
+
+/// Remove the workspace root holding the current file
+pub fn remove_folder(
+  editor: &mut Editor,
+  _: &RemoveFolder,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  workspace::remove_folder(editor, cx);
+}
+
+/// Move to the next merge conflict region
+pub fn next_conflict(
+  editor: &mut Editor,
+  _: &NextConflict,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  conflicts::go_to_conflict(editor, true, window, cx);
+}
+
+pub fn previous_conflict(
+  editor: &mut Editor,
+  _: &PreviousConflict,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  conflicts::go_to_conflict(editor, false, window, cx);
+}
+
+/// Copy the selected lines prefixed with `file:line`
+pub fn copy_with_line_numbers(
+  editor: &mut Editor,
+  _: &CopyWithLineNumbers,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  permalink::copy_with_line_numbers(editor, cx);
+}
+
+/// Copy a link to the selected lines on the repository's host
+pub fn copy_permalink(
+  editor: &mut Editor,
+  _: &CopyPermalink,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  permalink::copy_permalink(editor, cx);
+}
+
+/// Re-indent code pasted into documents of the current language
+pub fn toggle_format_on_paste(
+  editor: &mut Editor,
+  _: &ToggleFormatOnPaste,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  paste_format::toggle(editor, cx);
+}
+
+pub fn toggle_hex_mode(
+  editor: &mut Editor,
+  _: &ToggleHexMode,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  editor.hex_mode = !editor.hex_mode;
+  // Rows mean something else in the other view, scroll from the top to the cursor
+  editor.scroll_offset_y = 0.0;
+  editor.ensure_cursor_visible(window, cx);
+  cx.notify();
+}
+
+pub fn toggle_soft_wrap(
+  editor: &mut Editor,
+  _: &ToggleSoftWrap,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  editor.set_soft_wrap(!editor.soft_wrap, cx);
+  editor.ensure_cursor_visible(window, cx);
+}
+
+pub fn toggle_elastic_tabstops(
+  editor: &mut Editor,
+  _: &ToggleElasticTabstops,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  editor.set_elastic_tabstops(!editor.elastic_tabstops, cx);
+  editor.ensure_cursor_visible(window, cx);
+}
+
+// === System Actions ===
+
+pub fn show_character_palette(
+  _editor: &mut Editor,
+  _: &ShowCharacterPalette,
+  window: &mut Window,
+  _: &mut Context<Editor>,
+) {
+  window.show_character_palette();
+}
+
+pub fn quit(_editor: &mut Editor, _: &Quit, _: &mut Window, cx: &mut Context<Editor>) {
+  cx.quit();
+}
+
+/// Highlight the document again after highlighting panicked
+pub fn retry_highlighting(
+  editor: &mut Editor,
+  _: &RetryHighlighting,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  editor
+    .document
+    .update(cx, |document, cx| document.retry_highlighting(cx));
+}
+
+/// Open the cheat sheet of the key bindings in effect
+pub fn show_keyboard_shortcuts(
+  editor: &mut Editor,
+  _: &ShowKeyboardShortcuts,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  shortcuts::open(editor, window, cx);
+}
+
+/// Open the palette of the commands plugins registered
+pub fn show_command_palette(
+  editor: &mut Editor,
+  _: &ShowCommandPalette,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  command_palette::open(editor, window, cx);
+}
+
+/// Fold or unfold the region from the bracket at the cursor to its match
+pub fn fold_bracket_region(
+  editor: &mut Editor,
+  _: &FoldBracketRegion,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  folds::toggle_bracket_region(editor, cx);
+}
+
+/// Make movements extend the selection until toggled off, or stop doing so
+pub fn toggle_selection_lock(
+  editor: &mut Editor,
+  _: &ToggleSelectionLock,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  selection_lock::toggle(editor, cx);
+}
+
+pub fn swap_selection_ends(
+  editor: &mut Editor,
+  _: &SwapSelectionEnds,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  motion::flush(editor, window, cx);
+  selection_lock::swap_ends(editor, cx);
+  editor.ensure_cursor_visible(window, cx);
+}
+
+/// Open a new scratch buffer in the language of the open document
+pub fn new_scratch_buffer(
+  editor: &mut Editor,
+  _: &NewScratchBuffer,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  let extension = editor
+    .document
+    .read(cx)
+    .file_extension()
+    .map(str::to_string);
+  if let Err(err) = scratch::new_buffer(editor, extension.as_deref(), cx) {
+    eprintln!("{}", err);
+  }
+}
+
+// === File Actions ===
+
+pub fn open(_editor: &mut Editor, _: &Open, window: &mut Window, cx: &mut Context<Editor>) {
+  files::open(window, cx);
+}
+
+pub fn save(editor: &mut Editor, _: &Save, window: &mut Window, cx: &mut Context<Editor>) {
+  files::save(editor, window, cx);
+}
+
+pub fn save_as(editor: &mut Editor, _: &SaveAs, window: &mut Window, cx: &mut Context<Editor>) {
+  files::save_as(editor, window, cx);
+}
+
+// === Find Actions ===
+
+pub fn find(editor: &mut Editor, _: &Find, window: &mut Window, cx: &mut Context<Editor>) {
+  find::open(editor, false, window, cx);
+}
+
+pub fn replace(editor: &mut Editor, _: &Replace, window: &mut Window, cx: &mut Context<Editor>) {
+  find::open(editor, true, window, cx);
+}
+
+pub fn find_next(editor: &mut Editor, _: &FindNext, window: &mut Window, cx: &mut Context<Editor>) {
+  find::select_next(editor, true, window, cx);
+}
+
+pub fn find_previous(
+  editor: &mut Editor,
+  _: &FindPrevious,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  find::select_next(editor, false, window, cx);
+}
+
+/// Close the topmost transient UI, down to collapsing the selection
+pub fn cancel(editor: &mut Editor, _: &Cancel, window: &mut Window, cx: &mut Context<Editor>) {
+  if !escape::close_topmost(editor, window, cx) {
+    cx.propagate();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::{AppContext, TestAppContext};
+
+  fn markdown_editor(cx: &mut TestAppContext, text: &str) -> gpui::Entity<Editor> {
+    cx.new(|cx| {
+      let doc = cx.new(|cx| Document::new(text, Some("md"), cx));
+      Editor::with_document(doc, cx)
+    })
+  }
+
+  #[gpui::test]
+  fn test_enter_continues_markdown_lists(cx: &mut TestAppContext) {
+    let editor = markdown_editor(cx, "3. three\n- [x] done\n- \ntext");
+    editor.read_with(cx, |editor, cx| {
+      assert_eq!(enter_edit(editor, 8, cx), (8..8, "\n4. ".to_string()));
+      assert_eq!(enter_edit(editor, 19, cx), (19..19, "\n- [ ] ".to_string()));
+      // An empty item loses its marker instead
+      assert_eq!(enter_edit(editor, 22, cx), (20..22, String::new()));
+      // Not at the end of an item, or not an item
+      assert_eq!(enter_edit(editor, 5, cx), (5..5, "\n".to_string()));
+      assert_eq!(enter_edit(editor, 27, cx), (27..27, "\n".to_string()));
+    });
+
+    // Only Markdown documents continue lists
+    let ctx = EditorTestContext::with_text(cx.clone(), "- item");
+    ctx.editor.read_with(&ctx.cx, |editor, cx| {
+      assert_eq!(enter_edit(editor, 6, cx), (6..6, "\n".to_string()));
+    });
+  }
+
+  #[test]
+  fn test_append_clipboard_text() {
+    assert_eq!(append_clipboard_text(None, "one"), "one");
+    assert_eq!(append_clipboard_text(Some(""), "one"), "one");
+    assert_eq!(append_clipboard_text(Some("one"), "two"), "one\ntwo");
+    assert_eq!(append_clipboard_text(Some("one\n"), "two"), "one\ntwo");
+  }
+
+  #[test]
+  fn test_split_clipboard() {
+    let lengths = [4, 0, 3];
+    assert_eq!(
+      split_clipboard("a\nbc\n\nxyz", Some(&lengths), 3),
+      Some(vec!["a\nbc".to_string(), String::new(), "xyz".to_string()])
+    );
+    assert_eq!(split_clipboard("a\nbc\n\nxyz", Some(&lengths), 2), None);
+    assert_eq!(split_clipboard("a\nbc", Some(&lengths), 3), None);
+
+    // Text copied elsewhere goes one line per cursor
+    assert_eq!(
+      split_clipboard("one\r\ntwo\n", None, 2),
+      Some(vec!["one".to_string(), "two".to_string()])
+    );
+    assert_eq!(split_clipboard("one\ntwo", None, 3), None);
+  }
+
+  #[gpui::test]
+  fn test_copy_append_collects_fragments(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "let a = 1;\nlet b = 2;\nlet c = 3;");
+    ctx
+      .cx
+      .write_to_clipboard(ClipboardItem::new_string("// start".to_string()));
+
+    ctx.set_selection(4..5, false);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert_eq!(copy_append_fragment(editor, cx), 4..5);
+    });
+    // Without a selection the whole line is appended
+    ctx.set_cursor(25);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert_eq!(copy_append_fragment(editor, cx), 22..32);
+    });
+
+    assert_eq!(
+      ctx.cx.read_from_clipboard().and_then(|item| item.text()),
+      Some("// start\na\nlet c = 3;".to_string())
+    );
+  }
+
+  #[gpui::test]
+  fn test_line_actions(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "one\ntwo\nthree");
+    ctx.set_cursor(5);
+
+    // The cursor keeps its column on the moved line
+    ctx
+      .editor
+      .update(&mut ctx.cx, |editor, cx| move_lines(editor, true, cx));
+    assert_eq!(ctx.text(), "two\none\nthree");
+    assert_eq!(ctx.cursor_offset(), 1);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      move_lines(editor, false, cx);
+      move_lines(editor, false, cx);
+      // Already the last line
+      move_lines(editor, false, cx);
+    });
+    assert_eq!(ctx.text(), "one\nthree\ntwo");
+    assert_eq!(ctx.cursor_offset(), 11);
+
+    ctx.editor.update(&mut ctx.cx, duplicate_lines);
+    assert_eq!(ctx.text(), "one\nthree\ntwo\ntwo");
+    assert_eq!(ctx.cursor_offset(), 15);
+
+    // Deleting the last line takes the line break before it
+    ctx.editor.update(&mut ctx.cx, delete_lines);
+    assert_eq!(ctx.text(), "one\nthree\ntwo");
+    assert_eq!(ctx.cursor_offset(), 11);
+
+    ctx.set_selection(1..6, false);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      delete_lines(editor, cx);
+      assert_eq!(editor.undo_description(cx), Some("Delete Line"));
+    });
+    assert_eq!(ctx.text(), "two");
+    assert_eq!(ctx.cursor_offset(), 2);
+  }
+
+  #[gpui::test]
+  fn test_escape_suspicious_unicode(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "a\u{202E}b");
+    ctx.set_cursor(3);
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      replace_suspicious_unicode(editor, |ch, _| escape_char(ch), cx);
+    });
+
+    assert_eq!(ctx.text(), "a\\u{202E}b");
+    assert_eq!(ctx.cursor_offset(), 10);
+  }
+
+  #[gpui::test]
+  fn test_remove_suspicious_unicode_in_selection(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "\u{200B}x = \"Ѕеt\"\u{200B}");
+    ctx.set_selection(1..10, false);
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      replace_suspicious_unicode(
+        editor,
+        |_, warning| warning.replacement().map(String::from).unwrap_or_default(),
+        cx,
+      );
+    });
+
+    assert_eq!(ctx.text(), "\u{200B}x = \"Set\"\u{200B}");
+    assert_eq!(ctx.selection(), 1..10);
+  }
+
+  #[gpui::test]
+  fn test_remove_suspicious_unicode_keeps_prose(cx: &mut TestAppContext) {
+    let text = "// Привет, “мир”\nlet nаme = \"Καλημέρα\";\n";
+    let mut ctx = EditorTestContext::with_text(cx.clone(), text);
+    ctx.set_cursor(0);
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      replace_suspicious_unicode(
+        editor,
+        |_, warning| warning.replacement().map(String::from).unwrap_or_default(),
+        cx,
+      );
+    });
+
+    // Only the Cyrillic letter of the identifier is replaced
+    assert_eq!(ctx.text(), "// Привет, “мир”\nlet name = \"Καλημέρα\";\n");
+  }
+
+  #[gpui::test]
+  fn test_reflow_paragraph_in_comment(cx: &mut TestAppContext) {
+    let text = "// one two\r\n// three four\r\nlet x = 1;\r\n";
+    let window = cx.add_window(|_, cx| {
+      let document = cx.new(|cx| Document::new(text, Some("rs"), cx));
+      Editor::with_document(document, cx)
+    });
+    window
+      .update(cx, |editor, window, cx| {
+        editor.wrap_column = 16;
+
+        // Code lines are left alone
+        editor.selected_range = 32..32;
+        reflow_paragraph(editor, &ReflowParagraph, window, cx);
+        assert_eq!(editor.document.read(cx).text(), text);
+
+        editor.selected_range = 3..3;
+        reflow_paragraph(editor, &ReflowParagraph, window, cx);
+        assert_eq!(
+          editor.document.read(cx).text(),
+          "// one two three\r\n// four\r\nlet x = 1;\r\n"
+        );
+      })
+      .unwrap();
+  }
 }
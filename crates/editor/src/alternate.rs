@@ -0,0 +1,218 @@
+//! Switching between paired files: header/source and test/implementation
+//!
+//! Pairing rules are per language. The first candidate that exists is
+//! opened; when none does, the user is offered to create the first one from
+//! a small template.
+
+use std::path::{Path, PathBuf};
+
+use gpui::{Context, PromptLevel, Window};
+
+use crate::editor::Editor;
+
+const C_SOURCE_EXTENSIONS: &[&str] = &["c", "cpp", "cc", "cxx"];
+const C_HEADER_EXTENSIONS: &[&str] = &["h", "hpp", "hh", "hxx"];
+const TS_EXTENSIONS: &[&str] = &["ts", "tsx", "js", "jsx"];
+
+fn file_parts(path: &Path) -> Option<(&Path, &str, &str)> {
+  let dir = path.parent()?;
+  let stem = path.file_stem()?.to_str()?;
+  let ext = path.extension()?.to_str()?;
+  Some((dir, stem, ext))
+}
+
+/// Counterparts of `path`, most likely first
+pub(crate) fn alternate_candidates(path: &Path) -> Vec<PathBuf> {
+  let Some((dir, stem, ext)) = file_parts(path) else {
+    return Vec::new();
+  };
+
+  match ext {
+    "rs" => rust_candidates(dir, stem),
+    ext if C_HEADER_EXTENSIONS.contains(&ext) => C_SOURCE_EXTENSIONS
+      .iter()
+      .map(|source_ext| dir.join(format!("{}.{}", stem, source_ext)))
+      .collect(),
+    ext if C_SOURCE_EXTENSIONS.contains(&ext) => C_HEADER_EXTENSIONS
+      .iter()
+      .map(|header_ext| dir.join(format!("{}.{}", stem, header_ext)))
+      .collect(),
+    ext if TS_EXTENSIONS.contains(&ext) => match stem
+      .strip_suffix(".test")
+      .or_else(|| stem.strip_suffix(".spec"))
+    {
+      Some(impl_stem) => vec![dir.join(format!("{}.{}", impl_stem, ext))],
+      None => vec![
+        dir.join(format!("{}.test.{}", stem, ext)),
+        dir.join(format!("{}.spec.{}", stem, ext)),
+      ],
+    },
+    _ => Vec::new(),
+  }
+}
+
+/// foo.rs <-> foo_test.rs in the same directory, or tests/foo.rs next to src/
+fn rust_candidates(dir: &Path, stem: &str) -> Vec<PathBuf> {
+  if let Some(impl_stem) = stem.strip_suffix("_test") {
+    return vec![dir.join(format!("{}.rs", impl_stem))];
+  }
+  if dir.file_name().is_some_and(|name| name == "tests")
+    && let Some(root) = dir.parent()
+  {
+    return vec![root.join("src").join(format!("{}.rs", stem))];
+  }
+
+  let mut candidates = Vec::new();
+  if dir.file_name().is_some_and(|name| name == "src")
+    && let Some(root) = dir.parent()
+  {
+    candidates.push(root.join("tests").join(format!("{}.rs", stem)));
+  }
+  candidates.push(dir.join(format!("{}_test.rs", stem)));
+  candidates
+}
+
+/// Initial contents of a new counterpart of `source`
+pub(crate) fn template(path: &Path, source: &Path) -> String {
+  let (Some((_, stem, ext)), Some(source_name)) = (
+    file_parts(path),
+    source.file_name().and_then(|name| name.to_str()),
+  ) else {
+    return String::new();
+  };
+
+  match ext {
+    "rs" if stem.ends_with("_test") || path.parent().is_some_and(|dir| dir.ends_with("tests")) => {
+      "#[test]\nfn it_works() {}\n".to_string()
+    }
+    ext if C_HEADER_EXTENSIONS.contains(&ext) => {
+      let guard = format!("{}_{}", stem, ext)
+        .to_ascii_uppercase()
+        .replace(|ch: char| !ch.is_ascii_alphanumeric(), "_");
+      format!("#ifndef {0}\n#define {0}\n\n#endif // {0}\n", guard)
+    }
+    ext if C_SOURCE_EXTENSIONS.contains(&ext) => format!("#include \"{}\"\n", source_name),
+    ext if TS_EXTENSIONS.contains(&ext) && (stem.ends_with(".test") || stem.ends_with(".spec")) => {
+      let module = source_name
+        .rsplit_once('.')
+        .map_or(source_name, |(module, _)| module);
+      format!("import {{}} from \"./{}\";\n", module)
+    }
+    _ => String::new(),
+  }
+}
+
+/// Open the counterpart of the editor's file, or offer to create it
+pub(crate) fn switch(editor: &mut Editor, window: &mut Window, cx: &mut Context<Editor>) {
+  let Some(path) = editor.file_path().map(Path::to_path_buf) else {
+    return;
+  };
+  let candidates = alternate_candidates(&path);
+
+  if let Some(existing) = candidates.iter().find(|candidate| candidate.is_file()) {
+    if let Err(err) = editor.open_file(existing.clone(), cx) {
+      eprintln!("Failed to open {}: {}", existing.display(), err);
+    }
+    return;
+  }
+  let Some(new_path) = candidates.into_iter().next() else {
+    return;
+  };
+
+  let answer = window.prompt(
+    PromptLevel::Info,
+    &format!("Create {}?", new_path.display()),
+    None,
+    &["Create", "Cancel"],
+    cx,
+  );
+  cx.spawn_in(window, async move |this, cx| {
+    if answer.await != Ok(0) {
+      return;
+    }
+    let created = new_path
+      .parent()
+      .map_or(Ok(()), std::fs::create_dir_all)
+      .and_then(|_| std::fs::write(&new_path, template(&new_path, &path)))
+      .map_err(|err| err.to_string());
+    let opened = this.update(cx, |editor, cx| {
+      created.and_then(|_| editor.open_file(new_path.clone(), cx))
+    });
+    if let Ok(Err(err)) = opened {
+      eprintln!("Failed to create {}: {}", new_path.display(), err);
+    }
+  })
+  .detach();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn candidates(path: &str) -> Vec<String> {
+    alternate_candidates(Path::new(path))
+      .into_iter()
+      .map(|path| path.to_string_lossy().into_owned())
+      .collect()
+  }
+
+  #[test]
+  fn test_rust_pairs() {
+    assert_eq!(
+      candidates("crate/src/parser.rs"),
+      ["crate/tests/parser.rs", "crate/src/parser_test.rs"]
+    );
+    assert_eq!(candidates("crate/tests/parser.rs"), ["crate/src/parser.rs"]);
+    assert_eq!(
+      candidates("crate/src/lexer/token_test.rs"),
+      ["crate/src/lexer/token.rs"]
+    );
+    assert_eq!(
+      candidates("crate/src/lexer/token.rs"),
+      ["crate/src/lexer/token_test.rs"]
+    );
+  }
+
+  #[test]
+  fn test_c_and_typescript_pairs() {
+    assert_eq!(candidates("lib/vec.h")[..2], ["lib/vec.c", "lib/vec.cpp"]);
+    assert_eq!(candidates("lib/vec.cpp")[..2], ["lib/vec.h", "lib/vec.hpp"]);
+    assert_eq!(candidates("app/button.test.tsx"), ["app/button.tsx"]);
+    assert_eq!(
+      candidates("app/button.ts"),
+      ["app/button.test.ts", "app/button.spec.ts"]
+    );
+    assert!(candidates("notes.txt").is_empty());
+    assert!(candidates("Makefile").is_empty());
+  }
+
+  #[test]
+  fn test_templates() {
+    assert_eq!(
+      template(Path::new("lib/vec.h"), Path::new("lib/vec.c")),
+      "#ifndef VEC_H\n#define VEC_H\n\n#endif // VEC_H\n"
+    );
+    assert_eq!(
+      template(Path::new("lib/vec.cpp"), Path::new("lib/vec.h")),
+      "#include \"vec.h\"\n"
+    );
+    assert_eq!(
+      template(Path::new("app/button.test.ts"), Path::new("app/button.ts")),
+      "import {} from \"./button\";\n"
+    );
+    assert_eq!(
+      template(
+        Path::new("crate/tests/parser.rs"),
+        Path::new("crate/src/parser.rs")
+      ),
+      "#[test]\nfn it_works() {}\n"
+    );
+    assert_eq!(
+      template(
+        Path::new("crate/src/parser.rs"),
+        Path::new("crate/tests/parser.rs")
+      ),
+      ""
+    );
+  }
+}
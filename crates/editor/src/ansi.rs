@@ -0,0 +1,544 @@
+//! Styled program output and the file references in it
+//!
+//! Output shown by the editor, such as that of code cells, goes through
+//! here: SGR escape sequences become styled runs and other escape sequences
+//! are dropped, long outputs are folded to their first lines, and
+//! `path:line[:column]` references are found so they can be followed into
+//! the editor.
+
+use std::{ops::Range, path::Path};
+
+use gpui::{
+  Context, FontStyle, FontWeight, HighlightStyle, Hsla, Rgba, UnderlineStyle, Window, px, rgb,
+};
+
+use crate::editor::Editor;
+
+/// Colors 0-15: the normal then the bright variants of black, red, green,
+/// yellow, blue, magenta, cyan and white
+const PALETTE: [u32; 16] = [
+  0x000000, 0xcd3131, 0x0dbc79, 0xe5e510, 0x2472c8, 0xbc3fbc, 0x11a8cd, 0xe5e5e5, 0x666666,
+  0xf14c4c, 0x23d18b, 0xf5f543, 0x3b8eea, 0xd670d6, 0x29b8db, 0xffffff,
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AnsiStyle {
+  pub foreground: Option<Hsla>,
+  pub background: Option<Hsla>,
+  pub bold: bool,
+  pub italic: bool,
+  pub underline: bool,
+}
+
+impl AnsiStyle {
+  fn highlight(&self) -> HighlightStyle {
+    HighlightStyle {
+      color: self.foreground,
+      background_color: self.background,
+      font_weight: self.bold.then_some(FontWeight::BOLD),
+      font_style: self.italic.then_some(FontStyle::Italic),
+      underline: self.underline.then(|| UnderlineStyle {
+        thickness: px(1.),
+        color: self.foreground,
+        wavy: false,
+      }),
+      ..Default::default()
+    }
+  }
+
+  /// Apply the parameters of one SGR sequence
+  fn apply_sgr(&mut self, params: &[u16]) {
+    if params.is_empty() {
+      *self = Self::default();
+      return;
+    }
+    let mut params = params.iter().copied();
+    while let Some(param) = params.next() {
+      match param {
+        0 => *self = Self::default(),
+        1 => self.bold = true,
+        3 => self.italic = true,
+        4 => self.underline = true,
+        22 => self.bold = false,
+        23 => self.italic = false,
+        24 => self.underline = false,
+        30..=37 => self.foreground = Some(palette_color(param - 30)),
+        38 => self.foreground = extended_color(&mut params),
+        39 => self.foreground = None,
+        40..=47 => self.background = Some(palette_color(param - 40)),
+        48 => self.background = extended_color(&mut params),
+        49 => self.background = None,
+        90..=97 => self.foreground = Some(palette_color(param - 90 + 8)),
+        100..=107 => self.background = Some(palette_color(param - 100 + 8)),
+        _ => {}
+      }
+    }
+  }
+}
+
+fn palette_color(index: u16) -> Hsla {
+  rgb(PALETTE[index as usize]).into()
+}
+
+/// Color of a `5;n` or `2;r;g;b` tail of a 38 or 48 parameter
+fn extended_color(params: &mut impl Iterator<Item = u16>) -> Option<Hsla> {
+  match params.next()? {
+    5 => {
+      let index = params.next()?;
+      Some(match index {
+        0..=15 => palette_color(index),
+        16..=231 => {
+          let index = index - 16;
+          let level = |value: u16| if value == 0 { 0 } else { 55 + value * 40 };
+          rgb_color(level(index / 36), level(index / 6 % 6), level(index % 6))
+        }
+        _ => {
+          let gray = 8 + (index.min(255) - 232) * 10;
+          rgb_color(gray, gray, gray)
+        }
+      })
+    }
+    2 => {
+      let (r, g, b) = (params.next()?, params.next()?, params.next()?);
+      Some(rgb_color(r, g, b))
+    }
+    _ => None,
+  }
+}
+
+fn rgb_color(r: u16, g: u16, b: u16) -> Hsla {
+  Rgba {
+    r: r.min(255) as f32 / 255.,
+    g: g.min(255) as f32 / 255.,
+    b: b.min(255) as f32 / 255.,
+    a: 1.,
+  }
+  .into()
+}
+
+/// Output text with its escape sequences turned into styled runs
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StyledOutput {
+  pub text: String,
+  /// Byte ranges of `text` that aren't in the default style, in order
+  pub runs: Vec<(Range<usize>, AnsiStyle)>,
+}
+
+impl StyledOutput {
+  /// Parse `input`, keeping SGR styles and dropping other escape sequences
+  ///
+  /// A carriage return not followed by a line feed starts the line over, as
+  /// progress bars expect.
+  pub fn parse(input: &str) -> Self {
+    let mut output = Self::default();
+    let mut style = AnsiStyle::default();
+    let mut chars = input.chars().peekable();
+    while let Some(ch) = chars.next() {
+      match ch {
+        '\x1b' => match chars.next() {
+          // Control sequence: parameters, intermediates, then a final byte
+          Some('[') => {
+            let mut sequence = String::new();
+            for ch in chars.by_ref() {
+              if ('\x40'..='\x7e').contains(&ch) {
+                if ch == 'm' {
+                  let params: Vec<u16> = sequence
+                    .split(';')
+                    .filter(|param| !param.is_empty())
+                    .map(|param| param.parse().unwrap_or(0))
+                    .collect();
+                  style.apply_sgr(&params);
+                }
+                break;
+              }
+              sequence.push(ch);
+            }
+          }
+          // Operating system command, ended by BEL or ESC \
+          Some(']') => {
+            while let Some(ch) = chars.next() {
+              if ch == '\x07' {
+                break;
+              }
+              if ch == '\x1b' {
+                chars.next_if_eq(&'\\');
+                break;
+              }
+            }
+          }
+          _ => {}
+        },
+        '\r' if chars.peek() != Some(&'\n') => {
+          let line_start = output.text.rfind('\n').map_or(0, |ix| ix + 1);
+          output.truncate(line_start);
+        }
+        _ => output.push(ch, style),
+      }
+    }
+    output
+  }
+
+  fn push(&mut self, ch: char, style: AnsiStyle) {
+    let start = self.text.len();
+    self.text.push(ch);
+    if style == AnsiStyle::default() {
+      return;
+    }
+    match self.runs.last_mut() {
+      Some((range, last)) if range.end == start && *last == style => range.end = self.text.len(),
+      _ => self.runs.push((start..self.text.len(), style)),
+    }
+  }
+
+  fn truncate(&mut self, len: usize) {
+    self.text.truncate(len);
+    self.runs.retain_mut(|(range, _)| {
+      range.end = range.end.min(len);
+      range.start < range.end
+    });
+  }
+
+  /// The first `max_lines` lines, and how many lines were left out
+  pub fn folded(&self, max_lines: usize) -> (Self, usize) {
+    let len = fold_lines(&self.text, max_lines);
+    let rest = &self.text[len..];
+    let hidden = rest.strip_prefix('\n').unwrap_or(rest).lines().count();
+    let mut folded = self.clone();
+    if hidden > 0 {
+      folded.truncate(len);
+    }
+    (folded, hidden)
+  }
+
+  /// Highlights of the styled runs, with file references underlined
+  pub(crate) fn highlights(&self, links: &[FileLink]) -> Vec<(Range<usize>, HighlightStyle)> {
+    let mut boundaries: Vec<usize> = self
+      .runs
+      .iter()
+      .map(|(range, _)| range)
+      .chain(links.iter().map(|link| &link.range))
+      .flat_map(|range| [range.start, range.end])
+      .collect();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let mut highlights = Vec::new();
+    for segment in boundaries.windows(2) {
+      let (start, end) = (segment[0], segment[1]);
+      let mut style = self
+        .runs
+        .iter()
+        .find(|(range, _)| range.start <= start && end <= range.end)
+        .map(|(_, style)| *style)
+        .unwrap_or_default();
+      if links
+        .iter()
+        .any(|link| link.range.start <= start && end <= link.range.end)
+      {
+        style.underline = true;
+      }
+      if style != AnsiStyle::default() {
+        highlights.push((start..end, style.highlight()));
+      }
+    }
+    highlights
+  }
+}
+
+/// Byte length of the first `max_lines` lines of `text`, without their
+/// last line break
+fn fold_lines(text: &str, max_lines: usize) -> usize {
+  if max_lines == 0 {
+    return 0;
+  }
+  text
+    .match_indices('\n')
+    .nth(max_lines - 1)
+    .map_or(text.len(), |(ix, _)| ix)
+}
+
+/// A reference to a place in a file, found in program output
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileLink {
+  /// Byte range of the reference in the output
+  pub range: Range<usize>,
+  pub path: String,
+  /// One-based line
+  pub line: usize,
+  /// One-based column, when given
+  pub column: Option<usize>,
+}
+
+/// References like `src/main.rs:12:5` and Python's `File "x.py", line 3`
+pub fn find_file_links(text: &str) -> Vec<FileLink> {
+  let mut links = find_python_links(text);
+  let mut token_start = None;
+  for (ix, ch) in text.char_indices().chain([(text.len(), ' ')]) {
+    if is_path_char(ch) || ch == ':' {
+      token_start.get_or_insert(ix);
+    } else if let Some(start) = token_start.take()
+      && let Some(link) = parse_path_link(&text[start..ix], start)
+      && !links
+        .iter()
+        .any(|other| other.range.contains(&link.range.start))
+    {
+      links.push(link);
+    }
+  }
+  links.sort_by_key(|link| link.range.start);
+  links
+}
+
+fn is_path_char(ch: char) -> bool {
+  ch.is_alphanumeric() || matches!(ch, '/' | '\\' | '.' | '_' | '-' | '~' | '+' | '@')
+}
+
+/// A `path:line[:column]` link in a token of path characters and colons
+fn parse_path_link(token: &str, offset: usize) -> Option<FileLink> {
+  let colon = token
+    .match_indices(':')
+    .map(|(ix, _)| ix)
+    .find(|&ix| token[ix + 1..].starts_with(|ch: char| ch.is_ascii_digit()))?;
+  let path = &token[..colon];
+  if path.is_empty() || !path.contains(['.', '/']) {
+    return None;
+  }
+
+  let number = |text: &str| {
+    let len = text
+      .find(|ch: char| !ch.is_ascii_digit())
+      .unwrap_or(text.len());
+    text[..len].parse::<usize>().ok().map(|value| (value, len))
+  };
+  let mut end = colon + 1;
+  let (line, len) = number(&token[end..])?;
+  end += len;
+  let mut column = None;
+  if let Some(rest) = token[end..].strip_prefix(':')
+    && let Some((value, len)) = number(rest)
+  {
+    column = Some(value);
+    end += 1 + len;
+  }
+  // Ports in URLs are followed by a path
+  if token[end..].starts_with('/') || line == 0 {
+    return None;
+  }
+
+  Some(FileLink {
+    range: offset..offset + end,
+    path: path.to_string(),
+    line,
+    column,
+  })
+}
+
+fn find_python_links(text: &str) -> Vec<FileLink> {
+  const PREFIX: &str = "File \"";
+  const LINE: &str = "\", line ";
+  let mut links = Vec::new();
+  for (ix, _) in text.match_indices(PREFIX) {
+    let path_start = ix + PREFIX.len();
+    let Some(path_len) = text[path_start..].find(['"', '\n']) else {
+      continue;
+    };
+    let path_end = path_start + path_len;
+    let Some(rest) = text[path_end..].strip_prefix(LINE) else {
+      continue;
+    };
+    let digits = rest
+      .find(|ch: char| !ch.is_ascii_digit())
+      .unwrap_or(rest.len());
+    let Ok(line) = rest[..digits].parse() else {
+      continue;
+    };
+    links.push(FileLink {
+      range: ix..path_end + LINE.len() + digits,
+      path: text[path_start..path_end].to_string(),
+      line,
+      column: None,
+    });
+  }
+  links
+}
+
+/// Open the file `link` refers to, relative paths from `base`, and put the
+/// cursor at the line and column
+pub(crate) fn follow_link(
+  editor: &mut Editor,
+  link: &FileLink,
+  base: Option<&Path>,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  let path = match base {
+    Some(base) => base.join(&link.path),
+    None => Path::new(&link.path).to_path_buf(),
+  };
+  if let Err(err) = editor.open_file(path.clone(), cx) {
+    eprintln!("Failed to open {}: {}", path.display(), err);
+    return;
+  }
+  move_to_line(editor, link.line, link.column, window, cx);
+}
+
+/// Put the cursor at a one-based line and column of the document
+pub(crate) fn move_to_line(
+  editor: &mut Editor,
+  line: usize,
+  column: Option<usize>,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  let offset = {
+    let document = editor.document.read(cx);
+    let line = line
+      .saturating_sub(1)
+      .min(document.len_lines().saturating_sub(1));
+    let line_len = document
+      .line_content(line)
+      .map_or(0, |content| content.chars().count());
+    document.line_to_char(line) + column.unwrap_or(1).saturating_sub(1).min(line_len)
+  };
+  editor.move_to(offset, cx);
+  editor.ensure_cursor_visible(window, cx);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn styles(output: &StyledOutput) -> Vec<(&str, AnsiStyle)> {
+    output
+      .runs
+      .iter()
+      .map(|(range, style)| (&output.text[range.clone()], *style))
+      .collect()
+  }
+
+  #[test]
+  fn test_parse_sgr() {
+    let output =
+      StyledOutput::parse("ok \x1b[1;31merror\x1b[0m done \x1b[4;38;5;196mx\x1b[24my\x1b[m");
+    assert_eq!(output.text, "ok error done xy");
+    let red = AnsiStyle {
+      foreground: Some(palette_color(1)),
+      bold: true,
+      ..Default::default()
+    };
+    let bright_red = AnsiStyle {
+      foreground: Some(rgb_color(255, 0, 0)),
+      underline: true,
+      ..Default::default()
+    };
+    assert_eq!(
+      styles(&output),
+      [
+        ("error", red),
+        ("x", bright_red),
+        (
+          "y",
+          AnsiStyle {
+            underline: false,
+            ..bright_red
+          }
+        ),
+      ]
+    );
+
+    let output = StyledOutput::parse("\x1b[38;2;1;2;3;42mrgb\x1b[39mbg\x1b[49m");
+    assert_eq!(output.text, "rgbbg");
+    assert_eq!(
+      styles(&output),
+      [
+        (
+          "rgb",
+          AnsiStyle {
+            foreground: Some(rgb_color(1, 2, 3)),
+            background: Some(palette_color(2)),
+            ..Default::default()
+          }
+        ),
+        (
+          "bg",
+          AnsiStyle {
+            background: Some(palette_color(2)),
+            ..Default::default()
+          }
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_parse_drops_other_sequences() {
+    let output = StyledOutput::parse("\x1b]0;title\x07a\x1b[2Kb\x1b]8;;url\x1b\\c\x1b=d");
+    assert_eq!(output.text, "abcd");
+    assert!(output.runs.is_empty());
+
+    let output = StyledOutput::parse("10%\r\x1b[32m50%\x1b[0m\r100%\r\nend\n");
+    assert_eq!(output.text, "100%\r\nend\n");
+    assert!(output.runs.is_empty());
+  }
+
+  #[test]
+  fn test_folded() {
+    let output = StyledOutput::parse("\x1b[31mone\ntwo\x1b[0m\nthree\nfour\n");
+    let (folded, hidden) = output.folded(2);
+    assert_eq!(folded.text, "one\ntwo");
+    assert_eq!(hidden, 2);
+    assert_eq!(folded.runs.len(), 1);
+    assert_eq!(folded.runs[0].0, 0..7);
+
+    let (folded, hidden) = output.folded(10);
+    assert_eq!(folded, output);
+    assert_eq!(hidden, 0);
+    assert_eq!(fold_lines("a\nb", 0), 0);
+  }
+
+  #[test]
+  fn test_find_file_links() {
+    let text = "error: --> src/main.rs:12:5\nwarning at lib.rs:3: unused\nsee http://example.com:8080/x and 10:30 pm\n";
+    let links = find_file_links(text);
+    let found: Vec<_> = links
+      .iter()
+      .map(|link| {
+        (
+          &text[link.range.clone()],
+          link.path.as_str(),
+          link.line,
+          link.column,
+        )
+      })
+      .collect();
+    assert_eq!(
+      found,
+      [
+        ("src/main.rs:12:5", "src/main.rs", 12, Some(5)),
+        ("lib.rs:3", "lib.rs", 3, None),
+      ]
+    );
+
+    let text = "Traceback (most recent call last):\n  File \"<stdin>\", line 2, in <module>\n  File \"/usr/lib/x.py\", line 10\n";
+    let links = find_file_links(text);
+    assert_eq!(links.len(), 2);
+    assert_eq!(links[0].path, "<stdin>");
+    assert_eq!(links[0].line, 2);
+    assert_eq!(
+      &text[links[1].range.clone()],
+      "File \"/usr/lib/x.py\", line 10"
+    );
+  }
+
+  #[test]
+  fn test_highlights() {
+    let output = StyledOutput::parse("at \x1b[31ma.rs:1 failed\x1b[0m");
+    let links = find_file_links(&output.text);
+    let ranges: Vec<_> = output
+      .highlights(&links)
+      .into_iter()
+      .map(|(range, style)| (range, style.underline.is_some(), style.color.is_some()))
+      .collect();
+    assert_eq!(ranges, [(3..9, true, true), (9..16, false, true)]);
+  }
+}
@@ -0,0 +1,98 @@
+//! Following the light or dark appearance of the OS
+//!
+//! An editor that follows its window switches between the light and dark
+//! themes, with their syntax colors, whenever the window's appearance
+//! changes, until a theme is picked explicitly. UseSystemTheme goes back to
+//! following the window.
+
+use gpui::{Context, Subscription, Window, WindowAppearance};
+use syntax::Theme;
+
+use crate::editor::Editor;
+
+#[derive(Default)]
+pub(crate) struct ThemeSync {
+  /// Set once a theme is picked, which then stays regardless of the OS
+  pinned: bool,
+  appearance_subscription: Option<Subscription>,
+}
+
+pub(crate) fn theme_for_appearance(appearance: WindowAppearance) -> Theme {
+  match appearance {
+    WindowAppearance::Dark | WindowAppearance::VibrantDark => Theme::dark(),
+    WindowAppearance::Light | WindowAppearance::VibrantLight => Theme::light(),
+  }
+}
+
+pub(crate) fn is_pinned(editor: &Editor) -> bool {
+  editor.theme_sync.pinned
+}
+
+/// Match the window's appearance now and whenever it changes
+pub(crate) fn follow_window(editor: &mut Editor, window: &mut Window, cx: &mut Context<Editor>) {
+  editor.theme_sync.pinned = false;
+  if editor.theme_sync.appearance_subscription.is_none() {
+    editor.theme_sync.appearance_subscription =
+      Some(cx.observe_window_appearance(window, |editor, window, cx| {
+        if !is_pinned(editor) {
+          apply_theme(editor, theme_for_appearance(window.appearance()), cx);
+        }
+      }));
+  }
+  apply_theme(editor, theme_for_appearance(window.appearance()), cx);
+}
+
+/// Use `theme` from now on, whatever the OS appearance
+pub(crate) fn pin_theme(editor: &mut Editor, theme: Theme, cx: &mut Context<Editor>) {
+  editor.theme_sync.pinned = true;
+  apply_theme(editor, theme, cx);
+}
+
+fn apply_theme(editor: &mut Editor, theme: Theme, cx: &mut Context<Editor>) {
+  if editor.theme == theme {
+    return;
+  }
+  editor.theme = theme;
+  // Shaped lines carry the syntax colors of the previous theme
+  editor.line_layouts.clear();
+  cx.notify();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[test]
+  fn test_theme_for_appearance() {
+    assert!(theme_for_appearance(WindowAppearance::VibrantDark).is_dark);
+    assert!(!theme_for_appearance(WindowAppearance::Light).is_dark);
+  }
+
+  #[gpui::test]
+  fn test_pin_theme(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "fn main() {}");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert!(!is_pinned(editor));
+      pin_theme(editor, Theme::light(), cx);
+      assert!(is_pinned(editor));
+      assert!(!editor.theme.is_dark);
+    });
+  }
+
+  #[gpui::test]
+  fn test_swap_theme_clears_layouts(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "fn main() {}");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      pin_theme(editor, Theme::dark(), cx);
+      editor.line_layouts.insert(0, Default::default());
+
+      // Same appearance, different syntax colors
+      let custom = Theme::from_toml("[syntax]\nkeyword = \"#ff0000\"\n").unwrap();
+      pin_theme(editor, custom.clone(), cx);
+      assert!(editor.line_layouts.is_empty());
+      assert_eq!(editor.theme, custom);
+    });
+  }
+}
@@ -0,0 +1,163 @@
+//! Auto-closing brackets and quotes
+//!
+//! Typing an opening bracket or a quote before whitespace, a closing bracket
+//! or the end of the document types its closing partner too, with the cursor
+//! between them. Typing a closing character just before the same one steps
+//! over it, and typing an opening one with text selected wraps the selection.
+//! A quote after a letter or digit stays single, as in "don't". Pairs are
+//! inserted as one edit, so one undo takes both characters out.
+
+use std::ops::Range;
+
+use crate::document::Document;
+
+/// Opening and closing characters that pair up
+const PAIRS: [(char, char); 5] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+/// What typing a pair character does instead of inserting it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum AutoPair {
+  /// Move past the closing character after the cursor
+  Skip,
+  /// Replace the selection with `text`, selecting `selection` after
+  Edit {
+    text: String,
+    selection: Range<usize>,
+  },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pairing {
+  Skip,
+  Close(char),
+  Wrap(char),
+}
+
+/// How typing `typed` pairs up between `before` and `after`, or over a
+/// selection
+fn pairing(
+  typed: char,
+  before: Option<char>,
+  after: Option<char>,
+  has_selection: bool,
+) -> Option<Pairing> {
+  let close = PAIRS
+    .iter()
+    .find(|(open, _)| *open == typed)
+    .map(|(_, close)| *close);
+  if has_selection {
+    return close.map(Pairing::Wrap);
+  }
+  if after == Some(typed) && PAIRS.iter().any(|(_, close)| *close == typed) {
+    return Some(Pairing::Skip);
+  }
+  let close = close?;
+  let fits_after = after.is_none_or(|ch| ch.is_whitespace() || ")]}".contains(ch));
+  let is_quote = typed == close;
+  let fits_before = !is_quote || before.is_none_or(|ch| !ch.is_alphanumeric());
+  (fits_after && fits_before).then_some(Pairing::Close(close))
+}
+
+/// What typing `typed` over `range` does instead of inserting it, if it
+/// pairs up
+pub(crate) fn edit_for(document: &Document, range: Range<usize>, typed: &str) -> Option<AutoPair> {
+  let mut chars = typed.chars();
+  let (Some(typed), None) = (chars.next(), chars.next()) else {
+    return None;
+  };
+  let char_at = |offset: usize| {
+    (offset < document.len())
+      .then(|| document.slice_to_string(offset..offset + 1).chars().next())
+      .flatten()
+  };
+  let before = range.start.checked_sub(1).and_then(char_at);
+  let after = char_at(range.end);
+
+  let edit = match pairing(typed, before, after, !range.is_empty())? {
+    Pairing::Skip => AutoPair::Skip,
+    Pairing::Close(close) => AutoPair::Edit {
+      text: format!("{typed}{close}"),
+      selection: range.start + 1..range.start + 1,
+    },
+    Pairing::Wrap(close) => {
+      let selected = document.slice_to_string(range.clone());
+      AutoPair::Edit {
+        text: format!("{typed}{selected}{close}"),
+        selection: range.start + 1..range.end + 1,
+      }
+    }
+  };
+  Some(edit)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{actions, editor::Editor};
+  use gpui::{AppContext, EntityInputHandler, TestAppContext};
+
+  #[test]
+  fn test_pairing() {
+    assert_eq!(pairing('(', None, None, false), Some(Pairing::Close(')')));
+    assert_eq!(
+      pairing('[', Some('a'), Some(')'), false),
+      Some(Pairing::Close(']'))
+    );
+    // Not in front of a word
+    assert_eq!(pairing('(', None, Some('a'), false), None);
+    // A quote after a letter is an apostrophe
+    assert_eq!(pairing('\'', Some('n'), None, false), None);
+    assert_eq!(
+      pairing('"', Some(' '), Some(' '), false),
+      Some(Pairing::Close('"'))
+    );
+    assert_eq!(
+      pairing(')', Some('('), Some(')'), false),
+      Some(Pairing::Skip)
+    );
+    assert_eq!(
+      pairing('"', Some('a'), Some('"'), false),
+      Some(Pairing::Skip)
+    );
+    assert_eq!(pairing(')', None, None, false), None);
+    assert_eq!(
+      pairing('{', Some('a'), Some('b'), true),
+      Some(Pairing::Wrap('}'))
+    );
+    assert_eq!(pairing(')', None, None, true), None);
+  }
+
+  #[gpui::test]
+  fn test_typing_pairs(cx: &mut TestAppContext) {
+    let window = cx.add_window(|_, cx| {
+      let document = cx.new(|cx| Document::new("f x", None, cx));
+      Editor::with_document(document, cx)
+    });
+    window
+      .update(cx, |editor, window, cx| {
+        editor.document.update(cx, |doc, _| {
+          doc.buffer.set_group_interval(std::time::Duration::ZERO)
+        });
+        editor.selected_range = 1..1;
+        editor.replace_text_in_range(None, "(", window, cx);
+        assert_eq!(editor.document.read(cx).text(), "f() x");
+        assert_eq!(editor.selected_range, 2..2);
+        editor.replace_text_in_range(None, ")", window, cx);
+        assert_eq!(editor.document.read(cx).text(), "f() x");
+        assert_eq!(editor.selected_range, 3..3);
+
+        // A selection is wrapped and stays selected
+        editor.selected_range = 4..5;
+        editor.replace_text_in_range(None, "\"", window, cx);
+        assert_eq!(editor.document.read(cx).text(), "f() \"x\"");
+        assert_eq!(editor.selected_range, 5..6);
+
+        // One undo takes out a whole pair
+        actions::undo(editor, &actions::Undo, window, cx);
+        assert_eq!(editor.document.read(cx).text(), "f() x");
+        actions::undo(editor, &actions::Undo, window, cx);
+        assert_eq!(editor.document.read(cx).text(), "f x");
+      })
+      .unwrap();
+  }
+}
@@ -0,0 +1,250 @@
+//! Unbalanced brackets and quotes
+//!
+//! A missing bracket in a config or source file, or one typed before the
+//! closing half, breaks the parse far from where it is. After edits settle,
+//! the document is scanned for the first delimiter without a partner, which
+//! is underlined with a note saying what is wrong with it. Delimiters inside
+//! strings and comments don't count. Single quotes aren't checked, as they
+//! are also apostrophes and Rust lifetimes.
+
+use std::time::Duration;
+
+use gpui::{Context, Task};
+
+use crate::{
+  decorations::{DecorationId, DecorationStyle, VirtualText},
+  editor::Editor,
+  json, large_file, markdown,
+};
+
+/// Delay before checking the document again after an edit
+const REFRESH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Problem {
+  /// A closing bracket without an opening one, or closing another kind
+  Unmatched,
+  /// An opening bracket never closed
+  Unclosed,
+  /// A string still open at the end of its line, or of the document
+  Unterminated,
+}
+
+/// The first delimiter without a partner, at a byte offset
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Unbalanced {
+  pub offset: usize,
+  pub delimiter: char,
+  pub problem: Problem,
+}
+
+impl Unbalanced {
+  fn message(&self) -> String {
+    match self.problem {
+      Problem::Unmatched => format!("Unmatched `{}`", self.delimiter),
+      Problem::Unclosed => format!("Unclosed `{}`", self.delimiter),
+      Problem::Unterminated => "Unterminated string".to_string(),
+    }
+  }
+}
+
+#[derive(Default)]
+pub(crate) struct BracketBalance {
+  decoration: Option<DecorationId>,
+  // Document version the check ran on
+  checked_version: Option<usize>,
+  refresh_task: Option<Task<()>>,
+}
+
+fn closing(open: char) -> char {
+  match open {
+    '(' => ')',
+    '[' => ']',
+    _ => '}',
+  }
+}
+
+/// The first unbalanced delimiter of `text`. A stray closing bracket is
+/// reported where it is found; brackets left open at the end are reported
+/// from the outermost.
+pub(crate) fn first_unbalanced(text: &str) -> Option<Unbalanced> {
+  let mut open: Vec<(usize, char)> = Vec::new();
+  let mut chars = text.char_indices().peekable();
+  while let Some((offset, ch)) = chars.next() {
+    match ch {
+      '(' | '[' | '{' => open.push((offset, ch)),
+      ')' | ']' | '}' => match open.pop() {
+        Some((_, opening)) if closing(opening) == ch => {}
+        _ => {
+          return Some(Unbalanced {
+            offset,
+            delimiter: ch,
+            problem: Problem::Unmatched,
+          });
+        }
+      },
+      '"' | '`' => {
+        // Double quoted strings end with their line, template strings don't
+        let mut terminated = false;
+        while let Some((_, next)) = chars.next() {
+          match next {
+            '\\' => {
+              chars.next();
+            }
+            '\n' if ch == '"' => break,
+            _ if next == ch => {
+              terminated = true;
+              break;
+            }
+            _ => {}
+          }
+        }
+        if !terminated {
+          return Some(Unbalanced {
+            offset,
+            delimiter: ch,
+            problem: Problem::Unterminated,
+          });
+        }
+      }
+      '/' if chars.peek().is_some_and(|(_, next)| *next == '/') => {
+        while chars.next_if(|(_, next)| *next != '\n').is_some() {}
+      }
+      '/' if chars.peek().is_some_and(|(_, next)| *next == '*') => {
+        chars.next();
+        let mut previous = '\0';
+        for (_, next) in chars.by_ref() {
+          if previous == '*' && next == '/' {
+            break;
+          }
+          previous = next;
+        }
+      }
+      _ => {}
+    }
+  }
+  open.first().map(|&(offset, delimiter)| Unbalanced {
+    offset,
+    delimiter,
+    problem: Problem::Unclosed,
+  })
+}
+
+/// Prose has quotes and brackets that aren't code, and JSON documents get
+/// the parser's own error instead
+fn is_checked(file_ext: Option<&str>) -> bool {
+  !markdown::is_markdown(file_ext)
+    && !json::is_json(file_ext)
+    && !matches!(file_ext, None | Some("txt" | "text"))
+}
+
+/// Forget the result for the previous document
+pub(crate) fn clear(editor: &mut Editor) {
+  editor.bracket_balance = BracketBalance::default();
+}
+
+/// Check the document again once edits have settled
+pub(crate) fn schedule_refresh(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let document = editor.document.read(cx);
+  let version = document.version();
+  if editor.bracket_balance.checked_version == Some(version) {
+    return;
+  }
+  if !is_checked(document.file_extension()) || large_file::is_degraded(editor) {
+    return;
+  }
+
+  let snapshot = document.snapshot();
+  editor.bracket_balance.refresh_task = Some(cx.spawn(async move |this, cx| {
+    cx.background_executor().timer(REFRESH_DEBOUNCE).await;
+
+    let unbalanced = cx
+      .background_executor()
+      .spawn(async move { first_unbalanced(&snapshot.text()) })
+      .await;
+
+    let _ = this.update(cx, |editor, cx| {
+      // Byte offsets are only valid for the text they were computed from
+      if editor.document.read(cx).version() == version {
+        apply(editor, unbalanced, version, cx);
+      }
+    });
+  }));
+}
+
+fn apply(
+  editor: &mut Editor,
+  unbalanced: Option<Unbalanced>,
+  version: usize,
+  cx: &mut Context<Editor>,
+) {
+  if let Some(id) = editor.bracket_balance.decoration.take() {
+    editor.decorations.remove(id);
+  }
+  if let Some(unbalanced) = unbalanced {
+    let document = editor.document.read(cx);
+    let start = document.byte_to_char(unbalanced.offset);
+    let range = document.anchor_range(start..start + 1);
+    let color = editor.theme.warning();
+    let style = DecorationStyle {
+      underline: Some(color),
+      virtual_text: Some(VirtualText {
+        text: unbalanced.message().into(),
+        color,
+      }),
+      ..Default::default()
+    };
+    editor.bracket_balance.decoration = Some(editor.decorations.insert(range, style));
+  }
+  editor.bracket_balance.checked_version = Some(version);
+  cx.notify();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  fn unbalanced(text: &str) -> Option<(usize, char, Problem)> {
+    first_unbalanced(text).map(|found| (found.offset, found.delimiter, found.problem))
+  }
+
+  #[test]
+  fn test_balanced() {
+    assert_eq!(unbalanced(r#"{"a": [1, (2)], "b": "}\"]"}"#), None);
+    assert_eq!(unbalanced("f(x) // )\n/* ] */ g[0]"), None);
+    assert_eq!(unbalanced("let s = `a\n(b`;"), None);
+  }
+
+  #[test]
+  fn test_first_unbalanced() {
+    assert_eq!(
+      unbalanced("{\"a\": [1, 2}"),
+      Some((11, '}', Problem::Unmatched))
+    );
+    assert_eq!(unbalanced("a)"), Some((1, ')', Problem::Unmatched)));
+    // The outermost of the brackets left open
+    assert_eq!(unbalanced("{ [ ("), Some((0, '{', Problem::Unclosed)));
+    assert_eq!(
+      unbalanced("{\"a\": \"b\n}"),
+      Some((6, '"', Problem::Unterminated))
+    );
+  }
+
+  #[gpui::test]
+  fn test_apply_marks_delimiter(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "[1, 2");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      let version = editor.document.read(cx).version();
+      apply(editor, first_unbalanced("[1, 2"), version, cx);
+      let document = editor.document.read(cx);
+      let marked = editor.decorations.resolve_in_lines(0..1, document);
+      assert_eq!(marked.len(), 1);
+      assert_eq!(marked[0].range, 0..1);
+
+      apply(editor, None, version, cx);
+      assert!(editor.decorations.is_empty());
+    });
+  }
+}
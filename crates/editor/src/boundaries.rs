@@ -26,6 +26,17 @@ pub fn next_boundary(editor: &Editor, offset: usize, cx: &Context<Editor>) -> us
   (offset + 1).min(doc_len)
 }
 
+/// Byte offsets where the words and punctuation tokens of `text` start, and
+/// its end; the segmentation word movement and selection use
+pub fn word_bounds(text: &str) -> Vec<usize> {
+  let mut bounds: Vec<usize> = text
+    .split_word_bound_indices()
+    .map(|(idx, _)| idx)
+    .collect();
+  bounds.push(text.len());
+  bounds
+}
+
 /// Move to the previous word boundary (start of current or previous word/token)
 /// This includes punctuation as separate tokens
 pub fn previous_word_boundary(editor: &Editor, offset: usize, cx: &Context<Editor>) -> usize {
@@ -219,6 +230,33 @@ pub fn line_range_at_offset(
   }
 }
 
+/// Where Home goes from `offset`: the first non-blank character of its line,
+/// or the line start when already on that character
+pub fn smart_home(editor: &Editor, offset: usize, cx: &Context<Editor>) -> usize {
+  let doc = editor.document.read(cx);
+  let line = doc.char_to_line(offset.min(doc.len()));
+  let line_start = doc.line_to_char(line);
+  let indent = doc
+    .line_content(line)
+    .unwrap_or_default()
+    .chars()
+    .take_while(|ch| ch.is_whitespace())
+    .count();
+  if offset == line_start + indent {
+    line_start
+  } else {
+    line_start + indent
+  }
+}
+
+/// End of the line at `offset`, before its line break
+pub fn line_end(editor: &Editor, offset: usize, cx: &Context<Editor>) -> usize {
+  let doc = editor.document.read(cx);
+  let line = doc.char_to_line(offset.min(doc.len()));
+  let line_len = doc.line_content(line).unwrap_or_default().chars().count();
+  doc.line_to_char(line) + line_len
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -594,6 +632,12 @@ mod tests {
     assert_eq!((start, end), (6, 6));
   }
 
+  #[test]
+  fn test_word_bounds() {
+    assert_eq!(word_bounds("RGB(u8, x)"), [0, 3, 4, 6, 7, 8, 9, 10]);
+    assert_eq!(word_bounds(""), [0]);
+  }
+
   #[gpui::test]
   fn test_line_range_at_offset_simple(cx: &mut TestAppContext) {
     let mut ctx = EditorTestContext::with_text(cx.clone(), "line1\nline2\nline3");
@@ -636,4 +680,28 @@ mod tests {
     });
     assert_eq!((start, end), (0, 11));
   }
+
+  #[gpui::test]
+  fn test_smart_home_and_line_end(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "a\n    let x;\n");
+
+    let (home, again, from_indent, end) = ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      let home = smart_home(editor, 10, cx);
+      (
+        home,
+        smart_home(editor, home, cx),
+        smart_home(editor, 3, cx),
+        line_end(editor, 3, cx),
+      )
+    });
+    // First to the text, then to the line start, and back to the text
+    assert_eq!((home, again, from_indent), (6, 2, 6));
+    assert_eq!(end, 12);
+
+    // The last, empty line
+    let (home, end) = ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      (smart_home(editor, 13, cx), line_end(editor, 13, cx))
+    });
+    assert_eq!((home, end), (13, 13));
+  }
 }
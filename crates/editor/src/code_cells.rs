@@ -0,0 +1,837 @@
+//! Running code cells and showing their output inline
+//!
+//! In code cells mode, a document is split into cells at `# %%` or `// %%`
+//! marker lines; in Markdown every fenced block tagged with a language is a
+//! cell. Each cell gets a run button in the gutter. Running a cell feeds its
+//! code to the interpreter for its language, and the output is shown after
+//! the cell's last line until the cell is run again or outputs are cleared.
+//! A running interpreter is killed when the cell is stopped, run again or
+//! cleared, and only the first `MAX_OUTPUT_BYTES` of its output are kept.
+//! The full output of the cell under the cursor is shown beside the text,
+//! folded when long, with its file references linked.
+
+use std::{
+  collections::HashMap,
+  io::{self, Read, Write},
+  ops::Range,
+  process::{Child, Command, Stdio},
+  sync::Arc,
+  thread,
+  time::Duration,
+};
+
+use buffer::Anchor;
+use gpui::{
+  App, ClickEvent, Context, Global, Hsla, InteractiveText, SharedString, StyledText, Task, div,
+  green, prelude::*, px, red, yellow,
+};
+use parking_lot::Mutex;
+
+use crate::{
+  ansi::{self, StyledOutput},
+  decorations::{DecorationId, DecorationStyle, GutterIcon, VirtualText},
+  editor::Editor,
+  trust,
+};
+
+/// Delay before re-scanning the document for cells after an edit
+const REFRESH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Prefixes of the lines that start a cell, after indentation
+const CELL_MARKERS: &[&str] = &["# %%", "// %%"];
+
+/// Characters of output shown after a cell
+const MAX_OUTPUT_SUMMARY: usize = 120;
+
+/// Lines of a long output shown in the output panel until it is unfolded
+const FOLDED_OUTPUT_LINES: usize = 12;
+
+const OUTPUT_PANEL_WIDTH: f32 = 320.0;
+
+/// Bytes of each of stdout and stderr kept from a run; the rest is skipped
+const MAX_OUTPUT_BYTES: usize = 1 << 20;
+
+/// How often a run whose output has closed checks whether it exited
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// File name Python gives code read from stdin in tracebacks
+const STDIN_FILE: &str = "<stdin>";
+
+/// Program a cell's code is piped into
+#[derive(Clone, Debug)]
+pub struct Interpreter {
+  pub command: String,
+  pub args: Vec<String>,
+}
+
+/// Interpreters by language, as named in fenced code blocks or by extension
+pub struct Interpreters {
+  by_language: HashMap<String, Interpreter>,
+}
+
+impl Global for Interpreters {}
+
+impl Default for Interpreters {
+  fn default() -> Self {
+    let mut interpreters = Self {
+      by_language: HashMap::new(),
+    };
+    let python = Interpreter {
+      command: "python3".to_string(),
+      args: vec!["-".to_string()],
+    };
+    let node = Interpreter {
+      command: "node".to_string(),
+      args: vec!["-".to_string()],
+    };
+    for language in ["python", "py"] {
+      interpreters.set(language, python.clone());
+    }
+    for language in ["javascript", "js", "node"] {
+      interpreters.set(language, node.clone());
+    }
+    interpreters
+  }
+}
+
+impl Interpreters {
+  pub fn get(&self, language: &str) -> Option<&Interpreter> {
+    self.by_language.get(&language.to_ascii_lowercase())
+  }
+
+  pub fn set(&mut self, language: impl Into<String>, interpreter: Interpreter) {
+    self
+      .by_language
+      .insert(language.into().to_ascii_lowercase(), interpreter);
+  }
+}
+
+/// A cell found in a document's text
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct FoundCell {
+  /// Byte range from the start of the marker line to the end of the code
+  pub range: Range<usize>,
+  /// Byte range of the code
+  pub code: Range<usize>,
+  pub language: String,
+}
+
+/// Cells of `text`; `extension` gives the language of marker cells
+pub(crate) fn find_cells(text: &str, extension: Option<&str>) -> Vec<FoundCell> {
+  let mut lines = Vec::new();
+  let mut offset = 0;
+  for line in text.split_inclusive('\n') {
+    lines.push((offset, line));
+    offset += line.len();
+  }
+
+  if matches!(extension, Some("md" | "markdown")) {
+    find_fenced_cells(&lines)
+  } else {
+    extension.map_or_else(Vec::new, |language| {
+      find_marker_cells(&lines, text.len(), language)
+    })
+  }
+}
+
+fn find_marker_cells(lines: &[(usize, &str)], text_len: usize, language: &str) -> Vec<FoundCell> {
+  let markers: Vec<(usize, usize)> = lines
+    .iter()
+    .filter(|(_, line)| {
+      let line = line.trim_start();
+      CELL_MARKERS.iter().any(|marker| line.starts_with(marker))
+    })
+    .map(|(start, line)| (*start, start + line.len()))
+    .collect();
+
+  let mut cells = Vec::new();
+  for (ix, &(start, code_start)) in markers.iter().enumerate() {
+    let end = markers.get(ix + 1).map_or(text_len, |(next, _)| *next);
+    // Blank lines before the next marker are not part of the cell
+    let code_end = lines
+      .iter()
+      .rev()
+      .find(|(offset, line)| (code_start..end).contains(offset) && !line.trim().is_empty())
+      .map_or(code_start, |(offset, line)| offset + line.len());
+    cells.push(FoundCell {
+      range: start..code_end,
+      code: code_start..code_end,
+      language: language.to_string(),
+    });
+  }
+  cells
+}
+
+fn find_fenced_cells(lines: &[(usize, &str)]) -> Vec<FoundCell> {
+  let mut cells = Vec::new();
+  let mut open: Option<(usize, usize, String)> = None;
+  for &(offset, line) in lines {
+    let trimmed = line.trim();
+    if !trimmed.starts_with("```") {
+      continue;
+    }
+    match open.take() {
+      Some((start, code_start, language)) => {
+        if !language.is_empty() {
+          cells.push(FoundCell {
+            range: start..offset,
+            code: code_start..offset,
+            language,
+          });
+        }
+      }
+      None => {
+        let language = trimmed
+          .trim_start_matches('`')
+          .split_whitespace()
+          .next()
+          .unwrap_or_default()
+          .to_string();
+        open = Some((offset, offset + line.len(), language));
+      }
+    }
+  }
+  cells
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CellStatus {
+  Idle,
+  Running,
+  Succeeded,
+  Failed,
+}
+
+struct Cell {
+  range: Range<Anchor>,
+  code: Range<Anchor>,
+  language: String,
+  status: CellStatus,
+  output: Option<String>,
+  /// Whether the whole output is shown in the output panel
+  expanded: bool,
+  decoration: DecorationId,
+  task: Option<Task<()>>,
+  process: Option<RunningProcess>,
+}
+
+/// Interpreter of a running cell, killed when dropped
+struct RunningProcess(Arc<Mutex<Child>>);
+
+impl Drop for RunningProcess {
+  fn drop(&mut self) {
+    // Killing a child that already exited and was waited for does nothing
+    let _ = self.0.lock().kill();
+  }
+}
+
+#[derive(Default)]
+pub(crate) struct CodeCells {
+  enabled: bool,
+  cells: Vec<Cell>,
+  // Document version the cells were found in
+  scanned_version: Option<usize>,
+  refresh_task: Option<Task<()>>,
+}
+
+pub(crate) fn is_enabled(editor: &Editor) -> bool {
+  editor.code_cells.enabled
+}
+
+pub(crate) fn toggle(editor: &mut Editor, cx: &mut Context<Editor>) {
+  editor.code_cells.enabled = !editor.code_cells.enabled;
+  if editor.code_cells.enabled {
+    editor.code_cells.scanned_version = None;
+    schedule_refresh(editor, cx);
+  } else {
+    editor.code_cells.refresh_task = None;
+    apply_cells(editor, Vec::new(), None, cx);
+  }
+}
+
+/// Forget the cells of the previous document, staying in code cells mode
+pub(crate) fn clear(editor: &mut Editor) {
+  editor.code_cells.cells.clear();
+  editor.code_cells.scanned_version = None;
+  editor.code_cells.refresh_task = None;
+}
+
+/// Re-scan the document for cells once edits have settled
+pub(crate) fn schedule_refresh(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let document = editor.document.read(cx);
+  let version = document.version();
+  if !editor.code_cells.enabled || editor.code_cells.scanned_version == Some(version) {
+    return;
+  }
+
+  let snapshot = document.snapshot();
+  editor.code_cells.refresh_task = Some(cx.spawn(async move |this, cx| {
+    cx.background_executor().timer(REFRESH_DEBOUNCE).await;
+
+    let cells = cx
+      .background_executor()
+      .spawn(async move { find_cells(&snapshot.text(), snapshot.file_extension()) })
+      .await;
+
+    let _ = this.update(cx, |editor, cx| {
+      // Byte offsets are only valid for the text they were computed from
+      if editor.document.read(cx).version() == version {
+        apply_cells(editor, cells, Some(version), cx);
+      }
+    });
+  }));
+}
+
+fn apply_cells(
+  editor: &mut Editor,
+  found: Vec<FoundCell>,
+  version: Option<usize>,
+  cx: &mut Context<Editor>,
+) {
+  let mut previous = std::mem::take(&mut editor.code_cells.cells);
+  for cell in &previous {
+    editor.decorations.remove(cell.decoration);
+  }
+  let idle_color = editor.theme.line_number();
+
+  let mut cells = Vec::with_capacity(found.len());
+  for found in found {
+    let (range, code, start) = {
+      let document = editor.document.read(cx);
+      let start = document.byte_to_char(found.range.start);
+      let end = document.byte_to_char(found.range.end);
+      let code_start = document.byte_to_char(found.code.start);
+      (
+        document.anchor_range(start..end),
+        document.anchor_range(code_start..end),
+        start,
+      )
+    };
+
+    // A cell that still starts where it did keeps its output and running task,
+    // while the interpreters of cells that are gone are killed with them
+    let kept = previous
+      .iter()
+      .position(|cell| editor.document.read(cx).resolve_anchor(&cell.range.start) == start)
+      .map(|ix| previous.swap_remove(ix));
+    let (status, output, expanded, run, process) =
+      kept.map_or((CellStatus::Idle, None, false, None, None), |mut cell| {
+        (
+          cell.status,
+          cell.output.take(),
+          cell.expanded,
+          cell.task.take(),
+          cell.process.take(),
+        )
+      });
+
+    let decoration = editor.decorations.insert(
+      range.clone(),
+      style_for_cell(status, output.as_deref(), idle_color),
+    );
+    cells.push(Cell {
+      range,
+      code,
+      language: found.language,
+      status,
+      output,
+      expanded,
+      decoration,
+      task: run,
+      process,
+    });
+  }
+
+  editor.code_cells.cells = cells;
+  editor.code_cells.scanned_version = version;
+  cx.notify();
+}
+
+fn cell_at_line(editor: &Editor, line: usize, cx: &App) -> Option<usize> {
+  let document = editor.document.read(cx);
+  editor.code_cells.cells.iter().position(|cell| {
+    // Cells end after a line break, which doesn't make the next line theirs
+    let range = document.resolve_anchor_range(&cell.range);
+    let last = document.char_to_line(range.end.saturating_sub(1).max(range.start));
+    (document.char_to_line(range.start)..=last).contains(&line)
+  })
+}
+
+fn cell_starting_at(editor: &Editor, start: &Anchor, cx: &App) -> Option<usize> {
+  let document = editor.document.read(cx);
+  let start = document.resolve_anchor(start);
+  editor
+    .code_cells
+    .cells
+    .iter()
+    .position(|cell| document.resolve_anchor(&cell.range.start) == start)
+}
+
+/// Whether a cell starts on `line`, where its run button is
+pub(crate) fn has_cell_at_line(editor: &Editor, line: usize, cx: &App) -> bool {
+  let document = editor.document.read(cx);
+  editor
+    .code_cells
+    .cells
+    .iter()
+    .any(|cell| document.char_to_line(document.resolve_anchor(&cell.range.start)) == line)
+}
+
+/// Run the cell containing `line`; returns false if there is none
+///
+/// Nothing runs while the workspace is restricted.
+pub(crate) fn run_cell_at_line(editor: &mut Editor, line: usize, cx: &mut Context<Editor>) -> bool {
+  if trust::is_restricted(editor, cx) {
+    return false;
+  }
+  let Some(ix) = cell_at_line(editor, line, cx) else {
+    return false;
+  };
+
+  let cell = &editor.code_cells.cells[ix];
+  let interpreter = cx
+    .default_global::<Interpreters>()
+    .get(&cell.language)
+    .cloned();
+  let Some(interpreter) = interpreter else {
+    let message = format!("No interpreter for {}", cell.language);
+    set_result(editor, ix, CellStatus::Failed, Some(message), cx);
+    return true;
+  };
+  let code = {
+    let document = editor.document.read(cx);
+    let range = document.resolve_anchor_range(&cell.code);
+    document.slice_to_string(range)
+  };
+  let start = cell.range.start;
+  let workspace = trust::workspace(editor, cx);
+
+  // Any previous run of the cell is killed before the new one starts
+  let cell = &mut editor.code_cells.cells[ix];
+  cell.task = None;
+  cell.process = None;
+  set_result(editor, ix, CellStatus::Running, None, cx);
+  let child = match spawn(&interpreter, workspace) {
+    Ok(child) => Arc::new(Mutex::new(child)),
+    Err(message) => {
+      set_result(editor, ix, CellStatus::Failed, Some(message), cx);
+      return true;
+    }
+  };
+
+  let running = child.clone();
+  let task = cx.spawn(async move |this, cx| {
+    let result = cx
+      .background_executor()
+      .spawn(async move { collect(&running, code) })
+      .await;
+    let _ = this.update(cx, |editor, cx| {
+      // The cell may have moved or been re-found since it started running
+      if let Some(ix) = cell_starting_at(editor, &start, cx) {
+        let (status, output) = match result {
+          Ok(output) => (CellStatus::Succeeded, output),
+          Err(output) => (CellStatus::Failed, output),
+        };
+        set_result(editor, ix, status, Some(output), cx);
+      }
+    });
+  });
+
+  let cell = &mut editor.code_cells.cells[ix];
+  cell.task = Some(task);
+  cell.process = Some(RunningProcess(child));
+  true
+}
+
+/// Stop the running cell containing `line`; returns false if there is none
+pub(crate) fn stop_cell_at_line(
+  editor: &mut Editor,
+  line: usize,
+  cx: &mut Context<Editor>,
+) -> bool {
+  let Some(ix) = cell_at_line(editor, line, cx) else {
+    return false;
+  };
+  if editor.code_cells.cells[ix].status != CellStatus::Running {
+    return false;
+  }
+  set_result(
+    editor,
+    ix,
+    CellStatus::Failed,
+    Some("Stopped".to_string()),
+    cx,
+  );
+  true
+}
+
+/// Whether the cell starting on `line` is running
+pub(crate) fn is_running_at_line(editor: &Editor, line: usize, cx: &App) -> bool {
+  let document = editor.document.read(cx);
+  editor.code_cells.cells.iter().any(|cell| {
+    cell.status == CellStatus::Running
+      && document.char_to_line(document.resolve_anchor(&cell.range.start)) == line
+  })
+}
+
+/// Start the interpreter with its stdio piped
+fn spawn(
+  interpreter: &Interpreter,
+  workspace: Option<std::path::PathBuf>,
+) -> Result<Child, String> {
+  let mut command = Command::new(&interpreter.command);
+  command
+    .args(&interpreter.args)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped());
+  if let Some(workspace) = workspace {
+    command.current_dir(workspace);
+  }
+  command
+    .spawn()
+    .map_err(|err| format!("{}: {}", interpreter.command, err))
+}
+
+/// Output of feeding `code` to the child, as an error if the interpreter failed
+///
+/// The child is only locked between reads so that it can be killed meanwhile.
+fn collect(child: &Mutex<Child>, code: String) -> Result<String, String> {
+  let (stdin, stdout, stderr) = {
+    let mut child = child.lock();
+    (child.stdin.take(), child.stdout.take(), child.stderr.take())
+  };
+  // Writing while reading keeps a child that answers before it has read all
+  // its input from filling its output pipe and waiting on us forever
+  if let Some(mut stdin) = stdin {
+    thread::spawn(move || stdin.write_all(code.as_bytes()));
+  }
+  let stderr = stderr.map(|stderr| thread::spawn(move || read_capped(stderr, MAX_OUTPUT_BYTES)));
+  let mut text = match stdout {
+    Some(stdout) => read_capped(stdout, MAX_OUTPUT_BYTES),
+    None => String::new(),
+  };
+  if let Some(stderr) = stderr {
+    text.push_str(&stderr.join().unwrap_or_default());
+  }
+
+  let status = loop {
+    if let Some(status) = child.lock().try_wait().map_err(|err| err.to_string())? {
+      break status;
+    }
+    thread::sleep(EXIT_POLL_INTERVAL);
+  };
+  if status.success() {
+    Ok(text)
+  } else {
+    Err(text)
+  }
+}
+
+/// Text read from `reader` until it closes, keeping only the first `limit` bytes
+fn read_capped(reader: impl Read, limit: usize) -> String {
+  let mut reader = reader.take(limit as u64);
+  let mut bytes = Vec::new();
+  let _ = reader.read_to_end(&mut bytes);
+  // Keep draining so that the child doesn't block on a full pipe
+  let skipped = io::copy(&mut reader.into_inner(), &mut io::sink()).unwrap_or(0);
+  let mut text = String::from_utf8_lossy(&bytes).into_owned();
+  if skipped > 0 {
+    if !text.ends_with('\n') {
+      text.push('\n');
+    }
+    text.push_str(&format!("… {skipped} more bytes not shown\n"));
+  }
+  text
+}
+
+/// Remove the outputs of all cells, stopping those still running
+pub(crate) fn clear_outputs(editor: &mut Editor, cx: &mut Context<Editor>) {
+  for ix in 0..editor.code_cells.cells.len() {
+    set_result(editor, ix, CellStatus::Idle, None, cx);
+  }
+}
+
+fn set_result(
+  editor: &mut Editor,
+  ix: usize,
+  status: CellStatus,
+  output: Option<String>,
+  cx: &mut Context<Editor>,
+) {
+  let idle_color = editor.theme.line_number();
+  let cell = &mut editor.code_cells.cells[ix];
+  editor.decorations.remove(cell.decoration);
+  cell.status = status;
+  cell.output = output;
+  cell.expanded = false;
+  if status != CellStatus::Running {
+    cell.task = None;
+    cell.process = None;
+  }
+  cell.decoration = editor.decorations.insert(
+    cell.range.clone(),
+    style_for_cell(status, cell.output.as_deref(), idle_color),
+  );
+  cx.notify();
+}
+
+/// Output on one line: its first non-empty line and how many more there are
+pub(crate) fn summarize_output(output: &str) -> String {
+  let output = StyledOutput::parse(output).text;
+  let lines: Vec<&str> = output
+    .lines()
+    .map(str::trim_end)
+    .filter(|line| !line.is_empty())
+    .collect();
+  let Some(first) = lines.first() else {
+    return "(no output)".to_string();
+  };
+  let mut summary: String = first.chars().take(MAX_OUTPUT_SUMMARY).collect();
+  if summary.len() < first.len() {
+    summary.push('…');
+  }
+  if lines.len() > 1 {
+    summary.push_str(&format!("  (+{} lines)", lines.len() - 1));
+  }
+  summary
+}
+
+fn style_for_cell(status: CellStatus, output: Option<&str>, idle_color: Hsla) -> DecorationStyle {
+  let (glyph, color) = match status {
+    CellStatus::Idle => ("▶", idle_color),
+    // Clicking the button of a running cell stops it
+    CellStatus::Running => ("■", yellow()),
+    CellStatus::Succeeded => ("✔", green()),
+    CellStatus::Failed => ("✘", red()),
+  };
+  let output_color = if status == CellStatus::Failed {
+    red()
+  } else {
+    idle_color
+  };
+
+  DecorationStyle {
+    gutter_icon: Some(GutterIcon {
+      glyph: SharedString::new_static(glyph),
+      color,
+    }),
+    virtual_text: output.map(|output| VirtualText {
+      text: format!("⇒ {}", summarize_output(output)).into(),
+      color: output_color,
+    }),
+    ..Default::default()
+  }
+}
+
+/// Full output of the cell under the cursor, beside the text
+pub(crate) fn render_output_panel(
+  editor: &Editor,
+  cx: &mut Context<Editor>,
+) -> Option<impl IntoElement> {
+  let line = editor
+    .document
+    .read(cx)
+    .char_to_line(editor.cursor_offset());
+  let ix = cell_at_line(editor, line, cx)?;
+  let cell = &editor.code_cells.cells[ix];
+  let output = StyledOutput::parse(cell.output.as_ref()?);
+  let (shown, hidden) = if cell.expanded {
+    (output, 0)
+  } else {
+    output.folded(FOLDED_OUTPUT_LINES)
+  };
+  let links = ansi::find_file_links(&shown.text);
+  let highlights = shown.highlights(&links);
+  let text = StyledText::new(shown.text).with_highlights(highlights);
+
+  let this = cx.entity().downgrade();
+  let code_start = cell.code.start;
+  let link_ranges = links.iter().map(|link| link.range.clone()).collect();
+  let text = InteractiveText::new("cell-output-text", text).on_click(
+    link_ranges,
+    move |link_ix, window, cx| {
+      let link = &links[link_ix];
+      let _ = this.update(cx, |editor, cx| {
+        if link.path == STDIN_FILE {
+          // Lines of code piped to the interpreter count from the cell's code
+          let code_line = {
+            let document = editor.document.read(cx);
+            document.char_to_line(document.resolve_anchor(&code_start))
+          };
+          ansi::move_to_line(editor, code_line + link.line, link.column, window, cx);
+        } else {
+          let workspace = trust::workspace(editor, cx);
+          ansi::follow_link(editor, link, workspace.as_deref(), window, cx);
+        }
+      });
+    },
+  );
+
+  let expanded = cell.expanded;
+  let start = cell.range.start;
+  let fold_label = if expanded {
+    "Show less".to_string()
+  } else {
+    format!("Show {} more lines", hidden)
+  };
+  Some(
+    div()
+      .id("cell-output")
+      .w(px(OUTPUT_PANEL_WIDTH))
+      .h_full()
+      .flex()
+      .flex_col()
+      .p_1()
+      .overflow_scroll()
+      .bg(editor.theme.gutter_background())
+      .text_size(px(12.0))
+      .when_some(editor.font().family.clone(), |el, family| {
+        el.font_family(family)
+      })
+      .child(
+        div()
+          .text_color(editor.theme.line_number())
+          .child(SharedString::from(format!("Output ({})", cell.language))),
+      )
+      .child(text)
+      .when(expanded || hidden > 0, |el| {
+        el.child(
+          div()
+            .id("cell-output-fold")
+            .cursor_pointer()
+            .text_color(editor.theme.line_number())
+            .child(fold_label)
+            .on_click(cx.listener(move |editor, _: &ClickEvent, _, cx| {
+              if let Some(ix) = cell_starting_at(editor, &start, cx) {
+                editor.code_cells.cells[ix].expanded = !expanded;
+                cx.notify();
+              }
+            })),
+        )
+      }),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  fn cell_code<'a>(text: &'a str, cell: &FoundCell) -> &'a str {
+    &text[cell.code.clone()]
+  }
+
+  #[test]
+  fn test_marker_cells() {
+    let text = "import os\n# %% load\nx = 1\n\n  # %%\nprint(x)\n";
+    let cells = find_cells(text, Some("py"));
+    assert_eq!(cells.len(), 2);
+    assert_eq!(cell_code(text, &cells[0]), "x = 1\n");
+    assert_eq!(&text[cells[0].range.clone()], "# %% load\nx = 1\n");
+    assert_eq!(cell_code(text, &cells[1]), "print(x)\n");
+    assert_eq!(cells[1].language, "py");
+
+    assert!(find_cells("x = 1\n", Some("py")).is_empty());
+    assert!(find_cells("# %%\nx\n", None).is_empty());
+  }
+
+  #[test]
+  fn test_fenced_cells() {
+    let text =
+      "# Notes\n```python\nprint(1)\n```\n\n```\nplain\n```\n``` js\nconsole.log(2)\n```\n";
+    let cells = find_cells(text, Some("md"));
+    let found: Vec<_> = cells
+      .iter()
+      .map(|cell| (cell.language.as_str(), cell_code(text, cell)))
+      .collect();
+    assert_eq!(
+      found,
+      [("python", "print(1)\n"), ("js", "console.log(2)\n")]
+    );
+    // An unclosed block isn't a cell
+    assert!(find_cells("```python\nprint(1)\n", Some("md")).is_empty());
+  }
+
+  #[test]
+  fn test_summarize_output() {
+    assert_eq!(summarize_output("42\n"), "42");
+    assert_eq!(summarize_output("\na\nb\n\nc\n"), "a  (+2 lines)");
+    assert_eq!(summarize_output(""), "(no output)");
+    assert_eq!(summarize_output("\x1b[32mok\x1b[0m\n"), "ok");
+    let long = "x".repeat(MAX_OUTPUT_SUMMARY + 10);
+    assert_eq!(
+      summarize_output(&long).chars().count(),
+      MAX_OUTPUT_SUMMARY + 1
+    );
+  }
+
+  #[test]
+  fn test_read_capped() {
+    assert_eq!(read_capped("short\n".as_bytes(), 10), "short\n");
+    assert_eq!(
+      read_capped("0123456789".as_bytes(), 4),
+      "0123\n… 6 more bytes not shown\n"
+    );
+  }
+
+  #[gpui::test]
+  fn test_stop_kills_interpreter(cx: &mut TestAppContext) {
+    let text = "# %%\nwhile True: pass\n";
+    let mut ctx = EditorTestContext::with_text(cx.clone(), text);
+    let child = ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.code_cells.enabled = true;
+      apply_cells(editor, find_cells(text, Some("py")), None, cx);
+      let sleep = Interpreter {
+        command: "sleep".to_string(),
+        args: vec!["30".to_string()],
+      };
+      let child = Arc::new(Mutex::new(spawn(&sleep, None).unwrap()));
+      set_result(editor, 0, CellStatus::Running, None, cx);
+      editor.code_cells.cells[0].process = Some(RunningProcess(child.clone()));
+      assert!(is_running_at_line(editor, 0, cx));
+
+      assert!(stop_cell_at_line(editor, 1, cx));
+      assert!(!stop_cell_at_line(editor, 1, cx));
+      let cell = &editor.code_cells.cells[0];
+      assert_eq!(cell.status, CellStatus::Failed);
+      assert!(cell.process.is_none());
+      child
+    });
+    assert!(!child.lock().wait().unwrap().success());
+  }
+
+  #[gpui::test]
+  fn test_outputs_survive_rescan(cx: &mut TestAppContext) {
+    let text = "# %%\nx = 1\n# %%\ny = 2\n";
+    let mut ctx = EditorTestContext::with_text(cx.clone(), text);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.code_cells.enabled = true;
+      apply_cells(editor, find_cells(text, Some("py")), None, cx);
+      assert!(has_cell_at_line(editor, 2, cx));
+      assert!(!has_cell_at_line(editor, 3, cx));
+      assert_eq!(cell_at_line(editor, 3, cx), Some(1));
+
+      set_result(editor, 1, CellStatus::Succeeded, Some("2".to_string()), cx);
+      apply_cells(editor, find_cells(text, Some("py")), None, cx);
+      let cell = &editor.code_cells.cells[1];
+      assert_eq!(cell.status, CellStatus::Succeeded);
+      assert_eq!(cell.output.as_deref(), Some("2"));
+
+      clear_outputs(editor, cx);
+      assert!(
+        editor
+          .code_cells
+          .cells
+          .iter()
+          .all(|cell| cell.output.is_none())
+      );
+
+      toggle(editor, cx);
+      assert!(!is_enabled(editor));
+      assert!(editor.code_cells.cells.is_empty());
+      assert!(editor.decorations.is_empty());
+    });
+  }
+}
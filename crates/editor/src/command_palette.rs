@@ -0,0 +1,216 @@
+//! Palette of the commands plugins registered
+//!
+//! The palette takes keyboard focus while it is open. Typing filters the
+//! commands by name, and Enter runs the selected one.
+
+use gpui::{
+  App, Context, FocusHandle, Focusable, KeyDownEvent, WeakEntity, Window, div, prelude::*, px,
+};
+
+use crate::{
+  actions::{Backspace, Down, Enter, Up},
+  editor::Editor,
+  escape,
+  plugins::{self, PluginCommand},
+};
+
+/// Commands shown at once
+const MAX_VISIBLE_COMMANDS: usize = 12;
+
+/// Commands whose label has every word of `query` in it, ignoring case
+pub(crate) fn filter(commands: Vec<PluginCommand>, query: &str) -> Vec<PluginCommand> {
+  let query = query.to_lowercase();
+  commands
+    .into_iter()
+    .filter(|command| {
+      let label = command.label.to_lowercase();
+      query.split_whitespace().all(|word| label.contains(word))
+    })
+    .collect()
+}
+
+pub struct CommandPalette {
+  editor: WeakEntity<Editor>,
+  focus_handle: FocusHandle,
+  query: String,
+  selected: usize,
+}
+
+/// Open the palette at the top of the editor and give it focus
+pub(crate) fn open(editor: &mut Editor, window: &mut Window, cx: &mut Context<Editor>) {
+  let editor_handle = cx.weak_entity();
+  let palette = cx.new(|cx| CommandPalette {
+    editor: editor_handle,
+    focus_handle: cx.focus_handle(),
+    query: String::new(),
+    selected: 0,
+  });
+  window.focus(&palette.focus_handle(cx), cx);
+  editor.command_palette = Some(palette);
+  cx.notify();
+}
+
+/// Close the palette, returning focus to the editor if it had it
+pub(crate) fn close(editor: &mut Editor, window: &mut Window, cx: &mut Context<Editor>) {
+  let Some(palette) = editor.command_palette.take() else {
+    return;
+  };
+  if palette.focus_handle(cx).is_focused(window) {
+    window.focus(&editor.focus_handle, cx);
+  }
+  cx.notify();
+}
+
+impl CommandPalette {
+  fn matches(&self, cx: &App) -> Vec<PluginCommand> {
+    filter(plugins::commands(cx), &self.query)
+  }
+
+  fn set_query(&mut self, query: String, cx: &mut Context<Self>) {
+    self.query = query;
+    self.selected = 0;
+    cx.notify();
+  }
+
+  fn select(&mut self, delta: isize, cx: &mut Context<Self>) {
+    let count = self.matches(cx).len().min(MAX_VISIBLE_COMMANDS);
+    if count > 0 {
+      self.selected = (self.selected as isize + delta).rem_euclid(count as isize) as usize;
+      cx.notify();
+    }
+  }
+
+  fn confirm(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+    let command = self.matches(cx).into_iter().nth(self.selected);
+    let _ = self.editor.update(cx, |editor, cx| {
+      close(editor, window, cx);
+      if let Some(command) = command {
+        plugins::run(editor, &command, cx);
+      }
+    });
+  }
+
+  fn dismiss(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+    let _ = self.editor.update(cx, |editor, cx| {
+      escape::close_topmost(editor, window, cx);
+    });
+  }
+
+  fn key_down(&mut self, event: &KeyDownEvent, window: &mut Window, cx: &mut Context<Self>) {
+    let keystroke = &event.keystroke;
+    if keystroke.key == "escape" {
+      self.dismiss(window, cx);
+    } else if let Some(key_char) = keystroke.key_char.as_ref()
+      && !keystroke.modifiers.platform
+      && !keystroke.modifiers.control
+    {
+      let query = format!("{}{}", self.query, key_char);
+      self.set_query(query, cx);
+    } else {
+      return;
+    }
+    cx.stop_propagation();
+  }
+}
+
+impl Focusable for CommandPalette {
+  fn focus_handle(&self, _: &App) -> FocusHandle {
+    self.focus_handle.clone()
+  }
+}
+
+impl Render for CommandPalette {
+  fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+    let Some(editor) = self.editor.upgrade() else {
+      return div();
+    };
+    let theme = editor.read(cx).theme.clone();
+    let matches = self.matches(cx);
+    let query = if self.query.is_empty() {
+      "Run a plugin command".to_string()
+    } else {
+      self.query.clone()
+    };
+
+    div()
+      .key_context("CommandPalette")
+      .track_focus(&self.focus_handle)
+      // The palette's own versions of the editor keys it shadows
+      .on_action(cx.listener(|this, _: &Enter, window, cx| this.confirm(window, cx)))
+      .on_action(cx.listener(|this, _: &Backspace, _, cx| {
+        let mut query = this.query.clone();
+        query.pop();
+        this.set_query(query, cx);
+      }))
+      .on_action(cx.listener(|this, _: &Up, _, cx| this.select(-1, cx)))
+      .on_action(cx.listener(|this, _: &Down, _, cx| this.select(1, cx)))
+      .on_key_down(cx.listener(Self::key_down))
+      .w(px(480.0))
+      .flex()
+      .flex_col()
+      .p_1()
+      .rounded_md()
+      .border_1()
+      .border_color(theme.line_number())
+      .bg(theme.gutter_background())
+      .text_size(px(12.0))
+      .child(div().px_1().pb_1().child(query))
+      .when(matches.is_empty(), |el| {
+        el.child(
+          div()
+            .px_1()
+            .text_color(theme.line_number())
+            .child("No matching commands"),
+        )
+      })
+      .children(
+        matches
+          .into_iter()
+          .take(MAX_VISIBLE_COMMANDS)
+          .enumerate()
+          .map(|(ix, command)| {
+            div()
+              .px_1()
+              .rounded_sm()
+              .when(ix == self.selected, |el| el.bg(theme.selection()))
+              .child(command.label)
+          }),
+      )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn labels(commands: Vec<PluginCommand>) -> Vec<String> {
+    commands
+      .into_iter()
+      .map(|command| command.label.to_string())
+      .collect()
+  }
+
+  #[test]
+  fn test_filter() {
+    let commands: Vec<PluginCommand> = ["json: Sort Keys", "case: Upper Case"]
+      .into_iter()
+      .enumerate()
+      .map(|(plugin, label)| PluginCommand {
+        plugin,
+        id: 0,
+        label: label.into(),
+      })
+      .collect();
+    assert_eq!(labels(filter(commands.clone(), "")).len(), 2);
+    assert_eq!(
+      labels(filter(commands.clone(), "SORT")),
+      ["json: Sort Keys"]
+    );
+    // Words match anywhere, in any order
+    assert_eq!(
+      labels(filter(commands.clone(), "case up")),
+      ["case: Upper Case"]
+    );
+    assert!(filter(commands, "sort case").is_empty());
+  }
+}
@@ -0,0 +1,142 @@
+//! Toggling line comments
+//!
+//! The lines under the selection are commented out with the language's line
+//! comment token, placed at the indentation of the least indented of them so
+//! the tokens line up. When every line with text is commented already, the
+//! tokens come off instead, each with the space after it. Blank lines are
+//! left alone unless no line has text.
+
+use std::ops::Range;
+
+use gpui::Context;
+
+use crate::{editor::Editor, indent};
+
+fn indent_len(line: &str) -> usize {
+  line
+    .chars()
+    .take_while(|ch| *ch == ' ' || *ch == '\t')
+    .count()
+}
+
+/// Edits toggling `token` on `lines`, as a line index and the columns to
+/// replace on that line
+fn line_edits(lines: &[&str], token: &str) -> Vec<(usize, Range<usize>, String)> {
+  let mut filled: Vec<(usize, &str)> = lines
+    .iter()
+    .copied()
+    .enumerate()
+    .filter(|(_, line)| !line.trim().is_empty())
+    .collect();
+  if filled.is_empty() {
+    filled = lines.iter().copied().enumerate().collect();
+  }
+
+  let commented = filled
+    .iter()
+    .all(|(_, line)| line.trim_start_matches([' ', '\t']).starts_with(token));
+  if commented {
+    return filled
+      .into_iter()
+      .map(|(index, line)| {
+        let start = indent_len(line);
+        let rest = &line.trim_start_matches([' ', '\t'])[token.len()..];
+        let len = token.chars().count() + usize::from(rest.starts_with(' '));
+        (index, start..start + len, String::new())
+      })
+      .collect();
+  }
+
+  let column = filled
+    .iter()
+    .map(|(_, line)| indent_len(line))
+    .min()
+    .unwrap_or(0);
+  filled
+    .into_iter()
+    .map(|(index, _)| (index, column..column, format!("{token} ")))
+    .collect()
+}
+
+/// Comment out the lines under the selection, or uncomment them; a document
+/// whose language has no line comments is left as it is
+pub(crate) fn toggle_comment(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let document = editor.document.read(cx);
+  let Some(token) = document
+    .language_config()
+    .and_then(|config| config.comments.line)
+  else {
+    return;
+  };
+  let lines = indent::selected_lines(document, &editor.selected_range);
+  let texts: Vec<String> = lines
+    .clone()
+    .map(|line| document.line_content(line).unwrap_or_default().into_owned())
+    .collect();
+  let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+  let edits = line_edits(&texts, token)
+    .into_iter()
+    .map(|(index, columns, text)| {
+      let start = document.line_to_char(lines.start + index);
+      (start + columns.start..start + columns.end, text)
+    })
+    .collect();
+  indent::edit_line_starts(editor, edits, "Toggle Comment", cx);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{document::Document, editor::tests::EditorTestContext};
+  use gpui::{AppContext, TestAppContext};
+
+  #[test]
+  fn test_line_edits() {
+    assert_eq!(
+      line_edits(&["  a", "", "    b"], "//"),
+      vec![(0, 2..2, "// ".to_string()), (2, 2..2, "// ".to_string())]
+    );
+    assert_eq!(
+      line_edits(&["// a", "  //b"], "//"),
+      vec![(0, 0..3, String::new()), (1, 2..4, String::new())]
+    );
+    // One line without a comment comments them all
+    assert_eq!(
+      line_edits(&["// a", "b"], "//"),
+      vec![(0, 0..0, "// ".to_string()), (1, 0..0, "// ".to_string())]
+    );
+    assert_eq!(line_edits(&[""], "//"), vec![(0, 0..0, "// ".to_string())]);
+  }
+
+  #[gpui::test]
+  fn test_toggle_comment(cx: &mut TestAppContext) {
+    let editor = cx.new(|cx| {
+      let document = cx.new(|cx| Document::new("fn f() {\n  a();\n}", Some("rs"), cx));
+      Editor::with_document(document, cx)
+    });
+    let mut ctx = EditorTestContext {
+      cx: cx.clone(),
+      editor,
+    };
+
+    // The cursor stays on the same text
+    ctx.set_cursor(13);
+    ctx
+      .editor
+      .update(&mut ctx.cx, |editor, cx| toggle_comment(editor, cx));
+    assert_eq!(ctx.text(), "fn f() {\n  // a();\n}");
+    assert_eq!(ctx.cursor_offset(), 16);
+
+    ctx.set_selection(0..20, false);
+    ctx
+      .editor
+      .update(&mut ctx.cx, |editor, cx| toggle_comment(editor, cx));
+    assert_eq!(ctx.text(), "// fn f() {\n//   // a();\n// }");
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      toggle_comment(editor, cx);
+      assert_eq!(editor.undo_description(cx), Some("Toggle Comment"));
+    });
+    assert_eq!(ctx.text(), "fn f() {\n  // a();\n}");
+  }
+}
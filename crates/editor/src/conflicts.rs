@@ -0,0 +1,388 @@
+//! Resolving merge conflicts left in a file
+//!
+//! Conflict regions, from a `<<<<<<<` line to a `>>>>>>>` line, are found
+//! after edits settle. Their current and incoming sides are tinted, and the
+//! marker line of each gets buttons to keep either side or both, replacing
+//! the region as one undoable edit. A `|||||||` base section, as written by
+//! the diff3 conflict style, is dropped by every resolution.
+
+use std::{
+  ops::Range,
+  time::{Duration, Instant},
+};
+
+use buffer::Anchor;
+use gpui::{App, ClickEvent, Context, Hsla, IntoElement, Task, Window, div, prelude::*, px};
+
+use crate::{
+  decorations::{DecorationId, DecorationStyle},
+  editor::Editor,
+  wrap_map,
+};
+
+/// Delay before re-scanning the document for conflicts after an edit
+const REFRESH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// A conflict found in a document's text, as byte ranges
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct FoundConflict {
+  /// From the start of the `<<<<<<<` line to the end of the `>>>>>>>` line
+  pub range: Range<usize>,
+  /// Lines of the current side, with their line breaks
+  pub current: Range<usize>,
+  pub incoming: Range<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Resolution {
+  Current,
+  Incoming,
+  Both,
+}
+
+struct Conflict {
+  range: Range<Anchor>,
+  current: Range<Anchor>,
+  incoming: Range<Anchor>,
+  decorations: [DecorationId; 2],
+}
+
+#[derive(Default)]
+pub(crate) struct Conflicts {
+  conflicts: Vec<Conflict>,
+  // Document version the conflicts were found in
+  scanned_version: Option<usize>,
+  refresh_task: Option<Task<()>>,
+}
+
+/// Conflict regions of `text`; unterminated regions are ignored
+pub(crate) fn find_conflicts(text: &str) -> Vec<FoundConflict> {
+  let is_marker = |line: &str, marker: &str| {
+    line
+      .strip_prefix(marker)
+      .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\r', '\n']))
+  };
+
+  let mut conflicts = Vec::new();
+  // Start of the region, of the current side, and the end of the side being read
+  let mut open: Option<(usize, usize, Option<usize>)> = None;
+  let mut incoming_start = None;
+  let mut offset = 0;
+  for line in text.split_inclusive('\n') {
+    let start = offset;
+    offset += line.len();
+    if is_marker(line, "<<<<<<<") {
+      open = Some((start, offset, None));
+      incoming_start = None;
+    } else if let Some((region_start, current_start, current_end)) = &mut open {
+      if current_end.is_none() && is_marker(line, "|||||||") {
+        *current_end = Some(start);
+      } else if incoming_start.is_none() && is_marker(line, "=======") {
+        current_end.get_or_insert(start);
+        incoming_start = Some(offset);
+      } else if let Some(incoming) = incoming_start
+        && is_marker(line, ">>>>>>>")
+      {
+        conflicts.push(FoundConflict {
+          range: *region_start..offset,
+          current: *current_start..current_end.unwrap_or(start),
+          incoming: incoming..start,
+        });
+        open = None;
+        incoming_start = None;
+      }
+    }
+  }
+  conflicts
+}
+
+/// Forget the conflicts of the previous document
+pub(crate) fn clear(editor: &mut Editor) {
+  editor.conflicts.conflicts.clear();
+  editor.conflicts.scanned_version = None;
+  editor.conflicts.refresh_task = None;
+}
+
+/// Re-scan the document for conflicts once edits have settled
+pub(crate) fn schedule_refresh(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let document = editor.document.read(cx);
+  let version = document.version();
+  if editor.conflicts.scanned_version == Some(version) {
+    return;
+  }
+
+  let snapshot = document.snapshot();
+  editor.conflicts.refresh_task = Some(cx.spawn(async move |this, cx| {
+    cx.background_executor().timer(REFRESH_DEBOUNCE).await;
+
+    let conflicts = cx
+      .background_executor()
+      .spawn(async move { find_conflicts(&snapshot.text()) })
+      .await;
+
+    let _ = this.update(cx, |editor, cx| {
+      // Byte offsets are only valid for the text they were computed from
+      if editor.document.read(cx).version() == version {
+        apply_conflicts(editor, conflicts, version, cx);
+      }
+    });
+  }));
+}
+
+fn remove_conflict(editor: &mut Editor, ix: usize) -> Conflict {
+  let conflict = editor.conflicts.conflicts.remove(ix);
+  for id in conflict.decorations {
+    editor.decorations.remove(id);
+  }
+  conflict
+}
+
+fn apply_conflicts(
+  editor: &mut Editor,
+  found: Vec<FoundConflict>,
+  version: usize,
+  cx: &mut Context<Editor>,
+) {
+  while !editor.conflicts.conflicts.is_empty() {
+    remove_conflict(editor, 0);
+  }
+
+  for found in found {
+    let (range, current, incoming) = {
+      let document = editor.document.read(cx);
+      let anchor_range = |range: Range<usize>| {
+        document.anchor_range(document.byte_to_char(range.start)..document.byte_to_char(range.end))
+      };
+      (
+        anchor_range(found.range),
+        anchor_range(found.current),
+        anchor_range(found.incoming),
+      )
+    };
+    let tint = |color: Hsla| DecorationStyle {
+      background: Some(color),
+      gutter_background: Some(color),
+      ..Default::default()
+    };
+    let decorations = [
+      editor
+        .decorations
+        .insert(current.clone(), tint(current_color())),
+      editor
+        .decorations
+        .insert(incoming.clone(), tint(incoming_color())),
+    ];
+    editor.conflicts.conflicts.push(Conflict {
+      range,
+      current,
+      incoming,
+      decorations,
+    });
+  }
+
+  editor.conflicts.scanned_version = Some(version);
+  cx.notify();
+}
+
+fn current_color() -> Hsla {
+  Hsla {
+    h: 120.0 / 360.0,
+    s: 0.6,
+    l: 0.45,
+    a: 0.2,
+  }
+}
+
+fn incoming_color() -> Hsla {
+  Hsla {
+    h: 210.0 / 360.0,
+    s: 0.7,
+    l: 0.55,
+    a: 0.2,
+  }
+}
+
+/// Replace conflict `ix` with the side or sides picked
+pub(crate) fn resolve(
+  editor: &mut Editor,
+  ix: usize,
+  resolution: Resolution,
+  cx: &mut Context<Editor>,
+) {
+  if ix >= editor.conflicts.conflicts.len() {
+    return;
+  }
+  let conflict = remove_conflict(editor, ix);
+  let (range, text) = {
+    let document = editor.document.read(cx);
+    let side =
+      |range: &Range<Anchor>| document.slice_to_string(document.resolve_anchor_range(range));
+    let text = match resolution {
+      Resolution::Current => side(&conflict.current),
+      Resolution::Incoming => side(&conflict.incoming),
+      Resolution::Both => side(&conflict.current) + &side(&conflict.incoming),
+    };
+    (document.resolve_anchor_range(&conflict.range), text)
+  };
+  let first_line = editor.document.read(cx).char_to_line(range.start);
+  let selection_before = editor.selected_range.clone();
+
+  let transaction_id = editor.document.update(cx, |doc, cx| {
+    let id = doc.buffer.transaction(Instant::now(), |buffer, tx| {
+      buffer.replace(tx, range.clone(), &text);
+    });
+    doc.schedule_recompute_highlights(cx);
+    cx.notify();
+    id
+  });
+
+  editor.move_to(range.start, cx);
+  let selection_after = editor.selected_range.clone();
+  editor.record_transaction(
+    transaction_id,
+    selection_before,
+    selection_after,
+    "Resolve Conflict",
+    cx,
+  );
+  editor.invalidate_lines_from(first_line);
+  cx.notify();
+}
+
+/// First lines of the conflicts, in document order
+fn conflict_lines(editor: &Editor, cx: &App) -> Vec<usize> {
+  let document = editor.document.read(cx);
+  editor
+    .conflicts
+    .conflicts
+    .iter()
+    .map(|conflict| document.char_to_line(document.resolve_anchor(&conflict.range.start)))
+    .collect()
+}
+
+/// Move to the next conflict after the cursor, or the previous one before
+/// it, wrapping around the document
+pub(crate) fn go_to_conflict(
+  editor: &mut Editor,
+  forward: bool,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  let lines = conflict_lines(editor, cx);
+  let cursor_line = editor
+    .document
+    .read(cx)
+    .char_to_line(editor.cursor_offset());
+  let target = if forward {
+    lines
+      .iter()
+      .find(|&&line| line > cursor_line)
+      .or(lines.first())
+  } else {
+    lines
+      .iter()
+      .rev()
+      .find(|&&line| line < cursor_line)
+      .or(lines.last())
+  };
+  let Some(&line) = target else {
+    return;
+  };
+  let offset = editor.document.read(cx).line_to_char(line);
+  editor.target_column = None;
+  editor.move_to(offset, cx);
+  editor.ensure_cursor_visible(window, cx);
+}
+
+/// Resolution buttons at the end of the marker line of each visible conflict
+pub(crate) fn render_actions(editor: &Editor, cx: &mut Context<Editor>) -> Vec<impl IntoElement> {
+  if editor.hex_mode {
+    return Vec::new();
+  }
+  let lines = conflict_lines(editor, cx);
+  let visible_lines = editor.viewport_height / editor.line_height;
+  let button_color = editor.theme.line_number();
+
+  lines
+    .into_iter()
+    .enumerate()
+    .map(|(ix, line)| (ix, wrap_map::rows_for_line(editor, line, cx).start))
+    .filter(|(_, row)| {
+      let row = *row as f32 - editor.scroll_offset_y;
+      row >= 0.0 && row < visible_lines
+    })
+    .map(|(ix, row)| {
+      let y = editor.line_height * (row as f32 - editor.scroll_offset_y);
+      let button = |label: &'static str, resolution: Resolution| {
+        div()
+          .id(("conflict-action", ix * 3 + resolution as usize))
+          .px_1()
+          .rounded_sm()
+          .cursor_pointer()
+          .border_1()
+          .border_color(button_color)
+          .child(label)
+          .on_click(cx.listener(move |editor, _: &ClickEvent, _, cx| {
+            resolve(editor, ix, resolution, cx);
+          }))
+      };
+      div()
+        .absolute()
+        .top(y)
+        .right(px(16.))
+        .h(editor.line_height)
+        .occlude()
+        .flex()
+        .flex_row()
+        .items_center()
+        .gap_1()
+        .text_size(px(11.0))
+        .text_color(button_color)
+        .child(button("Accept Current", Resolution::Current))
+        .child(button("Accept Incoming", Resolution::Incoming))
+        .child(button("Accept Both", Resolution::Both))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  const TEXT: &str = "a\n<<<<<<< HEAD\nmine\n=======\ntheirs\n>>>>>>> feature\nb\n<<<<<<< ours\nx\n||||||| base\nw\n=======\ny\n>>>>>>> theirs\n";
+
+  #[test]
+  fn test_find_conflicts() {
+    let conflicts = find_conflicts(TEXT);
+    assert_eq!(conflicts.len(), 2);
+    let sides: Vec<_> = conflicts
+      .iter()
+      .map(|c| (&TEXT[c.current.clone()], &TEXT[c.incoming.clone()]))
+      .collect();
+    assert_eq!(sides, [("mine\n", "theirs\n"), ("x\n", "y\n")]);
+    assert!(TEXT[conflicts[0].range.clone()].ends_with(">>>>>>> feature\n"));
+
+    // Unterminated regions and look-alike lines aren't conflicts
+    assert!(find_conflicts("<<<<<<< HEAD\na\n=======\nb\n").is_empty());
+    assert!(find_conflicts("<<<<<<<< x\n=======\n>>>>>>>\n").is_empty());
+  }
+
+  #[gpui::test]
+  fn test_resolve(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), TEXT);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      let version = editor.document.read(cx).version();
+      apply_conflicts(editor, find_conflicts(TEXT), version, cx);
+      assert_eq!(conflict_lines(editor, cx), [1, 7]);
+
+      resolve(editor, 1, Resolution::Both, cx);
+      resolve(editor, 0, Resolution::Incoming, cx);
+      assert!(editor.conflicts.conflicts.is_empty());
+      assert!(editor.decorations.is_empty());
+      assert_eq!(editor.document.read(cx).buffer.undo_depth(), 2);
+    });
+    assert_eq!(ctx.text(), "a\ntheirs\nb\nx\ny\n");
+  }
+}
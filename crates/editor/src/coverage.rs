@@ -0,0 +1,277 @@
+//! Line coverage overlay fed from lcov reports
+//!
+//! Covered and uncovered lines are tinted in the gutter with decorations, so
+//! the overlay keeps tracking the right lines while the document is edited.
+//! Each run of adjacent lines with the same state shares one decoration. The
+//! runs are anchored once, when the report is set, and hiding the overlay
+//! keeps their anchors, so it comes back on the lines they moved to.
+
+use std::{collections::BTreeMap, ops::Range};
+
+use buffer::Anchor;
+use gpui::{Context, Hsla};
+
+use crate::{
+  decorations::{DecorationId, DecorationStyle},
+  editor::Editor,
+};
+
+/// Hit counts for the instrumented lines of one source file
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileCoverage {
+  pub path: String,
+  /// 1-based line number to execution count
+  pub lines: BTreeMap<usize, u64>,
+}
+
+impl FileCoverage {
+  /// Runs of adjacent instrumented lines, 0-based, and whether they were executed
+  pub fn runs(&self) -> Vec<(Range<usize>, bool)> {
+    let mut runs: Vec<(Range<usize>, bool)> = Vec::new();
+    for (&line_number, &hits) in &self.lines {
+      let Some(line_idx) = line_number.checked_sub(1) else {
+        continue;
+      };
+      let covered = hits > 0;
+      match runs.last_mut() {
+        Some((lines, run_covered)) if lines.end == line_idx && *run_covered == covered => {
+          lines.end += 1;
+        }
+        _ => runs.push((line_idx..line_idx + 1, covered)),
+      }
+    }
+    runs
+  }
+
+  pub fn covered_lines(&self) -> usize {
+    self.lines.values().filter(|&&hits| hits > 0).count()
+  }
+
+  /// Percentage of instrumented lines that were executed, if any are instrumented
+  pub fn percentage(&self) -> Option<f32> {
+    if self.lines.is_empty() {
+      return None;
+    }
+    Some(self.covered_lines() as f32 * 100.0 / self.lines.len() as f32)
+  }
+}
+
+/// Parse an lcov tracefile into per-file line coverage
+pub fn parse_lcov(text: &str) -> Result<Vec<FileCoverage>, String> {
+  let mut files = Vec::new();
+  let mut current: Option<FileCoverage> = None;
+
+  for (line_idx, line) in text.lines().enumerate() {
+    let line = line.trim();
+    if let Some(path) = line.strip_prefix("SF:") {
+      current = Some(FileCoverage {
+        path: path.to_string(),
+        lines: BTreeMap::new(),
+      });
+    } else if let Some(data) = line.strip_prefix("DA:") {
+      let file = current
+        .as_mut()
+        .ok_or_else(|| format!("line {}: DA record outside of a file", line_idx + 1))?;
+      let mut fields = data.split(',');
+      let line_number = fields.next().and_then(|field| field.parse::<usize>().ok());
+      let hits = fields.next().and_then(|field| field.parse::<u64>().ok());
+      match (line_number, hits) {
+        (Some(line_number), Some(hits)) => {
+          *file.lines.entry(line_number).or_default() += hits;
+        }
+        _ => return Err(format!("line {}: malformed DA record", line_idx + 1)),
+      }
+    } else if line == "end_of_record" {
+      files.extend(current.take());
+    }
+  }
+
+  // Tolerate a missing trailing end_of_record
+  files.extend(current);
+  Ok(files)
+}
+
+#[derive(Default)]
+pub struct CoverageOverlay {
+  coverage: Option<FileCoverage>,
+  /// Lines of each run of the report, and whether they were executed
+  runs: Vec<(Range<Anchor>, bool)>,
+  decorations: Vec<DecorationId>,
+  visible: bool,
+}
+
+impl CoverageOverlay {
+  pub fn is_visible(&self) -> bool {
+    self.visible
+  }
+
+  pub fn percentage(&self) -> Option<f32> {
+    self.coverage.as_ref().and_then(FileCoverage::percentage)
+  }
+}
+
+/// Replace the coverage shown for the document and display the overlay
+pub(crate) fn set_coverage(editor: &mut Editor, coverage: FileCoverage, cx: &mut Context<Editor>) {
+  let document = editor.document.read(cx);
+  editor.coverage.runs = coverage
+    .runs()
+    .into_iter()
+    .filter_map(|(lines, covered)| {
+      let first = document.line_range(lines.start)?;
+      // Reports can run past the end of a document edited since
+      let last_line = (lines.end - 1).min(document.len_lines().saturating_sub(1));
+      let last = document
+        .line_range(last_line)
+        .unwrap_or_else(|| first.clone());
+      let line_range = first.start..last.end.max(first.end);
+      Some((document.anchor_range(line_range), covered))
+    })
+    .collect();
+  editor.coverage.coverage = Some(coverage);
+  editor.coverage.visible = true;
+  refresh(editor, cx);
+}
+
+pub(crate) fn clear_coverage(editor: &mut Editor, cx: &mut Context<Editor>) {
+  editor.coverage.coverage = None;
+  editor.coverage.runs.clear();
+  refresh(editor, cx);
+}
+
+pub(crate) fn toggle_overlay(editor: &mut Editor, cx: &mut Context<Editor>) {
+  editor.coverage.visible = !editor.coverage.visible;
+  refresh(editor, cx);
+}
+
+fn refresh(editor: &mut Editor, cx: &mut Context<Editor>) {
+  for id in editor.coverage.decorations.drain(..) {
+    editor.decorations.remove(id);
+  }
+
+  if editor.coverage.visible {
+    for (range, covered) in &editor.coverage.runs {
+      let color = if *covered {
+        covered_color()
+      } else {
+        uncovered_color()
+      };
+      let id = editor.decorations.insert(
+        range.clone(),
+        DecorationStyle {
+          gutter_background: Some(color),
+          ..Default::default()
+        },
+      );
+      editor.coverage.decorations.push(id);
+    }
+  }
+
+  cx.notify();
+}
+
+fn covered_color() -> Hsla {
+  Hsla {
+    h: 120.0 / 360.0,
+    s: 0.6,
+    l: 0.45,
+    a: 0.35,
+  }
+}
+
+fn uncovered_color() -> Hsla {
+  Hsla {
+    h: 0.0,
+    s: 0.7,
+    l: 0.5,
+    a: 0.35,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  const LCOV: &str = "TN:\nSF:src/lib.rs\nDA:1,4\nDA:2,0\nDA:4,1\nLF:3\nLH:2\nend_of_record\nSF:src/main.rs\nDA:1,0\nend_of_record\n";
+
+  #[test]
+  fn test_parse_lcov() {
+    let files = parse_lcov(LCOV).unwrap();
+
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].path, "src/lib.rs");
+    assert_eq!(files[0].lines.get(&1), Some(&4));
+    assert_eq!(files[0].lines.get(&2), Some(&0));
+    assert_eq!(files[0].covered_lines(), 2);
+    assert_eq!(files[1].percentage(), Some(0.0));
+  }
+
+  #[test]
+  fn test_runs() {
+    let coverage = parse_lcov("SF:a.rs\nDA:1,1\nDA:2,3\nDA:3,0\nDA:4,0\nDA:6,1\nDA:7,0\n").unwrap();
+    assert_eq!(
+      coverage[0].runs(),
+      vec![(0..2, true), (2..4, false), (5..6, true), (6..7, false)]
+    );
+  }
+
+  #[test]
+  fn test_parse_lcov_malformed() {
+    assert!(parse_lcov("SF:a.rs\nDA:x,1\n").is_err());
+    assert!(parse_lcov("DA:1,1\n").is_err());
+  }
+
+  #[test]
+  fn test_parse_lcov_without_end_of_record() {
+    let files = parse_lcov("SF:a.rs\nDA:3,2").unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].percentage(), Some(100.0));
+  }
+
+  #[gpui::test]
+  fn test_overlay_toggle(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "a\nb\nc\nd");
+    let coverage = parse_lcov(LCOV).unwrap().remove(0);
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      set_coverage(editor, coverage, cx);
+      assert!(editor.coverage.is_visible());
+      assert_eq!(editor.coverage.decorations.len(), 3);
+
+      toggle_overlay(editor, cx);
+      assert!(editor.coverage.decorations.is_empty());
+      assert!(editor.decorations.is_empty());
+
+      toggle_overlay(editor, cx);
+      assert_eq!(editor.coverage.decorations.len(), 3);
+
+      clear_coverage(editor, cx);
+      assert!(editor.decorations.is_empty());
+    });
+  }
+
+  #[gpui::test]
+  fn test_overlay_follows_edits_while_hidden(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "a\nb\nc\nd");
+    let coverage = parse_lcov(LCOV).unwrap().remove(0);
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      set_coverage(editor, coverage, cx);
+      toggle_overlay(editor, cx);
+      editor.move_to(0, cx);
+      editor.insert_text("new\n", "Typing", cx);
+      toggle_overlay(editor, cx);
+
+      let document = editor.document.read(cx);
+      let lines: Vec<Range<usize>> = editor
+        .decorations
+        .resolve_in_lines(0..document.len_lines(), document)
+        .into_iter()
+        .map(|decoration| decoration.lines)
+        .collect();
+      // Lines 1 and 2 of the report, then line 4, all one line further down
+      assert_eq!(lines, [1..2, 2..3, 4..5]);
+    });
+  }
+}
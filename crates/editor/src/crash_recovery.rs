@@ -0,0 +1,201 @@
+//! Recovery of unsaved edits after a crash
+//!
+//! With `CrashRecovery` set, the edits made to a file are journaled as they
+//! are made, to a file named after it in the journal directory, and the
+//! journal is cut down to the edits made since whenever the file is saved.
+//! Opening a file whose journal still holds edits replays them over it,
+//! bringing back what a crashed session hadn't saved.
+
+use std::path::{Path, PathBuf};
+
+use gpui::{App, Context, Global};
+
+use crate::{document::Document, paths};
+
+/// Where journals of unsaved edits are kept
+#[derive(Clone, Debug, Default)]
+pub struct CrashRecovery {
+  /// Directory of the journals, none to not journal edits
+  pub journal_dir: Option<PathBuf>,
+}
+
+impl Global for CrashRecovery {}
+
+impl CrashRecovery {
+  /// Journals in `journals` in the editor's config directory
+  pub fn in_config_dir() -> Self {
+    Self {
+      journal_dir: paths::config_dir().map(|dir| dir.join("journals")),
+    }
+  }
+
+  fn journal_path(&self, file: &Path) -> Option<PathBuf> {
+    let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+    Some(
+      self
+        .journal_dir
+        .as_ref()?
+        .join(paths::workspace_file_name(&file)),
+    )
+  }
+}
+
+fn journal_path(file: &Path, cx: &App) -> Option<PathBuf> {
+  cx.try_global::<CrashRecovery>()?.journal_path(file)
+}
+
+/// Replay the journal of the file the document was just read from, then
+/// journal its edits
+pub(crate) fn open(document: &mut Document, cx: &mut Context<Document>) {
+  let Some(journal) = document.path().and_then(|path| journal_path(path, cx)) else {
+    return;
+  };
+  if journal.exists() {
+    match document.recover_from_journal(&journal, cx) {
+      Ok(0) => {}
+      Ok(count) => eprintln!("Recovered {} unsaved edits of {}", count, journal.display()),
+      Err(err) => {
+        // Written against another version of the file, the edits are lost
+        eprintln!("Failed to replay {}: {}", journal.display(), err);
+        let _ = std::fs::remove_file(&journal);
+      }
+    }
+  }
+  let version = document.version();
+  start(document, journal, version);
+}
+
+/// Journal to the file the document was just saved to, `version` being the
+/// one saved; the journal of the file it was saved to before is dropped
+pub(crate) fn saved_to_new_path(
+  document: &mut Document,
+  previous: Option<&Path>,
+  version: usize,
+  cx: &mut Context<Document>,
+) {
+  if let Some(journal) = document.buffer.journal()
+    && previous.and_then(|path| journal_path(path, cx)).as_deref() == Some(journal)
+  {
+    let _ = std::fs::remove_file(journal);
+  }
+  document.set_journal(None);
+  let Some(journal) = document.path().and_then(|path| journal_path(path, cx)) else {
+    return;
+  };
+  // Whatever was journaled for the file is older than what was just saved
+  let _ = std::fs::remove_file(&journal);
+  start(document, journal, version);
+}
+
+fn start(document: &mut Document, journal: PathBuf, version: usize) {
+  if let Some(dir) = journal.parent()
+    && let Err(err) = std::fs::create_dir_all(dir)
+  {
+    eprintln!("Failed to create {}: {}", dir.display(), err);
+    return;
+  }
+  document.buffer.start_journal(journal, version);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  fn journal_dir(cx: &mut TestAppContext) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    cx.update(|cx| {
+      cx.set_global(CrashRecovery {
+        journal_dir: Some(dir.path().join("journals")),
+      })
+    });
+    dir
+  }
+
+  /// A new editor on the file at `path`, as in a new session
+  fn open(path: &Path, cx: &TestAppContext) -> EditorTestContext {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.open_file(path.to_path_buf(), cx).unwrap()
+    });
+    ctx
+  }
+
+  fn is_dirty(ctx: &mut EditorTestContext) -> bool {
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.document.read(cx).is_dirty()
+    })
+  }
+
+  #[gpui::test]
+  fn test_unsaved_edits_are_replayed_on_open(cx: &mut TestAppContext) {
+    let dir = journal_dir(cx);
+    let path = dir.path().join("notes.txt");
+    std::fs::write(&path, "hello").unwrap();
+
+    let mut ctx = open(&path, cx);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.move_to(5, cx);
+      editor.insert_text(" world", "Typing", cx);
+    });
+    // The session crashes without saving
+
+    let mut ctx = open(&path, cx);
+    assert_eq!(ctx.text(), "hello world");
+    assert!(is_dirty(&mut ctx));
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor
+        .document
+        .update(cx, |document, cx| document.save(cx).detach())
+    });
+    ctx.cx.run_until_parked();
+    let mut ctx = open(&path, cx);
+    assert_eq!(ctx.text(), "hello world");
+    assert!(!is_dirty(&mut ctx));
+  }
+
+  #[gpui::test]
+  fn test_journal_follows_save_as(cx: &mut TestAppContext) {
+    let dir = journal_dir(cx);
+    let old_path = dir.path().join("a.txt");
+    let new_path = dir.path().join("b.txt");
+    std::fs::write(&old_path, "a").unwrap();
+
+    let mut ctx = open(&old_path, cx);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.document.update(cx, |document, cx| {
+        document.replace(1..1, "b", cx);
+        document.save_as(new_path.clone(), cx).detach();
+        // Typed while the file is being written
+        document.replace(2..2, "c", cx);
+      })
+    });
+    ctx.cx.run_until_parked();
+
+    let journals = std::fs::read_dir(dir.path().join("journals")).unwrap();
+    assert_eq!(journals.count(), 1);
+    assert_eq!(open(&old_path, cx).text(), "a");
+    assert_eq!(open(&new_path, cx).text(), "abc");
+  }
+
+  #[gpui::test]
+  fn test_journal_of_changed_file_is_dropped(cx: &mut TestAppContext) {
+    let dir = journal_dir(cx);
+    let path = dir.path().join("notes.txt");
+    std::fs::write(&path, "hello").unwrap();
+
+    let mut ctx = open(&path, cx);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor
+        .document
+        .update(cx, |document, cx| document.replace(0..5, "", cx))
+    });
+    std::fs::write(&path, "hi").unwrap();
+
+    let mut ctx = open(&path, cx);
+    assert_eq!(ctx.text(), "hi");
+    assert!(!is_dirty(&mut ctx));
+  }
+}
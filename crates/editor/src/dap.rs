@@ -0,0 +1,698 @@
+//! Debugging through the Debug Adapter Protocol
+//!
+//! Clicking the breakpoint lane of the gutter toggles a breakpoint on a line.
+//! Debugging launches the adapter configured for the file's extension, e.g.
+//! debugpy for Python, and talks DAP with it over its stdin and stdout. While
+//! the program is paused its current line is highlighted and the variables
+//! of the innermost frame are listed in a panel beside the text.
+
+use std::{
+  collections::HashMap,
+  io::{self, BufRead, BufReader, Read, Write},
+  ops::Range,
+  path::Path,
+  process::{Child, ChildStdin, Command, Stdio},
+};
+
+use buffer::Anchor;
+use gpui::{App, Context, Global, SharedString, Task, Window, div, prelude::*, px, yellow};
+use serde_json::{Value, json};
+
+use crate::{
+  decorations::{DecorationId, DecorationStyle},
+  editor::Editor,
+  trust, wrap_map,
+};
+
+/// Placeholder for the open file's path in launch arguments
+const FILE_PLACEHOLDER: &str = "$FILE";
+
+/// Width of the variables panel
+const VARIABLES_PANEL_WIDTH: f32 = 260.0;
+
+/// How to start a debug adapter and launch the program to debug
+#[derive(Clone, Debug)]
+pub struct DebugAdapter {
+  pub command: String,
+  pub args: Vec<String>,
+  /// Arguments of the launch request, with `$FILE` in strings replaced
+  pub launch: Value,
+}
+
+/// Debug adapters by file extension
+pub struct DebugAdapters {
+  by_extension: HashMap<String, DebugAdapter>,
+}
+
+impl Global for DebugAdapters {}
+
+impl Default for DebugAdapters {
+  fn default() -> Self {
+    let mut adapters = Self {
+      by_extension: HashMap::new(),
+    };
+    adapters.set(
+      "py",
+      DebugAdapter {
+        command: "python3".to_string(),
+        args: vec!["-m".to_string(), "debugpy.adapter".to_string()],
+        launch: json!({ "program": FILE_PLACEHOLDER, "console": "internalConsole" }),
+      },
+    );
+    adapters
+  }
+}
+
+impl DebugAdapters {
+  pub fn get(&self, extension: &str) -> Option<&DebugAdapter> {
+    self.by_extension.get(extension)
+  }
+
+  pub fn set(&mut self, extension: impl Into<String>, adapter: DebugAdapter) {
+    self.by_extension.insert(extension.into(), adapter);
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Variable {
+  pub name: String,
+  pub value: String,
+}
+
+#[derive(Default)]
+pub(crate) struct Debugger {
+  /// Start of each line with a breakpoint, following edits
+  breakpoints: Vec<Anchor>,
+  session: Option<DebugSession>,
+}
+
+struct DebugSession {
+  process: Child,
+  stdin: ChildStdin,
+  seq: i64,
+  /// Command of each request awaiting its response, by sequence number
+  pending: HashMap<i64, String>,
+  /// Sent once the adapter has answered `initialize`
+  launch: Option<Value>,
+  thread_id: Option<i64>,
+  /// Line of the open file the program is paused at
+  stopped_line: Option<usize>,
+  execution_line: Option<DecorationId>,
+  variables: Vec<Variable>,
+  _reader: Task<()>,
+}
+
+impl DebugSession {
+  fn request(&mut self, command: &str, arguments: Value) {
+    self.seq += 1;
+    self.pending.insert(self.seq, command.to_string());
+    let message = json!({
+      "seq": self.seq,
+      "type": "request",
+      "command": command,
+      "arguments": arguments,
+    });
+    if let Err(err) = self
+      .stdin
+      .write_all(&encode(&message))
+      .and_then(|_| self.stdin.flush())
+    {
+      eprintln!("Failed to send {} to the debug adapter: {}", command, err);
+    }
+  }
+}
+
+/// `message` with the header DAP frames it with
+fn encode(message: &Value) -> Vec<u8> {
+  let body = message.to_string();
+  format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes()
+}
+
+/// Next message from `reader`, none at the end of the stream
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+  let mut content_length = None;
+  loop {
+    let mut header = String::new();
+    if reader.read_line(&mut header)? == 0 {
+      return Ok(None);
+    }
+    let header = header.trim_end();
+    if header.is_empty() {
+      break;
+    }
+    if let Some(length) = header.strip_prefix("Content-Length:") {
+      content_length = length.trim().parse::<usize>().ok();
+    }
+  }
+  let length = content_length
+    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+  let mut body = vec![0; length];
+  reader.read_exact(&mut body)?;
+  Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// `value` with the placeholder in its strings replaced by `path`
+fn substitute_file(value: &Value, path: &Path) -> Value {
+  match value {
+    Value::String(text) => Value::String(text.replace(FILE_PLACEHOLDER, &path.to_string_lossy())),
+    Value::Array(items) => Value::Array(
+      items
+        .iter()
+        .map(|item| substitute_file(item, path))
+        .collect(),
+    ),
+    Value::Object(fields) => Value::Object(
+      fields
+        .iter()
+        .map(|(key, item)| (key.clone(), substitute_file(item, path)))
+        .collect(),
+    ),
+    other => other.clone(),
+  }
+}
+
+fn parse_variables(body: &Value) -> Vec<Variable> {
+  body["variables"]
+    .as_array()
+    .into_iter()
+    .flatten()
+    .filter_map(|variable| {
+      Some(Variable {
+        name: variable["name"].as_str()?.to_string(),
+        value: variable["value"].as_str().unwrap_or_default().to_string(),
+      })
+    })
+    .collect()
+}
+
+/// Lines with a breakpoint, in order
+pub(crate) fn breakpoint_lines(editor: &Editor, cx: &App) -> Vec<usize> {
+  let document = editor.document.read(cx);
+  let mut lines: Vec<usize> = editor
+    .debugger
+    .breakpoints
+    .iter()
+    .map(|anchor| document.char_to_line(document.resolve_anchor(anchor)))
+    .collect();
+  lines.sort_unstable();
+  lines.dedup();
+  lines
+}
+
+/// Add a breakpoint on `line`, or remove the one there
+pub(crate) fn toggle_breakpoint(editor: &mut Editor, line: usize, cx: &mut Context<Editor>) {
+  let document = editor.document.read(cx);
+  if line >= document.len_lines() {
+    return;
+  }
+  let before = editor.debugger.breakpoints.len();
+  editor
+    .debugger
+    .breakpoints
+    .retain(|anchor| document.char_to_line(document.resolve_anchor(anchor)) != line);
+  if editor.debugger.breakpoints.len() == before {
+    let anchor = document.anchor_before(document.line_to_char(line));
+    editor.debugger.breakpoints.push(anchor);
+  }
+  send_breakpoints(editor, cx);
+  cx.notify();
+}
+
+fn send_breakpoints(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let lines = breakpoint_lines(editor, cx);
+  let Some(path) = editor.file_path().map(Path::to_path_buf) else {
+    return;
+  };
+  let Some(session) = editor.debugger.session.as_mut() else {
+    return;
+  };
+  let breakpoints: Vec<Value> = lines
+    .iter()
+    .map(|line| json!({ "line": line + 1 }))
+    .collect();
+  session.request(
+    "setBreakpoints",
+    json!({ "source": { "path": path }, "breakpoints": breakpoints }),
+  );
+}
+
+pub(crate) fn is_debugging(editor: &Editor) -> bool {
+  editor.debugger.session.is_some()
+}
+
+/// Line the debugged program is paused at, if it is paused in this file
+pub(crate) fn stopped_line(editor: &Editor) -> Option<usize> {
+  editor.debugger.session.as_ref()?.stopped_line
+}
+
+/// Start debugging the open file, or continue if paused
+pub(crate) fn start(editor: &mut Editor, window: &mut Window, cx: &mut Context<Editor>) {
+  if is_debugging(editor) {
+    resume(editor, "continue", cx);
+    return;
+  }
+  if trust::is_restricted(editor, cx) {
+    trust::request_trust(editor, window, cx);
+    return;
+  }
+  if let Err(err) = launch(editor, cx) {
+    eprintln!("Failed to start debugging: {}", err);
+  }
+}
+
+fn launch(editor: &mut Editor, cx: &mut Context<Editor>) -> Result<(), String> {
+  let path = editor
+    .file_path()
+    .map(Path::to_path_buf)
+    .ok_or("the document has no file")?;
+  let extension = path
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .unwrap_or_default();
+  let adapter = cx
+    .default_global::<DebugAdapters>()
+    .get(extension)
+    .cloned()
+    .ok_or_else(|| format!("no debug adapter for .{} files", extension))?;
+
+  let mut command = Command::new(&adapter.command);
+  command
+    .args(&adapter.args)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null());
+  if let Some(workspace) = trust::workspace(editor, cx) {
+    command.current_dir(workspace);
+  }
+  let mut process = command
+    .spawn()
+    .map_err(|err| format!("{}: {}", adapter.command, err))?;
+  let stdin = process.stdin.take().ok_or("no stdin")?;
+  let stdout = process.stdout.take().ok_or("no stdout")?;
+
+  // Messages are read on a thread of their own and handled on the main thread
+  let (sender, receiver) = smol::channel::unbounded();
+  std::thread::spawn(move || {
+    let mut reader = BufReader::new(stdout);
+    while let Ok(Some(message)) = read_message(&mut reader) {
+      if sender.send_blocking(message).is_err() {
+        break;
+      }
+    }
+  });
+  let reader = cx.spawn(async move |this, cx| {
+    while let Ok(message) = receiver.recv().await {
+      if this
+        .update(cx, |editor, cx| handle_message(editor, message, cx))
+        .is_err()
+      {
+        return;
+      }
+    }
+    let _ = this.update(cx, end_session);
+  });
+
+  let mut session = DebugSession {
+    process,
+    stdin,
+    seq: 0,
+    pending: HashMap::new(),
+    launch: Some(substitute_file(&adapter.launch, &path)),
+    thread_id: None,
+    stopped_line: None,
+    execution_line: None,
+    variables: Vec::new(),
+    _reader: reader,
+  };
+  session.request(
+    "initialize",
+    json!({
+      "clientID": "gpui-editor",
+      "adapterID": adapter.command,
+      "linesStartAt1": true,
+      "columnsStartAt1": true,
+      "pathFormat": "path",
+    }),
+  );
+  editor.debugger.session = Some(session);
+  cx.notify();
+  Ok(())
+}
+
+/// Send `command` (`continue`, `next`, `stepIn` or `stepOut`) for the paused thread
+pub(crate) fn resume(editor: &mut Editor, command: &str, cx: &mut Context<Editor>) {
+  let Some(session) = editor.debugger.session.as_mut() else {
+    return;
+  };
+  let Some(thread_id) = session.thread_id else {
+    return;
+  };
+  session.request(command, json!({ "threadId": thread_id }));
+  clear_stopped(editor, cx);
+}
+
+/// Stop debugging, terminating the debugged program
+pub(crate) fn stop(editor: &mut Editor, cx: &mut Context<Editor>) {
+  if let Some(session) = editor.debugger.session.as_mut() {
+    session.request("disconnect", json!({ "terminateDebuggee": true }));
+  }
+  end_session(editor, cx);
+}
+
+/// Stop debugging and drop the breakpoints, for another document
+pub(crate) fn reset(editor: &mut Editor, cx: &mut Context<Editor>) {
+  stop(editor, cx);
+  editor.debugger.breakpoints.clear();
+}
+
+fn end_session(editor: &mut Editor, cx: &mut Context<Editor>) {
+  clear_stopped(editor, cx);
+  if let Some(mut session) = editor.debugger.session.take() {
+    let _ = session.process.kill();
+    let _ = session.process.wait();
+  }
+  cx.notify();
+}
+
+fn clear_stopped(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let Some(session) = editor.debugger.session.as_mut() else {
+    return;
+  };
+  session.stopped_line = None;
+  session.variables.clear();
+  if let Some(id) = session.execution_line.take() {
+    editor.decorations.remove(id);
+  }
+  cx.notify();
+}
+
+fn handle_message(editor: &mut Editor, message: Value, cx: &mut Context<Editor>) {
+  match message["type"].as_str() {
+    Some("response") => {
+      let command = message["request_seq"]
+        .as_i64()
+        .and_then(|seq| editor.debugger.session.as_mut()?.pending.remove(&seq));
+      let Some(command) = command else {
+        return;
+      };
+      if message["success"].as_bool() != Some(true) {
+        eprintln!(
+          "Debug adapter failed {}: {}",
+          command,
+          message["message"].as_str().unwrap_or_default()
+        );
+        return;
+      }
+      handle_response(editor, &command, &message["body"], cx);
+    }
+    Some("event") => handle_event(
+      editor,
+      message["event"].as_str().unwrap_or_default(),
+      &message["body"],
+      cx,
+    ),
+    _ => {}
+  }
+}
+
+fn handle_response(editor: &mut Editor, command: &str, body: &Value, cx: &mut Context<Editor>) {
+  match command {
+    "initialize" => {
+      if let Some(session) = editor.debugger.session.as_mut()
+        && let Some(launch) = session.launch.take()
+      {
+        session.request("launch", launch);
+      }
+    }
+    "stackTrace" => {
+      let frame = &body["stackFrames"][0];
+      let Some(frame_id) = frame["id"].as_i64() else {
+        return;
+      };
+      let in_file = frame["source"]["path"]
+        .as_str()
+        .zip(editor.file_path())
+        .is_some_and(|(path, file)| same_file(Path::new(path), file));
+      if in_file && let Some(line) = frame["line"].as_u64() {
+        show_stopped_line((line as usize).saturating_sub(1), editor, cx);
+      }
+      if let Some(session) = editor.debugger.session.as_mut() {
+        session.request("scopes", json!({ "frameId": frame_id }));
+      }
+    }
+    "scopes" => {
+      // The first scope holds the locals
+      if let Some(reference) = body["scopes"][0]["variablesReference"].as_i64()
+        && let Some(session) = editor.debugger.session.as_mut()
+      {
+        session.request("variables", json!({ "variablesReference": reference }));
+      }
+    }
+    "variables" => {
+      if let Some(session) = editor.debugger.session.as_mut() {
+        session.variables = parse_variables(body);
+        cx.notify();
+      }
+    }
+    _ => {}
+  }
+}
+
+fn handle_event(editor: &mut Editor, event: &str, body: &Value, cx: &mut Context<Editor>) {
+  match event {
+    "initialized" => {
+      send_breakpoints(editor, cx);
+      if let Some(session) = editor.debugger.session.as_mut() {
+        session.request("configurationDone", json!({}));
+      }
+    }
+    "stopped" => {
+      if let Some(session) = editor.debugger.session.as_mut() {
+        session.thread_id = body["threadId"].as_i64().or(session.thread_id);
+        if let Some(thread_id) = session.thread_id {
+          session.request(
+            "stackTrace",
+            json!({ "threadId": thread_id, "startFrame": 0, "levels": 1 }),
+          );
+        }
+      }
+    }
+    "continued" => clear_stopped(editor, cx),
+    "terminated" | "exited" => end_session(editor, cx),
+    _ => {}
+  }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+  a == b
+    || a
+      .canonicalize()
+      .is_ok_and(|a| b.canonicalize().is_ok_and(|b| a == b))
+}
+
+/// Highlight `line` as the one the program is paused at and scroll to it
+fn show_stopped_line(line: usize, editor: &mut Editor, cx: &mut Context<Editor>) {
+  if !is_debugging(editor) {
+    return;
+  }
+  clear_stopped(editor, cx);
+  let range: Range<Anchor> = {
+    let document = editor.document.read(cx);
+    if line >= document.len_lines() {
+      return;
+    }
+    let start = document.line_to_char(line);
+    let end = start
+      + document
+        .line_content(line)
+        .map_or(0, |content| content.chars().count());
+    document.anchor_range(start..end)
+  };
+  let mut background = yellow();
+  background.a = 0.2;
+  let id = editor.decorations.insert(
+    range,
+    DecorationStyle {
+      background: Some(background),
+      gutter_background: Some(background),
+      ..Default::default()
+    },
+  );
+  if let Some(session) = editor.debugger.session.as_mut() {
+    session.stopped_line = Some(line);
+    session.execution_line = Some(id);
+  }
+
+  wrap_map::refresh(editor, cx);
+  let row = wrap_map::rows_for_line(editor, line, cx).start;
+  let visible_rows = (editor.viewport_height / editor.line_height).floor() as usize;
+  let first_row = editor.scroll_offset_y as usize;
+  if row < first_row || row >= first_row + visible_rows {
+    editor.scroll_offset_y = row.saturating_sub(visible_rows / 2) as f32;
+  }
+  cx.notify();
+}
+
+/// Variables of the paused frame, beside the text
+pub(crate) fn render_variables_panel(editor: &Editor) -> Option<impl IntoElement> {
+  let session = editor.debugger.session.as_ref()?;
+  session.stopped_line?;
+  Some(
+    div()
+      .id("debug-variables")
+      .w(px(VARIABLES_PANEL_WIDTH))
+      .h_full()
+      .flex()
+      .flex_col()
+      .p_1()
+      .overflow_y_scroll()
+      .bg(editor.theme.gutter_background())
+      .text_size(px(12.0))
+      .child(
+        div()
+          .text_color(editor.theme.line_number())
+          .child("Variables"),
+      )
+      .children(session.variables.iter().map(|variable| {
+        div().child(SharedString::from(format!(
+          "{} = {}",
+          variable.name, variable.value
+        )))
+      })),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[test]
+  fn test_message_framing() {
+    let request = json!({ "seq": 1, "type": "request", "command": "initialize" });
+    let mut stream = encode(&request);
+    stream.extend(encode(&json!({ "type": "event", "event": "initialized" })));
+
+    let mut reader = io::Cursor::new(stream);
+    assert_eq!(read_message(&mut reader).unwrap(), Some(request));
+    assert_eq!(
+      read_message(&mut reader).unwrap().unwrap()["event"],
+      "initialized"
+    );
+    assert_eq!(read_message(&mut reader).unwrap(), None);
+
+    let mut reader = io::Cursor::new(b"Content-Type: json\r\n\r\n{}".to_vec());
+    assert!(read_message(&mut reader).is_err());
+  }
+
+  #[test]
+  fn test_launch_arguments() {
+    let launch =
+      json!({ "program": "$FILE", "args": ["--input", "$FILE.txt"], "stopOnEntry": true });
+    assert_eq!(
+      substitute_file(&launch, Path::new("/src/main.py")),
+      json!({ "program": "/src/main.py", "args": ["--input", "/src/main.py.txt"], "stopOnEntry": true })
+    );
+  }
+
+  #[test]
+  fn test_parse_variables() {
+    let body = json!({ "variables": [
+      { "name": "x", "value": "42", "variablesReference": 0 },
+      { "value": "nameless" },
+      { "name": "items", "value": "[1, 2]", "variablesReference": 7 },
+    ] });
+    let names: Vec<_> = parse_variables(&body)
+      .into_iter()
+      .map(|variable| format!("{}={}", variable.name, variable.value))
+      .collect();
+    assert_eq!(names, ["x=42", "items=[1, 2]"]);
+  }
+
+  #[gpui::test]
+  fn test_breakpoints_follow_edits(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "a = 1\nb = 2\nc = 3\n");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      toggle_breakpoint(editor, 1, cx);
+      toggle_breakpoint(editor, 2, cx);
+      toggle_breakpoint(editor, 9, cx);
+      assert_eq!(breakpoint_lines(editor, cx), [1, 2]);
+
+      editor
+        .document
+        .update(cx, |doc, cx| doc.insert_char(0, '\n', cx));
+      assert_eq!(breakpoint_lines(editor, cx), [2, 3]);
+
+      toggle_breakpoint(editor, 2, cx);
+      assert_eq!(breakpoint_lines(editor, cx), [3]);
+    });
+  }
+
+  /// A session with an adapter that ignores every request
+  fn fake_session() -> DebugSession {
+    let mut process = Command::new("cat")
+      .stdin(Stdio::piped())
+      .stdout(Stdio::null())
+      .spawn()
+      .unwrap();
+    DebugSession {
+      stdin: process.stdin.take().unwrap(),
+      process,
+      seq: 0,
+      pending: HashMap::new(),
+      launch: None,
+      thread_id: None,
+      stopped_line: None,
+      execution_line: None,
+      variables: Vec::new(),
+      _reader: Task::ready(()),
+    }
+  }
+
+  /// Answer the last request sent
+  fn respond(editor: &mut Editor, body: Value, cx: &mut Context<Editor>) {
+    let seq = editor.debugger.session.as_ref().unwrap().seq;
+    let response = json!({ "type": "response", "request_seq": seq, "success": true, "body": body });
+    handle_message(editor, response, cx);
+  }
+
+  #[gpui::test]
+  fn test_pause_and_continue(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "a = 1\nb = 2\n");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.file_path = Some("/src/main.py".into());
+      editor.debugger.session = Some(fake_session());
+
+      let stopped = json!({ "type": "event", "event": "stopped", "body": { "threadId": 3 } });
+      handle_message(editor, stopped, cx);
+      let frame = json!({ "id": 9, "line": 2, "source": { "path": "/src/main.py" } });
+      respond(editor, json!({ "stackFrames": [frame] }), cx);
+      assert_eq!(stopped_line(editor), Some(1));
+      assert!(!editor.decorations.is_empty());
+
+      respond(
+        editor,
+        json!({ "scopes": [{ "variablesReference": 4 }] }),
+        cx,
+      );
+      respond(
+        editor,
+        json!({ "variables": [{ "name": "a", "value": "1" }] }),
+        cx,
+      );
+      let session = editor.debugger.session.as_ref().unwrap();
+      assert_eq!(session.thread_id, Some(3));
+      assert_eq!(session.variables.len(), 1);
+      assert!(render_variables_panel(editor).is_some());
+
+      resume(editor, "next", cx);
+      assert_eq!(stopped_line(editor), None);
+      assert!(editor.decorations.is_empty());
+
+      let terminated = json!({ "type": "event", "event": "terminated" });
+      handle_message(editor, terminated, cx);
+      assert!(!is_debugging(editor));
+    });
+  }
+}
@@ -0,0 +1,368 @@
+//! Line decorations that host applications attach to ranges of the document
+//!
+//! Decorations are stored with anchors so they follow edits, and are resolved
+//! against the current document contents at render time. The anchors are rebased
+//! and the decorations sorted once per document version, so a frame only resolves
+//! the decorations near the viewport.
+
+use std::{
+  cell::{Cell, RefCell},
+  ops::Range,
+};
+
+use buffer::Anchor;
+use gpui::{Hsla, SharedString};
+
+use crate::document::Document;
+
+pub type DecorationId = usize;
+
+/// Icon painted in the gutter next to the first line of a decoration
+#[derive(Clone, Debug)]
+pub struct GutterIcon {
+  pub glyph: SharedString,
+  pub color: Hsla,
+}
+
+/// Text painted after the end of the last line of a decoration
+#[derive(Clone, Debug)]
+pub struct VirtualText {
+  pub text: SharedString,
+  pub color: Hsla,
+}
+
+/// Visual style of a line decoration, every part is optional
+#[derive(Clone, Debug, Default)]
+pub struct DecorationStyle {
+  /// Tint painted behind the full width of every decorated line
+  pub background: Option<Hsla>,
+  /// Tint painted behind the gutter of every decorated line
+  pub gutter_background: Option<Hsla>,
+  pub gutter_icon: Option<GutterIcon>,
+  /// Underline painted below the decorated text range
+  pub underline: Option<Hsla>,
+  pub virtual_text: Option<VirtualText>,
+}
+
+/// Who added a decoration, so that clearing the host's decorations leaves the
+/// ones the editor's own features keep track of
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Owner {
+  Editor,
+  Host,
+}
+
+#[derive(Clone, Debug)]
+pub struct LineDecoration {
+  pub id: DecorationId,
+  owner: Owner,
+  // Rebased to the current version whenever the document changes, so resolving
+  // the anchors only walks the edits made since the last frame
+  start: Cell<Anchor>,
+  end: Cell<Anchor>,
+  pub style: DecorationStyle,
+}
+
+impl LineDecoration {
+  pub fn range(&self) -> Range<Anchor> {
+    self.start.get()..self.end.get()
+  }
+}
+
+/// A decoration resolved against the current document contents
+pub struct ResolvedDecoration<'a> {
+  pub range: Range<usize>,
+  /// Lines covered by the decoration (end exclusive)
+  pub lines: Range<usize>,
+  pub style: &'a DecorationStyle,
+}
+
+/// Decorations in document order, as of a document version
+#[derive(Default)]
+struct DecorationIndex {
+  version: Option<usize>,
+  /// Resolved range and position in `decorations`, sorted by start
+  ranges: Vec<(Range<usize>, usize)>,
+  /// Length in chars of the longest decoration, how far before the viewport
+  /// a decoration can start and still reach into it
+  longest: usize,
+}
+
+#[derive(Default)]
+pub struct DecorationSet {
+  next_id: DecorationId,
+  decorations: Vec<LineDecoration>,
+  index: RefCell<DecorationIndex>,
+}
+
+impl DecorationSet {
+  pub fn insert(&mut self, range: Range<Anchor>, style: DecorationStyle) -> DecorationId {
+    self.insert_owned(Owner::Editor, range, style)
+  }
+
+  /// Add a decoration of the host application
+  pub fn insert_host(&mut self, range: Range<Anchor>, style: DecorationStyle) -> DecorationId {
+    self.insert_owned(Owner::Host, range, style)
+  }
+
+  fn insert_owned(
+    &mut self,
+    owner: Owner,
+    range: Range<Anchor>,
+    style: DecorationStyle,
+  ) -> DecorationId {
+    let id = self.next_id;
+    self.next_id += 1;
+    self.decorations.push(LineDecoration {
+      id,
+      owner,
+      start: Cell::new(range.start),
+      end: Cell::new(range.end),
+      style,
+    });
+    self.index.get_mut().version = None;
+    id
+  }
+
+  pub fn remove(&mut self, id: DecorationId) -> bool {
+    self.remove_where(|decoration| decoration.id == id)
+  }
+
+  /// Remove a decoration of the host application, never one of the editor's
+  pub fn remove_host(&mut self, id: DecorationId) -> bool {
+    self.remove_where(|decoration| decoration.id == id && decoration.owner == Owner::Host)
+  }
+
+  /// Remove every decoration of the host application
+  pub fn clear_host(&mut self) -> bool {
+    self.remove_where(|decoration| decoration.owner == Owner::Host)
+  }
+
+  fn remove_where(&mut self, remove: impl Fn(&LineDecoration) -> bool) -> bool {
+    let len = self.decorations.len();
+    self.decorations.retain(|decoration| !remove(decoration));
+    self.index.get_mut().version = None;
+    self.decorations.len() != len
+  }
+
+  pub fn clear(&mut self) {
+    self.decorations.clear();
+    *self.index.get_mut() = DecorationIndex::default();
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.decorations.is_empty()
+  }
+
+  /// Resolve all decorations touching at least one line in `lines`
+  ///
+  /// Only the decorations starting close enough to `lines` to reach them are
+  /// resolved, the others are skipped through the index.
+  pub fn resolve_in_lines(
+    &self,
+    lines: Range<usize>,
+    document: &Document,
+  ) -> Vec<ResolvedDecoration<'_>> {
+    if self.decorations.is_empty() || lines.is_empty() {
+      return Vec::new();
+    }
+    self.refresh_index(document);
+    let index = self.index.borrow();
+
+    let line_count = document.len_lines();
+    let first_char = document.line_to_char(lines.start.min(line_count));
+    let last_char = if lines.end < line_count {
+      document.line_to_char(lines.end)
+    } else {
+      document.len()
+    };
+    let earliest = first_char.saturating_sub(index.longest);
+    let from = index
+      .ranges
+      .partition_point(|(range, _)| range.start < earliest);
+
+    index.ranges[from..]
+      .iter()
+      .take_while(|(range, _)| range.start <= last_char)
+      .filter_map(|(range, position)| {
+        let decoration_lines = lines_for_range(range, document);
+        (decoration_lines.start < lines.end && decoration_lines.end > lines.start).then(|| {
+          ResolvedDecoration {
+            range: range.clone(),
+            lines: decoration_lines,
+            style: &self.decorations[*position].style,
+          }
+        })
+      })
+      .collect()
+  }
+
+  /// Rebase the anchors and re-sort the decorations once per document version
+  fn refresh_index(&self, document: &Document) {
+    let mut index = self.index.borrow_mut();
+    if index.version == Some(document.version()) {
+      return;
+    }
+
+    index.ranges.clear();
+    index.longest = 0;
+    for (position, decoration) in self.decorations.iter().enumerate() {
+      decoration
+        .start
+        .set(document.rebase_anchor(&decoration.start.get()));
+      decoration
+        .end
+        .set(document.rebase_anchor(&decoration.end.get()));
+      let range = document.resolve_anchor_range(&decoration.range());
+      index.longest = index.longest.max(range.len());
+      index.ranges.push((range, position));
+    }
+    index.ranges.sort_by_key(|(range, _)| range.start);
+    index.version = Some(document.version());
+  }
+}
+
+/// Lines covered by a char range; a range ending at a line start doesn't cover that line
+fn lines_for_range(range: &Range<usize>, document: &Document) -> Range<usize> {
+  let start_line = document.char_to_line(range.start);
+  let end_line = if range.end > range.start {
+    document.char_to_line(range.end - 1)
+  } else {
+    start_line
+  };
+  start_line..end_line + 1
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use gpui::{AppContext, TestAppContext, red};
+
+  fn tint() -> DecorationStyle {
+    DecorationStyle {
+      background: Some(red()),
+      ..Default::default()
+    }
+  }
+
+  #[gpui::test]
+  fn test_insert_and_remove(cx: &mut TestAppContext) {
+    let doc = cx.new(|cx| Document::new("line1\nline2\nline3", None, cx));
+    doc.read_with(cx, |doc, _| {
+      let mut set = DecorationSet::default();
+      let first = set.insert(doc.anchor_range(0..5), tint());
+      let second = set.insert(doc.anchor_range(6..11), tint());
+
+      assert_ne!(first, second);
+      assert!(set.remove(first));
+      assert!(!set.remove(first));
+      assert_eq!(set.resolve_in_lines(0..3, doc).len(), 1);
+
+      set.clear();
+      assert!(set.is_empty());
+    });
+  }
+
+  #[gpui::test]
+  fn test_clear_host_keeps_editor_decorations(cx: &mut TestAppContext) {
+    let doc = cx.new(|cx| Document::new("line1\nline2\nline3", None, cx));
+    doc.read_with(cx, |doc, _| {
+      let mut set = DecorationSet::default();
+      let own = set.insert(doc.anchor_range(0..5), tint());
+      let host = set.insert_host(doc.anchor_range(6..11), tint());
+      set.insert_host(doc.anchor_range(12..17), tint());
+
+      // The host can't remove the editor's decorations
+      assert!(!set.remove_host(own));
+      assert!(set.remove_host(host));
+      assert!(set.clear_host());
+      assert!(!set.clear_host());
+      let resolved = set.resolve_in_lines(0..3, doc);
+      assert_eq!(resolved.len(), 1);
+      assert_eq!(resolved[0].lines, 0..1);
+      assert!(set.remove(own));
+    });
+  }
+
+  #[gpui::test]
+  fn test_resolve_in_lines(cx: &mut TestAppContext) {
+    let doc = cx.new(|cx| Document::new("line1\nline2\nline3\nline4", None, cx));
+    doc.read_with(cx, |doc, _| {
+      let mut set = DecorationSet::default();
+      // Whole line 1 including its newline
+      set.insert(doc.anchor_range(6..12), tint());
+
+      let resolved = set.resolve_in_lines(0..4, doc);
+      assert_eq!(resolved.len(), 1);
+      assert_eq!(resolved[0].lines, 1..2);
+
+      assert!(set.resolve_in_lines(2..4, doc).is_empty());
+    });
+  }
+
+  #[gpui::test]
+  fn test_decoration_follows_edits(cx: &mut TestAppContext) {
+    let doc = cx.new(|cx| Document::new("line1\nline2\nline3", None, cx));
+    let mut set = DecorationSet::default();
+    doc.read_with(cx, |doc, _| {
+      set.insert(doc.anchor_range(12..17), tint());
+    });
+
+    doc.update(cx, |doc, cx| {
+      doc.replace(0..0, "line0\n", cx);
+    });
+
+    doc.read_with(cx, |doc, _| {
+      let resolved = set.resolve_in_lines(0..4, doc);
+      assert_eq!(resolved[0].range, 18..23);
+      assert_eq!(resolved[0].lines, 3..4);
+    });
+  }
+
+  #[gpui::test]
+  fn test_resolve_from_index(cx: &mut TestAppContext) {
+    let text = (0..100).map(|i| format!("line{i}\n")).collect::<String>();
+    let doc = cx.new(|cx| Document::new(&text, None, cx));
+    let mut set = DecorationSet::default();
+    doc.read_with(cx, |doc, _| {
+      // Inserted out of document order, one spanning lines 10 to 40
+      set.insert(
+        doc.anchor_range(doc.line_to_char(50)..doc.line_to_char(51)),
+        tint(),
+      );
+      set.insert(
+        doc.anchor_range(doc.line_to_char(10)..doc.line_to_char(41)),
+        tint(),
+      );
+      set.insert(
+        doc.anchor_range(doc.line_to_char(2)..doc.line_to_char(3)),
+        tint(),
+      );
+    });
+
+    doc.update(cx, |doc, cx| {
+      doc.replace(0..0, "new\n", cx);
+    });
+
+    doc.read_with(cx, |doc, _| {
+      let lines = |resolved: Vec<ResolvedDecoration>| {
+        resolved.iter().map(|d| d.lines.clone()).collect::<Vec<_>>()
+      };
+      assert_eq!(
+        lines(set.resolve_in_lines(0..100, doc)),
+        vec![3..4, 11..42, 51..52]
+      );
+      assert_eq!(lines(set.resolve_in_lines(30..35, doc)), vec![11..42]);
+      assert!(set.resolve_in_lines(60..70, doc).is_empty());
+
+      let index = set.index.borrow();
+      assert_eq!(index.version, Some(doc.version()));
+      assert!(
+        set
+          .decorations
+          .iter()
+          .all(|d| d.range().start == doc.rebase_anchor(&d.range().start))
+      );
+    });
+  }
+}
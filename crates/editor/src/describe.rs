@@ -0,0 +1,202 @@
+//! Popup describing the character at the caret
+//!
+//! Shows the grapheme under the caret broken down into its code points, with
+//! their UTF-8 bytes and Unicode names, which tells apart characters that
+//! look the same or don't render at all.
+
+use gpui::{Context, IntoElement, div, prelude::*, px};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{document::Document, editor::Editor};
+
+/// Characters read past the caret to find the grapheme; longer ones are cut
+const MAX_GRAPHEME_LEN: usize = 32;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct CodePoint {
+  pub ch: char,
+  pub name: String,
+}
+
+impl CodePoint {
+  pub fn new(ch: char) -> Self {
+    let name = unicode_names2::name(ch)
+      .map(|name| name.to_string())
+      .unwrap_or_else(|| unnamed(ch).to_string());
+    Self { ch, name }
+  }
+
+  /// UTF-8 encoding as hex bytes, e.g. `CC 81`
+  pub fn utf8_bytes(&self) -> String {
+    let mut buf = [0; 4];
+    self
+      .ch
+      .encode_utf8(&mut buf)
+      .bytes()
+      .map(|byte| format!("{:02X}", byte))
+      .collect::<Vec<_>>()
+      .join(" ")
+  }
+
+  pub fn label(&self) -> String {
+    format!(
+      "U+{:04X}  {:<11}  {}",
+      self.ch as u32,
+      self.utf8_bytes(),
+      self.name
+    )
+  }
+}
+
+/// Name of a character without one in the Unicode name list
+fn unnamed(ch: char) -> &'static str {
+  match ch {
+    '\0' => "NULL",
+    '\t' => "CHARACTER TABULATION",
+    '\n' => "LINE FEED",
+    '\r' => "CARRIAGE RETURN",
+    '\u{1B}' => "ESCAPE",
+    '\u{7F}' => "DELETE",
+    '\u{85}' => "NEXT LINE",
+    ch if ch.is_control() => "<control>",
+    '\u{E000}'..='\u{F8FF}' | '\u{F0000}'..='\u{10FFFF}' => "<private use>",
+    _ => "<unassigned>",
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct CharacterDescription {
+  /// Offset of the grapheme in the document
+  pub offset: usize,
+  pub grapheme: String,
+  pub code_points: Vec<CodePoint>,
+}
+
+impl CharacterDescription {
+  pub fn new(offset: usize, grapheme: &str) -> Self {
+    Self {
+      offset,
+      grapheme: grapheme.to_string(),
+      code_points: grapheme.chars().map(CodePoint::new).collect(),
+    }
+  }
+
+  pub fn summary(&self) -> String {
+    let count = |n: usize, what: &str| format!("{} {}{}", n, what, if n == 1 { "" } else { "s" });
+    format!(
+      "{}, {}",
+      count(self.code_points.len(), "code point"),
+      count(self.grapheme.len(), "byte")
+    )
+  }
+}
+
+/// The grapheme starting at `offset`, none at the end of the document
+pub(crate) fn describe_at(document: &Document, offset: usize) -> Option<CharacterDescription> {
+  let end = (offset + MAX_GRAPHEME_LEN).min(document.len());
+  if offset >= end {
+    return None;
+  }
+  let text = document.slice_to_string(offset..end);
+  let grapheme = text.graphemes(true).next()?;
+  Some(CharacterDescription::new(offset, grapheme))
+}
+
+/// Describe the character at the caret, or hide the popup if there is none
+pub(crate) fn show(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let offset = editor.cursor_offset();
+  editor.character_description = describe_at(editor.document.read(cx), offset);
+  cx.notify();
+}
+
+pub(crate) fn dismiss(editor: &mut Editor, cx: &mut Context<Editor>) {
+  if editor.character_description.take().is_some() {
+    cx.notify();
+  }
+}
+
+pub(crate) fn render_popup(
+  editor: &Editor,
+  description: &CharacterDescription,
+) -> impl IntoElement {
+  div()
+    .flex()
+    .flex_col()
+    .gap_1()
+    .p_2()
+    .rounded_md()
+    .border_1()
+    .border_color(editor.theme.line_number())
+    .bg(editor.theme.gutter_background())
+    .when_some(editor.font.family.clone(), |el, family| {
+      el.font_family(family)
+    })
+    .text_size(px(12.0))
+    .child(description.summary())
+    .children(
+      description
+        .code_points
+        .iter()
+        .map(|code_point| code_point.label()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[test]
+  fn test_combining_sequence() {
+    let description = CharacterDescription::new(0, "e\u{301}");
+
+    assert_eq!(description.summary(), "2 code points, 3 bytes");
+    assert_eq!(
+      description.code_points[0].label(),
+      "U+0065  65           LATIN SMALL LETTER E"
+    );
+    assert_eq!(description.code_points[1].utf8_bytes(), "CC 81");
+    assert_eq!(description.code_points[1].name, "COMBINING ACUTE ACCENT");
+  }
+
+  #[test]
+  fn test_invisible_and_control_names() {
+    assert_eq!(CodePoint::new('\u{200B}').name, "ZERO WIDTH SPACE");
+    assert_eq!(CodePoint::new('\u{202E}').name, "RIGHT-TO-LEFT OVERRIDE");
+    assert_eq!(CodePoint::new('\n').name, "LINE FEED");
+    assert_eq!(CodePoint::new('\u{7}').name, "<control>");
+    assert_eq!(CodePoint::new('\u{E000}').name, "<private use>");
+    assert_eq!(CodePoint::new('😀').utf8_bytes(), "F0 9F 98 80");
+  }
+
+  #[gpui::test]
+  fn test_describe_at_caret(cx: &mut TestAppContext) {
+    // A family emoji is a single grapheme of five code points
+    let mut ctx = EditorTestContext::with_text(
+      cx.clone(),
+      "a\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\r\n",
+    );
+    ctx.set_cursor(1);
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      show(editor, cx);
+      let description = editor.character_description.clone().unwrap();
+      assert_eq!(description.offset, 1);
+      assert_eq!(description.code_points.len(), 5);
+      assert_eq!(description.code_points[1].name, "ZERO WIDTH JOINER");
+
+      // CRLF is one grapheme too
+      editor.move_to(6, cx);
+      show(editor, cx);
+      assert_eq!(
+        editor.character_description.as_ref().unwrap().grapheme,
+        "\r\n"
+      );
+
+      editor.move_to(8, cx);
+      show(editor, cx);
+      assert!(editor.character_description.is_none());
+    });
+  }
+}
@@ -0,0 +1,524 @@
+//! Line and word differences between two texts
+//!
+//! Lines are compared with Myers' algorithm, which finds the fewest lines
+//! to delete and insert, or with patience diff, which first lines up the
+//! lines appearing once in each text and only then matches the braces and
+//! blank lines between them. Lines replaced in place are then compared word
+//! by word. The algorithm used for lines is set as a global, Myers by
+//! default.
+
+use std::{collections::HashMap, hash::Hash, ops::Range};
+
+use gpui::Global;
+
+use crate::boundaries;
+
+/// Most lines deleted and inserted that are matched exactly; bigger changes
+/// count as one hunk
+const MAX_EDIT_DISTANCE: usize = 1_000;
+
+/// How lines of two texts are matched up
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiffAlgorithm {
+  /// The shortest edit script
+  #[default]
+  Myers,
+  /// Lines unique to both texts anchor the match, Myers fills in between
+  Patience,
+}
+
+impl Global for DiffAlgorithm {}
+
+/// A run of lines of the new text replacing a run of the old one
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct LineHunk {
+  pub old_lines: Range<usize>,
+  pub new_lines: Range<usize>,
+  pub old_text: String,
+}
+
+/// Changed lines, and the changed words on lines replaced in place
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct LineChanges {
+  pub hunks: Vec<LineHunk>,
+  /// Byte ranges of the changed words in the new text
+  pub changed_words: Vec<Range<usize>>,
+}
+
+/// Hunks of changes between `old` and `new`, as the ranges they replace in
+/// each, in order
+pub fn diff<T: Eq + Hash>(
+  old: &[T],
+  new: &[T],
+  algorithm: DiffAlgorithm,
+) -> Vec<(Range<usize>, Range<usize>)> {
+  let mut matches = Vec::new();
+  match algorithm {
+    DiffAlgorithm::Myers => myers(old, new, 0, 0, &mut matches),
+    DiffAlgorithm::Patience => patience(old, new, 0, 0, &mut matches),
+  }
+  hunks(&matches, old.len(), new.len())
+}
+
+/// Replacements turning `old` into `new` a whole line at a time, as byte
+/// ranges of `old` and their new text, in order
+pub fn line_edits(old: &str, new: &str, algorithm: DiffAlgorithm) -> Vec<(Range<usize>, String)> {
+  let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+  let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+  let old_starts = line_starts(&old_lines);
+  let new_starts = line_starts(&new_lines);
+  diff(&old_lines, &new_lines, algorithm)
+    .into_iter()
+    .map(|(old_range, new_range)| {
+      (
+        old_starts[old_range.start]..old_starts[old_range.end],
+        new[new_starts[new_range.start]..new_starts[new_range.end]].to_string(),
+      )
+    })
+    .collect()
+}
+
+/// Byte offset of each line, and of the end
+fn line_starts(lines: &[&str]) -> Vec<usize> {
+  let mut starts = Vec::with_capacity(lines.len() + 1);
+  let mut offset = 0;
+  starts.push(0);
+  for line in lines {
+    offset += line.len();
+    starts.push(offset);
+  }
+  starts
+}
+
+/// Lines of `new` differing from `old`, and the words that differ on each
+/// line replaced in place from the line it replaced
+pub(crate) fn line_changes(old: &str, new: &str, algorithm: DiffAlgorithm) -> LineChanges {
+  let old_lines = lines(old);
+  let new_lines = lines(new);
+  let old_texts: Vec<&str> = old_lines.iter().map(|(_, line)| *line).collect();
+  let new_texts: Vec<&str> = new_lines.iter().map(|(_, line)| *line).collect();
+
+  let mut changes = LineChanges::default();
+  let line_start = |ix: usize| old_lines.get(ix).map_or(old.len(), |(start, _)| *start);
+  for (old_range, new_range) in diff(&old_texts, &new_texts, algorithm) {
+    changes.hunks.push(LineHunk {
+      old_lines: old_range.clone(),
+      new_lines: new_range.clone(),
+      old_text: old[line_start(old_range.start)..line_start(old_range.end)].to_string(),
+    });
+    // Pure insertions and deletions have no old line to compare with
+    for (old_ix, new_ix) in old_range.zip(new_range) {
+      let (line_start, new_line) = new_lines[new_ix];
+      let old_line = old_lines[old_ix].1;
+      changes.changed_words.extend(
+        changed_words(old_line, new_line)
+          .into_iter()
+          .map(|word| line_start + word.start..line_start + word.end),
+      );
+    }
+  }
+  changes
+}
+
+/// Start offset and text without line break of each line
+fn lines(text: &str) -> Vec<(usize, &str)> {
+  let mut offset = 0;
+  text
+    .split_inclusive('\n')
+    .map(|line| {
+      let start = offset;
+      offset += line.len();
+      (start, line.trim_end_matches(['\n', '\r']))
+    })
+    .collect()
+}
+
+/// Byte ranges of the words of `new` that aren't in `old`, merged when adjacent
+pub(crate) fn changed_words(old: &str, new: &str) -> Vec<Range<usize>> {
+  let old_tokens = tokens(old);
+  let new_tokens = tokens(new);
+  let old_texts: Vec<&str> = old_tokens.iter().map(|range| &old[range.clone()]).collect();
+  let new_texts: Vec<&str> = new_tokens.iter().map(|range| &new[range.clone()]).collect();
+
+  let mut words: Vec<Range<usize>> = Vec::new();
+  for (_, new_range) in diff(&old_texts, &new_texts, DiffAlgorithm::Myers) {
+    // Changed whitespace alone isn't worth underlining
+    let changed = new_tokens[new_range]
+      .iter()
+      .filter(|range| !new[(*range).clone()].trim().is_empty());
+    for range in changed {
+      match words.last_mut() {
+        Some(last) if new[last.end..range.start].trim().is_empty() => last.end = range.end,
+        _ => words.push(range.clone()),
+      }
+    }
+  }
+  words
+}
+
+fn tokens(text: &str) -> Vec<Range<usize>> {
+  boundaries::word_bounds(text)
+    .windows(2)
+    .map(|bounds| bounds[0]..bounds[1])
+    .collect()
+}
+
+/// The ranges between matched pairs of indices
+fn hunks(matches: &[(usize, usize)], n: usize, m: usize) -> Vec<(Range<usize>, Range<usize>)> {
+  let mut hunks = Vec::new();
+  let (mut i, mut j) = (0, 0);
+  for &(next_i, next_j) in matches.iter().chain([(n, m)].iter()) {
+    if next_i > i || next_j > j {
+      hunks.push((i..next_i, j..next_j));
+    }
+    (i, j) = (next_i + 1, next_j + 1);
+  }
+  hunks
+}
+
+/// Number of equal items at the start and at the end of `old` and `new`,
+/// not overlapping
+fn common_affixes<T: PartialEq>(old: &[T], new: &[T]) -> (usize, usize) {
+  let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+  let suffix = old[prefix..]
+    .iter()
+    .rev()
+    .zip(new[prefix..].iter().rev())
+    .take_while(|(a, b)| a == b)
+    .count();
+  (prefix, suffix)
+}
+
+/// Push the pairs of indices a shortest edit script from `old` to `new`
+/// keeps, offset by `old_start` and `new_start`
+fn myers<T: PartialEq>(
+  old: &[T],
+  new: &[T],
+  old_start: usize,
+  new_start: usize,
+  matches: &mut Vec<(usize, usize)>,
+) {
+  let (prefix, suffix) = common_affixes(old, new);
+  matches.extend((0..prefix).map(|ix| (old_start + ix, new_start + ix)));
+  let middle_old = &old[prefix..old.len() - suffix];
+  let middle_new = &new[prefix..new.len() - suffix];
+  if !middle_old.is_empty() && !middle_new.is_empty() {
+    let found = shortest_edit(middle_old, middle_new).unwrap_or_default();
+    matches.extend(
+      found
+        .into_iter()
+        .map(|(i, j)| (old_start + prefix + i, new_start + prefix + j)),
+    );
+  }
+  let (old_end, new_end) = (old_start + old.len(), new_start + new.len());
+  matches.extend(
+    (0..suffix)
+      .rev()
+      .map(|ix| (old_end - 1 - ix, new_end - 1 - ix)),
+  );
+}
+
+/// The pairs of indices kept by a shortest edit script, none when it would
+/// take more than `MAX_EDIT_DISTANCE` edits
+fn shortest_edit<T: PartialEq>(old: &[T], new: &[T]) -> Option<Vec<(usize, usize)>> {
+  let (n, m) = (old.len() as isize, new.len() as isize);
+  // Furthest x reached on each diagonal k = x - y after d edits, at k + d
+  let mut trace: Vec<Vec<isize>> = Vec::new();
+  for d in 0..=(n + m).min(MAX_EDIT_DISTANCE as isize) {
+    let mut row = vec![0; 2 * d as usize + 1];
+    for k in (-d..=d).step_by(2) {
+      let mut x = match trace.last() {
+        None => 0,
+        Some(prev) => {
+          let at = |k: isize| prev[(k + d - 1) as usize];
+          if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            at(k + 1)
+          } else {
+            at(k - 1) + 1
+          }
+        }
+      };
+      let mut y = x - k;
+      while x < n && y < m && old[x as usize] == new[y as usize] {
+        x += 1;
+        y += 1;
+      }
+      row[(k + d) as usize] = x;
+      if x >= n && y >= m {
+        trace.push(row);
+        return Some(backtrack(&trace, n, m));
+      }
+    }
+    trace.push(row);
+  }
+  None
+}
+
+/// Walk the furthest reaching paths back from the end, collecting the
+/// diagonal moves
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<(usize, usize)> {
+  let mut matches = Vec::new();
+  let (mut x, mut y) = (n, m);
+  for d in (1..trace.len() as isize).rev() {
+    let k = x - y;
+    let at = |k: isize| trace[d as usize - 1][(k + d - 1) as usize];
+    let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+      k + 1
+    } else {
+      k - 1
+    };
+    let prev_x = at(prev_k);
+    let prev_y = prev_x - prev_k;
+    // The snake starts right after the insertion or deletion
+    let (snake_x, snake_y) = if prev_k == k + 1 {
+      (prev_x, prev_y + 1)
+    } else {
+      (prev_x + 1, prev_y)
+    };
+    while x > snake_x && y > snake_y {
+      x -= 1;
+      y -= 1;
+      matches.push((x as usize, y as usize));
+    }
+    (x, y) = (prev_x, prev_y);
+  }
+  while x > 0 && y > 0 {
+    x -= 1;
+    y -= 1;
+    matches.push((x as usize, y as usize));
+  }
+  matches.reverse();
+  matches
+}
+
+/// Push the pairs of indices patience diff keeps, offset by `old_start` and
+/// `new_start`
+fn patience<T: Eq + Hash>(
+  old: &[T],
+  new: &[T],
+  old_start: usize,
+  new_start: usize,
+  matches: &mut Vec<(usize, usize)>,
+) {
+  let (prefix, suffix) = common_affixes(old, new);
+  matches.extend((0..prefix).map(|ix| (old_start + ix, new_start + ix)));
+  let middle_old = &old[prefix..old.len() - suffix];
+  let middle_new = &new[prefix..new.len() - suffix];
+  let (middle_old_start, middle_new_start) = (old_start + prefix, new_start + prefix);
+
+  let anchors = unique_anchors(middle_old, middle_new);
+  if anchors.is_empty() {
+    myers(
+      middle_old,
+      middle_new,
+      middle_old_start,
+      middle_new_start,
+      matches,
+    );
+  } else {
+    let (mut i, mut j) = (0, 0);
+    for (anchor_i, anchor_j) in anchors {
+      patience(
+        &middle_old[i..anchor_i],
+        &middle_new[j..anchor_j],
+        middle_old_start + i,
+        middle_new_start + j,
+        matches,
+      );
+      matches.push((middle_old_start + anchor_i, middle_new_start + anchor_j));
+      (i, j) = (anchor_i + 1, anchor_j + 1);
+    }
+    patience(
+      &middle_old[i..],
+      &middle_new[j..],
+      middle_old_start + i,
+      middle_new_start + j,
+      matches,
+    );
+  }
+
+  let (old_end, new_end) = (old_start + old.len(), new_start + new.len());
+  matches.extend(
+    (0..suffix)
+      .rev()
+      .map(|ix| (old_end - 1 - ix, new_end - 1 - ix)),
+  );
+}
+
+/// Pairs of indices of the items appearing once in both `old` and `new`,
+/// the longest run of them in the same order in both
+fn unique_anchors<T: Eq + Hash>(old: &[T], new: &[T]) -> Vec<(usize, usize)> {
+  // Count and last index of each item in old, then in new
+  let mut counts: HashMap<&T, (usize, usize, usize, usize)> = HashMap::new();
+  for (ix, item) in old.iter().enumerate() {
+    let entry = counts.entry(item).or_default();
+    entry.0 += 1;
+    entry.1 = ix;
+  }
+  for (ix, item) in new.iter().enumerate() {
+    if let Some(entry) = counts.get_mut(item) {
+      entry.2 += 1;
+      entry.3 = ix;
+    }
+  }
+  let pairs: Vec<(usize, usize)> = new
+    .iter()
+    .filter_map(|item| match counts.get(item) {
+      Some(&(1, old_ix, 1, new_ix)) => Some((old_ix, new_ix)),
+      _ => None,
+    })
+    .collect();
+  longest_increasing(&pairs)
+}
+
+/// The longest run of `pairs`, ordered by their second index, whose first
+/// indices increase too, found by patience sorting
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+  // Index in pairs of the top card of each pile, and each card's predecessor
+  let mut piles: Vec<usize> = Vec::new();
+  let mut previous: Vec<Option<usize>> = Vec::with_capacity(pairs.len());
+  for (ix, (old_ix, _)) in pairs.iter().enumerate() {
+    let pile = piles.partition_point(|&top| pairs[top].0 < *old_ix);
+    previous.push(pile.checked_sub(1).map(|pile| piles[pile]));
+    if pile == piles.len() {
+      piles.push(ix);
+    } else {
+      piles[pile] = ix;
+    }
+  }
+  let mut run = Vec::with_capacity(piles.len());
+  let mut card = piles.last().copied();
+  while let Some(ix) = card {
+    run.push(pairs[ix]);
+    card = previous[ix];
+  }
+  run.reverse();
+  run
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// `old` with `edits` applied, back to front so offsets stay valid
+  fn apply(old: &str, edits: &[(Range<usize>, String)]) -> String {
+    let mut text = old.to_string();
+    for (range, new_text) in edits.iter().rev() {
+      text.replace_range(range.clone(), new_text);
+    }
+    text
+  }
+
+  /// Length of the longest common subsequence, by dynamic programming
+  fn lcs_len(old: &[&str], new: &[&str]) -> usize {
+    let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+      for j in (0..new.len()).rev() {
+        lengths[i][j] = if old[i] == new[j] {
+          lengths[i + 1][j + 1] + 1
+        } else {
+          lengths[i + 1][j].max(lengths[i][j + 1])
+        };
+      }
+    }
+    lengths[0][0]
+  }
+
+  #[test]
+  fn test_diff() {
+    let old = ["a", "b", "c", "d"];
+    for algorithm in [DiffAlgorithm::Myers, DiffAlgorithm::Patience] {
+      assert_eq!(diff(&old, &old, algorithm), []);
+      assert_eq!(diff(&old, &["a", "x", "c", "d"], algorithm), [(1..2, 1..2)]);
+      assert_eq!(
+        diff(&old, &["a", "c", "d", "e"], algorithm),
+        [(1..2, 1..1), (4..4, 3..4)]
+      );
+      assert_eq!(diff(&old, &[], algorithm), [(0..4, 0..0)]);
+    }
+  }
+
+  #[test]
+  fn test_patience_anchors() {
+    // The braces appear twice, so only the bodies anchor, in one order
+    let old = ["{", "a", "}", "{", "b", "}"];
+    let new = ["{", "b", "}", "{", "a", "}"];
+    assert_eq!(unique_anchors(&old, &new), [(1, 4)]);
+    assert_eq!(
+      longest_increasing(&[(3, 0), (0, 1), (1, 2), (4, 3), (2, 4)]),
+      [(0, 1), (1, 2), (2, 4)]
+    );
+    // Within the outer braces the inner ones are unique and anchor instead,
+    // so each body is replaced in place
+    assert_eq!(
+      diff(&old, &new, DiffAlgorithm::Patience),
+      [(1..2, 1..2), (4..5, 4..5)]
+    );
+  }
+
+  #[test]
+  fn test_line_edits_reproduce_new_text() {
+    // A fixed seed keeps failures reproducible
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut random = |bound: u64| {
+      state ^= state << 13;
+      state ^= state >> 7;
+      state ^= state << 17;
+      state % bound
+    };
+    let words = ["a\n", "b\n", "c\n", "}\n", "\n", "d"];
+    for _ in 0..500 {
+      let mut text = || -> String {
+        (0..random(12))
+          .map(|_| words[random(words.len() as u64) as usize])
+          .collect()
+      };
+      let (old, new) = (text(), text());
+      for algorithm in [DiffAlgorithm::Myers, DiffAlgorithm::Patience] {
+        let edits = line_edits(&old, &new, algorithm);
+        assert_eq!(apply(&old, &edits), new, "{:?} -> {:?}", old, new);
+      }
+      // Myers keeps as many lines as any edit script can
+      let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+      let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+      let kept = old_lines.len()
+        - diff(&old_lines, &new_lines, DiffAlgorithm::Myers)
+          .iter()
+          .map(|(old_range, _)| old_range.len())
+          .sum::<usize>();
+      assert_eq!(kept, lcs_len(&old_lines, &new_lines));
+    }
+  }
+
+  #[test]
+  fn test_changed_words() {
+    let new = "let total = count * 2;";
+    let words: Vec<_> = changed_words("let sum = count + 1;", new)
+      .into_iter()
+      .map(|range| &new[range])
+      .collect();
+    assert_eq!(words, ["total", "* 2"]);
+    assert!(changed_words("let x = 1;", "let  x = 1;").is_empty());
+  }
+
+  #[test]
+  fn test_line_changes() {
+    let old = "fn main() {\n  let a = 1;\n  call(a);\n}\n";
+    let new = "fn main() {\n  let a = 2;\n  log();\n  call(a);\n}\n";
+    let changes = line_changes(old, new, DiffAlgorithm::Myers);
+    assert_eq!(
+      changes.hunks,
+      [LineHunk {
+        old_lines: 1..2,
+        new_lines: 1..3,
+        old_text: "  let a = 1;\n".to_string(),
+      }]
+    );
+    let words: Vec<_> = changes
+      .changed_words
+      .iter()
+      .map(|range| &new[range.clone()])
+      .collect();
+    assert_eq!(words, ["2"]);
+  }
+}
@@ -0,0 +1,821 @@
+//! Mapping between buffer offsets and display points
+//!
+//! What is painted differs from the buffer in three ways: folded lines are
+//! hidden behind the line starting the fold, inlays add text that isn't in
+//! the buffer (inlay hints, and virtual text placed after a line's end), and
+//! soft wrap splits long lines into rows at the wrap column. A display map
+//! composes them into one mapping between char offsets of the buffer and
+//! display points, a row and a column counted in chars of what is shown.
+//!
+//! An inlay sits before the character at its offset, so every column of the
+//! inlay maps back to that offset. A fold keeps its first line visible,
+//! followed by a placeholder, and offsets in the hidden lines map to the end
+//! of that line.
+//!
+//! After an edit the map is spliced rather than built again: only the edited
+//! lines are measured, and the lines after them are shifted.
+
+use std::ops::Range;
+
+use gpui::Hsla;
+
+/// Shown after the first line of a fold
+pub const FOLD_PLACEHOLDER: &str = " ⋯ ";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DisplayPoint {
+  pub row: usize,
+  pub column: usize,
+}
+
+impl DisplayPoint {
+  pub fn new(row: usize, column: usize) -> Self {
+    Self { row, column }
+  }
+}
+
+/// Text shown at a buffer offset without being part of the buffer
+#[derive(Clone, Debug, PartialEq)]
+pub struct Inlay {
+  pub offset: usize,
+  pub text: String,
+  /// Color the text is painted in, the text color if none
+  pub color: Option<Hsla>,
+}
+
+/// Part of an inlay, or of a fold placeholder, shown on a row
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InlayChunk {
+  /// Display column the part starts at, within the row
+  pub column: usize,
+  /// Buffer offset the inlay sits before
+  pub offset: usize,
+  /// Index of the inlay in `DisplayMap::inlays`, none for a fold placeholder
+  pub inlay: Option<usize>,
+  /// Chars of the inlay's text shown on the row
+  pub chars: Range<usize>,
+}
+
+/// A buffer line that is shown, with what changes its display
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct DisplayLine {
+  line: usize,
+  /// Char range of the line, without its line break
+  range: Range<usize>,
+  /// Column of the buffer line each inlay sits before, and its width; a
+  /// fold placeholder comes last
+  inlays: Vec<(usize, usize)>,
+  /// Whether the line starts a fold
+  folded: bool,
+  first_row: usize,
+  row_count: usize,
+}
+
+impl DisplayLine {
+  fn width(&self) -> usize {
+    self.range.len() + self.inlays.iter().map(|(_, width)| width).sum::<usize>()
+  }
+
+  /// Display column of buffer column `column`, within the whole line
+  fn display_column(&self, column: usize) -> usize {
+    column
+      + self
+        .inlays
+        .iter()
+        .filter(|(inlay_column, _)| *inlay_column < column)
+        .map(|(_, width)| width)
+        .sum::<usize>()
+  }
+
+  /// Buffer column shown at `display_column` of the whole line
+  fn buffer_column(&self, display_column: usize) -> usize {
+    let mut inlay_width = 0;
+    for &(column, width) in &self.inlays {
+      let start = column + inlay_width;
+      if display_column < start {
+        break;
+      }
+      if display_column <= start + width {
+        return column;
+      }
+      inlay_width += width;
+    }
+    (display_column - inlay_width).min(self.range.len())
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DisplayMap {
+  lines: Vec<DisplayLine>,
+  /// Width and index of each shown line, widest first, so a new wrap column
+  /// only visits the lines wide enough to wrap differently
+  lines_by_width: Vec<(usize, usize)>,
+  /// Buffer lines hidden by folds, in order
+  folded: Vec<Range<usize>>,
+  /// Inlays in order of offset, those in hidden lines included
+  inlays: Vec<Inlay>,
+  wrap_column: Option<usize>,
+  line_count: usize,
+  row_count: usize,
+}
+
+/// Shown line `line` with char range `range`, with the inlays from `inlays`
+/// that sit on it, starting at `first_row`
+fn display_line<'a>(
+  line: usize,
+  range: Range<usize>,
+  inlays: &mut std::iter::Peekable<impl Iterator<Item = &'a Inlay>>,
+  folded: bool,
+  wrap_column: Option<usize>,
+  first_row: usize,
+) -> DisplayLine {
+  let mut line_inlays = Vec::new();
+  // Inlays before this line were in hidden lines
+  while inlays.next_if(|inlay| inlay.offset < range.start).is_some() {}
+  while let Some(inlay) = inlays.next_if(|inlay| inlay.offset <= range.end) {
+    line_inlays.push((inlay.offset - range.start, inlay.text.chars().count()));
+  }
+  if folded {
+    line_inlays.push((range.len(), FOLD_PLACEHOLDER.chars().count()));
+  }
+  let mut display_line = DisplayLine {
+    line,
+    range,
+    inlays: line_inlays,
+    folded,
+    first_row,
+    row_count: 1,
+  };
+  if let Some(wrap_column) = wrap_column {
+    display_line.row_count = display_line.width().div_ceil(wrap_column).max(1);
+  }
+  display_line
+}
+
+/// Width and index of `lines`, widest first
+fn by_width(lines: &[DisplayLine]) -> Vec<(usize, usize)> {
+  let mut lines_by_width: Vec<(usize, usize)> = lines
+    .iter()
+    .enumerate()
+    .map(|(ix, line)| (line.width(), ix))
+    .collect();
+  lines_by_width.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+  lines_by_width
+}
+
+impl DisplayMap {
+  /// Map the buffer lines with char ranges `lines`, without line breaks
+  ///
+  /// A fold `start..end` of buffer lines hides the lines after `start` up to
+  /// `end`; overlapping folds are merged. Rows wrap after `wrap_column`
+  /// chars, without looking for word boundaries.
+  pub fn new(
+    lines: &[Range<usize>],
+    folds: &[Range<usize>],
+    inlays: &[Inlay],
+    wrap_column: Option<usize>,
+  ) -> Self {
+    let wrap_column = wrap_column.filter(|&column| column > 0);
+    let folded = merge_folds(folds, lines.len());
+
+    let mut inlays = inlays.to_vec();
+    inlays.sort_by_key(|inlay| inlay.offset);
+    let mut inlay_iter = inlays.iter().peekable();
+
+    let mut display_lines = Vec::new();
+    let mut folds = folded.iter().peekable();
+    let mut row_count = 0;
+    let mut line = 0;
+    while line < lines.len() {
+      let fold = folds.next_if(|fold| fold.start == line + 1);
+      let display_line = display_line(
+        line,
+        lines[line].clone(),
+        &mut inlay_iter,
+        fold.is_some(),
+        wrap_column,
+        row_count,
+      );
+      row_count += display_line.row_count;
+      display_lines.push(display_line);
+      line = fold.map_or(line + 1, |fold| fold.end);
+    }
+
+    Self {
+      lines_by_width: by_width(&display_lines),
+      lines: display_lines,
+      folded,
+      inlays,
+      wrap_column,
+      line_count: lines.len(),
+      row_count,
+    }
+  }
+
+  /// Update the map after an edit replaced buffer lines `old_lines` with
+  /// `new_lines`, whose char ranges are `ranges`, moving the text after it
+  /// by `char_delta` chars; `inlays` are all inlays after the edit
+  ///
+  /// The map ends up as `DisplayMap::new` would build it for the new text.
+  /// Only the edited lines are measured again, the lines after them are
+  /// shifted. An edit touching a fold, or inlays changing away from the
+  /// edit, can't be spliced: false is returned, the map left as it was, and
+  /// it has to be built again.
+  pub fn splice(
+    &mut self,
+    old_lines: Range<usize>,
+    new_lines: Range<usize>,
+    ranges: &[Range<usize>],
+    char_delta: isize,
+    inlays: &[Inlay],
+  ) -> bool {
+    debug_assert_eq!(new_lines.len(), ranges.len());
+    if old_lines.is_empty()
+      || new_lines.start != old_lines.start
+      || old_lines.end > self.line_count
+      || self
+        .folded
+        .iter()
+        .any(|fold| fold.start - 1 < old_lines.end && fold.end > old_lines.start)
+    {
+      return false;
+    }
+    // No fold touches the old lines, so all of them are shown
+    let Ok(first) = self
+      .lines
+      .binary_search_by_key(&old_lines.start, |line| line.line)
+    else {
+      return false;
+    };
+    let last = first + old_lines.len();
+    let old_chars = self.lines[first].range.start..self.lines[last - 1].range.end;
+    let new_chars = ranges[0].start..ranges[ranges.len() - 1].end;
+    let shift = |offset: usize| offset.checked_add_signed(char_delta);
+
+    let mut inlays = inlays.to_vec();
+    inlays.sort_by_key(|inlay| inlay.offset);
+    let old_before = self
+      .inlays
+      .partition_point(|inlay| inlay.offset < old_chars.start);
+    let old_after = self
+      .inlays
+      .partition_point(|inlay| inlay.offset <= old_chars.end);
+    let new_before = inlays.partition_point(|inlay| inlay.offset < new_chars.start);
+    let new_after = inlays.partition_point(|inlay| inlay.offset <= new_chars.end);
+    let unchanged = self.inlays[..old_before] == inlays[..new_before]
+      && self.inlays.len() - old_after == inlays.len() - new_after
+      && self.inlays[old_after..]
+        .iter()
+        .zip(&inlays[new_after..])
+        .all(|(old, new)| {
+          shift(old.offset) == Some(new.offset) && old.text == new.text && old.color == new.color
+        });
+    if !unchanged {
+      return false;
+    }
+
+    let mut inlay_iter = inlays[new_before..new_after].iter().peekable();
+    let mut row = self.lines[first].first_row;
+    let spliced: Vec<DisplayLine> = new_lines
+      .clone()
+      .zip(ranges.iter().cloned())
+      .map(|(line, range)| {
+        let display_line = display_line(line, range, &mut inlay_iter, false, self.wrap_column, row);
+        row += display_line.row_count;
+        display_line
+      })
+      .collect();
+    let old_rows = self.lines[last - 1].first_row + self.lines[last - 1].row_count;
+    let row_delta = row as isize - old_rows as isize;
+    let line_delta = new_lines.len() as isize - old_lines.len() as isize;
+
+    let ix_delta = spliced.len() as isize - (last - first) as isize;
+    let new_widths: Vec<(usize, usize)> = spliced
+      .iter()
+      .enumerate()
+      .map(|(ix, line)| (line.width(), first + ix))
+      .collect();
+    self.lines.splice(first..last, spliced);
+    for line in &mut self.lines[first + new_widths.len()..] {
+      line.line = line.line.wrapping_add_signed(line_delta);
+      line.range = line.range.start.wrapping_add_signed(char_delta)
+        ..line.range.end.wrapping_add_signed(char_delta);
+      line.first_row = line.first_row.wrapping_add_signed(row_delta);
+    }
+    self
+      .lines_by_width
+      .retain(|(_, ix)| !(first..last).contains(ix));
+    for (_, ix) in &mut self.lines_by_width {
+      if *ix >= last {
+        *ix = ix.wrapping_add_signed(ix_delta);
+      }
+    }
+    for entry in new_widths {
+      let at = self
+        .lines_by_width
+        .partition_point(|&(width, ix)| width > entry.0 || (width == entry.0 && ix < entry.1));
+      self.lines_by_width.insert(at, entry);
+    }
+    for fold in &mut self.folded {
+      if fold.start > old_lines.end {
+        *fold =
+          fold.start.wrapping_add_signed(line_delta)..fold.end.wrapping_add_signed(line_delta);
+      }
+    }
+    self.inlays = inlays;
+    self.line_count = self.line_count.wrapping_add_signed(line_delta);
+    self.row_count = self.row_count.wrapping_add_signed(row_delta);
+    true
+  }
+
+  /// Inlays in order of offset
+  pub fn inlays(&self) -> &[Inlay] {
+    &self.inlays
+  }
+
+  /// Buffer lines the map was built for
+  pub fn line_count(&self) -> usize {
+    self.line_count
+  }
+
+  pub fn row_count(&self) -> usize {
+    self.row_count
+  }
+
+  pub fn wrap_column(&self) -> Option<usize> {
+    self.wrap_column
+  }
+
+  /// Wrap rows after `wrap_column` chars instead, returning the buffer lines
+  /// whose rows changed, in order
+  ///
+  /// Lines that fit on one row at both columns are left alone, and rows are
+  /// only renumbered from the first line that changed, so resizing with a
+  /// few long lines stays cheap on large files.
+  pub fn set_wrap_column(&mut self, wrap_column: Option<usize>) -> Vec<usize> {
+    let wrap_column = wrap_column.filter(|&column| column > 0);
+    if self.wrap_column == wrap_column {
+      return Vec::new();
+    }
+    let fits = match (self.wrap_column, wrap_column) {
+      (Some(old), Some(new)) => old.min(new),
+      (Some(column), None) | (None, Some(column)) => column,
+      (None, None) => return Vec::new(),
+    };
+    self.wrap_column = wrap_column;
+
+    let mut changed = Vec::new();
+    for &(width, ix) in self
+      .lines_by_width
+      .iter()
+      .take_while(|(width, _)| *width > fits)
+    {
+      let row_count = wrap_column.map_or(1, |column| width.div_ceil(column).max(1));
+      if self.lines[ix].row_count != row_count {
+        self.lines[ix].row_count = row_count;
+        changed.push(ix);
+      }
+    }
+    changed.sort_unstable();
+
+    if let Some(&first) = changed.first() {
+      let mut row = self.lines[first].first_row;
+      for line in &mut self.lines[first..] {
+        line.first_row = row;
+        row += line.row_count;
+      }
+      self.row_count = row;
+    }
+    changed.into_iter().map(|ix| self.lines[ix].line).collect()
+  }
+
+  pub fn is_hidden(&self, line: usize) -> bool {
+    self.folded.iter().any(|fold| fold.contains(&line))
+  }
+
+  fn line_at_row(&self, row: usize) -> Option<&DisplayLine> {
+    let ix = self
+      .lines
+      .partition_point(|line| line.first_row <= row)
+      .checked_sub(1)?;
+    let line = &self.lines[ix];
+    (row < line.first_row + line.row_count).then_some(line)
+  }
+
+  /// The buffer line shown on `row`
+  pub fn buffer_line(&self, row: usize) -> Option<usize> {
+    Some(self.line_at_row(row)?.line)
+  }
+
+  /// Rows showing buffer line `line`, none if it is hidden
+  pub fn rows_for_line(&self, line: usize) -> Option<Range<usize>> {
+    let ix = self.lines.binary_search_by_key(&line, |l| l.line).ok()?;
+    let line = &self.lines[ix];
+    Some(line.first_row..line.first_row + line.row_count)
+  }
+
+  /// Parts of inlays and fold placeholders shown on `row`, left to right
+  pub fn inlays_in_row(&self, row: usize) -> Vec<InlayChunk> {
+    let Some(line) = self.line_at_row(row) else {
+      return Vec::new();
+    };
+    let row_start = self
+      .wrap_column
+      .map_or(0, |column| (row - line.first_row) * column);
+    let row_end = row_start + self.row_len(row);
+    let first_inlay = self
+      .inlays
+      .partition_point(|inlay| inlay.offset < line.range.start);
+
+    let mut chunks = Vec::new();
+    let mut inlay_width = 0;
+    for (ix, &(column, width)) in line.inlays.iter().enumerate() {
+      let start = column + inlay_width;
+      inlay_width += width;
+      let shown = start.max(row_start)..(start + width).min(row_end);
+      if shown.is_empty() {
+        continue;
+      }
+      let is_placeholder = line.folded && ix + 1 == line.inlays.len();
+      chunks.push(InlayChunk {
+        column: shown.start - row_start,
+        offset: line.range.start + column,
+        inlay: (!is_placeholder).then_some(first_inlay + ix),
+        chars: shown.start - start..shown.end - start,
+      });
+    }
+    chunks
+  }
+
+  /// Columns shown on `row`
+  pub fn row_len(&self, row: usize) -> usize {
+    let Some(line) = self.line_at_row(row) else {
+      return 0;
+    };
+    let width = line.width();
+    match self.wrap_column {
+      Some(wrap_column) => {
+        let start = (row - line.first_row) * wrap_column;
+        (width - start).min(wrap_column)
+      }
+      None => width,
+    }
+  }
+
+  /// Where buffer offset `offset` is shown; offsets in hidden lines are shown
+  /// at the end of the fold's first line
+  pub fn to_display_point(&self, offset: usize) -> DisplayPoint {
+    let Some(ix) = self
+      .lines
+      .partition_point(|line| line.range.start <= offset)
+      .checked_sub(1)
+    else {
+      return DisplayPoint::default();
+    };
+    let line = &self.lines[ix];
+    let column = line.display_column(offset.min(line.range.end) - line.range.start);
+    match self.wrap_column {
+      Some(wrap_column) => {
+        // The end of a full row is the start of the next, but the end of the line stays on its last row
+        let row = (column / wrap_column).min(line.row_count - 1);
+        DisplayPoint::new(line.first_row + row, column - row * wrap_column)
+      }
+      None => DisplayPoint::new(line.first_row, column),
+    }
+  }
+
+  /// Buffer offset shown at `point`, clipped to the row, or the last row
+  ///
+  /// A column past the end of a wrapped row maps to its last character, as
+  /// its end is the start of the next row.
+  pub fn to_offset(&self, point: DisplayPoint) -> usize {
+    let row = point.row.min(self.row_count.saturating_sub(1));
+    let Some(line) = self.line_at_row(row) else {
+      return 0;
+    };
+    let row_in_line = row - line.first_row;
+    let row_len = self.row_len(row);
+    let column = if row_in_line + 1 < line.row_count {
+      point.column.min(row_len.saturating_sub(1))
+    } else {
+      point.column.min(row_len)
+    };
+    let display_column = self.wrap_column.unwrap_or(0) * row_in_line + column;
+    line.range.start + line.buffer_column(display_column)
+  }
+}
+
+/// Hidden line ranges of `folds`, merged and within `line_count`
+fn merge_folds(folds: &[Range<usize>], line_count: usize) -> Vec<Range<usize>> {
+  let mut hidden: Vec<Range<usize>> = folds
+    .iter()
+    .map(|fold| fold.start + 1..fold.end.min(line_count))
+    .filter(|range| !range.is_empty())
+    .collect();
+  hidden.sort_by_key(|range| range.start);
+
+  let mut merged: Vec<Range<usize>> = Vec::new();
+  for range in hidden {
+    match merged.last_mut() {
+      // A fold whose first line is hidden by another is part of it
+      Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+      _ => merged.push(range),
+    }
+  }
+  merged
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Char ranges of the lines of `text`, without line breaks
+  fn lines_of(text: &str) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for line in text.split('\n') {
+      let len = line.trim_end_matches('\r').chars().count();
+      lines.push(start..start + len);
+      start += line.chars().count() + 1;
+    }
+    lines
+  }
+
+  fn inlay(offset: usize, text: &str) -> Inlay {
+    Inlay {
+      offset,
+      text: text.to_string(),
+      color: None,
+    }
+  }
+
+  #[test]
+  fn test_plain_lines() {
+    let map = DisplayMap::new(&lines_of("ab\r\ncd\n\nlast"), &[], &[], None);
+    assert_eq!(map.row_count(), 4);
+    assert_eq!(map.to_display_point(5), DisplayPoint::new(1, 1));
+    // Inside a CRLF break is the end of the line
+    assert_eq!(map.to_display_point(3), DisplayPoint::new(0, 2));
+    assert_eq!(map.to_offset(DisplayPoint::new(0, 9)), 2);
+    assert_eq!(map.to_offset(DisplayPoint::new(9, 1)), 9);
+  }
+
+  #[test]
+  fn test_inlays() {
+    // "let x = 1;" with a type hint after `x` and virtual text after the end
+    let inlays = [inlay(5, ": i32"), inlay(10, "  // note")];
+    let map = DisplayMap::new(&lines_of("let x = 1;"), &[], &inlays, None);
+    assert_eq!(map.row_len(0), 24);
+    assert_eq!(map.to_display_point(5), DisplayPoint::new(0, 5));
+    assert_eq!(map.to_display_point(6), DisplayPoint::new(0, 11));
+    for column in 5..=10 {
+      assert_eq!(map.to_offset(DisplayPoint::new(0, column)), 5);
+    }
+    assert_eq!(map.to_offset(DisplayPoint::new(0, 24)), 10);
+  }
+
+  #[test]
+  fn test_inlays_in_row() {
+    let chunk = |column, offset, inlay, chars| InlayChunk {
+      column,
+      offset,
+      inlay,
+      chars,
+    };
+    let inlays = [inlay(5, ": i32"), inlay(10, "  // note")];
+    let map = DisplayMap::new(&lines_of("let x = 1;"), &[], &inlays, Some(8));
+    assert_eq!(map.row_count(), 3);
+    assert_eq!(map.inlays_in_row(0), [chunk(5, 5, Some(0), 0..3)]);
+    assert_eq!(
+      map.inlays_in_row(1),
+      [chunk(0, 5, Some(0), 3..5), chunk(7, 10, Some(1), 0..1)]
+    );
+    assert_eq!(map.inlays_in_row(2), [chunk(0, 10, Some(1), 1..9)]);
+
+    let text = "fn a() {\n  1\n}\nfn b() {}";
+    let map = DisplayMap::new(&lines_of(text), &[0..2], &[inlay(20, "x")], None);
+    assert_eq!(map.inlays_in_row(0), [chunk(8, 8, None, 0..3)]);
+    assert_eq!(map.inlays_in_row(2), [chunk(5, 20, Some(0), 0..1)]);
+  }
+
+  #[test]
+  fn test_soft_wrap() {
+    let map = DisplayMap::new(&lines_of("abcdefg\nhi"), &[], &[], Some(3));
+    assert_eq!(map.row_count(), 4);
+    assert_eq!(map.rows_for_line(1), Some(3..4));
+    assert_eq!(map.buffer_line(2), Some(0));
+    assert_eq!(
+      (0..4).map(|row| map.row_len(row)).collect::<Vec<_>>(),
+      [3, 3, 1, 2]
+    );
+    assert_eq!(map.to_display_point(3), DisplayPoint::new(1, 0));
+    assert_eq!(map.to_display_point(7), DisplayPoint::new(2, 1));
+    // Past the end of a wrapped row is its last character
+    assert_eq!(map.to_offset(DisplayPoint::new(0, 5)), 2);
+
+    let full = DisplayMap::new(&lines_of("abcdef"), &[], &[], Some(3));
+    assert_eq!(full.row_count(), 2);
+    assert_eq!(full.to_display_point(6), DisplayPoint::new(1, 3));
+  }
+
+  #[test]
+  fn test_set_wrap_column() {
+    let text = "short\na much longer line\n\nmid-length";
+    let lines = lines_of(text);
+    let inlays = [inlay(2, "::T")];
+    let mut map = DisplayMap::new(&lines, &[], &inlays, None);
+    // The first line is as wide as the column and still fits
+    assert_eq!(map.set_wrap_column(Some(8)), [1, 3]);
+    assert_eq!(map, DisplayMap::new(&lines, &[], &inlays, Some(8)));
+    // Only the longest line wraps differently
+    assert_eq!(map.set_wrap_column(Some(9)), [1]);
+    assert_eq!(map, DisplayMap::new(&lines, &[], &inlays, Some(9)));
+    assert!(map.set_wrap_column(Some(9)).is_empty());
+    assert_eq!(map.set_wrap_column(Some(0)), [1, 3]);
+    assert_eq!(map, DisplayMap::new(&lines, &[], &inlays, None));
+  }
+
+  #[test]
+  fn test_folds() {
+    let text = "fn a() {\n  1\n  2\n}\nfn b() {}";
+    let map = DisplayMap::new(&lines_of(text), &[0..4], &[inlay(12, "x")], None);
+    assert_eq!(map.row_count(), 2);
+    assert!(map.is_hidden(3) && !map.is_hidden(0) && !map.is_hidden(4));
+    assert_eq!(map.rows_for_line(2), None);
+    assert_eq!(map.buffer_line(1), Some(4));
+    assert_eq!(map.row_len(0), 8 + FOLD_PLACEHOLDER.chars().count());
+    // Hidden offsets show at the end of the first line, before the placeholder
+    assert_eq!(map.to_display_point(14), DisplayPoint::new(0, 8));
+    assert_eq!(map.to_offset(DisplayPoint::new(0, 10)), 8);
+    assert_eq!(map.to_display_point(19), DisplayPoint::new(1, 0));
+
+    assert_eq!(merge_folds(&[4..6, 0..2, 1..3, 9..20], 10), [1..3, 5..6]);
+  }
+
+  /// Splicing an edit into a map gives the map built for the new text,
+  /// unless the edit touches a fold
+  #[test]
+  fn test_splice() {
+    let text = "fn a() {\n  one\n}\n\nfn b() {\n  two\n  three\n}\nend";
+    let edits = [
+      (0..0, "x"),
+      (12..13, ""),
+      (10..15, "a\nb\nc"),
+      (16..17, ""),
+      (17..18, "\n\n"),
+      (20..20, "é"),
+      (40..45, "!\n"),
+      (44..46, "d\r\nnew"),
+      (0..46, "new"),
+    ];
+    let fold_sets: [&[Range<usize>]; 2] = [&[], &[4..7]];
+    // Offset of `offset` after replacing `range` with `inserted` chars
+    let transform = |offset: usize, range: &Range<usize>, inserted: usize| {
+      if offset < range.start {
+        offset
+      } else if offset >= range.end && !(range.is_empty() && offset == range.start) {
+        offset - range.len() + inserted
+      } else {
+        range.start
+      }
+    };
+
+    for (range, inserted) in edits {
+      let mut new_text: Vec<char> = text.chars().collect();
+      new_text.splice(range.clone(), inserted.chars());
+      let new_text: String = new_text.into_iter().collect();
+      let inserted_len = inserted.chars().count();
+      let old_lines = lines_of(text);
+      let new_lines = lines_of(&new_text);
+      let line_of = |lines: &[Range<usize>], offset: usize| {
+        lines.partition_point(|line| line.start <= offset) - 1
+      };
+      let first = line_of(&new_lines, range.start);
+      let last = line_of(&new_lines, range.start + inserted_len);
+      let line_delta = new_lines.len() as isize - old_lines.len() as isize;
+      let old_last = last.wrapping_add_signed(-line_delta);
+      let char_delta = inserted_len as isize - range.len() as isize;
+
+      for folds in fold_sets {
+        for wrap in [None, Some(4)] {
+          let inlays = vec![inlay(8, "  // a"), inlay(46, "  // end"), inlay(29, ": T")];
+          let new_inlays: Vec<Inlay> = inlays
+            .iter()
+            .map(|old| Inlay {
+              offset: transform(old.offset, &range, inserted_len),
+              ..old.clone()
+            })
+            .collect();
+          let new_folds: Vec<Range<usize>> = folds
+            .iter()
+            .map(|fold| {
+              if fold.start > old_last {
+                fold.start.wrapping_add_signed(line_delta)..fold.end.wrapping_add_signed(line_delta)
+              } else {
+                fold.clone()
+              }
+            })
+            .collect();
+
+          let mut map = DisplayMap::new(&old_lines, folds, &inlays, wrap);
+          let spliced = map.splice(
+            first..old_last + 1,
+            first..last + 1,
+            &new_lines[first..last + 1],
+            char_delta,
+            &new_inlays,
+          );
+          let context = format!(
+            "{:?} {:?} folds {:?} wrap {:?}",
+            range, inserted, folds, wrap
+          );
+          let touches_fold = folds
+            .iter()
+            .any(|fold| fold.start <= old_last && fold.end > first);
+          assert_eq!(spliced, !touches_fold, "{}", context);
+          if spliced {
+            let expected = DisplayMap::new(&new_lines, &new_folds, &new_inlays, wrap);
+            assert_eq!(map, expected, "{}", context);
+          }
+        }
+      }
+    }
+
+    // Inlays changing away from the edit can't be spliced
+    let lines = lines_of("ab\ncd");
+    let mut map = DisplayMap::new(&lines, &[], &[inlay(4, "x")], None);
+    assert!(!map.splice(0..1, 0..1, &[0..2], 0, &[inlay(5, "x")]));
+    assert_eq!(map, DisplayMap::new(&lines, &[], &[inlay(4, "x")], None));
+  }
+
+  /// Every shown offset round-trips, and display points map to offsets in
+  /// order, with any combination of wrap, folds and inlays
+  #[test]
+  fn test_round_trip() {
+    let texts = [
+      "",
+      "a",
+      "héllo wörld\r\n\nfn main() {\n  body();\n}\n",
+      "one\ntwo\nthree\nfour\nfive\nsix",
+    ];
+    let fold_sets: [&[Range<usize>]; 4] = [&[], &[0..2], &[1..4, 2..3], &[0..1, 3..6]];
+    let wraps = [None, Some(1), Some(2), Some(3), Some(5), Some(80)];
+
+    for text in texts {
+      let lines = lines_of(text);
+      let len = text.chars().count();
+      let inlay_sets = [
+        Vec::new(),
+        vec![inlay(0, "<")],
+        vec![
+          inlay(len / 2, "::T"),
+          inlay(len / 2, "!"),
+          inlay(len, "  // end"),
+        ],
+        lines.iter().map(|line| inlay(line.end, "¶")).collect(),
+      ];
+      for folds in fold_sets {
+        for inlays in &inlay_sets {
+          for wrap in wraps {
+            let map = DisplayMap::new(&lines, folds, inlays, wrap);
+            let context = format!(
+              "{:?} folds {:?} inlays {:?} wrap {:?}",
+              text, folds, inlays, wrap
+            );
+
+            for (line, range) in lines.iter().enumerate() {
+              let Some(rows) = map.rows_for_line(line) else {
+                assert!(map.is_hidden(line), "{}", context);
+                continue;
+              };
+              for offset in range.start..=range.end {
+                let point = map.to_display_point(offset);
+                assert!(rows.contains(&point.row), "{} offset {}", context, offset);
+                assert!(point.column <= map.row_len(point.row), "{}", context);
+                assert_eq!(
+                  map.to_offset(point),
+                  offset,
+                  "{} offset {}",
+                  context,
+                  offset
+                );
+              }
+            }
+
+            let mut previous = 0;
+            for row in 0..map.row_count() {
+              let line = map.buffer_line(row).unwrap();
+              assert!(!map.is_hidden(line), "{}", context);
+              for column in 0..=map.row_len(row) + 1 {
+                let offset = map.to_offset(DisplayPoint::new(row, column));
+                assert!(offset >= previous, "{} at {}:{}", context, row, column);
+                assert!(lines[line].contains(&offset) || offset == lines[line].end);
+                previous = offset;
+              }
+            }
+          }
+        }
+      }
+    }
+  }
+}
@@ -1,25 +1,105 @@
-use buffer::TextBuffer;
-use gpui::{Context, Task};
+use buffer::{Anchor, BufferSnapshot, Edit, TextBuffer, write_atomically};
+use gpui::{App, AppContext, Context, Entity, Task};
 use parking_lot::RwLock;
 use std::{
+  any::Any,
   borrow::Cow,
   ops::Range,
+  panic::{self, AssertUnwindSafe},
+  path::{Path, PathBuf},
   sync::Arc,
   time::{Duration, Instant},
 };
 use syntax::languages;
-use syntax::{HighlightSpan, SyntaxHighlighter};
+use syntax::{HighlightSpan, InputEdit, LanguageConfig, Point, SyntaxHighlighter, TextChunks};
+
+use crate::{crash_recovery, search::SearchQuery};
+
+/// Idle time after which highlights are checked against the text
+const IDLE_RECONCILE_DELAY: Duration = Duration::from_secs(2);
+
+/// Lines whose highlights are reconciled between two yields
+const RECONCILE_CHUNK_LINES: usize = 500;
+
+/// Pause between two chunks, letting input and rendering through
+const RECONCILE_CHUNK_PAUSE: Duration = Duration::from_millis(4);
+
+/// Lines highlighted between two repaints while a pass streams in
+const HIGHLIGHT_CHUNK_LINES: usize = 500;
 
 pub struct Document {
   pub buffer: TextBuffer,
 
+  // Extension of the file the text came from, used for language-specific editing
+  file_ext: Option<String>,
+
+  // File the text is read from and saved to
+  path: Option<PathBuf>,
+  // Text version last read from or written to the file
+  saved_version: usize,
+
   // Syntax highlighting support
   highlighter: Option<SyntaxHighlighter>,
+  // Text the highlighter's tree was parsed from, none until its first parse
+  parsed: Option<BufferSnapshot>,
+  // Byte ranges of the parsed text whose highlights are still to be redone
+  stale: Vec<Range<usize>>,
+  // Lines in view, highlighted before the rest of the text
+  visible_lines: Range<usize>,
   highlights: Arc<RwLock<Vec<HighlightSpan>>>,
   pending_highlight_task: Option<Task<()>>,
 
   // Flag to track when highlights have been updated (for cache invalidation)
   pub highlights_version: Arc<RwLock<usize>>,
+
+  // Text version the highlights were computed for, none before the first pass
+  highlighted_text_version: Arc<RwLock<Option<usize>>>,
+  idle_reconcile_task: Option<Task<()>>,
+  // Set when highlighting panicked, the text is shown plain until a retry
+  highlighting_failed: bool,
+  // Set while highlighting is turned off, for documents too big to highlight
+  highlighting_paused: bool,
+
+  // Append a trailing newline when the document is prepared for saving
+  pub insert_final_newline: bool,
+}
+
+/// Immutable copy of a document's text and metadata for background tasks
+///
+/// Taken in constant time, it stays consistent while the document keeps
+/// changing; results computed from it are only valid for its version.
+#[derive(Clone)]
+pub struct DocumentSnapshot {
+  pub buffer: BufferSnapshot,
+  file_ext: Option<String>,
+  language_name: Option<&'static str>,
+}
+
+impl DocumentSnapshot {
+  pub fn version(&self) -> usize {
+    self.buffer.version()
+  }
+
+  pub fn len(&self) -> usize {
+    self.buffer.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.buffer.is_empty()
+  }
+
+  /// The whole text, best built on the background thread that needs it
+  pub fn text(&self) -> String {
+    self.buffer.text()
+  }
+
+  pub fn file_extension(&self) -> Option<&str> {
+    self.file_ext.as_deref()
+  }
+
+  pub fn language_name(&self) -> Option<&'static str> {
+    self.language_name
+  }
 }
 
 impl Document {
@@ -27,16 +107,31 @@ impl Document {
   pub fn new(text: &str, file_ext: Option<&str>, cx: &mut Context<Self>) -> Self {
     let buffer = TextBuffer::from_text(text);
 
-    let highlighter = file_ext
-      .and_then(languages::detect_language_config)
-      .map(SyntaxHighlighter::new);
+    // Files without extension may name their language on the first line
+    let highlighter = match file_ext {
+      Some(ext) => languages::detect_language_config(ext),
+      None => languages::detect_from_first_line(text.lines().next().unwrap_or_default()),
+    }
+    .map(SyntaxHighlighter::new);
 
+    let saved_version = buffer.version();
     let mut doc = Self {
       buffer,
+      file_ext: file_ext.map(str::to_ascii_lowercase),
+      path: None,
+      saved_version,
       highlighter,
+      parsed: None,
+      stale: Vec::new(),
+      visible_lines: 0..0,
       highlights: Arc::new(RwLock::new(Vec::new())),
       pending_highlight_task: None,
+      insert_final_newline: true,
       highlights_version: Arc::new(RwLock::new(0)),
+      highlighted_text_version: Arc::new(RwLock::new(None)),
+      idle_reconcile_task: None,
+      highlighting_failed: false,
+      highlighting_paused: false,
     };
 
     // Schedule initial highlighting
@@ -47,6 +142,79 @@ impl Document {
     doc
   }
 
+  /// Read the text file at `path` on a background thread
+  pub fn open(path: PathBuf, cx: &mut App) -> Task<Result<Entity<Self>, String>> {
+    cx.spawn(async move |cx| {
+      let read_path = path.clone();
+      let text = cx
+        .background_executor()
+        .spawn(async move { std::fs::read_to_string(read_path) })
+        .await
+        .map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+      cx.update(|cx| {
+        cx.new(|cx| {
+          let file_ext = path.extension().and_then(|ext| ext.to_str());
+          let mut document = Self::new(&text, file_ext, cx);
+          document.path = Some(path);
+          crash_recovery::open(&mut document, cx);
+          document
+        })
+      })
+      .map_err(|err| err.to_string())
+    })
+  }
+
+  /// File the document is saved to
+  pub fn path(&self) -> Option<&Path> {
+    self.path.as_deref()
+  }
+
+  /// Set the file the text was just read from
+  pub fn set_path(&mut self, path: Option<PathBuf>) {
+    self.path = path;
+    self.saved_version = self.version();
+  }
+
+  /// Whether the text changed since it was last read or saved
+  pub fn is_dirty(&self) -> bool {
+    self.version() != self.saved_version
+  }
+
+  /// Write the text to its file on a background thread
+  pub fn save(&mut self, cx: &mut Context<Self>) -> Task<Result<(), String>> {
+    match self.path.clone() {
+      Some(path) => self.save_as(path, cx),
+      None => Task::ready(Err("The document has no file to save to".to_string())),
+    }
+  }
+
+  /// Write the text to `path`, which the document is saved to from then on.
+  /// The text is written as it is; `prepare_for_save` adds the final newline.
+  pub fn save_as(&mut self, path: PathBuf, cx: &mut Context<Self>) -> Task<Result<(), String>> {
+    let snapshot = self.buffer.snapshot();
+    let version = snapshot.version();
+    cx.spawn(async move |this, cx| {
+      let write_path = path.clone();
+      cx.background_executor()
+        .spawn(async move { write_atomically(&write_path, &snapshot.text()) })
+        .await
+        .map_err(|err| format!("Failed to save {}: {}", path.display(), err))?;
+      this
+        .update(cx, |document, cx| {
+          let previous = document.path.replace(path);
+          document.saved_version = version;
+          if previous != document.path {
+            crash_recovery::saved_to_new_path(document, previous.as_deref(), version, cx);
+          } else if let Err(err) = document.journal_saved(version) {
+            // Edits made while writing are not in the file yet
+            eprintln!("Failed to truncate the edit journal: {}", err);
+          }
+          cx.notify();
+        })
+        .map_err(|err| err.to_string())
+    })
+  }
+
   pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
     self.buffer.chars()
   }
@@ -63,6 +231,11 @@ impl Document {
     self.buffer.is_empty()
   }
 
+  /// Whether a non-empty document lacks a trailing newline
+  pub fn missing_final_newline(&self) -> bool {
+    !self.buffer.is_empty() && !self.buffer.ends_with_newline()
+  }
+
   pub fn line_content(&self, line_idx: usize) -> Option<Cow<'_, str>> {
     self.buffer.line_content(line_idx)
   }
@@ -83,6 +256,114 @@ impl Document {
     self.buffer.line_to_char(line_idx)
   }
 
+  pub fn byte_to_char(&self, byte_idx: usize) -> usize {
+    self.buffer.byte_to_char(byte_idx)
+  }
+
+  pub fn char_to_byte(&self, char_idx: usize) -> usize {
+    self.buffer.char_to_byte(char_idx)
+  }
+
+  pub fn len_bytes(&self) -> usize {
+    self.buffer.len_bytes()
+  }
+
+  pub fn bytes_in_range(&self, range: Range<usize>) -> Vec<u8> {
+    self.buffer.bytes_in_range(range)
+  }
+
+  /// Matches of `query` on `lines`, as char ranges in document order
+  ///
+  /// Lines are searched one at a time straight from the rope, so matches
+  /// don't span line breaks.
+  pub fn find_in_lines(&self, query: &SearchQuery, lines: Range<usize>) -> Vec<Range<usize>> {
+    let mut matches = Vec::new();
+    for line in lines.start..lines.end.min(self.len_lines()) {
+      let Some(content) = self.line_content(line) else {
+        continue;
+      };
+      // Match bytes counted in chars from the end of the previous match
+      let mut byte = 0;
+      let mut offset = self.line_to_char(line);
+      for range in query.find_all(&content) {
+        offset += content[byte..range.start].chars().count();
+        let start = offset;
+        offset += content[range.clone()].chars().count();
+        byte = range.end;
+        matches.push(start..offset);
+      }
+    }
+    matches
+  }
+
+  /// Matches of `query` in the whole document
+  pub fn find_all(&self, query: &SearchQuery) -> Vec<Range<usize>> {
+    self.find_in_lines(query, 0..self.len_lines())
+  }
+
+  /// Number of edits applied so far, changes whenever the text changes
+  pub fn version(&self) -> usize {
+    self.buffer.version()
+  }
+
+  /// Edits applied since the text was at `version`, in chars, oldest first
+  pub fn edits_since(&self, version: usize) -> &[buffer::Edit] {
+    self.buffer.edits_since(version)
+  }
+
+  pub fn file_extension(&self) -> Option<&str> {
+    self.file_ext.as_deref()
+  }
+
+  /// Name of the language used for highlighting, if one was detected
+  pub fn language_name(&self) -> Option<&'static str> {
+    self
+      .highlighter
+      .as_ref()
+      .map(|highlighter| highlighter.config.name)
+  }
+
+  /// Configuration of the language used for highlighting
+  pub fn language_config(&self) -> Option<&'static LanguageConfig> {
+    self
+      .highlighter
+      .as_ref()
+      .map(|highlighter| highlighter.config)
+  }
+
+  /// Capture the text as it is now, for a background task to read
+  pub fn snapshot(&self) -> DocumentSnapshot {
+    DocumentSnapshot {
+      buffer: self.buffer.snapshot(),
+      file_ext: self.file_ext.clone(),
+      language_name: self.language_name(),
+    }
+  }
+
+  pub fn anchor_before(&self, offset: usize) -> Anchor {
+    self.buffer.anchor_before(offset)
+  }
+
+  pub fn anchor_after(&self, offset: usize) -> Anchor {
+    self.buffer.anchor_after(offset)
+  }
+
+  pub fn anchor_range(&self, range: Range<usize>) -> Range<Anchor> {
+    self.buffer.anchor_range(range)
+  }
+
+  pub fn resolve_anchor(&self, anchor: &Anchor) -> usize {
+    self.buffer.resolve_anchor(anchor)
+  }
+
+  pub fn rebase_anchor(&self, anchor: &Anchor) -> Anchor {
+    self.buffer.rebase_anchor(anchor)
+  }
+
+  pub fn resolve_anchor_range(&self, range: &Range<Anchor>) -> Range<usize> {
+    self.buffer.resolve_anchor_range(range)
+  }
+
   #[cfg(test)]
   pub fn insert_char(&mut self, offset: usize, ch: char, cx: &mut Context<Self>) {
     self.buffer.transaction(Instant::now(), |buffer, tx| {
@@ -95,9 +376,65 @@ impl Document {
     self.buffer.transaction(Instant::now(), |buffer, tx| {
       buffer.replace(tx, range, text);
     });
+    if self.redetect_language() {
+      self.schedule_recompute_highlights(cx);
+    } else {
+      // Callers don't re-highlight after every replace, the idle pass catches up
+      self.schedule_idle_reconcile(cx);
+    }
     cx.notify();
   }
 
+  /// Pick the language again from the first line of a file without
+  /// extension, returning whether it changed
+  fn redetect_language(&mut self) -> bool {
+    if self.file_ext.is_some() {
+      return false;
+    }
+    let first_line = self.buffer.line_content(0).unwrap_or_default();
+    let config = languages::detect_from_first_line(&first_line);
+    if config.map(|config| config.name) == self.language_name() {
+      return false;
+    }
+
+    self.highlighter = config.map(SyntaxHighlighter::new);
+    self.parsed = None;
+    self.highlighting_failed = false;
+    self.highlights.write().clear();
+    *self.highlighted_text_version.write() = None;
+    *self.highlights_version.write() += 1;
+    true
+  }
+
+  /// Append text that arrived from outside the editor, it isn't added to undo history
+  pub fn append(&mut self, text: &str, cx: &mut Context<Self>) {
+    self.buffer.append(text);
+    self.schedule_recompute_highlights(cx);
+    cx.notify();
+  }
+
+  /// Replace the whole text with text from outside the editor; the undo
+  /// history no longer applies to it and is dropped
+  pub fn reset(&mut self, text: &str, cx: &mut Context<Self>) {
+    self.buffer.reset(text);
+    self.schedule_recompute_highlights(cx);
+    cx.notify();
+  }
+
+  /// Save hook: append the final newline if the setting asks for one
+  pub fn prepare_for_save(&mut self, cx: &mut Context<Self>) -> Option<buffer::TransactionId> {
+    if !self.insert_final_newline || !self.missing_final_newline() {
+      return None;
+    }
+
+    let len = self.len();
+    let id = self.buffer.transaction(Instant::now(), |buffer, tx| {
+      buffer.insert(tx, len, "\n");
+    });
+    cx.notify();
+    Some(id)
+  }
+
   pub fn undo(&mut self, cx: &mut Context<Self>) -> Option<buffer::TransactionId> {
     let result = self.buffer.undo();
     if result.is_some() {
@@ -126,6 +463,37 @@ impl Document {
     self.buffer.set_group_interval(interval);
   }
 
+  /// Undo the edits made past the first `depth` of the history together,
+  /// restoring `before`, and redo them restoring `after`
+  pub fn group_past(&mut self, depth: usize, before: Range<usize>, after: Range<usize>) {
+    self.buffer.group_past(depth, before, after);
+  }
+
+  /// Journal edits to `path` so they can be recovered after a crash
+  pub fn set_journal(&mut self, path: Option<PathBuf>) {
+    self.buffer.set_journal(path);
+  }
+
+  /// Drop the journaled edits up to `version` once the text at that version
+  /// has been written to disk
+  pub fn journal_saved(&mut self, version: usize) -> Result<(), String> {
+    self.buffer.truncate_journal(version)
+  }
+
+  /// Replay a journal left by a crashed session over the saved text
+  pub fn recover_from_journal(
+    &mut self,
+    path: &Path,
+    cx: &mut Context<Self>,
+  ) -> Result<usize, String> {
+    let count = self.buffer.replay_journal(path)?;
+    if count > 0 {
+      self.schedule_recompute_highlights(cx);
+      cx.notify();
+    }
+    Ok(count)
+  }
+
   /// Get syntax highlights for a specific line
   pub fn get_highlights_for_line(&self, line_idx: usize) -> Option<Vec<HighlightSpan>> {
     let highlights = self.highlights.read();
@@ -145,21 +513,99 @@ impl Document {
     }
   }
 
+  /// Whether highlighting panicked, leaving the text plain until retried
+  pub fn highlighting_failed(&self) -> bool {
+    self.highlighting_failed
+  }
+
+  /// Highlight again after highlighting panicked
+  pub fn retry_highlighting(&mut self, cx: &mut Context<Self>) {
+    if self.highlighting_failed {
+      self.highlighting_failed = false;
+      self.schedule_recompute_highlights(cx);
+    }
+  }
+
+  /// Whether highlighting is turned off with `pause_highlighting`
+  pub fn highlighting_paused(&self) -> bool {
+    self.highlighting_paused
+  }
+
+  /// Show the text plain, without highlighting it again until resumed
+  pub fn pause_highlighting(&mut self, cx: &mut Context<Self>) {
+    self.highlighting_paused = true;
+    self.pending_highlight_task = None;
+    self.parsed = None;
+    self.idle_reconcile_task = None;
+    self.highlights.write().clear();
+    *self.highlighted_text_version.write() = None;
+    *self.highlights_version.write() += 1;
+    cx.notify();
+  }
+
+  pub fn resume_highlighting(&mut self, cx: &mut Context<Self>) {
+    if self.highlighting_paused {
+      self.highlighting_paused = false;
+      self.schedule_recompute_highlights(cx);
+    }
+  }
+
+  /// Show the text plain after highlighting panicked
+  fn fail_highlighting(&mut self, message: &str, cx: &mut Context<Self>) {
+    if let Some(highlighter) = &self.highlighter {
+      eprintln!(
+        "Syntax highlighting for {} panicked, showing plain text: {}",
+        highlighter.config.name, message
+      );
+    }
+    self.highlighting_failed = true;
+    self.parsed = None;
+    self.highlights.write().clear();
+    *self.highlights_version.write() += 1;
+    cx.notify();
+  }
+
+  /// Have highlighting passes do `lines` before the rest of the text
+  ///
+  /// Called with the lines in view, which a pass streaming in turns to at its
+  /// next chunk, so they are colored first.
+  pub fn highlight_range(&mut self, lines: Range<usize>) {
+    self.visible_lines = lines;
+  }
+
   /// Schedule async re-highlighting with debouncing
+  ///
+  /// The highlighter's tree of the text it last parsed is edited and reused,
+  /// and only the lines whose syntax changed are highlighted again, as long as
+  /// the cached highlights are those of that text. They are highlighted a
+  /// chunk at a time, the lines in view first, each chunk repainted as it
+  /// arrives.
   pub fn schedule_recompute_highlights(&mut self, cx: &mut Context<Self>) {
-    // Cancel previous task
+    // Cancel previous task, and the idle pass this one supersedes
     self.pending_highlight_task = None;
+    self.idle_reconcile_task = None;
+    self.redetect_language();
 
-    let Some(ref mut highlighter) = self.highlighter else {
+    let Some(highlighter) = &self.highlighter else {
       return;
     };
+    if self.highlighting_failed || self.highlighting_paused {
+      return;
+    }
 
-    let text = self.buffer.slice_to_string(0..self.buffer.len());
+    let snapshot = self.buffer.snapshot();
+    let base = self
+      .parsed
+      .clone()
+      .filter(|parsed| *self.highlighted_text_version.read() == Some(parsed.version()))
+      .map(|parsed| {
+        let edits = self.buffer.edits_since(parsed.version()).to_vec();
+        (parsed, edits, self.stale.clone())
+      });
+    let mut highlighter = highlighter.clone();
     let highlights_cache = self.highlights.clone();
     let highlights_version = self.highlights_version.clone();
-
-    // Clone highlighter config for background work
-    let config = highlighter.config;
+    let highlighted_text_version = self.highlighted_text_version.clone();
 
     let task = cx.spawn(async move |this, cx| {
       // Debounce: wait 150ms
@@ -167,44 +613,654 @@ impl Document {
         .timer(Duration::from_millis(150))
         .await;
 
-      // Highlighting in background
+      // Parsing in background
+      let parsed = snapshot.clone();
       let result = cx
         .background_executor()
         .spawn(async move {
-          let mut bg_highlighter = SyntaxHighlighter::new(config);
-          bg_highlighter.highlight_text(&text)
+          reparse(&mut highlighter, base, &parsed).map(|reparse| (highlighter, reparse))
         })
         .await;
+      let (highlighter, reparse) = match result {
+        Ok(result) => result,
+        Err(HighlightError::Panicked(message)) => {
+          let _ = this.update(cx, |doc, cx| doc.fail_highlighting(&message, cx));
+          return;
+        }
+        Err(HighlightError::Failed(e)) => {
+          eprintln!("Syntax highlighting failed: {}", e);
+          // Fallback: clear cache so we show plain text, and parse afresh next time
+          highlights_cache.write().clear();
+          let _ = this.update(cx, |doc, _| doc.parsed = None);
+          return;
+        }
+      };
 
-      // Update cache
-      match result {
-        Ok(highlights) => {
-          *highlights_cache.write() = highlights;
-
-          // Increment version to signal that highlights have been updated
-          *highlights_version.write() += 1;
+      // Bring the cached highlights to the new text, keeping the tree for
+      // the next edit
+      match &reparse.edit {
+        Some((old, new_end)) => move_spans(&mut highlights_cache.write(), old, *new_end),
+        None => highlights_cache.write().clear(),
+      }
+      *highlighted_text_version.write() = Some(snapshot.version());
+      // Increment version to signal that highlights have been updated
+      *highlights_version.write() += 1;
+      let stored = this.update(cx, |doc, cx| {
+        doc.highlighter = Some(highlighter.clone());
+        doc.parsed = Some(snapshot.clone());
+        doc.stale = reparse.dirty;
+        cx.notify();
+      });
+      if stored.is_err() {
+        return;
+      }
 
-          // Notify UI to re-render
-          let _ = this.update(cx, |_doc, cx| {
+      // Highlight the stale lines in background, the lines in view first
+      loop {
+        let chunk = this.update(cx, |doc, _| {
+          let visible = line_bytes(&snapshot, doc.visible_lines.clone());
+          next_chunk(&doc.stale, &visible, |start| {
+            let line = snapshot.char_to_line(snapshot.byte_to_char(start));
+            line_bytes(&snapshot, line..line + HIGHLIGHT_CHUNK_LINES).end
+          })
+        });
+        let Ok(Some(chunk)) = chunk else {
+          break;
+        };
+
+        let highlighter = highlighter.clone();
+        let text = snapshot.clone();
+        let bytes = chunk.clone();
+        let spans = cx
+          .background_executor()
+          .spawn(async move {
+            panic::catch_unwind(AssertUnwindSafe(|| {
+              highlighter.highlight_range(&RopeText(&text), bytes)
+            }))
+          })
+          .await;
+        let updated = this.update(cx, |doc, cx| match spans {
+          Ok(spans) => {
+            splice_spans(&mut doc.highlights.write(), &[chunk.clone()], spans);
+            doc.stale = doc
+              .stale
+              .iter()
+              .flat_map(|range| subtract(range.clone(), &[chunk.clone()]))
+              .collect();
+            *doc.highlights_version.write() += 1;
             cx.notify();
-          });
+            true
+          }
+          Err(payload) => {
+            doc.fail_highlighting(&panic_message(&*payload), cx);
+            false
+          }
+        });
+        if !matches!(updated, Ok(true)) {
+          return;
         }
-        Err(e) => {
+      }
+
+      // Check again once edits stop
+      let _ = this.update(cx, |doc, cx| doc.schedule_idle_reconcile(cx));
+    });
+
+    self.pending_highlight_task = Some(task);
+  }
+
+  /// Restart the wait before the idle highlight pass, stopping a running one
+  ///
+  /// Called on user input so the pass only works while nothing else happens.
+  pub fn defer_idle_work(&mut self, cx: &mut Context<Self>) {
+    if self.idle_reconcile_task.is_some()
+      && *self.highlighted_text_version.read() != Some(self.version())
+    {
+      self.schedule_idle_reconcile(cx);
+    }
+  }
+
+  /// Once the document has been idle, highlight its text again and bring the
+  /// cached highlights in line with the result a chunk of lines at a time
+  ///
+  /// Edits the editor doesn't re-highlight after, like composed input, leave
+  /// highlights at stale offsets that are only repainted once visited; this
+  /// pass fixes them everywhere. It stops as soon as the text changes.
+  fn schedule_idle_reconcile(&mut self, cx: &mut Context<Self>) {
+    let Some(ref highlighter) = self.highlighter else {
+      return;
+    };
+    if self.highlighting_failed || self.highlighting_paused {
+      return;
+    }
+    let config = highlighter.config;
+    let highlights_cache = self.highlights.clone();
+    let highlights_version = self.highlights_version.clone();
+    let highlighted_text_version = self.highlighted_text_version.clone();
+
+    let task = cx.spawn(async move |this, cx| {
+      cx.background_executor().timer(IDLE_RECONCILE_DELAY).await;
+
+      let Ok(snapshot) = this.update(cx, |doc, _| doc.buffer.snapshot()) else {
+        return;
+      };
+      let version = snapshot.version();
+      if *highlighted_text_version.read() == Some(version) {
+        return;
+      }
+
+      let fresh = cx
+        .background_executor()
+        .spawn(async move { highlight_text(config, &snapshot) })
+        .await;
+      let fresh = match fresh {
+        Ok(fresh) => fresh,
+        Err(HighlightError::Panicked(message)) => {
+          let _ = this.update(cx, |doc, cx| doc.fail_highlighting(&message, cx));
+          return;
+        }
+        Err(HighlightError::Failed(e)) => {
           eprintln!("Syntax highlighting failed: {}", e);
-          // Fallback: clear cache so we show plain text
-          highlights_cache.write().clear();
+          return;
+        }
+      };
+
+      let mut line = 0;
+      loop {
+        let chunk = this.update(cx, |doc, cx| {
+          if doc.version() != version {
+            return None;
+          }
+          let start = doc.char_to_byte(doc.line_to_char(line));
+          let end_line = (line + RECONCILE_CHUNK_LINES).min(doc.len_lines());
+          // The last chunk also takes spans left past the end of the text
+          let end = if end_line == doc.len_lines() {
+            usize::MAX
+          } else {
+            doc.char_to_byte(doc.line_to_char(end_line))
+          };
+          if reconcile_spans(&mut highlights_cache.write(), &fresh, start..end) {
+            *highlights_version.write() += 1;
+            cx.notify();
+          }
+          Some((end_line, end == usize::MAX))
+        });
+        let Ok(Some((end_line, last))) = chunk else {
+          return;
+        };
+        if last {
+          break;
         }
+        line = end_line;
+        cx.background_executor().timer(RECONCILE_CHUNK_PAUSE).await;
       }
+      *highlighted_text_version.write() = Some(version);
     });
 
-    self.pending_highlight_task = Some(task);
+    self.idle_reconcile_task = Some(task);
   }
 }
 
+enum HighlightError {
+  /// The highlighter returned an error, the next edit tries again
+  Failed(String),
+  /// The highlighter panicked, likely on a grammar bug the same text hits again
+  Panicked(String),
+}
+
+/// A snapshot's text as the highlighter reads it, a rope chunk at a time
+struct RopeText<'a>(&'a BufferSnapshot);
+
+impl TextChunks for RopeText<'_> {
+  fn len_bytes(&self) -> usize {
+    self.0.len_bytes()
+  }
+
+  fn chunk_at_byte(&self, byte: usize) -> &[u8] {
+    self.0.chunk_at_byte(byte)
+  }
+
+  fn chunks_in(&self, range: Range<usize>) -> impl Iterator<Item = &[u8]> {
+    self.0.byte_chunks(range).map(str::as_bytes)
+  }
+}
+
+/// Highlight `snapshot` on a fresh highlighter, catching a panic in the grammar
+fn highlight_text(
+  config: &'static LanguageConfig,
+  snapshot: &BufferSnapshot,
+) -> Result<Vec<HighlightSpan>, HighlightError> {
+  panic::catch_unwind(AssertUnwindSafe(|| {
+    let text = RopeText(snapshot);
+    let mut highlighter = SyntaxHighlighter::new(config);
+    highlighter.update(&text)?;
+    Ok(highlighter.highlight_range(&text, 0..snapshot.len_bytes()))
+  }))
+  .map_err(|payload| HighlightError::Panicked(panic_message(&*payload)))?
+  .map_err(HighlightError::Failed)
+}
+
+/// A parse of the text, and the highlights it leaves to redo
+struct Reparse {
+  /// Bytes of the text last parsed that edits replaced, and the end of what
+  /// replaced them, or none when the cached highlights are dropped
+  edit: Option<(Range<usize>, usize)>,
+  /// Byte ranges of whole lines whose highlights are to be redone
+  dirty: Vec<Range<usize>>,
+}
+
+/// Parse `snapshot` with `highlighter`, reusing its tree of `base`, the edits
+/// made since and the ranges it left stale when given, catching a panic in
+/// the grammar
+fn reparse(
+  highlighter: &mut SyntaxHighlighter,
+  base: Option<(BufferSnapshot, Vec<Edit>, Vec<Range<usize>>)>,
+  snapshot: &BufferSnapshot,
+) -> Result<Reparse, HighlightError> {
+  panic::catch_unwind(AssertUnwindSafe(|| {
+    let text = RopeText(snapshot);
+    let Some((parsed, edits, stale)) = base else {
+      *highlighter = SyntaxHighlighter::new(highlighter.config);
+      highlighter.update(&text)?;
+      return Ok(Reparse {
+        edit: None,
+        dirty: vec![0..snapshot.len_bytes()],
+      });
+    };
+    let Some(edit) = input_edit(&parsed, snapshot, &edits) else {
+      return Ok(Reparse {
+        edit: Some((0..0, 0)),
+        dirty: stale,
+      });
+    };
+
+    highlighter.edit(&edit);
+    let changed = highlighter.update(&text)?;
+    let old = edit.start_byte..edit.old_end_byte;
+    let new_end = edit.new_end_byte;
+    // Text changed within a token leaves the syntax as it was
+    let edited = edit.start_byte..new_end;
+    let stale = stale.into_iter().map(|range| {
+      let map = |byte: usize, inside: usize| {
+        if byte <= old.start {
+          byte
+        } else if byte >= old.end {
+          byte - old.end + new_end
+        } else {
+          inside
+        }
+      };
+      map(range.start, old.start)..map(range.end, new_end)
+    });
+    let dirty = line_ranges(snapshot, changed.into_iter().chain([edited]).chain(stale));
+    Ok(Reparse {
+      edit: Some((old, new_end)),
+      dirty,
+    })
+  }))
+  .map_err(|payload| HighlightError::Panicked(panic_message(&*payload)))?
+  .map_err(HighlightError::Failed)
+}
+
+/// Chars `edits` replaced, as their start and end in the text before the
+/// first of them, and the end of what replaced them in the text after the last
+fn merge_edits(edits: &[Edit]) -> Option<(usize, usize, usize)> {
+  let (first, rest) = edits.split_first()?;
+  let mut start = first.start;
+  let mut old_end = first.start + first.old_len;
+  let mut new_end = first.start + first.new_len;
+  for edit in rest {
+    // The end of the replaced text so far, in the text before `edit`
+    let end = new_end.max(edit.start + edit.old_len);
+    start = start.min(edit.start);
+    old_end += end - new_end;
+    new_end = end - edit.old_len + edit.new_len;
+  }
+  Some((start, old_end, new_end))
+}
+
+/// `edits` made to `old`, giving `new`, as one tree-sitter edit
+fn input_edit(old: &BufferSnapshot, new: &BufferSnapshot, edits: &[Edit]) -> Option<InputEdit> {
+  let (start, old_end, new_end) = merge_edits(edits)?;
+  let point = |text: &BufferSnapshot, offset: usize| {
+    let row = text.char_to_line(offset);
+    let line_start = text.char_to_byte(text.line_to_char(row));
+    Point::new(row, text.char_to_byte(offset) - line_start)
+  };
+  Some(InputEdit {
+    start_byte: old.char_to_byte(start),
+    old_end_byte: old.char_to_byte(old_end),
+    new_end_byte: new.char_to_byte(new_end),
+    start_position: point(old, start),
+    old_end_position: point(old, old_end),
+    new_end_position: point(new, new_end),
+  })
+}
+
+/// Bytes of `lines` of `text`, clamped to its end
+fn line_bytes(text: &BufferSnapshot, lines: Range<usize>) -> Range<usize> {
+  let line_start = |line: usize| {
+    if line < text.len_lines() {
+      text.char_to_byte(text.line_to_char(line))
+    } else {
+      text.len_bytes()
+    }
+  };
+  line_start(lines.start)..line_start(lines.end)
+}
+
+/// Byte `ranges` of `text` grown to whole lines, sorted and merged
+fn line_ranges(
+  text: &BufferSnapshot,
+  ranges: impl IntoIterator<Item = Range<usize>>,
+) -> Vec<Range<usize>> {
+  let line_of = |byte: usize| text.char_to_line(text.byte_to_char(byte));
+  let mut lines: Vec<Range<usize>> = ranges
+    .into_iter()
+    .map(|range| {
+      let last_line = line_of(range.end.max(range.start + 1) - 1);
+      line_bytes(text, line_of(range.start)..last_line + 1)
+    })
+    .collect();
+  lines.sort_by_key(|range| range.start);
+
+  let mut merged: Vec<Range<usize>> = Vec::new();
+  for range in lines {
+    match merged.last_mut() {
+      Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+      _ => merged.push(range),
+    }
+  }
+  merged
+}
+
+/// Next bytes of `dirty` to highlight: those in view, or else from the start
+/// of the first range to `chunk_end` of it
+fn next_chunk(
+  dirty: &[Range<usize>],
+  visible: &Range<usize>,
+  chunk_end: impl Fn(usize) -> usize,
+) -> Option<Range<usize>> {
+  dirty
+    .iter()
+    .find_map(|range| {
+      let start = range.start.max(visible.start);
+      let end = range.end.min(visible.end);
+      (start < end).then_some(start..end)
+    })
+    .or_else(|| {
+      let range = dirty.first()?;
+      Some(range.start..chunk_end(range.start).min(range.end))
+    })
+}
+
+/// Parts of `range` outside the sorted `cuts`
+fn subtract(range: Range<usize>, cuts: &[Range<usize>]) -> Vec<Range<usize>> {
+  let mut parts = Vec::new();
+  let mut start = range.start;
+  for cut in cuts {
+    if cut.start >= range.end {
+      break;
+    }
+    if cut.end <= start {
+      continue;
+    }
+    if cut.start > start {
+      parts.push(start..cut.start);
+    }
+    start = start.max(cut.end);
+  }
+  if start < range.end {
+    parts.push(start..range.end);
+  }
+  parts
+}
+
+/// Move the spans of `cached` past an edit that replaced bytes `old` with
+/// ones ending at `new_end`, dropping what they had of the replaced bytes
+fn move_spans(cached: &mut Vec<HighlightSpan>, old: &Range<usize>, new_end: usize) {
+  *cached = cached
+    .drain(..)
+    .flat_map(|span| {
+      let bytes = span.byte_range;
+      let before = (bytes.start < old.start).then(|| bytes.start..bytes.end.min(old.start));
+      let after = (bytes.end > old.end)
+        .then(|| bytes.start.max(old.end) - old.end + new_end..bytes.end - old.end + new_end);
+      [before, after]
+        .into_iter()
+        .flatten()
+        .map(move |bytes| HighlightSpan {
+          byte_range: bytes,
+          token_type: span.token_type,
+        })
+    })
+    .collect();
+}
+
+/// Put `fresh` in place of what `cached` has in `ranges`; all are sorted by
+/// start
+fn splice_spans(
+  cached: &mut Vec<HighlightSpan>,
+  ranges: &[Range<usize>],
+  fresh: Vec<HighlightSpan>,
+) {
+  let mut spans: Vec<HighlightSpan> = cached
+    .drain(..)
+    .flat_map(|span| {
+      subtract(span.byte_range, ranges)
+        .into_iter()
+        .map(move |bytes| HighlightSpan {
+          byte_range: bytes,
+          token_type: span.token_type,
+        })
+    })
+    .collect();
+  spans.extend(fresh);
+  spans.sort_by_key(|span| span.byte_range.start);
+  *cached = spans;
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+  payload
+    .downcast_ref::<&str>()
+    .map(|message| message.to_string())
+    .or_else(|| payload.downcast_ref::<String>().cloned())
+    .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// Replace the spans of `cached` starting in `bytes` with those of `fresh`,
+/// returning whether they differed; both are sorted by start
+fn reconcile_spans(
+  cached: &mut Vec<HighlightSpan>,
+  fresh: &[HighlightSpan],
+  bytes: Range<usize>,
+) -> bool {
+  let in_chunk = |span: &&HighlightSpan| bytes.contains(&span.byte_range.start);
+  if cached
+    .iter()
+    .filter(in_chunk)
+    .eq(fresh.iter().filter(in_chunk))
+  {
+    return false;
+  }
+  cached.retain(|span| !bytes.contains(&span.byte_range.start));
+  let at = cached.partition_point(|span| span.byte_range.start < bytes.start);
+  cached.splice(at..at, fresh.iter().filter(in_chunk).cloned());
+  true
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
   use gpui::{AppContext, TestAppContext};
+  use syntax::{TokenType, languages::rust::RUST_CONFIG};
+
+  #[test]
+  fn test_reconcile_spans() {
+    let span = |start: usize, end: usize, token_type| HighlightSpan {
+      byte_range: start..end,
+      token_type,
+    };
+    let mut cached = vec![
+      span(0, 2, TokenType::Keyword),
+      span(10, 12, TokenType::Keyword),
+      span(20, 22, TokenType::Keyword),
+    ];
+    let fresh = [
+      span(0, 2, TokenType::Keyword),
+      span(11, 13, TokenType::Keyword),
+      span(15, 16, TokenType::Type),
+      span(20, 22, TokenType::Keyword),
+    ];
+
+    assert!(!reconcile_spans(&mut cached, &fresh, 0..5));
+    assert!(reconcile_spans(&mut cached, &fresh, 5..18));
+    assert_eq!(cached, fresh);
+    assert!(!reconcile_spans(&mut cached, &fresh, 18..usize::MAX));
+  }
+
+  #[test]
+  fn test_merge_edits() {
+    let edit = |start, old_len, new_len| Edit {
+      start,
+      old_len,
+      new_len,
+    };
+    assert_eq!(merge_edits(&[]), None);
+    assert_eq!(merge_edits(&[edit(4, 2, 3)]), Some((4, 6, 7)));
+    // Typing two chars, then deleting one before them
+    assert_eq!(
+      merge_edits(&[edit(4, 0, 1), edit(5, 0, 1), edit(2, 1, 0)]),
+      Some((2, 4, 5))
+    );
+    // Apart, with the text between them taken in
+    assert_eq!(
+      merge_edits(&[edit(2, 1, 0), edit(10, 2, 2)]),
+      Some((2, 13, 12))
+    );
+  }
+
+  #[test]
+  fn test_move_and_splice_spans() {
+    let span = |start: usize, end: usize, token_type| HighlightSpan {
+      byte_range: start..end,
+      token_type,
+    };
+    let mut cached = vec![
+      span(0, 2, TokenType::Keyword),
+      span(4, 10, TokenType::Comment),
+      span(20, 22, TokenType::Keyword),
+    ];
+    // Bytes 6..8 became 6..9, and the line from 4 to 12 was highlighted again
+    move_spans(&mut cached, &(6..8), 9);
+    splice_spans(&mut cached, &[4..12], vec![span(4, 11, TokenType::String)]);
+    assert_eq!(
+      cached,
+      vec![
+        span(0, 2, TokenType::Keyword),
+        span(4, 11, TokenType::String),
+        span(21, 23, TokenType::Keyword),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_next_chunk() {
+    let chunk_end = |start| start + 10;
+    let dirty = [0..30, 40..50];
+    // The lines in view come first
+    assert_eq!(next_chunk(&dirty, &(25..45), chunk_end), Some(25..30));
+    assert_eq!(next_chunk(&dirty, &(32..38), chunk_end), Some(0..10));
+    assert_eq!(next_chunk(&[45..50], &(0..5), chunk_end), Some(45..50));
+    assert_eq!(next_chunk(&[], &(0..5), chunk_end), None);
+
+    assert_eq!(subtract(0..30, &[5..10, 20..40]), vec![0..5, 10..20]);
+    assert_eq!(subtract(10..20, &[0..30]), vec![]);
+  }
+
+  #[gpui::test]
+  fn test_incremental_highlights(cx: &mut TestAppContext) {
+    let text = "fn main() {\n  let a = 1;\n}\n\nfn other() {}\n";
+    let doc = cx.new(|cx| Document::new(text, Some("rs"), cx));
+    let settle = |cx: &mut TestAppContext| {
+      cx.executor().advance_clock(Duration::from_millis(200));
+      cx.run_until_parked();
+    };
+    settle(cx);
+
+    // Edits between two passes are patched in as one
+    let passes: [&[(Range<usize>, &str)]; 3] = [
+      &[(24..24, " // note"), (0..2, "pub fn")],
+      &[(53..53, "\n*/"), (40..40, "/*\n")],
+      &[(0..4, "")],
+    ];
+    for edits in passes {
+      doc.update(cx, |doc, cx| {
+        for (range, new_text) in edits {
+          doc.replace(range.clone(), new_text, cx);
+        }
+        doc.schedule_recompute_highlights(cx);
+      });
+      settle(cx);
+      doc.read_with(cx, |doc, _| {
+        assert_eq!(*doc.highlighted_text_version.read(), Some(doc.version()));
+        assert!(doc.stale.is_empty());
+        assert_eq!(
+          doc.parsed.as_ref().map(|parsed| parsed.version()),
+          Some(doc.version())
+        );
+        // Patched highlights color every byte as highlighting from scratch does
+        let fresh = highlight_text(&RUST_CONFIG, &doc.buffer.snapshot())
+          .ok()
+          .expect("highlighting failed");
+        let colors = |spans: &[HighlightSpan]| {
+          let mut colors = vec![None; doc.text().len()];
+          for span in spans {
+            colors[span.byte_range.clone()].fill(Some(span.token_type));
+          }
+          colors
+        };
+        assert_eq!(colors(&doc.highlights.read()), colors(&fresh));
+      });
+    }
+  }
+
+  #[gpui::test]
+  fn test_language_from_first_line(cx: &mut TestAppContext) {
+    let doc = cx.new(|cx| Document::new("#!/usr/bin/env node\nlet x;\n", None, cx));
+    doc.update(cx, |doc, cx| {
+      assert_eq!(doc.language_name(), Some("typescript"));
+      let first_line = doc.line_range(0).unwrap();
+      doc.replace(first_line.start..first_line.end - 1, "// vim: ft=rust", cx);
+      assert_eq!(doc.language_name(), Some("rust"));
+      doc.replace(0..15, "// plain", cx);
+      assert_eq!(doc.language_name(), None);
+    });
+
+    // An extension decides the language
+    let doc = cx.new(|cx| Document::new("#!/usr/bin/env node\n", Some("txt"), cx));
+    doc.read_with(cx, |doc, _| assert_eq!(doc.language_name(), None));
+  }
+
+  #[test]
+  fn test_panic_message() {
+    let payload = panic::catch_unwind(|| panic!("bad {}", "grammar")).unwrap_err();
+    assert_eq!(panic_message(&*payload), "bad grammar");
+  }
+
+  #[gpui::test]
+  fn test_retry_after_highlighting_panicked(cx: &mut TestAppContext) {
+    let doc = cx.new(|cx| Document::new("fn main() {}", Some("rs"), cx));
+    doc.update(cx, |doc, cx| {
+      doc.fail_highlighting("test", cx);
+      assert!(doc.highlighting_failed());
+      doc.schedule_recompute_highlights(cx);
+      assert!(doc.pending_highlight_task.is_none());
+
+      doc.retry_highlighting(cx);
+      assert!(!doc.highlighting_failed());
+      assert!(doc.pending_highlight_task.is_some());
+    });
+  }
 
   #[gpui::test]
   fn test_new_document(cx: &mut TestAppContext) {
@@ -227,6 +1283,70 @@ mod tests {
     });
   }
 
+  #[gpui::test]
+  fn test_recover_from_journal(cx: &mut TestAppContext) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("journal");
+    let doc = cx.new(|cx| Document::new("hello", None, cx));
+    doc.update(cx, |doc, cx| {
+      doc.set_journal(Some(path.clone()));
+      doc.journal_saved(doc.version()).unwrap();
+      doc.replace(5..5, " world", cx);
+    });
+
+    let recovered = cx.new(|cx| Document::new("hello", None, cx));
+    recovered.update(cx, |doc, cx| {
+      assert_eq!(doc.recover_from_journal(&path, cx), Ok(1));
+      assert_eq!(doc.slice_to_string(0..doc.len()), "hello world");
+    });
+  }
+
+  #[gpui::test]
+  fn test_save_keeps_later_edits_in_journal(cx: &mut TestAppContext) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("notes.txt");
+    let journal = dir.path().join("journal");
+    let doc = cx.new(|cx| Document::new("hello", None, cx));
+    doc.update(cx, |doc, cx| {
+      doc.set_journal(Some(journal.clone()));
+      doc.replace(5..5, " world", cx);
+      doc.save_as(path.clone(), cx).detach();
+      // Typed while the file is being written
+      doc.replace(11..11, "!", cx);
+    });
+    cx.run_until_parked();
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello world");
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    doc.read_with(cx, |doc, _| {
+      assert_eq!(doc.path(), Some(path.as_path()));
+      assert!(doc.is_dirty());
+    });
+
+    let recovered = cx.new(|cx| Document::new("hello world", None, cx));
+    recovered.update(cx, |doc, cx| {
+      assert_eq!(doc.recover_from_journal(&journal, cx), Ok(1));
+      assert_eq!(doc.slice_to_string(0..doc.len()), "hello world!");
+    });
+  }
+
+  #[gpui::test]
+  fn test_prepare_for_save_inserts_final_newline(cx: &mut TestAppContext) {
+    let doc = cx.new(|cx| Document::new("hello", None, cx));
+    doc.update(cx, |doc, cx| {
+      assert!(doc.missing_final_newline());
+      assert!(doc.prepare_for_save(cx).is_some());
+      assert_eq!(doc.slice_to_string(0..doc.len()), "hello\n");
+      assert!(!doc.missing_final_newline());
+      assert!(doc.prepare_for_save(cx).is_none());
+
+      doc.insert_final_newline = false;
+      doc.replace(5..6, "", cx);
+      assert!(doc.prepare_for_save(cx).is_none());
+      assert!(doc.missing_final_newline());
+    });
+  }
+
   #[gpui::test]
   fn test_insert_char(cx: &mut TestAppContext) {
     let doc = cx.new(|cx| Document::new("hello", None, cx));
@@ -300,6 +1420,18 @@ mod tests {
     });
   }
 
+  #[gpui::test]
+  fn test_find_in_lines(cx: &mut TestAppContext) {
+    let doc = cx.new(|cx| Document::new("café cafe\nno match\nCafé", None, cx));
+    doc.read_with(cx, |doc, _| {
+      let query = SearchQuery::new("café");
+      assert_eq!(doc.find_all(&query), [0..4, 19..23]);
+      assert_eq!(doc.find_in_lines(&query, 1..3), [19..23]);
+      let query = SearchQuery::new("cafe").match_diacritics(false);
+      assert_eq!(doc.find_all(&query), [0..4, 5..9, 19..23]);
+    });
+  }
+
   #[gpui::test]
   fn test_empty_lines(cx: &mut TestAppContext) {
     let doc = cx.new(|cx| Document::new("\n\n\n", None, cx));
@@ -0,0 +1,165 @@
+//! Optimistic caret echo for typed text
+//!
+//! Reshaping the edited line with syntax colors is the slowest part of the
+//! frame after a keystroke. For plain insertions at the caret, the stale
+//! cached line is kept and only the typed text is shaped and painted in the
+//! gap, with the caret moved past it. The next frame drops the echo and
+//! reshapes the line properly.
+
+use std::ops::Range;
+
+use gpui::{App, Pixels, ShapedLine};
+
+use crate::editor::Editor;
+
+pub(crate) struct CaretEcho {
+  /// Line whose cached layout predates the echoed text
+  pub line: usize,
+  /// Byte index in the cached layout where the text was typed
+  pub column: usize,
+  pub text: String,
+  /// Document version and selection right after the echoed insertions
+  version: usize,
+  selection: Range<usize>,
+  /// Set once a frame has shown the echo, the next one reconciles it
+  pub shown: bool,
+}
+
+/// Layout of an echo for one frame
+pub(crate) struct EchoLayout {
+  pub line: usize,
+  /// Offset of the typed text from the start of the line
+  pub x: Pixels,
+  pub shaped: ShapedLine,
+}
+
+/// Try to echo `text` typed at the collapsed `range`, instead of invalidating its line
+///
+/// Must be called after the edit was applied and the caret moved; returns
+/// false when the line has to be reshaped normally.
+pub(crate) fn begin(editor: &mut Editor, range: Range<usize>, text: &str, cx: &App) -> bool {
+  if !range.is_empty() || text.is_empty() || text.contains(['\n', '\r', '\t']) {
+    return false;
+  }
+
+  let document = editor.document.read(cx);
+  let line = document.char_to_line(range.start);
+  if !editor.line_layouts.contains_key(&line) {
+    return false;
+  }
+  // Text before the caret is unchanged by the insertion
+  let column =
+    document.char_to_byte(range.start) - document.char_to_byte(document.line_to_char(line));
+  let version = document.version();
+  let selection = editor.selected_range.clone();
+
+  if let Some(echo) = editor.echo.as_mut()
+    && !echo.shown
+    && echo.line == line
+    && echo.column + echo.text.len() == column
+  {
+    // Keep typing into the same echo
+    echo.text.push_str(text);
+    echo.version = version;
+    echo.selection = selection;
+    return true;
+  }
+  if let Some(echo) = editor.echo.take() {
+    // The earlier echo can't be extended, reshape its line too
+    editor.invalidate_line(echo.line);
+    return false;
+  }
+
+  editor.echo = Some(CaretEcho {
+    line,
+    column,
+    text: text.to_string(),
+    version,
+    selection,
+    shown: false,
+  });
+  true
+}
+
+/// Take the echo to show in this frame, invalidating its line if it is outdated
+///
+/// An echo survives a single frame, or less if the text, selection or cache changed.
+pub(crate) fn take_for_frame(editor: &mut Editor, cx: &App) -> Option<(usize, usize, String)> {
+  let echo = editor.echo.as_mut()?;
+  if !echo.shown
+    && echo.version == editor.document.read(cx).version()
+    && echo.selection == editor.selected_range
+    && editor.line_layouts.contains_key(&echo.line)
+  {
+    echo.shown = true;
+    return Some((echo.line, echo.column, echo.text.clone()));
+  }
+
+  let line = echo.line;
+  editor.echo = None;
+  editor.invalidate_line(line);
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+  use std::sync::Arc;
+
+  fn cache_line(editor: &mut Editor, line: usize) {
+    editor
+      .line_layouts
+      .insert(line, Arc::new(ShapedLine::default()));
+  }
+
+  fn insert(editor: &mut Editor, offset: usize, text: &str, cx: &mut gpui::Context<Editor>) {
+    editor
+      .document
+      .update(cx, |doc, cx| doc.replace(offset..offset, text, cx));
+    let caret = offset + text.chars().count();
+    editor.selected_range = caret..caret;
+  }
+
+  #[gpui::test]
+  fn test_echo_accumulates_typed_text(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "fn\nlet é");
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      cache_line(editor, 1);
+      insert(editor, 8, "x", cx);
+      assert!(begin(editor, 8..8, "x", cx));
+      insert(editor, 9, "y", cx);
+      assert!(begin(editor, 9..9, "y", cx));
+
+      let echo = editor.echo.as_ref().unwrap();
+      assert_eq!((echo.line, echo.column, echo.text.as_str()), (1, 6, "xy"));
+
+      // Shown for one frame, then reconciled by reshaping the line
+      assert_eq!(take_for_frame(editor, cx), Some((1, 6, "xy".to_string())));
+      assert_eq!(take_for_frame(editor, cx), None);
+      assert!(editor.echo.is_none());
+      assert!(!editor.line_layouts.contains_key(&1));
+    });
+  }
+
+  #[gpui::test]
+  fn test_echo_falls_back_to_reshaping(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "one\ntwo");
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      // Uncached lines and newlines are never echoed
+      assert!(!begin(editor, 3..3, "x", cx));
+      cache_line(editor, 0);
+      assert!(!begin(editor, 3..3, "\n", cx));
+
+      // Other edits since the echo make it outdated
+      insert(editor, 3, "x", cx);
+      assert!(begin(editor, 3..3, "x", cx));
+      insert(editor, 0, "z", cx);
+      assert_eq!(take_for_frame(editor, cx), None);
+      assert!(!editor.line_layouts.contains_key(&0));
+    });
+  }
+}
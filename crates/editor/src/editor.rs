@@ -1,62 +1,132 @@
 use std::{
-  collections::{HashMap, VecDeque},
+  borrow::Cow,
+  collections::HashMap,
   ops::Range,
+  path::{Path, PathBuf},
+  rc::Rc,
   sync::Arc,
   time::Instant,
 };
 
-use buffer::TransactionId;
+use buffer::{Anchor, TransactionId};
 use gpui::{
   App, Bounds, Context, CursorStyle, Entity, EntityInputHandler, FocusHandle, Focusable,
-  MouseDownEvent, MouseMoveEvent, MouseUpEvent, Pixels, Point, ScrollHandle, ShapedLine,
-  UTF16Selection, Window, black, div, point, prelude::*, px, white,
+  KeyDownEvent, MouseDownEvent, MouseMoveEvent, MouseUpEvent, Pixels, Point, ScrollHandle,
+  ShapedLine, SharedString, TextStyle, UTF16Selection, Window, div, point, prelude::*, px,
 };
 use syntax::Theme;
 
+#[cfg(feature = "test-interface")]
+use crate::test_interface::PaintedFrame;
 use crate::{
+  appearance::{self, ThemeSync},
+  auto_pair::{self, AutoPair},
+  balance::{self, BracketBalance},
   boundaries::{line_range_at_offset, word_range_at_offset},
+  code_cells::{self, CodeCells},
+  command_palette::{self, CommandPalette},
+  conflicts::{self, Conflicts},
+  coverage::{self, CoverageOverlay, FileCoverage},
+  crash_recovery,
   cursor_blink::CursorBlink,
+  dap::{self, Debugger},
+  decorations::{DecorationId, DecorationSet, DecorationStyle},
+  describe::{self, CharacterDescription},
   document::Document,
+  echo::{self, CaretEcho},
   editor_element::{EditorElement, PositionMap},
-  gutter_element::GutterElement,
+  elastic_tabs::{self, ElasticTabs},
+  files,
+  find::{FindBar, FindMatches},
+  focus_mode::{self, FocusMode},
+  folds,
+  font::EditorFont,
+  git_status::{self, GitStatuses},
+  gutter_element::{GutterElement, GutterLane, GutterLayout},
+  hex_edit::{self, HexCursor},
+  hex_element::{self, BYTES_PER_ROW, HexElement},
+  ime,
+  json::{self, JsonValidation},
+  jump::{self, JumpLabels},
+  large_file,
+  line_height::{self, LineHeightSettings},
+  line_index::ViewportLineIndex,
+  motion::{self, PendingMotion},
+  multi_cursor::{self, ExtraSelection},
+  path_completion::{self, PathCompletion},
+  plugins,
+  preview::{self, FileContents, FilePreview},
+  progress, quick_diff, ruler,
+  scratch::{self, ScratchSave},
+  scrollbar_element::{SCROLLBAR_THICKNESS, ScrollbarAxis, ScrollbarElement, Scrollbars},
+  selection_lock,
+  shortcuts::{self, ShortcutSheet},
+  tail::{self, TailMode, TailPattern},
+  test_runner::{self, TestRunner},
+  trace::{self, PerformanceTrace},
+  trust,
+  unicode_input::{self, UnicodeInput},
+  word_count::{self, WordCount},
+  word_diff::{self, Hunk, WordDiff},
+  wrap_map::{self, WrapMap},
 };
 
-#[derive(Clone, Debug)]
-pub struct Transaction {
-  pub id: TransactionId,
-  pub selection_before: Range<usize>,
-  pub selection_after: Range<usize>,
-}
-
 /// Default viewport height before first render
 const DEFAULT_VIEWPORT_HEIGHT: f32 = 800.0;
 /// Default viewport width before first render
 const DEFAULT_VIEWPORT_WIDTH: f32 = 1200.0;
+/// Default line height before first render
+const DEFAULT_LINE_HEIGHT: f32 = 20.0;
 /// Default maximum line width
 pub const DEFAULT_MAX_LINE_WIDTH: f32 = 800.0;
 /// Maximum number of cached shaped lines
 const MAX_CACHE_SIZE: usize = 200;
 /// Number of lines of padding when auto-scrolling to cursor
 const SCROLL_PADDING: usize = 3;
-/// Width of the gutter area
-const GUTTER_WIDTH: f32 = 70.0;
 /// Padding inside the editor content area
-const EDITOR_PADDING: f32 = 4.0;
+pub(crate) const EDITOR_PADDING: f32 = 4.0;
+/// Column that paragraphs are rewrapped to by default
+const DEFAULT_WRAP_COLUMN: usize = 80;
+/// Height of the status bar below the editor
+const STATUS_BAR_HEIGHT: f32 = 22.0;
+
+/// What a mouse drag extends the selection by, set by the click starting it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum SelectionGranularity {
+  #[default]
+  Character,
+  Word,
+  Line,
+}
 
 pub struct Editor {
   pub document: Entity<Document>,
+  // File the document was opened from
+  pub(crate) file_path: Option<PathBuf>,
   pub focus_handle: FocusHandle,
   pub selected_range: Range<usize>,
   pub selection_reversed: bool,
+  // Cursors and selections besides `selected_range`, edited along with it
+  pub(crate) extra_selections: Vec<ExtraSelection>,
   pub marked_range: Option<Range<usize>>,
   pub is_selecting: bool,
+  // Unit of the drag in progress, and the word or line its click selected
+  pub(crate) drag_granularity: SelectionGranularity,
+  pub(crate) drag_origin: Range<usize>,
 
   // Performance: cache and viewport
   pub line_layouts: HashMap<usize, Arc<ShapedLine>>,
+  // Typed text painted over a stale cached line until the next frame
+  pub(crate) echo: Option<CaretEcho>,
+  // Line starts of the visible lines, rebuilt after edits and scrolling
+  pub(crate) line_index: Arc<ViewportLineIndex>,
+  // Layout of the last painted frame, for queries from the platform's text input
+  pub(crate) last_position_map: Option<Rc<PositionMap>>,
 
   pub scroll_offset_y: f32, // Vertical scroll offset in lines (0.0 = top, 1.5 = 1.5 lines down)
   pub viewport_height: Pixels,
   pub viewport_width: Pixels,
+  pub line_height: Pixels,
   pub max_line_width: Pixels, // Maximum width of visible lines (never decreases to avoid scroll jumps)
   pub scroll_handle: ScrollHandle, // Handle for horizontal scrolling
 
@@ -65,131 +135,835 @@ pub struct Editor {
 
   // Target column for vertical navigation
   pub(crate) target_column: Option<usize>,
+  // Repeated movement not applied yet, coalesced until the next render
+  pub(crate) pending_motion: Option<PendingMotion>,
 
-  pub(crate) undo_stack: VecDeque<Transaction>,
-  pub(crate) redo_stack: VecDeque<Transaction>,
+  // Description of the next edit made through the input handler
+  pub(crate) edit_description: Option<&'static str>,
 
   pub theme: Theme,
+  // Whether the theme follows the window's appearance or was picked
+  pub(crate) theme_sync: ThemeSync,
+
+  // Font family, fallbacks and ligatures used for shaping
+  pub(crate) font: EditorFont,
+
+  // Maximum line length used when reflowing paragraphs
+  pub wrap_column: usize,
+  pub show_wrap_ruler: bool,
+  pub(crate) dragging_ruler: bool,
+
+  // Wrap long lines at the width of the text area instead of scrolling sideways
+  pub soft_wrap: bool,
+  pub(crate) wrap_map: WrapMap,
+  // Align tab-separated cells of adjacent lines, and the padding of their tabs
+  pub(crate) elastic_tabstops: bool,
+  pub(crate) elastic_tabs: ElasticTabs,
+
+  // Scrollbars over the text, shown while it scrolls
+  pub(crate) scrollbars: Scrollbars,
+
+  // Show the document as hex + ASCII bytes instead of text
+  pub hex_mode: bool,
+  pub(crate) hex_cursor: HexCursor,
+
+  // Lanes of the gutter and their widths
+  pub gutter_layout: GutterLayout,
 
   // Track syntax highlighting version to invalidate cache when highlights change
   pub last_highlights_version: usize,
 
   // Cursor blinking
   pub cursor_blink: Entity<CursorBlink>,
-}
 
-fn generate_rust_test_content_100k() -> String {
-  let base_content = r#"// Rust example with syntax highlighting - THIS IS A VERY LONG LINE TO TEST HORIZONTAL SCROLLING ============================================================================================================================================================================
-fn main() {
-    let x = 42;
-    let name = "World";
-    println!("Hello, {}! The answer is {}", name, x);
-    let very_long_variable_name_to_test_horizontal_scrolling = "This is a very long string that should cause horizontal scrolling when displayed in the editor viewport because it exceeds the normal width of the editor window";
-
-    // Test various token types with a very long comment that goes on and on and on and on and on and on and on and on and on and on and on and on and on and on and on and on and on and on and on and on
-    let mut counter = 0;
-    for i in 0..10 {
-        counter += i;
-    }
+  // Line decorations added by host applications
+  pub(crate) decorations: DecorationSet,
+  /// Decorations plugins added, by index of the plugin
+  pub(crate) plugin_decorations: Vec<(usize, DecorationId)>,
 
-    if counter > 20 {
-        println!("Counter is greater than 20: {} - and here is some extra text to make this line very long so we can test horizontal scrolling in the editor viewport", counter);
-    }
-}
+  // Run buttons for tests found in Rust documents
+  pub(crate) test_runner: TestRunner,
 
-struct Person {
-    name: String,
-    age: u32,
-}
+  // Breakpoints and the debugging session
+  pub(crate) debugger: Debugger,
 
-impl Person {
-    fn new(name: &str, age: u32) -> Self {
-        Self {
-            name: name.to_string(),
-            age,
-        }
-    }
+  // Code cells run inline in code cells mode
+  pub(crate) code_cells: CodeCells,
 
-    fn greet(&self) {
-        println!("Hi, I'm {} and I'm {} years old - and this is another very long line that should demonstrate horizontal scrolling capabilities in the text editor with the sticky gutter feature", self.name, self.age);
-    }
-}
+  // Scopes dimmed around the cursor in focus mode
+  pub(crate) focus_mode: FocusMode,
 
-// Test with more lines for scrolling
-fn fibonacci(n: u32) -> u32 {
-    match n {
-        0 => 0,
-        1 => 1,
-        _ => fibonacci(n - 1) + fibonacci(n - 2), // Recursive call with a long comment ===============================================================================================
-    }
-}
+  /// Document version at which ToggleSelectionLock made movements select
+  pub(crate) selection_lock: Option<usize>,
 
-#[derive(Debug, Clone)]
-enum Color {
-    Red,
-    Green,
-    Blue,
-    RGB(u8, u8, u8), // RGB color variant with three u8 values representing red, green, and blue components respectively in the standard RGB color model used in computer graphics
-}
+  /// Writing back the open scratch buffer after edits
+  pub(crate) scratch_save: ScratchSave,
 
-trait Drawable {
-    fn draw(&self);
-}
-"#;
+  /// Highlighting and whole-document checks are off for a document above
+  /// the large file thresholds
+  pub(crate) large_file: bool,
 
-  // Repeat content to reach 100K+ lines
-  let mut content = String::new();
-  let base_line_count = base_content.lines().count();
-  let repetitions = (100_000 / base_line_count) + 1;
+  /// Regions folded with FoldBracketRegion, from bracket to bracket
+  pub(crate) folds: Vec<Range<Anchor>>,
 
-  for i in 0..repetitions {
-    content.push_str(&format!("// ===== Repetition {} =====\n", i + 1));
-    content.push_str(base_content);
-    content.push('\n');
-  }
+  // Changed words on lines modified since the last commit
+  pub(crate) word_diff: WordDiff,
+
+  // Merge conflict regions with their resolution buttons
+  pub(crate) conflicts: Conflicts,
+
+  // First unbalanced bracket or quote, marked as a warning
+  pub(crate) bracket_balance: BracketBalance,
+
+  // Syntax error of JSON documents, marked where the parser stopped
+  pub(crate) json_validation: JsonValidation,
+
+  // Words per line of prose documents, for the status bar
+  pub(crate) word_count: WordCount,
+  // Whether the list of running background tasks is open
+  pub(crate) progress_menu_open: bool,
+
+  // Hunk shown in the quick diff popup
+  pub(crate) quick_diff: Option<Hunk>,
+
+  // Covered/uncovered line tints from an lcov report
+  pub(crate) coverage: CoverageOverlay,
 
-  content
+  // Following a growing log file
+  pub(crate) tail: Option<TailMode>,
+
+  // Frame timings and input events recorded for a performance trace
+  pub(crate) trace: Option<PerformanceTrace>,
+
+  /// Character popup opened with DescribeCharacter, closed by the next key or click
+  pub(crate) character_description: Option<CharacterDescription>,
+
+  /// Word labels of a JumpToWord in progress, taking the keys typed
+  pub(crate) jump: Option<JumpLabels>,
+
+  /// Open InsertUnicode palette, focused instead of the editor
+  pub(crate) unicode_input: Option<Entity<UnicodeInput>>,
+  /// Open ShowCommandPalette palette, focused instead of the editor
+  pub(crate) command_palette: Option<Entity<CommandPalette>>,
+  /// Open find bar, focused instead of the editor
+  pub(crate) find_bar: Option<Entity<FindBar>>,
+  /// Last query found, kept for FindNext once the bar is closed
+  pub(crate) find_matches: FindMatches,
+  // Entries offered for the path typed in a string
+  pub(crate) path_completion: Option<PathCompletion>,
+
+  /// Open ShowKeyboardShortcuts cheat sheet, focused instead of the editor
+  pub(crate) shortcut_sheet: Option<Entity<ShortcutSheet>>,
+
+  /// Shown instead of the text when the open file is an image or binary
+  pub(crate) preview: Option<FilePreview>,
+
+  /// What the last frame painted, reported by TestInterface
+  #[cfg(feature = "test-interface")]
+  pub(crate) painted: Option<PaintedFrame>,
 }
 
 impl Editor {
+  /// An editor on an empty, untitled document
   pub fn new(cx: &mut Context<Self>) -> Self {
-    let content = generate_rust_test_content_100k();
+    let document = cx.new(|cx| Document::new("", None, cx));
+    Self::with_document(document, cx)
+  }
 
-    let document = cx.new(|cx| Document::new(&content, Some("rs"), cx));
+  pub fn with_document(document: Entity<Document>, cx: &mut Context<Self>) -> Self {
     let cursor_blink = cx.new(CursorBlink::new);
 
-    Self {
+    cx.observe(&document, |editor, _, cx| {
+      test_runner::schedule_refresh(editor, cx);
+      code_cells::schedule_refresh(editor, cx);
+      focus_mode::schedule_refresh(editor, cx);
+      word_diff::schedule_refresh(editor, cx);
+      conflicts::schedule_refresh(editor, cx);
+      balance::schedule_refresh(editor, cx);
+      json::schedule_refresh(editor, cx);
+      word_count::update(editor, cx);
+      scratch::schedule_save(editor, cx);
+      files::follow_document_path(editor, cx);
+    })
+    .detach();
+    cx.observe_global::<GitStatuses>(word_diff::head_changed)
+      .detach();
+
+    let mut editor = Self {
       document,
+      file_path: None,
       focus_handle: cx.focus_handle(),
       selected_range: 0..0,
       selection_reversed: false,
+      extra_selections: Vec::new(),
       marked_range: None,
       is_selecting: false,
+      drag_granularity: SelectionGranularity::Character,
+      drag_origin: 0..0,
       line_layouts: HashMap::new(),
+      echo: None,
+      line_index: Arc::default(),
+      last_position_map: None,
       scroll_offset_y: 0.0,
       viewport_height: px(DEFAULT_VIEWPORT_HEIGHT), // Will be updated on first render
       viewport_width: px(DEFAULT_VIEWPORT_WIDTH),   // Will be updated on first render
+      line_height: px(DEFAULT_LINE_HEIGHT),         // Will be updated on first render
       max_line_width: px(DEFAULT_MAX_LINE_WIDTH),   // Will be updated on first render
       scroll_handle: ScrollHandle::new(),
       max_cache_size: MAX_CACHE_SIZE,
       target_column: None,
-      undo_stack: VecDeque::new(),
-      redo_stack: VecDeque::new(),
+      pending_motion: None,
+      edit_description: None,
       theme: Theme::dark(),
+      theme_sync: ThemeSync::default(),
+      font: EditorFont::default(),
+      wrap_column: DEFAULT_WRAP_COLUMN,
+      show_wrap_ruler: false,
+      dragging_ruler: false,
+      soft_wrap: false,
+      wrap_map: WrapMap::default(),
+      elastic_tabstops: false,
+      elastic_tabs: ElasticTabs::default(),
+      scrollbars: Scrollbars::default(),
+      hex_mode: false,
+      hex_cursor: HexCursor::default(),
+      gutter_layout: GutterLayout::default(),
       last_highlights_version: 0,
       cursor_blink,
+      decorations: DecorationSet::default(),
+      plugin_decorations: Vec::new(),
+      test_runner: TestRunner::default(),
+      debugger: Debugger::default(),
+      code_cells: CodeCells::default(),
+      focus_mode: FocusMode::default(),
+      selection_lock: None,
+      scratch_save: ScratchSave::default(),
+      large_file: false,
+      folds: Vec::new(),
+      word_diff: WordDiff::default(),
+      conflicts: Conflicts::default(),
+      bracket_balance: BracketBalance::default(),
+      json_validation: JsonValidation::default(),
+      word_count: WordCount::default(),
+      progress_menu_open: false,
+      quick_diff: None,
+      coverage: CoverageOverlay::default(),
+      tail: None,
+      trace: None,
+      character_description: None,
+      jump: None,
+      unicode_input: None,
+      command_palette: None,
+      find_bar: None,
+      find_matches: FindMatches::default(),
+      path_completion: None,
+      shortcut_sheet: None,
+      preview: None,
+      #[cfg(feature = "test-interface")]
+      painted: None,
+    };
+    large_file::check(&mut editor, cx);
+    test_runner::schedule_refresh(&mut editor, cx);
+    conflicts::schedule_refresh(&mut editor, cx);
+    balance::schedule_refresh(&mut editor, cx);
+    json::schedule_refresh(&mut editor, cx);
+    word_count::update(&mut editor, cx);
+    editor
+  }
+
+  /// Another view of the document `editor` shows, with its own cursor and
+  /// scroll position; edits in either show up in both
+  pub fn view_of(editor: &Entity<Editor>, cx: &mut Context<Self>) -> Self {
+    let (document, file_path) = {
+      let editor = editor.read(cx);
+      (editor.document.clone(), editor.file_path.clone())
+    };
+    let mut view = Self::with_document(document, cx);
+    view.file_path = file_path;
+    view
+  }
+
+  pub fn file_path(&self) -> Option<&Path> {
+    self.file_path.as_deref()
+  }
+
+  /// Whether the document changed since its file was read or saved or, for
+  /// scratch buffers, last written
+  pub fn is_edited(&self, cx: &App) -> bool {
+    if scratch::is_scratch(self) {
+      scratch::has_unsaved_edits(self, cx)
+    } else {
+      self.document.read(cx).is_dirty()
+    }
+  }
+
+  /// Whether `path` names the file the editor shows
+  pub fn is_open(&self, path: &Path) -> bool {
+    self.file_path().is_some_and(|open| {
+      open == path
+        || path
+          .canonicalize()
+          .is_ok_and(|path| open.canonicalize().is_ok_and(|open| open == path))
+    })
+  }
+
+  /// Replace the document with the contents of `path`, or a preview of it.
+  /// The file already open is kept as it is, edits and cursor included.
+  pub fn open_file(&mut self, path: PathBuf, cx: &mut Context<Self>) -> Result<(), String> {
+    if self.is_open(&path) {
+      return Ok(());
+    }
+    let contents = preview::load(&path)?;
+    self.show_file(path, contents, cx);
+    Ok(())
+  }
+
+  /// Replace the document with `contents` read from `path`
+  pub(crate) fn show_file(
+    &mut self,
+    path: PathBuf,
+    contents: FileContents,
+    cx: &mut Context<Self>,
+  ) {
+    // The scratch buffer being replaced keeps its latest edits
+    scratch::save_now(self, cx);
+    let (text, preview) = match contents {
+      FileContents::Text(text) => (text, None),
+      FileContents::Preview(preview) => (String::new(), Some(preview)),
+    };
+    let file_ext = path.extension().and_then(|ext| ext.to_str());
+    let document = cx.new(|cx| {
+      let mut document = Document::new(&text, file_ext, cx);
+      document.set_path(preview.is_none().then(|| path.clone()));
+      crash_recovery::open(&mut document, cx);
+      document
+    });
+    cx.observe(&document, |editor, _, cx| {
+      test_runner::schedule_refresh(editor, cx);
+      code_cells::schedule_refresh(editor, cx);
+      focus_mode::schedule_refresh(editor, cx);
+      word_diff::schedule_refresh(editor, cx);
+      conflicts::schedule_refresh(editor, cx);
+      balance::schedule_refresh(editor, cx);
+      json::schedule_refresh(editor, cx);
+      word_count::update(editor, cx);
+      scratch::schedule_save(editor, cx);
+      files::follow_document_path(editor, cx);
+    })
+    .detach();
+
+    self.stop_tail(cx);
+    self.clear_coverage(cx);
+    dap::reset(self, cx);
+    code_cells::clear(self);
+    focus_mode::clear(self);
+    word_diff::clear(self);
+    conflicts::clear(self);
+    balance::clear(self);
+    json::clear(self);
+    word_count::clear(self);
+    elastic_tabs::clear(self);
+    plugins::clear(self);
+    self.quick_diff = None;
+    self.jump = None;
+    self.folds.clear();
+    self.decorations.clear();
+    self.document = document;
+    self.file_path = Some(path);
+    self.preview = preview;
+    self.selected_range = 0..0;
+    self.selection_reversed = false;
+    self.extra_selections.clear();
+    self.marked_range = None;
+    self.target_column = None;
+    self.pending_motion = None;
+    self.echo = None;
+    self.character_description = None;
+    self.path_completion = None;
+    self.find_matches.invalidate();
+    self.wrap_map.invalidate();
+    self.line_layouts.clear();
+    self.last_position_map = None;
+    self.last_highlights_version = 0;
+    self.scroll_offset_y = 0.0;
+    self.scroll_handle.set_offset(point(px(0.), px(0.)));
+    self.max_line_width = px(DEFAULT_MAX_LINE_WIDTH);
+    large_file::check(self, cx);
+    folds::restore(self, cx);
+    test_runner::schedule_refresh(self, cx);
+    code_cells::schedule_refresh(self, cx);
+    focus_mode::schedule_refresh(self, cx);
+    word_diff::reload_head(self, cx);
+    conflicts::schedule_refresh(self, cx);
+    balance::schedule_refresh(self, cx);
+    json::schedule_refresh(self, cx);
+    word_count::update(self, cx);
+    scratch::opened(self, cx);
+    cx.notify();
+  }
+
+  /// Open a new scratch buffer, highlighted for files with `extension`
+  pub fn new_scratch_buffer(
+    &mut self,
+    extension: Option<&str>,
+    cx: &mut Context<Self>,
+  ) -> Result<(), String> {
+    scratch::new_buffer(self, extension, cx)
+  }
+
+  pub fn test_status(&self, name: &str) -> Option<test_runner::TestStatus> {
+    self.test_runner.status(name)
+  }
+
+  /// Show line coverage for the document in the gutter
+  pub fn set_coverage(&mut self, coverage: FileCoverage, cx: &mut Context<Self>) {
+    coverage::set_coverage(self, coverage, cx);
+  }
+
+  pub fn clear_coverage(&mut self, cx: &mut Context<Self>) {
+    coverage::clear_coverage(self, cx);
+  }
+
+  pub(crate) fn toggle_coverage_overlay(&mut self, cx: &mut Context<Self>) {
+    coverage::toggle_overlay(self, cx);
+  }
+
+  /// Percentage of instrumented lines covered, while the overlay is shown
+  pub fn coverage_percentage(&self) -> Option<f32> {
+    if self.coverage.is_visible() {
+      self.coverage.percentage()
+    } else {
+      None
+    }
+  }
+
+  pub fn font(&self) -> &EditorFont {
+    &self.font
+  }
+
+  /// Change the editor font, reshaping all lines with it
+  pub fn set_font(&mut self, font: EditorFont, cx: &mut Context<Self>) {
+    if self.font == font {
+      return;
+    }
+    self.font = font;
+    self.line_layouts.clear();
+    // Line widths change with the font
+    self.max_line_width = px(DEFAULT_MAX_LINE_WIDTH);
+    cx.notify();
+  }
+
+  /// Text style of the window with the editor font applied
+  pub(crate) fn text_style(&self, window: &Window, cx: &App) -> TextStyle {
+    let mut style = window.text_style();
+    self.font.apply(&mut style);
+    line_height::apply(self, &mut style, cx);
+    style
+  }
+
+  /// Height of a text row, with the line height set for the language
+  pub(crate) fn line_height_px(&self, window: &Window, cx: &App) -> Pixels {
+    self
+      .text_style(window, cx)
+      .line_height_in_pixels(window.rem_size())
+  }
+
+  /// Set the line height, as a multiple of the font size, for documents of
+  /// this editor's language
+  pub fn set_line_height(&mut self, multiplier: f32, cx: &mut Context<Self>) {
+    let language = ruler::language_key(self, cx).to_string();
+    cx.default_global::<LineHeightSettings>()
+      .set(language, multiplier);
+    self.line_layouts.clear();
+    cx.notify();
+  }
+
+  /// Wrap long lines at the width of the text area, or scroll sideways to them
+  pub fn set_soft_wrap(&mut self, enabled: bool, cx: &mut Context<Self>) {
+    if self.soft_wrap == enabled {
+      return;
+    }
+    // Rows mean something else once wrapped, keep the top line in view
+    let top_line = wrap_map::line_at_row(self, self.scroll_offset_y as usize, cx);
+    self.soft_wrap = enabled;
+    wrap_map::refresh(self, cx);
+    self.scroll_offset_y = wrap_map::rows_for_line(self, top_line, cx).start as f32;
+    self.scroll_handle.set_offset(point(px(0.), px(0.)));
+    self.max_line_width = px(DEFAULT_MAX_LINE_WIDTH);
+    cx.notify();
+  }
+
+  /// Lay out tab-separated cells of adjacent lines in aligned columns
+  pub fn set_elastic_tabstops(&mut self, enabled: bool, cx: &mut Context<Self>) {
+    if self.elastic_tabstops == enabled {
+      return;
+    }
+    self.elastic_tabstops = enabled;
+    elastic_tabs::clear(self);
+    wrap_map::refresh(self, cx);
+    cx.notify();
+  }
+
+  /// Set the wrap column for documents of this editor's language
+  pub fn set_wrap_column(&mut self, column: usize, cx: &mut Context<Self>) {
+    ruler::set_wrap_column(self, column, cx);
+  }
+
+  /// Follow `path` as a log file, the document must hold its current contents
+  pub fn start_tail(&mut self, path: PathBuf, patterns: Vec<TailPattern>, cx: &mut Context<Self>) {
+    let read_offset = self.document.read(cx).len_bytes() as u64;
+    tail::start(self, path, read_offset, patterns, cx);
+  }
+
+  pub fn stop_tail(&mut self, cx: &mut Context<Self>) {
+    tail::stop(self, cx);
+  }
+
+  /// Start recording frame timings, line cache stats and input events
+  pub fn start_performance_trace(&mut self, cx: &mut Context<Self>) {
+    trace::start(self, cx);
+  }
+
+  /// Stop recording and write a Chrome trace JSON file, returning its path
+  pub fn stop_performance_trace(
+    &mut self,
+    cx: &mut Context<Self>,
+  ) -> Result<Option<PathBuf>, String> {
+    trace::stop(self, cx)
+  }
+
+  pub fn is_tracing(&self) -> bool {
+    self.trace.is_some()
+  }
+
+  pub(crate) fn trace_event(&mut self, name: &'static str) {
+    if let Some(trace) = self.trace.as_mut() {
+      trace.record_event(name, Instant::now());
+    }
+  }
+
+  pub(crate) fn is_scrolled_to_bottom(&self, cx: &App) -> bool {
+    let visible_rows = (self.viewport_height / self.line_height).floor() as usize;
+    self.scroll_offset_y as usize + visible_rows >= self.scrollable_rows(cx)
+  }
+
+  pub(crate) fn scroll_to_bottom(&mut self, cx: &mut Context<Self>) {
+    let visible_rows = (self.viewport_height / self.line_height).floor() as usize;
+    self.scroll_offset_y = self.scrollable_rows(cx).saturating_sub(visible_rows) as f32;
+    cx.notify();
+  }
+
+  /// Run save hooks on the document, recording any edit for undo
+  pub fn prepare_for_save(&mut self, cx: &mut Context<Self>) {
+    let selection = self.selected_range.clone();
+    let transaction_id = self.document.update(cx, |doc, cx| doc.prepare_for_save(cx));
+    if let Some(transaction_id) = transaction_id {
+      let last_line = self.document.read(cx).len_lines().saturating_sub(2);
+      self.invalidate_lines_from(last_line);
+      self.record_transaction(
+        transaction_id,
+        selection.clone(),
+        selection,
+        "Insert Final Newline",
+        cx,
+      );
+      cx.notify();
+    }
+  }
+
+  /// Text items displayed in the status bar, left to right
+  pub fn status_items(&self, cx: &App) -> Vec<SharedString> {
+    let mut items = Vec::new();
+    if trust::is_restricted(self, cx) {
+      items.push(SharedString::new_static("Restricted Mode"));
+    }
+    if let Some(line) = dap::stopped_line(self) {
+      items.push(format!("Paused on line {}", line + 1).into());
+    } else if dap::is_debugging(self) {
+      items.push(SharedString::new_static("Debugging"));
+    }
+    if self.is_tracing() {
+      items.push(SharedString::new_static("Recording trace"));
+    }
+    if jump::is_active(self) {
+      items.push(SharedString::new_static("Type a label to jump"));
+    }
+    if focus_mode::is_enabled(self) {
+      items.push(SharedString::new_static("Focus mode"));
+    }
+    if code_cells::is_enabled(self) {
+      items.push(SharedString::new_static("Code cells"));
+    }
+    if word_diff::is_enabled(self) {
+      items.push(SharedString::new_static("Word diff"));
+    }
+    if selection_lock::is_locked(self, cx) {
+      items.push(SharedString::new_static("Selection locked"));
+    }
+    items.extend(word_count::status_items(self, cx));
+    if let Some(tail) = &self.tail
+      && let Some(file_name) = tail.path().file_name()
+    {
+      items.push(format!("Following {}", file_name.to_string_lossy()).into());
+    }
+    if self.document.read(cx).highlighting_failed() {
+      items.push(SharedString::new_static("Highlighting failed"));
+    }
+    if self.document.read(cx).missing_final_newline() {
+      items.push(SharedString::new_static("No newline at end of file"));
+    }
+    if let Some(status) = self
+      .file_path()
+      .and_then(|path| git_status::file_status(path, cx))
+    {
+      items.push(SharedString::new_static(status.label()));
     }
+    if let Some(percentage) = self.coverage_percentage() {
+      items.push(format!("Coverage: {:.1}%", percentage).into());
+    }
+    items
+  }
+
+  /// Where a popup below the cursor goes, relative to the editor
+  pub(crate) fn popup_origin(&self, cx: &App) -> Point<Pixels> {
+    let document = self.document.read(cx);
+    let cursor_offset = self.cursor_offset();
+    let cursor_line = document.char_to_line(cursor_offset);
+    let (cursor_row, row_start) = wrap_map::row_at(self, cursor_offset, cx);
+    let y = self.line_height * (cursor_row as f32 + 1.0 - self.scroll_offset_y);
+    if self.hex_mode {
+      return point(px(EDITOR_PADDING), y.max(px(0.)));
+    }
+
+    let line_start = document.line_to_char(cursor_line);
+    let cursor_x = self
+      .line_layouts
+      .get(&cursor_line)
+      .map_or(px(0.), |shaped_line| {
+        shaped_line.x_for_index(cursor_offset - line_start)
+          - shaped_line.x_for_index(row_start - line_start)
+      });
+    let gutter_width = self.gutter_layout.width();
+    let x = gutter_width + px(EDITOR_PADDING) + cursor_x + self.scroll_handle.offset().x;
+    point(x.max(gutter_width), y.max(px(0.)))
+  }
+
+  /// Rows the view can scroll through: text lines or the rows they wrap
+  /// to, or byte rows in hex mode
+  pub(crate) fn scrollable_rows(&self, cx: &App) -> usize {
+    if self.hex_mode {
+      hex_element::row_count(self.document.read(cx).len_bytes())
+    } else {
+      wrap_map::row_count(self, cx)
+    }
+  }
+
+  /// Place the cursor on `byte`, or select up to its character
+  pub fn hex_clicked(&mut self, byte: usize, extend_selection: bool, cx: &mut Context<Self>) {
+    hex_edit::move_to_byte(self, byte, extend_selection, cx);
+  }
+
+  /// Handle a click on `lane` of the gutter next to `line`
+  pub fn gutter_clicked(
+    &mut self,
+    line: usize,
+    lane: GutterLane,
+    window: &mut Window,
+    cx: &mut Context<Self>,
+  ) {
+    match lane {
+      GutterLane::Markers | GutterLane::LineNumbers => {
+        // The button of a running cell stops it
+        if code_cells::is_running_at_line(self, line, cx) {
+          code_cells::stop_cell_at_line(self, line, cx);
+          return;
+        }
+        let is_cell = code_cells::has_cell_at_line(self, line, cx);
+        let runnable = is_cell || test_runner::test_at_line(self, line, cx).is_some();
+        if runnable && trust::is_restricted(self, cx) {
+          trust::request_trust(self, window, cx);
+          return;
+        }
+        if is_cell {
+          code_cells::run_cell_at_line(self, line, cx);
+        } else if runnable {
+          test_runner::run_test_at_line(self, line, cx);
+        } else if !quick_diff::show(self, line, cx) {
+          quick_diff::dismiss(self, cx);
+        }
+      }
+      GutterLane::Breakpoints => dap::toggle_breakpoint(self, line, cx),
+      // Nothing folds yet
+      GutterLane::Folds => {}
+    }
+  }
+
+  /// Switch between the light and dark themes with the window's appearance
+  pub fn follow_system_appearance(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+    appearance::follow_window(self, window, cx);
+  }
+
+  /// Close the popups and palettes shown over the editor, giving focus back
+  /// to it from any of them
+  pub fn dismiss_overlays(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+    escape::close_overlays(self, window, cx);
+  }
+
+  /// Use `theme` regardless of the system appearance
+  pub fn set_theme(&mut self, theme: Theme, cx: &mut Context<Self>) {
+    appearance::pin_theme(self, theme, cx);
+  }
+
+  /// Swap in the theme from a `.toml` or `.json` file, see [`Theme::load_from_path`]
+  pub fn load_theme(&mut self, path: &Path, cx: &mut Context<Self>) -> Result<(), String> {
+    let theme = Theme::load_from_path(path)?;
+    appearance::pin_theme(self, theme, cx);
+    Ok(())
+  }
+
+  /// Switch to the default theme of the other mode, which then stays when the
+  /// OS appearance changes
+  pub fn toggle_dark_mode(&mut self, cx: &mut Context<Self>) {
+    let mut theme = self.theme.clone();
+    theme.toggle();
+    appearance::pin_theme(self, theme, cx);
   }
 
-  #[cfg(test)]
-  pub fn toggle_dark_mode(&mut self) {
-    self.theme.toggle();
+  /// Replace the selection with `text` as one undoable edit, leaving the
+  /// cursor after it
+  pub fn insert_text(&mut self, text: &str, description: &'static str, cx: &mut Context<Self>) {
+    self.target_column = None;
+    let range = self.selected_range.clone();
+    let start_line = self.document.read(cx).char_to_line(range.start);
+
+    let transaction_id = self.document.update(cx, |doc, cx| {
+      let id = doc.buffer.transaction(Instant::now(), |buffer, tx| {
+        buffer.replace(tx, range.clone(), text);
+      });
+      doc.schedule_recompute_highlights(cx);
+      cx.notify();
+      id
+    });
+
+    let cursor = range.start + text.chars().count();
+    self.selected_range = cursor..cursor;
+    self.selection_reversed = false;
+    self.invalidate_lines_from(start_line);
+    self.record_transaction(transaction_id, range, cursor..cursor, description, cx);
+    cx.notify();
+  }
+
+  /// Replace `range_utf16`, the marked text or the selection with `new_text`
+  /// as typed, leaving the cursor after it
+  fn replace_text(
+    &mut self,
+    range_utf16: Option<Range<usize>>,
+    new_text: &str,
+    cx: &mut Context<Self>,
+  ) {
+    let range = range_utf16
+      .as_ref()
+      .map(|range_utf16| self.range_from_utf16(range_utf16, cx))
+      .or(self.marked_range.clone())
+      .unwrap_or(self.selected_range.clone());
+
+    // Brackets and quotes typed at the selection pair up
+    let auto_pair =
+      (range_utf16.is_none() && self.marked_range.is_none() && self.edit_description.is_none())
+        .then(|| auto_pair::edit_for(self.document.read(cx), range.clone(), new_text))
+        .flatten();
+    let (new_text, selection) = match auto_pair {
+      Some(AutoPair::Skip) => {
+        self.move_to(range.end + 1, cx);
+        return;
+      }
+      Some(AutoPair::Edit { text, selection }) => (Cow::Owned(text), Some(selection)),
+      None => (Cow::Borrowed(new_text), None),
+    };
+    let new_text = new_text.as_ref();
+
+    let selection_before = self.selected_range.clone();
+    let start_line = self.document.read(cx).char_to_line(range.start);
+    let end_line = self.document.read(cx).char_to_line(range.end);
+
+    let transaction_id = self.document.update(cx, |doc, cx| {
+      let id = doc.buffer.transaction(Instant::now(), |buffer, tx| {
+        buffer.replace(tx, range.clone(), new_text);
+      });
+
+      // Trigger async syntax re-highlighting with debouncing
+      doc.schedule_recompute_highlights(cx);
+
+      cx.notify();
+      id
+    });
+
+    self.selected_range = selection
+      .clone()
+      .unwrap_or(range.start + new_text.len()..range.start + new_text.len());
+    self.marked_range.take();
+
+    let has_newline = new_text.contains('\n');
+
+    if has_newline || start_line != end_line {
+      // Multi-line edit: invalidate from start line onwards
+      self.invalidate_lines_from(start_line);
+    } else if selection.is_some() || !echo::begin(self, range.clone(), new_text, cx) {
+      // Single-line edit that can't be echoed, such as a pair with the cursor
+      // inside it: only invalidate the affected line
+      self.invalidate_line(start_line);
+    }
+
+    let selection_after = self.selected_range.clone();
+    let description = self.edit_description.take().unwrap_or("Typing");
+
+    self.record_transaction(
+      transaction_id,
+      selection_before,
+      selection_after,
+      description,
+      cx,
+    );
+
+    path_completion::update(self, cx);
+    cx.notify();
   }
 
   pub fn document(&self) -> &Entity<Document> {
     &self.document
   }
 
+  /// Create an anchor range for `range` that follows subsequent edits
+  pub fn anchor_range(&self, range: Range<usize>, cx: &App) -> Range<Anchor> {
+    self.document.read(cx).anchor_range(range)
+  }
+
+  /// Decorate the lines covered by `range`, returning an id to remove it later
+  pub fn add_line_decoration(
+    &mut self,
+    range: Range<Anchor>,
+    style: DecorationStyle,
+    cx: &mut Context<Self>,
+  ) -> DecorationId {
+    let id = self.decorations.insert_host(range, style);
+    cx.notify();
+    id
+  }
+
+  pub fn remove_line_decoration(&mut self, id: DecorationId, cx: &mut Context<Self>) {
+    if self.decorations.remove_host(id) {
+      cx.notify();
+    }
+  }
+
+  /// Remove the decorations added with `add_line_decoration`, leaving those
+  /// of the editor's own features
+  pub fn clear_line_decorations(&mut self, cx: &mut Context<Self>) {
+    if self.decorations.clear_host() {
+      cx.notify();
+    }
+  }
+
   /// Invalidate a single line in the cache
   pub(crate) fn invalidate_line(&mut self, line: usize) {
     self.line_layouts.remove(&line);
@@ -202,6 +976,37 @@ impl Editor {
       .retain(|&line_idx, _| line_idx < start_line);
   }
 
+  /// Invalidate the lines changed since the document was at `version`, and
+  /// every line after them if the line count changed from `line_count`
+  pub(crate) fn invalidate_edits_since(&mut self, version: usize, line_count: usize, cx: &App) {
+    let document = self.document.read(cx);
+    let Some(range) = word_count::changed_range(document.edits_since(version)) else {
+      return;
+    };
+    let first = document.char_to_line(range.start);
+    let last = document.char_to_line(range.end);
+    if document.len_lines() != line_count {
+      self.invalidate_lines_from(first);
+    } else {
+      self
+        .line_layouts
+        .retain(|&line_idx, _| line_idx < first || line_idx > last);
+    }
+  }
+
+  /// Index of the line starts in `lines`, rebuilt if the text changed or the view scrolled
+  pub(crate) fn viewport_line_index(
+    &mut self,
+    lines: Range<usize>,
+    cx: &App,
+  ) -> Arc<ViewportLineIndex> {
+    let document = self.document.read(cx);
+    if !self.line_index.covers(document, &lines) {
+      self.line_index = Arc::new(ViewportLineIndex::new(document, lines));
+    }
+    Arc::clone(&self.line_index)
+  }
+
   pub fn ensure_cache_size(&mut self, viewport: Range<usize>) {
     // If cache is too large, keep only lines near the viewport
     if self.line_layouts.len() > self.max_cache_size {
@@ -215,13 +1020,22 @@ impl Editor {
   }
 
   pub(crate) fn ensure_cursor_visible(&mut self, window: &Window, cx: &mut Context<Self>) {
+    wrap_map::refresh(self, cx);
+    let total_lines = self.scrollable_rows(cx);
     let document = self.document.read(cx);
     let cursor_offset = self.cursor_offset();
-    let cursor_line = document.char_to_line(cursor_offset);
-    let total_lines = document.len_lines();
+    let cursor_row = if self.hex_mode {
+      hex_edit::cursor_byte(self, cx) / BYTES_PER_ROW
+    } else {
+      wrap_map::row_at(self, cursor_offset, cx).0
+    };
 
     // Calculate how many lines are visible in the viewport
-    let line_height = window.line_height();
+    let line_height = if self.hex_mode {
+      window.line_height()
+    } else {
+      self.line_height_px(window, cx)
+    };
     let visible_lines = (self.viewport_height / line_height).floor() as usize;
 
     // Offset for context padding when scrolling
@@ -232,22 +1046,26 @@ impl Editor {
     let scroll_end = scroll_start + visible_lines;
 
     // Ensure cursor is within the visible range with padding (vertical)
-    if cursor_line < scroll_start + scroll_padding {
+    if cursor_row < scroll_start + scroll_padding {
       // Cursor is too close to top, scroll up
-      self.scroll_offset_y = (cursor_line.saturating_sub(scroll_padding)) as f32;
-    } else if cursor_line >= scroll_end.saturating_sub(scroll_padding) {
+      self.scroll_offset_y = (cursor_row.saturating_sub(scroll_padding)) as f32;
+    } else if cursor_row >= scroll_end.saturating_sub(scroll_padding) {
       // Cursor is too close to bottom, scroll down
-      let target_line = cursor_line + scroll_padding;
-      self.scroll_offset_y = (target_line as f32 - visible_lines as f32 + 1.0).max(0.0);
+      let target_row = cursor_row + scroll_padding;
+      self.scroll_offset_y = (target_row as f32 - visible_lines as f32 + 1.0).max(0.0);
     }
 
-    // Ensure cursor is visible horizontally
-    if let Some(shaped_line) = self.line_layouts.get(&cursor_line) {
+    // Ensure cursor is visible horizontally, wrapped rows always are
+    let cursor_line = document.char_to_line(cursor_offset);
+    if !self.hex_mode
+      && !wrap_map::is_enabled(self)
+      && let Some(shaped_line) = self.line_layouts.get(&cursor_line)
+    {
       let line_start = document.line_to_char(cursor_line);
       let cursor_in_line = cursor_offset - line_start;
       let cursor_x = shaped_line.x_for_index(cursor_in_line);
 
-      let horizontal_padding = px(GUTTER_WIDTH) + px(EDITOR_PADDING) + px(100.0); // Extra padding for horizontal scrolling
+      let horizontal_padding = self.gutter_layout.width() + px(EDITOR_PADDING) + px(100.0); // Extra padding for horizontal scrolling
       let current_scroll_x = self.scroll_handle.offset().x;
 
       // Note: scroll_x is negative when scrolled right (0 = left edge, -100 = scrolled 100px right)
@@ -272,24 +1090,55 @@ impl Editor {
     self.scroll_offset_y = self.scroll_offset_y.clamp(0.0, max_scroll);
   }
 
+  /// Record the selections around transaction `id` in the document's
+  /// history, for undo and redo to restore
   pub(crate) fn record_transaction(
     &mut self,
     id: TransactionId,
     selection_before: Range<usize>,
     selection_after: Range<usize>,
+    description: &'static str,
+    cx: &mut App,
   ) {
-    // Check if we should update an existing transaction with the same ID (grouping)
-    if let Some(transaction) = self.undo_stack.iter_mut().find(|t| t.id == id) {
-      transaction.selection_after = selection_after;
-    } else {
-      // Create new transaction
-      self.undo_stack.push_back(Transaction {
-        id,
-        selection_before,
-        selection_after,
-      });
-      self.redo_stack.clear();
-    }
+    self.document.update(cx, |document, _| {
+      document
+        .buffer
+        .set_selection(id, selection_before, selection_after, description)
+    });
+  }
+
+  /// Name the next edit made through the input handler, which is typing
+  /// unless named
+  pub(crate) fn describe_next_edit(&mut self, description: &'static str) {
+    self.edit_description = Some(description);
+  }
+
+  /// What undo would revert, for an Edit menu's "Undo <description>" item
+  pub fn undo_description(&self, cx: &App) -> Option<&'static str> {
+    let buffer = &self.document.read(cx).buffer;
+    buffer
+      .undo_selection()
+      .map(|selection| selection.description)
+  }
+
+  /// What redo would reapply, for an Edit menu's "Redo <description>" item
+  pub fn redo_description(&self, cx: &App) -> Option<&'static str> {
+    let buffer = &self.document.read(cx).buffer;
+    buffer
+      .redo_selection()
+      .map(|selection| selection.description)
+  }
+
+  pub fn can_undo(&self, cx: &App) -> bool {
+    self.document.read(cx).can_undo()
+  }
+
+  pub fn can_redo(&self, cx: &App) -> bool {
+    self.document.read(cx).can_redo()
+  }
+
+  pub fn has_selection(&self) -> bool {
+    !self.selected_range.is_empty()
   }
 
   pub(crate) fn move_to(&mut self, offset: usize, cx: &mut Context<Self>) {
@@ -378,9 +1227,17 @@ impl Editor {
       return;
     };
 
+    if event.modifiers.alt && !event.modifiers.shift && event.click_count == 1 {
+      self.drag_granularity = SelectionGranularity::Character;
+      multi_cursor::add_cursor(self, offset, cx);
+      return;
+    }
+    multi_cursor::clear(self, cx);
+
     if event.modifiers.shift {
-      self.select_to(offset, cx);
+      self.extend_selection_to(offset, cx);
     } else {
+      self.drag_granularity = SelectionGranularity::Character;
       match event.click_count {
         1 => {
           self.move_to(offset, cx);
@@ -389,16 +1246,20 @@ impl Editor {
           let (word_start, word_end) = word_range_at_offset(self, offset, cx);
           self.selected_range = word_start..word_end;
           self.selection_reversed = false;
+          self.drag_granularity = SelectionGranularity::Word;
+          self.drag_origin = word_start..word_end;
           cx.notify();
         }
         3 => {
           let (line_start, line_end) = line_range_at_offset(self, offset, cx);
           self.selected_range = line_start..line_end;
           self.selection_reversed = false;
+          self.drag_granularity = SelectionGranularity::Line;
+          self.drag_origin = line_start..line_end;
           cx.notify();
         }
         _ => {
-          let doc_len = document.len();
+          let doc_len = self.document.read(cx).len();
           self.selected_range = 0..doc_len;
           self.selection_reversed = false;
           cx.notify();
@@ -427,7 +1288,32 @@ impl Editor {
       return;
     };
 
-    self.select_to(offset, cx);
+    self.extend_drag_to(offset, cx);
+  }
+
+  /// Extend the selection to a shift-click at `offset`, from the word or line
+  /// of an earlier double or triple click while it is still selected
+  pub(crate) fn extend_selection_to(&mut self, offset: usize, cx: &mut Context<Self>) {
+    let origin = &self.drag_origin;
+    if origin.start < self.selected_range.start || self.selected_range.end < origin.end {
+      self.drag_granularity = SelectionGranularity::Character;
+    }
+    self.extend_drag_to(offset, cx);
+  }
+
+  /// Extend the selection of a drag to `offset`, by whole words or lines
+  /// after a double or triple click
+  pub(crate) fn extend_drag_to(&mut self, offset: usize, cx: &mut Context<Self>) {
+    let (start, end) = match self.drag_granularity {
+      SelectionGranularity::Character => return self.select_to(offset, cx),
+      SelectionGranularity::Word => word_range_at_offset(self, offset, cx),
+      SelectionGranularity::Line => line_range_at_offset(self, offset, cx),
+    };
+    // The word or line clicked stays selected whichever way the drag goes
+    let origin = self.drag_origin.clone();
+    self.selection_reversed = start < origin.start;
+    self.selected_range = start.min(origin.start)..end.max(origin.end);
+    cx.notify();
   }
 }
 
@@ -476,53 +1362,27 @@ impl EntityInputHandler for Editor {
     &mut self,
     range_utf16: Option<Range<usize>>,
     new_text: &str,
-    _: &mut Window,
+    window: &mut Window,
     cx: &mut Context<Self>,
   ) {
+    self.trace_event("text_input");
+    motion::flush(self, window, cx);
     // Pause cursor blinking when typing
     self.cursor_blink.update(cx, |blink, cx| {
       blink.pause_blinking(cx);
     });
-    let range = range_utf16
-      .as_ref()
-      .map(|range_utf16| self.range_from_utf16(range_utf16, cx))
-      .or(self.marked_range.clone())
-      .unwrap_or(self.selected_range.clone());
-
-    let selection_before = self.selected_range.clone();
-    let start_line = self.document.read(cx).char_to_line(range.start);
-    let end_line = self.document.read(cx).char_to_line(range.end);
-
-    let transaction_id = self.document.update(cx, |doc, cx| {
-      let id = doc.buffer.transaction(Instant::now(), |buffer, tx| {
-        buffer.replace(tx, range.clone(), new_text);
+    // Hex digits edit bytes in the hex view
+    if self.hex_mode && hex_edit::input(self, new_text, cx) {
+      return;
+    }
+    if range_utf16.is_none() && self.marked_range.is_none() && multi_cursor::is_active(self) {
+      let description = self.edit_description.take();
+      return multi_cursor::for_each(self, cx, |editor, cx| {
+        editor.edit_description = description;
+        editor.replace_text(None, new_text, cx);
       });
-
-      // Trigger async syntax re-highlighting with debouncing
-      doc.schedule_recompute_highlights(cx);
-
-      cx.notify();
-      id
-    });
-
-    let has_newline = new_text.contains('\n');
-
-    if has_newline || start_line != end_line {
-      // Multi-line edit: invalidate from start line onwards
-      self.invalidate_lines_from(start_line);
-    } else {
-      // Single-line edit: only invalidate the affected line
-      self.invalidate_line(start_line);
     }
-
-    self.selected_range = range.start + new_text.len()..range.start + new_text.len();
-    self.marked_range.take();
-
-    let selection_after = self.selected_range.clone();
-
-    self.record_transaction(transaction_id, selection_before, selection_after);
-
-    cx.notify();
+    self.replace_text(range_utf16, new_text, cx);
   }
 
   fn replace_and_mark_text_in_range(
@@ -530,9 +1390,10 @@ impl EntityInputHandler for Editor {
     range_utf16: Option<Range<usize>>,
     new_text: &str,
     new_selected_range_utf16: Option<Range<usize>>,
-    _window: &mut Window,
+    window: &mut Window,
     cx: &mut Context<Self>,
   ) {
+    motion::flush(self, window, cx);
     // Pause cursor blinking when typing
     self.cursor_blink.update(cx, |blink, cx| {
       blink.pause_blinking(cx);
@@ -568,95 +1429,343 @@ impl EntityInputHandler for Editor {
 
   fn bounds_for_range(
     &mut self,
-    _range_utf16: Range<usize>,
-    _bounds: Bounds<Pixels>,
-    _window: &mut Window,
-    _cx: &mut Context<Self>,
+    range_utf16: Range<usize>,
+    bounds: Bounds<Pixels>,
+    window: &mut Window,
+    cx: &mut Context<Self>,
   ) -> Option<Bounds<Pixels>> {
-    None
+    let range = self.range_from_utf16(&range_utf16, cx);
+    ime::bounds_for_range(self, range, bounds, window.scale_factor(), cx)
   }
 
   fn character_index_for_point(
     &mut self,
-    _point: Point<Pixels>,
+    point: Point<Pixels>,
     _window: &mut Window,
-    _cx: &mut Context<Self>,
+    cx: &mut Context<Self>,
   ) -> Option<usize> {
-    None
+    let offset = ime::character_index_for_point(self, point, cx)?;
+    Some(self.offset_to_utf16(offset, cx))
   }
 }
 
 impl Render for Editor {
-  fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+  fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+    motion::flush(self, window, cx);
+    let status_items = self.status_items(cx);
+    progress::close_menu_if_idle(self, cx);
+
     div()
       .key_context("Editor")
       .track_focus(&self.focus_handle(cx))
       .cursor(CursorStyle::IBeam)
       .size_full()
-      .on_action(cx.listener(crate::actions::enter))
-      .on_action(cx.listener(crate::actions::backspace))
-      .on_action(cx.listener(crate::actions::backspace_word))
-      .on_action(cx.listener(crate::actions::backspace_all))
-      .on_action(cx.listener(crate::actions::delete))
+      .relative()
+      .on_action(cx.listener(motion::flushed(crate::actions::enter)))
+      .on_action(cx.listener(motion::flushed(crate::actions::indent)))
+      .on_action(cx.listener(motion::flushed(crate::actions::outdent)))
+      .on_action(cx.listener(motion::flushed(crate::actions::toggle_comment)))
+      .on_action(cx.listener(motion::flushed(crate::actions::backspace)))
+      .on_action(cx.listener(motion::flushed(crate::actions::backspace_word)))
+      .on_action(cx.listener(motion::flushed(crate::actions::backspace_all)))
+      .on_action(cx.listener(motion::flushed(crate::actions::delete)))
       .on_action(cx.listener(crate::actions::up))
       .on_action(cx.listener(crate::actions::down))
+      .on_action(cx.listener(motion::flushed(crate::actions::page_up)))
+      .on_action(cx.listener(motion::flushed(crate::actions::page_down)))
       .on_action(cx.listener(crate::actions::left))
-      .on_action(cx.listener(crate::actions::alt_left))
-      .on_action(cx.listener(crate::actions::cmd_left))
+      .on_action(cx.listener(motion::flushed(crate::actions::alt_left)))
+      .on_action(cx.listener(motion::flushed(crate::actions::cmd_left)))
       .on_action(cx.listener(crate::actions::right))
-      .on_action(cx.listener(crate::actions::alt_right))
-      .on_action(cx.listener(crate::actions::cmd_right))
-      .on_action(cx.listener(crate::actions::cmd_up))
-      .on_action(cx.listener(crate::actions::cmd_down))
-      .on_action(cx.listener(crate::actions::select_cmd_left))
-      .on_action(cx.listener(crate::actions::select_cmd_right))
-      .on_action(cx.listener(crate::actions::select_cmd_up))
-      .on_action(cx.listener(crate::actions::select_cmd_down))
+      .on_action(cx.listener(motion::flushed(crate::actions::alt_right)))
+      .on_action(cx.listener(motion::flushed(crate::actions::cmd_right)))
+      .on_action(cx.listener(motion::flushed(crate::actions::cmd_up)))
+      .on_action(cx.listener(motion::flushed(crate::actions::cmd_down)))
+      .on_action(cx.listener(motion::flushed(crate::actions::select_cmd_left)))
+      .on_action(cx.listener(motion::flushed(crate::actions::select_cmd_right)))
+      .on_action(cx.listener(motion::flushed(crate::actions::select_cmd_up)))
+      .on_action(cx.listener(motion::flushed(crate::actions::select_cmd_down)))
       .on_action(cx.listener(crate::actions::select_up))
       .on_action(cx.listener(crate::actions::select_down))
+      .on_action(cx.listener(motion::flushed(crate::actions::select_page_up)))
+      .on_action(cx.listener(motion::flushed(crate::actions::select_page_down)))
       .on_action(cx.listener(crate::actions::select_left))
-      .on_action(cx.listener(crate::actions::select_word_left))
+      .on_action(cx.listener(motion::flushed(crate::actions::select_word_left)))
       .on_action(cx.listener(crate::actions::select_right))
-      .on_action(cx.listener(crate::actions::select_word_right))
-      .on_action(cx.listener(crate::actions::select_all))
-      .on_action(cx.listener(crate::actions::home))
-      .on_action(cx.listener(crate::actions::end))
-      .on_action(cx.listener(crate::actions::show_character_palette))
-      .on_action(cx.listener(crate::actions::paste))
-      .on_action(cx.listener(crate::actions::cut))
-      .on_action(cx.listener(crate::actions::copy))
-      .on_action(cx.listener(crate::actions::undo))
-      .on_action(cx.listener(crate::actions::redo))
-      .when_else(self.theme.is_dark, |el| el.bg(black()), |el| el.bg(white()))
-      .when_else(
-        self.theme.is_dark,
-        |el| el.text_color(white()),
-        |el| el.text_color(black()),
-      )
+      .on_action(cx.listener(motion::flushed(crate::actions::select_word_right)))
+      .on_action(cx.listener(motion::flushed(crate::actions::select_all)))
+      .on_action(cx.listener(motion::flushed(crate::actions::select_next_occurrence)))
+      .on_action(cx.listener(motion::flushed(crate::actions::home)))
+      .on_action(cx.listener(motion::flushed(crate::actions::end)))
+      .on_action(cx.listener(motion::flushed(crate::actions::select_home)))
+      .on_action(cx.listener(motion::flushed(crate::actions::select_end)))
+      .on_action(cx.listener(motion::flushed(crate::actions::show_character_palette)))
+      .on_action(cx.listener(motion::flushed(crate::actions::paste)))
+      // Left unhandled while they'd do nothing, which disables their menu items
+      .when(self.has_selection(), |el| {
+        el.on_action(cx.listener(motion::flushed(crate::actions::cut)))
+          .on_action(cx.listener(motion::flushed(crate::actions::copy)))
+      })
+      .on_action(cx.listener(motion::flushed(crate::actions::copy_append)))
+      .on_action(cx.listener(motion::flushed(crate::actions::cut_append)))
+      .when(self.can_undo(cx), |el| {
+        el.on_action(cx.listener(motion::flushed(crate::actions::undo)))
+      })
+      .when(self.can_redo(cx), |el| {
+        el.on_action(cx.listener(motion::flushed(crate::actions::redo)))
+      })
+      .on_action(cx.listener(motion::flushed(crate::actions::reflow_paragraph)))
+      .on_action(cx.listener(motion::flushed(crate::actions::duplicate_line)))
+      .on_action(cx.listener(motion::flushed(crate::actions::delete_line)))
+      .on_action(cx.listener(motion::flushed(crate::actions::move_line_up)))
+      .on_action(cx.listener(motion::flushed(crate::actions::move_line_down)))
+      .on_action(cx.listener(motion::flushed(crate::actions::toggle_coverage_overlay)))
+      .on_action(cx.listener(motion::flushed(crate::actions::toggle_hex_mode)))
+      .on_action(cx.listener(motion::flushed(crate::actions::toggle_soft_wrap)))
+      .on_action(cx.listener(motion::flushed(crate::actions::toggle_elastic_tabstops)))
+      .on_action(cx.listener(motion::flushed(crate::actions::escape_suspicious_unicode)))
+      .on_action(cx.listener(motion::flushed(crate::actions::remove_suspicious_unicode)))
+      .on_action(cx.listener(motion::flushed(crate::actions::start_performance_trace)))
+      .on_action(cx.listener(motion::flushed(crate::actions::stop_performance_trace)))
+      .on_action(cx.listener(motion::flushed(crate::actions::switch_to_alternate_file)))
+      .on_action(cx.listener(motion::flushed(crate::actions::trust_workspace)))
+      .on_action(cx.listener(motion::flushed(crate::actions::describe_character)))
+      .on_action(cx.listener(motion::flushed(crate::actions::insert_unicode)))
+      .on_action(cx.listener(motion::flushed(crate::actions::zoom_in)))
+      .on_action(cx.listener(motion::flushed(crate::actions::zoom_out)))
+      .on_action(cx.listener(motion::flushed(crate::actions::zoom_to_fit)))
+      .on_action(cx.listener(motion::flushed(crate::actions::start_debugging)))
+      .on_action(cx.listener(motion::flushed(crate::actions::stop_debugging)))
+      .on_action(cx.listener(motion::flushed(crate::actions::step_over)))
+      .on_action(cx.listener(motion::flushed(crate::actions::step_into)))
+      .on_action(cx.listener(motion::flushed(crate::actions::step_out)))
+      .on_action(cx.listener(motion::flushed(crate::actions::toggle_code_cells)))
+      .on_action(cx.listener(motion::flushed(crate::actions::run_cell)))
+      .on_action(cx.listener(motion::flushed(crate::actions::stop_cell)))
+      .on_action(cx.listener(motion::flushed(crate::actions::clear_cell_outputs)))
+      .on_action(cx.listener(motion::flushed(crate::actions::jump_to_word)))
+      .on_action(cx.listener(motion::flushed(crate::actions::toggle_dark_mode)))
+      .on_action(cx.listener(motion::flushed(crate::actions::use_system_theme)))
+      .on_action(cx.listener(motion::flushed(crate::actions::toggle_focus_mode)))
+      .on_action(cx.listener(motion::flushed(crate::actions::toggle_word_diff)))
+      .on_action(cx.listener(motion::flushed(crate::actions::add_folder_to_workspace)))
+      .on_action(cx.listener(motion::flushed(crate::actions::remove_folder)))
+      .on_action(cx.listener(motion::flushed(crate::actions::next_conflict)))
+      .on_action(cx.listener(motion::flushed(crate::actions::previous_conflict)))
+      .on_action(cx.listener(motion::flushed(crate::actions::copy_with_line_numbers)))
+      .on_action(cx.listener(motion::flushed(crate::actions::copy_permalink)))
+      .on_action(cx.listener(motion::flushed(crate::actions::toggle_format_on_paste)))
+      .on_action(cx.listener(motion::flushed(crate::actions::retry_highlighting)))
+      .on_action(cx.listener(motion::flushed(crate::actions::show_keyboard_shortcuts)))
+      .on_action(cx.listener(motion::flushed(crate::actions::show_command_palette)))
+      .on_action(cx.listener(motion::flushed(crate::actions::fold_bracket_region)))
+      .on_action(cx.listener(motion::flushed(crate::actions::toggle_selection_lock)))
+      .on_action(cx.listener(motion::flushed(crate::actions::swap_selection_ends)))
+      .on_action(cx.listener(motion::flushed(crate::actions::new_scratch_buffer)))
+      .on_action(cx.listener(motion::flushed(crate::actions::cancel)))
+      .on_action(cx.listener(motion::flushed(crate::actions::format_json)))
+      .on_action(cx.listener(motion::flushed(crate::actions::minify_json)))
+      .on_action(cx.listener(motion::flushed(crate::actions::base64_encode)))
+      .on_action(cx.listener(motion::flushed(crate::actions::base64_decode)))
+      .on_action(cx.listener(motion::flushed(crate::actions::url_encode)))
+      .on_action(cx.listener(motion::flushed(crate::actions::url_decode)))
+      .on_action(cx.listener(motion::flushed(crate::actions::json_escape)))
+      .on_action(cx.listener(motion::flushed(crate::actions::json_unescape)))
+      .on_action(cx.listener(motion::flushed(crate::actions::insert_uuid)))
+      .on_action(cx.listener(motion::flushed(crate::actions::insert_timestamp)))
+      .on_action(cx.listener(motion::flushed(crate::actions::insert_lorem_ipsum)))
+      .on_action(cx.listener(motion::flushed(crate::actions::open)))
+      .on_action(cx.listener(motion::flushed(crate::actions::save)))
+      .on_action(cx.listener(motion::flushed(crate::actions::save_as)))
+      .on_action(cx.listener(motion::flushed(crate::actions::find)))
+      .on_action(cx.listener(motion::flushed(crate::actions::replace)))
+      .on_action(cx.listener(motion::flushed(crate::actions::find_next)))
+      .on_action(cx.listener(motion::flushed(crate::actions::find_previous)))
+      .capture_key_down(cx.listener(|editor, event: &KeyDownEvent, window, cx| {
+        editor.trace_event("key_down");
+        editor
+          .document
+          .update(cx, |document, cx| document.defer_idle_work(cx));
+        // Only repeats of a held key can extend the queued movement, anything
+        // else sees the cursor where the movement leaves it
+        if !event.is_held {
+          motion::flush(editor, window, cx);
+        }
+        // Escape closes the popups one layer at a time instead
+        if event.keystroke.key != "escape" {
+          describe::dismiss(editor, cx);
+          quick_diff::dismiss(editor, cx);
+        }
+        if jump::is_active(editor) {
+          jump::key_down(editor, event, window, cx);
+        }
+      }))
+      .capture_any_mouse_down(cx.listener(|editor, _: &MouseDownEvent, window, cx| {
+        editor
+          .document
+          .update(cx, |document, cx| document.defer_idle_work(cx));
+        motion::flush(editor, window, cx);
+        describe::dismiss(editor, cx);
+        jump::cancel(editor, cx);
+        unicode_input::close(editor, window, cx);
+        command_palette::close(editor, window, cx);
+        path_completion::close(editor, cx);
+        shortcuts::close(editor, window, cx);
+        selection_lock::cancel(editor, cx);
+      }))
+      .bg(self.theme.background())
+      .text_color(self.theme.foreground())
       .flex()
-      .flex_row()
+      .flex_col()
+      .children(large_file::render_banner(self, cx))
+      .children(self.find_bar.clone())
+      .map(|el| match &self.preview {
+        Some(preview) => el.child(preview::render(self, preview, window, cx)),
+        None => el.child(
+          div()
+            .flex()
+            .flex_row()
+            .flex_1()
+            .min_h_0()
+            .when(!self.hex_mode, |el| {
+              el.child(
+                div()
+                  .w(self.gutter_layout.width())
+                  .h_full()
+                  .bg(self.theme.gutter_background())
+                  .child(GutterElement::new(cx.entity().clone())),
+              )
+            })
+            .child(
+              div()
+                .flex_1()
+                .h_full()
+                .relative()
+                .child(
+                  div()
+                    .size_full()
+                    .id("editor-content")
+                    .overflow_x_scroll()
+                    .track_scroll(&self.scroll_handle)
+                    .px(px(EDITOR_PADDING))
+                    .child(
+                      div()
+                        .when(!wrap_map::is_enabled(self), |el| {
+                          el.min_w(self.max_line_width)
+                        })
+                        .h_full()
+                        .when_else(
+                          self.hex_mode,
+                          |el| el.child(HexElement::new(cx.entity().clone())),
+                          |el| el.child(EditorElement::new(cx.entity().clone())),
+                        ),
+                    ),
+                )
+                .child(
+                  div()
+                    .absolute()
+                    .top_0()
+                    .right_0()
+                    .bottom(px(SCROLLBAR_THICKNESS))
+                    .w(px(SCROLLBAR_THICKNESS))
+                    .child(ScrollbarElement::new(
+                      cx.entity().clone(),
+                      ScrollbarAxis::Vertical,
+                    )),
+                )
+                .child(
+                  div()
+                    .absolute()
+                    .left_0()
+                    .bottom_0()
+                    .right(px(SCROLLBAR_THICKNESS))
+                    .h(px(SCROLLBAR_THICKNESS))
+                    .child(ScrollbarElement::new(
+                      cx.entity().clone(),
+                      ScrollbarAxis::Horizontal,
+                    )),
+                ),
+            )
+            .children(dap::render_variables_panel(self))
+            .children(code_cells::render_output_panel(self, cx)),
+        ),
+      })
       .child(
         div()
-          .w(px(GUTTER_WIDTH))
-          .h_full()
+          .h(px(STATUS_BAR_HEIGHT))
+          .w_full()
+          .flex()
+          .flex_row()
+          .items_center()
+          .gap_4()
+          .px(px(EDITOR_PADDING * 2.0))
           .bg(self.theme.gutter_background())
-          .child(GutterElement::new(cx.entity().clone())),
-      )
-      .child(
-        div()
-          .flex_1()
-          .h_full()
-          .id("editor-content")
-          .overflow_x_scroll()
-          .track_scroll(&self.scroll_handle)
-          .px(px(EDITOR_PADDING))
-          .child(
-            div()
-              .min_w(self.max_line_width)
-              .h_full()
-              .child(EditorElement::new(cx.entity().clone())),
-          ),
+          .text_color(self.theme.line_number())
+          .children(status_items)
+          .children(progress::render_status(cx)),
       )
+      .children(progress::render_menu(self, STATUS_BAR_HEIGHT, cx))
+      .when_some(self.character_description.as_ref(), |el, description| {
+        let origin = self.popup_origin(cx);
+        el.child(
+          div()
+            .absolute()
+            .left(origin.x)
+            .top(origin.y)
+            .occlude()
+            .child(describe::render_popup(self, description)),
+        )
+      })
+      .children(conflicts::render_actions(self, cx))
+      .when_some(self.quick_diff.clone(), |el, hunk| {
+        el.child(quick_diff::render(self, &hunk, cx))
+      })
+      .when_some(path_completion::render(self), |el, popup| {
+        let origin = self.popup_origin(cx);
+        el.child(
+          div()
+            .absolute()
+            .left(origin.x)
+            .top(origin.y)
+            .occlude()
+            .child(popup),
+        )
+      })
+      .when_some(self.unicode_input.clone(), |el, input| {
+        let origin = self.popup_origin(cx);
+        el.child(
+          div()
+            .absolute()
+            .left(origin.x)
+            .top(origin.y)
+            .occlude()
+            .child(input),
+        )
+      })
+      .when_some(self.command_palette.clone(), |el, palette| {
+        el.child(
+          div()
+            .absolute()
+            .top(px(40.0))
+            .left_0()
+            .right_0()
+            .flex()
+            .justify_center()
+            .child(div().occlude().child(palette)),
+        )
+      })
+      .when_some(self.shortcut_sheet.clone(), |el, sheet| {
+        el.child(
+          div()
+            .absolute()
+            .top(px(40.0))
+            .left_0()
+            .right_0()
+            .flex()
+            .justify_center()
+            .child(div().occlude().child(sheet)),
+        )
+      })
   }
 }
 
@@ -682,28 +1791,7 @@ pub mod tests {
     pub fn with_text(mut cx: TestAppContext, text: &str) -> Self {
       let editor = cx.new(|cx| {
         let doc = cx.new(|cx| Document::new(text, None, cx));
-        let cursor_blink = cx.new(CursorBlink::new);
-        Editor {
-          document: doc,
-          focus_handle: cx.focus_handle(),
-          selected_range: 0..0,
-          selection_reversed: false,
-          marked_range: None,
-          is_selecting: false,
-          line_layouts: HashMap::new(),
-          scroll_offset_y: 0.0,
-          viewport_height: px(DEFAULT_VIEWPORT_HEIGHT),
-          viewport_width: px(DEFAULT_VIEWPORT_WIDTH),
-          max_line_width: px(DEFAULT_MAX_LINE_WIDTH),
-          scroll_handle: ScrollHandle::new(),
-          max_cache_size: MAX_CACHE_SIZE,
-          target_column: None,
-          undo_stack: VecDeque::new(),
-          redo_stack: VecDeque::new(),
-          theme: Theme::dark(),
-          last_highlights_version: 0,
-          cursor_blink,
-        }
+        Editor::with_document(doc, cx)
       });
 
       Self { cx, editor }
@@ -1390,29 +2478,305 @@ pub mod tests {
     assert_eq!(ctx.selection(), 3..10);
   }
 
+  #[gpui::test]
+  fn test_open_file_replaces_document(cx: &mut TestAppContext) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("open.md");
+    std::fs::write(&path, "- item").unwrap();
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "old text");
+    ctx.set_cursor(3);
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.open_file(path.clone(), cx).unwrap();
+      assert_eq!(editor.file_path(), Some(path.as_path()));
+      assert_eq!(editor.document.read(cx).file_extension(), Some("md"));
+      assert!(!editor.can_undo(cx));
+      assert!(
+        editor
+          .open_file(path.with_extension("missing"), cx)
+          .is_err()
+      );
+    });
+
+    assert_eq!(ctx.text(), "- item");
+    assert_eq!(ctx.cursor_offset(), 0);
+  }
+
+  #[gpui::test]
+  fn test_open_file_keeps_open_document(cx: &mut TestAppContext) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("reopen.rs");
+    std::fs::write(&path, "fn main() {}").unwrap();
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "");
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.open_file(path.clone(), cx).unwrap();
+      assert!(!editor.is_edited(cx));
+      editor.move_to(3, cx);
+      editor.insert_text("my_", "Typing", cx);
+      assert!(editor.is_edited(cx));
+      let document = editor.document.clone();
+      // The same file through another spelling of its path
+      let relative = dir.path().join(".").join(path.file_name().unwrap());
+      editor.open_file(relative, cx).unwrap();
+      assert_eq!(editor.document, document);
+      assert_eq!(editor.document.read(cx).buffer.undo_depth(), 1);
+    });
+
+    assert_eq!(ctx.text(), "fn my_main() {}");
+    assert_eq!(ctx.cursor_offset(), 6);
+  }
+
+  #[gpui::test]
+  fn test_view_of_shares_document(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "shared");
+    let editor = ctx.editor.clone();
+    let view = ctx.cx.new(|cx| Editor::view_of(&editor, cx));
+
+    view.update(&mut ctx.cx, |view, cx| {
+      view.move_to(6, cx);
+      view.insert_text("!", "Typing", cx);
+    });
+
+    assert_eq!(ctx.text(), "shared!");
+    assert_eq!(ctx.cursor_offset(), 0);
+  }
+
+  #[gpui::test]
+  fn test_set_font_invalidates_cache(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_lines(cx.clone(), 3);
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor
+        .line_layouts
+        .insert(0, Arc::new(ShapedLine::default()));
+
+      // Setting the same font keeps the cache
+      editor.set_font(EditorFont::default(), cx);
+      assert_eq!(editor.line_layouts.len(), 1);
+
+      editor.set_font(
+        EditorFont {
+          ligatures: false,
+          ..Default::default()
+        },
+        cx,
+      );
+      assert!(editor.line_layouts.is_empty());
+      assert!(!editor.font().ligatures);
+    });
+  }
+
+  #[gpui::test]
+  fn test_invalidate_edits_since(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_lines(cx.clone(), 6);
+    let cache_all = |editor: &mut Editor| {
+      for line in 0..6 {
+        editor
+          .line_layouts
+          .insert(line, Arc::new(ShapedLine::default()));
+      }
+    };
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      cache_all(editor);
+      let (version, line_count) = (editor.document.read(cx).version(), 6);
+      let start = editor.document.read(cx).line_to_char(2);
+      editor
+        .document
+        .update(cx, |doc, cx| doc.replace(start..start + 1, "x", cx));
+      editor.invalidate_edits_since(version, line_count, cx);
+    });
+    let cached: Vec<usize> = (0..6).filter(|&line| ctx.is_line_cached(line)).collect();
+    assert_eq!(cached, [0, 1, 3, 4, 5]);
+
+    // Joining two lines moves the lines after them
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      cache_all(editor);
+      let version = editor.document.read(cx).version();
+      let end = editor.document.read(cx).line_to_char(3);
+      editor
+        .document
+        .update(cx, |doc, cx| doc.replace(end - 1..end, "", cx));
+      editor.invalidate_edits_since(version, 6, cx);
+    });
+    let cached: Vec<usize> = (0..6).filter(|&line| ctx.is_line_cached(line)).collect();
+    assert_eq!(cached, [0, 1]);
+  }
+
+  #[gpui::test]
+  fn test_undo_descriptions(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "a b");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert_eq!(editor.undo_description(cx), None);
+      editor.document.update(cx, |doc, _| {
+        doc.buffer.set_group_interval(std::time::Duration::ZERO)
+      });
+
+      editor.selected_range = 1..2;
+      crate::unicode_input::insert(editor, "→", cx);
+      assert_eq!(editor.undo_description(cx), Some("Insert Character"));
+
+      // An edit grouped into the last transaction keeps its description
+      let set_group_interval = |editor: &mut Editor, interval, cx: &mut Context<Editor>| {
+        editor
+          .document
+          .update(cx, |doc, _| doc.buffer.set_group_interval(interval))
+      };
+      set_group_interval(editor, std::time::Duration::from_secs(60), cx);
+      editor.insert_text("c", "Typing", cx);
+      assert_eq!(editor.document.read(cx).buffer.undo_depth(), 1);
+      assert_eq!(editor.undo_description(cx), Some("Insert Character"));
+      set_group_interval(editor, std::time::Duration::ZERO, cx);
+
+      editor.prepare_for_save(cx);
+      assert_eq!(editor.undo_description(cx), Some("Insert Final Newline"));
+      assert_eq!(editor.redo_description(cx), None);
+    });
+  }
+
+  #[gpui::test]
+  fn test_undo_history_belongs_to_document(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "ab");
+    ctx.set_cursor(1);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.insert_text("x", "Typing", cx);
+    });
+
+    // Another view of the document undoes the edit, selection included
+    let editor = ctx.editor.clone();
+    let view = ctx.cx.add_window(|_, cx| Editor::view_of(&editor, cx));
+    view
+      .update(&mut ctx.cx, |view, window, cx| {
+        assert_eq!(view.undo_description(cx), Some("Typing"));
+        crate::actions::undo(view, &crate::actions::Undo, window, cx);
+        assert_eq!(view.selected_range, 1..1);
+        assert_eq!(view.redo_description(cx), Some("Typing"));
+      })
+      .unwrap();
+    assert_eq!(ctx.text(), "ab");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert!(editor.can_redo(cx));
+    });
+  }
+
+  #[gpui::test]
+  fn test_prepare_for_save_updates_indicator(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "fn main() {}");
+    ctx.set_cursor(3);
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert!(
+        editor
+          .status_items(cx)
+          .contains(&SharedString::new_static("No newline at end of file"))
+      );
+
+      editor.prepare_for_save(cx);
+      assert!(
+        !editor
+          .status_items(cx)
+          .contains(&SharedString::new_static("No newline at end of file"))
+      );
+      assert_eq!(editor.document.read(cx).buffer.undo_depth(), 1);
+    });
+
+    assert_eq!(ctx.text(), "fn main() {}\n");
+    assert_eq!(ctx.cursor_offset(), 3);
+  }
+
   #[gpui::test]
   fn test_editor_toggle_dark_mode(cx: &mut TestAppContext) {
     let editor = cx.new(Editor::new);
 
-    editor.update(cx, |editor, _| {
+    editor.update(cx, |editor, cx| {
       let was_dark = editor.theme.is_dark;
-      editor.toggle_dark_mode();
+      editor.line_layouts.insert(0, Default::default());
+      editor.toggle_dark_mode(cx);
       assert_eq!(editor.theme.is_dark, !was_dark);
+      assert_eq!(
+        editor.theme.background(),
+        Theme::new(!was_dark).background()
+      );
+      // Shaped lines are reshaped with the new syntax colors
+      assert!(editor.line_layouts.is_empty());
     });
   }
 
   #[gpui::test]
-  fn test_syntax_highlights_cached(cx: &mut TestAppContext) {
+  fn test_new_editor_is_empty(cx: &mut TestAppContext) {
     let editor = cx.new(Editor::new);
 
-    // Wait for async highlighting to complete (it's scheduled but not immediate)
     editor.read_with(cx, |editor, cx| {
       let doc = editor.document().read(cx);
+      assert!(doc.is_empty());
+      assert_eq!(doc.len_lines(), 1);
+      assert_eq!(doc.path(), None);
+    });
+  }
+
+  #[gpui::test]
+  fn test_drag_after_double_click_extends_by_words(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "one two three\nfour five");
 
-      // Highlighting is async with debouncing, so it might not be ready immediately
-      // Just verify the document has content that should be highlighted
-      assert!(doc.len() > 0);
-      assert!(doc.len_lines() > 0);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      // Double click on "two"
+      editor.selected_range = 4..7;
+      editor.drag_granularity = SelectionGranularity::Word;
+      editor.drag_origin = 4..7;
+
+      editor.extend_drag_to(10, cx);
+      assert_eq!(editor.selected_range, 4..13);
+      assert!(!editor.selection_reversed);
+
+      // Back over the clicked word and past it
+      editor.extend_drag_to(1, cx);
+      assert_eq!(editor.selected_range, 0..7);
+      assert!(editor.selection_reversed);
+
+      editor.extend_drag_to(5, cx);
+      assert_eq!(editor.selected_range, 4..7);
+    });
+  }
+
+  #[gpui::test]
+  fn test_drag_after_triple_click_extends_by_lines(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "line1\nline2\nline3");
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.selected_range = 6..12;
+      editor.drag_granularity = SelectionGranularity::Line;
+      editor.drag_origin = 6..12;
+
+      editor.extend_drag_to(14, cx);
+      assert_eq!(editor.selected_range, 6..17);
+      editor.extend_drag_to(2, cx);
+      assert_eq!(editor.selected_range, 0..12);
+      assert!(editor.selection_reversed);
+    });
+  }
+
+  #[gpui::test]
+  fn test_shift_click_extends_from_clicked_word(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "one two three");
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      // Double click on "two", then shift-click before it
+      editor.selected_range = 4..7;
+      editor.drag_granularity = SelectionGranularity::Word;
+      editor.drag_origin = 4..7;
+      editor.extend_selection_to(1, cx);
+      assert_eq!(editor.selected_range, 0..7);
+      assert!(editor.selection_reversed);
+
+      // And after it, keeping "two" selected
+      editor.extend_selection_to(9, cx);
+      assert_eq!(editor.selected_range, 4..13);
+
+      // Once the word isn't selected anymore, shift-clicks extend by characters
+      editor.move_to(0, cx);
+      editor.extend_selection_to(9, cx);
+      assert_eq!(editor.selected_range, 0..9);
     });
   }
 
@@ -1,14 +1,24 @@
 use gpui::{
-  App, Bounds, DispatchPhase, ElementId, ElementInputHandler, Entity, GlobalElementId,
+  App, Bounds, ContentMask, DispatchPhase, ElementId, ElementInputHandler, Entity, GlobalElementId,
   InspectorElementId, LayoutId, MouseButton, MouseDownEvent, MouseMoveEvent, MouseUpEvent,
-  PaintQuad, Pixels, Point, ScrollDelta, ScrollWheelEvent, ShapedLine, Style, TextAlign, TextRun,
-  TextStyle, Window, fill, point, prelude::*, px, relative, size,
+  PaintQuad, Pixels, Point, ScrollDelta, ScrollWheelEvent, ShapedLine, SharedString, Style,
+  TextAlign, TextRun, TextStyle, Window, black, fill, point, prelude::*, px, relative, size,
+  yellow,
 };
-use std::{ops::Range, rc::Rc, sync::Arc};
+use std::{ops::Range, rc::Rc, sync::Arc, time::Instant};
 
 use crate::{
   document::Document,
+  echo::{self, EchoLayout},
   editor::{DEFAULT_MAX_LINE_WIDTH, Editor},
+  find,
+  focus_mode::{self, DIMMED_OPACITY},
+  invisibles::{invisibles_in_line, unicode_warnings_in_line},
+  line_index::ViewportLineIndex,
+  multi_cursor, ruler,
+  selection_layout::selection_layout,
+  shaping,
+  wrap_map::{self, RowLayout},
 };
 use syntax::{HighlightSpan, Theme};
 
@@ -18,6 +28,12 @@ const NEWLINE_SELECTION_WIDTH: f32 = 4.0;
 const PIXEL_SCROLL_DIVISOR: f32 = 20.0;
 // Scroll sensitivity for line-based scrolling (mouse wheel)
 const LINE_SCROLL_MULTIPLIER: f32 = 3.0;
+// Thickness of decoration underlines
+const DECORATION_UNDERLINE_THICKNESS: f32 = 1.0;
+// Height of the strip at the top where the wrap ruler can be grabbed
+const RULER_HANDLE_HEIGHT: f32 = 6.0;
+// Half width of the wrap ruler handle
+const RULER_HANDLE_HALF_WIDTH: f32 = 4.0;
 
 /// Encapsulates layout information for mouse position -> text offset conversion
 #[derive(Clone)]
@@ -26,6 +42,10 @@ pub struct PositionMap {
   pub bounds: Bounds<Pixels>,
   pub line_height: Pixels,
   pub viewport: Range<usize>,
+  pub(crate) line_index: Arc<ViewportLineIndex>,
+  pub(crate) rows: Arc<RowLayout>,
+  /// Width of a char of an inlay
+  pub(crate) char_width: Pixels,
 }
 
 impl PositionMap {
@@ -39,23 +59,39 @@ impl PositionMap {
     }
 
     let y_offset = position.y - self.bounds.top();
-    let row_in_viewport = (y_offset / self.line_height).floor() as usize;
-    let actual_row = self.viewport.start + row_in_viewport;
-
-    if actual_row >= document.len_lines() {
+    let row = self.rows.rows.start + (y_offset / self.line_height).floor() as usize;
+    let Some((line, row_bytes)) = self.rows.at_row(row) else {
       return Some(document.len());
-    }
+    };
 
     let shaped = self
       .shaped_lines
       .iter()
-      .find(|(idx, _)| *idx == actual_row)
+      .find(|(idx, _)| *idx == line)
       .map(|(_, s)| s)?;
 
-    let x_offset = position.x - self.bounds.left();
-    let column = shaped.closest_index_for_x(x_offset);
+    // Shaped lines are indexed by byte, documents by char
+    let mut byte = self.rows.index_for_x(
+      row,
+      position.x - self.bounds.left(),
+      shaped,
+      self.char_width,
+    )?;
+    let line_content = document.line_content(line).unwrap_or_default();
+    // The end of a wrapped row is the start of the next one, past it is its
+    // last character
+    if byte == row_bytes.end && byte < line_content.len() {
+      byte = line_content[..byte]
+        .char_indices()
+        .next_back()
+        .map_or(row_bytes.start, |(offset, _)| offset.max(row_bytes.start));
+    }
+    let column = line_content
+      .char_indices()
+      .take_while(|(offset, _)| *offset < byte)
+      .count();
 
-    let line_start = document.line_to_char(actual_row);
+    let line_start = self.line_index.line_to_char(document, line);
     Some(line_start + column)
   }
 }
@@ -134,11 +170,33 @@ pub struct EditorElement {
   editor: Entity<Editor>,
 }
 
+/// Wrap column ruler and its drag handle
+struct RulerLayout {
+  line: PaintQuad,
+  handle: PaintQuad,
+  char_width: Pixels,
+}
+
 pub struct PrepaintState {
   shaped_lines: Vec<(usize, Arc<ShapedLine>)>,
+  // Deferred lines as plain text until they are shaped
+  placeholder_lines: Vec<(usize, ShapedLine)>,
   cursor_quad: Option<PaintQuad>,
+  // Cursors of the extra selections, blinking with the primary one
+  extra_cursor_quads: Vec<PaintQuad>,
   selection_quads: Vec<PaintQuad>,
+  decoration_quads: Vec<PaintQuad>,
+  // Inlays of the rows in view, fold placeholders and virtual text
+  inlays: Vec<(Point<Pixels>, ShapedLine)>,
+  jump_labels: Vec<(Point<Pixels>, ShapedLine)>,
+  dim_quads: Vec<PaintQuad>,
+  invisible_glyphs: Vec<(Point<Pixels>, ShapedLine)>,
+  ruler: Option<RulerLayout>,
+  echo: Option<EchoLayout>,
+  line_index: Arc<ViewportLineIndex>,
   viewport: Range<usize>,
+  rows: Arc<RowLayout>,
+  char_width: Pixels,
   bounds: Bounds<Pixels>,
   line_height: Pixels,
 }
@@ -155,13 +213,106 @@ impl EditorElement {
     scroll_offset: f32,
     total_lines: usize,
   ) -> Range<usize> {
-    let visible_line_count = ((bounds.size.height / line_height).ceil() as usize).max(1);
+    calculate_viewport(bounds, line_height, scroll_offset, total_lines)
+  }
+}
 
-    let start_line = (scroll_offset.floor() as usize).min(total_lines.saturating_sub(1));
-    let end_line = (start_line + visible_line_count).min(total_lines);
+/// Rows visible in `bounds` when scrolled `scroll_offset` rows down
+pub(crate) fn calculate_viewport(
+  bounds: Bounds<Pixels>,
+  line_height: Pixels,
+  scroll_offset: f32,
+  total_lines: usize,
+) -> Range<usize> {
+  let visible_line_count = ((bounds.size.height / line_height).ceil() as usize).max(1);
 
-    start_line..end_line
-  }
+  let start_line = (scroll_offset.floor() as usize).min(total_lines.saturating_sub(1));
+  let end_line = (start_line + visible_line_count).min(total_lines);
+
+  start_line..end_line
+}
+
+/// Scroll the editor vertically on mouse wheel events
+pub(crate) fn register_scroll_wheel(editor: &Entity<Editor>, window: &mut Window) {
+  let editor = editor.clone();
+  window.on_mouse_event(move |event: &ScrollWheelEvent, phase, _window, cx| {
+    if phase == DispatchPhase::Bubble {
+      editor.update(cx, |editor, cx| {
+        editor.trace_event("scroll");
+        let total_lines = editor.scrollable_rows(cx);
+
+        // Extract scroll delta (handle both pixel and line scrolling)
+        // Note: Negative delta because scrolling down should increase scroll_offset
+        let scroll_delta = match event.delta {
+          ScrollDelta::Pixels(point) => -(point.y / px(PIXEL_SCROLL_DIVISOR)), // Pixel scrolling (trackpad)
+          ScrollDelta::Lines(point) => -(point.y * LINE_SCROLL_MULTIPLIER), // Line scrolling (mouse wheel)
+        };
+
+        let new_scroll = (editor.scroll_offset_y + scroll_delta)
+          .max(0.0)
+          .min((total_lines.saturating_sub(1)) as f32);
+
+        editor.scroll_offset_y = new_scroll;
+        cx.notify();
+      });
+    }
+  });
+}
+
+/// Paint a stale line split at the caret, with the echoed text in the gap
+fn paint_echoed_line(
+  line: &ShapedLine,
+  echo: &EchoLayout,
+  origin: Point<Pixels>,
+  line_height: Pixels,
+  window: &mut Window,
+  cx: &mut App,
+) {
+  let split_x = origin.x + echo.x;
+  let gap = echo.shaped.width;
+  let bottom = origin.y + line_height;
+
+  window.with_content_mask(
+    Some(ContentMask {
+      bounds: Bounds::from_corners(origin, point(split_x, bottom)),
+    }),
+    |window| {
+      line
+        .paint(origin, line_height, TextAlign::Left, None, window, cx)
+        .ok();
+    },
+  );
+  echo
+    .shaped
+    .paint(
+      point(split_x, origin.y),
+      line_height,
+      TextAlign::Left,
+      None,
+      window,
+      cx,
+    )
+    .ok();
+  window.with_content_mask(
+    Some(ContentMask {
+      bounds: Bounds::from_corners(
+        point(split_x + gap, origin.y),
+        point(origin.x + line.width + gap, bottom),
+      ),
+    }),
+    |window| {
+      line
+        .paint(
+          point(origin.x + gap, origin.y),
+          line_height,
+          TextAlign::Left,
+          None,
+          window,
+          cx,
+        )
+        .ok();
+    },
+  );
 }
 
 impl IntoElement for EditorElement {
@@ -207,6 +358,8 @@ impl Element for EditorElement {
     window: &mut Window,
     cx: &mut App,
   ) -> Self::PrepaintState {
+    let prepaint_start = Instant::now();
+
     // Check if syntax highlights have been updated and invalidate cache if needed
     let highlights_version = *self
       .editor
@@ -215,8 +368,9 @@ impl Element for EditorElement {
       .read(cx)
       .highlights_version
       .read();
-    self.editor.update(cx, |editor, _| {
+    self.editor.update(cx, |editor, cx| {
       editor.viewport_height = bounds.size.height;
+      editor.line_height = editor.line_height_px(window, cx);
       editor.viewport_width = window.bounds().size.width;
 
       // If highlights have been updated since last render, invalidate the cache
@@ -225,15 +379,42 @@ impl Element for EditorElement {
         editor.last_highlights_version = highlights_version;
       }
     });
+    // Soft wrap fits as many columns as the text area is wide
+    let char_width =
+      shaping::monospace_advance(&self.editor.read(cx).text_style(window, cx), window);
+    let wrap_column = (bounds.size.width / char_width).floor() as usize;
+    // Rows in view and the lines they show
+    let rows = self.editor.update(cx, |editor, cx| {
+      wrap_map::set_column(editor, Some(wrap_column), cx);
+      let total_rows = wrap_map::row_count(editor, cx);
+      let rows = self.calculate_viewport(
+        bounds,
+        editor.line_height,
+        editor.scroll_offset_y,
+        total_rows,
+      );
+      let rows = wrap_map::layout(editor, rows, cx);
+      editor
+        .document
+        .update(cx, |doc, _| doc.highlight_range(rows.lines()));
+      Arc::new(rows)
+    });
+    // Typed text shown over its stale line this frame, reconciled on the next
+    let echo = self
+      .editor
+      .update(cx, |editor, cx| echo::take_for_frame(editor, cx));
 
-    let (viewport, selected_range, cursor_offset, mut shaped_lines, lines_to_shape) = {
+    let (
+      viewport,
+      selected_range,
+      cursor_offset,
+      extra_selections,
+      mut shaped_lines,
+      lines_to_shape,
+    ) = {
       let editor = self.editor.read(cx);
       let document = editor.document().read(cx);
-      let line_height = window.line_height();
-      let scroll_offset = editor.scroll_offset_y;
-
-      let viewport =
-        self.calculate_viewport(bounds, line_height, scroll_offset, document.len_lines());
+      let viewport = rows.lines();
 
       let mut lines_to_shape = Vec::new();
       let mut shaped_lines = Vec::new();
@@ -242,6 +423,10 @@ impl Element for EditorElement {
         if line_idx >= document.len_lines() {
           break;
         }
+        // Lines hidden by a fold are never painted
+        if !rows.is_shown(line_idx) {
+          continue;
+        }
         match editor.line_layouts.get(&line_idx) {
           Some(shaped) => {
             // Arc::clone is cheap - just incrementing reference count
@@ -261,23 +446,44 @@ impl Element for EditorElement {
         viewport,
         editor.selected_range.clone(),
         editor.cursor_offset(),
+        multi_cursor::extra_ranges(editor, cx),
         shaped_lines,
         lines_to_shape,
       )
     };
 
-    let style = window.text_style();
+    let line_index = self.editor.update(cx, |editor, cx| {
+      editor.viewport_line_index(viewport.clone(), cx)
+    });
+
+    let cache_hits = shaped_lines.len();
+    let cache_misses = lines_to_shape.len();
+
+    let style = self.editor.read(cx).text_style(window, cx);
     let font_size = style.font_size.to_pixels(window.rem_size());
-    let line_height = window.line_height();
+    let line_height = style.line_height_in_pixels(window.rem_size());
 
     // Get theme for syntax highlighting colors
     let theme = self.editor.read(cx).theme.clone();
 
+    // Expensive lines past the frame's budget are shaped on the next frame,
+    // except the cursor's
+    let cursor_line = {
+      let document = self.editor.read(cx).document().read(cx);
+      line_index.char_to_line(document, cursor_offset)
+    };
+    let shaping_start = Instant::now();
+    let mut deferred_lines = Vec::new();
+
     let mut newly_shaped = Vec::new();
     for (line_idx, line_content) in lines_to_shape {
+      if line_idx != cursor_line && shaping::should_defer(&line_content, shaping_start.elapsed()) {
+        deferred_lines.push((line_idx, line_content));
+        continue;
+      }
       // Try to get syntax highlights for this line
       let document = self.editor.read(cx).document().read(cx);
-      let line_range = document.line_range(line_idx);
+      let line_range = line_index.line_range(document, line_idx);
 
       let runs = if let (Some(highlights), Some(range)) =
         (document.get_highlights_for_line(line_idx), line_range)
@@ -322,88 +528,509 @@ impl Element for EditorElement {
       .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
       .unwrap_or(px(DEFAULT_MAX_LINE_WIDTH));
 
+    // Wrapped rows fit the text area instead
     self.editor.update(cx, |editor, _| {
-      editor.max_line_width = editor.max_line_width.max(max_width);
+      if !wrap_map::is_enabled(editor) {
+        editor.max_line_width = editor.max_line_width.max(max_width);
+      }
+    });
+
+    let placeholder_advance = if deferred_lines.is_empty() {
+      px(0.)
+    } else {
+      window.request_animation_frame();
+      shaping::monospace_advance(&style, window)
+    };
+    // Until it is shaped, a deferred line shows the columns in view as plain
+    // text, one per monospace advance
+    let placeholder_lines: Vec<(usize, ShapedLine)> = {
+      let scroll = &self.editor.read(cx).scroll_handle;
+      let visible_width = scroll.bounds().size.width - scroll.offset().x;
+      let visible_columns = (visible_width / placeholder_advance.max(px(1.))).ceil() as usize;
+      deferred_lines
+        .iter()
+        .map(|(line_idx, content)| {
+          // A wrapped line only fills its first row
+          let columns = rows
+            .row_of(*line_idx, 0)
+            .and_then(|(row, _)| rows.at_row(row))
+            .filter(|_| rows.is_wrapped(*line_idx))
+            .map_or(visible_columns, |(_, bytes)| content[bytes].chars().count());
+          let text = shaping::placeholder_text(content, columns);
+          let runs = vec![TextRun {
+            len: text.len(),
+            font: style.font(),
+            color: style.color,
+            background_color: None,
+            underline: None,
+            strikethrough: None,
+          }];
+          let shaped = window.text_system().shape_line(
+            text.into(),
+            font_size,
+            &runs,
+            Some(placeholder_advance),
+          );
+          (*line_idx, shaped)
+        })
+        .collect()
+    };
+
+    // Wrapped lines wait for their new shape instead
+    let echo = echo.and_then(|(line, column, text)| {
+      if rows.is_wrapped(line) || rows.has_inlays(line) {
+        return None;
+      }
+      let (_, stale) = shaped_lines.iter().find(|(idx, _)| *idx == line)?;
+      let runs = vec![TextRun {
+        len: text.len(),
+        font: style.font(),
+        color: style.color,
+        background_color: None,
+        underline: None,
+        strikethrough: None,
+      }];
+      Some(EchoLayout {
+        line,
+        x: stale.x_for_index(column),
+        shaped: window
+          .text_system()
+          .shape_line(text.into(), font_size, &runs, None),
+      })
+    });
+    if echo.is_some() {
+      window.request_animation_frame();
+    }
+    let search_matches = self.editor.update(cx, |editor, cx| {
+      find::visible_matches(editor, viewport.clone(), cx)
     });
 
     let document = self.editor.read(cx).document().read(cx);
 
-    let cursor_line = document.char_to_line(cursor_offset);
-    let cursor_quad = if viewport.contains(&cursor_line) {
+    // Shaped lines are indexed by byte, the document by char
+    let byte_for_column = |line_content: &str, column: usize| {
+      line_content
+        .char_indices()
+        .nth(column)
+        .map_or(line_content.len(), |(offset, _)| offset)
+    };
+    // Where the caret at byte `byte` of a line is painted, on the row showing it
+    let text_position = |line: usize, shaped: &ShapedLine, byte: usize| {
+      let (row, x) = rows.caret_x(line, byte, shaped, char_width)?;
+      rows.rows.contains(&row).then(|| {
+        point(
+          bounds.left() + x,
+          rows.row_top(row, bounds.top(), line_height),
+        )
+      })
+    };
+    // Where the glyph at byte `byte` of a line is painted, past the inlays before it
+    let glyph_position = |line: usize, shaped: &ShapedLine, byte: usize| {
+      let (row, x) = rows.glyph_x(line, byte, shaped, char_width)?;
+      rows.rows.contains(&row).then(|| {
+        point(
+          bounds.left() + x,
+          rows.row_top(row, bounds.top(), line_height),
+        )
+      })
+    };
+    // Bounds of `bytes` of a line, a part on each row it spans
+    let span_bounds = |line: usize, shaped: &ShapedLine, bytes: Range<usize>| {
+      rows
+        .span_xs(line, bytes, shaped, char_width)
+        .into_iter()
+        .map(|(row, xs)| {
+          let y = rows.row_top(row, bounds.top(), line_height);
+          Bounds::from_corners(
+            point(bounds.left() + xs.start, y),
+            point(bounds.left() + xs.end, y + line_height),
+          )
+        })
+        .collect::<Vec<_>>()
+    };
+    // Full width rows of a line
+    let row_bounds = |line: usize| {
+      rows.line_rows(line).into_iter().map(|(row, _)| {
+        let y = rows.row_top(row, bounds.top(), line_height);
+        Bounds::from_corners(
+          point(bounds.left(), y),
+          point(bounds.right(), y + line_height),
+        )
+      })
+    };
+
+    let cursor_quad_at = |offset: usize, echo: Option<&EchoLayout>| {
+      let line = line_index.char_to_line(document, offset);
+      if !viewport.contains(&line) {
+        return None;
+      }
+      let (_, shaped) = shaped_lines.iter().find(|(idx, _)| *idx == line)?;
+      let line_content = document.line_content(line).unwrap_or_default();
+      let byte = byte_for_column(
+        &line_content,
+        offset - line_index.line_to_char(document, line),
+      );
+      let mut origin = text_position(line, shaped, byte)?;
+      if let Some(echo) = echo.filter(|echo| echo.line == line) {
+        origin.x = bounds.left() + echo.x + echo.shaped.width;
+      }
+      Some(fill(
+        Bounds::new(origin, size(px(2.), line_height)),
+        theme.cursor(),
+      ))
+    };
+    let cursor_quad = cursor_quad_at(cursor_offset, echo.as_ref());
+    let extra_cursor_quads: Vec<_> = extra_selections
+      .iter()
+      .filter_map(|(range, reversed)| {
+        let offset = if *reversed { range.start } else { range.end };
+        cursor_quad_at(offset, None)
+      })
+      .collect();
+
+    let mut selection_quads = Vec::new();
+    let mut invisible_glyphs = Vec::new();
+    let selected_ranges = std::iter::once(selected_range.clone())
+      .chain(extra_selections.into_iter().map(|(range, _)| range));
+    for span in
+      selected_ranges.flat_map(|range| selection_layout(range, &rows, document, &line_index))
+    {
+      let line_idx = span.line;
       let shaped_opt = shaped_lines
         .iter()
-        .find(|(idx, _)| *idx == cursor_line)
+        .find(|(idx, _)| *idx == line_idx)
         .map(|(_, shaped)| shaped);
+
       if let Some(shaped) = shaped_opt {
-        let line_start = document.line_to_char(cursor_line);
-        let cursor_in_line = cursor_offset - line_start;
-        let cursor_x = shaped.x_for_index(cursor_in_line);
-        let y = bounds.top() + line_height * (cursor_line - viewport.start) as f32;
-        Some(fill(
-          Bounds::new(
-            point(bounds.left() + cursor_x, y),
-            size(px(2.), line_height),
+        let line_content = document.line_content(line_idx).unwrap_or_default();
+        let xs = rows.row_span_x(span.row, span.bytes.clone(), shaped, char_width);
+        let y = rows.row_top(span.row, bounds.top(), line_height);
+        // Extend past the end of the row when its line break is selected
+        let x_end = if span.past_end {
+          xs.end + px(NEWLINE_SELECTION_WIDTH)
+        } else {
+          xs.end
+        };
+        selection_quads.push(fill(
+          Bounds::from_corners(
+            point(bounds.left() + xs.start, y),
+            point(bounds.left() + x_end, y + line_height),
           ),
-          theme.cursor(),
-        ))
-      } else {
-        None
+          theme.selection(),
+        ));
+
+        // Show what is about to be copied or deleted
+        for (offset, invisible) in invisibles_in_line(&line_content) {
+          if !span.bytes.contains(&offset) {
+            continue;
+          }
+          let Some(origin) = glyph_position(line_idx, shaped, offset) else {
+            continue;
+          };
+          let (x, y) = (origin.x, origin.y);
+          let color = if invisible.suspicious {
+            let char_len = line_content[offset..]
+              .chars()
+              .next()
+              .map_or(1, char::len_utf8);
+            let x_end = (x + shaped.x_for_index(offset + char_len) - shaped.x_for_index(offset))
+              .max(x + px(NEWLINE_SELECTION_WIDTH));
+            let mut background = theme.warning();
+            background.a = 0.3;
+            selection_quads.push(fill(
+              Bounds::from_corners(point(x, y), point(x_end, y + line_height)),
+              background,
+            ));
+            theme.warning()
+          } else {
+            theme.invisible()
+          };
+
+          let glyph = SharedString::new_static(invisible.glyph);
+          let runs = vec![TextRun {
+            len: glyph.len(),
+            font: style.font(),
+            color,
+            background_color: None,
+            underline: None,
+            strikethrough: None,
+          }];
+          let shaped_glyph = window
+            .text_system()
+            .shape_line(glyph, font_size, &runs, None);
+          invisible_glyphs.push((point(x, y), shaped_glyph));
+        }
+      } else if deferred_lines.iter().any(|(idx, _)| *idx == line_idx)
+        && rows.row_of(line_idx, 0).map(|(row, _)| row) == Some(span.row)
+      {
+        // Until it is shaped, a deferred line is shown on its first row
+        let line_content = document.line_content(line_idx).unwrap_or_default();
+        let column = |byte: usize| line_content[..byte].chars().count();
+        let x_start = shaping::placeholder_x(column(span.bytes.start), placeholder_advance);
+        let x_end = shaping::placeholder_x(column(span.bytes.end), placeholder_advance);
+        let y = rows.row_top(span.row, bounds.top(), line_height);
+        let visual_x_end = if span.past_end {
+          x_end + px(NEWLINE_SELECTION_WIDTH)
+        } else {
+          x_end
+        };
+        selection_quads.push(fill(
+          Bounds::from_corners(
+            point(bounds.left() + x_start, y),
+            point(bounds.left() + visual_x_end, y + line_height),
+          ),
+          theme.selection(),
+        ));
       }
-    } else {
-      None
-    };
+    }
 
-    let mut selection_quads = Vec::new();
-    if !selected_range.is_empty() {
-      let sel_start = selected_range.start;
-      let sel_end = selected_range.end;
-      let sel_start_line = document.char_to_line(sel_start);
-      let sel_end_line = document.char_to_line(sel_end);
+    let mut decoration_quads = Vec::new();
+    for range in &search_matches {
+      let line_idx = line_index.char_to_line(document, range.start);
+      let Some((_, shaped)) = shaped_lines.iter().find(|(idx, _)| *idx == line_idx) else {
+        continue;
+      };
+      let line_start = line_index.line_to_char(document, line_idx);
+      let line_content = document.line_content(line_idx).unwrap_or_default();
+      let bytes = byte_for_column(&line_content, range.start - line_start)
+        ..byte_for_column(&line_content, range.end - line_start);
+      for match_bounds in span_bounds(line_idx, shaped, bytes) {
+        decoration_quads.push(fill(match_bounds, theme.search_match()));
+      }
+    }
 
-      for line_idx in sel_start_line..=sel_end_line {
+    let decorations = self
+      .editor
+      .read(cx)
+      .decorations
+      .resolve_in_lines(viewport.clone(), document);
+    for decoration in &decorations {
+      for line_idx in decoration.lines.clone() {
         if !viewport.contains(&line_idx) {
           continue;
         }
-        let line_range = document.line_range(line_idx).unwrap();
+
+        if let Some(color) = decoration.style.background {
+          for row_bounds in row_bounds(line_idx) {
+            decoration_quads.push(fill(row_bounds, color));
+          }
+        }
+
         let shaped_opt = shaped_lines
           .iter()
           .find(|(idx, _)| *idx == line_idx)
           .map(|(_, shaped)| shaped);
 
-        if let Some(shaped) = shaped_opt {
-          let line_start = line_range.start;
-          let line_end = line_range.end;
-          let sel_line_start = sel_start.max(line_start) - line_start;
-          let sel_line_end = sel_end.min(line_end) - line_start;
-          let x_start = shaped.x_for_index(sel_line_start);
-          let x_end = shaped.x_for_index(sel_line_end);
-          let y = bounds.top() + line_height * (line_idx - viewport.start) as f32;
-
-          // If selection is empty on this line (selecting just the newline),
-          // Only add width if we're actually selecting the newline character
-          let is_selecting_newline = sel_line_end > sel_line_start && x_start == x_end;
-          let visual_x_end = if is_selecting_newline {
-            x_end + px(NEWLINE_SELECTION_WIDTH) // Small width to show newline selection
-          } else {
-            x_end
-          };
+        if let (Some(color), Some(shaped)) = (decoration.style.underline, shaped_opt) {
+          let line_range = line_index.line_range(document, line_idx).unwrap_or(0..0);
+          let line_len = document.line_content(line_idx).unwrap_or_default().len();
+          let start = decoration.range.start.max(line_range.start) - line_range.start;
+          let end = (decoration.range.end.min(line_range.end) - line_range.start).min(line_len);
+          if end > start {
+            for span in span_bounds(line_idx, shaped, start..end) {
+              decoration_quads.push(fill(
+                Bounds::from_corners(
+                  point(
+                    span.left(),
+                    span.bottom() - px(DECORATION_UNDERLINE_THICKNESS),
+                  ),
+                  span.bottom_right(),
+                ),
+                color,
+              ));
+            }
+          }
+        }
+      }
+    }
 
-          selection_quads.push(fill(
-            Bounds::from_corners(
-              point(bounds.left() + x_start, y),
-              point(bounds.left() + visual_x_end, y + line_height),
-            ),
-            theme.selection(),
-          ));
+    // Fold placeholders and virtual text are painted where the rows leave
+    // room for them
+    let mut inlays = Vec::new();
+    for row in rows.rows.clone() {
+      let Some((line_idx, _)) = rows.at_row(row) else {
+        continue;
+      };
+      let Some((_, shaped)) = shaped_lines.iter().find(|(idx, _)| *idx == line_idx) else {
+        continue;
+      };
+      let y = rows.row_top(row, bounds.top(), line_height);
+      for (inlay, x) in rows
+        .inlays(row)
+        .iter()
+        .zip(rows.inlay_xs(row, shaped, char_width))
+      {
+        let color = if inlay.placeholder {
+          theme.line_number()
+        } else {
+          inlay.color.unwrap_or(style.color)
+        };
+        let runs = vec![TextRun {
+          len: inlay.text.len(),
+          font: style.font(),
+          color,
+          background_color: None,
+          underline: None,
+          strikethrough: None,
+        }];
+        let shaped_inlay =
+          window
+            .text_system()
+            .shape_line(inlay.text.clone().into(), font_size, &runs, None);
+        inlays.push((point(bounds.left() + x, y), shaped_inlay));
+      }
+    }
+
+    // Focus mode fades the lines outside the focused scope into the background
+    let mut dim_quads = Vec::new();
+    if let Some(focused) = focus_mode::focused_lines(self.editor.read(cx), cx) {
+      let mut veil = theme.background();
+      veil.a = 1.0 - DIMMED_OPACITY;
+      for line_idx in viewport.clone() {
+        if focused.contains(&line_idx) {
+          continue;
+        }
+        for row_bounds in row_bounds(line_idx) {
+          dim_quads.push(fill(row_bounds, veil));
         }
       }
     }
 
+    // Jump labels cover the start of their words, showing the letters left to type
+    let mut jump_labels = Vec::new();
+    if let Some(jump) = self.editor.read(cx).jump.as_ref() {
+      for target in jump.visible_targets() {
+        let line_idx = document.char_to_line(target.offset);
+        let Some((_, shaped)) = shaped_lines.iter().find(|(idx, _)| *idx == line_idx) else {
+          continue;
+        };
+        let line_content = document.line_content(line_idx).unwrap_or_default();
+        let column = target.offset - document.line_to_char(line_idx);
+        let byte = byte_for_column(&line_content, column);
+        let Some(origin) = glyph_position(line_idx, shaped, byte) else {
+          continue;
+        };
+        let label = SharedString::from(target.label[jump.typed().len()..].to_string());
+        let runs = vec![TextRun {
+          len: label.len(),
+          font: style.font(),
+          color: black(),
+          background_color: Some(yellow()),
+          underline: None,
+          strikethrough: None,
+        }];
+        let shaped_label = window
+          .text_system()
+          .shape_line(label, font_size, &runs, None);
+        jump_labels.push((origin, shaped_label));
+      }
+    }
+
+    // Flag bidi controls and confusable characters on every visible line
+    let mut warning_background = theme.warning();
+    warning_background.a = 0.2;
+    for (line_idx, shaped) in &shaped_lines {
+      let line_content = document.line_content(*line_idx).unwrap_or_default();
+      for (offset, _) in unicode_warnings_in_line(&line_content) {
+        let char_len = line_content[offset..]
+          .chars()
+          .next()
+          .map_or(1, char::len_utf8);
+        let Some(origin) = glyph_position(*line_idx, shaped, offset) else {
+          continue;
+        };
+        let (x_start, y) = (origin.x, origin.y);
+        let x_end = (x_start + shaped.x_for_index(offset + char_len) - shaped.x_for_index(offset))
+          .max(x_start + px(NEWLINE_SELECTION_WIDTH));
+        decoration_quads.push(fill(
+          Bounds::from_corners(point(x_start, y), point(x_end, y + line_height)),
+          warning_background,
+        ));
+        decoration_quads.push(fill(
+          Bounds::from_corners(
+            point(
+              x_start,
+              y + line_height - px(DECORATION_UNDERLINE_THICKNESS),
+            ),
+            point(x_end, y + line_height),
+          ),
+          theme.warning(),
+        ));
+      }
+    }
+
+    let ruler = if self.editor.read(cx).show_wrap_ruler {
+      let column = ruler::wrap_column(self.editor.read(cx), cx);
+      let runs = vec![TextRun {
+        len: 1,
+        font: style.font(),
+        color: style.color,
+        background_color: None,
+        underline: None,
+        strikethrough: None,
+      }];
+      let char_width = window
+        .text_system()
+        .shape_line("m".into(), font_size, &runs, None)
+        .width;
+      let x = bounds.left() + char_width * column as f32;
+      let mut line_color = theme.line_number();
+      line_color.a = 0.3;
+
+      Some(RulerLayout {
+        line: fill(
+          Bounds::from_corners(point(x, bounds.top()), point(x + px(1.), bounds.bottom())),
+          line_color,
+        ),
+        handle: fill(
+          Bounds::from_corners(
+            point(x - px(RULER_HANDLE_HALF_WIDTH), bounds.top()),
+            point(
+              x + px(RULER_HANDLE_HALF_WIDTH),
+              bounds.top() + px(RULER_HANDLE_HEIGHT),
+            ),
+          ),
+          theme.line_number(),
+        ),
+        char_width,
+      })
+    } else {
+      None
+    };
+
+    self.editor.update(cx, |editor, _| {
+      if let Some(trace) = editor.trace.as_mut() {
+        let now = Instant::now();
+        trace.record_span("prepaint", prepaint_start, now);
+        trace.record_counter(
+          "line_cache",
+          now,
+          vec![
+            ("hits", cache_hits),
+            ("misses", cache_misses),
+            ("size", editor.line_layouts.len()),
+          ],
+        );
+      }
+    });
+
     PrepaintState {
       shaped_lines,
+      placeholder_lines,
       cursor_quad,
+      extra_cursor_quads,
       selection_quads,
+      decoration_quads,
+      inlays,
+      jump_labels,
+      dim_quads,
+      invisible_glyphs,
+      ruler,
+      echo,
+      line_index,
       viewport,
+      rows,
+      char_width,
       bounds,
       line_height,
     }
@@ -419,6 +1046,7 @@ impl Element for EditorElement {
     window: &mut Window,
     cx: &mut App,
   ) {
+    let paint_start = Instant::now();
     let (focus_handle, is_focused) = {
       let editor = self.editor.read(cx);
       (
@@ -439,6 +1067,12 @@ impl Element for EditorElement {
       bounds: prepaint.bounds,
       line_height: prepaint.line_height,
       viewport: prepaint.viewport.clone(),
+      line_index: Arc::clone(&prepaint.line_index),
+      rows: Arc::clone(&prepaint.rows),
+      char_width: prepaint.char_width,
+    });
+    self.editor.update(cx, |editor, _| {
+      editor.last_position_map = Some(Rc::clone(&position_map));
     });
 
     window.on_mouse_event({
@@ -480,40 +1114,126 @@ impl Element for EditorElement {
     });
 
     // Handle mouse wheel scroll
-    window.on_mouse_event({
-      let editor = self.editor.clone();
-      move |event: &ScrollWheelEvent, phase, _window, cx| {
-        if phase == DispatchPhase::Bubble {
-          editor.update(cx, |editor, cx| {
-            let document = editor.document().read(cx);
-            let total_lines = document.len_lines();
-
-            // Extract scroll delta (handle both pixel and line scrolling)
-            // Note: Negative delta because scrolling down should increase scroll_offset
-            let scroll_delta = match event.delta {
-              ScrollDelta::Pixels(point) => -(point.y / px(PIXEL_SCROLL_DIVISOR)), // Pixel scrolling (trackpad)
-              ScrollDelta::Lines(point) => -(point.y * LINE_SCROLL_MULTIPLIER), // Line scrolling (mouse wheel)
-            };
-
-            let new_scroll = (editor.scroll_offset_y + scroll_delta)
-              .max(0.0)
-              .min((total_lines.saturating_sub(1)) as f32);
-
-            editor.scroll_offset_y = new_scroll;
-            cx.notify();
-          });
+    register_scroll_wheel(&self.editor, window);
+
+    // Clicking the top strip moves the wrap ruler there, dragging keeps moving it
+    if let Some(ruler) = &prepaint.ruler {
+      let handle_strip = Bounds::from_corners(
+        bounds.origin,
+        point(bounds.right(), bounds.top() + px(RULER_HANDLE_HEIGHT)),
+      );
+      let char_width = ruler.char_width;
+      let column_at = move |x: Pixels| ((x - bounds.left()) / char_width).round().max(1.0) as usize;
+
+      window.on_mouse_event({
+        let editor = self.editor.clone();
+        move |event: &MouseDownEvent, phase, _window, cx| {
+          if phase == DispatchPhase::Capture
+            && event.button == MouseButton::Left
+            && handle_strip.contains(&event.position)
+          {
+            editor.update(cx, |editor, cx| {
+              editor.dragging_ruler = true;
+              ruler::set_wrap_column(editor, column_at(event.position.x), cx);
+            });
+            cx.stop_propagation();
+          }
         }
-      }
-    });
+      });
+
+      window.on_mouse_event({
+        let editor = self.editor.clone();
+        move |event: &MouseMoveEvent, phase, _window, cx| {
+          if phase == DispatchPhase::Capture && editor.read(cx).dragging_ruler {
+            editor.update(cx, |editor, cx| {
+              ruler::set_wrap_column(editor, column_at(event.position.x), cx);
+            });
+            cx.stop_propagation();
+          }
+        }
+      });
+
+      window.on_mouse_event({
+        let editor = self.editor.clone();
+        move |_: &MouseUpEvent, phase, _window, cx| {
+          if phase == DispatchPhase::Capture && editor.read(cx).dragging_ruler {
+            editor.update(cx, |editor, _| editor.dragging_ruler = false);
+            cx.stop_propagation();
+          }
+        }
+      });
+    }
+
+    // Paint decorations below selection and text
+    for quad in &prepaint.decoration_quads {
+      window.paint_quad(quad.clone());
+    }
+
+    if let Some(ruler) = &prepaint.ruler {
+      window.paint_quad(ruler.line.clone());
+      window.paint_quad(ruler.handle.clone());
+    }
 
     // Paint selection
     for quad in &prepaint.selection_quads {
       window.paint_quad(quad.clone());
     }
 
-    // Paint text lines
+    // Paint text lines, one with inlays or wrapped piece by piece, row by row
     for (line_idx, shaped_line) in &prepaint.shaped_lines {
-      let y = bounds.top() + prepaint.line_height * (*line_idx - prepaint.viewport.start) as f32;
+      if prepaint.rows.is_wrapped(*line_idx) || prepaint.rows.has_inlays(*line_idx) {
+        for (row, _) in prepaint.rows.line_rows(*line_idx) {
+          let y = prepaint
+            .rows
+            .row_top(row, bounds.top(), prepaint.line_height);
+          for (bytes, x) in prepaint
+            .rows
+            .text_pieces(row, shaped_line, prepaint.char_width)
+          {
+            let x_start = shaped_line.x_for_index(bytes.start);
+            let width = shaped_line.x_for_index(bytes.end) - x_start;
+            let left = bounds.left() + x;
+            window.with_content_mask(
+              Some(ContentMask {
+                bounds: Bounds::from_corners(
+                  point(left, y),
+                  point(left + width, y + prepaint.line_height),
+                ),
+              }),
+              |window| {
+                shaped_line
+                  .paint(
+                    point(left - x_start, y),
+                    prepaint.line_height,
+                    TextAlign::Left,
+                    None,
+                    window,
+                    cx,
+                  )
+                  .ok();
+              },
+            );
+          }
+        }
+        continue;
+      }
+      let Some((row, _)) = prepaint.rows.row_of(*line_idx, 0) else {
+        continue;
+      };
+      let y = prepaint
+        .rows
+        .row_top(row, bounds.top(), prepaint.line_height);
+      if let Some(echo) = prepaint.echo.as_ref().filter(|echo| echo.line == *line_idx) {
+        paint_echoed_line(
+          shaped_line,
+          echo,
+          point(bounds.left(), y),
+          prepaint.line_height,
+          window,
+          cx,
+        );
+        continue;
+      }
       shaped_line
         .paint(
           point(bounds.left(), y),
@@ -525,6 +1245,70 @@ impl Element for EditorElement {
         )
         .ok();
     }
+    for (line_idx, placeholder) in &prepaint.placeholder_lines {
+      let Some((row, _)) = prepaint.rows.row_of(*line_idx, 0) else {
+        continue;
+      };
+      let y = prepaint
+        .rows
+        .row_top(row, bounds.top(), prepaint.line_height);
+      placeholder
+        .paint(
+          point(bounds.left(), y),
+          prepaint.line_height,
+          TextAlign::Left,
+          None,
+          window,
+          cx,
+        )
+        .ok();
+    }
+
+    // Paint glyphs for invisible characters in the selection
+    for (origin, shaped_glyph) in &prepaint.invisible_glyphs {
+      shaped_glyph
+        .paint(
+          *origin,
+          prepaint.line_height,
+          TextAlign::Left,
+          None,
+          window,
+          cx,
+        )
+        .ok();
+    }
+
+    // Paint fold placeholders and virtual text
+    for (origin, shaped_text) in &prepaint.inlays {
+      shaped_text
+        .paint(
+          *origin,
+          prepaint.line_height,
+          TextAlign::Left,
+          None,
+          window,
+          cx,
+        )
+        .ok();
+    }
+
+    for quad in &prepaint.dim_quads {
+      window.paint_quad(quad.clone());
+    }
+
+    // Paint jump labels over the text
+    for (origin, shaped_label) in &prepaint.jump_labels {
+      shaped_label
+        .paint(
+          *origin,
+          prepaint.line_height,
+          TextAlign::Left,
+          None,
+          window,
+          cx,
+        )
+        .ok();
+    }
 
     // Paint cursor (if focused and visible from blink)
     let cursor_visible = self.editor.read(cx).cursor_blink.read(cx).visible();
@@ -534,6 +1318,33 @@ impl Element for EditorElement {
     {
       window.paint_quad(cursor_quad.clone());
     }
+    if is_focused && cursor_visible {
+      for cursor_quad in &prepaint.extra_cursor_quads {
+        window.paint_quad(cursor_quad.clone());
+      }
+    }
+
+    self.editor.update(cx, |editor, _| {
+      if let Some(trace) = editor.trace.as_mut() {
+        trace.record_span("paint", paint_start, Instant::now());
+      }
+      #[cfg(feature = "test-interface")]
+      {
+        editor.painted = Some(crate::test_interface::PaintedFrame {
+          cursor: prepaint.cursor_quad.as_ref().map(|quad| quad.bounds),
+          selections: prepaint
+            .selection_quads
+            .iter()
+            .map(|quad| quad.bounds)
+            .collect(),
+          lines: prepaint
+            .shaped_lines
+            .iter()
+            .map(|(line_idx, _)| *line_idx)
+            .collect(),
+        });
+      }
+    });
   }
 }
 
@@ -0,0 +1,183 @@
+//! Elastic tabstops
+//!
+//! With elastic tabstops a tab ends a cell instead of jumping to a fixed
+//! column. The cells in one column of adjacent lines that all have a tab
+//! there form a column block, and every cell of a block is as wide as its
+//! widest one, so tab-separated tables line up without counting spaces.
+//!
+//! The width a cell is missing is an inlay of spaces before its tab, so the
+//! display map moves what follows the tab to its column while shaped lines
+//! stay as they are: an edit widening a cell changes the rows of the lines
+//! around it without shaping them again. The cells are measured once per
+//! document version.
+
+use std::ops::Range;
+
+use gpui::App;
+
+use crate::{display_map::Inlay, document::Document, editor::Editor};
+
+/// Columns between the widest cell of a column block and the next cell
+const PADDING: usize = 2;
+
+#[derive(Default)]
+pub(crate) struct ElasticTabs {
+  /// Document version `inlays` were laid out for
+  version: Option<usize>,
+  inlays: Vec<Inlay>,
+}
+
+/// Width in columns of each tab-terminated cell of `lines`; the text after a
+/// line's last tab isn't a cell
+pub(crate) fn cell_widths<S: AsRef<str>>(lines: &[S]) -> Vec<Vec<usize>> {
+  let cells: Vec<Vec<usize>> = lines
+    .iter()
+    .map(|line| {
+      let mut cells: Vec<usize> = line
+        .as_ref()
+        .split('\t')
+        .map(|cell| cell.chars().count())
+        .collect();
+      cells.pop();
+      cells
+    })
+    .collect();
+
+  let mut widths = cells.clone();
+  let columns = cells.iter().map(Vec::len).max().unwrap_or(0);
+  for column in 0..columns {
+    let mut line = 0;
+    while line < cells.len() {
+      if cells[line].len() <= column {
+        line += 1;
+        continue;
+      }
+      // Adjacent lines with a cell in this column form one block
+      let block_start = line;
+      while line < cells.len() && cells[line].len() > column {
+        line += 1;
+      }
+      let width = cells[block_start..line]
+        .iter()
+        .map(|cells| cells[column])
+        .max()
+        .unwrap_or(0)
+        + PADDING;
+      for widths in &mut widths[block_start..line] {
+        widths[column] = width;
+      }
+    }
+  }
+  widths
+}
+
+/// Spaces before each tab of `lines` of `document` that widen its cell to
+/// `widths`, the tab itself taking one column
+fn padding(document: &Document, lines: Range<usize>, widths: &[Vec<usize>]) -> Vec<Inlay> {
+  let mut inlays = Vec::new();
+  for (line, widths) in lines.zip(widths) {
+    let Some(content) = document.line_content(line) else {
+      continue;
+    };
+    let line_start = document.line_to_char(line);
+    let mut cell_start = 0;
+    let tabs = content
+      .chars()
+      .enumerate()
+      .filter(|(_, ch)| *ch == '\t')
+      .map(|(column, _)| column);
+    for (column, width) in tabs.zip(widths) {
+      let missing = width.saturating_sub(column - cell_start + 1);
+      if missing > 0 {
+        inlays.push(Inlay {
+          offset: line_start + column,
+          text: " ".repeat(missing),
+          color: None,
+        });
+      }
+      cell_start = column + 1;
+    }
+  }
+  inlays
+}
+
+/// Padding of the tabs of the document, as inlays in order of offset; none
+/// unless elastic tabstops are on
+pub(crate) fn inlays<'a>(editor: &'a mut Editor, cx: &App) -> &'a [Inlay] {
+  if !editor.elastic_tabstops {
+    return &[];
+  }
+  let document = editor.document.read(cx);
+  let version = document.version();
+  let tabs = &mut editor.elastic_tabs;
+  if tabs.version != Some(version) {
+    let lines: Vec<String> = (0..document.len_lines())
+      .map(|line| {
+        document
+          .line_content(line)
+          .map(|content| content.into_owned())
+          .unwrap_or_default()
+      })
+      .collect();
+    let widths = cell_widths(&lines);
+    tabs.inlays = padding(document, 0..lines.len(), &widths);
+    tabs.version = Some(version);
+  }
+  &tabs.inlays
+}
+
+/// Forget the padding of the previous document
+pub(crate) fn clear(editor: &mut Editor) {
+  editor.elastic_tabs = ElasticTabs::default();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{editor::tests::EditorTestContext, wrap_map};
+  use gpui::TestAppContext;
+
+  #[test]
+  fn test_cell_widths() {
+    let lines = ["a\tbb\tc", "aaaa\tb\t", "", "x\ty"];
+    assert_eq!(
+      cell_widths(&lines),
+      [vec![6, 4], vec![6, 4], vec![], vec![3]]
+    );
+    // The text after the last tab isn't a cell
+    assert_eq!(cell_widths(&["no tabs", "end\t"]), [vec![], vec![5]]);
+  }
+
+  #[test]
+  fn test_block_ends_at_line_without_column() {
+    // The second column of the first and last lines aren't adjacent
+    let lines = ["a\tb\tc", "a\tc", "a\tbbbbb\tc"];
+    assert_eq!(cell_widths(&lines), [vec![3, 3], vec![3], vec![3, 7]]);
+  }
+
+  #[gpui::test]
+  fn test_cells_line_up(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "a\tb\nccc\td\nplain");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.set_elastic_tabstops(true, cx);
+      let map = wrap_map::rows(editor, cx).unwrap();
+      // Cells are 5 columns wide, the text after the tabs starts at column 5
+      assert_eq!(map.to_display_point(2).column, 5);
+      assert_eq!(map.to_display_point(8).column, 5);
+      // The caret at a tab is at the end of its cell's text
+      assert_eq!(map.to_display_point(1).column, 1);
+
+      // Widening a cell moves the next column of the lines around it
+      editor.move_to(1, cx);
+      editor.insert_text("xxxx", "Typing", cx);
+      wrap_map::refresh(editor, cx);
+      let map = wrap_map::rows(editor, cx).unwrap();
+      assert_eq!(map.to_display_point(6).column, 7);
+      assert_eq!(map.to_display_point(12).column, 7);
+
+      editor.set_elastic_tabstops(false, cx);
+      let map = wrap_map::rows(editor, cx).unwrap();
+      assert_eq!(map.to_display_point(12).column, 4);
+    });
+  }
+}
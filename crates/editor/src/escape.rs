@@ -0,0 +1,177 @@
+//! What Escape closes, one layer at a time
+//!
+//! The editor's transient UI is stacked in the order of `LAYERS`, topmost
+//! first. Each Escape closes only the topmost layer that is open, so a
+//! palette over a selection goes away first and the selection on the next
+//! press, however focus moved between them.
+
+use gpui::{App, Context, Window};
+
+use crate::{
+  command_palette, describe, editor::Editor, find, jump, multi_cursor, path_completion, quick_diff,
+  selection_lock, shortcuts, unicode_input,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Layer {
+  ShortcutSheet,
+  CommandPalette,
+  UnicodeInput,
+  PathCompletion,
+  JumpLabels,
+  ProgressMenu,
+  CharacterDescription,
+  QuickDiff,
+  FindBar,
+  SelectionLock,
+  ExtraCursors,
+  Selection,
+}
+
+/// Layers from the topmost down
+const LAYERS: &[Layer] = &[
+  Layer::ShortcutSheet,
+  Layer::CommandPalette,
+  Layer::UnicodeInput,
+  Layer::PathCompletion,
+  Layer::JumpLabels,
+  Layer::ProgressMenu,
+  Layer::CharacterDescription,
+  Layer::QuickDiff,
+  Layer::FindBar,
+  Layer::SelectionLock,
+  Layer::ExtraCursors,
+  Layer::Selection,
+];
+
+fn is_open(editor: &Editor, layer: Layer, cx: &App) -> bool {
+  match layer {
+    Layer::ShortcutSheet => editor.shortcut_sheet.is_some(),
+    Layer::CommandPalette => editor.command_palette.is_some(),
+    Layer::UnicodeInput => editor.unicode_input.is_some(),
+    Layer::PathCompletion => path_completion::is_open(editor),
+    Layer::JumpLabels => jump::is_active(editor),
+    Layer::ProgressMenu => editor.progress_menu_open,
+    Layer::CharacterDescription => editor.character_description.is_some(),
+    Layer::QuickDiff => editor.quick_diff.is_some(),
+    Layer::FindBar => find::is_open(editor),
+    Layer::SelectionLock => selection_lock::is_locked(editor, cx),
+    Layer::ExtraCursors => multi_cursor::is_active(editor),
+    Layer::Selection => !editor.selected_range.is_empty(),
+  }
+}
+
+fn close(editor: &mut Editor, layer: Layer, window: &mut Window, cx: &mut Context<Editor>) {
+  match layer {
+    Layer::ShortcutSheet => shortcuts::close(editor, window, cx),
+    Layer::CommandPalette => command_palette::close(editor, window, cx),
+    Layer::UnicodeInput => unicode_input::close(editor, window, cx),
+    Layer::PathCompletion => path_completion::close(editor, cx),
+    Layer::JumpLabels => jump::cancel(editor, cx),
+    Layer::ProgressMenu => {
+      editor.progress_menu_open = false;
+      cx.notify();
+    }
+    Layer::CharacterDescription => describe::dismiss(editor, cx),
+    Layer::QuickDiff => quick_diff::dismiss(editor, cx),
+    Layer::FindBar => find::close(editor, window, cx),
+    Layer::SelectionLock => selection_lock::cancel(editor, cx),
+    Layer::ExtraCursors => multi_cursor::clear(editor, cx),
+    Layer::Selection => {
+      let cursor = editor.cursor_offset();
+      editor.move_to(cursor, cx);
+    }
+  }
+}
+
+/// The open layer Escape would close
+pub(crate) fn topmost(editor: &Editor, cx: &App) -> Option<Layer> {
+  LAYERS
+    .iter()
+    .copied()
+    .find(|&layer| is_open(editor, layer, cx))
+}
+
+/// Close the topmost open layer, returning whether there was one
+pub(crate) fn close_topmost(
+  editor: &mut Editor,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) -> bool {
+  let Some(layer) = topmost(editor, cx) else {
+    return false;
+  };
+  close(editor, layer, window, cx);
+  true
+}
+
+/// Close every layer above the selection
+pub(crate) fn close_overlays(editor: &mut Editor, window: &mut Window, cx: &mut Context<Editor>) {
+  for &layer in LAYERS {
+    if layer != Layer::Selection && is_open(editor, layer, cx) {
+      close(editor, layer, window, cx);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::document::Document;
+  use gpui::{AppContext, TestAppContext, WindowHandle};
+
+  fn open_editor(cx: &mut TestAppContext, text: &str) -> WindowHandle<Editor> {
+    cx.add_window(|window, cx| {
+      let document = cx.new(|cx| Document::new(text, None, cx));
+      let editor = Editor::with_document(document, cx);
+      window.focus(&editor.focus_handle, cx);
+      editor
+    })
+  }
+
+  #[gpui::test]
+  fn test_escape_closes_one_layer_at_a_time(cx: &mut TestAppContext) {
+    let window = open_editor(cx, "one two");
+    window
+      .update(cx, |editor, window, cx| {
+        editor.selected_range = 0..3;
+        selection_lock::toggle(editor, cx);
+        describe::show(editor, cx);
+        unicode_input::open(editor, window, cx);
+
+        let mut closed = Vec::new();
+        while let Some(layer) = topmost(editor, cx) {
+          assert!(close_topmost(editor, window, cx));
+          closed.push(layer);
+        }
+        assert_eq!(
+          closed,
+          [
+            Layer::UnicodeInput,
+            Layer::CharacterDescription,
+            Layer::SelectionLock,
+            Layer::Selection,
+          ]
+        );
+        // The selection collapses to the cursor, and then nothing is left
+        assert_eq!(editor.selected_range, 3..3);
+        assert!(!close_topmost(editor, window, cx));
+      })
+      .unwrap();
+  }
+
+  #[gpui::test]
+  fn test_close_overlays_keeps_selection(cx: &mut TestAppContext) {
+    let window = open_editor(cx, "one two");
+    window
+      .update(cx, |editor, window, cx| {
+        editor.selected_range = 4..6;
+        describe::show(editor, cx);
+        shortcuts::open(editor, window, cx);
+        close_overlays(editor, window, cx);
+        assert_eq!(topmost(editor, cx), Some(Layer::Selection));
+        assert!(editor.focus_handle.is_focused(window));
+      })
+      .unwrap();
+  }
+}
@@ -0,0 +1,151 @@
+//! Opening and saving files
+//!
+//! Files are read and written on a background thread. Opening asks for a
+//! file and shows it, or a preview of it, in place of the document. Saving
+//! first adds the final newline the document asks for, as an undoable edit,
+//! and writes to the file the document came from; a document without one
+//! asks where to write it, as Save As does.
+
+use std::path::{Path, PathBuf};
+
+use gpui::{Context, PathPromptOptions, Window};
+
+use crate::{editor::Editor, folds, preview};
+
+/// Ask for a file and open it
+pub(crate) fn open(window: &mut Window, cx: &mut Context<Editor>) {
+  let paths = cx.prompt_for_paths(PathPromptOptions {
+    files: true,
+    directories: false,
+    multiple: false,
+    prompt: Some("Open".into()),
+  });
+  cx.spawn_in(window, async move |this, cx| {
+    let Ok(Ok(Some(paths))) = paths.await else {
+      return;
+    };
+    let Some(path) = paths.into_iter().next() else {
+      return;
+    };
+    let load_path = path.clone();
+    let contents = cx
+      .background_executor()
+      .spawn(async move { preview::load(&load_path) })
+      .await;
+    let _ = this.update(cx, |editor, cx| match contents {
+      Ok(_) if editor.is_open(&path) => {}
+      Ok(contents) => editor.show_file(path, contents, cx),
+      Err(err) => eprintln!("Failed to open {}: {}", path.display(), err),
+    });
+  })
+  .detach();
+}
+
+/// Save to the document's file, or ask for one
+pub(crate) fn save(editor: &mut Editor, window: &mut Window, cx: &mut Context<Editor>) {
+  if editor.preview.is_some() {
+    return;
+  }
+  match editor.document.read(cx).path().map(Path::to_path_buf) {
+    Some(path) => save_to(editor, path, cx),
+    None => save_as(editor, window, cx),
+  }
+}
+
+/// Ask for a file to save to, next to the open one
+pub(crate) fn save_as(editor: &mut Editor, window: &mut Window, cx: &mut Context<Editor>) {
+  if editor.preview.is_some() {
+    return;
+  }
+  let directory = editor
+    .file_path()
+    .and_then(Path::parent)
+    .map(Path::to_path_buf)
+    .or_else(|| std::env::current_dir().ok())
+    .unwrap_or_default();
+  let name = editor
+    .file_path()
+    .and_then(Path::file_name)
+    .map(|name| name.to_string_lossy().into_owned());
+  let path = cx.prompt_for_new_path(&directory, name.as_deref());
+  cx.spawn_in(window, async move |this, cx| {
+    let Ok(Ok(Some(path))) = path.await else {
+      return;
+    };
+    let _ = this.update(cx, |editor, cx| save_to(editor, path, cx));
+  })
+  .detach();
+}
+
+/// Write the document to `path`, the file it is saved to from then on
+pub(crate) fn save_to(editor: &mut Editor, path: PathBuf, cx: &mut Context<Editor>) {
+  editor.prepare_for_save(cx);
+  let task = editor
+    .document
+    .update(cx, |document, cx| document.save_as(path, cx));
+  cx.spawn(async move |this, cx| match task.await {
+    // The folds are kept along with the text they fold
+    Ok(()) => {
+      let _ = this.update(cx, |editor, cx| folds::persist(editor, cx));
+    }
+    Err(err) => eprintln!("{}", err),
+  })
+  .detach();
+  cx.notify();
+}
+
+/// Show the document's file as the editor's once a save moves it there; the
+/// document's path is the one saves go to, the editor's may name a preview
+pub(crate) fn follow_document_path(editor: &mut Editor, cx: &mut Context<Editor>) {
+  if let Some(path) = editor.document.read(cx).path()
+    && editor.file_path.as_deref() != Some(path)
+  {
+    editor.file_path = Some(path.to_path_buf());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[gpui::test]
+  fn test_save_to_writes_and_clears_dirty(cx: &mut TestAppContext) {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path();
+    let path = dir.join("notes.txt");
+    std::fs::write(&path, "one").unwrap();
+
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.open_file(path.clone(), cx).unwrap();
+      assert_eq!(editor.document.read(cx).path(), Some(path.as_path()));
+      editor.move_to(3, cx);
+      editor.insert_text(" two", "Typing", cx);
+      assert!(editor.document.read(cx).is_dirty());
+      save_to(editor, path.clone(), cx);
+    });
+    ctx.cx.run_until_parked();
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "one two\n");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert!(!editor.document.read(cx).is_dirty());
+      assert_eq!(editor.undo_description(cx), Some("Insert Final Newline"));
+    });
+
+    // Save As moves the document to the new file
+    let copy = dir.join("copy.txt");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      save_to(editor, copy.clone(), cx);
+      // Until the file is written the editor still shows the old one
+      assert_eq!(editor.file_path(), Some(path.as_path()));
+    });
+    ctx.cx.run_until_parked();
+    assert_eq!(std::fs::read_to_string(&copy).unwrap(), "one two\n");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert_eq!(editor.file_path(), Some(copy.as_path()));
+      assert_eq!(editor.document.read(cx).path(), Some(copy.as_path()));
+    });
+  }
+}
@@ -0,0 +1,658 @@
+//! Find and replace in the open document
+//!
+//! Find opens a bar above the text that takes keyboard focus. Matches of the
+//! query are highlighted as it is typed, selecting the first one from the
+//! cursor on; Enter and shift-Enter, or FindNext and FindPrevious, select the
+//! next or previous one, wrapping around the document. Replace adds a field
+//! for the replacement, switched to with Tab: Enter there replaces the
+//! selected match and selects the next, and Replace All rewrites every match
+//! as one undoable edit. Matches are looked for line by line, so they don't
+//! span line breaks.
+//!
+//! A match selected in a folded region unfolds just the folds hiding it.
+//! `FindSettings` can have next and previous skip such matches instead, and
+//! fold again what was unfolded for matches once the bar is closed.
+
+use std::{ops::Range, time::Instant};
+
+use buffer::Anchor;
+use gpui::{
+  App, ClickEvent, Context, FocusHandle, Focusable, Global, KeyDownEvent, WeakEntity, Window, div,
+  prelude::*, px,
+};
+
+use crate::{
+  actions::{Backspace, Enter, Indent, Paste},
+  editor::Editor,
+  escape, folds, multi_cursor,
+  search::SearchQuery,
+};
+
+/// How matches in folded regions are gone to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FindSettings {
+  /// Pass over matches in folded regions instead of unfolding them
+  pub skip_folded: bool,
+  /// Fold again, on closing the bar, what was unfolded to show matches
+  pub refold_on_close: bool,
+}
+
+impl Global for FindSettings {}
+
+/// Last query searched for, and its matches in the document
+#[derive(Default)]
+pub(crate) struct FindMatches {
+  query: Option<SearchQuery>,
+  // Matches at a document version, found again once the text changed
+  found: Option<(usize, Vec<Range<usize>>)>,
+  /// Folds unfolded to show matches since the bar was opened
+  revealed: Vec<Range<Anchor>>,
+}
+
+impl FindMatches {
+  /// Search again, for another document
+  pub(crate) fn invalidate(&mut self) {
+    self.found = None;
+    self.revealed.clear();
+  }
+}
+
+fn settings(cx: &App) -> FindSettings {
+  cx.try_global::<FindSettings>().copied().unwrap_or_default()
+}
+
+pub(crate) fn is_open(editor: &Editor) -> bool {
+  editor.find_bar.is_some()
+}
+
+/// Open the bar, or focus it, with the replace field if `replace`
+pub(crate) fn open(
+  editor: &mut Editor,
+  replace: bool,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  let bar = match editor.find_bar.clone() {
+    Some(bar) => bar,
+    None => {
+      // Search for the selected text, or again for the last query
+      let document = editor.document.read(cx);
+      let selected = Some(document.slice_to_string(editor.selected_range.clone()))
+        .filter(|text| !text.is_empty() && !text.contains('\n'));
+      let last = editor.find_matches.query.clone();
+      let query = match (selected, last) {
+        (Some(text), Some(last)) => SearchQuery { text, ..last },
+        (Some(text), None) => SearchQuery::new(text),
+        (None, Some(last)) => last,
+        (None, None) => SearchQuery::new(""),
+      };
+      let editor_handle = cx.weak_entity();
+      let bar = cx.new(|cx| FindBar {
+        editor: editor_handle,
+        focus_handle: cx.focus_handle(),
+        query,
+        replacement: String::new(),
+        replace: false,
+        editing_replacement: false,
+      });
+      editor.find_bar = Some(bar.clone());
+      let query = bar.read(cx).query.clone();
+      set_query(editor, query, cx);
+      bar
+    }
+  };
+  bar.update(cx, |bar, cx| {
+    bar.replace |= replace;
+    bar.editing_replacement = replace;
+    cx.notify();
+  });
+  window.focus(&bar.focus_handle(cx), cx);
+  cx.notify();
+}
+
+/// Close the bar, returning focus to the editor if it had it
+pub(crate) fn close(editor: &mut Editor, window: &mut Window, cx: &mut Context<Editor>) {
+  let Some(bar) = editor.find_bar.take() else {
+    return;
+  };
+  if bar.focus_handle(cx).is_focused(window) {
+    window.focus(&editor.focus_handle, cx);
+  }
+  let revealed = std::mem::take(&mut editor.find_matches.revealed);
+  if settings(cx).refold_on_close {
+    // Leaving the selection shown
+    let cursor = editor.cursor_offset();
+    folds::refold(editor, revealed, cursor, cx);
+  }
+  cx.notify();
+}
+
+fn set_query(editor: &mut Editor, query: SearchQuery, cx: &mut Context<Editor>) {
+  editor.find_matches.query = Some(query).filter(|query| !query.text.is_empty());
+  editor.find_matches.found = None;
+  cx.notify();
+}
+
+/// Matches of the query, searched for again when the text changed
+fn matches<'a>(editor: &'a mut Editor, cx: &App) -> &'a [Range<usize>] {
+  let document = editor.document.read(cx);
+  let version = document.version();
+  let find = &mut editor.find_matches;
+  if find
+    .found
+    .as_ref()
+    .is_none_or(|(found, _)| *found != version)
+  {
+    let ranges = find
+      .query
+      .as_ref()
+      .map(|query| document.find_all(query))
+      .unwrap_or_default();
+    find.found = Some((version, ranges));
+  }
+  find.found.as_ref().map_or(&[], |(_, ranges)| ranges)
+}
+
+/// Matches on `lines` to highlight, while the bar is open
+pub(crate) fn visible_matches(
+  editor: &mut Editor,
+  lines: Range<usize>,
+  cx: &App,
+) -> Vec<Range<usize>> {
+  if !is_open(editor) {
+    return Vec::new();
+  }
+  let document = editor.document.read(cx);
+  let start = document.line_to_char(lines.start.min(document.len_lines()));
+  let end = document.line_to_char(lines.end.min(document.len_lines()));
+  let ranges = matches(editor, cx);
+  let first = ranges.partition_point(|range| range.end <= start);
+  let last = ranges.partition_point(|range| range.start < end);
+  ranges[first..last.max(first)].to_vec()
+}
+
+/// Index of the selected match and the number of matches, for the bar
+fn match_position(editor: &mut Editor, cx: &App) -> (Option<usize>, usize) {
+  let selection = editor.selected_range.clone();
+  let ranges = matches(editor, cx);
+  let position = ranges.binary_search_by_key(&selection.start, |range| range.start);
+  (
+    position.ok().filter(|&ix| ranges[ix] == selection),
+    ranges.len(),
+  )
+}
+
+/// The match after `selection`, or before it, wrapping around
+fn next_match(
+  ranges: &[Range<usize>],
+  selection: &Range<usize>,
+  forward: bool,
+) -> Option<Range<usize>> {
+  let next = if forward {
+    let ix = ranges.partition_point(|range| range.start < selection.end);
+    ranges.get(ix).or(ranges.first())
+  } else {
+    let ix = ranges.partition_point(|range| range.end <= selection.start);
+    ix.checked_sub(1)
+      .and_then(|ix| ranges.get(ix))
+      .or(ranges.last())
+  };
+  next.cloned()
+}
+
+/// The match to go to from `selection`, passing over those in folds when
+/// they are skipped
+fn match_to_select(
+  editor: &mut Editor,
+  selection: &Range<usize>,
+  forward: bool,
+  cx: &App,
+) -> Option<Range<usize>> {
+  let skip_folded = settings(cx).skip_folded && !editor.folds.is_empty();
+  let ranges = matches(editor, cx).to_vec();
+  if !skip_folded {
+    return next_match(&ranges, selection, forward);
+  }
+  let shown: Vec<_> = ranges
+    .into_iter()
+    .filter(|range| !folds::is_folded(editor, range.start, cx))
+    .collect();
+  next_match(&shown, selection, forward)
+}
+
+fn select(editor: &mut Editor, range: Range<usize>, window: &Window, cx: &mut Context<Editor>) {
+  let revealed = folds::reveal(editor, range.start, cx);
+  editor.find_matches.revealed.extend(revealed);
+  multi_cursor::clear(editor, cx);
+  editor.selected_range = range;
+  editor.selection_reversed = false;
+  editor.target_column = None;
+  editor.ensure_cursor_visible(window, cx);
+  cx.notify();
+}
+
+/// Select the next or previous match, opening the bar if there is no query yet
+pub(crate) fn select_next(
+  editor: &mut Editor,
+  forward: bool,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  if editor.find_matches.query.is_none() {
+    return open(editor, false, window, cx);
+  }
+  let selection = editor.selected_range.clone();
+  if let Some(range) = match_to_select(editor, &selection, forward, cx) {
+    select(editor, range, window, cx);
+  }
+}
+
+/// Select the first match from `offset` on, as the query is typed
+fn select_from(editor: &mut Editor, offset: usize, window: &Window, cx: &mut Context<Editor>) {
+  if let Some(range) = match_to_select(editor, &(offset..offset), true, cx) {
+    select(editor, range, window, cx);
+  }
+}
+
+/// Replace the selected match, if it is one, and select the next
+fn replace_next(
+  editor: &mut Editor,
+  replacement: &str,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  if match_position(editor, cx).0.is_some() {
+    editor.insert_text(replacement, "Replace", cx);
+  }
+  select_next(editor, true, window, cx);
+}
+
+/// Replace every match as one undoable edit, returning how many there were
+pub(crate) fn replace_all(
+  editor: &mut Editor,
+  replacement: &str,
+  cx: &mut Context<Editor>,
+) -> usize {
+  let ranges = matches(editor, cx).to_vec();
+  let Some(first) = ranges.first() else {
+    return 0;
+  };
+  let first_line = editor.document.read(cx).char_to_line(first.start);
+
+  let selection_before = editor.selected_range.clone();
+  let transaction_id = editor.document.update(cx, |doc, cx| {
+    let id = doc.buffer.transaction(Instant::now(), |buffer, tx| {
+      // Back to front, so the ranges before each edit stay valid
+      for range in ranges.iter().rev() {
+        buffer.replace(tx, range.clone(), replacement);
+      }
+    });
+    doc.schedule_recompute_highlights(cx);
+    cx.notify();
+    id
+  });
+
+  multi_cursor::clear(editor, cx);
+  let cursor = selection_before.start.min(editor.document.read(cx).len());
+  editor.move_to(cursor, cx);
+  editor.record_transaction(
+    transaction_id,
+    selection_before,
+    cursor..cursor,
+    "Replace All",
+    cx,
+  );
+  editor.invalidate_lines_from(first_line);
+  ranges.len()
+}
+
+pub struct FindBar {
+  editor: WeakEntity<Editor>,
+  focus_handle: FocusHandle,
+  query: SearchQuery,
+  replacement: String,
+  /// Whether the replace field is shown
+  replace: bool,
+  /// Whether typing goes to the replace field
+  editing_replacement: bool,
+}
+
+impl FindBar {
+  fn update_query(
+    &mut self,
+    update: impl FnOnce(&mut SearchQuery),
+    window: &mut Window,
+    cx: &mut Context<Self>,
+  ) {
+    update(&mut self.query);
+    let query = self.query.clone();
+    let _ = self.editor.update(cx, |editor, cx| {
+      let offset = editor.selected_range.start;
+      set_query(editor, query, cx);
+      select_from(editor, offset, window, cx);
+    });
+    cx.notify();
+  }
+
+  fn edit(&mut self, edit: impl FnOnce(&mut String), window: &mut Window, cx: &mut Context<Self>) {
+    if self.editing_replacement {
+      edit(&mut self.replacement);
+      cx.notify();
+    } else {
+      self.update_query(|query| edit(&mut query.text), window, cx);
+    }
+  }
+
+  fn confirm(&mut self, forward: bool, window: &mut Window, cx: &mut Context<Self>) {
+    let replacement = self.replacement.clone();
+    let editing_replacement = self.editing_replacement;
+    let _ = self.editor.update(cx, |editor, cx| {
+      if editing_replacement && forward {
+        replace_next(editor, &replacement, window, cx);
+      } else {
+        select_next(editor, forward, window, cx);
+      }
+    });
+    cx.notify();
+  }
+
+  fn replace_all(&mut self, cx: &mut Context<Self>) {
+    let replacement = self.replacement.clone();
+    let _ = self
+      .editor
+      .update(cx, |editor, cx| replace_all(editor, &replacement, cx));
+    cx.notify();
+  }
+
+  fn dismiss(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+    let _ = self.editor.update(cx, |editor, cx| {
+      escape::close_topmost(editor, window, cx);
+    });
+  }
+
+  fn key_down(&mut self, event: &KeyDownEvent, window: &mut Window, cx: &mut Context<Self>) {
+    let keystroke = &event.keystroke;
+    let modifiers = &keystroke.modifiers;
+    if keystroke.key == "escape" {
+      self.dismiss(window, cx);
+    } else if keystroke.key == "enter" && modifiers.shift {
+      self.confirm(false, window, cx);
+    } else if modifiers.alt && keystroke.key == "c" {
+      self.update_query(
+        |query| query.case_sensitive = !query.case_sensitive,
+        window,
+        cx,
+      );
+    } else if modifiers.alt && keystroke.key == "w" {
+      self.update_query(|query| query.whole_word = !query.whole_word, window, cx);
+    } else if let Some(key_char) = keystroke.key_char.as_ref()
+      && !modifiers.platform
+      && !modifiers.control
+    {
+      self.edit(|text| text.push_str(key_char), window, cx);
+    } else {
+      return;
+    }
+    cx.stop_propagation();
+  }
+}
+
+impl Focusable for FindBar {
+  fn focus_handle(&self, _: &App) -> FocusHandle {
+    self.focus_handle.clone()
+  }
+}
+
+impl Render for FindBar {
+  fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+    let Some(editor) = self.editor.upgrade() else {
+      return div();
+    };
+    let (theme, (position, count)) = editor.update(cx, |editor, cx| {
+      (editor.theme.clone(), match_position(editor, cx))
+    });
+    let status = match (position, count) {
+      _ if self.query.text.is_empty() => String::new(),
+      (_, 0) => "No results".to_string(),
+      (Some(ix), count) => format!("{} of {}", ix + 1, count),
+      (None, count) => format!("{} matches", count),
+    };
+
+    let field = |text: &str, placeholder: &'static str, active: bool| {
+      div()
+        .w(px(240.0))
+        .px_1()
+        .rounded_sm()
+        .border_1()
+        .border_color(if active {
+          theme.cursor()
+        } else {
+          theme.line_number()
+        })
+        .when_else(
+          text.is_empty(),
+          |el| el.text_color(theme.line_number()).child(placeholder),
+          |el| el.child(text.to_string()),
+        )
+    };
+    let button = |id: &'static str, label: &'static str, on: bool| {
+      div()
+        .id(id)
+        .px_1()
+        .rounded_sm()
+        .cursor_pointer()
+        .when(on, |el| el.bg(theme.selection()))
+        .child(label)
+    };
+
+    div()
+      .key_context("FindBar")
+      .track_focus(&self.focus_handle)
+      // The bar's own versions of the editor keys it shadows
+      .on_action(cx.listener(|this, _: &Enter, window, cx| this.confirm(true, window, cx)))
+      .on_action(cx.listener(|this, _: &Indent, _, cx| {
+        if this.replace {
+          this.editing_replacement = !this.editing_replacement;
+          cx.notify();
+        }
+      }))
+      .on_action(cx.listener(|this, _: &Backspace, window, cx| {
+        this.edit(
+          |text| {
+            text.pop();
+          },
+          window,
+          cx,
+        )
+      }))
+      .on_action(cx.listener(|this, _: &Paste, window, cx| {
+        let text = cx
+          .read_from_clipboard()
+          .and_then(|item| item.text())
+          .unwrap_or_default();
+        let line = text.lines().next().unwrap_or_default().to_string();
+        this.edit(|text| text.push_str(&line), window, cx);
+      }))
+      .on_key_down(cx.listener(Self::key_down))
+      .w_full()
+      .flex()
+      .flex_col()
+      .gap_1()
+      .px_2()
+      .py_1()
+      .bg(theme.gutter_background())
+      .text_size(px(12.0))
+      .child(
+        div()
+          .flex()
+          .flex_row()
+          .items_center()
+          .gap_2()
+          .child(field(&self.query.text, "Find", !self.editing_replacement))
+          .child(
+            button("find-case-sensitive", "Aa", self.query.case_sensitive).on_click(cx.listener(
+              |this, _: &ClickEvent, window, cx| {
+                this.update_query(
+                  |query| query.case_sensitive = !query.case_sensitive,
+                  window,
+                  cx,
+                )
+              },
+            )),
+          )
+          .child(
+            button("find-whole-word", "W", self.query.whole_word).on_click(cx.listener(
+              |this, _: &ClickEvent, window, cx| {
+                this.update_query(|query| query.whole_word = !query.whole_word, window, cx)
+              },
+            )),
+          )
+          .child(div().text_color(theme.line_number()).child(status)),
+      )
+      .when(self.replace, |el| {
+        el.child(
+          div()
+            .flex()
+            .flex_row()
+            .items_center()
+            .gap_2()
+            .child(field(
+              &self.replacement,
+              "Replace",
+              self.editing_replacement,
+            ))
+            .child(
+              button("find-replace", "Replace", false).on_click(cx.listener(
+                |this, _: &ClickEvent, window, cx| {
+                  this.editing_replacement = true;
+                  this.confirm(true, window, cx)
+                },
+              )),
+            )
+            .child(
+              button("find-replace-all", "Replace All", false)
+                .on_click(cx.listener(|this, _: &ClickEvent, _, cx| this.replace_all(cx))),
+            ),
+        )
+      })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{actions, document::Document};
+  use gpui::{AppContext, TestAppContext, WindowHandle};
+
+  fn open_editor(cx: &mut TestAppContext, text: &str) -> WindowHandle<Editor> {
+    cx.add_window(|_, cx| {
+      let document = cx.new(|cx| Document::new(text, None, cx));
+      Editor::with_document(document, cx)
+    })
+  }
+
+  #[test]
+  fn test_next_match_wraps() {
+    let ranges = [0..3, 8..11, 16..19];
+    assert_eq!(next_match(&ranges, &(4..4), true), Some(8..11));
+    assert_eq!(next_match(&ranges, &(8..11), true), Some(16..19));
+    assert_eq!(next_match(&ranges, &(16..19), true), Some(0..3));
+    assert_eq!(next_match(&ranges, &(8..11), false), Some(0..3));
+    assert_eq!(next_match(&ranges, &(0..3), false), Some(16..19));
+    // A match starting at the cursor is the next one
+    assert_eq!(next_match(&ranges, &(8..8), true), Some(8..11));
+    assert_eq!(next_match(&[], &(0..0), true), None);
+  }
+
+  #[gpui::test]
+  fn test_matches_in_folds_are_revealed(cx: &mut TestAppContext) {
+    cx.update(|cx| {
+      cx.set_global(FindSettings {
+        skip_folded: false,
+        refold_on_close: true,
+      })
+    });
+    let window = open_editor(cx, "fn a() {\n  foo\n}\nfoo\n");
+    window
+      .update(cx, |editor, window, cx| {
+        editor.selected_range = 7..7;
+        folds::toggle_bracket_region(editor, cx);
+        editor.selected_range = 0..0;
+        open(editor, false, window, cx);
+        set_query(editor, SearchQuery::new("foo"), cx);
+        select_from(editor, 0, window, cx);
+        assert_eq!(editor.selected_range, 11..14);
+        assert!(editor.fold_ranges(cx).is_empty());
+
+        // Closed on a match outside the region, it folds again
+        select_next(editor, true, window, cx);
+        assert_eq!(editor.selected_range, 17..20);
+        close(editor, window, cx);
+        assert_eq!(editor.fold_ranges(cx), [7..16]);
+      })
+      .unwrap();
+  }
+
+  #[gpui::test]
+  fn test_matches_in_folds_are_skipped(cx: &mut TestAppContext) {
+    cx.update(|cx| {
+      cx.set_global(FindSettings {
+        skip_folded: true,
+        refold_on_close: false,
+      })
+    });
+    let window = open_editor(cx, "fn a() {\n  foo\n}\nfoo\n");
+    window
+      .update(cx, |editor, window, cx| {
+        editor.selected_range = 7..7;
+        folds::toggle_bracket_region(editor, cx);
+        editor.selected_range = 0..0;
+        open(editor, false, window, cx);
+        set_query(editor, SearchQuery::new("foo"), cx);
+        select_from(editor, 0, window, cx);
+        assert_eq!(editor.selected_range, 17..20);
+        select_next(editor, true, window, cx);
+        assert_eq!(editor.selected_range, 17..20);
+        assert_eq!(editor.fold_ranges(cx), [7..16]);
+        // Still counted and highlighted
+        assert_eq!(match_position(editor, cx), (Some(1), 2));
+      })
+      .unwrap();
+  }
+
+  #[gpui::test]
+  fn test_find_and_replace(cx: &mut TestAppContext) {
+    let window = open_editor(cx, "foo bar\nfoo baz foo");
+    window
+      .update(cx, |editor, window, cx| {
+        editor.document.update(cx, |doc, _| {
+          doc.buffer.set_group_interval(std::time::Duration::ZERO)
+        });
+        editor.selected_range = 4..4;
+        open(editor, true, window, cx);
+        set_query(editor, SearchQuery::new("foo"), cx);
+        select_from(editor, 4, window, cx);
+        // The first match from the cursor on is selected
+        assert_eq!(editor.selected_range, 8..11);
+        assert_eq!(visible_matches(editor, 1..2, cx), [8..11, 16..19]);
+        assert_eq!(match_position(editor, cx), (Some(1), 3));
+
+        select_next(editor, false, window, cx);
+        assert_eq!(editor.selected_range, 0..3);
+
+        replace_next(editor, "qux", window, cx);
+        assert_eq!(editor.document.read(cx).text(), "qux bar\nfoo baz foo");
+        assert_eq!(editor.selected_range, 8..11);
+
+        assert_eq!(replace_all(editor, "x", cx), 2);
+        assert_eq!(editor.document.read(cx).text(), "qux bar\nx baz x");
+        assert_eq!(editor.undo_description(cx), Some("Replace All"));
+        actions::undo(editor, &actions::Undo, window, cx);
+        assert_eq!(editor.document.read(cx).text(), "qux bar\nfoo baz foo");
+
+        close(editor, window, cx);
+        assert!(visible_matches(editor, 0..2, cx).is_empty());
+      })
+      .unwrap();
+  }
+}
@@ -0,0 +1,151 @@
+//! Focus mode: dimming the code outside the scope under the cursor
+//!
+//! The scopes of the document, its top-level items and the functions and
+//! type bodies nested in them, are found with tree-sitter after edits
+//! settle. While focus mode is on, lines outside the innermost scope
+//! containing the cursor are painted at reduced opacity.
+
+use std::{ops::Range, time::Duration};
+
+use buffer::Anchor;
+use gpui::{App, Context, Task};
+use syntax::scopes::find_scopes;
+
+use crate::editor::Editor;
+
+/// Delay before re-scanning the document for scopes after an edit
+const REFRESH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Opacity of the text outside the focused scope
+pub(crate) const DIMMED_OPACITY: f32 = 0.35;
+
+#[derive(Default)]
+pub(crate) struct FocusMode {
+  enabled: bool,
+  scopes: Vec<Range<Anchor>>,
+  // Document version the scopes were found in
+  scanned_version: Option<usize>,
+  refresh_task: Option<Task<()>>,
+}
+
+pub(crate) fn is_enabled(editor: &Editor) -> bool {
+  editor.focus_mode.enabled
+}
+
+pub(crate) fn toggle(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let focus_mode = &mut editor.focus_mode;
+  focus_mode.enabled = !focus_mode.enabled;
+  if focus_mode.enabled {
+    focus_mode.scanned_version = None;
+    schedule_refresh(editor, cx);
+  } else {
+    focus_mode.scopes.clear();
+    focus_mode.refresh_task = None;
+  }
+  cx.notify();
+}
+
+/// Forget the scopes of the previous document, staying in focus mode
+pub(crate) fn clear(editor: &mut Editor) {
+  editor.focus_mode.scopes.clear();
+  editor.focus_mode.scanned_version = None;
+  editor.focus_mode.refresh_task = None;
+}
+
+/// Re-scan the document for scopes once edits have settled
+pub(crate) fn schedule_refresh(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let document = editor.document.read(cx);
+  let version = document.version();
+  if !editor.focus_mode.enabled || editor.focus_mode.scanned_version == Some(version) {
+    return;
+  }
+  let Some(language) = document.language_name() else {
+    editor.focus_mode.scopes.clear();
+    editor.focus_mode.scanned_version = Some(version);
+    return;
+  };
+
+  let snapshot = document.snapshot();
+  editor.focus_mode.refresh_task = Some(cx.spawn(async move |this, cx| {
+    cx.background_executor().timer(REFRESH_DEBOUNCE).await;
+
+    let scopes = cx
+      .background_executor()
+      .spawn(async move { find_scopes(&snapshot.text(), language) })
+      .await;
+
+    let _ = this.update(cx, |editor, cx| {
+      // Byte offsets are only valid for the text they were computed from
+      if editor.document.read(cx).version() == version {
+        apply_scopes(editor, scopes, version, cx);
+      }
+    });
+  }));
+}
+
+fn apply_scopes(
+  editor: &mut Editor,
+  scopes: Vec<Range<usize>>,
+  version: usize,
+  cx: &mut Context<Editor>,
+) {
+  let document = editor.document.read(cx);
+  editor.focus_mode.scopes = scopes
+    .into_iter()
+    .map(|scope| {
+      let start = document.byte_to_char(scope.start);
+      let end = document.byte_to_char(scope.end);
+      document.anchor_range(start..end)
+    })
+    .collect();
+  editor.focus_mode.scanned_version = Some(version);
+  cx.notify();
+}
+
+/// Lines of the scope under the cursor, when focus mode dims the others
+pub(crate) fn focused_lines(editor: &Editor, cx: &App) -> Option<Range<usize>> {
+  if !editor.focus_mode.enabled {
+    return None;
+  }
+  let document = editor.document.read(cx);
+  let cursor = editor.cursor_offset();
+  let scope = editor
+    .focus_mode
+    .scopes
+    .iter()
+    .map(|scope| document.resolve_anchor_range(scope))
+    .filter(|scope| scope.start <= cursor && cursor <= scope.end)
+    .min_by_key(|scope| scope.len())?;
+  Some(document.char_to_line(scope.start)..document.char_to_line(scope.end) + 1)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[gpui::test]
+  fn test_focused_lines_follow_cursor(cx: &mut TestAppContext) {
+    let text = "fn a() {\n  1;\n}\n\nfn b() {\n  2;\n}\n";
+    let mut ctx = EditorTestContext::with_text(cx.clone(), text);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert_eq!(focused_lines(editor, cx), None);
+
+      editor.focus_mode.enabled = true;
+      apply_scopes(editor, find_scopes(text, "rust"), 0, cx);
+      editor.selected_range = 11..11;
+      assert_eq!(focused_lines(editor, cx), Some(0..3));
+      editor.selected_range = 27..27;
+      assert_eq!(focused_lines(editor, cx), Some(4..7));
+      // Between items nothing is dimmed
+      editor.selected_range = 16..16;
+      assert_eq!(focused_lines(editor, cx), None);
+
+      toggle(editor, cx);
+      assert!(!is_enabled(editor));
+      editor.selected_range = 11..11;
+      assert_eq!(focused_lines(editor, cx), None);
+    });
+  }
+}
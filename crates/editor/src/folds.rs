@@ -0,0 +1,394 @@
+//! Folding the region between a bracket and its match
+//!
+//! Brackets are matched by counting, not from the syntax tree, so a region
+//! folds the same way in data files and languages without a grammar. A fold
+//! is kept as an anchor range from the opening bracket to just past the
+//! closing one, so it follows edits around it; the lines it hides are left
+//! out of the rows of the view, and a placeholder follows the fold's first line.
+//!
+//! With a store set in `FoldSettings`, the folds of a file are kept across
+//! sessions in a file of their own, as character ranges of the text last
+//! saved: they are written when the file is saved or a fold is toggled while
+//! it has no unsaved edits, and folded again when the file is opened, as long
+//! as each still spans a bracketed region. A file with no folds stored yet
+//! can open with its top-level regions folded, such as a block of imports.
+
+use std::{
+  ops::Range,
+  path::{Path, PathBuf},
+};
+
+use buffer::Anchor;
+use gpui::{App, Context, Global};
+
+use crate::{editor::Editor, paths, wrap_map};
+
+/// Where folds are kept between sessions, and how files without any open
+#[derive(Clone, Debug, Default)]
+pub struct FoldSettings {
+  /// Directory of the fold files, none to not keep folds
+  pub store: Option<PathBuf>,
+  /// Fold the top-level bracketed regions of a file opened for the first time
+  pub fold_top_level: bool,
+}
+
+impl Global for FoldSettings {}
+
+impl FoldSettings {
+  /// Folds kept in `folds` in the editor's config directory
+  pub fn in_config_dir() -> Self {
+    Self {
+      store: paths::config_dir().map(|dir| dir.join("folds")),
+      fold_top_level: false,
+    }
+  }
+
+  fn store_path(&self, file: &Path) -> Option<PathBuf> {
+    let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+    Some(self.store.as_ref()?.join(paths::workspace_file_name(&file)))
+  }
+}
+
+/// Brackets whose regions can be folded, with their closing counterparts
+const BRACKETS: &[(char, char)] = &[('{', '}'), ('[', ']'), ('(', ')')];
+
+/// Offset of the bracket closing the one at `open`, or None if the character
+/// at `open` isn't an opening bracket or is never closed
+pub(crate) fn matching_close(chars: impl Iterator<Item = char>, open: usize) -> Option<usize> {
+  let mut chars = chars.skip(open);
+  let first = chars.next()?;
+  let &(open_ch, close_ch) = BRACKETS.iter().find(|(ch, _)| *ch == first)?;
+  let mut depth = 1usize;
+  for (ix, ch) in chars.enumerate() {
+    if ch == open_ch {
+      depth += 1;
+    } else if ch == close_ch {
+      depth -= 1;
+      if depth == 0 {
+        return Some(open + 1 + ix);
+      }
+    }
+  }
+  None
+}
+
+/// The bracketed regions not inside another, from each opening bracket to
+/// just past its match; a bracket that is never closed ends the search
+pub(crate) fn top_level_regions(chars: impl Iterator<Item = char>) -> Vec<Range<usize>> {
+  let mut regions = Vec::new();
+  // Opening bracket of the region being scanned, its closing one and depth
+  let mut open: Option<(usize, char, char, usize)> = None;
+  for (ix, ch) in chars.enumerate() {
+    match &mut open {
+      None => {
+        if let Some(&(open_ch, close_ch)) = BRACKETS.iter().find(|(open_ch, _)| *open_ch == ch) {
+          open = Some((ix, open_ch, close_ch, 1));
+        }
+      }
+      Some((start, open_ch, close_ch, depth)) => {
+        if ch == *open_ch {
+          *depth += 1;
+        } else if ch == *close_ch {
+          *depth -= 1;
+          if *depth == 0 {
+            regions.push(*start..ix + 1);
+            open = None;
+          }
+        }
+      }
+    }
+  }
+  regions
+}
+
+/// Toggle the fold of the bracketed region at the cursor, where the cursor
+/// is on or just after the opening bracket
+pub(crate) fn toggle_bracket_region(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let cursor = editor.cursor_offset();
+  let document = editor.document.read(cx);
+  let candidates = [Some(cursor), cursor.checked_sub(1)];
+  let Some(region) = candidates
+    .into_iter()
+    .flatten()
+    .find_map(|open| Some(open..matching_close(document.chars(), open)? + 1))
+  else {
+    return;
+  };
+  // A region within one line has no lines to hide
+  if document.char_to_line(region.start) == document.char_to_line(region.end) {
+    return;
+  }
+
+  let existing = editor
+    .folds
+    .iter()
+    .position(|fold| document.resolve_anchor_range(fold) == region);
+  match existing {
+    Some(ix) => {
+      editor.folds.remove(ix);
+    }
+    None => editor.folds.push(document.anchor_range(region)),
+  }
+  persist(editor, cx);
+  wrap_map::refresh(editor, cx);
+  cx.notify();
+}
+
+/// Whether the fold over `range` hides buffer line `line`; its first line
+/// and the line of its closing bracket stay shown
+fn hides_line(editor: &Editor, range: Range<usize>, line: usize, cx: &App) -> bool {
+  let document = editor.document.read(cx);
+  document.char_to_line(range.start) < line && line < document.char_to_line(range.end)
+}
+
+/// Whether a fold hides the line of `offset`
+pub(crate) fn is_folded(editor: &Editor, offset: usize, cx: &App) -> bool {
+  let document = editor.document.read(cx);
+  let line = document.char_to_line(offset);
+  editor
+    .folds
+    .iter()
+    .any(|fold| hides_line(editor, document.resolve_anchor_range(fold), line, cx))
+}
+
+/// Unfold the folds hiding the line of `offset`, leaving the others, even
+/// those around it, and return them
+pub(crate) fn reveal(
+  editor: &mut Editor,
+  offset: usize,
+  cx: &mut Context<Editor>,
+) -> Vec<Range<Anchor>> {
+  let document = editor.document.read(cx);
+  let line = document.char_to_line(offset);
+  let (revealed, kept): (Vec<_>, Vec<_>) = editor
+    .folds
+    .iter()
+    .cloned()
+    .partition(|fold| hides_line(editor, document.resolve_anchor_range(fold), line, cx));
+  if !revealed.is_empty() {
+    editor.folds = kept;
+    wrap_map::refresh(editor, cx);
+    cx.notify();
+  }
+  revealed
+}
+
+/// Fold `folds` again, but those hiding the line of `offset`
+pub(crate) fn refold(
+  editor: &mut Editor,
+  folds: Vec<Range<Anchor>>,
+  offset: usize,
+  cx: &mut Context<Editor>,
+) {
+  let document = editor.document.read(cx);
+  let line = document.char_to_line(offset);
+  let folds: Vec<_> = folds
+    .into_iter()
+    .filter(|fold| {
+      let range = document.resolve_anchor_range(fold);
+      range.start < range.end && !hides_line(editor, range, line, cx)
+    })
+    .collect();
+  if folds.is_empty() {
+    return;
+  }
+  editor.folds.extend(folds);
+  wrap_map::refresh(editor, cx);
+  cx.notify();
+}
+
+/// Keep the folds of the open file for the next session, unless the text
+/// differs from the file
+pub(crate) fn persist(editor: &Editor, cx: &App) {
+  let document = editor.document.read(cx);
+  if editor.preview.is_some() || document.is_dirty() {
+    return;
+  }
+  let Some(store_path) = document
+    .path()
+    .zip(cx.try_global::<FoldSettings>())
+    .and_then(|(path, settings)| settings.store_path(path))
+  else {
+    return;
+  };
+  let contents: String = editor
+    .fold_ranges(cx)
+    .into_iter()
+    .map(|range| format!("{} {}\n", range.start, range.end))
+    .collect();
+  if let Err(err) = store_path
+    .parent()
+    .map_or(Ok(()), std::fs::create_dir_all)
+    .and_then(|_| std::fs::write(&store_path, contents))
+  {
+    eprintln!("Failed to save folds to {}: {}", store_path.display(), err);
+  }
+}
+
+/// Fold what was folded when the file just opened was last saved, or its
+/// top-level regions if nothing was kept for it and `fold_top_level` is set
+pub(crate) fn restore(editor: &mut Editor, cx: &App) {
+  let document = editor.document.read(cx);
+  let (Some(path), Some(settings)) = (document.path(), cx.try_global::<FoldSettings>()) else {
+    return;
+  };
+  let regions = match settings
+    .store_path(path)
+    .and_then(|store_path| std::fs::read_to_string(store_path).ok())
+  {
+    Some(contents) => contents
+      .lines()
+      .filter_map(|line| {
+        let (start, end) = line.split_once(' ')?;
+        Some(start.parse().ok()?..end.parse().ok()?)
+      })
+      // The file may have changed since, so only regions still bracketed fold
+      .filter(|region: &Range<usize>| {
+        region.end > 0 && matching_close(document.chars(), region.start) == Some(region.end - 1)
+      })
+      .collect(),
+    None if settings.fold_top_level => top_level_regions(document.chars()),
+    None => Vec::new(),
+  };
+  editor.folds = regions
+    .into_iter()
+    // A region within one line has no lines to hide
+    .filter(|region| document.char_to_line(region.start) != document.char_to_line(region.end))
+    .map(|region| document.anchor_range(region))
+    .collect();
+}
+
+impl Editor {
+  /// Character ranges of the folded regions, in the order they were folded
+  pub fn fold_ranges(&self, cx: &App) -> Vec<Range<usize>> {
+    let document = self.document.read(cx);
+    self
+      .folds
+      .iter()
+      .map(|fold| document.resolve_anchor_range(fold))
+      .filter(|range| range.start < range.end)
+      .collect()
+  }
+
+  /// Folds as buffer line ranges for `DisplayMap::new`, keeping the line of
+  /// the closing bracket visible
+  pub fn folded_lines(&self, cx: &App) -> Vec<Range<usize>> {
+    let document = self.document.read(cx);
+    self
+      .fold_ranges(cx)
+      .into_iter()
+      .map(|range| document.char_to_line(range.start)..document.char_to_line(range.end))
+      .filter(|lines| lines.start < lines.end)
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[test]
+  fn test_matching_close() {
+    let text = "a { b [c] { d } } (";
+    assert_eq!(matching_close(text.chars(), 2), Some(16));
+    assert_eq!(matching_close(text.chars(), 6), Some(8));
+    assert_eq!(matching_close(text.chars(), 10), Some(14));
+    // Not a bracket, and a bracket that is never closed
+    assert_eq!(matching_close(text.chars(), 0), None);
+    assert_eq!(matching_close(text.chars(), 18), None);
+    assert_eq!(matching_close(text.chars(), 40), None);
+  }
+
+  #[test]
+  fn test_top_level_regions() {
+    let text = "use a::{\n  b,\n};\nfn f() { [1] }\n(";
+    assert_eq!(top_level_regions(text.chars()), [7..15, 21..23, 24..31]);
+  }
+
+  fn open(path: &std::path::Path, cx: &TestAppContext) -> EditorTestContext {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.open_file(path.to_path_buf(), cx).unwrap()
+    });
+    ctx
+  }
+
+  #[gpui::test]
+  fn test_folds_are_kept_across_sessions(cx: &mut TestAppContext) {
+    let dir = tempfile::tempdir().unwrap();
+    cx.update(|cx| {
+      cx.set_global(FoldSettings {
+        store: Some(dir.path().join("folds")),
+        fold_top_level: true,
+      })
+    });
+    let path = dir.path().join("data.json");
+    std::fs::write(&path, "{\n  \"a\": [\n    1\n  ]\n}\n").unwrap();
+
+    // Opened for the first time, the top-level region is folded
+    let mut ctx = open(&path, cx);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert_eq!(editor.fold_ranges(cx), [0..22]);
+      editor.selected_range = 0..0;
+      toggle_bracket_region(editor, cx);
+      editor.selected_range = 9..9;
+      toggle_bracket_region(editor, cx);
+    });
+    let mut ctx = open(&path, cx);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert_eq!(editor.fold_ranges(cx), [9..20]);
+      // Folds are kept with the text once it is saved
+      editor.selected_range = 0..0;
+      editor.insert_text("\n", "Typing", cx);
+      crate::files::save_to(editor, path.clone(), cx);
+    });
+    ctx.cx.run_until_parked();
+    let mut ctx = open(&path, cx);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert_eq!(editor.fold_ranges(cx), [10..21]);
+    });
+
+    // A fold no longer spanning a bracketed region is dropped
+    std::fs::write(&path, "[\n]\n").unwrap();
+    let mut ctx = open(&path, cx);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert!(editor.fold_ranges(cx).is_empty());
+    });
+  }
+
+  #[gpui::test]
+  fn test_toggle_bracket_region(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "{\n  \"a\": [\n    1\n  ]\n}\nnext");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      // Just after the opening bracket
+      editor.selected_range = 1..1;
+      toggle_bracket_region(editor, cx);
+      assert_eq!(editor.fold_ranges(cx), [0..22]);
+      assert_eq!(editor.folded_lines(cx), [0..4]);
+
+      // On the inner bracket
+      editor.selected_range = 9..9;
+      toggle_bracket_region(editor, cx);
+      assert_eq!(editor.folded_lines(cx), [0..4, 1..3]);
+
+      // Folding a region again unfolds it
+      editor.selected_range = 0..0;
+      toggle_bracket_region(editor, cx);
+      assert_eq!(editor.folded_lines(cx), [1..3]);
+
+      // Nothing to fold away from brackets
+      editor.selected_range = 24..24;
+      toggle_bracket_region(editor, cx);
+      assert_eq!(editor.folded_lines(cx), [1..3]);
+    });
+
+    // Folds follow edits before them
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.selected_range = 0..0;
+      crate::unicode_input::insert(editor, "\n", cx);
+      assert_eq!(editor.folded_lines(cx), [2..4]);
+    });
+  }
+}
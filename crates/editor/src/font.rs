@@ -0,0 +1,80 @@
+//! Per-editor font configuration applied to the text style used for shaping
+
+use std::sync::Arc;
+
+use gpui::{FontFallbacks, FontFeatures, SharedString, TextStyle};
+
+/// OpenType features that produce programming ligatures
+const LIGATURE_FEATURES: &[&str] = &["calt", "liga"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EditorFont {
+  /// Font family, the window's font when unset
+  pub family: Option<SharedString>,
+  /// Families tried in order for glyphs missing from `family`
+  pub fallbacks: Vec<String>,
+  pub ligatures: bool,
+}
+
+impl Default for EditorFont {
+  fn default() -> Self {
+    Self {
+      family: None,
+      fallbacks: Vec::new(),
+      ligatures: true,
+    }
+  }
+}
+
+impl EditorFont {
+  pub fn apply(&self, style: &mut TextStyle) {
+    if let Some(family) = &self.family {
+      style.font_family = family.clone();
+    }
+    if !self.fallbacks.is_empty() {
+      style.font_fallbacks = Some(FontFallbacks::from_fonts(self.fallbacks.clone()));
+    }
+    if !self.ligatures {
+      let mut features: Vec<(String, u32)> = style
+        .font_features
+        .0
+        .iter()
+        .filter(|(tag, _)| !LIGATURE_FEATURES.contains(&tag.as_str()))
+        .cloned()
+        .collect();
+      features.extend(LIGATURE_FEATURES.iter().map(|tag| (tag.to_string(), 0)));
+      style.font_features = FontFeatures(Arc::new(features));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_default_font_keeps_style() {
+    let mut style = TextStyle::default();
+    let family = style.font_family.clone();
+
+    EditorFont::default().apply(&mut style);
+    assert_eq!(style.font_family, family);
+    assert!(style.font_fallbacks.is_none());
+  }
+
+  #[test]
+  fn test_apply_family_and_disable_ligatures() {
+    let mut style = TextStyle::default();
+    let font = EditorFont {
+      family: Some("Fira Code".into()),
+      fallbacks: vec!["Menlo".to_string()],
+      ligatures: false,
+    };
+
+    font.apply(&mut style);
+    assert_eq!(style.font_family, SharedString::from("Fira Code"));
+    assert!(style.font_fallbacks.is_some());
+    assert!(style.font_features.0.contains(&("calt".to_string(), 0)));
+    assert!(style.font_features.0.contains(&("liga".to_string(), 0)));
+  }
+}
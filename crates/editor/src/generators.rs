@@ -0,0 +1,178 @@
+//! Generated text inserted at the cursor
+//!
+//! Random UUIDs, the current time and placeholder text, each replacing the
+//! selection through `Editor::insert_text`. Timestamps are written in the
+//! `TimestampFormat` set as a global, ISO 8601 in UTC by default.
+
+use std::{
+  collections::hash_map::RandomState,
+  hash::BuildHasher,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use gpui::{Context, Global};
+
+use crate::editor::Editor;
+
+const LOREM_IPSUM: &str = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do \
+  eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis \
+  nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat. Duis aute irure \
+  dolor in reprehenderit in voluptate velit esse cillum dolore eu fugiat nulla pariatur. \
+  Excepteur sint occaecat cupidatat non proident, sunt in culpa qui officia deserunt mollit anim \
+  id est laborum.";
+
+/// How InsertTimestamp writes the current time
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+  /// `2024-05-01T09:30:00Z`
+  #[default]
+  Iso8601,
+  /// `2024-05-01`
+  Date,
+  /// Seconds since the Unix epoch
+  Unix,
+}
+
+impl Global for TimestampFormat {}
+
+/// 16 bytes from the standard library's randomly seeded hasher keys; unique
+/// enough for identifiers, not for secrets
+fn random_bytes() -> [u8; 16] {
+  let nanos = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |elapsed| elapsed.as_nanos());
+  let high = RandomState::new().hash_one(nanos);
+  let low = RandomState::new().hash_one(nanos);
+  let mut bytes = [0; 16];
+  bytes[..8].copy_from_slice(&high.to_le_bytes());
+  bytes[8..].copy_from_slice(&low.to_le_bytes());
+  bytes
+}
+
+/// Version 4 UUID made from `bytes`
+pub(crate) fn uuid_v4(mut bytes: [u8; 16]) -> String {
+  bytes[6] = (bytes[6] & 0x0f) | 0x40;
+  bytes[8] = (bytes[8] & 0x3f) | 0x80;
+  let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+  format!(
+    "{}-{}-{}-{}-{}",
+    &hex[..8],
+    &hex[8..12],
+    &hex[12..16],
+    &hex[16..20],
+    &hex[20..]
+  )
+}
+
+/// Year, month and day of the day `days` after 1970-01-01
+fn civil_date(days: i64) -> (i64, u32, u32) {
+  // Howard Hinnant's days_from_civil, inverted
+  let days = days + 719_468;
+  let era = days.div_euclid(146_097);
+  let day_of_era = days.rem_euclid(146_097);
+  let year_of_era =
+    (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let month_index = (5 * day_of_year + 2) / 153;
+  let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+  let month = if month_index < 10 {
+    month_index + 3
+  } else {
+    month_index - 9
+  } as u32;
+  let year = year_of_era + era * 400 + i64::from(month <= 2);
+  (year, month, day)
+}
+
+/// `seconds` since the Unix epoch written in `format`, in UTC
+pub(crate) fn format_timestamp(seconds: u64, format: TimestampFormat) -> String {
+  let (year, month, day) = civil_date((seconds / 86_400) as i64);
+  let time = seconds % 86_400;
+  match format {
+    TimestampFormat::Iso8601 => format!(
+      "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+      year,
+      month,
+      day,
+      time / 3600,
+      time / 60 % 60,
+      time % 60
+    ),
+    TimestampFormat::Date => format!("{:04}-{:02}-{:02}", year, month, day),
+    TimestampFormat::Unix => seconds.to_string(),
+  }
+}
+
+pub(crate) fn insert_uuid(editor: &mut Editor, cx: &mut Context<Editor>) {
+  editor.insert_text(&uuid_v4(random_bytes()), "Insert UUID", cx);
+}
+
+pub(crate) fn insert_timestamp(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let format = cx
+    .try_global::<TimestampFormat>()
+    .copied()
+    .unwrap_or_default();
+  let seconds = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |elapsed| elapsed.as_secs());
+  editor.insert_text(&format_timestamp(seconds, format), "Insert Timestamp", cx);
+}
+
+pub(crate) fn insert_lorem_ipsum(editor: &mut Editor, cx: &mut Context<Editor>) {
+  editor.insert_text(LOREM_IPSUM, "Insert Lorem Ipsum", cx);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[test]
+  fn test_uuid_v4() {
+    let uuid = uuid_v4([0xff; 16]);
+    assert_eq!(uuid, "ffffffff-ffff-4fff-bfff-ffffffffffff");
+    assert_eq!(uuid_v4([0; 16]), "00000000-0000-4000-8000-000000000000");
+    assert_ne!(random_bytes(), random_bytes());
+  }
+
+  #[test]
+  fn test_format_timestamp() {
+    // 2024-02-29 13:05:09 UTC
+    let seconds = 1_709_211_909;
+    assert_eq!(
+      format_timestamp(seconds, TimestampFormat::Iso8601),
+      "2024-02-29T13:05:09Z"
+    );
+    assert_eq!(
+      format_timestamp(seconds, TimestampFormat::Date),
+      "2024-02-29"
+    );
+    assert_eq!(
+      format_timestamp(seconds, TimestampFormat::Unix),
+      "1709211909"
+    );
+    assert_eq!(
+      format_timestamp(0, TimestampFormat::Iso8601),
+      "1970-01-01T00:00:00Z"
+    );
+    assert_eq!(
+      format_timestamp(951_782_400, TimestampFormat::Date),
+      "2000-02-29"
+    );
+  }
+
+  #[gpui::test]
+  fn test_insert_timestamp_format(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "at ");
+    ctx.cx.update(|cx| cx.set_global(TimestampFormat::Unix));
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.selected_range = 3..3;
+      insert_timestamp(editor, cx);
+      assert_eq!(editor.undo_description(cx), Some("Insert Timestamp"));
+    });
+    let text = ctx.text();
+    assert!(text[3..].parse::<u64>().is_ok(), "{}", text);
+    assert_eq!(ctx.cursor_offset(), text.len());
+  }
+}
@@ -0,0 +1,348 @@
+//! Git status of the files in the workspace roots
+//!
+//! A background watcher runs `git status` for the repository of each
+//! workspace root every few seconds and keeps the result in a global, so
+//! file and folder names can be colored by status and folders can show the
+//! most pressing status below them with a count. The checked out commit is
+//! tracked as well, for word diff to re-read the committed text after a
+//! commit or checkout. Roots in restricted workspaces are left out, since
+//! running git there can run programs named by the repository's config.
+
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+  process::Command,
+  time::Duration,
+};
+
+use gpui::{App, Global, Hsla};
+
+use crate::{trust, workspace::Workspace};
+
+/// Delay between two runs of `git status`
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Status of a changed file, ordered by how much attention it needs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GitFileStatus {
+  Untracked,
+  Added,
+  Modified,
+  Conflicted,
+}
+
+impl GitFileStatus {
+  pub fn label(self) -> &'static str {
+    match self {
+      GitFileStatus::Untracked => "Untracked",
+      GitFileStatus::Added => "Added",
+      GitFileStatus::Modified => "Modified",
+      GitFileStatus::Conflicted => "Conflicted",
+    }
+  }
+
+  /// Color of the names of files with this status
+  pub fn color(self) -> Hsla {
+    let (h, s, l) = match self {
+      GitFileStatus::Untracked => (160.0, 0.45, 0.5),
+      GitFileStatus::Added => (120.0, 0.5, 0.45),
+      GitFileStatus::Modified => (40.0, 0.75, 0.5),
+      GitFileStatus::Conflicted => (0.0, 0.7, 0.5),
+    };
+    Hsla {
+      h: h / 360.0,
+      s,
+      l,
+      a: 1.0,
+    }
+  }
+}
+
+/// What a folder shows for the changed files below it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FolderBadge {
+  /// The status needing the most attention below the folder
+  pub status: GitFileStatus,
+  pub count: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RepositoryStatus {
+  /// Top-level directory of the working tree
+  pub root: PathBuf,
+  /// Checked out commit, none before the first commit
+  pub head: Option<String>,
+  /// Changed files, relative to the root
+  files: HashMap<PathBuf, GitFileStatus>,
+}
+
+impl RepositoryStatus {
+  /// Status from the output of `git status --porcelain=v2 --branch -z`
+  pub fn parse(root: PathBuf, output: &str) -> Self {
+    let mut status = Self {
+      root,
+      ..Default::default()
+    };
+    let mut records = output.split('\0');
+    while let Some(record) = records.next() {
+      let mut fields = record.splitn(2, ' ');
+      let (Some(kind), Some(rest)) = (fields.next(), fields.next()) else {
+        continue;
+      };
+      let (path, file_status) = match kind {
+        "#" => {
+          if let Some(oid) = rest.strip_prefix("branch.oid ") {
+            status.head = (oid != "(initial)").then(|| oid.to_string());
+          }
+          continue;
+        }
+        "?" => (rest, GitFileStatus::Untracked),
+        "1" | "2" | "u" => {
+          // Fields before the path: XY, submodule state, modes and object names
+          let skipped = match kind {
+            "1" => 7,
+            "2" => 8,
+            _ => 9,
+          };
+          let Some(path) = rest.splitn(skipped + 1, ' ').nth(skipped) else {
+            continue;
+          };
+          if kind == "2" {
+            // A rename or copy is followed by the path it came from
+            records.next();
+          }
+          let file_status = if kind == "u" {
+            GitFileStatus::Conflicted
+          } else if rest.starts_with('A') {
+            GitFileStatus::Added
+          } else {
+            GitFileStatus::Modified
+          };
+          (path, file_status)
+        }
+        _ => continue,
+      };
+      status.files.insert(PathBuf::from(path), file_status);
+    }
+    status
+  }
+
+  fn relative<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+    path.strip_prefix(&self.root).ok()
+  }
+
+  pub fn file_status(&self, path: &Path) -> Option<GitFileStatus> {
+    self.files.get(self.relative(path)?).copied()
+  }
+
+  /// Badge of the folder at `dir`, none when nothing below it changed
+  pub fn folder_status(&self, dir: &Path) -> Option<FolderBadge> {
+    let dir = self.relative(dir)?;
+    let mut badge: Option<FolderBadge> = None;
+    for (_, &status) in self.files.iter().filter(|(path, _)| path.starts_with(dir)) {
+      let badge = badge.get_or_insert(FolderBadge { status, count: 0 });
+      badge.status = badge.status.max(status);
+      badge.count += 1;
+    }
+    badge
+  }
+}
+
+/// Status of the repositories of the workspace roots
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GitStatuses {
+  repositories: Vec<RepositoryStatus>,
+}
+
+impl Global for GitStatuses {}
+
+impl GitStatuses {
+  pub fn new(repositories: Vec<RepositoryStatus>) -> Self {
+    Self { repositories }
+  }
+
+  /// The innermost repository containing `path`
+  pub fn repository_for(&self, path: &Path) -> Option<&RepositoryStatus> {
+    self
+      .repositories
+      .iter()
+      .filter(|repository| path.starts_with(&repository.root))
+      .max_by_key(|repository| repository.root.components().count())
+  }
+
+  pub fn file_status(&self, path: &Path) -> Option<GitFileStatus> {
+    self.repository_for(path)?.file_status(path)
+  }
+
+  pub fn folder_status(&self, dir: &Path) -> Option<FolderBadge> {
+    self.repository_for(dir)?.folder_status(dir)
+  }
+}
+
+/// Status of the repository containing `dir`, none outside of one
+fn read_status(dir: &Path) -> Result<Option<RepositoryStatus>, String> {
+  let git = |args: &[&str], dir: &Path| {
+    Command::new("git")
+      .arg("-C")
+      .arg(dir)
+      .args(args)
+      .output()
+      .map_err(|err| format!("git: {}", err))
+  };
+  let toplevel = git(&["rev-parse", "--show-toplevel"], dir)?;
+  if !toplevel.status.success() {
+    return Ok(None);
+  }
+  let root = PathBuf::from(String::from_utf8_lossy(&toplevel.stdout).trim_end());
+  let output = git(
+    &[
+      "status",
+      "--porcelain=v2",
+      "--branch",
+      "-z",
+      "--untracked-files=all",
+    ],
+    &root,
+  )?;
+  if !output.status.success() {
+    return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+  }
+  Ok(Some(RepositoryStatus::parse(
+    root,
+    &String::from_utf8_lossy(&output.stdout),
+  )))
+}
+
+fn read_statuses(roots: &[PathBuf]) -> GitStatuses {
+  let mut repositories: Vec<RepositoryStatus> = Vec::new();
+  for root in roots {
+    match read_status(root) {
+      Ok(Some(status)) => {
+        // Roots in the same repository share its status
+        if !repositories.iter().any(|repo| repo.root == status.root) {
+          repositories.push(status);
+        }
+      }
+      Ok(None) => {}
+      Err(err) => eprintln!(
+        "Failed to read the git status of {}: {}",
+        root.display(),
+        err
+      ),
+    }
+  }
+  GitStatuses::new(repositories)
+}
+
+/// Keep the `GitStatuses` global up to date with the workspace roots
+pub fn watch_git_status(cx: &mut App) {
+  cx.spawn(async move |cx| {
+    loop {
+      let Ok(roots) = cx.update(|cx| {
+        cx.try_global::<Workspace>()
+          .map(|workspace| {
+            workspace
+              .roots()
+              .iter()
+              .map(|root| root.path.clone())
+              .filter(|root| trust::is_trusted(root, cx))
+              .collect::<Vec<_>>()
+          })
+          .unwrap_or_default()
+      }) else {
+        break;
+      };
+
+      let statuses = cx
+        .background_executor()
+        .spawn(async move { read_statuses(&roots) })
+        .await;
+
+      let updated = cx.update(|cx| {
+        // Observers only hear about actual changes
+        if cx.try_global::<GitStatuses>() != Some(&statuses) {
+          cx.set_global(statuses);
+          cx.refresh_windows();
+        }
+      });
+      if updated.is_err() {
+        break;
+      }
+      cx.background_executor().timer(POLL_INTERVAL).await;
+    }
+  })
+  .detach();
+}
+
+/// Commit checked out in the repository of `path`
+pub(crate) fn head_commit(path: &Path, cx: &App) -> Option<String> {
+  let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+  cx.try_global::<GitStatuses>()?
+    .repository_for(&path)?
+    .head
+    .clone()
+}
+
+/// Status of the file at `path`, none when it is unchanged or not in a
+/// repository
+pub(crate) fn file_status(path: &Path, cx: &App) -> Option<GitFileStatus> {
+  let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+  cx.try_global::<GitStatuses>()?.file_status(&path)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const OUTPUT: &str = concat!(
+    "# branch.oid 1f2e3d\0",
+    "# branch.head main\0",
+    "1 .M N... 100644 100644 100644 aaa aaa src/lib.rs\0",
+    "1 AM N... 000000 100644 100644 000 bbb src/new file.rs\0",
+    "2 R. N... 100644 100644 100644 ccc ccc R100 src/moved.rs\0src/old.rs\0",
+    "u UU N... 100644 100644 100644 100644 ddd eee fff docs/guide.md\0",
+    "? notes.txt\0",
+  );
+
+  #[test]
+  fn test_parse() {
+    let status = RepositoryStatus::parse(PathBuf::from("/repo"), OUTPUT);
+    assert_eq!(status.head.as_deref(), Some("1f2e3d"));
+    let file = |path: &str| status.file_status(&Path::new("/repo").join(path));
+    assert_eq!(file("src/lib.rs"), Some(GitFileStatus::Modified));
+    assert_eq!(file("src/new file.rs"), Some(GitFileStatus::Added));
+    assert_eq!(file("src/moved.rs"), Some(GitFileStatus::Modified));
+    assert_eq!(file("src/old.rs"), None);
+    assert_eq!(file("docs/guide.md"), Some(GitFileStatus::Conflicted));
+    assert_eq!(file("notes.txt"), Some(GitFileStatus::Untracked));
+    assert_eq!(file("Cargo.toml"), None);
+
+    let initial = RepositoryStatus::parse(PathBuf::from("/repo"), "# branch.oid (initial)\0");
+    assert_eq!(initial.head, None);
+  }
+
+  #[test]
+  fn test_folder_badges() {
+    let statuses = GitStatuses::new(vec![
+      RepositoryStatus::parse(PathBuf::from("/repo"), OUTPUT),
+      RepositoryStatus::parse(PathBuf::from("/repo/vendor/dep"), "? a.rs\0"),
+    ]);
+    assert_eq!(
+      statuses.folder_status(Path::new("/repo/src")),
+      Some(FolderBadge {
+        status: GitFileStatus::Modified,
+        count: 3
+      })
+    );
+    assert_eq!(
+      statuses.folder_status(Path::new("/repo")).unwrap().status,
+      GitFileStatus::Conflicted
+    );
+    assert_eq!(statuses.folder_status(Path::new("/repo/target")), None);
+    assert_eq!(
+      statuses.file_status(Path::new("/repo/vendor/dep/a.rs")),
+      Some(GitFileStatus::Untracked)
+    );
+    assert_eq!(statuses.folder_status(Path::new("/elsewhere")), None);
+  }
+}
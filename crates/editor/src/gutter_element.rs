@@ -1,10 +1,91 @@
 use gpui::{
-  App, Bounds, ElementId, Entity, GlobalElementId, InspectorElementId, LayoutId, Pixels, Style,
-  TextAlign, TextRun, Window, point, prelude::*, px, relative,
+  App, Bounds, DispatchPhase, ElementId, Entity, GlobalElementId, Hsla, InspectorElementId,
+  LayoutId, MouseButton, MouseDownEvent, Pixels, Point, SharedString, Style, TextAlign, TextRun,
+  TextStyle, Window, fill, point, prelude::*, px, red, relative,
 };
 use std::ops::Range;
 
-use crate::editor::Editor;
+use crate::{
+  dap,
+  decorations::GutterIcon,
+  editor::Editor,
+  wrap_map::{self, RowLayout},
+};
+
+// Left padding of decoration icons in their lane
+const GUTTER_ICON_PADDING: f32 = 3.0;
+// Glyph marking a line with a breakpoint
+const BREAKPOINT_GLYPH: &str = "●";
+// Right padding of line numbers in their lane
+const LINE_NUMBER_PADDING: f32 = 8.0;
+
+/// A column of the gutter with its own contents and clicks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GutterLane {
+  /// Breakpoints and bookmarks
+  Breakpoints,
+  /// Decoration icons: test run buttons, diagnostics and git markers
+  Markers,
+  LineNumbers,
+  /// Fold chevrons
+  Folds,
+}
+
+/// Lanes of the gutter from left to right, each with its width
+#[derive(Clone, Debug, PartialEq)]
+pub struct GutterLayout {
+  lanes: Vec<(GutterLane, Pixels)>,
+}
+
+impl Default for GutterLayout {
+  fn default() -> Self {
+    Self::new([
+      (GutterLane::Breakpoints, px(16.0)),
+      (GutterLane::Markers, px(16.0)),
+      (GutterLane::LineNumbers, px(38.0)),
+    ])
+  }
+}
+
+impl GutterLayout {
+  /// Lanes left to right; a lane that isn't listed isn't shown
+  pub fn new(lanes: impl IntoIterator<Item = (GutterLane, Pixels)>) -> Self {
+    Self {
+      lanes: lanes.into_iter().collect(),
+    }
+  }
+
+  pub fn width(&self) -> Pixels {
+    self
+      .lanes
+      .iter()
+      .fold(px(0.), |total, (_, width)| total + *width)
+  }
+
+  /// Horizontal extent of `lane`, from the gutter's left edge
+  pub fn lane_range(&self, lane: GutterLane) -> Option<Range<Pixels>> {
+    let mut left = px(0.);
+    for (candidate, width) in &self.lanes {
+      if *candidate == lane {
+        return Some(left..left + *width);
+      }
+      left += *width;
+    }
+    None
+  }
+
+  /// Lane under `x`, measured from the gutter's left edge
+  pub fn lane_at(&self, x: Pixels) -> Option<GutterLane> {
+    let mut left = px(0.);
+    for (lane, width) in &self.lanes {
+      if x >= left && x < left + *width {
+        return Some(*lane);
+      }
+      left += *width;
+    }
+    None
+  }
+}
 
 pub struct GutterElement {
   editor: Entity<Editor>,
@@ -12,9 +93,13 @@ pub struct GutterElement {
 
 pub struct GutterPrepaintState {
   line_numbers: Vec<(usize, String)>,
-  viewport: Range<usize>,
+  icons: Vec<(usize, GutterIcon)>,
+  breakpoints: Vec<usize>,
+  tints: Vec<(usize, Hsla)>,
+  rows: RowLayout,
   line_height: Pixels,
-  line_number_color: gpui::Hsla,
+  line_number_color: Hsla,
+  layout: GutterLayout,
 }
 
 impl GutterElement {
@@ -66,35 +151,80 @@ impl Element for GutterElement {
     window: &mut Window,
     cx: &mut App,
   ) -> Self::PrepaintState {
-    let (viewport, line_numbers, line_height, line_number_color) = {
+    self
+      .editor
+      .update(cx, |editor, cx| wrap_map::refresh(editor, cx));
+    let (viewport, rows, line_numbers, icons, tints, line_height, line_number_color, layout) = {
       let editor = self.editor.read(cx);
       let document = editor.document().read(cx);
-      let line_height = window.line_height();
+      let line_height = editor.line_height_px(window, cx);
       let scroll_offset = editor.scroll_offset_y;
 
       // Calculate viewport (same logic as EditorElement)
-      let visible_line_count = ((bounds.size.height / line_height).ceil() as usize).max(1);
-      let start_line = (scroll_offset.floor() as usize).min(document.len_lines().saturating_sub(1));
-      let end_line = (start_line + visible_line_count).min(document.len_lines());
-      let viewport = start_line..end_line;
+      let total_rows = wrap_map::row_count(editor, cx);
+      let visible_row_count = ((bounds.size.height / line_height).ceil() as usize).max(1);
+      let start_row = (scroll_offset.floor() as usize).min(total_rows.saturating_sub(1));
+      let end_row = (start_row + visible_row_count).min(total_rows);
+      let rows = wrap_map::layout(editor, start_row..end_row, cx);
+      let viewport = rows.lines();
 
       // Format line numbers for visible lines
       let mut line_numbers = Vec::new();
-      for line_idx in viewport.clone() {
+      for line_idx in viewport.clone().filter(|&line| rows.is_shown(line)) {
         let line_number = format!("{}", line_idx + 1);
         line_numbers.push((line_idx, line_number));
       }
 
+      // Decoration icons are painted next to the first decorated line,
+      // tints behind every decorated line
+      let mut icons = Vec::new();
+      let mut tints = Vec::new();
+      for decoration in editor
+        .decorations
+        .resolve_in_lines(viewport.clone(), document)
+      {
+        if let Some(icon) = &decoration.style.gutter_icon
+          && viewport.contains(&decoration.lines.start)
+        {
+          icons.push((decoration.lines.start, icon.clone()));
+        }
+        if let Some(color) = decoration.style.gutter_background {
+          for line_idx in decoration.lines.clone() {
+            if viewport.contains(&line_idx) {
+              tints.push((line_idx, color));
+            }
+          }
+        }
+      }
+
       let line_number_color = editor.theme.line_number();
 
-      (viewport, line_numbers, line_height, line_number_color)
+      (
+        viewport,
+        rows,
+        line_numbers,
+        icons,
+        tints,
+        line_height,
+        line_number_color,
+        editor.gutter_layout.clone(),
+      )
     };
 
+    let breakpoints = dap::breakpoint_lines(self.editor.read(cx), cx)
+      .into_iter()
+      .filter(|line_idx| viewport.contains(line_idx))
+      .collect();
+
     GutterPrepaintState {
       line_numbers,
-      viewport,
+      icons,
+      breakpoints,
+      tints,
+      rows,
       line_height,
       line_number_color,
+      layout,
     }
   }
 
@@ -108,43 +238,188 @@ impl Element for GutterElement {
     window: &mut Window,
     cx: &mut App,
   ) {
-    let text_style = window.text_style();
+    let text_style = self.editor.read(cx).text_style(window, cx);
     let font_size = text_style.font_size.to_pixels(window.rem_size());
     let text_color = prepaint.line_number_color;
 
-    for (line_idx, line_number) in &prepaint.line_numbers {
-      let y = bounds.top() + prepaint.line_height * (*line_idx - prepaint.viewport.start) as f32;
-
-      let runs = vec![TextRun {
-        len: line_number.len(),
-        font: text_style.font(),
-        color: text_color,
-        background_color: None,
-        underline: None,
-        strikethrough: None,
-      }];
-
-      let shaped =
-        window
-          .text_system()
-          .shape_line(line_number.clone().into(), font_size, &runs, None);
-
-      // Align to the right with padding
-      let text_width = shaped.width;
-      let right_padding = px(8.0);
-      let x = bounds.right() - text_width - right_padding;
-
-      let line_origin = point(x, y);
-      shaped
-        .paint(
-          line_origin,
+    window.on_mouse_event({
+      let editor = self.editor.clone();
+      let rows = prepaint.rows.clone();
+      let line_height = prepaint.line_height;
+      let layout = prepaint.layout.clone();
+      move |event: &MouseDownEvent, phase, window, cx| {
+        if phase == DispatchPhase::Bubble
+          && event.button == MouseButton::Left
+          && bounds.contains(&event.position)
+          && let Some(lane) = layout.lane_at(event.position.x - bounds.left())
+        {
+          let row = ((event.position.y - bounds.top()) / line_height).floor() as usize;
+          let Some((line, _)) = rows.at_row(rows.rows.start + row) else {
+            return;
+          };
+          editor.update(cx, |editor, cx| {
+            editor.gutter_clicked(line, lane, window, cx);
+          });
+        }
+      }
+    });
+
+    for (line_idx, color) in &prepaint.tints {
+      for (row, _) in prepaint.rows.line_rows(*line_idx) {
+        let y = prepaint
+          .rows
+          .row_top(row, bounds.top(), prepaint.line_height);
+        window.paint_quad(fill(
+          Bounds::from_corners(
+            point(bounds.left(), y),
+            point(bounds.right(), y + prepaint.line_height),
+          ),
+          *color,
+        ));
+      }
+    }
+
+    // Line numbers and icons go on the first row of their line
+    let first_row_top = |line_idx: usize| {
+      let (row, _) = prepaint.rows.row_of(line_idx, 0)?;
+      prepaint.rows.rows.contains(&row).then(|| {
+        prepaint
+          .rows
+          .row_top(row, bounds.top(), prepaint.line_height)
+      })
+    };
+
+    let lane_range = |lane| {
+      prepaint
+        .layout
+        .lane_range(lane)
+        .map(|range| bounds.left() + range.start..bounds.left() + range.end)
+    };
+
+    if let Some(numbers_lane) = lane_range(GutterLane::LineNumbers) {
+      for (line_idx, line_number) in &prepaint.line_numbers {
+        let Some(y) = first_row_top(*line_idx) else {
+          continue;
+        };
+
+        let runs = vec![TextRun {
+          len: line_number.len(),
+          font: text_style.font(),
+          color: text_color,
+          background_color: None,
+          underline: None,
+          strikethrough: None,
+        }];
+
+        let shaped =
+          window
+            .text_system()
+            .shape_line(line_number.clone().into(), font_size, &runs, None);
+
+        // Align to the right of the lane with padding
+        let x = numbers_lane.end - shaped.width - px(LINE_NUMBER_PADDING);
+
+        let line_origin = point(x, y);
+        shaped
+          .paint(
+            line_origin,
+            prepaint.line_height,
+            TextAlign::Right,
+            None,
+            window,
+            cx,
+          )
+          .ok();
+      }
+    }
+
+    if let Some(markers_lane) = lane_range(GutterLane::Markers) {
+      for (line_idx, icon) in &prepaint.icons {
+        let Some(y) = first_row_top(*line_idx) else {
+          continue;
+        };
+        paint_glyph(
+          icon.glyph.clone(),
+          icon.color,
+          point(markers_lane.start + px(GUTTER_ICON_PADDING), y),
           prepaint.line_height,
-          TextAlign::Right,
-          None,
+          &text_style,
           window,
           cx,
-        )
-        .ok();
+        );
+      }
     }
+
+    if let Some(breakpoints_lane) = lane_range(GutterLane::Breakpoints) {
+      for line_idx in &prepaint.breakpoints {
+        let Some(y) = first_row_top(*line_idx) else {
+          continue;
+        };
+        paint_glyph(
+          SharedString::new_static(BREAKPOINT_GLYPH),
+          red(),
+          point(breakpoints_lane.start + px(GUTTER_ICON_PADDING), y),
+          prepaint.line_height,
+          &text_style,
+          window,
+          cx,
+        );
+      }
+    }
+  }
+}
+
+/// Paint a single glyph at `origin`, with the gutter's font
+fn paint_glyph(
+  glyph: SharedString,
+  color: Hsla,
+  origin: Point<Pixels>,
+  line_height: Pixels,
+  text_style: &TextStyle,
+  window: &mut Window,
+  cx: &mut App,
+) {
+  let runs = vec![TextRun {
+    len: glyph.len(),
+    font: text_style.font(),
+    color,
+    background_color: None,
+    underline: None,
+    strikethrough: None,
+  }];
+  let font_size = text_style.font_size.to_pixels(window.rem_size());
+  let shaped = window
+    .text_system()
+    .shape_line(glyph, font_size, &runs, None);
+  shaped
+    .paint(origin, line_height, TextAlign::Left, None, window, cx)
+    .ok();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_lanes() {
+    let layout = GutterLayout::default();
+    assert_eq!(layout.width(), px(70.0));
+    assert_eq!(
+      layout.lane_range(GutterLane::LineNumbers),
+      Some(px(32.0)..px(70.0))
+    );
+    assert_eq!(layout.lane_range(GutterLane::Folds), None);
+
+    assert_eq!(layout.lane_at(px(0.0)), Some(GutterLane::Breakpoints));
+    assert_eq!(layout.lane_at(px(16.0)), Some(GutterLane::Markers));
+    assert_eq!(layout.lane_at(px(69.5)), Some(GutterLane::LineNumbers));
+    assert_eq!(layout.lane_at(px(70.0)), None);
+
+    let layout = GutterLayout::new([
+      (GutterLane::LineNumbers, px(40.0)),
+      (GutterLane::Folds, px(12.0)),
+    ]);
+    assert_eq!(layout.width(), px(52.0));
+    assert_eq!(layout.lane_at(px(45.0)), Some(GutterLane::Folds));
   }
 }
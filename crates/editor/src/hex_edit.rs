@@ -0,0 +1,201 @@
+//! Moving and typing in the hex view
+//!
+//! The hex view has a cursor on a byte, as the bytes after the first of a
+//! character have no char offset of their own. It moves a byte or a row of
+//! bytes at a time, and two hex digits typed one after the other replace the
+//! byte under it. Documents are text, so a byte is only replaced if its
+//! character is still valid UTF-8 with it, and typing past the last byte
+//! appends an ASCII character.
+
+use gpui::{App, Context};
+
+use crate::{editor::Editor, hex_element::BYTES_PER_ROW, motion::Motion};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct HexCursor {
+  /// Byte under the cursor, used while it is within the cursor's character
+  byte: usize,
+  /// First hex digit typed for the byte under the cursor
+  high_nibble: Option<u8>,
+}
+
+/// Byte under the cursor, the first of the cursor's character unless the
+/// cursor was moved onto a later one
+pub(crate) fn cursor_byte(editor: &Editor, cx: &App) -> usize {
+  let document = editor.document.read(cx);
+  let offset = editor.cursor_offset();
+  let start = document.char_to_byte(offset);
+  let end = document.char_to_byte((offset + 1).min(document.len()));
+  let byte = editor.hex_cursor.byte;
+  if (start..end).contains(&byte) {
+    byte
+  } else {
+    start
+  }
+}
+
+/// Whether a first hex digit was typed for the byte under the cursor
+pub(crate) fn has_high_nibble(editor: &Editor, cx: &App) -> bool {
+  editor.hex_cursor.high_nibble.is_some() && editor.hex_cursor.byte == cursor_byte(editor, cx)
+}
+
+/// Put the cursor on `byte`, selecting from the anchor if `select`
+pub(crate) fn move_to_byte(
+  editor: &mut Editor,
+  byte: usize,
+  select: bool,
+  cx: &mut Context<Editor>,
+) {
+  let document = editor.document.read(cx);
+  let byte = byte.min(document.len_bytes());
+  let offset = document.byte_to_char(byte);
+  editor.target_column = None;
+  if select {
+    editor.select_to(offset, cx);
+  } else {
+    editor.move_to(offset, cx);
+  }
+  editor.hex_cursor = HexCursor {
+    byte,
+    high_nibble: None,
+  };
+}
+
+/// Move by bytes instead of characters: a byte left or right, a row up or down
+pub(crate) fn apply_motion(
+  editor: &mut Editor,
+  motion: Motion,
+  steps: usize,
+  cx: &mut Context<Editor>,
+) {
+  let byte = cursor_byte(editor, cx);
+  let (target, select) = match motion {
+    Motion::Left => (byte.saturating_sub(steps), false),
+    Motion::Right => (byte.saturating_add(steps), false),
+    Motion::Up => (byte.saturating_sub(steps * BYTES_PER_ROW), false),
+    Motion::Down => (byte.saturating_add(steps * BYTES_PER_ROW), false),
+    Motion::SelectLeft => (byte.saturating_sub(steps), true),
+    Motion::SelectRight => (byte.saturating_add(steps), true),
+    Motion::SelectUp => (byte.saturating_sub(steps * BYTES_PER_ROW), true),
+    Motion::SelectDown => (byte.saturating_add(steps * BYTES_PER_ROW), true),
+  };
+  move_to_byte(editor, target, select, cx);
+}
+
+/// Type `text` in the hex view, where only hex digits mean anything; returns
+/// false for no text, which deletes as in the text view
+pub(crate) fn input(editor: &mut Editor, text: &str, cx: &mut Context<Editor>) -> bool {
+  if text.is_empty() {
+    return false;
+  }
+  for digit in text.chars().filter_map(|ch| ch.to_digit(16)) {
+    type_nibble(editor, digit as u8, cx);
+  }
+  cx.notify();
+  true
+}
+
+fn type_nibble(editor: &mut Editor, digit: u8, cx: &mut Context<Editor>) {
+  let byte = cursor_byte(editor, cx);
+  let high = if has_high_nibble(editor, cx) {
+    editor.hex_cursor.high_nibble.take()
+  } else {
+    None
+  };
+  let Some(high) = high else {
+    editor.hex_cursor = HexCursor {
+      byte,
+      high_nibble: Some(digit),
+    };
+    return;
+  };
+  if write_byte(editor, byte, (high << 4) | digit, cx) {
+    move_to_byte(editor, byte + 1, false, cx);
+  }
+}
+
+/// Replace the byte at `byte` with `value`, or append it at the end; returns
+/// false if that wouldn't leave valid UTF-8
+fn write_byte(editor: &mut Editor, byte: usize, value: u8, cx: &mut Context<Editor>) -> bool {
+  let (range, text) = {
+    let document = editor.document.read(cx);
+    let offset = document.byte_to_char(byte);
+    if byte >= document.len_bytes() {
+      if !value.is_ascii() {
+        return false;
+      }
+      (offset..offset, (value as char).to_string())
+    } else {
+      let mut bytes = document.slice_to_string(offset..offset + 1).into_bytes();
+      bytes[byte - document.char_to_byte(offset)] = value;
+      let Ok(text) = String::from_utf8(bytes) else {
+        return false;
+      };
+      (offset..offset + 1, text)
+    }
+  };
+  editor.selected_range = range;
+  editor.selection_reversed = false;
+  editor.insert_text(&text, "Edit Byte", cx);
+  true
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[gpui::test]
+  fn test_arrows_move_by_bytes(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "aé0123456789abcdefgh");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.hex_mode = true;
+      apply_motion(editor, Motion::Right, 2, cx);
+      // On the second byte of "é", which has no char offset of its own
+      assert_eq!(cursor_byte(editor, cx), 2);
+      assert_eq!(editor.cursor_offset(), 1);
+      apply_motion(editor, Motion::Down, 1, cx);
+      assert_eq!(cursor_byte(editor, cx), 18);
+      apply_motion(editor, Motion::SelectUp, 1, cx);
+      assert_eq!(cursor_byte(editor, cx), 2);
+      assert_eq!(editor.selected_range, 1..17);
+
+      // Moving the cursor otherwise puts it on the first byte of its character
+      editor.move_to(1, cx);
+      editor.hex_cursor.byte = 5;
+      assert_eq!(cursor_byte(editor, cx), 1);
+    });
+  }
+
+  #[gpui::test]
+  fn test_typing_replaces_bytes(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "aé");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.hex_mode = true;
+      assert!(input(editor, "4", cx));
+      assert!(has_high_nibble(editor, cx));
+      assert!(input(editor, "2", cx));
+      assert_eq!(editor.document.read(cx).text(), "Bé");
+      assert_eq!(cursor_byte(editor, cx), 1);
+
+      // "é" is c3 a9, and c3 a8 is "è"
+      apply_motion(editor, Motion::Right, 1, cx);
+      assert!(input(editor, "a8", cx));
+      assert_eq!(editor.document.read(cx).text(), "Bè");
+      assert_eq!(cursor_byte(editor, cx), 3);
+
+      // Past the end only ASCII can be appended
+      assert!(input(editor, "ff", cx));
+      assert_eq!(editor.document.read(cx).text(), "Bè");
+      assert!(input(editor, "21", cx));
+      assert_eq!(editor.document.read(cx).text(), "Bè!");
+
+      // An invalid byte leaves the text alone
+      move_to_byte(editor, 1, false, cx);
+      assert!(input(editor, "41", cx));
+      assert_eq!(editor.document.read(cx).text(), "Bè!");
+      assert!(!input(editor, "", cx));
+    });
+  }
+}
@@ -0,0 +1,339 @@
+//! Hex + ASCII view of the document bytes, an alternate rendering of the editor
+//!
+//! Rows of 16 bytes are virtualized with the same viewport logic as
+//! `EditorElement`; the selection is mapped to byte offsets, and the cursor
+//! is on the byte `hex_edit` moved it to.
+
+use gpui::{
+  App, Bounds, DispatchPhase, ElementId, Entity, GlobalElementId, InspectorElementId, LayoutId,
+  MouseButton, MouseDownEvent, PaintQuad, Pixels, ShapedLine, Style, TextAlign, TextRun, Window,
+  fill, point, prelude::*, px, relative, size,
+};
+use std::ops::Range;
+
+use crate::{
+  editor::Editor,
+  editor_element::{calculate_viewport, register_scroll_wheel},
+  hex_edit,
+};
+
+pub const BYTES_PER_ROW: usize = 16;
+// Characters taken by the "00000000  " offset column
+const OFFSET_COLUMN_LEN: usize = 10;
+// Characters taken by each "xx " hex cell
+const HEX_CELL_LEN: usize = 3;
+// Characters between the hex cells and the ASCII column ("|")
+const ASCII_SEPARATOR_LEN: usize = 1;
+
+/// Number of rows needed to show `len_bytes` bytes, an empty document still has one
+pub fn row_count(len_bytes: usize) -> usize {
+  len_bytes.div_ceil(BYTES_PER_ROW).max(1)
+}
+
+/// Format one row as offset, hex cells and printable ASCII
+pub fn format_row(offset: usize, bytes: &[u8]) -> String {
+  let mut row = format!("{:08x}  ", offset);
+  for idx in 0..BYTES_PER_ROW {
+    match bytes.get(idx) {
+      Some(byte) => row.push_str(&format!("{:02x} ", byte)),
+      None => row.push_str("   "),
+    }
+  }
+  row.push('|');
+  row.extend(bytes.iter().map(|&byte| {
+    if byte.is_ascii_graphic() || byte == b' ' {
+      byte as char
+    } else {
+      '.'
+    }
+  }));
+  row.push('|');
+  row
+}
+
+fn hex_column(byte_in_row: usize) -> usize {
+  OFFSET_COLUMN_LEN + byte_in_row * HEX_CELL_LEN
+}
+
+fn ascii_column(byte_in_row: usize) -> usize {
+  hex_column(BYTES_PER_ROW) + ASCII_SEPARATOR_LEN + byte_in_row
+}
+
+/// Byte within a row under a character column of the formatted row
+fn byte_for_column(column: usize) -> usize {
+  if column >= ascii_column(0) {
+    (column - ascii_column(0)).min(BYTES_PER_ROW - 1)
+  } else {
+    (column.saturating_sub(OFFSET_COLUMN_LEN) / HEX_CELL_LEN).min(BYTES_PER_ROW - 1)
+  }
+}
+
+pub struct HexElement {
+  editor: Entity<Editor>,
+}
+
+pub struct HexPrepaintState {
+  rows: Vec<(usize, ShapedLine)>,
+  cursor_quad: Option<PaintQuad>,
+  selection_quads: Vec<PaintQuad>,
+  viewport: Range<usize>,
+  line_height: Pixels,
+}
+
+impl HexElement {
+  pub fn new(editor: Entity<Editor>) -> Self {
+    Self { editor }
+  }
+}
+
+impl IntoElement for HexElement {
+  type Element = Self;
+
+  fn into_element(self) -> Self::Element {
+    self
+  }
+}
+
+impl Element for HexElement {
+  type RequestLayoutState = ();
+  type PrepaintState = HexPrepaintState;
+
+  fn id(&self) -> Option<ElementId> {
+    None
+  }
+
+  fn source_location(&self) -> Option<&'static core::panic::Location<'static>> {
+    None
+  }
+
+  fn request_layout(
+    &mut self,
+    _id: Option<&GlobalElementId>,
+    _inspector_id: Option<&InspectorElementId>,
+    window: &mut Window,
+    cx: &mut App,
+  ) -> (LayoutId, Self::RequestLayoutState) {
+    let mut style = Style::default();
+    style.size.width = relative(1.).into();
+    style.size.height = relative(1.).into();
+
+    (window.request_layout(style, [], cx), ())
+  }
+
+  fn prepaint(
+    &mut self,
+    _id: Option<&GlobalElementId>,
+    _inspector_id: Option<&InspectorElementId>,
+    bounds: Bounds<Pixels>,
+    _request_layout: &mut Self::RequestLayoutState,
+    window: &mut Window,
+    cx: &mut App,
+  ) -> Self::PrepaintState {
+    self.editor.update(cx, |editor, _| {
+      editor.viewport_height = bounds.size.height;
+      editor.line_height = window.line_height();
+    });
+
+    let style = self.editor.read(cx).text_style(window, cx);
+    let font_size = style.font_size.to_pixels(window.rem_size());
+    let line_height = window.line_height();
+
+    let editor = self.editor.read(cx);
+    let document = editor.document().read(cx);
+    let len_bytes = document.len_bytes();
+    let viewport = calculate_viewport(
+      bounds,
+      line_height,
+      editor.scroll_offset_y,
+      row_count(len_bytes),
+    );
+
+    let mut rows = Vec::new();
+    for row in viewport.clone() {
+      let start = row * BYTES_PER_ROW;
+      let text = format_row(
+        start,
+        &document.bytes_in_range(start..start + BYTES_PER_ROW),
+      );
+      let runs = vec![TextRun {
+        len: text.len(),
+        font: style.font(),
+        color: style.color,
+        background_color: None,
+        underline: None,
+        strikethrough: None,
+      }];
+      let shaped = window
+        .text_system()
+        .shape_line(text.into(), font_size, &runs, None);
+      rows.push((row, shaped));
+    }
+
+    let shaped_row = |row: usize| {
+      rows
+        .iter()
+        .find(|(idx, _)| *idx == row)
+        .map(|(_, shaped)| shaped)
+    };
+    let row_top = |row: usize| bounds.top() + line_height * (row - viewport.start) as f32;
+
+    let cursor_byte = hex_edit::cursor_byte(editor, cx);
+    let cursor_row = cursor_byte / BYTES_PER_ROW;
+    // After a first hex digit the cursor waits before the second
+    let nibble = usize::from(hex_edit::has_high_nibble(editor, cx));
+    let cursor_quad = shaped_row(cursor_row).map(|shaped| {
+      let x = shaped.x_for_index(hex_column(cursor_byte % BYTES_PER_ROW) + nibble);
+      fill(
+        Bounds::new(
+          point(bounds.left() + x, row_top(cursor_row)),
+          size(px(2.), line_height),
+        ),
+        editor.theme.cursor(),
+      )
+    });
+
+    let mut selection_quads = Vec::new();
+    let selected_bytes = document.char_to_byte(editor.selected_range.start)
+      ..document.char_to_byte(editor.selected_range.end);
+    if !selected_bytes.is_empty() {
+      let first_row = selected_bytes.start / BYTES_PER_ROW;
+      let last_row = (selected_bytes.end - 1) / BYTES_PER_ROW;
+      for row in first_row.max(viewport.start)..=last_row.min(viewport.end.saturating_sub(1)) {
+        let Some(shaped) = shaped_row(row) else {
+          continue;
+        };
+        let row_start = row * BYTES_PER_ROW;
+        let start = selected_bytes.start.max(row_start) - row_start;
+        let end = selected_bytes.end.min(row_start + BYTES_PER_ROW) - row_start;
+        let y = row_top(row);
+
+        // Highlight the bytes in both the hex and the ASCII column
+        for (start_column, end_column) in [
+          (hex_column(start), hex_column(end) - 1),
+          (ascii_column(start), ascii_column(end)),
+        ] {
+          selection_quads.push(fill(
+            Bounds::from_corners(
+              point(bounds.left() + shaped.x_for_index(start_column), y),
+              point(
+                bounds.left() + shaped.x_for_index(end_column),
+                y + line_height,
+              ),
+            ),
+            editor.theme.selection(),
+          ));
+        }
+      }
+    }
+
+    HexPrepaintState {
+      rows,
+      cursor_quad,
+      selection_quads,
+      viewport,
+      line_height,
+    }
+  }
+
+  fn paint(
+    &mut self,
+    _id: Option<&GlobalElementId>,
+    _inspector_id: Option<&InspectorElementId>,
+    bounds: Bounds<Pixels>,
+    _request_layout: &mut Self::RequestLayoutState,
+    prepaint: &mut Self::PrepaintState,
+    window: &mut Window,
+    cx: &mut App,
+  ) {
+    // Clicking a hex cell or an ASCII character places the cursor on its byte
+    window.on_mouse_event({
+      let editor = self.editor.clone();
+      let rows = prepaint.rows.clone();
+      let viewport_start = prepaint.viewport.start;
+      let line_height = prepaint.line_height;
+      move |event: &MouseDownEvent, phase, _window, cx| {
+        if phase != DispatchPhase::Bubble
+          || event.button != MouseButton::Left
+          || !bounds.contains(&event.position)
+        {
+          return;
+        }
+        let row =
+          viewport_start + ((event.position.y - bounds.top()) / line_height).floor() as usize;
+        let Some((_, shaped)) = rows.iter().find(|(idx, _)| *idx == row) else {
+          return;
+        };
+        let column = shaped.closest_index_for_x(event.position.x - bounds.left());
+        let byte = row * BYTES_PER_ROW + byte_for_column(column);
+        editor.update(cx, |editor, cx| {
+          editor.hex_clicked(byte, event.modifiers.shift, cx);
+        });
+      }
+    });
+
+    register_scroll_wheel(&self.editor, window);
+
+    for quad in &prepaint.selection_quads {
+      window.paint_quad(quad.clone());
+    }
+
+    for (row, shaped) in &prepaint.rows {
+      let y = bounds.top() + prepaint.line_height * (*row - prepaint.viewport.start) as f32;
+      shaped
+        .paint(
+          point(bounds.left(), y),
+          prepaint.line_height,
+          TextAlign::Left,
+          None,
+          window,
+          cx,
+        )
+        .ok();
+    }
+
+    let (is_focused, cursor_visible) = {
+      let editor = self.editor.read(cx);
+      (
+        editor.focus_handle.is_focused(window),
+        editor.cursor_blink.read(cx).visible(),
+      )
+    };
+    if is_focused
+      && cursor_visible
+      && let Some(cursor_quad) = &prepaint.cursor_quad
+    {
+      window.paint_quad(cursor_quad.clone());
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_format_row() {
+    assert_eq!(
+      format_row(0x10, b"Hi\n"),
+      format!("00000010  48 69 0a {}|Hi.|", " ".repeat(13 * HEX_CELL_LEN))
+    );
+  }
+
+  #[test]
+  fn test_row_count() {
+    assert_eq!(row_count(0), 1);
+    assert_eq!(row_count(16), 1);
+    assert_eq!(row_count(17), 2);
+  }
+
+  #[test]
+  fn test_byte_for_column() {
+    assert_eq!(byte_for_column(0), 0);
+    assert_eq!(byte_for_column(hex_column(3) + 1), 3);
+    assert_eq!(byte_for_column(ascii_column(5)), 5);
+    assert_eq!(
+      byte_for_column(ascii_column(BYTES_PER_ROW) + 4),
+      BYTES_PER_ROW - 1
+    );
+  }
+}
@@ -0,0 +1,169 @@
+//! Where IME candidate windows and other OS text services are anchored
+//!
+//! The platform asks for the window bounds of a text range and for the
+//! character under a point. Both come from the last painted frame. Bounds
+//! are kept inside the visible part of the text, so a caret scrolled out of
+//! a long line still anchors at the edge of the view. They are also snapped
+//! to the device pixels of the window's current display, which can change
+//! scale factor when the window moves between monitors.
+
+use std::ops::Range;
+
+use gpui::{App, Bounds, Pixels, Point, point, px};
+
+use crate::editor::Editor;
+
+/// Window bounds of the text in `range`, or of the caret when it is empty
+///
+/// Only the first line of a range spanning lines is measured. Returns None
+/// when that line wasn't painted in the last frame, or in hex mode.
+pub(crate) fn bounds_for_range(
+  editor: &Editor,
+  range: Range<usize>,
+  element_bounds: Bounds<Pixels>,
+  scale_factor: f32,
+  cx: &App,
+) -> Option<Bounds<Pixels>> {
+  if editor.hex_mode {
+    return None;
+  }
+  let position_map = editor.last_position_map.as_ref()?;
+  let document = editor.document.read(cx);
+  let line_idx = document.char_to_line(range.start);
+  let (_, shaped) = position_map
+    .shaped_lines
+    .iter()
+    .find(|(idx, _)| *idx == line_idx)?;
+
+  let line_start = document.line_to_char(line_idx);
+  let line_content = document.line_content(line_idx).unwrap_or_default();
+  let byte_for_column = |column: usize| {
+    line_content
+      .char_indices()
+      .nth(column)
+      .map_or(line_content.len(), |(offset, _)| offset)
+  };
+  // Within the row showing the start of the range
+  let start_byte = byte_for_column(range.start - line_start);
+  let (row, start_x) =
+    position_map
+      .rows
+      .caret_x(line_idx, start_byte, shaped, position_map.char_width)?;
+  let (_, row_bytes) = position_map.rows.at_row(row)?;
+  let end_byte = if document.char_to_line(range.end) == line_idx {
+    byte_for_column(range.end - line_start).min(row_bytes.end)
+  } else {
+    row_bytes.end
+  };
+  let end_x = position_map
+    .rows
+    .span_xs(
+      line_idx,
+      start_byte..end_byte,
+      shaped,
+      position_map.char_width,
+    )
+    .into_iter()
+    .find(|(span_row, _)| *span_row == row)
+    .map_or(start_x, |(_, xs)| xs.end);
+
+  let top = position_map
+    .rows
+    .row_top(row, element_bounds.top(), position_map.line_height);
+  let text = element_bounds.left() + start_x..element_bounds.left() + end_x;
+  Some(anchor_bounds(
+    text,
+    top,
+    position_map.line_height,
+    editor.scroll_handle.bounds(),
+    scale_factor,
+  ))
+}
+
+/// Char offset of the character under `position`, in window coordinates
+pub(crate) fn character_index_for_point(
+  editor: &Editor,
+  position: Point<Pixels>,
+  cx: &App,
+) -> Option<usize> {
+  if editor.hex_mode {
+    return None;
+  }
+  let position_map = editor.last_position_map.as_ref()?;
+  position_map.point_for_position(position, editor.document.read(cx))
+}
+
+/// Bounds of text spanning `x` on a line at `top`, kept within `visible`
+fn anchor_bounds(
+  x: Range<Pixels>,
+  top: Pixels,
+  line_height: Pixels,
+  visible: Bounds<Pixels>,
+  scale_factor: f32,
+) -> Bounds<Pixels> {
+  // An empty visible area means the view hasn't been laid out yet
+  let (left, right) = if visible.size.width > px(0.) {
+    (
+      x.start.max(visible.left()).min(visible.right()),
+      x.end.max(visible.left()).min(visible.right()),
+    )
+  } else {
+    (x.start, x.end)
+  };
+  snap_to_device_pixels(
+    Bounds::from_corners(point(left, top), point(right.max(left), top + line_height)),
+    scale_factor,
+  )
+}
+
+/// The smallest bounds on the device pixel grid covering `bounds`
+fn snap_to_device_pixels(bounds: Bounds<Pixels>, scale_factor: f32) -> Bounds<Pixels> {
+  if scale_factor <= 0. {
+    return bounds;
+  }
+  let floor = |value: Pixels| px((f32::from(value) * scale_factor).floor() / scale_factor);
+  let ceil = |value: Pixels| px((f32::from(value) * scale_factor).ceil() / scale_factor);
+  Bounds::from_corners(
+    point(floor(bounds.left()), floor(bounds.top())),
+    point(ceil(bounds.right()), ceil(bounds.bottom())),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use gpui::size;
+
+  #[test]
+  fn test_snap_to_device_pixels() {
+    let bounds = Bounds::from_corners(point(px(10.3), px(20.0)), point(px(10.3), px(38.6)));
+    assert_eq!(
+      snap_to_device_pixels(bounds, 2.0),
+      Bounds::from_corners(point(px(10.0), px(20.0)), point(px(10.5), px(39.0)))
+    );
+    assert_eq!(
+      snap_to_device_pixels(bounds, 1.0),
+      Bounds::from_corners(point(px(10.0), px(20.0)), point(px(11.0), px(39.0)))
+    );
+    assert_eq!(snap_to_device_pixels(bounds, 0.0), bounds);
+  }
+
+  #[test]
+  fn test_anchor_bounds_stay_visible() {
+    let visible = Bounds::new(point(px(100.), px(0.)), size(px(300.), px(500.)));
+
+    let caret = anchor_bounds(px(150.)..px(150.), px(40.), px(20.), visible, 1.0);
+    assert_eq!(caret.origin, point(px(150.), px(40.)));
+    assert_eq!(caret.size.height, px(20.));
+
+    // Scrolled past the caret: anchor at the edges of the view
+    let right = anchor_bounds(px(900.)..px(910.), px(40.), px(20.), visible, 1.0);
+    assert_eq!(right.origin.x, px(400.));
+    let left = anchor_bounds(px(20.)..px(130.), px(40.), px(20.), visible, 1.0);
+    assert_eq!((left.left(), left.right()), (px(100.), px(130.)));
+
+    // Before layout nothing is clamped
+    let unlaid = anchor_bounds(px(900.)..px(900.), px(0.), px(20.), Bounds::default(), 1.5);
+    assert_eq!(unlaid.origin.x, px(900.));
+  }
+}
@@ -0,0 +1,232 @@
+//! Indenting and outdenting lines
+//!
+//! Tab at a cursor, or over part of one line, inserts indentation up to the
+//! next stop. Over several lines it indents each of them that has text and
+//! keeps them selected. Shift-Tab takes one level off every line the
+//! selection touches. The unit is a tab if the document's first indented
+//! line starts with one, otherwise its narrowest run of leading spaces.
+
+use std::{ops::Range, time::Instant};
+
+use gpui::Context;
+
+use crate::{document::Document, editor::Editor};
+
+// Spaces per level when the document has no indentation to go by
+const DEFAULT_INDENT_WIDTH: usize = 4;
+// Lines looked through for the document's indentation
+const DETECT_LINES: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum IndentUnit {
+  Tab,
+  Spaces(usize),
+}
+
+impl IndentUnit {
+  fn text(self) -> String {
+    match self {
+      IndentUnit::Tab => "\t".to_string(),
+      IndentUnit::Spaces(width) => " ".repeat(width),
+    }
+  }
+
+  /// Indentation that takes `column` to the next stop
+  fn to_next_stop(self, column: usize) -> String {
+    match self {
+      IndentUnit::Tab => "\t".to_string(),
+      IndentUnit::Spaces(width) => " ".repeat(width - column % width),
+    }
+  }
+
+  /// Leading characters of `line` that make up its first level
+  fn outdent_len(self, line: &str) -> usize {
+    if line.starts_with('\t') {
+      return 1;
+    }
+    let width = match self {
+      IndentUnit::Tab => DEFAULT_INDENT_WIDTH,
+      IndentUnit::Spaces(width) => width,
+    };
+    line.chars().take(width).take_while(|ch| *ch == ' ').count()
+  }
+}
+
+/// Indentation used by `lines`
+fn detect_in<'a>(lines: impl IntoIterator<Item = &'a str>) -> IndentUnit {
+  let mut narrowest: Option<usize> = None;
+  for line in lines {
+    let text = line.trim_start_matches([' ', '\t']);
+    if text.is_empty() || text.len() == line.len() {
+      continue;
+    }
+    if line.starts_with('\t') {
+      if narrowest.is_none() {
+        return IndentUnit::Tab;
+      }
+      continue;
+    }
+    // A single space is alignment, not a level
+    let spaces = line.len() - line.trim_start_matches(' ').len();
+    if spaces > 1 {
+      narrowest = Some(narrowest.map_or(spaces, |narrowest| narrowest.min(spaces)));
+    }
+  }
+  IndentUnit::Spaces(narrowest.unwrap_or(DEFAULT_INDENT_WIDTH))
+}
+
+pub(crate) fn detect(document: &Document) -> IndentUnit {
+  let lines: Vec<String> = (0..document.len_lines().min(DETECT_LINES))
+    .map(|line| document.line_content(line).unwrap_or_default().into_owned())
+    .collect();
+  detect_in(lines.iter().map(String::as_str))
+}
+
+/// Lines the selection covers; one ending at a line start leaves that line out
+pub(crate) fn selected_lines(document: &Document, range: &Range<usize>) -> Range<usize> {
+  let start = document.char_to_line(range.start);
+  let mut end = document.char_to_line(range.end);
+  if end > start && document.line_to_char(end) == range.end {
+    end -= 1;
+  }
+  start..end + 1
+}
+
+/// Indent at the cursor, or every selected line when the selection spans lines
+pub(crate) fn indent(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let document = editor.document.read(cx);
+  let range = editor.selected_range.clone();
+  let unit = detect(document);
+  let lines = selected_lines(document, &range);
+  if lines.len() == 1 {
+    let column = range.start - document.line_to_char(lines.start);
+    editor.insert_text(&unit.to_next_stop(column), "Indent", cx);
+    return;
+  }
+
+  let edits = lines
+    .filter(|&line| {
+      document
+        .line_content(line)
+        .is_some_and(|content| !content.trim().is_empty())
+    })
+    .map(|line| {
+      let start = document.line_to_char(line);
+      (start..start, unit.text())
+    })
+    .collect();
+  edit_line_starts(editor, edits, "Indent", cx);
+}
+
+/// Take one level of indentation off every line the selection touches
+pub(crate) fn outdent(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let document = editor.document.read(cx);
+  let unit = detect(document);
+  let edits = selected_lines(document, &editor.selected_range)
+    .filter_map(|line| {
+      let content = document.line_content(line)?;
+      let len = unit.outdent_len(&content);
+      let start = document.line_to_char(line);
+      (len > 0).then(|| (start..start + len, String::new()))
+    })
+    .collect();
+  edit_line_starts(editor, edits, "Outdent", cx);
+}
+
+/// Where `offset` moves to after `edits`, in document order; text inserted
+/// right at it goes after it
+fn map_offset(offset: usize, edits: &[(Range<usize>, String)]) -> usize {
+  edits
+    .iter()
+    .take_while(|(range, _)| range.start < offset)
+    .fold(offset, |mapped, (range, text)| {
+      mapped + text.chars().count() - (range.end.min(offset) - range.start)
+    })
+}
+
+/// Apply `edits` to the starts of lines as one undoable edit, keeping the
+/// selection on the same text
+pub(crate) fn edit_line_starts(
+  editor: &mut Editor,
+  edits: Vec<(Range<usize>, String)>,
+  description: &'static str,
+  cx: &mut Context<Editor>,
+) {
+  let Some((first, _)) = edits.first() else {
+    return;
+  };
+  editor.target_column = None;
+  let first_line = editor.document.read(cx).char_to_line(first.start);
+  let selection_before = editor.selected_range.clone();
+
+  let transaction_id = editor.document.update(cx, |doc, cx| {
+    let id = doc.buffer.transaction(Instant::now(), |buffer, tx| {
+      // Back to front, so the ranges before each edit stay put
+      for (range, text) in edits.iter().rev() {
+        buffer.replace(tx, range.clone(), text);
+      }
+    });
+    doc.schedule_recompute_highlights(cx);
+    cx.notify();
+    id
+  });
+
+  editor.selected_range =
+    map_offset(selection_before.start, &edits)..map_offset(selection_before.end, &edits);
+  let selection_after = editor.selected_range.clone();
+  editor.record_transaction(
+    transaction_id,
+    selection_before,
+    selection_after,
+    description,
+    cx,
+  );
+  editor.invalidate_lines_from(first_line);
+  cx.notify();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[test]
+  fn test_detect_indent_unit() {
+    assert_eq!(detect_in(["fn main() {}"]), IndentUnit::Spaces(4));
+    assert_eq!(detect_in(["a", "\tb", "  c"]), IndentUnit::Tab);
+    assert_eq!(
+      detect_in(["a", "    b", "  c", " * d"]),
+      IndentUnit::Spaces(2)
+    );
+    assert_eq!(IndentUnit::Spaces(4).to_next_stop(6), "  ");
+    assert_eq!(IndentUnit::Spaces(4).outdent_len("  \tx"), 2);
+  }
+
+  #[gpui::test]
+  fn test_indent_and_outdent_lines(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "ab\n\ncd\nef");
+    // From inside "ab" to the start of "ef", which is left out
+    ctx.set_selection(1..7, false);
+    ctx
+      .editor
+      .update(&mut ctx.cx, |editor, cx| indent(editor, cx));
+    assert_eq!(ctx.text(), "    ab\n\n    cd\nef");
+    assert_eq!(ctx.selection(), 5..15);
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert_eq!(editor.undo_description(cx), Some("Indent"));
+      outdent(editor, cx);
+    });
+    assert_eq!(ctx.text(), "ab\n\ncd\nef");
+    assert_eq!(ctx.selection(), 1..7);
+
+    // A cursor indents where it is, to the next stop
+    ctx.set_cursor(1);
+    ctx
+      .editor
+      .update(&mut ctx.cx, |editor, cx| indent(editor, cx));
+    assert_eq!(ctx.text(), "a   b\n\ncd\nef");
+    assert_eq!(ctx.cursor_offset(), 4);
+  }
+}
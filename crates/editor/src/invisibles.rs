@@ -0,0 +1,269 @@
+//! Glyphs standing in for characters that don't render visibly on their own,
+//! and detection of Unicode that can make code read differently than it runs
+
+/// How an invisible character is shown inside a selection
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Invisible {
+  pub glyph: &'static str,
+  /// Characters that can make source code read differently than it compiles
+  pub suspicious: bool,
+}
+
+/// Bidirectional formatting controls, the trojan-source class of characters
+pub fn is_bidi_control(ch: char) -> bool {
+  matches!(
+    ch,
+    '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}'
+  )
+}
+
+pub fn is_zero_width(ch: char) -> bool {
+  matches!(
+    ch,
+    '\u{00AD}' | '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}'
+  )
+}
+
+/// Glyph for `ch`, plain spaces are only shown when they trail the line
+pub fn invisible_for(ch: char, trailing: bool) -> Option<Invisible> {
+  let (glyph, suspicious) = match ch {
+    '\t' => ("→", false),
+    ' ' if trailing => ("·", false),
+    '\u{00A0}' | '\u{202F}' => ("⍽", false),
+    ch if is_bidi_control(ch) => ("⇄", true),
+    ch if is_zero_width(ch) => ("∘", false),
+    _ => return None,
+  };
+  Some(Invisible { glyph, suspicious })
+}
+
+/// Invisible characters of a line (without its newline), keyed by byte offset
+pub fn invisibles_in_line(line: &str) -> Vec<(usize, Invisible)> {
+  let trailing_start = line.trim_end_matches(' ').len();
+  line
+    .char_indices()
+    .filter_map(|(offset, ch)| {
+      invisible_for(ch, offset >= trailing_start).map(|invisible| (offset, invisible))
+    })
+    .collect()
+}
+
+/// Why a character is flagged as a potential trojan-source issue
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnicodeWarning {
+  Bidi,
+  Invisible,
+  /// Looks like the given ASCII character
+  Confusable(char),
+}
+
+impl UnicodeWarning {
+  /// Text the character is replaced with when removing suspicious Unicode
+  pub fn replacement(&self) -> Option<char> {
+    match self {
+      UnicodeWarning::Bidi | UnicodeWarning::Invisible => None,
+      UnicodeWarning::Confusable(ascii) => Some(*ascii),
+    }
+  }
+}
+
+/// ASCII character that `ch` is commonly mistaken for
+pub fn confusable_ascii(ch: char) -> Option<char> {
+  let ascii = match ch {
+    '\u{00A0}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}' => ' ',
+    'а' => 'a',
+    'с' => 'c',
+    'е' => 'e',
+    'һ' => 'h',
+    'і' => 'i',
+    'ј' => 'j',
+    'о' | 'ο' => 'o',
+    'р' | 'ρ' => 'p',
+    'ѕ' => 's',
+    'х' => 'x',
+    'у' => 'y',
+    'А' | 'Α' => 'A',
+    'В' | 'Β' => 'B',
+    'С' => 'C',
+    'Ѕ' => 'S',
+    'Е' | 'Ε' => 'E',
+    'Н' | 'Η' => 'H',
+    'І' | 'Ι' => 'I',
+    'К' | 'Κ' => 'K',
+    'М' | 'Μ' => 'M',
+    'О' | 'Ο' => 'O',
+    'Р' | 'Ρ' => 'P',
+    'Т' | 'Τ' => 'T',
+    'Х' | 'Χ' => 'X',
+    'ν' => 'v',
+    '\u{037E}' => ';',
+    '‚' => ',',
+    'ǀ' => 'l',
+    '\u{2010}' | '\u{2011}' | '\u{2212}' => '-',
+    '\u{2018}' | '\u{2019}' => '\'',
+    '\u{201C}' | '\u{201D}' => '"',
+    ch @ '\u{FF01}'..='\u{FF5E}' => char::from_u32(ch as u32 - 0xFEE0)?,
+    _ => return None,
+  };
+  Some(ascii)
+}
+
+/// Suspicious characters of a line, keyed by byte offset
+///
+/// Bidi controls and zero-width characters are always flagged. Confusables only
+/// are where they pass for ASCII: letters and digits inside an identifier that
+/// also has ASCII letters or digits, spaces and punctuation between two ASCII
+/// characters. Words written in Cyrillic or Greek and typographic quotes in
+/// prose aren't flagged.
+pub fn unicode_warnings_in_line(line: &str) -> Vec<(usize, UnicodeWarning)> {
+  if line.is_ascii() {
+    return Vec::new();
+  }
+  let chars: Vec<(usize, char)> = line.char_indices().collect();
+  chars
+    .iter()
+    .enumerate()
+    .filter_map(|(index, &(offset, ch))| {
+      let warning = if ch.is_ascii() {
+        return None;
+      } else if is_bidi_control(ch) {
+        UnicodeWarning::Bidi
+      } else if is_zero_width(ch) {
+        UnicodeWarning::Invisible
+      } else {
+        let ascii = confusable_ascii(ch)?;
+        let passes_for_ascii = if is_identifier_char(ch) {
+          in_mixed_identifier(&chars, index)
+        } else if ascii == ' ' {
+          // Only where it splits what would otherwise be one token, like `let\u{A0}x`
+          between_ascii(&chars, index, |ch| ch.is_ascii_alphanumeric() || ch == '_')
+        } else {
+          between_ascii(&chars, index, |ch| ch.is_ascii_graphic())
+        };
+        if !passes_for_ascii {
+          return None;
+        }
+        UnicodeWarning::Confusable(ascii)
+      };
+      Some((offset, warning))
+    })
+    .collect()
+}
+
+fn is_identifier_char(ch: char) -> bool {
+  ch.is_alphanumeric() || ch == '_'
+}
+
+/// Whether the identifier around `chars[index]` has ASCII letters or digits
+fn in_mixed_identifier(chars: &[(usize, char)], index: usize) -> bool {
+  let is_ascii_alphanumeric = |&(_, ch): &(usize, char)| ch.is_ascii_alphanumeric();
+  let before = chars[..index]
+    .iter()
+    .rev()
+    .take_while(|(_, ch)| is_identifier_char(*ch));
+  let after = chars[index + 1..]
+    .iter()
+    .take_while(|(_, ch)| is_identifier_char(*ch));
+  before.chain(after).any(is_ascii_alphanumeric)
+}
+
+/// Whether the characters on both sides of `chars[index]` are `neighbor`s
+fn between_ascii(chars: &[(usize, char)], index: usize, neighbor: impl Fn(char) -> bool) -> bool {
+  let before = index
+    .checked_sub(1)
+    .is_some_and(|before| neighbor(chars[before].1));
+  let after = chars.get(index + 1).is_some_and(|&(_, ch)| neighbor(ch));
+  before && after
+}
+
+/// Rust/JavaScript style escape of a character, e.g. `\u{202E}`
+pub fn escape_char(ch: char) -> String {
+  format!("\\u{{{:04X}}}", ch as u32)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_trailing_spaces_only() {
+    let invisibles = invisibles_in_line("a b  ");
+    let offsets: Vec<_> = invisibles.iter().map(|(offset, _)| *offset).collect();
+
+    assert_eq!(offsets, vec![3, 4]);
+    assert_eq!(invisibles[0].1.glyph, "·");
+  }
+
+  #[test]
+  fn test_tabs_and_nbsp() {
+    let invisibles = invisibles_in_line("\tx\u{00A0}y");
+
+    assert_eq!(invisibles.len(), 2);
+    assert_eq!(invisibles[0], (0, invisible_for('\t', false).unwrap()));
+    assert_eq!(invisibles[1].0, 2);
+    assert!(!invisibles[1].1.suspicious);
+  }
+
+  #[test]
+  fn test_bidi_controls_are_suspicious() {
+    let invisibles = invisibles_in_line("let a\u{202E} = 1;\u{200B}");
+
+    assert_eq!(invisibles.len(), 2);
+    assert!(invisibles[0].1.suspicious);
+    assert!(!invisibles[1].1.suspicious);
+  }
+
+  #[test]
+  fn test_visible_text() {
+    assert!(invisibles_in_line("fn main() {}").is_empty());
+    assert!(invisible_for(' ', false).is_none());
+  }
+
+  #[test]
+  fn test_unicode_warnings() {
+    let warnings = unicode_warnings_in_line("if a\u{202E} == \"аb\"\u{200B}");
+
+    assert_eq!(warnings.len(), 3);
+    assert_eq!(warnings[0], (4, UnicodeWarning::Bidi));
+    assert_eq!(warnings[1].1, UnicodeWarning::Confusable('a'));
+    assert_eq!(warnings[2].1, UnicodeWarning::Invisible);
+    assert!(unicode_warnings_in_line("plain ascii").is_empty());
+    assert!(unicode_warnings_in_line("café ✓").is_empty());
+  }
+
+  #[test]
+  fn test_confusables_in_context() {
+    // Mixed-script identifiers and punctuation inside code
+    let offsets = |line: &str| -> Vec<usize> {
+      unicode_warnings_in_line(line)
+        .into_iter()
+        .map(|(offset, _)| offset)
+        .collect()
+    };
+    assert_eq!(offsets("let usеr = 1;"), vec![6]);
+    assert_eq!(offsets("x = a\u{2212}b"), vec![5]);
+    assert_eq!(offsets("print(“hi”)"), vec![6, 11]);
+    assert_eq!(offsets("let\u{00A0}x = 1"), vec![3]);
+
+    // Cyrillic and Greek words, typographic quotes and spaces in prose
+    assert!(offsets("// Привет, мир").is_empty());
+    assert!(offsets("let title = \"Καλημέρα κόσμε\";").is_empty());
+    assert!(offsets("He said “hello” to me").is_empty());
+    assert!(offsets("Prix\u{00A0}: 10\u{00A0}€").is_empty());
+  }
+
+  #[test]
+  fn test_confusables() {
+    assert_eq!(confusable_ascii('Ａ'), Some('A'));
+    assert_eq!(confusable_ascii('\u{00A0}'), Some(' '));
+    assert_eq!(confusable_ascii('é'), None);
+    assert_eq!(UnicodeWarning::Confusable('o').replacement(), Some('o'));
+    assert_eq!(UnicodeWarning::Bidi.replacement(), None);
+  }
+
+  #[test]
+  fn test_escape_char() {
+    assert_eq!(escape_char('\u{202E}'), "\\u{202E}");
+    assert_eq!(escape_char('а'), "\\u{0430}");
+  }
+}
@@ -0,0 +1,296 @@
+//! Formatting and checking JSON documents
+//!
+//! JSON documents can be pretty-printed with two-space indentation or
+//! minified. Both only rewrite the whitespace between tokens, so keys keep
+//! their order and numbers their spelling. After edits settle, the document
+//! is parsed and a syntax error is underlined where the parser stopped.
+
+use std::{ops::Range, time::Duration};
+
+use gpui::{Context, Hsla, Task};
+
+use crate::{
+  decorations::{DecorationId, DecorationStyle, VirtualText},
+  editor::Editor,
+  large_file,
+};
+
+/// Delay before parsing the document again after an edit
+const REFRESH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+const INDENT: &str = "  ";
+
+/// Where the parser stopped, as a byte range, and why
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ParseError {
+  pub range: Range<usize>,
+  pub message: String,
+}
+
+#[derive(Default)]
+pub(crate) struct JsonValidation {
+  decoration: Option<DecorationId>,
+  // Document version the parse ran on
+  checked_version: Option<usize>,
+  refresh_task: Option<Task<()>>,
+}
+
+pub(crate) fn is_json(file_ext: Option<&str>) -> bool {
+  file_ext == Some("json")
+}
+
+/// The syntax error of `text`, none when it is valid JSON
+pub(crate) fn validate(text: &str) -> Option<ParseError> {
+  let err = serde_json::from_str::<serde_json::Value>(text).err()?;
+  // Lines and columns count from one, columns in bytes
+  let line_start = text
+    .split_inclusive('\n')
+    .take(err.line().saturating_sub(1))
+    .map(str::len)
+    .sum::<usize>();
+  let line_len = text[line_start..]
+    .find('\n')
+    .unwrap_or(text.len() - line_start);
+  let start = line_start + err.column().saturating_sub(1).min(line_len);
+  let end = text[start..]
+    .chars()
+    .next()
+    .map_or(start, |ch| start + ch.len_utf8());
+  let message = err.to_string();
+  let message = message
+    .rsplit_once(" at line ")
+    .map_or(message.as_str(), |(message, _)| message);
+  Some(ParseError {
+    range: start..end,
+    message: message.to_string(),
+  })
+}
+
+/// `text` pretty-printed, or the reason it can't be
+pub(crate) fn format(text: &str) -> Result<String, String> {
+  check(text)?;
+  Ok(reformat(text, Some(INDENT)))
+}
+
+/// `text` with every whitespace between tokens removed
+pub(crate) fn minify(text: &str) -> Result<String, String> {
+  check(text)?;
+  Ok(reformat(text, None))
+}
+
+fn check(text: &str) -> Result<(), String> {
+  match validate(text) {
+    Some(err) => Err(format!("Invalid JSON: {}", err.message)),
+    None => Ok(()),
+  }
+}
+
+/// Rewrite the whitespace between the tokens of valid JSON, putting each
+/// member on its own line when there is an indent
+fn reformat(text: &str, indent: Option<&str>) -> String {
+  let newline = |out: &mut String, depth: usize| {
+    if let Some(indent) = indent {
+      out.push('\n');
+      out.push_str(&indent.repeat(depth));
+    }
+  };
+
+  let mut out = String::with_capacity(text.len());
+  let mut depth = 0;
+  let mut chars = text.chars().peekable();
+  while let Some(ch) = chars.next() {
+    match ch {
+      '"' => {
+        out.push(ch);
+        while let Some(ch) = chars.next() {
+          out.push(ch);
+          if ch == '\\' {
+            out.extend(chars.next());
+          } else if ch == '"' {
+            break;
+          }
+        }
+      }
+      '{' | '[' => {
+        out.push(ch);
+        while chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+        // Empty objects and arrays stay on one line
+        match chars.next_if(|next| matches!(next, '}' | ']')) {
+          Some(close) => out.push(close),
+          None => {
+            depth += 1;
+            newline(&mut out, depth);
+          }
+        }
+      }
+      '}' | ']' => {
+        depth -= 1;
+        newline(&mut out, depth);
+        out.push(ch);
+      }
+      ',' => {
+        out.push(ch);
+        newline(&mut out, depth);
+      }
+      ':' => {
+        out.push(ch);
+        if indent.is_some() {
+          out.push(' ');
+        }
+      }
+      ch if ch.is_whitespace() => {}
+      ch => out.push(ch),
+    }
+  }
+  if indent.is_some() {
+    out.push('\n');
+  }
+  out
+}
+
+/// Whether each char of `text` is part of a token, rather than whitespace
+/// between tokens
+fn in_tokens(text: &str) -> Vec<bool> {
+  let mut kept = Vec::with_capacity(text.len());
+  let mut in_string = false;
+  let mut escaped = false;
+  for ch in text.chars() {
+    kept.push(in_string || !ch.is_whitespace());
+    if in_string {
+      if escaped {
+        escaped = false;
+      } else if ch == '\\' {
+        escaped = true;
+      } else if ch == '"' {
+        in_string = false;
+      }
+    } else if ch == '"' {
+      in_string = true;
+    }
+  }
+  kept
+}
+
+/// Char offset in `new` before the same token char as `offset` is in `old`,
+/// so the cursor stays on its token when only whitespace changed
+pub(crate) fn map_offset(old: &str, new: &str, offset: usize) -> usize {
+  let before = in_tokens(old)
+    .iter()
+    .take(offset)
+    .filter(|kept| **kept)
+    .count();
+  let new_kept = in_tokens(new);
+  new_kept
+    .iter()
+    .enumerate()
+    .filter(|(_, kept)| **kept)
+    .nth(before)
+    .map_or(new_kept.len(), |(offset, _)| offset)
+}
+
+/// Forget the result for the previous document
+pub(crate) fn clear(editor: &mut Editor) {
+  editor.json_validation = JsonValidation::default();
+}
+
+/// Parse the document again once edits have settled
+pub(crate) fn schedule_refresh(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let document = editor.document.read(cx);
+  let version = document.version();
+  if editor.json_validation.checked_version == Some(version) {
+    return;
+  }
+  if !is_json(document.file_extension()) || large_file::is_degraded(editor) {
+    return;
+  }
+
+  let snapshot = document.snapshot();
+  editor.json_validation.refresh_task = Some(cx.spawn(async move |this, cx| {
+    cx.background_executor().timer(REFRESH_DEBOUNCE).await;
+
+    let error = cx
+      .background_executor()
+      .spawn(async move { validate(&snapshot.text()) })
+      .await;
+
+    let _ = this.update(cx, |editor, cx| {
+      // Byte offsets are only valid for the text they were computed from
+      if editor.document.read(cx).version() == version {
+        apply(editor, error, version, cx);
+      }
+    });
+  }));
+}
+
+fn apply(editor: &mut Editor, error: Option<ParseError>, version: usize, cx: &mut Context<Editor>) {
+  if let Some(id) = editor.json_validation.decoration.take() {
+    editor.decorations.remove(id);
+  }
+  if let Some(error) = error {
+    let document = editor.document.read(cx);
+    let start = document.byte_to_char(error.range.start);
+    let end = document.byte_to_char(error.range.end);
+    // An error at the end of the document marks its last character
+    let range = document.anchor_range(start.min(end.saturating_sub(1))..end.max(start));
+    let style = DecorationStyle {
+      underline: Some(error_color()),
+      virtual_text: Some(VirtualText {
+        text: error.message.into(),
+        color: error_color(),
+      }),
+      ..Default::default()
+    };
+    editor.json_validation.decoration = Some(editor.decorations.insert(range, style));
+  }
+  editor.json_validation.checked_version = Some(version);
+  cx.notify();
+}
+
+fn error_color() -> Hsla {
+  Hsla {
+    h: 0.0,
+    s: 0.75,
+    l: 0.55,
+    a: 1.0,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const TEXT: &str = "{ \"b\": [1, 2.50, {}],\n  \"a\": \"x, {y}\" , \"e\": [ ] }";
+
+  #[test]
+  fn test_format_keeps_order_and_numbers() {
+    assert_eq!(
+      format(TEXT).unwrap(),
+      "{\n  \"b\": [\n    1,\n    2.50,\n    {}\n  ],\n  \"a\": \"x, {y}\",\n  \"e\": []\n}\n"
+    );
+    assert_eq!(
+      minify(TEXT).unwrap(),
+      "{\"b\":[1,2.50,{}],\"a\":\"x, {y}\",\"e\":[]}"
+    );
+    assert_eq!(minify(&format(TEXT).unwrap()), minify(TEXT));
+  }
+
+  #[test]
+  fn test_validate() {
+    assert_eq!(validate(TEXT), None);
+    let text = "{\n  \"a\": 1,\n  \"b\" 2\n}";
+    let error = validate(text).unwrap();
+    assert_eq!(&text[error.range.clone()], "2");
+    assert_eq!(error.message, "expected `:`");
+    assert!(format(text).is_err());
+  }
+
+  #[test]
+  fn test_map_offset() {
+    let old = "{ \"a\" :  1 }";
+    let new = "{\"a\":1}";
+    // Before the `1`, past the whitespace
+    assert_eq!(map_offset(old, new, 9), 5);
+    assert_eq!(map_offset(old, new, 2), 1);
+    assert_eq!(map_offset(old, new, old.len()), new.len());
+  }
+}
@@ -0,0 +1,223 @@
+//! Jumping to a word on screen by typing its label
+//!
+//! Starting a jump labels every word start in the viewport with two letters,
+//! drawn over the text. Typing a label moves the cursor to its word, or
+//! extends the selection there when the letters are typed with shift; any
+//! other key cancels the jump.
+
+use gpui::{Context, KeyDownEvent, Window};
+
+use crate::{boundaries, editor::Editor, wrap_map};
+
+/// Letters labels are made of, easiest to reach first
+const LABEL_ALPHABET: &[u8] = b"asdfghjklqwertyuiopzxcvbnm";
+
+/// A word start on screen and the label typed to reach it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct JumpTarget {
+  pub label: String,
+  /// Char offset of the word start in the document
+  pub offset: usize,
+}
+
+pub(crate) struct JumpLabels {
+  targets: Vec<JumpTarget>,
+  /// Letters of a label typed so far
+  typed: String,
+}
+
+impl JumpLabels {
+  /// Targets still reachable with the letters typed so far
+  pub fn visible_targets(&self) -> impl Iterator<Item = &JumpTarget> {
+    self
+      .targets
+      .iter()
+      .filter(|target| target.label.starts_with(&self.typed))
+  }
+
+  pub fn typed(&self) -> &str {
+    &self.typed
+  }
+}
+
+/// The `ix`th two-letter label
+fn label(ix: usize) -> String {
+  let count = LABEL_ALPHABET.len();
+  [LABEL_ALPHABET[ix / count], LABEL_ALPHABET[ix % count]]
+    .iter()
+    .map(|&byte| byte as char)
+    .collect()
+}
+
+/// Byte offsets of the words starting in `line`
+pub(crate) fn word_starts(line: &str) -> Vec<usize> {
+  boundaries::word_bounds(line)
+    .into_iter()
+    .filter(|&offset| {
+      line[offset..]
+        .chars()
+        .next()
+        .is_some_and(|ch| ch.is_alphanumeric() || ch == '_')
+    })
+    .collect()
+}
+
+pub(crate) fn is_active(editor: &Editor) -> bool {
+  editor.jump.is_some()
+}
+
+/// Label the word starts of the visible lines
+pub(crate) fn start(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let max_targets = LABEL_ALPHABET.len() * LABEL_ALPHABET.len();
+  let visible_rows = (editor.viewport_height / editor.line_height).ceil() as usize;
+  wrap_map::refresh(editor, cx);
+  let first_row = editor.scroll_offset_y.floor() as usize;
+  let last_row = first_row + visible_rows.max(1) - 1;
+  let document = editor.document.read(cx);
+  let first_line = wrap_map::line_at_row(editor, first_row, cx).min(document.len_lines());
+  let last_line = (wrap_map::line_at_row(editor, last_row, cx) + 1).min(document.len_lines());
+
+  let mut offsets = Vec::new();
+  for line_idx in first_line..last_line {
+    let line = document.line_content(line_idx).unwrap_or_default();
+    let line_start = document.line_to_char(line_idx);
+    offsets.extend(
+      word_starts(&line)
+        .into_iter()
+        .map(|byte| line_start + line[..byte].chars().count()),
+    );
+  }
+  let targets = offsets
+    .into_iter()
+    .take(max_targets)
+    .enumerate()
+    .map(|(ix, offset)| JumpTarget {
+      label: label(ix),
+      offset,
+    })
+    .collect::<Vec<_>>();
+
+  editor.jump = (!targets.is_empty()).then(|| JumpLabels {
+    targets,
+    typed: String::new(),
+  });
+  cx.notify();
+}
+
+pub(crate) fn cancel(editor: &mut Editor, cx: &mut Context<Editor>) {
+  if editor.jump.take().is_some() {
+    cx.notify();
+  }
+}
+
+/// Handle a key while labels are shown; every key is taken
+pub(crate) fn key_down(
+  editor: &mut Editor,
+  event: &KeyDownEvent,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  let keystroke = &event.keystroke;
+  let modifiers = &keystroke.modifiers;
+  let letter = keystroke
+    .key
+    .chars()
+    .next()
+    .filter(|_| keystroke.key.chars().count() == 1)
+    .filter(|_| !modifiers.control && !modifiers.alt && !modifiers.platform);
+  match letter {
+    Some(letter) => type_letter(editor, letter, modifiers.shift, cx),
+    None => cancel(editor, cx),
+  }
+  if !is_active(editor) {
+    editor.ensure_cursor_visible(window, cx);
+  }
+  cx.stop_propagation();
+}
+
+/// Add a letter to the typed label, jumping once it names a target
+fn type_letter(editor: &mut Editor, letter: char, extend: bool, cx: &mut Context<Editor>) {
+  let Some(jump) = editor.jump.as_mut() else {
+    return;
+  };
+  jump.typed.push(letter.to_ascii_lowercase());
+  let offset = jump
+    .targets
+    .iter()
+    .find(|target| target.label == jump.typed)
+    .map(|target| target.offset);
+  let reachable = jump.visible_targets().next().is_some();
+
+  if let Some(offset) = offset {
+    editor.jump = None;
+    let offset = offset.min(editor.document.read(cx).len());
+    editor.target_column = None;
+    if extend {
+      editor.select_to(offset, cx);
+    } else {
+      editor.move_to(offset, cx);
+    }
+  } else if !reachable {
+    cancel(editor, cx);
+  }
+  cx.notify();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::{TestAppContext, px};
+
+  #[test]
+  fn test_labels() {
+    assert_eq!(label(0), "aa");
+    assert_eq!(label(1), "as");
+    assert_eq!(label(26), "sa");
+    assert_eq!(label(26 * 26 - 1), "mm");
+  }
+
+  #[test]
+  fn test_word_starts() {
+    assert_eq!(word_starts("let x = foo(bar);"), [0, 4, 8, 12]);
+    assert_eq!(word_starts("  héllo_world 42"), [2, 15]);
+    assert!(word_starts("  -> {}").is_empty());
+  }
+
+  #[gpui::test]
+  fn test_jump(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "one two\nthree four\n");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.viewport_height = px(200.);
+      editor.line_height = px(20.);
+
+      start(editor, cx);
+      let labels: Vec<_> = editor
+        .jump
+        .as_ref()
+        .unwrap()
+        .visible_targets()
+        .map(|target| (target.label.as_str(), target.offset))
+        .collect();
+      assert_eq!(labels, [("aa", 0), ("as", 4), ("ad", 8), ("af", 14)]);
+
+      type_letter(editor, 'a', false, cx);
+      assert_eq!(editor.jump.as_ref().unwrap().visible_targets().count(), 4);
+      type_letter(editor, 'd', false, cx);
+      assert!(!is_active(editor));
+      assert_eq!(editor.cursor_offset(), 8);
+
+      // Shifted labels extend the selection
+      start(editor, cx);
+      type_letter(editor, 'A', true, cx);
+      type_letter(editor, 'F', true, cx);
+      assert_eq!(editor.selected_range, 8..14);
+
+      // A letter no label starts with cancels
+      start(editor, cx);
+      type_letter(editor, 'z', false, cx);
+      assert!(!is_active(editor));
+      assert_eq!(editor.selected_range, 8..14);
+    });
+  }
+}
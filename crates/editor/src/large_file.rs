@@ -0,0 +1,141 @@
+//! Degraded mode for documents too big to edit comfortably
+//!
+//! A file above the size or line count set in `LargeFileThresholds` opens
+//! with syntax highlighting and the whole-document checks for unbalanced
+//! brackets and JSON errors turned off, so giant logs and datasets stay
+//! responsive. A banner above the text says so, with a button turning them
+//! back on for the document.
+
+use gpui::{ClickEvent, Context, Global, IntoElement, div, prelude::*, px};
+
+use crate::{balance, editor::Editor, json};
+
+/// Sizes above which a document is opened in degraded mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LargeFileThresholds {
+  pub max_bytes: usize,
+  pub max_lines: usize,
+}
+
+impl Default for LargeFileThresholds {
+  fn default() -> Self {
+    Self {
+      max_bytes: 50 * 1024 * 1024,
+      max_lines: 1_000_000,
+    }
+  }
+}
+
+impl Global for LargeFileThresholds {}
+
+impl LargeFileThresholds {
+  pub(crate) fn exceeded_by(&self, bytes: usize, lines: usize) -> bool {
+    bytes > self.max_bytes || lines > self.max_lines
+  }
+}
+
+pub(crate) fn is_degraded(editor: &Editor) -> bool {
+  editor.large_file
+}
+
+/// Turn features off when the document just opened is above the thresholds
+pub(crate) fn check(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let thresholds = cx
+    .try_global::<LargeFileThresholds>()
+    .copied()
+    .unwrap_or_default();
+  let document = editor.document.read(cx);
+  editor.large_file = thresholds.exceeded_by(document.len_bytes(), document.len_lines());
+  if editor.large_file {
+    editor
+      .document
+      .update(cx, |document, cx| document.pause_highlighting(cx));
+  }
+}
+
+/// Turn the features back on for the open document
+pub(crate) fn force_enable(editor: &mut Editor, cx: &mut Context<Editor>) {
+  if !editor.large_file {
+    return;
+  }
+  editor.large_file = false;
+  editor
+    .document
+    .update(cx, |document, cx| document.resume_highlighting(cx));
+  balance::schedule_refresh(editor, cx);
+  json::schedule_refresh(editor, cx);
+  cx.notify();
+}
+
+pub(crate) fn render_banner(editor: &Editor, cx: &mut Context<Editor>) -> Option<impl IntoElement> {
+  if !editor.large_file {
+    return None;
+  }
+  let color = editor.theme.warning();
+  Some(
+    div()
+      .w_full()
+      .flex()
+      .flex_row()
+      .items_center()
+      .gap_2()
+      .px_2()
+      .py_1()
+      .bg(editor.theme.gutter_background())
+      .text_size(px(12.0))
+      .text_color(color)
+      .child("Large file: syntax highlighting and bracket checks are off")
+      .child(
+        div()
+          .id("large-file-enable")
+          .px_1()
+          .rounded_sm()
+          .cursor_pointer()
+          .border_1()
+          .border_color(color)
+          .child("Enable Anyway")
+          .on_click(cx.listener(|editor, _: &ClickEvent, _, cx| {
+            force_enable(editor, cx);
+          })),
+      ),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[test]
+  fn test_thresholds() {
+    let thresholds = LargeFileThresholds::default();
+    assert!(!thresholds.exceeded_by(1024, 10));
+    assert!(thresholds.exceeded_by(60 * 1024 * 1024, 10));
+    assert!(thresholds.exceeded_by(1024, 2_000_000));
+  }
+
+  #[gpui::test]
+  fn test_large_file_degrades_until_forced(cx: &mut TestAppContext) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("big.rs");
+    std::fs::write(&path, "fn main() {\n".repeat(20)).unwrap();
+
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "");
+    ctx.cx.update(|cx| {
+      cx.set_global(LargeFileThresholds {
+        max_bytes: usize::MAX,
+        max_lines: 10,
+      })
+    });
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.open_file(path.clone(), cx).unwrap();
+      assert!(is_degraded(editor));
+      assert!(editor.document.read(cx).highlighting_paused());
+
+      force_enable(editor, cx);
+      assert!(!is_degraded(editor));
+      assert!(!editor.document.read(cx).highlighting_paused());
+    });
+  }
+}
@@ -1,14 +1,123 @@
 mod actions;
+mod alternate;
+mod ansi;
+mod appearance;
+mod auto_pair;
+mod balance;
 mod boundaries;
+mod code_cells;
+mod command_palette;
+mod comment;
+mod conflicts;
+mod coverage;
+mod crash_recovery;
 mod cursor_blink;
+mod dap;
+mod decorations;
+mod describe;
+mod diff;
+mod display_map;
 mod document;
+mod echo;
 mod editor;
 mod editor_element;
+mod elastic_tabs;
+mod escape;
+mod files;
+mod find;
+mod focus_mode;
+mod folds;
+mod font;
+mod generators;
+mod git_status;
 mod gutter_element;
+mod hex_edit;
+mod hex_element;
+mod ime;
+mod indent;
+mod invisibles;
+mod json;
+mod jump;
+mod large_file;
+mod line_height;
+mod line_index;
+mod markdown;
+mod menus;
+mod motion;
+mod multi_cursor;
+mod panels;
+mod paste_format;
+mod path_completion;
+mod paths;
+mod permalink;
+mod plugins;
+mod preview;
+mod progress;
+mod project_search;
+mod quick_diff;
+mod reflow;
+mod ruler;
+mod scratch;
+mod scrollbar_element;
+mod search;
+mod search_history;
+mod selection_layout;
+mod selection_lock;
+mod shaping;
+mod shortcuts;
+mod tail;
+#[cfg(feature = "test-interface")]
+mod test_interface;
+mod test_runner;
+mod trace;
+mod transform;
+mod trust;
+mod unicode_input;
+mod window_title;
+mod word_count;
+mod word_diff;
+mod workspace;
+mod wrap_map;
 
 pub use actions::*;
+pub use ansi::{AnsiStyle, FileLink, StyledOutput, find_file_links};
+pub use buffer::Anchor;
+pub use code_cells::{Interpreter, Interpreters};
+pub use coverage::{FileCoverage, parse_lcov};
+pub use crash_recovery::CrashRecovery;
 pub use cursor_blink::CursorBlink;
-pub use document::Document;
+pub use dap::{DebugAdapter, DebugAdapters};
+pub use decorations::{DecorationId, DecorationStyle, GutterIcon, VirtualText};
+pub use diff::{DiffAlgorithm, diff, line_edits};
+pub use display_map::{DisplayMap, DisplayPoint, FOLD_PLACEHOLDER, Inlay, InlayChunk};
+pub use document::{Document, DocumentSnapshot};
 pub use editor::Editor;
 pub use editor_element::{EditorElement, PositionMap};
-pub use gutter_element::GutterElement;
+pub use find::FindSettings;
+pub use folds::FoldSettings;
+pub use font::EditorFont;
+pub use generators::TimestampFormat;
+pub use git_status::{FolderBadge, GitFileStatus, GitStatuses, RepositoryStatus, watch_git_status};
+pub use gutter_element::{GutterElement, GutterLane, GutterLayout};
+pub use hex_element::HexElement;
+pub use large_file::LargeFileThresholds;
+pub use line_height::LineHeightSettings;
+pub use line_index::ViewportLineIndex;
+pub use menus::{app_menus, history_labels};
+pub use panels::{PanelKind, Panels, focus_ring};
+pub use paste_format::{PasteFormat, PasteFormatSettings};
+pub use plugins::{Plugins, load_plugins};
+pub use progress::{BackgroundTasks, ProgressHandle};
+pub use project_search::{FileReplace, ProjectReplace, ReplaceMatch};
+pub use ruler::WrapColumnSettings;
+pub use scratch::scratch_buffers;
+pub use scrollbar_element::{ScrollbarAxis, ScrollbarElement};
+pub use search::SearchQuery;
+pub use search_history::{HistoryItem, SearchHistory, SearchHistoryEntry};
+pub use tail::{TailPattern, default_tail_patterns};
+#[cfg(feature = "test-interface")]
+pub use test_interface::{PaintedFrame, TestInterface};
+pub use test_runner::TestStatus;
+pub use trust::WorkspaceTrust;
+pub use window_title::window_title;
+pub use workspace::{RootSettings, Workspace, WorkspaceRoot};
@@ -0,0 +1,79 @@
+//! Line height per language
+//!
+//! Prose reads better with more room between lines than code does, so the
+//! line height can be set as a multiple of the font size for each language.
+//! Languages without a setting keep the window's line height. The height is
+//! part of the editor's text style, which the layout, the viewport and the
+//! position map all take their row height from.
+
+use std::collections::HashMap;
+
+use gpui::{App, Global, TextStyle, relative};
+
+use crate::{editor::Editor, ruler};
+
+#[derive(Default)]
+pub struct LineHeightSettings {
+  by_language: HashMap<String, f32>,
+}
+
+impl Global for LineHeightSettings {}
+
+impl LineHeightSettings {
+  /// Line height for `language`, as a multiple of the font size
+  pub fn get(&self, language: &str) -> Option<f32> {
+    self.by_language.get(language).copied()
+  }
+
+  pub fn set(&mut self, language: impl Into<String>, multiplier: f32) {
+    self.by_language.insert(language.into(), multiplier);
+  }
+}
+
+/// Set the line height of `style` for the editor's language, if one is set
+pub(crate) fn apply(editor: &Editor, style: &mut TextStyle, cx: &App) {
+  let multiplier = cx
+    .try_global::<LineHeightSettings>()
+    .and_then(|settings| settings.get(ruler::language_key(editor, cx)));
+  if let Some(multiplier) = multiplier.filter(|multiplier| *multiplier > 0.0) {
+    style.line_height = relative(multiplier).into();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{document::Document, editor::tests::EditorTestContext};
+  use gpui::{AppContext, TestAppContext, px};
+
+  #[gpui::test]
+  fn test_line_height_for_language(cx: &mut TestAppContext) {
+    let ctx = EditorTestContext::with_text(cx.clone(), "");
+    let mut cx = ctx.cx.clone();
+    cx.update(|cx| {
+      let mut settings = LineHeightSettings::default();
+      settings.set("plain", 1.6);
+      settings.set("rust", 0.0);
+      cx.set_global(settings);
+    });
+
+    let rust = cx.new(|cx| {
+      let document = cx.new(|cx| Document::new("fn main() {}", Some("rs"), cx));
+      Editor::with_document(document, cx)
+    });
+    cx.update(|cx| {
+      let mut style = TextStyle {
+        font_size: px(10.0).into(),
+        ..Default::default()
+      };
+      apply(ctx.editor.read(cx), &mut style, cx);
+      assert_eq!(style.line_height_in_pixels(px(16.0)), px(16.0));
+
+      // Languages without a usable setting keep the style's line height
+      let mut code = TextStyle::default();
+      let line_height = code.line_height;
+      apply(rust.read(cx), &mut code, cx);
+      assert_eq!(code.line_height, line_height);
+    });
+  }
+}
@@ -0,0 +1,141 @@
+//! Line start offsets of the lines in the viewport
+//!
+//! Painting and hit-testing convert between offsets and lines many times per
+//! frame. The starts of the visible lines are collected once and reused until
+//! the text changes or the view scrolls; lookups outside the indexed lines, or
+//! made after an edit, fall back to the rope.
+
+use std::ops::Range;
+
+use crate::document::Document;
+
+/// Starts of a range of lines, read at one document version
+#[derive(Debug, Default)]
+pub struct ViewportLineIndex {
+  /// Document version the offsets were read at
+  version: usize,
+  first_line: usize,
+  /// Start of each indexed line, followed by the end of the last one
+  starts: Vec<usize>,
+  /// Whether the last indexed line is the last line of the document
+  ends_document: bool,
+}
+
+impl ViewportLineIndex {
+  pub fn new(document: &Document, lines: Range<usize>) -> Self {
+    let len_lines = document.len_lines();
+    let end_line = lines.end.min(len_lines);
+    let first_line = lines.start.min(end_line);
+
+    let mut starts: Vec<usize> = (first_line..end_line)
+      .map(|line| document.line_to_char(line))
+      .collect();
+    starts.push(if end_line < len_lines {
+      document.line_to_char(end_line)
+    } else {
+      document.len()
+    });
+
+    Self {
+      version: document.version(),
+      first_line,
+      starts,
+      ends_document: end_line == len_lines,
+    }
+  }
+
+  /// Whether the index still describes `lines` of the document
+  pub fn covers(&self, document: &Document, lines: &Range<usize>) -> bool {
+    self.is_current(document)
+      && self.first_line == lines.start
+      && self.first_line + self.line_count() == lines.end.min(document.len_lines())
+  }
+
+  fn is_current(&self, document: &Document) -> bool {
+    self.version == document.version()
+  }
+
+  fn line_count(&self) -> usize {
+    self.starts.len().saturating_sub(1)
+  }
+
+  fn indexed_line_range(&self, line: usize) -> Option<Range<usize>> {
+    let idx = line.checked_sub(self.first_line)?;
+    if idx >= self.line_count() {
+      return None;
+    }
+    Some(self.starts[idx]..self.starts[idx + 1])
+  }
+
+  fn indexed_line(&self, offset: usize) -> Option<usize> {
+    let (&first, &last) = (self.starts.first()?, self.starts.last()?);
+    if self.line_count() == 0 || offset < first {
+      return None;
+    }
+    if offset >= last {
+      // The end of the document belongs to its last line
+      return (offset == last && self.ends_document)
+        .then(|| self.first_line + self.line_count() - 1);
+    }
+    Some(self.first_line + self.starts.partition_point(|&start| start <= offset) - 1)
+  }
+
+  pub fn line_range(&self, document: &Document, line: usize) -> Option<Range<usize>> {
+    match self.indexed_line_range(line) {
+      Some(range) if self.is_current(document) => Some(range),
+      _ => document.line_range(line),
+    }
+  }
+
+  pub fn line_to_char(&self, document: &Document, line: usize) -> usize {
+    match self.indexed_line_range(line) {
+      Some(range) if self.is_current(document) => range.start,
+      _ => document.line_to_char(line),
+    }
+  }
+
+  pub fn char_to_line(&self, document: &Document, offset: usize) -> usize {
+    match self.indexed_line(offset) {
+      Some(line) if self.is_current(document) => line,
+      _ => document.char_to_line(offset),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use gpui::{AppContext, TestAppContext};
+
+  #[gpui::test]
+  fn test_lookups_match_document(cx: &mut TestAppContext) {
+    let doc = cx.new(|cx| Document::new("zero\none\n\nthree\nfour", None, cx));
+    doc.read_with(cx, |doc, _| {
+      for lines in [0..2, 1..4, 3..10, 0..0] {
+        let index = ViewportLineIndex::new(doc, lines.clone());
+        assert!(index.covers(doc, &lines));
+        for line in 0..doc.len_lines() {
+          assert_eq!(index.line_range(doc, line), doc.line_range(line));
+          assert_eq!(index.line_to_char(doc, line), doc.line_to_char(line));
+        }
+        for offset in 0..=doc.len() {
+          assert_eq!(index.char_to_line(doc, offset), doc.char_to_line(offset));
+        }
+      }
+    });
+  }
+
+  #[gpui::test]
+  fn test_edit_makes_index_stale(cx: &mut TestAppContext) {
+    let doc = cx.new(|cx| Document::new("one\ntwo\nthree", None, cx));
+    let index = doc.read_with(cx, |doc, _| ViewportLineIndex::new(doc, 0..3));
+
+    doc.update(cx, |doc, cx| doc.replace(0..0, "zero\n", cx));
+    doc.read_with(cx, |doc, _| {
+      assert!(!index.covers(doc, &(0..3)));
+      // Stale offsets are never returned
+      assert_eq!(index.line_to_char(doc, 1), 5);
+      assert_eq!(index.char_to_line(doc, 5), 1);
+    });
+  }
+}
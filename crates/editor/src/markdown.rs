@@ -0,0 +1,217 @@
+//! Markdown list continuation on Enter, and link-aware paste
+//!
+//! Enter at the end of a list item starts the next item with the same
+//! bullet, the following number or an unchecked checkbox. Enter on an item
+//! with no text ends the list by removing its marker.
+//!
+//! Pasting a URL over selected text turns the selection into a link, and
+//! pasting the path of an image can insert an image link instead.
+
+/// File extensions of Markdown documents
+const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown"];
+
+pub(crate) fn is_markdown(file_ext: Option<&str>) -> bool {
+  file_ext.is_some_and(|ext| MARKDOWN_EXTENSIONS.contains(&ext))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ListEnter {
+  /// Start a new item with this prefix
+  Continue(String),
+  /// The item only has a marker, remove it
+  EndList,
+}
+
+/// What Enter does at the end of `line`, if it is a list item
+pub(crate) fn list_enter(line: &str) -> Option<ListEnter> {
+  let rest = line.trim_start_matches([' ', '\t']);
+  let indent = &line[..line.len() - rest.len()];
+
+  let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+  let (next_marker, rest) = if digits > 0 {
+    // Ordered lists are at most 9 digits long in CommonMark
+    if digits > 9 {
+      return None;
+    }
+    let number: u32 = rest[..digits].parse().ok()?;
+    let delimiter = rest[digits..]
+      .chars()
+      .next()
+      .filter(|ch| matches!(ch, '.' | ')'))?;
+    (format!("{}{}", number + 1, delimiter), &rest[digits + 1..])
+  } else {
+    let bullet = rest
+      .chars()
+      .next()
+      .filter(|ch| matches!(ch, '-' | '*' | '+'))?;
+    (bullet.to_string(), &rest[1..])
+  };
+  // A marker must be followed by a space, which also rules out `---` and `**bold**`
+  let rest = rest.strip_prefix(' ')?;
+
+  let (checkbox, text) = match rest.get(..3) {
+    Some("[ ]" | "[x]" | "[X]") if rest[3..].is_empty() || rest[3..].starts_with(' ') => {
+      ("[ ] ", &rest[3..])
+    }
+    _ => ("", rest),
+  };
+
+  if text.trim().is_empty() {
+    Some(ListEnter::EndList)
+  } else {
+    Some(ListEnter::Continue(format!(
+      "{}{} {}",
+      indent, next_marker, checkbox
+    )))
+  }
+}
+
+/// Extensions of image files that Markdown renderers display inline
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "svg", "webp"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum SmartPaste {
+  /// Replace the selection with this link to the pasted URL
+  Link(String),
+  /// The pasted text is the path of an image, with this image link for it
+  Image(String),
+}
+
+/// A single `http(s)` or `mailto` URL without whitespace
+fn is_url(text: &str) -> bool {
+  ["https://", "http://", "mailto:"]
+    .iter()
+    .any(|scheme| text.len() > scheme.len() && text.starts_with(scheme))
+    && !text.contains(char::is_whitespace)
+}
+
+/// Link destination, in angle brackets if it has spaces or parentheses
+fn destination(url: &str) -> String {
+  if url.contains([' ', '(', ')']) {
+    format!("<{}>", url)
+  } else {
+    url.to_string()
+  }
+}
+
+/// Path of an image file in pasted text, as a plain path or a `file://` URL
+fn image_path(text: &str) -> Option<&str> {
+  let path = text.strip_prefix("file://").unwrap_or(text);
+  if path.is_empty() || path.contains('\n') {
+    return None;
+  }
+  let (_, ext) = path.rsplit_once('.')?;
+  IMAGE_EXTENSIONS
+    .contains(&ext.to_ascii_lowercase().as_str())
+    .then_some(path)
+}
+
+/// What pasting `clipboard` over `selection` does differently in Markdown
+pub(crate) fn smart_paste(selection: &str, clipboard: &str) -> Option<SmartPaste> {
+  let clipboard = clipboard.trim();
+  if !selection.is_empty() && !selection.contains('\n') && is_url(clipboard) {
+    return Some(SmartPaste::Link(format!(
+      "[{}]({})",
+      selection,
+      destination(clipboard)
+    )));
+  }
+
+  let path = image_path(clipboard)?;
+  let alt = if selection.is_empty() {
+    let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    file_name
+      .rsplit_once('.')
+      .map_or(file_name, |(stem, _)| stem)
+  } else {
+    selection
+  };
+  Some(SmartPaste::Image(format!(
+    "![{}]({})",
+    alt,
+    destination(path)
+  )))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn continues(line: &str) -> Option<String> {
+    match list_enter(line) {
+      Some(ListEnter::Continue(prefix)) => Some(prefix),
+      _ => None,
+    }
+  }
+
+  #[test]
+  fn test_continue_bullets_and_numbers() {
+    assert_eq!(continues("- item"), Some("- ".to_string()));
+    assert_eq!(continues("  * nested"), Some("  * ".to_string()));
+    assert_eq!(continues("+ item"), Some("+ ".to_string()));
+    assert_eq!(continues("9. ninth"), Some("10. ".to_string()));
+    assert_eq!(continues("\t3) third"), Some("\t4) ".to_string()));
+  }
+
+  #[test]
+  fn test_continue_checkboxes_unchecked() {
+    assert_eq!(continues("- [x] done"), Some("- [ ] ".to_string()));
+    assert_eq!(continues("1. [ ] todo"), Some("2. [ ] ".to_string()));
+    // Brackets that aren't a checkbox are item text
+    assert_eq!(continues("- [link](url)"), Some("- ".to_string()));
+  }
+
+  #[test]
+  fn test_empty_items_end_the_list() {
+    assert_eq!(list_enter("- "), Some(ListEnter::EndList));
+    assert_eq!(list_enter("  2. "), Some(ListEnter::EndList));
+    assert_eq!(list_enter("- [ ] "), Some(ListEnter::EndList));
+  }
+
+  #[test]
+  fn test_not_list_items() {
+    assert_eq!(list_enter("plain text"), None);
+    assert_eq!(list_enter("---"), None);
+    assert_eq!(list_enter("**bold**"), None);
+    assert_eq!(list_enter("-"), None);
+    assert_eq!(list_enter("2024 was a year"), None);
+    assert_eq!(list_enter("1234567890. too long"), None);
+  }
+
+  #[test]
+  fn test_url_over_selection_becomes_link() {
+    assert_eq!(
+      smart_paste("the docs", " https://example.com/a_(b)\n"),
+      Some(SmartPaste::Link(
+        "[the docs](<https://example.com/a_(b)>)".to_string()
+      ))
+    );
+    assert_eq!(
+      smart_paste("mail me", "mailto:me@example.com"),
+      Some(SmartPaste::Link(
+        "[mail me](mailto:me@example.com)".to_string()
+      ))
+    );
+    // Without a selection, or with a multi-line one, a URL is pasted as is
+    assert_eq!(smart_paste("", "https://example.com"), None);
+    assert_eq!(smart_paste("one\ntwo", "https://example.com"), None);
+    assert_eq!(smart_paste("text", "https://example.com and more"), None);
+    assert_eq!(smart_paste("text", "https://"), None);
+  }
+
+  #[test]
+  fn test_image_paths() {
+    assert_eq!(
+      smart_paste("", "/Users/me/Desktop/Screen Shot.PNG"),
+      Some(SmartPaste::Image(
+        "![Screen Shot](</Users/me/Desktop/Screen Shot.PNG>)".to_string()
+      ))
+    );
+    assert_eq!(
+      smart_paste("Logo", "file:///tmp/logo.svg"),
+      Some(SmartPaste::Image("![Logo](/tmp/logo.svg)".to_string()))
+    );
+    assert_eq!(smart_paste("", "notes.txt"), None);
+    assert_eq!(smart_paste("", "a.png\nb.png"), None);
+  }
+}
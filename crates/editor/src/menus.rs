@@ -0,0 +1,201 @@
+//! The application's menu bar
+//!
+//! Menu items dispatch the same actions as the key bindings, so the
+//! platform shows each item's current shortcut next to it. An item is
+//! disabled while nothing in the focused view handles its action, which the
+//! editor uses to disable undo, redo, cut and copy when they'd do nothing.
+//! The undo and redo items name the edit they apply to, so the menus are
+//! built again when those change.
+
+use gpui::{App, Menu, MenuItem, OsAction};
+
+use crate::{actions::*, editor::Editor};
+
+/// "Undo" or "Redo" followed by what it applies to
+fn history_label(verb: &str, description: Option<&str>) -> String {
+  match description {
+    Some(description) => format!("{} {}", verb, description),
+    None => verb.to_string(),
+  }
+}
+
+/// Labels of the undo and redo items for the editor's history
+pub fn history_labels(editor: &Editor, cx: &App) -> (String, String) {
+  (
+    history_label("Undo", editor.undo_description(cx)),
+    history_label("Redo", editor.redo_description(cx)),
+  )
+}
+
+/// The menus of the menu bar, with undo and redo items named for `editor`
+pub fn app_menus(editor: &Editor, cx: &App) -> Vec<Menu> {
+  let (undo, redo) = history_labels(editor, cx);
+  vec![
+    Menu {
+      name: "Editor".into(),
+      items: vec![MenuItem::action("Quit", Quit)],
+    },
+    Menu {
+      name: "File".into(),
+      items: vec![
+        MenuItem::action("Open…", Open),
+        MenuItem::action("New Scratch Buffer", NewScratchBuffer),
+        MenuItem::separator(),
+        MenuItem::action("Save", Save),
+        MenuItem::action("Save As…", SaveAs),
+        MenuItem::separator(),
+        MenuItem::action("Add Folder to Workspace…", AddFolderToWorkspace),
+        MenuItem::action("Remove Folder from Workspace", RemoveFolder),
+        MenuItem::separator(),
+        MenuItem::action("Trust Workspace", TrustWorkspace),
+      ],
+    },
+    Menu {
+      name: "Edit".into(),
+      items: vec![
+        MenuItem::os_action(undo, Undo, OsAction::Undo),
+        MenuItem::os_action(redo, Redo, OsAction::Redo),
+        MenuItem::separator(),
+        MenuItem::os_action("Cut", Cut, OsAction::Cut),
+        MenuItem::os_action("Copy", Copy, OsAction::Copy),
+        MenuItem::os_action("Paste", Paste, OsAction::Paste),
+        MenuItem::action("Copy with Line Numbers", CopyWithLineNumbers),
+        MenuItem::action("Copy Permalink", CopyPermalink),
+        MenuItem::separator(),
+        MenuItem::submenu(Menu {
+          name: "Find".into(),
+          items: vec![
+            MenuItem::action("Find…", Find),
+            MenuItem::action("Find and Replace…", Replace),
+            MenuItem::action("Find Next", FindNext),
+            MenuItem::action("Find Previous", FindPrevious),
+          ],
+        }),
+        MenuItem::separator(),
+        MenuItem::action("Indent", Indent),
+        MenuItem::action("Outdent", Outdent),
+        MenuItem::action("Toggle Comment", ToggleComment),
+        MenuItem::action("Reflow Paragraph", ReflowParagraph),
+        MenuItem::action("Insert Unicode Character…", InsertUnicode),
+        MenuItem::submenu(Menu {
+          name: "Suspicious Unicode".into(),
+          items: vec![
+            MenuItem::action("Remove", RemoveSuspiciousUnicode),
+            MenuItem::action("Escape", EscapeSuspiciousUnicode),
+          ],
+        }),
+        MenuItem::submenu(Menu {
+          name: "Insert".into(),
+          items: vec![
+            MenuItem::action("UUID", InsertUuid),
+            MenuItem::action("Timestamp", InsertTimestamp),
+            MenuItem::action("Lorem Ipsum", InsertLoremIpsum),
+          ],
+        }),
+        MenuItem::action("Format Code on Paste", ToggleFormatOnPaste),
+        MenuItem::separator(),
+        MenuItem::action("Format JSON", FormatJson),
+        MenuItem::action("Minify JSON", MinifyJson),
+        MenuItem::submenu(Menu {
+          name: "Transform".into(),
+          items: vec![
+            MenuItem::action("Base64 Encode", Base64Encode),
+            MenuItem::action("Base64 Decode", Base64Decode),
+            MenuItem::action("URL Encode", UrlEncode),
+            MenuItem::action("URL Decode", UrlDecode),
+            MenuItem::action("JSON Escape", JsonEscape),
+            MenuItem::action("JSON Unescape", JsonUnescape),
+          ],
+        }),
+      ],
+    },
+    Menu {
+      name: "Selection".into(),
+      items: vec![
+        MenuItem::os_action("Select All", SelectAll, OsAction::SelectAll),
+        MenuItem::action("Add Next Occurrence", SelectNextOccurrence),
+        MenuItem::separator(),
+        MenuItem::action("Duplicate Line", DuplicateLine),
+        MenuItem::action("Delete Line", DeleteLine),
+        MenuItem::action("Move Line Up", MoveLineUp),
+        MenuItem::action("Move Line Down", MoveLineDown),
+        MenuItem::separator(),
+        MenuItem::action("Select to Start of Document", SelectCmdUp),
+        MenuItem::action("Select to End of Document", SelectCmdDown),
+        MenuItem::action("Select to Start of Line", SelectCmdLeft),
+        MenuItem::action("Select to End of Line", SelectCmdRight),
+        MenuItem::separator(),
+        MenuItem::action("Lock Selection", ToggleSelectionLock),
+        MenuItem::action("Swap Selection Ends", SwapSelectionEnds),
+      ],
+    },
+    Menu {
+      name: "View".into(),
+      items: vec![
+        MenuItem::action("Command Palette…", ShowCommandPalette),
+        MenuItem::separator(),
+        MenuItem::action("Zoom In", ZoomIn),
+        MenuItem::action("Zoom Out", ZoomOut),
+        MenuItem::action("Zoom to Fit", ZoomToFit),
+        MenuItem::separator(),
+        MenuItem::action("Toggle Dark Mode", ToggleDarkMode),
+        MenuItem::action("Use System Theme", UseSystemTheme),
+        MenuItem::separator(),
+        MenuItem::action("Focus Mode", ToggleFocusMode),
+        MenuItem::action("Word Diff", ToggleWordDiff),
+        MenuItem::action("Coverage Overlay", ToggleCoverageOverlay),
+        MenuItem::action("Hex Mode", ToggleHexMode),
+        MenuItem::action("Soft Wrap", ToggleSoftWrap),
+        MenuItem::action("Elastic Tabstops", ToggleElasticTabstops),
+        MenuItem::action("Code Cells", ToggleCodeCells),
+        MenuItem::separator(),
+        MenuItem::action("Retry Highlighting", RetryHighlighting),
+      ],
+    },
+    Menu {
+      name: "Go".into(),
+      items: vec![
+        MenuItem::action("Start of Document", CmdUp),
+        MenuItem::action("End of Document", CmdDown),
+        MenuItem::action("Jump to Word", JumpToWord),
+        MenuItem::separator(),
+        MenuItem::action("Next Conflict", NextConflict),
+        MenuItem::action("Previous Conflict", PreviousConflict),
+        MenuItem::separator(),
+        MenuItem::action("Alternate File", SwitchToAlternateFile),
+        MenuItem::action("Next Panel", FocusNextPanel),
+      ],
+    },
+    Menu {
+      name: "Help".into(),
+      items: vec![MenuItem::action(
+        "Keyboard Shortcuts",
+        ShowKeyboardShortcuts,
+      )],
+    },
+  ]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[gpui::test]
+  fn test_history_labels(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "a b");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert_eq!(history_labels(editor, cx), ("Undo".into(), "Redo".into()));
+      assert!(!editor.can_undo(cx));
+
+      editor.selected_range = 1..2;
+      crate::unicode_input::insert(editor, "→", cx);
+      assert!(editor.can_undo(cx));
+      assert_eq!(
+        history_labels(editor, cx),
+        ("Undo Insert Character".into(), "Redo".into())
+      );
+    });
+  }
+}
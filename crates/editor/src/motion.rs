@@ -0,0 +1,388 @@
+//! Coalescing of cursor movements repeated within a frame
+//!
+//! Holding an arrow key delivers movement actions faster than frames are
+//! drawn. Identical movements are counted instead of applied one by one, and
+//! the editor applies them as a single multi-step movement when it renders,
+//! or earlier if another key or the mouse needs the real cursor position.
+//! Every other action applies them before it runs, whether it came from a
+//! key, a held key's repeat or the menu.
+
+use gpui::{Action, Context, Window};
+
+use crate::{
+  boundaries, display_map::DisplayPoint, editor::Editor, hex_edit, multi_cursor, selection_lock,
+  wrap_map,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Motion {
+  Up,
+  Down,
+  Left,
+  Right,
+  SelectUp,
+  SelectDown,
+  SelectLeft,
+  SelectRight,
+}
+
+impl Motion {
+  /// The movement that selects along the way
+  fn extending(self) -> Self {
+    match self {
+      Motion::Up => Motion::SelectUp,
+      Motion::Down => Motion::SelectDown,
+      Motion::Left => Motion::SelectLeft,
+      Motion::Right => Motion::SelectRight,
+      motion => motion,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct PendingMotion {
+  motion: Motion,
+  steps: usize,
+}
+
+/// Count `motion` into `pending`, returning a different earlier motion to apply first
+fn push(pending: &mut Option<PendingMotion>, motion: Motion) -> Option<PendingMotion> {
+  match pending {
+    Some(pending) if pending.motion == motion => {
+      pending.steps += 1;
+      None
+    }
+    _ => pending.replace(PendingMotion { motion, steps: 1 }),
+  }
+}
+
+/// Queue a movement, it is applied on the next render or flush
+pub(crate) fn queue(
+  editor: &mut Editor,
+  motion: Motion,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  let motion = if selection_lock::is_locked(editor, cx) {
+    motion.extending()
+  } else {
+    motion
+  };
+  if let Some(previous) = push(&mut editor.pending_motion, motion) {
+    apply(editor, previous.motion, previous.steps, cx);
+    editor.ensure_cursor_visible(window, cx);
+  }
+  cx.notify();
+}
+
+/// Apply queued movements, before anything relies on the cursor position
+pub(crate) fn flush(editor: &mut Editor, window: &Window, cx: &mut Context<Editor>) {
+  if let Some(pending) = editor.pending_motion.take() {
+    apply(editor, pending.motion, pending.steps, cx);
+    editor.ensure_cursor_visible(window, cx);
+  }
+}
+
+/// `handler` run after the queued movements are applied, for an action that
+/// isn't a movement to queue
+pub(crate) fn flushed<A: Action>(
+  handler: fn(&mut Editor, &A, &mut Window, &mut Context<Editor>),
+) -> impl Fn(&mut Editor, &A, &mut Window, &mut Context<Editor>) + 'static {
+  move |editor, action, window, cx| {
+    flush(editor, window, cx);
+    handler(editor, action, window, cx);
+  }
+}
+
+/// Move a page of rows up or down, selecting along if `select`, and scroll
+/// the view as far so the cursor keeps its place on screen
+pub(crate) fn page(
+  editor: &mut Editor,
+  up: bool,
+  select: bool,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  flush(editor, window, cx);
+  let select = select || selection_lock::is_locked(editor, cx);
+  page_by(editor, up, select, cx);
+  editor.ensure_cursor_visible(window, cx);
+  cx.notify();
+}
+
+fn page_by(editor: &mut Editor, up: bool, select: bool, cx: &mut Context<Editor>) {
+  // The rows that fully fit in the viewport
+  let rows = ((editor.viewport_height / editor.line_height).floor() as usize).max(1);
+  multi_cursor::for_each(editor, cx, |editor, cx| {
+    let cursor = vertical_target(editor, up, rows, cx);
+    if select {
+      editor.select_to(cursor, cx);
+    } else {
+      editor.move_to(cursor, cx);
+    }
+  });
+
+  let max_scroll = editor.scrollable_rows(cx).saturating_sub(rows) as f32;
+  let scroll = if up {
+    editor.scroll_offset_y - rows as f32
+  } else {
+    editor.scroll_offset_y + rows as f32
+  };
+  editor.scroll_offset_y = scroll.clamp(0.0, max_scroll);
+}
+
+fn apply(editor: &mut Editor, motion: Motion, steps: usize, cx: &mut Context<Editor>) {
+  if editor.hex_mode {
+    return hex_edit::apply_motion(editor, motion, steps, cx);
+  }
+  multi_cursor::for_each(editor, cx, |editor, cx| match motion {
+    Motion::Up | Motion::Down => {
+      let cursor = vertical_target(editor, motion == Motion::Up, steps, cx);
+      editor.move_to(cursor, cx);
+    }
+    Motion::SelectUp | Motion::SelectDown => {
+      let cursor = vertical_target(editor, motion == Motion::SelectUp, steps, cx);
+      editor.select_to(cursor, cx);
+    }
+    Motion::Left => {
+      editor.target_column = None;
+      for _ in 0..steps {
+        let cursor = if editor.selected_range.is_empty() {
+          boundaries::previous_boundary(editor, editor.cursor_offset(), cx)
+        } else {
+          editor.selected_range.start
+        };
+        editor.move_to(cursor, cx);
+      }
+    }
+    Motion::Right => {
+      editor.target_column = None;
+      for _ in 0..steps {
+        let cursor = if editor.selected_range.is_empty() {
+          boundaries::next_boundary(editor, editor.selected_range.end, cx)
+        } else {
+          editor.selected_range.end
+        };
+        editor.move_to(cursor, cx);
+      }
+    }
+    Motion::SelectLeft | Motion::SelectRight => {
+      editor.target_column = None;
+      for _ in 0..steps {
+        let cursor = if motion == Motion::SelectLeft {
+          boundaries::previous_boundary(editor, editor.cursor_offset(), cx)
+        } else {
+          boundaries::next_boundary(editor, editor.cursor_offset(), cx)
+        };
+        editor.select_to(cursor, cx);
+      }
+    }
+  });
+}
+
+/// Offset `steps` rows above or below the cursor, keeping the target column
+///
+/// Rows are lines unless they are wrapped. Moving past the first or last row
+/// goes to the start or end of the document.
+fn vertical_target(editor: &mut Editor, up: bool, steps: usize, cx: &Context<Editor>) -> usize {
+  wrap_map::refresh(editor, cx);
+  if let Some(map) = wrap_map::rows(editor, cx) {
+    let cursor = map.to_display_point(editor.cursor_offset());
+    let column = editor.target_column.unwrap_or(cursor.column);
+    let target = if up {
+      cursor.row.checked_sub(steps)
+    } else {
+      Some(cursor.row + steps).filter(|row| *row < map.row_count())
+    }
+    .map(|row| map.to_offset(DisplayPoint::new(row, column)));
+    editor.target_column = target.map(|_| column);
+    return target.unwrap_or_else(|| {
+      if up {
+        0
+      } else {
+        editor.document.read(cx).len()
+      }
+    });
+  }
+
+  let document = editor.document.read(cx);
+  let cursor_offset = editor.cursor_offset();
+  let current_line = document.char_to_line(cursor_offset);
+
+  let target_line = if up {
+    current_line.checked_sub(steps)
+  } else {
+    Some(current_line + steps).filter(|line| *line < document.len_lines())
+  };
+  let Some(target_line) = target_line else {
+    editor.target_column = None;
+    return if up { 0 } else { document.len() };
+  };
+
+  let target_column = *editor
+    .target_column
+    .get_or_insert_with(|| cursor_offset - document.line_to_char(current_line));
+  let target_start = document.line_to_char(target_line);
+  let target_len = document.line_content(target_line).unwrap_or_default().len();
+  target_start + target_column.min(target_len)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[test]
+  fn test_push_coalesces_identical_motions() {
+    let mut pending = None;
+    assert_eq!(push(&mut pending, Motion::Down), None);
+    assert_eq!(push(&mut pending, Motion::Down), None);
+    assert_eq!(push(&mut pending, Motion::Down), None);
+    assert_eq!(
+      push(&mut pending, Motion::Right),
+      Some(PendingMotion {
+        motion: Motion::Down,
+        steps: 3
+      })
+    );
+    assert_eq!(
+      pending,
+      Some(PendingMotion {
+        motion: Motion::Right,
+        steps: 1
+      })
+    );
+  }
+
+  #[test]
+  fn test_extending_motions() {
+    assert_eq!(Motion::Up.extending(), Motion::SelectUp);
+    assert_eq!(Motion::Right.extending(), Motion::SelectRight);
+    assert_eq!(Motion::SelectLeft.extending(), Motion::SelectLeft);
+  }
+
+  #[gpui::test]
+  fn test_actions_apply_queued_motions(cx: &mut TestAppContext) {
+    let window = cx.add_window(|_, cx| {
+      let document = cx.new(|cx| crate::document::Document::new("abcd", None, cx));
+      Editor::with_document(document, cx)
+    });
+    window
+      .update(cx, |editor, window, cx| {
+        editor.pending_motion = Some(PendingMotion {
+          motion: Motion::SelectRight,
+          steps: 2,
+        });
+        // Delete removes the selection the queued movement made
+        let delete = flushed(crate::actions::delete);
+        delete(editor, &crate::actions::Delete, window, cx);
+        assert_eq!(editor.document.read(cx).text(), "cd");
+        assert!(editor.pending_motion.is_none());
+      })
+      .unwrap();
+  }
+
+  #[gpui::test]
+  fn test_multi_step_vertical_motion(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "abcd\nab\nabcd\nabcd");
+    ctx.set_cursor(3);
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      // The target column survives the short line in between
+      apply(editor, Motion::Down, 2, cx);
+      assert_eq!(editor.cursor_offset(), 11);
+
+      apply(editor, Motion::SelectUp, 5, cx);
+      assert_eq!(editor.selected_range, 0..11);
+      assert!(editor.selection_reversed);
+
+      apply(editor, Motion::Down, 10, cx);
+      assert_eq!(editor.cursor_offset(), 17);
+    });
+  }
+
+  #[gpui::test]
+  fn test_every_cursor_keeps_its_target_column(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "abcd\nab\nabcd\nab\nabcd");
+    ctx.set_cursor(11);
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      multi_cursor::add_cursor(editor, 3, cx);
+      apply(editor, Motion::Down, 1, cx);
+      assert_eq!(editor.cursor_offset(), 7);
+      assert_eq!(multi_cursor::extra_ranges(editor, cx), [(15..15, false)]);
+
+      // Both cursors go back to their column past the short lines
+      apply(editor, Motion::Down, 1, cx);
+      assert_eq!(editor.cursor_offset(), 11);
+      assert_eq!(multi_cursor::extra_ranges(editor, cx), [(19..19, false)]);
+    });
+  }
+
+  #[gpui::test]
+  fn test_vertical_motion_moves_by_row(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "abcdefgh\nij");
+    ctx.set_cursor(1);
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.soft_wrap = true;
+      wrap_map::set_column(editor, Some(3), cx);
+
+      apply(editor, Motion::Down, 1, cx);
+      assert_eq!(editor.cursor_offset(), 4);
+      apply(editor, Motion::Down, 2, cx);
+      assert_eq!(editor.cursor_offset(), 10);
+      apply(editor, Motion::SelectUp, 1, cx);
+      assert_eq!(editor.selected_range, 7..10);
+      apply(editor, Motion::Up, 5, cx);
+      assert_eq!(editor.cursor_offset(), 0);
+    });
+  }
+
+  #[gpui::test]
+  fn test_page_motion(cx: &mut TestAppContext) {
+    let text = vec!["abcd"; 10].join("\n");
+    let mut ctx = EditorTestContext::with_text(cx.clone(), &text);
+    ctx.set_cursor(2);
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.line_height = gpui::px(10.);
+      editor.viewport_height = gpui::px(35.);
+
+      page_by(editor, false, false, cx);
+      assert_eq!(editor.cursor_offset(), 17);
+      assert_eq!(editor.scroll_offset_y, 3.0);
+
+      page_by(editor, false, true, cx);
+      assert_eq!(editor.selected_range, 17..32);
+
+      // The view stops at the last page
+      page_by(editor, false, false, cx);
+      assert_eq!(editor.cursor_offset(), 47);
+      assert_eq!(editor.scroll_offset_y, 7.0);
+
+      page_by(editor, true, false, cx);
+      assert_eq!(editor.cursor_offset(), 32);
+      assert_eq!(editor.scroll_offset_y, 4.0);
+    });
+  }
+
+  #[gpui::test]
+  fn test_multi_step_horizontal_motion(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "hello world");
+    ctx.set_cursor(2);
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      apply(editor, Motion::SelectRight, 3, cx);
+      assert_eq!(editor.selected_range, 2..5);
+
+      // The first step collapses the selection
+      apply(editor, Motion::Right, 2, cx);
+      assert_eq!(editor.cursor_offset(), 6);
+
+      apply(editor, Motion::Left, 20, cx);
+      assert_eq!(editor.cursor_offset(), 0);
+    });
+  }
+}
@@ -0,0 +1,466 @@
+//! Cursors and selections edited together with the primary one
+//!
+//! `selected_range` stays the primary selection, the one scrolled to and
+//! reported to the platform's text input. Alt-click and SelectNextOccurrence
+//! add more, kept as anchors so an edit at one moves the others along.
+//! Typing, deleting, Enter and the cursor movements run once per selection,
+//! the primary last, as one undoable edit. Copy and cut take every selection,
+//! one per line, and paste hands them back one per cursor when the counts
+//! match; other actions use the primary.
+//! Selections that come to overlap are merged.
+
+use std::ops::Range;
+
+use buffer::Anchor;
+use gpui::{App, Context, Window};
+
+use crate::{boundaries::word_range_at_offset, editor::Editor, search::SearchQuery};
+
+#[derive(Clone, Debug)]
+pub(crate) struct ExtraSelection {
+  range: Range<Anchor>,
+  reversed: bool,
+  // Column vertical movement returns to, as `Editor::target_column`
+  target_column: Option<usize>,
+}
+
+pub(crate) fn is_active(editor: &Editor) -> bool {
+  !editor.extra_selections.is_empty()
+}
+
+/// Keep only the primary selection
+pub(crate) fn clear(editor: &mut Editor, cx: &mut Context<Editor>) {
+  if !editor.extra_selections.is_empty() {
+    editor.extra_selections.clear();
+    cx.notify();
+  }
+}
+
+/// Ranges of the extra selections, for painting
+pub(crate) fn extra_ranges(editor: &Editor, cx: &App) -> Vec<(Range<usize>, bool)> {
+  let document = editor.document.read(cx);
+  editor
+    .extra_selections
+    .iter()
+    .map(|selection| {
+      (
+        document.resolve_anchor_range(&selection.range),
+        selection.reversed,
+      )
+    })
+    .collect()
+}
+
+/// Keep the current selection and put the primary cursor at `offset`, for an alt-click
+pub(crate) fn add_cursor(editor: &mut Editor, offset: usize, cx: &mut Context<Editor>) {
+  push_primary(editor, cx);
+  editor.selected_range = offset..offset;
+  editor.selection_reversed = false;
+  editor.target_column = None;
+  merge_overlapping(editor, cx);
+  cx.notify();
+}
+
+/// Select the word at the cursor, or add a selection at the next occurrence
+/// of the selected text, wrapping around the end of the document
+///
+/// Occurrences are searched for on a background thread, in a snapshot.
+pub(crate) fn select_next_occurrence(
+  editor: &mut Editor,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  editor.target_column = None;
+  if editor.selected_range.is_empty() {
+    let (start, end) = word_range_at_offset(editor, editor.cursor_offset(), cx);
+    editor.selected_range = start..end;
+    editor.selection_reversed = false;
+    editor.ensure_cursor_visible(window, cx);
+    cx.notify();
+    return;
+  }
+
+  let selected = editor.selected_range.clone();
+  let taken: Vec<_> = extra_ranges(editor, cx)
+    .into_iter()
+    .map(|(range, _)| range)
+    .chain([selected.clone()])
+    .collect();
+  let document = editor.document.read(cx);
+  let needle = document.slice_to_string(selected.clone());
+  let snapshot = document.snapshot();
+  let version = snapshot.version();
+  cx.spawn_in(window, async move |this, cx| {
+    let matches = cx
+      .background_executor()
+      .spawn(async move {
+        let text = snapshot.text();
+        let buffer = &snapshot.buffer;
+        SearchQuery::new(needle)
+          .case_sensitive(true)
+          .find_all(&text)
+          .into_iter()
+          .map(|range| buffer.byte_to_char(range.start)..buffer.byte_to_char(range.end))
+          .filter(|range| !taken.contains(range))
+          .collect::<Vec<_>>()
+      })
+      .await;
+
+    let _ = this.update_in(cx, |editor, window, cx| {
+      // Matches are only valid for the text and selection they were searched for
+      if editor.document.read(cx).version() != version || editor.selected_range != selected {
+        return;
+      }
+      let Some(next) = next_after(&matches, selected.end) else {
+        return;
+      };
+      push_primary(editor, cx);
+      editor.selected_range = next;
+      editor.selection_reversed = false;
+      editor.ensure_cursor_visible(window, cx);
+      cx.notify();
+    });
+  })
+  .detach();
+}
+
+/// First of `matches` starting at or after `offset`, or the first of all
+fn next_after(matches: &[Range<usize>], offset: usize) -> Option<Range<usize>> {
+  matches
+    .iter()
+    .find(|range| range.start >= offset)
+    .or(matches.first())
+    .cloned()
+}
+
+/// Where each selection comes in document order, listed in the order
+/// `for_each` visits them
+pub(crate) fn document_order(editor: &Editor, cx: &App) -> Vec<usize> {
+  let mut ranges: Vec<_> = extra_ranges(editor, cx)
+    .into_iter()
+    .map(|(range, _)| range)
+    .collect();
+  ranges.push(editor.selected_range.clone());
+  let mut order: Vec<usize> = (0..ranges.len()).collect();
+  order.sort_by_key(|&ix| (ranges[ix].start, ranges[ix].end));
+  let mut positions = vec![0; ranges.len()];
+  for (position, ix) in order.into_iter().enumerate() {
+    positions[ix] = position;
+  }
+  positions
+}
+
+/// Run `f` at every selection in turn, the primary last
+///
+/// `f` sees each one as `selected_range` and leaves it where it should go.
+/// Edits made by `f` are undone together, back to the primary selection.
+pub(crate) fn for_each(
+  editor: &mut Editor,
+  cx: &mut Context<Editor>,
+  mut f: impl FnMut(&mut Editor, &mut Context<Editor>),
+) {
+  // Taken while `f` runs, so edits it makes through the input handler only
+  // apply to the selection at hand
+  let extras = std::mem::take(&mut editor.extra_selections);
+  if extras.is_empty() {
+    return f(editor, cx);
+  }
+
+  let undo_depth = editor.document.read(cx).buffer.undo_depth();
+  let selection_before = editor.selected_range.clone();
+  let primary = ExtraSelection {
+    range: editor.anchor_range(editor.selected_range.clone(), cx),
+    reversed: editor.selection_reversed,
+    target_column: editor.target_column,
+  };
+
+  let mut done = Vec::with_capacity(extras.len());
+  for extra in extras {
+    editor.selected_range = editor.document.read(cx).resolve_anchor_range(&extra.range);
+    editor.selection_reversed = extra.reversed;
+    editor.target_column = extra.target_column;
+    f(editor, cx);
+    done.push(ExtraSelection {
+      range: editor.anchor_range(editor.selected_range.clone(), cx),
+      reversed: editor.selection_reversed,
+      target_column: editor.target_column,
+    });
+  }
+
+  editor.selected_range = editor
+    .document
+    .read(cx)
+    .resolve_anchor_range(&primary.range);
+  editor.selection_reversed = primary.reversed;
+  editor.target_column = primary.target_column;
+  f(editor, cx);
+
+  editor.extra_selections = done;
+  merge_overlapping(editor, cx);
+  group_edits(editor, undo_depth, selection_before, cx);
+  cx.notify();
+}
+
+/// Make the transactions recorded past `undo_depth` a single undo step,
+/// restoring `selection_before`
+fn group_edits(
+  editor: &mut Editor,
+  undo_depth: usize,
+  selection_before: Range<usize>,
+  cx: &mut Context<Editor>,
+) {
+  let selection_after = editor.selected_range.clone();
+  editor.document.update(cx, |document, _| {
+    document.group_past(undo_depth, selection_before, selection_after)
+  });
+}
+
+fn push_primary(editor: &mut Editor, cx: &App) {
+  let range = editor.anchor_range(editor.selected_range.clone(), cx);
+  editor.extra_selections.push(ExtraSelection {
+    range,
+    reversed: editor.selection_reversed,
+    target_column: editor.target_column,
+  });
+}
+
+fn merge_overlapping(editor: &mut Editor, cx: &App) {
+  let mut selections = extra_ranges(editor, cx);
+  selections.push((editor.selected_range.clone(), editor.selection_reversed));
+  let primary = selections.len() - 1;
+  let (merged, primary) = merge(selections, primary);
+  if merged.len() == editor.extra_selections.len() + 1 {
+    return;
+  }
+
+  let document = editor.document.read(cx);
+  let mut extras = Vec::with_capacity(merged.len() - 1);
+  for (ix, (range, reversed)) in merged.into_iter().enumerate() {
+    if ix == primary {
+      editor.selected_range = range;
+      editor.selection_reversed = reversed;
+    } else {
+      extras.push(ExtraSelection {
+        range: document.anchor_range(range),
+        reversed,
+        target_column: None,
+      });
+    }
+  }
+  editor.extra_selections = extras;
+}
+
+/// Merge overlapping `selections` and cursors at the same place, in document
+/// order, returning where the one holding `primary` ended up
+///
+/// A merged selection takes the direction of the primary if it holds it.
+fn merge(
+  selections: Vec<(Range<usize>, bool)>,
+  primary: usize,
+) -> (Vec<(Range<usize>, bool)>, usize) {
+  let mut order: Vec<usize> = (0..selections.len()).collect();
+  order.sort_by_key(|&ix| (selections[ix].0.start, selections[ix].0.end));
+
+  let mut merged: Vec<(Range<usize>, bool)> = Vec::new();
+  let mut merged_primary = 0;
+  for ix in order {
+    let (range, reversed) = selections[ix].clone();
+    let overlaps = merged.last().is_some_and(|(last, _)| {
+      range.start < last.end
+        || range.start == last.start
+        || (range.start == last.end && (range.is_empty() || last.is_empty()))
+    });
+    if overlaps {
+      let last = merged.last_mut().unwrap();
+      last.0.end = last.0.end.max(range.end);
+      if ix == primary {
+        last.1 = reversed;
+        merged_primary = merged.len() - 1;
+      }
+    } else {
+      if ix == primary {
+        merged_primary = merged.len();
+      }
+      merged.push((range, reversed));
+    }
+  }
+  (merged, merged_primary)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{actions, document::Document};
+  use gpui::{AppContext, EntityInputHandler, TestAppContext, WindowHandle};
+
+  #[test]
+  fn test_merge() {
+    let (merged, primary) = merge(vec![(4..4, false), (0..2, false), (1..3, true)], 2);
+    assert_eq!(merged, [(0..3, true), (4..4, false)]);
+    assert_eq!(primary, 0);
+
+    // Cursors at the same place and at the end of a selection merge,
+    // selections only touching don't
+    let (merged, primary) = merge(vec![(2..2, false), (2..2, false), (3..5, false)], 0);
+    assert_eq!(merged, [(2..2, false), (3..5, false)]);
+    assert_eq!(primary, 0);
+    let (merged, _) = merge(vec![(0..2, false), (2..4, false), (4..4, false)], 0);
+    assert_eq!(merged, [(0..2, false), (2..4, false)]);
+  }
+
+  #[test]
+  fn test_next_after_wraps() {
+    let matches = [0..3, 8..11];
+    assert_eq!(next_after(&matches, 4), Some(8..11));
+    assert_eq!(next_after(&matches, 9), Some(0..3));
+    assert_eq!(next_after(&[], 0), None);
+  }
+
+  fn open_editor(cx: &mut TestAppContext, text: &str) -> WindowHandle<Editor> {
+    cx.add_window(|_, cx| {
+      let document = cx.new(|cx| Document::new(text, None, cx));
+      Editor::with_document(document, cx)
+    })
+  }
+
+  #[gpui::test]
+  fn test_typing_at_every_cursor(cx: &mut TestAppContext) {
+    let window = open_editor(cx, "one\ntwo\nthree");
+    window
+      .update(cx, |editor, window, cx| {
+        editor.document.update(cx, |doc, _| {
+          doc.buffer.set_group_interval(std::time::Duration::ZERO)
+        });
+        add_cursor(editor, 4, cx);
+        add_cursor(editor, 8, cx);
+        editor.replace_text_in_range(None, "- ", window, cx);
+        assert_eq!(editor.document.read(cx).text(), "- one\n- two\n- three");
+        assert_eq!(editor.selected_range, 12..12);
+        assert_eq!(extra_ranges(editor, cx), [(2..2, false), (8..8, false)]);
+
+        actions::backspace(editor, &actions::Backspace, window, cx);
+        assert_eq!(editor.document.read(cx).text(), "-one\n-two\n-three");
+
+        // The edit at every cursor is undone at once
+        actions::undo(editor, &actions::Undo, window, cx);
+        assert_eq!(editor.document.read(cx).text(), "- one\n- two\n- three");
+        assert_eq!(editor.selected_range, 12..12);
+        assert!(!is_active(editor));
+      })
+      .unwrap();
+  }
+
+  #[gpui::test]
+  fn test_copy_and_cut_every_selection(cx: &mut TestAppContext) {
+    let window = open_editor(cx, "one two three");
+    window
+      .update(cx, |editor, window, cx| {
+        editor.selected_range = 8..13;
+        push_primary(editor, cx);
+        editor.selected_range = 0..3;
+        actions::copy(editor, &actions::Copy, window, cx);
+        assert_eq!(
+          cx.read_from_clipboard().and_then(|item| item.text()),
+          Some("one\nthree".to_string())
+        );
+
+        actions::cut(editor, &actions::Cut, window, cx);
+        assert_eq!(editor.document.read(cx).text(), " two ");
+        assert_eq!(
+          cx.read_from_clipboard().and_then(|item| item.text()),
+          Some("one\nthree".to_string())
+        );
+
+        // Both pieces come back with a single undo
+        actions::undo(editor, &actions::Undo, window, cx);
+        assert_eq!(editor.document.read(cx).text(), "one two three");
+      })
+      .unwrap();
+  }
+
+  #[gpui::test]
+  fn test_paste_one_piece_per_cursor(cx: &mut TestAppContext) {
+    let window = open_editor(cx, "one two\n\n");
+    window
+      .update(cx, |editor, window, cx| {
+        editor.selected_range = 4..7;
+        push_primary(editor, cx);
+        editor.selected_range = 0..3;
+        actions::copy(editor, &actions::Copy, window, cx);
+
+        // The primary cursor is first in the document and gets the first piece
+        clear(editor, cx);
+        editor.selected_range = 9..9;
+        push_primary(editor, cx);
+        editor.selected_range = 8..8;
+        actions::paste(editor, &actions::Paste, window, cx);
+        assert_eq!(editor.document.read(cx).text(), "one two\none\ntwo");
+
+        // With a different number of cursors every one gets the whole text
+        clear(editor, cx);
+        editor.selected_range = 0..0;
+        actions::paste(editor, &actions::Paste, window, cx);
+        assert_eq!(editor.document.read(cx).text(), "one\ntwoone two\none\ntwo");
+      })
+      .unwrap();
+  }
+
+  #[gpui::test]
+  fn test_cursors_merge_when_they_meet(cx: &mut TestAppContext) {
+    let window = open_editor(cx, "abc");
+    window
+      .update(cx, |editor, window, cx| {
+        editor.selected_range = 1..1;
+        add_cursor(editor, 2, cx);
+        actions::cmd_left(editor, &actions::CmdLeft, window, cx);
+        assert_eq!(editor.selected_range, 0..0);
+        assert!(!is_active(editor));
+      })
+      .unwrap();
+  }
+
+  #[gpui::test]
+  fn test_select_next_occurrence(cx: &mut TestAppContext) {
+    let window = open_editor(cx, "foo bar foo baz foo");
+    let select_next = |cx: &mut TestAppContext| {
+      window
+        .update(cx, |editor, window, cx| {
+          select_next_occurrence(editor, window, cx)
+        })
+        .unwrap();
+      cx.run_until_parked();
+    };
+
+    window
+      .update(cx, |editor, _, _| editor.selected_range = 9..9)
+      .unwrap();
+    select_next(cx);
+    window
+      .update(cx, |editor, _, _| {
+        assert_eq!(editor.selected_range, 8..11);
+        assert!(!is_active(editor));
+      })
+      .unwrap();
+
+    select_next(cx);
+    window
+      .update(cx, |editor, _, _| assert_eq!(editor.selected_range, 16..19))
+      .unwrap();
+    select_next(cx);
+    window
+      .update(cx, |editor, _, cx| {
+        assert_eq!(editor.selected_range, 0..3);
+        assert_eq!(extra_ranges(editor, cx), [(8..11, false), (16..19, false)]);
+      })
+      .unwrap();
+
+    // Every occurrence is selected
+    select_next(cx);
+    window
+      .update(cx, |editor, _, cx| {
+        assert_eq!(editor.selected_range, 0..3);
+        assert_eq!(extra_ranges(editor, cx).len(), 2);
+      })
+      .unwrap();
+  }
+}
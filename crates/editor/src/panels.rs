@@ -0,0 +1,96 @@
+//! Moving keyboard focus between the panels of a window
+//!
+//! A window registers the focus handles of its panels in the order focus
+//! cycles through them, then handles the focus actions with them. Panels
+//! draw a focus ring while they hold focus, so the keyboard's position is
+//! always visible.
+
+use gpui::{App, FocusHandle, Hsla, Styled, Window};
+
+/// A kind of panel focus can be moved to directly
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PanelKind {
+  Editor,
+  FileTree,
+  Terminal,
+}
+
+#[derive(Default)]
+pub struct Panels {
+  panels: Vec<(PanelKind, FocusHandle)>,
+}
+
+impl Panels {
+  /// Add a panel after the ones registered before it
+  pub fn register(&mut self, kind: PanelKind, focus_handle: FocusHandle) {
+    self.panels.push((kind, focus_handle));
+  }
+
+  /// The panel holding focus, also when a child of it has it
+  pub fn focused(&self, window: &Window, cx: &App) -> Option<PanelKind> {
+    self
+      .panels
+      .iter()
+      .find(|(_, handle)| handle.contains_focused(window, cx))
+      .map(|(kind, _)| *kind)
+  }
+
+  /// Focus the first panel of `kind`, returning whether there is one
+  pub fn focus(&self, kind: PanelKind, window: &mut Window, cx: &mut App) -> bool {
+    let Some((_, handle)) = self.panels.iter().find(|(panel, _)| *panel == kind) else {
+      return false;
+    };
+    window.focus(handle, cx);
+    true
+  }
+
+  /// Focus the panel after the focused one, wrapping around
+  pub fn focus_next(&self, window: &mut Window, cx: &mut App) {
+    let focused = self
+      .panels
+      .iter()
+      .position(|(_, handle)| handle.contains_focused(window, cx));
+    if let Some(ix) = next_index(focused, self.panels.len()) {
+      window.focus(&self.panels[ix].1, cx);
+    }
+  }
+}
+
+fn next_index(focused: Option<usize>, len: usize) -> Option<usize> {
+  if len == 0 {
+    return None;
+  }
+  Some(focused.map_or(0, |ix| (ix + 1) % len))
+}
+
+fn focus_ring_color() -> Hsla {
+  Hsla {
+    h: 210.0 / 360.0,
+    s: 0.8,
+    l: 0.55,
+    a: 1.0,
+  }
+}
+
+/// Border a panel shows, colored while `focused` so layouts don't shift
+pub fn focus_ring<E: Styled>(element: E, focused: bool) -> E {
+  let color = if focused {
+    focus_ring_color()
+  } else {
+    gpui::transparent_black()
+  };
+  element.border_2().border_color(color)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_next_index() {
+    assert_eq!(next_index(None, 0), None);
+    assert_eq!(next_index(None, 3), Some(0));
+    assert_eq!(next_index(Some(1), 3), Some(2));
+    assert_eq!(next_index(Some(2), 3), Some(0));
+  }
+}
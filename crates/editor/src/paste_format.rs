@@ -0,0 +1,239 @@
+//! Formatting code pasted into a language document
+//!
+//! Each language can have pasted lines re-indented to the line they land
+//! on, or piped through an external formatter first. Either way, the
+//! formatted text is what gets inserted, so the paste stays a single edit
+//! that one undo removes. Single-line pastes are inserted as they are.
+//! Formatters don't run in a restricted workspace, where pasted code is only
+//! re-indented, and their output lands where the paste started even if the
+//! cursor moved while they ran.
+
+use std::{
+  collections::HashMap,
+  io::Write,
+  process::{Command, Stdio},
+  thread,
+};
+
+use gpui::{Context, Global, Window};
+
+use crate::{actions::paste_text, editor::Editor, progress, trust};
+
+/// How pasted code is formatted for a language
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PasteFormat {
+  /// Shift the pasted lines to the indentation of the line pasted into
+  Reindent,
+  /// Pipe the pasted text through a program and its arguments, then
+  /// re-indent its output
+  Command(Vec<String>),
+}
+
+#[derive(Default)]
+pub struct PasteFormatSettings {
+  by_language: HashMap<String, PasteFormat>,
+}
+
+impl Global for PasteFormatSettings {}
+
+impl PasteFormatSettings {
+  pub fn get(&self, language: &str) -> Option<&PasteFormat> {
+    self.by_language.get(language)
+  }
+
+  pub fn set(&mut self, language: impl Into<String>, format: PasteFormat) {
+    self.by_language.insert(language.into(), format);
+  }
+
+  pub fn remove(&mut self, language: &str) -> Option<PasteFormat> {
+    self.by_language.remove(language)
+  }
+}
+
+fn leading_whitespace(line: &str) -> &str {
+  &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// `text` with its lines after the first moved to `indent`, keeping their
+/// indentation relative to each other; the first line loses its own
+/// indentation when it is pasted at the start of a line's text
+pub(crate) fn reindent(text: &str, indent: &str, at_line_start: bool) -> String {
+  let lines: Vec<&str> = text.split_inclusive('\n').collect();
+  let is_blank = |line: &str| line.trim().is_empty();
+  // A first line copied from the middle of one has no indentation to count
+  let first_indent = leading_whitespace(lines.first().copied().unwrap_or_default());
+  let base = lines
+    .iter()
+    .skip(1)
+    .filter(|line| !is_blank(line))
+    .map(|line| leading_whitespace(line).chars().count())
+    .chain((!first_indent.is_empty()).then(|| first_indent.chars().count()))
+    .min()
+    .unwrap_or(0);
+
+  let mut result = String::new();
+  for (ix, line) in lines.iter().enumerate() {
+    if ix == 0 {
+      result.push_str(if at_line_start {
+        &line[first_indent.len()..]
+      } else {
+        line
+      });
+    } else if is_blank(line) {
+      // Keep only the line break
+      result.push_str(&line[line.trim_end_matches(['\r', '\n']).len()..]);
+    } else {
+      let removed: usize = line.chars().take(base).map(char::len_utf8).sum();
+      result.push_str(indent);
+      result.push_str(&line[removed..]);
+    }
+  }
+  result
+}
+
+fn run_formatter(command: &[String], text: &str) -> Result<String, String> {
+  let (program, args) = command.split_first().ok_or("no formatter command")?;
+  let mut child = Command::new(program)
+    .args(args)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .map_err(|err| format!("{}: {}", program, err))?;
+  // Writing while the output is read keeps a formatter that answers before
+  // it has read everything from filling its output pipe and waiting on us
+  let mut stdin = child.stdin.take().ok_or("no stdin")?;
+  let input = text.to_string();
+  let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+  let output = child.wait_with_output().map_err(|err| err.to_string())?;
+  writer
+    .join()
+    .map_err(|_| "writing to the formatter panicked".to_string())?
+    .map_err(|err| err.to_string())?;
+  if !output.status.success() {
+    return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+  }
+  let mut formatted = String::from_utf8(output.stdout).map_err(|err| err.to_string())?;
+  // Formatters end files with a line break the paste may not have had
+  if !text.ends_with('\n') {
+    formatted.truncate(formatted.trim_end_matches(['\r', '\n']).len());
+  }
+  Ok(formatted)
+}
+
+/// Indentation of the cursor's line, and whether only it precedes the cursor
+fn paste_indent(editor: &Editor, cx: &Context<Editor>) -> (String, bool) {
+  let document = editor.document.read(cx);
+  let start = editor.selected_range.start;
+  let line_start = document.line_to_char(document.char_to_line(start));
+  let before = document.slice_to_string(line_start..start);
+  let indent = leading_whitespace(&before).to_string();
+  let at_line_start = indent.len() == before.len();
+  (indent, at_line_start)
+}
+
+/// Paste `text`, formatted as set for the document's language
+pub(crate) fn paste(
+  editor: &mut Editor,
+  text: String,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  let format = editor
+    .document
+    .read(cx)
+    .language_name()
+    .and_then(|language| cx.try_global::<PasteFormatSettings>()?.get(language))
+    .cloned();
+  let Some(format) = format.filter(|_| text.trim_end().contains('\n')) else {
+    paste_text(editor, &text, window, cx);
+    return;
+  };
+
+  let (indent, at_line_start) = paste_indent(editor, cx);
+  match format {
+    PasteFormat::Reindent => {
+      paste_text(editor, &reindent(&text, &indent, at_line_start), window, cx);
+    }
+    // Programs only run in trusted workspaces
+    PasteFormat::Command(_) if trust::is_restricted(editor, cx) => {
+      paste_text(editor, &reindent(&text, &indent, at_line_start), window, cx);
+    }
+    PasteFormat::Command(command) => {
+      let progress = progress::start("Formatting pasted code", cx);
+      let document = editor.document.clone();
+      let target = document
+        .read(cx)
+        .anchor_range(editor.selected_range.clone());
+      cx.spawn_in(window, async move |this, cx| {
+        let unformatted = text.clone();
+        let formatted = cx
+          .background_executor()
+          .spawn(async move {
+            // The formatter sees the code at its own indentation
+            let dedented = reindent(&text, "", true);
+            run_formatter(&command, &dedented).unwrap_or_else(|err| {
+              eprintln!("Failed to format the pasted code: {}", err);
+              dedented
+            })
+          })
+          .await;
+        let _ = this.update_in(cx, |editor, window, cx| {
+          progress::finish(&progress, cx);
+          // Nothing is pasted into another document opened meanwhile
+          if editor.document != document {
+            return;
+          }
+          // Cancelling pastes the code as it was copied
+          let text = if progress.is_cancelled() {
+            unformatted
+          } else {
+            reindent(&formatted, &indent, at_line_start)
+          };
+          // Where the paste started, with the indentation it had there
+          editor.selected_range = document.read(cx).resolve_anchor_range(&target);
+          editor.selection_reversed = false;
+          paste_text(editor, &text, window, cx);
+        });
+      })
+      .detach();
+    }
+  }
+}
+
+/// Turn re-indenting pasted code on or off for the document's language
+pub(crate) fn toggle(editor: &Editor, cx: &mut Context<Editor>) {
+  let Some(language) = editor.document.read(cx).language_name() else {
+    return;
+  };
+  let settings = cx.default_global::<PasteFormatSettings>();
+  if settings.remove(language).is_none() {
+    settings.set(language, PasteFormat::Reindent);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_reindent() {
+    let block = "if x {\n        body();\n\n    }\n";
+    assert_eq!(
+      reindent(block, "\t", true),
+      "if x {\n\t    body();\n\n\t}\n"
+    );
+    // Lines copied with the first one's indentation
+    assert_eq!(reindent("  a\n    b\n  c", "", true), "a\n  b\nc");
+    // Pasted after other text, the first line stays as it is
+    assert_eq!(reindent("  a\n  b", "  ", false), "  a\n  b");
+  }
+
+  #[test]
+  fn test_run_formatter_with_large_input() {
+    // More than a pipe holds, echoed back while it is still being written
+    let text = "x\n".repeat(1 << 20);
+    let command = ["cat".to_string()];
+    assert_eq!(run_formatter(&command, &text).unwrap(), text);
+  }
+}
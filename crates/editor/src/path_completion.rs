@@ -0,0 +1,248 @@
+//! Completing file paths typed inside strings
+//!
+//! While the cursor is in a string literal holding a relative path, the
+//! entries of the directory typed so far that start with the last segment
+//! are offered below the cursor, directories first. Paths starting with
+//! `./` or `../` are relative to the document's file, others to the
+//! workspace root it is in. Accepting a directory leaves a `/` after it, so
+//! the entries inside it are offered next.
+
+use std::path::{Path, PathBuf};
+
+use gpui::{Context, IntoElement, div, prelude::*, px};
+
+use crate::{editor::Editor, workspace};
+
+/// Entries listed at most
+const MAX_ITEMS: usize = 12;
+
+pub(crate) struct PathCompletion {
+  /// Char offset where the completed segment starts
+  start: usize,
+  /// Cursor offset the items are for; they are stale once it moves
+  cursor: usize,
+  /// Entry names, directories with a trailing `/`
+  items: Vec<String>,
+  selected: usize,
+}
+
+/// The path typed so far when `before_cursor`, the line up to the cursor,
+/// ends inside a string literal that looks like a relative path
+pub(crate) fn path_prefix(before_cursor: &str) -> Option<&str> {
+  let mut open: Option<(usize, char)> = None;
+  let mut escaped = false;
+  for (offset, ch) in before_cursor.char_indices() {
+    match open {
+      Some(_) if escaped => escaped = false,
+      Some(_) if ch == '\\' => escaped = true,
+      Some((_, quote)) if ch == quote => open = None,
+      None if matches!(ch, '"' | '\'' | '`') => open = Some((offset + 1, ch)),
+      _ => {}
+    }
+  }
+  let prefix = &before_cursor[open?.0..];
+  let relative = prefix.starts_with("./") || prefix.starts_with("../") || prefix.contains('/');
+  (relative && !prefix.starts_with('/') && !prefix.contains("://")).then_some(prefix)
+}
+
+/// Entries of the directory `prefix` names under `base` that start with its
+/// last segment; hidden ones only once the segment starts with a dot
+pub(crate) fn complete(base: &Path, prefix: &str) -> Vec<String> {
+  let (dir, partial) = prefix.rsplit_once('/').unwrap_or(("", prefix));
+  let Ok(entries) = std::fs::read_dir(base.join(dir)) else {
+    return Vec::new();
+  };
+  let mut items: Vec<(bool, String)> = entries
+    .filter_map(|entry| {
+      let entry = entry.ok()?;
+      let name = entry.file_name().into_string().ok()?;
+      if !name.starts_with(partial) || (name.starts_with('.') && !partial.starts_with('.')) {
+        return None;
+      }
+      let is_dir = entry.file_type().ok()?.is_dir();
+      Some((!is_dir, if is_dir { name + "/" } else { name }))
+    })
+    .collect();
+  items.sort();
+  items
+    .into_iter()
+    .take(MAX_ITEMS)
+    .map(|(_, name)| name)
+    .collect()
+}
+
+/// Directory relative paths in `prefix` start from
+fn base_dir(editor: &Editor, prefix: &str, cx: &Context<Editor>) -> Option<PathBuf> {
+  let file_dir = editor.file_path()?.parent()?.to_path_buf();
+  if prefix.starts_with("./") || prefix.starts_with("../") {
+    return Some(file_dir);
+  }
+  Some(workspace::root_for_editor(editor, cx).unwrap_or(file_dir))
+}
+
+/// The completion, unless the cursor moved since it was offered
+fn active(editor: &Editor) -> Option<&PathCompletion> {
+  editor.path_completion.as_ref().filter(|completion| {
+    editor.selected_range.is_empty() && editor.cursor_offset() == completion.cursor
+  })
+}
+
+pub(crate) fn is_open(editor: &Editor) -> bool {
+  active(editor).is_some()
+}
+
+pub(crate) fn close(editor: &mut Editor, cx: &mut Context<Editor>) {
+  if editor.path_completion.take().is_some() {
+    cx.notify();
+  }
+}
+
+/// Offer the entries for the path at the cursor, or close when it isn't in one
+pub(crate) fn update(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let cursor = editor.cursor_offset();
+  let document = editor.document.read(cx);
+  let line = document.char_to_line(cursor);
+  let line_start = document.line_to_char(line);
+  let before_cursor = document.slice_to_string(line_start..cursor);
+  let items = editor
+    .selected_range
+    .is_empty()
+    .then(|| path_prefix(&before_cursor))
+    .flatten()
+    .and_then(|prefix| {
+      let base = base_dir(editor, prefix, cx)?;
+      let partial = prefix.rsplit('/').next().unwrap_or(prefix);
+      Some((partial.chars().count(), complete(&base, prefix)))
+    });
+
+  match items {
+    Some((partial_len, items)) if !items.is_empty() => {
+      let start = cursor - partial_len;
+      // Keep the highlighted entry while narrowing within the same segment
+      let selected = editor
+        .path_completion
+        .as_ref()
+        .filter(|completion| completion.start == start)
+        .and_then(|completion| completion.items.get(completion.selected))
+        .and_then(|name| items.iter().position(|item| item == name))
+        .unwrap_or(0);
+      editor.path_completion = Some(PathCompletion {
+        start,
+        cursor,
+        items,
+        selected,
+      });
+      cx.notify();
+    }
+    _ => close(editor, cx),
+  }
+}
+
+/// Move the highlight by `delta`, returning whether a completion was open
+pub(crate) fn select(editor: &mut Editor, delta: isize, cx: &mut Context<Editor>) -> bool {
+  if !is_open(editor) {
+    return false;
+  }
+  if let Some(completion) = editor.path_completion.as_mut() {
+    let len = completion.items.len() as isize;
+    completion.selected = (completion.selected as isize + delta).rem_euclid(len) as usize;
+    cx.notify();
+  }
+  true
+}
+
+/// Replace the typed segment with the highlighted entry, returning whether
+/// a completion was open
+pub(crate) fn accept(editor: &mut Editor, cx: &mut Context<Editor>) -> bool {
+  let Some(completion) = active(editor) else {
+    return false;
+  };
+  let range = completion.start..completion.cursor;
+  let item = completion.items[completion.selected].clone();
+  editor.path_completion = None;
+  editor.selected_range = range;
+  editor.insert_text(&item, "Complete Path", cx);
+  // A directory's entries come next
+  update(editor, cx);
+  true
+}
+
+pub(crate) fn render(editor: &Editor) -> Option<impl IntoElement> {
+  let completion = active(editor)?;
+  let theme = &editor.theme;
+  Some(
+    div()
+      .min_w(px(200.0))
+      .flex()
+      .flex_col()
+      .p_1()
+      .rounded_md()
+      .border_1()
+      .border_color(theme.line_number())
+      .bg(theme.gutter_background())
+      .text_size(px(12.0))
+      .children(completion.items.iter().enumerate().map(|(ix, item)| {
+        div()
+          .px_1()
+          .rounded_sm()
+          .when(ix == completion.selected, |el| el.bg(theme.selection()))
+          .child(item.clone())
+      })),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[test]
+  fn test_path_prefix() {
+    assert_eq!(path_prefix("import x from './comp"), Some("./comp"));
+    assert_eq!(path_prefix(r#"open("assets/ic"#), Some("assets/ic"));
+    assert_eq!(path_prefix(r#"let s = "a\"../b"#), Some(r#"a\"../b"#));
+    // Not in a string, not a path, or not relative
+    assert_eq!(path_prefix(r#"x = "./a" + ./b"#), None);
+    assert_eq!(path_prefix(r#"say("hello"#), None);
+    assert_eq!(path_prefix(r#"open("/etc/ho"#), None);
+    assert_eq!(path_prefix(r#"get("https://exa"#), None);
+  }
+
+  #[test]
+  fn test_complete_lists_directories_first() {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path();
+    std::fs::create_dir_all(dir.join("src/sub")).unwrap();
+    std::fs::create_dir_all(dir.join("scripts")).unwrap();
+    for file in ["src/a.rs", "src/sub.rs", "setup.py", ".secret"] {
+      std::fs::write(dir.join(file), "").unwrap();
+    }
+
+    assert_eq!(complete(dir, "./s"), ["scripts/", "src/", "setup.py"]);
+    assert_eq!(complete(dir, "src/"), ["sub/", "a.rs", "sub.rs"]);
+    assert_eq!(complete(dir, "./."), [".secret"]);
+    assert!(complete(dir, "missing/").is_empty());
+  }
+
+  #[gpui::test]
+  fn test_accept_descends_into_directory(cx: &mut TestAppContext) {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path();
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::write(dir.join("src/main.rs"), "").unwrap();
+    std::fs::write(dir.join("doc.ts"), "import \"./s").unwrap();
+
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.open_file(dir.join("doc.ts"), cx).unwrap();
+      editor.move_to(11, cx);
+      update(editor, cx);
+      assert!(is_open(editor));
+      assert!(accept(editor, cx));
+      let completion = editor.path_completion.as_ref().unwrap();
+      assert_eq!(completion.items, ["main.rs"]);
+    });
+    assert_eq!(ctx.text(), "import \"./src/");
+  }
+}
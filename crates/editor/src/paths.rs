@@ -0,0 +1,33 @@
+//! Locations of files the editor keeps between sessions
+
+use std::path::{Path, PathBuf};
+
+/// `$XDG_CONFIG_HOME/gpui-editor`, or `~/.config/gpui-editor`
+pub(crate) fn config_dir() -> Option<PathBuf> {
+  let config_home = std::env::var_os("XDG_CONFIG_HOME")
+    .map(PathBuf::from)
+    .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+  Some(config_home.join("gpui-editor"))
+}
+
+/// File name standing for `workspace`, for per-workspace state in one directory
+pub(crate) fn workspace_file_name(workspace: &Path) -> String {
+  workspace
+    .to_string_lossy()
+    .trim_start_matches(['/', '\\'])
+    .replace(['/', '\\', ':'], "%")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_workspace_file_name() {
+    assert_eq!(
+      workspace_file_name(Path::new("/home/me/project")),
+      "home%me%project"
+    );
+    assert_eq!(workspace_file_name(Path::new("C:\\src\\app")), "C%%src%app");
+  }
+}
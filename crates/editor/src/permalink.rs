@@ -0,0 +1,233 @@
+//! Copying the selection as a reference to share
+//!
+//! The selected lines can be copied with a `file:line` prefix on each line,
+//! or as a link to them on the repository's host. Links point at the
+//! checked out commit, so they only work for files in that commit and for
+//! remotes on a host the editor knows the URL layout of.
+
+use std::{
+  ops::Range,
+  path::{Path, PathBuf},
+  process::Command,
+};
+
+use gpui::{App, ClipboardItem, Context};
+
+use crate::{editor::Editor, workspace::Workspace};
+
+/// A repository on GitHub, as named by a remote URL
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Remote {
+  pub host: String,
+  /// `owner/repo`
+  pub path: String,
+}
+
+impl Remote {
+  /// Parse `git@host:owner/repo.git`, `ssh://git@host/owner/repo.git` or
+  /// `https://host/owner/repo`; none for hosts other than GitHub
+  pub fn parse(url: &str) -> Option<Self> {
+    let url = url.trim();
+    let (host, path) = if let Some((_, rest)) = url.split_once("://") {
+      rest.split_once('/')?
+    } else {
+      url.split_once(':')?
+    };
+    // Drop any user name and port
+    let host = host.rsplit('@').next()?;
+    let host = host.split(':').next()?;
+    let path = path.trim_end_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    if host != "github.com" || path.split('/').filter(|part| !part.is_empty()).count() != 2 {
+      return None;
+    }
+    Some(Self {
+      host: host.to_string(),
+      path: path.to_string(),
+    })
+  }
+
+  /// Link to lines `lines` of `file`, counted from 0, at `commit`
+  pub fn permalink(&self, commit: &str, file: &str, lines: Range<usize>) -> String {
+    let anchor = if lines.len() <= 1 {
+      format!("L{}", lines.start + 1)
+    } else {
+      format!("L{}-L{}", lines.start + 1, lines.end)
+    };
+    format!(
+      "https://{}/{}/blob/{}/{}#{}",
+      self.host, self.path, commit, file, anchor
+    )
+  }
+}
+
+/// `text` with each line prefixed by `label:N: `, numbering from
+/// `first_line` counted from 0
+pub(crate) fn with_line_numbers(text: &str, label: Option<&str>, first_line: usize) -> String {
+  let mut result = String::new();
+  for (ix, line) in text.split_inclusive('\n').enumerate() {
+    if let Some(label) = label {
+      result.push_str(label);
+      result.push(':');
+    }
+    result.push_str(&format!("{}: ", first_line + ix + 1));
+    result.push_str(line);
+  }
+  result
+}
+
+/// Lines the selection touches, or the cursor's line; a selection ending at
+/// the start of a line doesn't include it
+fn selected_lines(editor: &Editor, cx: &App) -> Range<usize> {
+  let document = editor.document.read(cx);
+  let range = &editor.selected_range;
+  let start = document.char_to_line(range.start);
+  let mut end = document.char_to_line(range.end);
+  if end > start && document.line_to_char(end) == range.end {
+    end -= 1;
+  }
+  start..end + 1
+}
+
+/// Copy the selected lines, each prefixed with the file and its line number
+pub(crate) fn copy_with_line_numbers(editor: &Editor, cx: &mut Context<Editor>) {
+  let lines = selected_lines(editor, cx);
+  let label = editor.file_path().map(|path| {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    match cx.try_global::<Workspace>() {
+      Some(workspace) => workspace.display_path(&path),
+      None => path.display().to_string(),
+    }
+  });
+  let document = editor.document.read(cx);
+  let start = document.line_to_char(lines.start);
+  let end = if lines.end < document.len_lines() {
+    document.line_to_char(lines.end)
+  } else {
+    document.len()
+  };
+  let text = document.slice_to_string(start..end);
+  cx.write_to_clipboard(ClipboardItem::new_string(with_line_numbers(
+    &text,
+    label.as_deref(),
+    lines.start,
+  )));
+}
+
+/// Copy a link to the selected lines at the checked out commit
+pub(crate) fn copy_permalink(editor: &Editor, cx: &mut Context<Editor>) {
+  let Some(path) = editor.file_path().map(Path::to_path_buf) else {
+    return;
+  };
+  let lines = selected_lines(editor, cx);
+  cx.spawn(async move |this, cx| {
+    let result = cx
+      .background_executor()
+      .spawn(async move { permalink(&path, lines) })
+      .await;
+    match result {
+      Ok(link) => {
+        let _ = this.update(cx, |_, cx| {
+          cx.write_to_clipboard(ClipboardItem::new_string(link));
+        });
+      }
+      Err(err) => eprintln!("Failed to copy a permalink: {}", err),
+    }
+  })
+  .detach();
+}
+
+fn permalink(path: &Path, lines: Range<usize>) -> Result<String, String> {
+  let path = path.canonicalize().map_err(|err| err.to_string())?;
+  let dir = path.parent().ok_or("not a file")?;
+  let git = |args: &[&str]| -> Result<String, String> {
+    let output = Command::new("git")
+      .arg("-C")
+      .arg(dir)
+      .args(args)
+      .output()
+      .map_err(|err| format!("git: {}", err))?;
+    if !output.status.success() {
+      return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(
+      String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string(),
+    )
+  };
+
+  let root = PathBuf::from(git(&["rev-parse", "--show-toplevel"])?);
+  let relative = path.strip_prefix(&root).map_err(|err| err.to_string())?;
+  let file = relative
+    .components()
+    .map(|part| part.as_os_str().to_string_lossy())
+    .collect::<Vec<_>>()
+    .join("/");
+  let commit = git(&["rev-parse", "HEAD"])?;
+  git(&["cat-file", "-e", &format!("{}:{}", commit, file)])
+    .map_err(|_| format!("{} is not committed", file))?;
+  let url = git(&["remote", "get-url", "origin"])?;
+  let remote = Remote::parse(&url).ok_or_else(|| format!("unrecognized remote {}", url))?;
+  Ok(remote.permalink(&commit, &file, lines))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[test]
+  fn test_parse_remote() {
+    let expected = Some(Remote {
+      host: "github.com".to_string(),
+      path: "owner/repo".to_string(),
+    });
+    assert_eq!(Remote::parse("git@github.com:owner/repo.git"), expected);
+    assert_eq!(
+      Remote::parse("ssh://git@github.com:22/owner/repo.git"),
+      expected
+    );
+    assert_eq!(Remote::parse("https://github.com/owner/repo\n"), expected);
+    assert_eq!(Remote::parse("https://gitlab.com/owner/repo.git"), None);
+    assert_eq!(Remote::parse("/srv/git/repo.git"), None);
+  }
+
+  #[test]
+  fn test_permalink() {
+    let remote = Remote::parse("git@github.com:owner/repo.git").unwrap();
+    assert_eq!(
+      remote.permalink("abc123", "src/lib.rs", 9..10),
+      "https://github.com/owner/repo/blob/abc123/src/lib.rs#L10"
+    );
+    assert!(remote.permalink("abc123", "a.rs", 0..3).ends_with("#L1-L3"));
+  }
+
+  #[gpui::test]
+  fn test_copy_with_line_numbers(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "one\ntwo\nthree\nfour");
+    // The line the selection ends at the start of is left out
+    ctx.set_selection(5..14, false);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      copy_with_line_numbers(editor, cx);
+    });
+    assert_eq!(
+      ctx.cx.read_from_clipboard().and_then(|item| item.text()),
+      Some("2: two\n3: three\n".to_string())
+    );
+
+    ctx.set_cursor(16);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      copy_with_line_numbers(editor, cx);
+    });
+    assert_eq!(
+      ctx.cx.read_from_clipboard().and_then(|item| item.text()),
+      Some("4: four".to_string())
+    );
+    assert_eq!(
+      with_line_numbers("a\nb", Some("src/a.rs"), 9),
+      "src/a.rs:10: a\nsrc/a.rs:11: b"
+    );
+  }
+}
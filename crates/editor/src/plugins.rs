@@ -0,0 +1,330 @@
+//! WASM plugins extending the editor
+//!
+//! Plugins are the `.wasm` files in `plugins` in the editor's config
+//! directory, compiled on a background thread at startup. Their commands are
+//! listed in the command palette. Running one hands a snapshot of the text to
+//! the plugin on a background thread and applies the edits it made as one
+//! undoable transaction, unless the document changed in the meantime; the
+//! decorations it made replace the ones the plugin added before.
+
+use std::{
+  ops::Range,
+  path::{Path, PathBuf},
+  sync::Arc,
+  time::Instant,
+};
+
+use gpui::{App, Context, Global, Hsla, SharedString, rgba};
+use parking_lot::Mutex;
+use plugin::{Effect, Plugin};
+
+use crate::{
+  decorations::{DecorationId, DecorationStyle, VirtualText},
+  editor::Editor,
+  multi_cursor, paths,
+};
+
+struct LoadedPlugin {
+  name: String,
+  /// Kept out of the plugin, so listing them never waits on a command
+  /// that is running
+  commands: Vec<String>,
+  plugin: Arc<Mutex<Plugin>>,
+}
+
+/// Plugins loaded into the application
+#[derive(Default)]
+pub struct Plugins {
+  plugins: Vec<LoadedPlugin>,
+}
+
+impl Global for Plugins {}
+
+impl Plugins {
+  /// Every `.wasm` file in `dir`, in order of name, skipping the ones that
+  /// fail to load
+  pub fn load_dir(dir: &Path) -> Self {
+    let mut plugins = Self::default();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+      return plugins;
+    };
+    let mut paths: Vec<PathBuf> = entries
+      .filter_map(|entry| Some(entry.ok()?.path()))
+      .filter(|path| path.extension().is_some_and(|ext| ext == "wasm"))
+      .collect();
+    paths.sort();
+    for path in paths {
+      let name = path
+        .file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+      let loaded = std::fs::read(&path)
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))
+        .and_then(|wasm| Plugin::load(name, &wasm));
+      match loaded {
+        Ok(plugin) => plugins.add(plugin),
+        Err(err) => eprintln!("{}", err),
+      }
+    }
+    plugins
+  }
+
+  pub fn add(&mut self, plugin: Plugin) {
+    self.plugins.push(LoadedPlugin {
+      name: plugin.name().to_string(),
+      commands: plugin.commands().to_vec(),
+      plugin: Arc::new(Mutex::new(plugin)),
+    });
+  }
+}
+
+/// Load the plugins in the editor's config directory into the `Plugins`
+/// global
+pub fn load_plugins(cx: &mut App) {
+  let Some(dir) = paths::config_dir().map(|dir| dir.join("plugins")) else {
+    return;
+  };
+  cx.spawn(async move |cx| {
+    let plugins = cx
+      .background_executor()
+      .spawn(async move { Plugins::load_dir(&dir) })
+      .await;
+    cx.update(|cx| cx.set_global(plugins)).ok();
+  })
+  .detach();
+}
+
+/// A command a plugin registered
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PluginCommand {
+  pub plugin: usize,
+  pub id: usize,
+  /// The command's name after its plugin's
+  pub label: SharedString,
+}
+
+/// Commands of every plugin, in order of plugin
+pub(crate) fn commands(cx: &App) -> Vec<PluginCommand> {
+  let Some(plugins) = cx.try_global::<Plugins>() else {
+    return Vec::new();
+  };
+  plugins
+    .plugins
+    .iter()
+    .enumerate()
+    .flat_map(|(plugin, loaded)| {
+      loaded
+        .commands
+        .iter()
+        .enumerate()
+        .map(move |(id, name)| PluginCommand {
+          plugin,
+          id,
+          label: format!("{}: {}", loaded.name, name).into(),
+        })
+    })
+    .collect()
+}
+
+/// Run `command` on a background thread against the document as it is now
+pub(crate) fn run(editor: &mut Editor, command: &PluginCommand, cx: &mut Context<Editor>) {
+  let Some(loaded) = cx
+    .try_global::<Plugins>()
+    .and_then(|plugins| plugins.plugins.get(command.plugin))
+  else {
+    return;
+  };
+  let plugin = loaded.plugin.clone();
+  let (plugin_ix, id) = (command.plugin, command.id);
+  let document = editor.document.read(cx);
+  let version = document.version();
+  let text = document.text();
+  let run = cx
+    .background_executor()
+    .spawn(async move { plugin.lock().run(id, text) });
+  cx.spawn(async move |this, cx| {
+    let effects = run.await;
+    this
+      .update(cx, |editor, cx| match effects {
+        Ok(effects) => apply(editor, plugin_ix, version, effects, cx),
+        Err(err) => eprintln!("{}", err),
+      })
+      .ok();
+  })
+  .detach();
+}
+
+/// Apply what a plugin's command did to the document at `version`
+fn apply(
+  editor: &mut Editor,
+  plugin: usize,
+  version: usize,
+  effects: Vec<Effect>,
+  cx: &mut Context<Editor>,
+) {
+  let document = editor.document.read(cx);
+  if document.version() != version {
+    eprintln!("The document changed while a plugin command ran, its changes were dropped");
+    return;
+  }
+  // The plugin's offsets are bytes of the text
+  let chars =
+    |range: Range<usize>| document.byte_to_char(range.start)..document.byte_to_char(range.end);
+  let mut edits = Vec::new();
+  let mut decorations = Vec::new();
+  for effect in effects {
+    match effect {
+      Effect::Replace { range, text } => edits.push((chars(range), text)),
+      Effect::Decorate {
+        range,
+        color,
+        label,
+      } => {
+        let color: Hsla = rgba(color).into();
+        let style = DecorationStyle {
+          underline: Some(color),
+          virtual_text: label.map(|label| VirtualText {
+            text: label.into(),
+            color,
+          }),
+          ..Default::default()
+        };
+        // Anchored before the edits, so they follow them
+        decorations.push((document.anchor_range(chars(range)), style));
+      }
+    }
+  }
+
+  let (stale, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut editor.plugin_decorations)
+    .into_iter()
+    .partition(|(owner, _)| *owner == plugin);
+  for (_, id) in stale {
+    editor.decorations.remove(id);
+  }
+  editor.plugin_decorations = kept;
+  for (range, style) in decorations {
+    let id = editor.decorations.insert(range, style);
+    editor.plugin_decorations.push((plugin, id));
+  }
+  if !edits.is_empty() {
+    replace(editor, edits, cx);
+  }
+  cx.notify();
+}
+
+/// Make `edits` of the current text as one undoable transaction
+fn replace(editor: &mut Editor, mut edits: Vec<(Range<usize>, String)>, cx: &mut Context<Editor>) {
+  edits.sort_by_key(|(range, _)| range.start);
+  let selection_before = editor.selected_range.clone();
+  let document = editor.document.read(cx);
+  let first_line = document.char_to_line(edits[0].0.start);
+  let cursor = document.anchor_before(editor.cursor_offset());
+  let transaction_id = editor.document.update(cx, |doc, cx| {
+    let id = doc.buffer.transaction(Instant::now(), |buffer, tx| {
+      // Back to front, so the ranges before each edit stay valid
+      for (range, text) in edits.iter().rev() {
+        buffer.replace(tx, range.clone(), text);
+      }
+    });
+    doc.schedule_recompute_highlights(cx);
+    cx.notify();
+    id
+  });
+
+  multi_cursor::clear(editor, cx);
+  let cursor = editor.document.read(cx).resolve_anchor(&cursor);
+  editor.move_to(cursor, cx);
+  editor.record_transaction(
+    transaction_id,
+    selection_before,
+    cursor..cursor,
+    "Run Plugin Command",
+    cx,
+  );
+  editor.invalidate_lines_from(first_line);
+}
+
+/// Forget which decorations the plugins added to the previous document
+pub(crate) fn clear(editor: &mut Editor) {
+  editor.plugin_decorations.clear();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  /// Plugin whose "Shout" command uppercases the text's first byte if it's
+  /// a lowercase ASCII letter and underlines the rest
+  const SHOUT: &str = r#"
+    (module
+      (import "editor" "register_command" (func $register (param i32 i32) (result i32)))
+      (import "editor" "text_len" (func $text_len (result i32)))
+      (import "editor" "read_text" (func $read (param i32 i32 i32) (result i32)))
+      (import "editor" "replace" (func $replace (param i32 i32 i32 i32)))
+      (import "editor" "decorate" (func $decorate (param i32 i32 i32 i32 i32)))
+      (memory (export "memory") 1)
+      (data (i32.const 0) "Shout")
+      (func (export "init") (drop (call $register (i32.const 0) (i32.const 5))))
+      (func (export "run_command") (param i32)
+        (drop (call $read (i32.const 0) (i32.const 100) (i32.const 1)))
+        (if (i32.ge_u (i32.load8_u (i32.const 100)) (i32.const 97))
+          (then
+            (i32.store8 (i32.const 100) (i32.sub (i32.load8_u (i32.const 100)) (i32.const 32)))
+            (call $replace (i32.const 0) (i32.const 1) (i32.const 100) (i32.const 1))))
+        (call $decorate (i32.const 1) (call $text_len) (i32.const 0xff0000ff)
+          (i32.const 0) (i32.const 0))))
+  "#;
+
+  fn install(cx: &mut TestAppContext) -> Vec<PluginCommand> {
+    let plugin = Plugin::load("shout", &wat::parse_str(SHOUT).unwrap()).unwrap();
+    cx.update(|cx| {
+      let mut plugins = Plugins::default();
+      plugins.add(plugin);
+      cx.set_global(plugins);
+      commands(cx)
+    })
+  }
+
+  #[gpui::test]
+  fn test_command_edits_and_decorates(cx: &mut TestAppContext) {
+    let commands = install(cx);
+    assert_eq!(commands.len(), 1);
+    assert_eq!(commands[0].label, "shout: Shout");
+
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "héllo");
+    for _ in 0..2 {
+      ctx.editor.update(&mut ctx.cx, |editor, cx| {
+        editor.move_to(3, cx);
+        run(editor, &commands[0], cx);
+      });
+      ctx.cx.run_until_parked();
+    }
+    assert_eq!(ctx.text(), "Héllo");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert_eq!(editor.cursor_offset(), 3);
+      // Only the decorations of the last run are kept
+      assert_eq!(editor.plugin_decorations.len(), 1);
+      let document = editor.document.read(cx);
+      let decorations = editor.decorations.resolve_in_lines(0..1, document);
+      assert_eq!(decorations.len(), 1);
+      assert_eq!(decorations[0].range, 1..5);
+      // The second run found nothing to change
+      assert_eq!(document.buffer.undo_depth(), 1);
+    });
+  }
+
+  #[gpui::test]
+  fn test_stale_results_are_dropped(cx: &mut TestAppContext) {
+    let commands = install(cx);
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "abc");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      run(editor, &commands[0], cx);
+      editor.move_to(3, cx);
+      editor.insert_text("d", "Typing", cx);
+    });
+    ctx.cx.run_until_parked();
+    assert_eq!(ctx.text(), "abcd");
+  }
+}
@@ -0,0 +1,288 @@
+//! Previews of files that aren't text
+//!
+//! Opening an image shows the image, scaled to fit and zoomable, instead of
+//! its bytes. Other files that aren't valid UTF-8 show a summary of what
+//! they are rather than an unreadable document.
+
+use std::{
+  path::{Path, PathBuf},
+  time::SystemTime,
+};
+
+use gpui::{
+  AnyElement, ClickEvent, Context, ObjectFit, SharedString, Window, div, img, prelude::*, px,
+  relative,
+};
+
+use crate::editor::Editor;
+
+/// Images the preview can draw
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "svg"];
+
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 8.0;
+const ZOOM_STEP: f32 = 1.25;
+
+/// What opening a file shows
+pub(crate) enum FileContents {
+  Text(String),
+  Preview(FilePreview),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum FilePreview {
+  Image(ImagePreview),
+  Binary(BinarySummary),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ImagePreview {
+  pub path: PathBuf,
+  /// Scale relative to fitting the image in the view
+  pub zoom: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct BinarySummary {
+  pub kind: &'static str,
+  pub size: u64,
+  pub modified: Option<SystemTime>,
+}
+
+pub(crate) fn is_image(path: &Path) -> bool {
+  path
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Kind of binary data, recognized by its leading magic bytes
+pub(crate) fn binary_kind(bytes: &[u8]) -> &'static str {
+  const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"%PDF-", "PDF document"),
+    (b"PK\x03\x04", "ZIP archive"),
+    (b"\x1f\x8b", "gzip archive"),
+    (b"\x7fELF", "ELF executable"),
+    (b"\xcf\xfa\xed\xfe", "Mach-O executable"),
+    (b"MZ", "Windows executable"),
+    (b"\0asm", "WebAssembly module"),
+    (b"SQLite format 3\0", "SQLite database"),
+    (b"\x89PNG", "PNG image"),
+    (b"\xff\xd8\xff", "JPEG image"),
+  ];
+  SIGNATURES
+    .iter()
+    .find(|(magic, _)| bytes.starts_with(magic))
+    .map_or("Binary data", |(_, kind)| *kind)
+}
+
+/// Read `path` as text, or as a preview if it is an image or not UTF-8
+pub(crate) fn load(path: &Path) -> Result<FileContents, String> {
+  let metadata = std::fs::metadata(path).map_err(|err| err.to_string())?;
+  if is_image(path) {
+    return Ok(FileContents::Preview(FilePreview::Image(ImagePreview {
+      path: path.to_path_buf(),
+      zoom: 1.0,
+    })));
+  }
+
+  let bytes = std::fs::read(path).map_err(|err| err.to_string())?;
+  match String::from_utf8(bytes) {
+    Ok(text) => Ok(FileContents::Text(text)),
+    Err(err) => Ok(FileContents::Preview(FilePreview::Binary(BinarySummary {
+      kind: binary_kind(err.as_bytes()),
+      size: metadata.len(),
+      modified: metadata.modified().ok(),
+    }))),
+  }
+}
+
+/// `1536` as `1.5 KB`
+pub(crate) fn format_size(size: u64) -> String {
+  const UNITS: &[&str] = &["KB", "MB", "GB", "TB"];
+  if size < 1024 {
+    return format!("{} bytes", size);
+  }
+  let mut value = size as f64 / 1024.0;
+  let mut unit = 0;
+  while value >= 1024.0 && unit < UNITS.len() - 1 {
+    value /= 1024.0;
+    unit += 1;
+  }
+  format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Multiply the zoom of an image preview by `factor`, `None` fits it again
+pub(crate) fn zoom(editor: &mut Editor, factor: Option<f32>, cx: &mut Context<Editor>) {
+  if let Some(FilePreview::Image(image)) = editor.preview.as_mut() {
+    image.zoom = factor.map_or(1.0, |factor| {
+      (image.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM)
+    });
+    cx.notify();
+  }
+}
+
+pub(crate) fn zoom_in(editor: &mut Editor, cx: &mut Context<Editor>) {
+  zoom(editor, Some(ZOOM_STEP), cx);
+}
+
+pub(crate) fn zoom_out(editor: &mut Editor, cx: &mut Context<Editor>) {
+  zoom(editor, Some(1.0 / ZOOM_STEP), cx);
+}
+
+fn zoom_button(
+  id: &'static str,
+  label: &'static str,
+  cx: &mut Context<Editor>,
+  on_click: fn(&mut Editor, &mut Context<Editor>),
+) -> impl IntoElement {
+  div()
+    .id(id)
+    .px_2()
+    .rounded_sm()
+    .cursor_pointer()
+    .child(label)
+    .on_click(cx.listener(move |editor, _: &ClickEvent, _, cx| on_click(editor, cx)))
+}
+
+pub(crate) fn render(
+  editor: &Editor,
+  preview: &FilePreview,
+  _window: &mut Window,
+  cx: &mut Context<Editor>,
+) -> AnyElement {
+  match preview {
+    FilePreview::Image(image) => div()
+      .flex()
+      .flex_col()
+      .flex_1()
+      .min_h_0()
+      .child(
+        div()
+          .flex()
+          .flex_row()
+          .items_center()
+          .gap_1()
+          .p_1()
+          .text_color(editor.theme.line_number())
+          .child(zoom_button("preview-fit", "Fit", cx, |editor, cx| {
+            zoom(editor, None, cx)
+          }))
+          .child(zoom_button("preview-zoom-out", "−", cx, zoom_out))
+          .child(zoom_button("preview-zoom-in", "+", cx, zoom_in))
+          .child(format!("{:.0}%", image.zoom * 100.0)),
+      )
+      .child(
+        div()
+          .id("preview-image")
+          .flex_1()
+          .min_h_0()
+          .overflow_scroll()
+          .child(
+            div().w(relative(image.zoom)).h(relative(image.zoom)).child(
+              img(image.path.clone())
+                .size_full()
+                .object_fit(ObjectFit::Contain),
+            ),
+          ),
+      )
+      .into_any_element(),
+    FilePreview::Binary(summary) => {
+      let file_name: SharedString = editor
+        .file_path()
+        .and_then(|path| path.file_name())
+        .map(|name| name.to_string_lossy().into_owned().into())
+        .unwrap_or_default();
+      let modified = summary
+        .modified
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map(|age| format!("Modified {} ago", format_age(age.as_secs())));
+
+      div()
+        .flex()
+        .flex_col()
+        .flex_1()
+        .items_center()
+        .justify_center()
+        .gap_1()
+        .text_color(editor.theme.line_number())
+        .child(div().text_size(px(16.0)).child(file_name))
+        .child(format!("{}, {}", summary.kind, format_size(summary.size)))
+        .children(modified)
+        .into_any_element()
+    }
+  }
+}
+
+fn format_age(secs: u64) -> String {
+  match secs {
+    0..60 => format!("{}s", secs),
+    60..3600 => format!("{}m", secs / 60),
+    3600..86400 => format!("{}h", secs / 3600),
+    _ => format!("{}d", secs / 86400),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[test]
+  fn test_binary_kind_and_size() {
+    assert_eq!(binary_kind(b"%PDF-1.7\n\xe2\xe3"), "PDF document");
+    assert_eq!(binary_kind(b"\x7fELF\x02\x01"), "ELF executable");
+    assert_eq!(binary_kind(&[0, 159, 146, 150]), "Binary data");
+
+    assert_eq!(format_size(512), "512 bytes");
+    assert_eq!(format_size(1536), "1.5 KB");
+    assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GB");
+  }
+
+  #[gpui::test]
+  fn test_open_previews_non_text_files(cx: &mut TestAppContext) {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path();
+    let image = dir.join("logo.PNG");
+    let binary = dir.join("data.bin");
+    let text = dir.join("notes.txt");
+    std::fs::write(&image, b"\x89PNG\r\n\x1a\n").unwrap();
+    std::fs::write(&binary, b"\x7fELF\xff\xfe").unwrap();
+    std::fs::write(&text, "notes").unwrap();
+
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "old text");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.open_file(image.clone(), cx).unwrap();
+      assert!(
+        matches!(&editor.preview, Some(FilePreview::Image(preview)) if preview.path == image)
+      );
+      assert_eq!(editor.document.read(cx).len(), 0);
+
+      zoom_in(editor, cx);
+      zoom_in(editor, cx);
+      let Some(FilePreview::Image(preview)) = &editor.preview else {
+        panic!("expected an image preview");
+      };
+      assert_eq!(preview.zoom, ZOOM_STEP * ZOOM_STEP);
+      for _ in 0..20 {
+        zoom_out(editor, cx);
+      }
+      let Some(FilePreview::Image(preview)) = &editor.preview else {
+        panic!("expected an image preview");
+      };
+      assert_eq!(preview.zoom, MIN_ZOOM);
+
+      editor.open_file(binary.clone(), cx).unwrap();
+      let Some(FilePreview::Binary(summary)) = &editor.preview else {
+        panic!("expected a binary summary");
+      };
+      assert_eq!(summary.kind, "ELF executable");
+      assert_eq!(summary.size, 6);
+
+      editor.open_file(text.clone(), cx).unwrap();
+      assert!(editor.preview.is_none());
+    });
+    assert_eq!(ctx.text(), "notes");
+  }
+}
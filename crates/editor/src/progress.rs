@@ -0,0 +1,220 @@
+//! Progress of long-running background work
+//!
+//! Work that takes a while starts a named task, reports how far along it is
+//! when it knows, and finishes the task when done. The status bar shows the
+//! running tasks, and clicking it lists them with a button to cancel each.
+//! Cancelling only raises a flag the work checks, so it stops at its next
+//! check and leaves things as they were.
+
+use std::sync::{
+  Arc,
+  atomic::{AtomicBool, Ordering},
+};
+
+use gpui::{App, ClickEvent, Context, Global, SharedString, div, prelude::*, px};
+
+use crate::editor::Editor;
+
+/// Held by the work a task stands for, to report on it
+#[derive(Clone)]
+pub struct ProgressHandle {
+  id: usize,
+  cancelled: Arc<AtomicBool>,
+}
+
+impl ProgressHandle {
+  /// Whether the task was cancelled, after which its result is dropped
+  pub fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::Relaxed)
+  }
+}
+
+struct RunningTask {
+  id: usize,
+  name: SharedString,
+  /// Between 0 and 1, none while the work can't tell
+  fraction: Option<f32>,
+  cancelled: Arc<AtomicBool>,
+}
+
+impl RunningTask {
+  fn label(&self) -> String {
+    match self.fraction {
+      Some(fraction) => format!("{} {}%", self.name, (fraction * 100.0).round() as u32),
+      None => format!("{}…", self.name),
+    }
+  }
+}
+
+/// Background work running in the application, oldest first
+#[derive(Default)]
+pub struct BackgroundTasks {
+  tasks: Vec<RunningTask>,
+  next_id: usize,
+}
+
+impl Global for BackgroundTasks {}
+
+impl BackgroundTasks {
+  pub fn start(&mut self, name: impl Into<SharedString>) -> ProgressHandle {
+    let handle = ProgressHandle {
+      id: self.next_id,
+      cancelled: Arc::new(AtomicBool::new(false)),
+    };
+    self.next_id += 1;
+    self.tasks.push(RunningTask {
+      id: handle.id,
+      name: name.into(),
+      fraction: None,
+      cancelled: handle.cancelled.clone(),
+    });
+    handle
+  }
+
+  pub fn set_progress(&mut self, handle: &ProgressHandle, fraction: f32) {
+    if let Some(task) = self.tasks.iter_mut().find(|task| task.id == handle.id) {
+      task.fraction = Some(fraction.clamp(0.0, 1.0));
+    }
+  }
+
+  pub fn finish(&mut self, handle: &ProgressHandle) {
+    self.tasks.retain(|task| task.id != handle.id);
+  }
+
+  /// Ask the work of task `id` to stop, and stop showing it
+  pub fn cancel(&mut self, id: usize) {
+    if let Some(ix) = self.tasks.iter().position(|task| task.id == id) {
+      let task = self.tasks.remove(ix);
+      task.cancelled.store(true, Ordering::Relaxed);
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.tasks.is_empty()
+  }
+
+  /// The oldest task, and how many others run
+  fn summary(&self) -> Option<String> {
+    let first = self.tasks.first()?;
+    Some(match self.tasks.len() {
+      1 => first.label(),
+      count => format!("{} (+{} more)", first.label(), count - 1),
+    })
+  }
+}
+
+/// Start showing the task `name` until it is finished or cancelled
+pub fn start(name: impl Into<SharedString>, cx: &mut App) -> ProgressHandle {
+  let handle = cx.default_global::<BackgroundTasks>().start(name);
+  cx.refresh_windows();
+  handle
+}
+
+pub fn set_progress(handle: &ProgressHandle, fraction: f32, cx: &mut App) {
+  cx.default_global::<BackgroundTasks>()
+    .set_progress(handle, fraction);
+  cx.refresh_windows();
+}
+
+pub fn finish(handle: &ProgressHandle, cx: &mut App) {
+  cx.default_global::<BackgroundTasks>().finish(handle);
+  cx.refresh_windows();
+}
+
+fn tasks(cx: &App) -> Option<&BackgroundTasks> {
+  cx.try_global::<BackgroundTasks>()
+    .filter(|tasks| !tasks.is_empty())
+}
+
+/// Status bar item for the running tasks, opening their list when clicked
+pub(crate) fn render_status(cx: &mut Context<Editor>) -> Option<impl IntoElement> {
+  let summary = tasks(cx)?.summary()?;
+  Some(
+    div()
+      .id("background-tasks")
+      .cursor_pointer()
+      .child(summary)
+      .on_click(cx.listener(|editor, _: &ClickEvent, _, cx| {
+        editor.progress_menu_open = !editor.progress_menu_open;
+        cx.notify();
+      })),
+  )
+}
+
+/// Close the list of tasks once none run, so it doesn't reopen with the next
+pub(crate) fn close_menu_if_idle(editor: &mut Editor, cx: &App) {
+  if tasks(cx).is_none() {
+    editor.progress_menu_open = false;
+  }
+}
+
+/// List of the running tasks above the status bar, each with a cancel button
+pub(crate) fn render_menu(
+  editor: &Editor,
+  status_bar_height: f32,
+  cx: &mut Context<Editor>,
+) -> Option<impl IntoElement> {
+  if !editor.progress_menu_open {
+    return None;
+  }
+  let tasks = tasks(cx)?;
+  let rows: Vec<_> = tasks
+    .tasks
+    .iter()
+    .map(|task| {
+      let id = task.id;
+      div()
+        .flex()
+        .flex_row()
+        .gap_4()
+        .justify_between()
+        .child(task.label())
+        .child(
+          div()
+            .id(("cancel-background-task", id))
+            .cursor_pointer()
+            .text_color(editor.theme.line_number())
+            .child("Cancel")
+            .on_click(cx.listener(move |_, _: &ClickEvent, _, cx| {
+              cx.default_global::<BackgroundTasks>().cancel(id);
+              cx.refresh_windows();
+            })),
+        )
+    })
+    .collect();
+  Some(
+    div()
+      .absolute()
+      .right(px(8.0))
+      .bottom(px(status_bar_height))
+      .occlude()
+      .flex()
+      .flex_col()
+      .gap_1()
+      .p_2()
+      .bg(editor.theme.gutter_background())
+      .children(rows),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_background_tasks() {
+    let mut tasks = BackgroundTasks::default();
+    let index = tasks.start("Indexing");
+    let replace = tasks.start("Replacing");
+    assert_eq!(tasks.summary().as_deref(), Some("Indexing… (+1 more)"));
+
+    tasks.set_progress(&index, 0.404);
+    assert_eq!(tasks.summary().as_deref(), Some("Indexing 40% (+1 more)"));
+
+    tasks.cancel(index.id);
+    assert!(index.is_cancelled() && !replace.is_cancelled());
+    assert_eq!(tasks.summary().as_deref(), Some("Replacing…"));
+    tasks.finish(&replace);
+    assert!(tasks.is_empty() && !replace.is_cancelled());
+  }
+}
@@ -0,0 +1,392 @@
+//! Find and replace across the files of a directory
+//!
+//! A search collects the matches of every text file below a directory into
+//! a preview of pending replacements, each file and each match with its own
+//! checkbox. Applying never writes to disk: the file open in the editor is
+//! changed through its document, and every other file is opened in a new
+//! document, each as a single undoable transaction left unsaved for review.
+
+use std::{
+  collections::HashSet,
+  ops::Range,
+  path::{Path, PathBuf},
+  time::Instant,
+};
+
+use gpui::{AppContext, Context, Entity};
+
+use crate::{document::Document, editor::Editor, search::SearchQuery};
+
+/// Directories that hold dependencies or build output rather than sources
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules"];
+
+/// Files larger than this are not searched
+const MAX_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplaceMatch {
+  /// Byte range of the match in the file
+  pub range: Range<usize>,
+  pub line: usize,
+  /// The line containing the match, as shown in the preview
+  pub line_text: String,
+  pub accepted: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileReplace {
+  pub path: PathBuf,
+  /// Path shown in the preview, relative to the searched root
+  pub display_path: String,
+  pub matches: Vec<ReplaceMatch>,
+  /// Text the matches were found in, to detect changes made since
+  contents: String,
+}
+
+impl FileReplace {
+  fn new(path: PathBuf, contents: String, query: &SearchQuery) -> Option<Self> {
+    let matches: Vec<ReplaceMatch> = query
+      .find_all(&contents)
+      .into_iter()
+      .map(|range| {
+        let line_start = contents[..range.start].rfind('\n').map_or(0, |ix| ix + 1);
+        let line_end = contents[range.start..]
+          .find('\n')
+          .map_or(contents.len(), |ix| range.start + ix);
+        ReplaceMatch {
+          line: contents[..range.start].matches('\n').count(),
+          line_text: contents[line_start..line_end]
+            .trim_end_matches('\r')
+            .to_string(),
+          range,
+          accepted: true,
+        }
+      })
+      .collect();
+    (!matches.is_empty()).then_some(Self {
+      display_path: path.display().to_string(),
+      path,
+      matches,
+      contents,
+    })
+  }
+
+  /// Whether the file's checkbox is checked, i.e. any of its matches is
+  pub fn accepted(&self) -> bool {
+    self.matches.iter().any(|m| m.accepted)
+  }
+
+  /// Accepted match ranges, last first so earlier ranges stay valid while
+  /// replacing
+  fn accepted_ranges(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+    self
+      .matches
+      .iter()
+      .rev()
+      .filter(|m| m.accepted)
+      .map(|m| m.range.clone())
+  }
+
+  /// The file's contents with the accepted matches replaced
+  pub fn replaced(&self, replacement: &str) -> String {
+    let mut text = self.contents.clone();
+    for range in self.accepted_ranges() {
+      text.replace_range(range, replacement);
+    }
+    text
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProjectReplace {
+  pub query: SearchQuery,
+  pub replacement: String,
+  pub files: Vec<FileReplace>,
+}
+
+impl ProjectReplace {
+  /// Find `query` in the text files below `root`, in path order
+  pub fn search(root: &Path, query: SearchQuery, replacement: impl Into<String>) -> Self {
+    Self::search_roots(&[root], query, replacement)
+  }
+
+  /// Find `query` in the text files below each of `roots`, root by root in
+  /// path order; a file below nested roots is searched once
+  pub fn search_roots(roots: &[&Path], query: SearchQuery, replacement: impl Into<String>) -> Self {
+    let mut files = Vec::new();
+    let mut searched = HashSet::new();
+    for root in roots {
+      let mut paths = Vec::new();
+      collect_files(root, &mut paths);
+      paths.sort();
+
+      files.extend(
+        paths
+          .into_iter()
+          .filter(|path| searched.insert(path.clone()))
+          .filter_map(|path| {
+            let contents = std::fs::read_to_string(&path).ok()?;
+            let mut file = FileReplace::new(path, contents, &query)?;
+            if let Ok(relative) = file.path.strip_prefix(root) {
+              file.display_path = relative.display().to_string();
+            }
+            Some(file)
+          }),
+      );
+    }
+    Self {
+      query,
+      replacement: replacement.into(),
+      files,
+    }
+  }
+
+  pub fn match_count(&self) -> usize {
+    self.files.iter().map(|file| file.matches.len()).sum()
+  }
+
+  /// Check or uncheck all matches of a file
+  pub fn toggle_file(&mut self, file_ix: usize) {
+    if let Some(file) = self.files.get_mut(file_ix) {
+      let accepted = !file.accepted();
+      for m in &mut file.matches {
+        m.accepted = accepted;
+      }
+    }
+  }
+
+  pub fn toggle_match(&mut self, file_ix: usize, match_ix: usize) {
+    if let Some(m) = self
+      .files
+      .get_mut(file_ix)
+      .and_then(|file| file.matches.get_mut(match_ix))
+    {
+      m.accepted = !m.accepted;
+    }
+  }
+
+  /// Make the accepted replacements, returning the documents changed
+  ///
+  /// The editor's own file is edited in its document, other files are
+  /// opened in new documents; all are left unsaved. Files that changed since
+  /// the search are skipped with an error.
+  pub fn apply(
+    &self,
+    editor: &mut Editor,
+    cx: &mut Context<Editor>,
+  ) -> (Vec<Entity<Document>>, Vec<String>) {
+    let mut changed = Vec::new();
+    let mut errors = Vec::new();
+    for file in self.files.iter().filter(|file| file.accepted()) {
+      let result = if editor.file_path() == Some(file.path.as_path()) {
+        apply_to_editor(file, &self.replacement, editor, cx).map(|()| editor.document.clone())
+      } else {
+        open_replaced(file, &self.replacement, cx)
+      };
+      match result {
+        Ok(document) => changed.push(document),
+        Err(err) => errors.push(format!("{}: {}", file.path.display(), err)),
+      }
+    }
+    (changed, errors)
+  }
+}
+
+fn collect_files(dir: &Path, paths: &mut Vec<PathBuf>) {
+  let Ok(entries) = std::fs::read_dir(dir) else {
+    return;
+  };
+  for entry in entries.flatten() {
+    let path = entry.path();
+    let Ok(file_type) = entry.file_type() else {
+      continue;
+    };
+    let hidden = entry.file_name().to_string_lossy().starts_with('.');
+    if file_type.is_dir() {
+      let skipped = SKIPPED_DIRS.iter().any(|name| entry.file_name() == *name);
+      if !hidden && !skipped {
+        collect_files(&path, paths);
+      }
+    } else if file_type.is_file()
+      && !hidden
+      && entry
+        .metadata()
+        .is_ok_and(|metadata| metadata.len() <= MAX_FILE_SIZE)
+    {
+      paths.push(path);
+    }
+  }
+}
+
+/// Open `file` in a new document with the replacements made, unsaved
+fn open_replaced(
+  file: &FileReplace,
+  replacement: &str,
+  cx: &mut Context<Editor>,
+) -> Result<Entity<Document>, String> {
+  let current = std::fs::read_to_string(&file.path).map_err(|err| err.to_string())?;
+  if current != file.contents {
+    return Err("changed since the search".to_string());
+  }
+  let file_ext = file.path.extension().and_then(|ext| ext.to_str());
+  let document = cx.new(|cx| {
+    let mut document = Document::new(&current, file_ext, cx);
+    document.set_path(Some(file.path.clone()));
+    document
+  });
+  document.update(cx, |document, cx| {
+    replace_matches(document, file, replacement, cx)
+  });
+  Ok(document)
+}
+
+fn apply_to_editor(
+  file: &FileReplace,
+  replacement: &str,
+  editor: &mut Editor,
+  cx: &mut Context<Editor>,
+) -> Result<(), String> {
+  if !editor
+    .document
+    .read(cx)
+    .buffer
+    .snapshot()
+    .text_eq(&file.contents)
+  {
+    return Err("changed since the search".to_string());
+  }
+
+  let selection = editor.selected_range.clone();
+  let transaction_id = editor.document.update(cx, |document, cx| {
+    replace_matches(document, file, replacement, cx)
+  });
+
+  let len = editor.document.read(cx).len();
+  editor.selected_range = selection.start.min(len)..selection.end.min(len);
+  editor.invalidate_lines_from(0);
+  editor.record_transaction(
+    transaction_id,
+    selection,
+    editor.selected_range.clone(),
+    "Replace All",
+    cx,
+  );
+  cx.notify();
+  Ok(())
+}
+
+/// Replace the accepted matches of `file` in `document`, which holds the
+/// text they were found in, as one transaction
+fn replace_matches(
+  document: &mut Document,
+  file: &FileReplace,
+  replacement: &str,
+  cx: &mut Context<Document>,
+) -> buffer::TransactionId {
+  let ranges: Vec<Range<usize>> = file
+    .accepted_ranges()
+    .map(|range| document.byte_to_char(range.start)..document.byte_to_char(range.end))
+    .collect();
+  let id = document.buffer.transaction(Instant::now(), |buffer, tx| {
+    for range in ranges {
+      buffer.replace(tx, range, replacement);
+    }
+  });
+  document.schedule_recompute_highlights(cx);
+  cx.notify();
+  id
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+  use tempfile::TempDir;
+
+  fn temp_project() -> TempDir {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path();
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::create_dir_all(dir.join("target")).unwrap();
+    std::fs::write(dir.join("src/a.rs"), "let old = 1;\nold + old\n").unwrap();
+    std::fs::write(dir.join("src/b.rs"), "fn Old() {}\n").unwrap();
+    std::fs::write(dir.join("target/c.rs"), "old").unwrap();
+    std::fs::write(dir.join("notes.txt"), "nothing here").unwrap();
+    temp
+  }
+
+  #[test]
+  fn test_search_preview() {
+    let temp = temp_project();
+    let dir = temp.path();
+    let mut replace = ProjectReplace::search(dir, SearchQuery::new("old"), "new");
+
+    assert_eq!(replace.files.len(), 2);
+    assert_eq!(replace.match_count(), 4);
+    let a = &replace.files[0];
+    assert!(a.path.ends_with("src/a.rs"));
+    assert_eq!(a.display_path, Path::new("src/a.rs").display().to_string());
+    assert_eq!(
+      (a.matches[1].line, a.matches[1].line_text.as_str()),
+      (1, "old + old")
+    );
+
+    replace.toggle_match(0, 1);
+    assert_eq!(
+      replace.files[0].replaced("new"),
+      "let new = 1;\nold + new\n"
+    );
+    replace.toggle_file(1);
+    assert!(!replace.files[1].accepted());
+    replace.toggle_file(1);
+    assert!(replace.files[1].accepted());
+  }
+
+  #[gpui::test]
+  fn test_apply(cx: &mut TestAppContext) {
+    let temp = temp_project();
+    let dir = temp.path();
+    let mut replace =
+      ProjectReplace::search(dir, SearchQuery::new("old").case_sensitive(true), "new");
+    assert_eq!(replace.files.len(), 1);
+    std::fs::write(dir.join("src/b.rs"), "old old\n").unwrap();
+    replace = ProjectReplace::search(dir, replace.query.clone(), "new");
+    replace.toggle_match(0, 0);
+
+    // The open file is changed in the editor, the other one in a new
+    // document, and nothing on disk
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.open_file(dir.join("src/b.rs"), cx).unwrap();
+      let (changed, errors) = replace.apply(editor, cx);
+      assert_eq!(changed.len(), 2);
+      assert!(errors.is_empty());
+      assert_eq!(editor.document.read(cx).buffer.undo_depth(), 1);
+
+      let a = changed[0].read(cx);
+      assert_eq!(a.path(), Some(dir.join("src/a.rs").as_path()));
+      assert_eq!(a.text(), "let old = 1;\nnew + new\n");
+      assert!(a.is_dirty());
+      changed[0].update(cx, |a, cx| a.undo(cx));
+      assert_eq!(changed[0].read(cx).text(), "let old = 1;\nold + old\n");
+    });
+
+    assert_eq!(ctx.text(), "new new\n");
+    assert_eq!(
+      std::fs::read_to_string(dir.join("src/b.rs")).unwrap(),
+      "old old\n"
+    );
+    assert_eq!(
+      std::fs::read_to_string(dir.join("src/a.rs")).unwrap(),
+      "let old = 1;\nold + old\n"
+    );
+
+    // Applying again finds the open file changed
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      let (changed, errors) = replace.apply(editor, cx);
+      assert_eq!(changed.len(), 1);
+      assert_eq!(errors.len(), 1);
+    });
+  }
+}
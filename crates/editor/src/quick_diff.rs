@@ -0,0 +1,300 @@
+//! Quick diff popup for a hunk marked in the gutter
+//!
+//! Clicking the gutter next to lines word diff marked as changed shows the
+//! committed version of the hunk below it, with buttons to revert the hunk
+//! in the document or to stage it. Staging applies a patch of the hunk to
+//! the index, so it fails if the index no longer matches the last commit
+//! for those lines.
+
+use std::{
+  io::Write,
+  path::{Path, PathBuf},
+  process::{Command, Stdio},
+  time::Instant,
+};
+
+use gpui::{ClickEvent, Context, IntoElement, SharedString, div, prelude::*, px};
+
+use crate::{
+  editor::Editor,
+  trust,
+  word_diff::{self, Hunk},
+  wrap_map,
+};
+
+/// Show the hunk next to `line`, returning whether there is one
+pub(crate) fn show(editor: &mut Editor, line: usize, cx: &mut Context<Editor>) -> bool {
+  let Some(hunk) = word_diff::hunk_at_line(editor, line, cx) else {
+    return false;
+  };
+  editor.quick_diff = Some(hunk);
+  cx.notify();
+  true
+}
+
+pub(crate) fn dismiss(editor: &mut Editor, cx: &mut Context<Editor>) {
+  if editor.quick_diff.take().is_some() {
+    cx.notify();
+  }
+}
+
+/// Put the committed lines of the shown hunk back in the document
+pub(crate) fn revert(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let Some(hunk) = editor.quick_diff.take() else {
+    return;
+  };
+  let range = editor.document.read(cx).resolve_anchor_range(&hunk.range);
+  let first_line = editor.document.read(cx).char_to_line(range.start);
+  let selection_before = editor.selected_range.clone();
+
+  let transaction_id = editor.document.update(cx, |doc, cx| {
+    let id = doc.buffer.transaction(Instant::now(), |buffer, tx| {
+      buffer.replace(tx, range.clone(), &hunk.old_text);
+    });
+    doc.schedule_recompute_highlights(cx);
+    cx.notify();
+    id
+  });
+
+  editor.move_to(range.start, cx);
+  let selection_after = editor.selected_range.clone();
+  editor.record_transaction(
+    transaction_id,
+    selection_before,
+    selection_after,
+    "Revert Change",
+    cx,
+  );
+  editor.invalidate_lines_from(first_line);
+  cx.notify();
+}
+
+/// Add the shown hunk to the index, leaving the rest of the file unstaged
+///
+/// Git isn't run while the workspace is restricted.
+pub(crate) fn stage(editor: &mut Editor, cx: &mut Context<Editor>) {
+  if trust::is_restricted(editor, cx) {
+    return;
+  }
+  let Some(hunk) = editor.quick_diff.take() else {
+    return;
+  };
+  let Some(path) = editor.file_path().map(Path::to_path_buf) else {
+    return;
+  };
+  let document = editor.document.read(cx);
+  let range = document.resolve_anchor_range(&hunk.range);
+  let new_start = document.char_to_line(range.start);
+  let new_text = document.slice_to_string(range);
+
+  cx.spawn(async move |_, cx| {
+    let result = cx
+      .background_executor()
+      .spawn(async move { apply_to_index(&path, &hunk, new_start, &new_text) })
+      .await;
+    if let Err(err) = result {
+      eprintln!("Failed to stage the hunk: {}", err);
+    }
+  })
+  .detach();
+  cx.notify();
+}
+
+fn apply_to_index(
+  path: &Path,
+  hunk: &Hunk,
+  new_start: usize,
+  new_text: &str,
+) -> Result<(), String> {
+  let path = path.canonicalize().map_err(|err| err.to_string())?;
+  let dir = path.parent().ok_or("not a file")?;
+  let toplevel = Command::new("git")
+    .arg("-C")
+    .arg(dir)
+    .args(["rev-parse", "--show-toplevel"])
+    .output()
+    .map_err(|err| format!("git: {}", err))?;
+  if !toplevel.status.success() {
+    return Err("not in a git repository".to_string());
+  }
+  let root = PathBuf::from(String::from_utf8_lossy(&toplevel.stdout).trim_end());
+  let relative = path.strip_prefix(&root).map_err(|err| err.to_string())?;
+  let patch = hunk_patch(
+    &relative.to_string_lossy(),
+    hunk.old_start,
+    &hunk.old_text,
+    new_start,
+    new_text,
+  );
+
+  let mut child = Command::new("git")
+    .arg("-C")
+    .arg(&root)
+    .args(["apply", "--cached", "--unidiff-zero", "-"])
+    .stdin(Stdio::piped())
+    .stdout(Stdio::null())
+    .stderr(Stdio::piped())
+    .spawn()
+    .map_err(|err| format!("git: {}", err))?;
+  child
+    .stdin
+    .take()
+    .ok_or("no stdin")?
+    .write_all(patch.as_bytes())
+    .map_err(|err| err.to_string())?;
+  let output = child.wait_with_output().map_err(|err| err.to_string())?;
+  if !output.status.success() {
+    return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+  }
+  Ok(())
+}
+
+/// Patch without context turning `old_text` at line `old_start` of `path`
+/// into `new_text` at line `new_start`, lines counted from 0
+fn hunk_patch(
+  path: &str,
+  old_start: usize,
+  old_text: &str,
+  new_start: usize,
+  new_text: &str,
+) -> String {
+  // An empty side is placed after the line before it, a non-empty one at
+  // its first line, counted from 1
+  let header_range = |start: usize, count: usize| {
+    let start = if count == 0 { start } else { start + 1 };
+    format!("{},{}", start, count)
+  };
+  let body = |prefix: char, text: &str| {
+    let mut body = String::new();
+    for line in text.split_inclusive('\n') {
+      body.push(prefix);
+      body.push_str(line);
+    }
+    if !text.is_empty() && !text.ends_with('\n') {
+      body.push_str("\n\\ No newline at end of file\n");
+    }
+    body
+  };
+  let old_count = old_text.split_inclusive('\n').count();
+  let new_count = new_text.split_inclusive('\n').count();
+  format!(
+    "diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n@@ -{} +{} @@\n{}{}",
+    header_range(old_start, old_count),
+    header_range(new_start, new_count),
+    body('-', old_text),
+    body('+', new_text),
+  )
+}
+
+pub(crate) fn render(editor: &Editor, hunk: &Hunk, cx: &mut Context<Editor>) -> impl IntoElement {
+  let document = editor.document.read(cx);
+  let range = document.resolve_anchor_range(&hunk.range);
+  let last_line = document.char_to_line(range.end.saturating_sub(1).max(range.start));
+  let below = wrap_map::rows_for_line(editor, last_line, cx).end;
+  let y = editor.line_height * (below as f32 - editor.scroll_offset_y);
+
+  let old_text = if hunk.old_text.is_empty() {
+    SharedString::new_static("Added lines")
+  } else {
+    SharedString::from(hunk.old_text.trim_end_matches('\n').to_string())
+  };
+  let button = |id: &'static str, label: &'static str| {
+    div()
+      .id(id)
+      .px_1()
+      .rounded_sm()
+      .cursor_pointer()
+      .border_1()
+      .border_color(editor.theme.line_number())
+      .child(label)
+  };
+
+  div()
+    .absolute()
+    .left(editor.gutter_layout.width())
+    .top(y.max(px(0.)))
+    .occlude()
+    .flex()
+    .flex_col()
+    .gap_1()
+    .p_2()
+    .rounded_md()
+    .border_1()
+    .border_color(editor.theme.line_number())
+    .bg(editor.theme.gutter_background())
+    .text_size(px(12.0))
+    .when_some(editor.font.family.clone(), |el, family| {
+      el.font_family(family)
+    })
+    .child(
+      div()
+        .text_color(editor.theme.line_number())
+        .whitespace_nowrap()
+        .child(old_text),
+    )
+    .child(
+      div()
+        .flex()
+        .flex_row()
+        .gap_2()
+        .child(
+          button("quick-diff-revert", "Revert")
+            .on_click(cx.listener(|editor, _: &ClickEvent, _, cx| revert(editor, cx))),
+        )
+        .child(
+          button("quick-diff-stage", "Stage")
+            .on_click(cx.listener(|editor, _: &ClickEvent, _, cx| stage(editor, cx))),
+        )
+        .child(
+          button("quick-diff-close", "Close")
+            .on_click(cx.listener(|editor, _: &ClickEvent, _, cx| dismiss(editor, cx))),
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    diff::{self, DiffAlgorithm},
+    editor::tests::EditorTestContext,
+  };
+  use gpui::TestAppContext;
+
+  #[test]
+  fn test_hunk_patch() {
+    assert_eq!(
+      hunk_patch("src/a.rs", 1, "b\n", 1, "B\nC\n"),
+      "diff --git a/src/a.rs b/src/a.rs\n--- a/src/a.rs\n+++ b/src/a.rs\n@@ -2,1 +2,2 @@\n-b\n+B\n+C\n"
+    );
+    // Insertions and deletions go after the line before them
+    assert!(hunk_patch("a", 2, "", 2, "x\n").contains("@@ -2,0 +3,1 @@\n+x\n"));
+    assert!(hunk_patch("a", 0, "x\n", 0, "").contains("@@ -1,1 +0,0 @@\n-x\n"));
+    assert!(
+      hunk_patch("a", 0, "x", 0, "y")
+        .ends_with("-x\n\\ No newline at end of file\n+y\n\\ No newline at end of file\n")
+    );
+  }
+
+  #[gpui::test]
+  fn test_revert_hunk(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "one\nTWO\nthree\n");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      let changes = diff::line_changes(
+        "one\ntwo\nthree\n",
+        "one\nTWO\nthree\n",
+        DiffAlgorithm::Myers,
+      );
+      let version = editor.document.read(cx).version();
+      word_diff::apply_changes(editor, changes, version, cx);
+      assert!(!show(editor, 0, cx));
+      assert!(show(editor, 1, cx));
+      assert!(editor.quick_diff.is_some());
+
+      revert(editor, cx);
+      assert!(editor.quick_diff.is_none());
+      assert_eq!(editor.document.read(cx).buffer.undo_depth(), 1);
+    });
+    assert_eq!(ctx.text(), "one\ntwo\nthree\n");
+  }
+}
@@ -0,0 +1,228 @@
+//! Rewrapping of comment and text paragraphs to a maximum line length
+//!
+//! In code only comments are rewrapped, recognized by the comment tokens of the
+//! document's language; plain text is rewrapped as prose.
+
+use syntax::CommentTokens;
+
+/// How the lines to rewrap are recognized
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReflowMode {
+  /// Every line is text
+  Prose,
+  /// Only comment lines are text, code lines are left as they are
+  Comments(CommentTokens),
+}
+
+/// Indentation and comment marker of a line, including one space after the marker
+///
+/// Doc comment markers extend the line token with its last character or `!`,
+/// like `///` and `//!`. Block comments continue on lines starting with `*`.
+pub fn line_prefix(line: &str, mode: ReflowMode) -> &str {
+  let indent_len = line.len() - line.trim_start().len();
+  let rest = &line[indent_len..];
+  let marker_len = match mode {
+    ReflowMode::Prose => 0,
+    ReflowMode::Comments(tokens) => comment_marker_len(rest, tokens),
+  };
+  let space_len = usize::from(marker_len > 0 && rest[marker_len..].starts_with(' '));
+  &line[..indent_len + marker_len + space_len]
+}
+
+fn comment_marker_len(rest: &str, tokens: CommentTokens) -> usize {
+  if let Some(token) = tokens.line.filter(|token| rest.starts_with(token)) {
+    let last = token.chars().last().unwrap_or_default();
+    let extra = rest[token.len()..]
+      .chars()
+      .next()
+      .filter(|ch| *ch == last || *ch == '!');
+    return token.len() + extra.map_or(0, char::len_utf8);
+  }
+  let continues_block = tokens.block.is_some_and(|(open, _)| open.ends_with('*'));
+  let after_star = rest.strip_prefix('*');
+  match after_star {
+    Some(after) if continues_block && (after.is_empty() || after.starts_with(' ')) => 1,
+    _ => 0,
+  }
+}
+
+fn body(line: &str, mode: ReflowMode) -> &str {
+  line[line_prefix(line, mode).len()..].trim()
+}
+
+/// Whether a line is rewrapped at all
+pub fn is_text(line: &str, mode: ReflowMode) -> bool {
+  match mode {
+    ReflowMode::Prose => true,
+    ReflowMode::Comments(_) => !line_prefix(line, mode).trim().is_empty(),
+  }
+}
+
+/// Lines without text after their prefix separate paragraphs
+pub fn is_blank(line: &str, mode: ReflowMode) -> bool {
+  body(line, mode).is_empty()
+}
+
+pub fn in_same_paragraph(a: &str, b: &str, mode: ReflowMode) -> bool {
+  is_text(a, mode)
+    && is_text(b, mode)
+    && !is_blank(a, mode)
+    && !is_blank(b, mode)
+    && line_prefix(a, mode).trim_end() == line_prefix(b, mode).trim_end()
+}
+
+/// Rewrap each paragraph of `lines` to `max_width` columns, keeping blank lines
+/// and, in code, the lines that aren't comments
+pub fn reflow_lines(lines: &[&str], mode: ReflowMode, max_width: usize) -> Vec<String> {
+  let mut result = Vec::new();
+  let mut start = 0;
+  while start < lines.len() {
+    let line = lines[start];
+    if !is_text(line, mode) {
+      result.push(line.to_string());
+      start += 1;
+      continue;
+    }
+    if is_blank(line, mode) {
+      result.push(line.trim_end().to_string());
+      start += 1;
+      continue;
+    }
+
+    let mut end = start + 1;
+    while end < lines.len() && in_same_paragraph(line, lines[end], mode) {
+      end += 1;
+    }
+    let words = lines[start..end]
+      .iter()
+      .flat_map(|line| body(line, mode).split_whitespace());
+    wrap_words(line_prefix(line, mode), words, max_width, &mut result);
+    start = end;
+  }
+  result
+}
+
+fn wrap_words<'a>(
+  prefix: &str,
+  words: impl Iterator<Item = &'a str>,
+  max_width: usize,
+  result: &mut Vec<String>,
+) {
+  let prefix_width = prefix.chars().count();
+  let mut current = prefix.to_string();
+  let mut current_width = prefix_width;
+
+  for word in words {
+    let word_width = word.chars().count();
+    // A word longer than the line still goes on a line of its own
+    if current_width > prefix_width && current_width + 1 + word_width > max_width {
+      result.push(std::mem::replace(&mut current, prefix.to_string()));
+      current_width = prefix_width;
+    }
+    if current_width > prefix_width {
+      current.push(' ');
+      current_width += 1;
+    }
+    current.push_str(word);
+    current_width += word_width;
+  }
+
+  result.push(current);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const RUST: ReflowMode = ReflowMode::Comments(CommentTokens {
+    line: Some("//"),
+    block: Some(("/*", "*/")),
+  });
+  const PYTHON: ReflowMode = ReflowMode::Comments(CommentTokens {
+    line: Some("#"),
+    block: None,
+  });
+
+  #[test]
+  fn test_line_prefix() {
+    assert_eq!(line_prefix("    /// Docs", RUST), "    /// ");
+    assert_eq!(line_prefix("//! Module", RUST), "//! ");
+    assert_eq!(line_prefix("  // comment", RUST), "  // ");
+    assert_eq!(line_prefix("   * block", RUST), "   * ");
+    assert_eq!(line_prefix("plain text", RUST), "");
+    assert_eq!(line_prefix("//no space", RUST), "//");
+    assert_eq!(line_prefix("  ## heading", PYTHON), "  ## ");
+    assert_eq!(line_prefix("  text", ReflowMode::Prose), "  ");
+  }
+
+  #[test]
+  fn test_code_is_not_a_comment() {
+    assert!(!is_text("*ptr = 5;", RUST));
+    assert!(!is_text("let x = 1;", RUST));
+    assert!(!is_text("# heading", RUST));
+    assert!(!is_text("// comment", PYTHON));
+    assert!(is_text("let x = 1;", ReflowMode::Prose));
+  }
+
+  #[test]
+  fn test_reflow_comment() {
+    let lines = ["  // The quick brown fox", "  // jumps over the lazy dog"];
+
+    assert_eq!(
+      reflow_lines(&lines, RUST, 20),
+      vec![
+        "  // The quick brown",
+        "  // fox jumps over",
+        "  // the lazy dog"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_reflow_joins_short_lines() {
+    let lines = ["/// one", "/// two", "/// three"];
+    assert_eq!(reflow_lines(&lines, RUST, 80), vec!["/// one two three"]);
+  }
+
+  #[test]
+  fn test_reflow_keeps_paragraphs() {
+    let lines = ["// a", "// b", "//", "// c", "", "text d", "text e"];
+
+    assert_eq!(
+      reflow_lines(&lines, RUST, 80),
+      vec!["// a b", "//", "// c", "", "text d", "text e"]
+    );
+    assert_eq!(
+      reflow_lines(&["text d", "text e", "", "f"], ReflowMode::Prose, 80),
+      vec!["text d text e", "", "f"]
+    );
+  }
+
+  #[test]
+  fn test_reflow_leaves_code() {
+    let lines = ["let x = 1;", "let y = 2;", "// a", "// b", "*ptr = 5;"];
+
+    assert_eq!(
+      reflow_lines(&lines, RUST, 80),
+      vec!["let x = 1;", "let y = 2;", "// a b", "*ptr = 5;"]
+    );
+  }
+
+  #[test]
+  fn test_long_word_gets_own_line() {
+    let lines = ["# short averyveryverylongword end"];
+
+    assert_eq!(
+      reflow_lines(&lines, PYTHON, 10),
+      vec!["# short", "# averyveryverylongword", "# end"]
+    );
+  }
+
+  #[test]
+  fn test_in_same_paragraph() {
+    assert!(in_same_paragraph("// a", "//  b", RUST));
+    assert!(!in_same_paragraph("// a", "/// b", RUST));
+    assert!(!in_same_paragraph("// a", "//", RUST));
+    assert!(!in_same_paragraph("let a = 1;", "let b = 2;", RUST));
+  }
+}
@@ -0,0 +1,84 @@
+//! Wrap column ruler, with a handle in the top margin to drag it
+//!
+//! Columns set with the ruler are stored per language in a global, so every
+//! editor showing a document of that language picks up the change.
+
+use std::collections::HashMap;
+
+use gpui::{App, Context, Global};
+
+use crate::{editor::Editor, workspace};
+
+/// Key for documents without a detected language
+const PLAIN_TEXT: &str = "plain";
+
+#[derive(Default)]
+pub struct WrapColumnSettings {
+  by_language: HashMap<String, usize>,
+}
+
+impl Global for WrapColumnSettings {}
+
+impl WrapColumnSettings {
+  pub fn get(&self, language: &str) -> Option<usize> {
+    self.by_language.get(language).copied()
+  }
+
+  pub fn set(&mut self, language: impl Into<String>, column: usize) {
+    self.by_language.insert(language.into(), column);
+  }
+}
+
+pub(crate) fn language_key(editor: &Editor, cx: &App) -> &'static str {
+  editor
+    .document
+    .read(cx)
+    .language_name()
+    .unwrap_or(PLAIN_TEXT)
+}
+
+/// Wrap column for the editor's language, falling back to the one set for
+/// the workspace root of its file, then to the editor's own
+pub(crate) fn wrap_column(editor: &Editor, cx: &App) -> usize {
+  cx.try_global::<WrapColumnSettings>()
+    .and_then(|settings| settings.get(language_key(editor, cx)))
+    .or_else(|| workspace::root_settings(editor, cx)?.wrap_column)
+    .unwrap_or(editor.wrap_column)
+}
+
+pub(crate) fn set_wrap_column(editor: &mut Editor, column: usize, cx: &mut Context<Editor>) {
+  let column = column.max(1);
+  if wrap_column(editor, cx) == column {
+    return;
+  }
+
+  editor.wrap_column = column;
+  let language = language_key(editor, cx);
+  cx.default_global::<WrapColumnSettings>()
+    .set(language, column);
+  // Other editors of the same language show the new column too
+  cx.refresh_windows();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[gpui::test]
+  fn test_wrap_column_is_shared_per_language(cx: &mut TestAppContext) {
+    let mut first = EditorTestContext::with_text(cx.clone(), "one");
+    let second = EditorTestContext::with_text(cx.clone(), "two");
+
+    first.editor.update(&mut first.cx, |editor, cx| {
+      assert_eq!(wrap_column(editor, cx), editor.wrap_column);
+      set_wrap_column(editor, 72, cx);
+      assert_eq!(wrap_column(editor, cx), 72);
+    });
+
+    second.editor.read_with(&second.cx, |editor, cx| {
+      assert_eq!(wrap_column(editor, cx), 72);
+    });
+  }
+}
@@ -0,0 +1,167 @@
+//! Scratch buffers, throwaway documents kept between sessions
+//!
+//! A scratch buffer is a numbered file in the scratch directory. There is no
+//! saving it: its text is written back shortly after every edit, and before
+//! another file replaces it in the editor, so it is still there after a
+//! restart. Its extension picks the language it is highlighted as.
+
+use std::{
+  io,
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+use gpui::{App, Context, Task};
+
+use crate::{editor::Editor, paths};
+
+/// Delay after an edit before a scratch buffer is written back
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Extension of scratch buffers without a language
+const PLAIN_EXTENSION: &str = "txt";
+
+#[derive(Default)]
+pub(crate) struct ScratchSave {
+  // Document version last written to the scratch file
+  saved_version: Option<usize>,
+  save_task: Option<Task<()>>,
+}
+
+/// `scratch` in the config directory
+fn scratch_dir() -> Option<PathBuf> {
+  Some(paths::config_dir()?.join("scratch"))
+}
+
+/// Scratch buffers kept from this and earlier sessions, oldest first
+pub fn scratch_buffers() -> Vec<PathBuf> {
+  scratch_dir().map_or_else(Vec::new, |dir| list(&dir))
+}
+
+fn list(dir: &Path) -> Vec<PathBuf> {
+  let Ok(entries) = std::fs::read_dir(dir) else {
+    return Vec::new();
+  };
+  let mut buffers: Vec<(usize, PathBuf)> = entries
+    .filter_map(|entry| {
+      let path = entry.ok()?.path();
+      Some((scratch_number(&path)?, path))
+    })
+    .collect();
+  buffers.sort();
+  buffers.into_iter().map(|(_, path)| path).collect()
+}
+
+/// N of a `scratch-N.ext` file
+fn scratch_number(path: &Path) -> Option<usize> {
+  path
+    .file_stem()?
+    .to_str()?
+    .strip_prefix("scratch-")?
+    .parse()
+    .ok()
+}
+
+/// Create an empty scratch file in `dir` numbered after the existing ones
+fn create_in(dir: &Path, extension: &str) -> io::Result<PathBuf> {
+  std::fs::create_dir_all(dir)?;
+  let number = list(dir)
+    .iter()
+    .filter_map(|path| scratch_number(path))
+    .max()
+    .map_or(1, |last| last + 1);
+  let path = dir.join(format!("scratch-{}.{}", number, extension));
+  std::fs::write(&path, "")?;
+  Ok(path)
+}
+
+pub(crate) fn is_scratch(editor: &Editor) -> bool {
+  let (Some(path), Some(dir)) = (editor.file_path(), scratch_dir()) else {
+    return false;
+  };
+  path.starts_with(dir)
+}
+
+/// Open a new scratch buffer highlighted for files with `extension`
+pub(crate) fn new_buffer(
+  editor: &mut Editor,
+  extension: Option<&str>,
+  cx: &mut Context<Editor>,
+) -> Result<(), String> {
+  let dir = scratch_dir().ok_or("No config directory for scratch buffers")?;
+  let path = create_in(&dir, extension.unwrap_or(PLAIN_EXTENSION))
+    .map_err(|err| format!("Failed to create a scratch buffer: {}", err))?;
+  editor.open_file(path, cx)
+}
+
+/// Write the scratch buffer back once edits settle
+pub(crate) fn schedule_save(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let version = editor.document.read(cx).version();
+  if !is_scratch(editor) || editor.scratch_save.saved_version == Some(version) {
+    return;
+  }
+  editor.scratch_save.save_task = Some(cx.spawn(async move |this, cx| {
+    cx.background_executor().timer(SAVE_DEBOUNCE).await;
+    let _ = this.update(cx, |editor, cx| save_now(editor, cx));
+  }));
+}
+
+/// Write the scratch buffer back if it changed since it was last written
+pub(crate) fn save_now(editor: &mut Editor, cx: &App) {
+  let document = editor.document.read(cx);
+  let version = document.version();
+  if editor.scratch_save.saved_version == Some(version) || !is_scratch(editor) {
+    return;
+  }
+  let Some(path) = editor.file_path() else {
+    return;
+  };
+  match std::fs::write(path, document.text()) {
+    Ok(()) => editor.scratch_save.saved_version = Some(version),
+    Err(err) => eprintln!("Failed to save {}: {}", path.display(), err),
+  }
+  editor.scratch_save.save_task = None;
+}
+
+/// Whether the scratch buffer changed since it was last written
+pub(crate) fn has_unsaved_edits(editor: &Editor, cx: &App) -> bool {
+  editor.scratch_save.saved_version != Some(editor.document.read(cx).version())
+}
+
+/// Start tracking the scratch file just opened as written
+pub(crate) fn opened(editor: &mut Editor, cx: &App) {
+  editor.scratch_save = ScratchSave {
+    saved_version: Some(editor.document.read(cx).version()),
+    save_task: None,
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_scratch_numbering() {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path().join("scratch");
+
+    let first = create_in(&dir, "rs").unwrap();
+    let second = create_in(&dir, "txt").unwrap();
+    std::fs::write(dir.join("notes.txt"), "").unwrap();
+    assert_eq!(first.file_name().unwrap(), "scratch-1.rs");
+    assert_eq!(second.file_name().unwrap(), "scratch-2.txt");
+
+    // Numbers keep going up after a buffer is removed
+    std::fs::remove_file(&first).unwrap();
+    let third = create_in(&dir, "ts").unwrap();
+    assert_eq!(third.file_name().unwrap(), "scratch-3.ts");
+    assert_eq!(list(&dir), [second, third]);
+  }
+
+  #[test]
+  fn test_scratch_number() {
+    assert_eq!(scratch_number(Path::new("/s/scratch-12.rs")), Some(12));
+    assert_eq!(scratch_number(Path::new("/s/scratch-.rs")), None);
+    assert_eq!(scratch_number(Path::new("/s/notes.txt")), None);
+  }
+}
@@ -0,0 +1,369 @@
+//! Scrollbars along the right and bottom edges of the text
+//!
+//! The vertical bar scrolls through rows with `scroll_offset_y`, the
+//! horizontal one through `max_line_width` with the scroll handle. Clicking
+//! the track jumps the thumb there and keeps dragging it. The bars show while
+//! the view scrolls or the mouse is over them, and fade after a moment still.
+
+use std::{ops::Range, time::Duration};
+
+use gpui::{
+  App, Bounds, Context, Corners, DispatchPhase, ElementId, Entity, GlobalElementId,
+  InspectorElementId, LayoutId, MouseButton, MouseDownEvent, MouseMoveEvent, MouseUpEvent, Pixels,
+  Point, Style, Task, Window, fill, point, prelude::*, px, relative, size,
+};
+
+use crate::editor::{EDITOR_PADDING, Editor};
+
+/// Thickness of a scrollbar
+pub const SCROLLBAR_THICKNESS: f32 = 10.0;
+// Shortest thumb, so that a long document still has one to grab
+const MIN_THUMB_LENGTH: f32 = 20.0;
+// Gap between the thumb and the sides of its track
+const THUMB_INSET: f32 = 2.0;
+// Time the bars stay after the view stops scrolling
+const HIDE_DELAY: Duration = Duration::from_millis(1000);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScrollbarAxis {
+  Vertical,
+  Horizontal,
+}
+
+/// Visibility and dragging of the editor's scrollbars
+#[derive(Default)]
+pub(crate) struct Scrollbars {
+  visible: bool,
+  /// Scroll offsets on the last frame, to notice the view scrolling
+  last_offsets: (f32, Pixels),
+  /// Axis being dragged, with the pointer's distance from the thumb start
+  drag: Option<(ScrollbarAxis, f32)>,
+  hide_task: Option<Task<()>>,
+}
+
+/// Show the scrollbars, and hide them once the view has been still a moment
+pub(crate) fn reveal(editor: &mut Editor, cx: &mut Context<Editor>) {
+  if !editor.scrollbars.visible {
+    editor.scrollbars.visible = true;
+    cx.notify();
+  }
+  editor.scrollbars.hide_task = Some(cx.spawn(async move |this, cx| {
+    cx.background_executor().timer(HIDE_DELAY).await;
+    let _ = this.update(cx, |editor, cx| {
+      if editor.scrollbars.drag.is_none() {
+        editor.scrollbars.visible = false;
+        cx.notify();
+      }
+    });
+  }));
+}
+
+pub(crate) fn is_visible(editor: &Editor) -> bool {
+  editor.scrollbars.visible || editor.scrollbars.drag.is_some()
+}
+
+/// Reveal the scrollbars if the view scrolled since the last frame
+fn track_scrolling(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let offsets = (editor.scroll_offset_y, editor.scroll_handle.offset().x);
+  if offsets != editor.scrollbars.last_offsets {
+    editor.scrollbars.last_offsets = offsets;
+    reveal(editor, cx);
+  }
+}
+
+/// How far along `axis` the view is scrolled, how much of it is visible and
+/// how far it can scroll, in rows or pixels
+fn extent(editor: &Editor, axis: ScrollbarAxis, cx: &App) -> (f32, f32, f32) {
+  match axis {
+    ScrollbarAxis::Vertical => (
+      editor.scroll_offset_y,
+      editor.viewport_height / editor.line_height,
+      editor.scrollable_rows(cx).saturating_sub(1) as f32,
+    ),
+    ScrollbarAxis::Horizontal => {
+      let visible = editor.scroll_handle.bounds().size.width;
+      let content = editor.max_line_width + px(2. * EDITOR_PADDING);
+      let max_offset = if crate::wrap_map::is_enabled(editor) {
+        px(0.)
+      } else {
+        (content - visible).max(px(0.))
+      };
+      (
+        f32::from(-editor.scroll_handle.offset().x),
+        f32::from(visible),
+        f32::from(max_offset),
+      )
+    }
+  }
+}
+
+fn set_offset(editor: &mut Editor, axis: ScrollbarAxis, offset: f32, cx: &mut Context<Editor>) {
+  match axis {
+    ScrollbarAxis::Vertical => editor.scroll_offset_y = offset,
+    ScrollbarAxis::Horizontal => {
+      editor.scroll_handle.set_offset(point(px(-offset), px(0.)));
+    }
+  }
+  cx.notify();
+}
+
+/// Span of the thumb along a track `track` long, for a view showing `visible`
+/// that can scroll `max_offset` further and is scrolled `offset`
+fn thumb_range(track: f32, visible: f32, max_offset: f32, offset: f32) -> Option<Range<f32>> {
+  if max_offset <= 0. || track <= 0. {
+    return None;
+  }
+  let len = (track * visible / (visible + max_offset))
+    .max(MIN_THUMB_LENGTH)
+    .min(track);
+  let start = (track - len) * (offset / max_offset).clamp(0., 1.);
+  Some(start..start + len)
+}
+
+/// Scroll offset that puts the thumb `thumb_len` long at `start` on its track
+fn offset_for_thumb(track: f32, thumb_len: f32, max_offset: f32, start: f32) -> f32 {
+  let travel = track - thumb_len;
+  if travel <= 0. {
+    return 0.;
+  }
+  (start / travel).clamp(0., 1.) * max_offset
+}
+
+pub struct ScrollbarElement {
+  editor: Entity<Editor>,
+  axis: ScrollbarAxis,
+}
+
+pub struct ScrollbarPrepaintState {
+  /// Thumb along the track, none when there is nothing to scroll
+  thumb: Option<Range<f32>>,
+  max_offset: f32,
+  visible: bool,
+}
+
+impl ScrollbarElement {
+  pub fn new(editor: Entity<Editor>, axis: ScrollbarAxis) -> Self {
+    Self { editor, axis }
+  }
+}
+
+/// Distance of `position` along a track in `bounds`
+fn along(axis: ScrollbarAxis, bounds: Bounds<Pixels>, position: Point<Pixels>) -> f32 {
+  match axis {
+    ScrollbarAxis::Vertical => f32::from(position.y - bounds.top()),
+    ScrollbarAxis::Horizontal => f32::from(position.x - bounds.left()),
+  }
+}
+
+fn track_len(axis: ScrollbarAxis, bounds: Bounds<Pixels>) -> f32 {
+  match axis {
+    ScrollbarAxis::Vertical => f32::from(bounds.size.height),
+    ScrollbarAxis::Horizontal => f32::from(bounds.size.width),
+  }
+}
+
+impl IntoElement for ScrollbarElement {
+  type Element = Self;
+
+  fn into_element(self) -> Self::Element {
+    self
+  }
+}
+
+impl Element for ScrollbarElement {
+  type RequestLayoutState = ();
+  type PrepaintState = ScrollbarPrepaintState;
+
+  fn id(&self) -> Option<ElementId> {
+    None
+  }
+
+  fn source_location(&self) -> Option<&'static core::panic::Location<'static>> {
+    None
+  }
+
+  fn request_layout(
+    &mut self,
+    _id: Option<&GlobalElementId>,
+    _inspector_id: Option<&InspectorElementId>,
+    window: &mut Window,
+    cx: &mut App,
+  ) -> (LayoutId, Self::RequestLayoutState) {
+    let mut style = Style::default();
+    style.size.width = relative(1.).into();
+    style.size.height = relative(1.).into();
+
+    (window.request_layout(style, [], cx), ())
+  }
+
+  fn prepaint(
+    &mut self,
+    _id: Option<&GlobalElementId>,
+    _inspector_id: Option<&InspectorElementId>,
+    bounds: Bounds<Pixels>,
+    _request_layout: &mut Self::RequestLayoutState,
+    _window: &mut Window,
+    cx: &mut App,
+  ) -> Self::PrepaintState {
+    self
+      .editor
+      .update(cx, |editor, cx| track_scrolling(editor, cx));
+    let editor = self.editor.read(cx);
+    let (offset, visible, max_offset) = extent(editor, self.axis, cx);
+    ScrollbarPrepaintState {
+      thumb: thumb_range(track_len(self.axis, bounds), visible, max_offset, offset),
+      max_offset,
+      visible: is_visible(editor),
+    }
+  }
+
+  fn paint(
+    &mut self,
+    _id: Option<&GlobalElementId>,
+    _inspector_id: Option<&InspectorElementId>,
+    bounds: Bounds<Pixels>,
+    _request_layout: &mut Self::RequestLayoutState,
+    prepaint: &mut Self::PrepaintState,
+    window: &mut Window,
+    cx: &mut App,
+  ) {
+    let Some(thumb) = prepaint.thumb.clone() else {
+      return;
+    };
+    let axis = self.axis;
+    let track = track_len(axis, bounds);
+    let max_offset = prepaint.max_offset;
+    let thumb_len = thumb.end - thumb.start;
+
+    // Pressing on the thumb grabs it, on the track jumps it there first
+    window.on_mouse_event({
+      let editor = self.editor.clone();
+      move |event: &MouseDownEvent, phase, _window, cx| {
+        if phase != DispatchPhase::Capture
+          || event.button != MouseButton::Left
+          || !bounds.contains(&event.position)
+        {
+          return;
+        }
+        let position = along(axis, bounds, event.position);
+        let grab = if thumb.contains(&position) {
+          position - thumb.start
+        } else {
+          thumb_len / 2.
+        };
+        editor.update(cx, |editor, cx| {
+          editor.scrollbars.drag = Some((axis, grab));
+          let offset = offset_for_thumb(track, thumb_len, max_offset, position - grab);
+          set_offset(editor, axis, offset, cx);
+          reveal(editor, cx);
+        });
+        cx.stop_propagation();
+      }
+    });
+
+    window.on_mouse_event({
+      let editor = self.editor.clone();
+      move |event: &MouseMoveEvent, phase, _window, cx| {
+        if phase != DispatchPhase::Capture {
+          return;
+        }
+        match editor.read(cx).scrollbars.drag {
+          Some((dragged, grab)) if dragged == axis => {
+            let start = along(axis, bounds, event.position) - grab;
+            editor.update(cx, |editor, cx| {
+              let offset = offset_for_thumb(track, thumb_len, max_offset, start);
+              set_offset(editor, axis, offset, cx);
+            });
+            cx.stop_propagation();
+          }
+          None if bounds.contains(&event.position) => {
+            editor.update(cx, reveal);
+          }
+          _ => {}
+        }
+      }
+    });
+
+    window.on_mouse_event({
+      let editor = self.editor.clone();
+      move |event: &MouseUpEvent, phase, _window, cx| {
+        if phase == DispatchPhase::Capture
+          && event.button == MouseButton::Left
+          && editor
+            .read(cx)
+            .scrollbars
+            .drag
+            .is_some_and(|(dragged, _)| dragged == axis)
+        {
+          editor.update(cx, |editor, cx| {
+            editor.scrollbars.drag = None;
+            reveal(editor, cx);
+          });
+          cx.stop_propagation();
+        }
+      }
+    });
+
+    if !prepaint.visible {
+      return;
+    }
+    let (origin, thumb_size) = match axis {
+      ScrollbarAxis::Vertical => (
+        point(
+          bounds.left() + px(THUMB_INSET),
+          bounds.top() + px(thumb.start),
+        ),
+        size(bounds.size.width - px(2. * THUMB_INSET), px(thumb_len)),
+      ),
+      ScrollbarAxis::Horizontal => (
+        point(
+          bounds.left() + px(thumb.start),
+          bounds.top() + px(THUMB_INSET),
+        ),
+        size(px(thumb_len), bounds.size.height - px(2. * THUMB_INSET)),
+      ),
+    };
+    let color = self.editor.read(cx).theme.scrollbar_thumb();
+    window.paint_quad(
+      fill(Bounds::new(origin, thumb_size), color)
+        .corner_radii(Corners::all(px(SCROLLBAR_THICKNESS / 2.))),
+    );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[test]
+  fn test_thumb_range() {
+    // Nothing to scroll
+    assert_eq!(thumb_range(100., 10., 0., 0.), None);
+    // A fifth of the content is visible, scrolled halfway
+    assert_eq!(thumb_range(100., 10., 40., 20.), Some(40.0..60.0));
+    // Long content keeps a thumb to grab
+    assert_eq!(thumb_range(100., 1., 999., 999.), Some(80.0..100.0));
+    // Dragging the thumb back to where it is keeps the offset
+    assert_eq!(offset_for_thumb(100., 20., 40., 40.), 20.);
+    assert_eq!(offset_for_thumb(100., 20., 40., -5.), 0.);
+    assert_eq!(offset_for_thumb(100., 20., 40., 95.), 40.);
+  }
+
+  #[gpui::test]
+  fn test_scrollbars_hide_after_scrolling_stops(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "a\nb\nc");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert!(!is_visible(editor));
+      editor.scroll_offset_y = 1.0;
+      track_scrolling(editor, cx);
+      assert!(is_visible(editor));
+    });
+    cx.executor().advance_clock(HIDE_DELAY);
+    cx.run_until_parked();
+    ctx.editor.update(&mut ctx.cx, |editor, _| {
+      assert!(!is_visible(editor));
+    });
+  }
+}
@@ -0,0 +1,197 @@
+//! Text search queries shared by buffer and project search
+//!
+//! Whole-word matches start and end on the word bounds used for word
+//! movement, so a match is a word exactly when double-clicking would select
+//! it. Without matching diacritics, text is compared with combining marks
+//! removed from its canonical decomposition, so `cafe` finds `café`.
+
+use std::ops::Range;
+
+use unicode_normalization::char::{decompose_canonical, is_combining_mark};
+
+use crate::boundaries::word_bounds;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchQuery {
+  pub text: String,
+  pub case_sensitive: bool,
+  pub whole_word: bool,
+  pub match_diacritics: bool,
+}
+
+impl SearchQuery {
+  pub fn new(text: impl Into<String>) -> Self {
+    Self {
+      text: text.into(),
+      case_sensitive: false,
+      whole_word: false,
+      match_diacritics: true,
+    }
+  }
+
+  pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+    self.case_sensitive = case_sensitive;
+    self
+  }
+
+  pub fn whole_word(mut self, whole_word: bool) -> Self {
+    self.whole_word = whole_word;
+    self
+  }
+
+  pub fn match_diacritics(mut self, match_diacritics: bool) -> Self {
+    self.match_diacritics = match_diacritics;
+    self
+  }
+
+  /// `ch` as compared under the query's options, nothing for an ignored mark
+  fn fold(&self, ch: char, out: &mut Vec<char>) {
+    let mut push = |ch: char| {
+      if self.case_sensitive {
+        out.push(ch);
+      } else {
+        out.extend(ch.to_lowercase());
+      }
+    };
+    if self.match_diacritics {
+      push(ch);
+    } else {
+      decompose_canonical(ch, |ch| {
+        if !is_combining_mark(ch) {
+          push(ch);
+        }
+      });
+    }
+  }
+
+  fn ignores(&self, ch: char) -> bool {
+    !self.match_diacritics && is_combining_mark(ch)
+  }
+
+  /// Length in bytes of a match of the folded `needle` at `haystack`'s start
+  fn match_len(&self, haystack: &str, needle: &[char], folded: &mut Vec<char>) -> Option<usize> {
+    let mut matched = 0;
+    for (idx, ch) in haystack.char_indices() {
+      if matched == needle.len() {
+        if !is_combining_mark(ch) {
+          return Some(idx);
+        }
+        // Ignored marks on the last matched character are part of the match,
+        // others make it a different character
+        if !self.ignores(ch) {
+          return None;
+        }
+        continue;
+      }
+      folded.clear();
+      self.fold(ch, folded);
+      if !needle[matched..].starts_with(folded) {
+        return None;
+      }
+      matched += folded.len();
+    }
+    (matched == needle.len()).then_some(haystack.len())
+  }
+
+  /// Byte ranges of the non-overlapping matches in `haystack`
+  pub fn find_all(&self, haystack: &str) -> Vec<Range<usize>> {
+    let mut matches = Vec::new();
+    let mut needle = Vec::new();
+    for ch in self.text.chars() {
+      self.fold(ch, &mut needle);
+    }
+    if needle.is_empty() {
+      return matches;
+    }
+    let bounds = if self.whole_word {
+      word_bounds(haystack)
+    } else {
+      Vec::new()
+    };
+    let is_bound = |offset: usize| bounds.binary_search(&offset).is_ok();
+
+    let mut folded = Vec::new();
+    let mut search_from = 0;
+    for (start, ch) in haystack.char_indices() {
+      if start < search_from || self.ignores(ch) || (self.whole_word && !is_bound(start)) {
+        continue;
+      }
+      if let Some(len) = self.match_len(&haystack[start..], &needle, &mut folded)
+        && (!self.whole_word || is_bound(start + len))
+      {
+        matches.push(start..start + len);
+        search_from = start + len;
+      }
+    }
+    matches
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_find_all() {
+    let query = SearchQuery::new("ab");
+    assert_eq!(query.find_all("ab AB aB xab"), [0..2, 3..5, 6..8, 10..12]);
+    assert_eq!(
+      query.case_sensitive(true).find_all("ab AB aB xab"),
+      [0..2, 10..12]
+    );
+    // Matches don't overlap
+    assert_eq!(SearchQuery::new("aa").find_all("aaaa a"), [0..2, 2..4]);
+    assert!(SearchQuery::new("").find_all("text").is_empty());
+  }
+
+  #[test]
+  fn test_case_insensitive_non_ascii() {
+    assert_eq!(
+      SearchQuery::new("straße").find_all("STRAẞE Straße"),
+      [0..8, 9..16]
+    );
+    assert_eq!(SearchQuery::new("é").find_all("café CAFÉ"), [3..5, 9..11]);
+  }
+
+  #[test]
+  fn test_whole_word() {
+    let query = SearchQuery::new("foo").whole_word(true);
+    assert_eq!(
+      query.find_all("foo foobar foo::bar foo_bar (foo)"),
+      [0..3, 11..14, 29..32]
+    );
+    // Underscores, digits and a single dot between letters are part of
+    // words, as for word movement
+    assert!(query.find_all("foo1 _foo foo.bar").is_empty());
+    assert_eq!(
+      SearchQuery::new("a b")
+        .whole_word(true)
+        .find_all("a b ab b"),
+      [0..3]
+    );
+  }
+
+  #[test]
+  fn test_match_diacritics() {
+    let text = "cafe café cafe\u{301} CAFÉ";
+    assert_eq!(SearchQuery::new("cafe").find_all(text), [0..4]);
+
+    let query = SearchQuery::new("cafe").match_diacritics(false);
+    // The combining accent after a decomposed match is part of it
+    assert_eq!(query.find_all(text), [0..4, 5..10, 11..17, 18..23]);
+    assert_eq!(
+      SearchQuery::new("CAFÉ")
+        .case_sensitive(true)
+        .match_diacritics(false)
+        .find_all(text),
+      [18..23]
+    );
+    assert_eq!(
+      SearchQuery::new("cafe")
+        .match_diacritics(false)
+        .whole_word(true)
+        .find_all("cafés café"),
+      [7..12]
+    );
+  }
+}
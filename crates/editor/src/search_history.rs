@@ -0,0 +1,297 @@
+//! Recent and saved searches of a workspace
+//!
+//! Every search run is remembered with its replacement and options, most
+//! recent last, and can be walked through with up and down like a shell
+//! history. Searches can also be saved under a name. Both lists persist in
+//! one file per workspace, with one tab separated record per line.
+
+use std::{
+  collections::VecDeque,
+  path::{Path, PathBuf},
+};
+
+use crate::{paths, search::SearchQuery};
+
+/// Recent searches kept per workspace
+const MAX_RECENT: usize = 50;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchHistoryEntry {
+  pub query: SearchQuery,
+  pub replacement: Option<String>,
+}
+
+/// What down shows in the search field
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HistoryItem<'a> {
+  Entry(&'a SearchHistoryEntry),
+  /// Past the newest search, the text typed before browsing
+  Draft(String),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchHistory {
+  recent: VecDeque<SearchHistoryEntry>,
+  saved: Vec<(String, SearchHistoryEntry)>,
+  /// Position while walking through `recent`, and the text typed before
+  browsing: Option<(usize, String)>,
+}
+
+impl SearchHistory {
+  /// History file of `workspace` in the editor's config directory
+  pub fn store_path(workspace: &Path) -> Option<PathBuf> {
+    Some(
+      paths::config_dir()?
+        .join("search_history")
+        .join(paths::workspace_file_name(workspace)),
+    )
+  }
+
+  /// Remember a search that was run, moving a repeated one to the end
+  pub fn push(&mut self, entry: SearchHistoryEntry) {
+    self.browsing = None;
+    if entry.query.text.is_empty() {
+      return;
+    }
+    self.recent.retain(|recent| *recent != entry);
+    self.recent.push_back(entry);
+    if self.recent.len() > MAX_RECENT {
+      self.recent.pop_front();
+    }
+  }
+
+  pub fn recent(&self) -> impl DoubleEndedIterator<Item = &SearchHistoryEntry> {
+    self.recent.iter()
+  }
+
+  /// The search before the one shown (up), starting from the most recent; `draft`
+  /// is the text in the search field, given back after the newest entry
+  pub fn older(&mut self, draft: &str) -> Option<&SearchHistoryEntry> {
+    let ix = match &self.browsing {
+      Some((ix, _)) => ix.checked_sub(1)?,
+      None => self.recent.len().checked_sub(1)?,
+    };
+    let draft = self
+      .browsing
+      .take()
+      .map_or_else(|| draft.to_string(), |(_, draft)| draft);
+    self.browsing = Some((ix, draft));
+    self.recent.get(ix)
+  }
+
+  /// The search after the one shown (down), none unless browsing
+  pub fn newer(&mut self) -> Option<HistoryItem<'_>> {
+    let (ix, draft) = self.browsing.take()?;
+    if ix + 1 < self.recent.len() {
+      self.browsing = Some((ix + 1, draft));
+      Some(HistoryItem::Entry(&self.recent[ix + 1]))
+    } else {
+      Some(HistoryItem::Draft(draft))
+    }
+  }
+
+  /// Save `entry` as `name`, replacing a saved search of the same name
+  pub fn save_as(&mut self, name: impl Into<String>, entry: SearchHistoryEntry) {
+    let name = name.into();
+    match self.saved.iter_mut().find(|(saved, _)| *saved == name) {
+      Some((_, saved)) => *saved = entry,
+      None => self.saved.push((name, entry)),
+    }
+  }
+
+  pub fn remove_saved(&mut self, name: &str) {
+    self.saved.retain(|(saved, _)| saved != name);
+  }
+
+  pub fn saved(&self) -> impl Iterator<Item = (&str, &SearchHistoryEntry)> {
+    self
+      .saved
+      .iter()
+      .map(|(name, entry)| (name.as_str(), entry))
+  }
+
+  /// History stored at `path`, empty if there is none yet
+  pub fn load(path: &Path) -> Self {
+    let mut history = Self::default();
+    let Ok(contents) = std::fs::read_to_string(path) else {
+      return history;
+    };
+    for line in contents.lines() {
+      let fields: Vec<String> = line.split('\t').map(unescape).collect();
+      match fields.as_slice() {
+        [kind, rest @ ..] if kind == "recent" => {
+          if let Some(entry) = parse_entry(rest) {
+            history.push(entry);
+          }
+        }
+        [kind, name, rest @ ..] if kind == "saved" => {
+          if let Some(entry) = parse_entry(rest) {
+            history.save_as(name.clone(), entry);
+          }
+        }
+        _ => {}
+      }
+    }
+    history
+  }
+
+  pub fn save(&self, path: &Path) -> Result<(), String> {
+    let mut contents = String::new();
+    for entry in &self.recent {
+      contents.push_str(&format!("recent\t{}\n", format_entry(entry)));
+    }
+    for (name, entry) in &self.saved {
+      contents.push_str(&format!(
+        "saved\t{}\t{}\n",
+        escape(name),
+        format_entry(entry)
+      ));
+    }
+    path
+      .parent()
+      .map_or(Ok(()), std::fs::create_dir_all)
+      .and_then(|_| std::fs::write(path, contents))
+      .map_err(|err| err.to_string())
+  }
+}
+
+/// Options of a query as letters: `c` case-sensitive, `w` whole word, `i`
+/// ignoring diacritics
+fn format_flags(query: &SearchQuery) -> String {
+  let mut flags = String::new();
+  if query.case_sensitive {
+    flags.push('c');
+  }
+  if query.whole_word {
+    flags.push('w');
+  }
+  if !query.match_diacritics {
+    flags.push('i');
+  }
+  flags
+}
+
+/// `flags, query[, replacement]` of a record
+fn format_entry(entry: &SearchHistoryEntry) -> String {
+  let mut fields = vec![format_flags(&entry.query), escape(&entry.query.text)];
+  if let Some(replacement) = &entry.replacement {
+    fields.push(escape(replacement));
+  }
+  fields.join("\t")
+}
+
+fn parse_entry(fields: &[String]) -> Option<SearchHistoryEntry> {
+  let [flags, text, rest @ ..] = fields else {
+    return None;
+  };
+  let query = SearchQuery::new(text.clone())
+    .case_sensitive(flags.contains('c'))
+    .whole_word(flags.contains('w'))
+    .match_diacritics(!flags.contains('i'));
+  Some(SearchHistoryEntry {
+    query,
+    replacement: rest.first().cloned(),
+  })
+}
+
+/// Escape the characters that separate fields and records
+fn escape(text: &str) -> String {
+  text
+    .replace('\\', "\\\\")
+    .replace('\t', "\\t")
+    .replace('\n', "\\n")
+}
+
+fn unescape(field: &str) -> String {
+  let mut text = String::with_capacity(field.len());
+  let mut chars = field.chars();
+  while let Some(ch) = chars.next() {
+    if ch != '\\' {
+      text.push(ch);
+      continue;
+    }
+    match chars.next() {
+      Some('t') => text.push('\t'),
+      Some('n') => text.push('\n'),
+      Some(other) => text.push(other),
+      None => text.push('\\'),
+    }
+  }
+  text
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry(text: &str) -> SearchHistoryEntry {
+    SearchHistoryEntry {
+      query: SearchQuery::new(text),
+      replacement: None,
+    }
+  }
+
+  #[test]
+  fn test_push_moves_repeats_to_the_end() {
+    let mut history = SearchHistory::default();
+    for text in ["one", "two", "one", ""] {
+      history.push(entry(text));
+    }
+    let recent: Vec<_> = history.recent().map(|e| e.query.text.as_str()).collect();
+    assert_eq!(recent, ["two", "one"]);
+
+    for ix in 0..MAX_RECENT + 5 {
+      history.push(entry(&ix.to_string()));
+    }
+    assert_eq!(history.recent().count(), MAX_RECENT);
+  }
+
+  #[test]
+  fn test_up_and_down_navigation() {
+    let mut history = SearchHistory::default();
+    assert!(history.older("draft").is_none());
+    assert!(history.newer().is_none());
+    for text in ["one", "two", "three"] {
+      history.push(entry(text));
+    }
+
+    assert_eq!(history.older("dra").unwrap().query.text, "three");
+    assert_eq!(history.older("ignored").unwrap().query.text, "two");
+    assert_eq!(history.older("").unwrap().query.text, "one");
+    assert!(history.older("").is_none());
+    assert_eq!(history.newer(), Some(HistoryItem::Entry(&entry("two"))));
+    assert_eq!(history.newer(), Some(HistoryItem::Entry(&entry("three"))));
+    // Past the newest search the typed text comes back
+    assert_eq!(history.newer(), Some(HistoryItem::Draft("dra".to_string())));
+    assert!(history.newer().is_none());
+    assert_eq!(history.older("dra").unwrap().query.text, "three");
+  }
+
+  #[test]
+  fn test_persistence() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("search_history");
+    let mut history = SearchHistory::default();
+    history.push(SearchHistoryEntry {
+      query: SearchQuery::new("a\tb\\n")
+        .case_sensitive(true)
+        .whole_word(true)
+        .match_diacritics(false),
+      replacement: Some("line\nbreak".to_string()),
+    });
+    history.push(entry("plain"));
+    history.save_as("todos", entry("TODO"));
+    history.save_as("todos", entry("TODO|FIXME"));
+    history.save_as("old", entry("deprecated"));
+    history.remove_saved("old");
+    history.save(&path).unwrap();
+
+    let loaded = SearchHistory::load(&path);
+    assert_eq!(loaded.recent, history.recent);
+    let saved: Vec<_> = loaded
+      .saved()
+      .map(|(name, entry)| (name, entry.query.text.as_str()))
+      .collect();
+    assert_eq!(saved, [("todos", "TODO|FIXME")]);
+  }
+}
@@ -0,0 +1,279 @@
+//! Layout of a selection as spans of display rows
+//!
+//! A selection covers a part of every display row between its ends: rows of
+//! a wrapped line each get the bytes of the line they show, and lines hidden
+//! by a fold get none, the fold's first line being selected through its
+//! line break instead. This is where selections follow the rows of the
+//! display map; rendering turns each span into a quad.
+
+use std::ops::Range;
+
+use crate::{document::Document, line_index::ViewportLineIndex, wrap_map::RowLayout};
+
+/// The selected part of one display row
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct RowSpan {
+  pub row: usize,
+  /// Buffer line shown on the row
+  pub line: usize,
+  /// Selected bytes of the line on the row, without its line break
+  pub bytes: Range<usize>,
+  /// The selection continues past the end of the row, through the line
+  /// break at the end of it
+  pub past_end: bool,
+}
+
+/// Spans of `range` on the rows of `rows` in the viewport, top to bottom
+pub(crate) fn selection_layout(
+  range: Range<usize>,
+  rows: &RowLayout,
+  document: &Document,
+  line_index: &ViewportLineIndex,
+) -> Vec<RowSpan> {
+  let lines = rows.lines();
+  if range.is_empty() || lines.is_empty() {
+    return Vec::new();
+  }
+
+  let first_line = line_index
+    .char_to_line(document, range.start)
+    .max(lines.start);
+  let last_line = line_index
+    .char_to_line(document, range.end)
+    .min(lines.end - 1);
+
+  let mut spans = Vec::new();
+  for line in first_line..=last_line {
+    if !rows.is_shown(line) {
+      continue;
+    }
+    let Some(line_range) = line_index.line_range(document, line) else {
+      break;
+    };
+    let content = document.line_content(line).unwrap_or_default();
+    let content_end = line_range.start + content.chars().count();
+
+    let start = range.start.max(line_range.start);
+    let end = range.end.min(content_end);
+    let past_end = range.end > content_end && line_range.end > content_end;
+    if start > end || (start == end && !past_end) {
+      // Nothing of this line is selected, e.g. the selection ends at its start
+      continue;
+    }
+    let byte_for_column = |column: usize| {
+      content
+        .char_indices()
+        .nth(column)
+        .map_or(content.len(), |(byte, _)| byte)
+    };
+    let bytes = byte_for_column(start - line_range.start)..byte_for_column(end - line_range.start);
+    for (row, bytes) in rows.spans(line, bytes) {
+      spans.push(RowSpan {
+        row,
+        line,
+        // Only the last row of a wrapped line ends with the line break
+        past_end: past_end && bytes.end == content.len(),
+        bytes,
+      });
+    }
+  }
+  spans
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{display_map::DisplayMap, wrap_map::line_ranges};
+  use gpui::{AppContext, TestAppContext};
+
+  fn span(row: usize, line: usize, bytes: Range<usize>, past_end: bool) -> RowSpan {
+    RowSpan {
+      row,
+      line,
+      bytes,
+      past_end,
+    }
+  }
+
+  #[gpui::test]
+  fn test_selection_layout(cx: &mut TestAppContext) {
+    // Lines: "alpha", "", "gamma", "delta" with a CRLF break, "omega"
+    let doc = cx.new(|cx| Document::new("alpha\n\ngamma\ndelta\r\nomega", None, cx));
+
+    doc.read_with(cx, |doc, _| {
+      let all_rows = 0..doc.len_lines();
+      let cases = [
+        // Empty selection
+        (2..2, all_rows.clone(), vec![]),
+        // Within one row
+        (1..4, all_rows.clone(), vec![span(0, 0, 1..4, false)]),
+        // Up to the end of a row, without its line break
+        (3..5, all_rows.clone(), vec![span(0, 0, 3..5, false)]),
+        // Only the line break
+        (5..6, all_rows.clone(), vec![span(0, 0, 5..5, true)]),
+        // Across an empty row, ending at the start of a row
+        (
+          3..7,
+          all_rows.clone(),
+          vec![span(0, 0, 3..5, true), span(1, 1, 0..0, true)],
+        ),
+        // Across a CRLF break into the last row
+        (
+          10..21,
+          all_rows.clone(),
+          vec![
+            span(2, 2, 3..5, true),
+            span(3, 3, 0..5, true),
+            span(4, 4, 0..1, false),
+          ],
+        ),
+        // Everything
+        (
+          0..doc.len(),
+          all_rows.clone(),
+          vec![
+            span(0, 0, 0..5, true),
+            span(1, 1, 0..0, true),
+            span(2, 2, 0..5, true),
+            span(3, 3, 0..5, true),
+            span(4, 4, 0..5, false),
+          ],
+        ),
+        // Clipped to the visible rows
+        (
+          0..doc.len(),
+          2..4,
+          vec![span(2, 2, 0..5, true), span(3, 3, 0..5, true)],
+        ),
+      ];
+
+      for (range, rows, expected) in cases {
+        let layout = RowLayout::unmapped(rows.clone(), &[], doc);
+        for line_index in [
+          ViewportLineIndex::default(),
+          ViewportLineIndex::new(doc, rows.clone()),
+        ] {
+          assert_eq!(
+            selection_layout(range.clone(), &layout, doc, &line_index),
+            expected,
+            "selection {:?} in rows {:?}",
+            range,
+            rows
+          );
+        }
+      }
+    });
+  }
+
+  #[gpui::test]
+  fn test_selection_layout_with_wrap_and_folds(cx: &mut TestAppContext) {
+    // Lines: "ab cd", "ef", "g", "hijk"; wrapped at 3 columns "ab cd" shows
+    // "ab " and "cd", and "hijk" shows "hij" and "k"
+    let doc = cx.new(|cx| Document::new("ab cd\nef\ng\nhijk", None, cx));
+
+    doc.read_with(cx, |doc, _| {
+      // Folding line 0 hides line 1 and adds a placeholder after "ab cd",
+      // whose end wraps onto a row of its own
+      let no_folds: &[Range<usize>] = &[];
+      let fold: &[Range<usize>] = &[0..2];
+      let cases = [
+        (
+          None,
+          no_folds,
+          3..12,
+          vec![
+            span(0, 0, 3..5, true),
+            span(1, 1, 0..2, true),
+            span(2, 2, 0..1, true),
+            span(3, 3, 0..1, false),
+          ],
+        ),
+        (
+          Some(3),
+          no_folds,
+          3..12,
+          vec![
+            span(1, 0, 3..5, true),
+            span(2, 1, 0..2, true),
+            span(3, 2, 0..1, true),
+            span(4, 3, 0..1, false),
+          ],
+        ),
+        (
+          None,
+          fold,
+          3..12,
+          vec![
+            span(0, 0, 3..5, true),
+            span(1, 2, 0..1, true),
+            span(2, 3, 0..1, false),
+          ],
+        ),
+        (
+          Some(3),
+          fold,
+          3..12,
+          vec![
+            span(1, 0, 3..5, true),
+            span(3, 2, 0..1, true),
+            span(4, 3, 0..1, false),
+          ],
+        ),
+        // Ending in the line a fold hides
+        (
+          None,
+          no_folds,
+          2..7,
+          vec![span(0, 0, 2..5, true), span(1, 1, 0..1, false)],
+        ),
+        (
+          Some(3),
+          no_folds,
+          2..7,
+          vec![
+            span(0, 0, 2..3, false),
+            span(1, 0, 3..5, true),
+            span(2, 1, 0..1, false),
+          ],
+        ),
+        (None, fold, 2..7, vec![span(0, 0, 2..5, true)]),
+        (
+          Some(3),
+          fold,
+          2..7,
+          vec![span(0, 0, 2..3, false), span(1, 0, 3..5, true)],
+        ),
+        // Within the last line
+        (None, no_folds, 12..15, vec![span(3, 3, 1..4, false)]),
+        (
+          Some(3),
+          no_folds,
+          12..15,
+          vec![span(4, 3, 1..3, false), span(5, 3, 3..4, false)],
+        ),
+        (None, fold, 12..15, vec![span(2, 3, 1..4, false)]),
+        (
+          Some(3),
+          fold,
+          12..15,
+          vec![span(4, 3, 1..3, false), span(5, 3, 3..4, false)],
+        ),
+      ];
+
+      let lines = line_ranges(doc, 0..doc.len_lines());
+      for (wrap, folds, range, expected) in cases {
+        let map = DisplayMap::new(&lines, folds, &[], wrap);
+        let layout = RowLayout::mapped(&map, 0..map.row_count(), doc);
+        let line_index = ViewportLineIndex::new(doc, layout.lines());
+        assert_eq!(
+          selection_layout(range.clone(), &layout, doc, &line_index),
+          expected,
+          "selection {:?} wrapped at {:?} with folds {:?}",
+          range,
+          wrap,
+          folds
+        );
+      }
+    });
+  }
+}
@@ -0,0 +1,83 @@
+//! Selection lock, where movements extend the selection
+//!
+//! Like setting the mark in Emacs: while the lock is on, the plain movement
+//! keys act as their selecting counterparts, extending the selection from
+//! where it was when the lock was turned on. The lock ends when it is toggled
+//! off, on Escape or a click, and once the text changes.
+
+use gpui::{App, Context};
+
+use crate::editor::Editor;
+
+/// Whether the lock is on and the text hasn't changed since it was turned on
+pub(crate) fn is_locked(editor: &Editor, cx: &App) -> bool {
+  editor.selection_lock == Some(editor.document.read(cx).version())
+}
+
+/// Turn the lock on, keeping the selection as it is, or off
+pub(crate) fn toggle(editor: &mut Editor, cx: &mut Context<Editor>) {
+  editor.selection_lock = if is_locked(editor, cx) {
+    None
+  } else {
+    Some(editor.document.read(cx).version())
+  };
+  cx.notify();
+}
+
+pub(crate) fn cancel(editor: &mut Editor, cx: &mut Context<Editor>) {
+  if editor.selection_lock.take().is_some() {
+    cx.notify();
+  }
+}
+
+/// Move the cursor to the other end of the selection
+pub(crate) fn swap_ends(editor: &mut Editor, cx: &mut Context<Editor>) {
+  if editor.selected_range.is_empty() {
+    return;
+  }
+  editor.selection_reversed = !editor.selection_reversed;
+  editor.target_column = None;
+  cx.notify();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[gpui::test]
+  fn test_swap_ends(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "hello world");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.selected_range = 2..5;
+      swap_ends(editor, cx);
+      assert_eq!(editor.cursor_offset(), 2);
+
+      // Extending now moves the start, the end stays where it was
+      editor.select_to(0, cx);
+      assert_eq!(editor.selected_range, 0..5);
+      swap_ends(editor, cx);
+      editor.select_to(8, cx);
+      assert_eq!(editor.selected_range, 0..8);
+    });
+  }
+
+  #[gpui::test]
+  fn test_edit_ends_lock(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "hello");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      toggle(editor, cx);
+      assert!(is_locked(editor, cx));
+      toggle(editor, cx);
+      assert!(!is_locked(editor, cx));
+
+      toggle(editor, cx);
+      crate::unicode_input::insert(editor, "!", cx);
+      assert!(!is_locked(editor, cx));
+      // Toggling after an edit turns a new lock on
+      toggle(editor, cx);
+      assert!(is_locked(editor, cx));
+    });
+  }
+}
@@ -0,0 +1,92 @@
+//! Spreading line shaping over frames
+//!
+//! Shaping a long line of non-ASCII text can take milliseconds, and a fast
+//! scroll brings many of them into view at once. Each frame shapes lines until
+//! its budget is spent and defers the expensive lines left over to the next
+//! frame. Until then a deferred line is a placeholder: the columns in view are
+//! painted as plain ASCII text, one per monospace advance of the font, and the
+//! cursor and selection on it are placed by the same advance.
+
+use std::time::Duration;
+
+use gpui::{Pixels, TextStyle, Window};
+
+/// Time a frame spends shaping before it defers expensive lines
+pub(crate) const FRAME_BUDGET: Duration = Duration::from_millis(4);
+
+/// Lines longer than this many bytes are expensive to shape
+const LONG_LINE_BYTES: usize = 500;
+
+/// Advance as a fraction of the font size when the font can't be measured
+const FALLBACK_ADVANCE: f32 = 0.6;
+
+/// Whether shaping `line` could take long enough to be worth deferring
+pub(crate) fn is_expensive(line: &str) -> bool {
+  line.len() > LONG_LINE_BYTES || !line.is_ascii()
+}
+
+/// Whether `line` waits for the next frame after `elapsed` of shaping
+pub(crate) fn should_defer(line: &str, elapsed: Duration) -> bool {
+  elapsed >= FRAME_BUDGET && is_expensive(line)
+}
+
+/// Width of one column of `style`'s font, measured on `m`
+pub(crate) fn monospace_advance(style: &TextStyle, window: &Window) -> Pixels {
+  let font_size = style.font_size.to_pixels(window.rem_size());
+  let text_system = window.text_system();
+  let font_id = text_system.resolve_font(&style.font());
+  text_system
+    .advance(font_id, font_size, 'm')
+    .map_or(font_size * FALLBACK_ADVANCE, |advance| advance.width)
+}
+
+/// Stand-in for non-ASCII characters on a placeholder line
+const PLACEHOLDER_CHAR: char = '.';
+
+/// First `columns` characters of `line` as text that is cheap to shape, one
+/// character per column
+pub(crate) fn placeholder_text(line: &str, columns: usize) -> String {
+  line
+    .chars()
+    .take(columns)
+    .map(|ch| match ch {
+      ' '..='~' => ch,
+      '\t' => ' ',
+      _ => PLACEHOLDER_CHAR,
+    })
+    .collect()
+}
+
+/// X of `column` on a placeholder line
+pub(crate) fn placeholder_x(column: usize, advance: Pixels) -> Pixels {
+  advance * column as f32
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use gpui::px;
+
+  #[test]
+  fn test_only_expensive_lines_are_deferred() {
+    let over_budget = FRAME_BUDGET + Duration::from_millis(1);
+    assert!(!should_defer("let x = 1;", over_budget));
+    assert!(should_defer("let é = 1;", over_budget));
+    assert!(should_defer(&"x".repeat(LONG_LINE_BYTES + 1), over_budget));
+    // Nothing waits while the budget lasts
+    assert!(!should_defer("let é = 1;", Duration::ZERO));
+  }
+
+  #[test]
+  fn test_placeholder_text() {
+    assert_eq!(placeholder_text("let é\t= 1;", 20), "let . = 1;");
+    assert_eq!(placeholder_text("abcdef", 3), "abc");
+    assert!(!is_expensive(&placeholder_text(&"日本".repeat(400), 400)));
+  }
+
+  #[test]
+  fn test_placeholder_x() {
+    assert_eq!(placeholder_x(0, px(8.0)), px(0.0));
+    assert_eq!(placeholder_x(3, px(8.0)), px(24.0));
+  }
+}
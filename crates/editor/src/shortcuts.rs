@@ -0,0 +1,308 @@
+//! Cheat sheet of the key bindings in effect
+//!
+//! The sheet is built from the keymap every time it renders, so bindings
+//! added or replaced after startup show up without anything to keep in sync.
+//! Actions that are in the menu bar are listed under their menu with the
+//! item's label; everything else goes under "Other", named after the action.
+
+use gpui::{
+  App, Context, FocusHandle, Focusable, KeyDownEvent, MenuItem, WeakEntity, Window, div,
+  prelude::*, px,
+};
+
+use crate::{actions::Backspace, editor::Editor, escape, menus::app_menus};
+
+/// Category of actions that aren't in any menu
+const OTHER: &str = "Other";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Shortcut {
+  pub category: String,
+  pub label: String,
+  pub keystrokes: String,
+}
+
+/// "editor::SelectWordLeft" as "Select Word Left"
+pub(crate) fn humanize(action_name: &str) -> String {
+  let name = action_name.rsplit("::").next().unwrap_or(action_name);
+  let mut label = String::new();
+  for ch in name.chars() {
+    if ch.is_uppercase() && !label.is_empty() {
+      label.push(' ');
+    }
+    label.push(ch);
+  }
+  label
+}
+
+/// The shortcuts of the active keymap, grouped by menu in menu bar order
+/// and then by label, with actions in no menu last
+pub(crate) fn shortcuts(editor: &Editor, cx: &App) -> Vec<Shortcut> {
+  let menus = app_menus(editor, cx);
+  let keymap = cx.key_bindings();
+  let keymap = keymap.borrow();
+
+  let mut shortcuts: Vec<(usize, Shortcut)> = Vec::new();
+  for binding in keymap.bindings() {
+    let keystrokes = binding
+      .keystrokes()
+      .iter()
+      .map(|keystroke| keystroke.to_string())
+      .collect::<Vec<_>>()
+      .join(" ");
+    // Bindings added later take precedence over those for the same keys
+    shortcuts.retain(|(_, shortcut)| shortcut.keystrokes != keystrokes);
+
+    let action = binding.action();
+    if action.name().ends_with("NoAction") {
+      continue;
+    }
+    let in_menu = menus.iter().enumerate().find_map(|(ix, menu)| {
+      menu.items.iter().find_map(|item| match item {
+        MenuItem::Action {
+          name, action: item, ..
+        } if item.partial_eq(action) => Some((ix, menu.name.to_string(), name.to_string())),
+        _ => None,
+      })
+    });
+    let (order, category, label) =
+      in_menu.unwrap_or((menus.len(), OTHER.to_string(), humanize(action.name())));
+    shortcuts.push((
+      order,
+      Shortcut {
+        category,
+        label,
+        keystrokes,
+      },
+    ));
+  }
+
+  shortcuts.sort_by(|(a_order, a), (b_order, b)| {
+    (a_order, &a.label, &a.keystrokes).cmp(&(b_order, &b.label, &b.keystrokes))
+  });
+  shortcuts
+    .into_iter()
+    .map(|(_, shortcut)| shortcut)
+    .collect()
+}
+
+/// Shortcuts whose category, label or keystrokes contain every word of `query`
+pub(crate) fn filter(shortcuts: Vec<Shortcut>, query: &str) -> Vec<Shortcut> {
+  let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+  shortcuts
+    .into_iter()
+    .filter(|shortcut| {
+      let haystack = format!(
+        "{} {} {}",
+        shortcut.category, shortcut.label, shortcut.keystrokes
+      )
+      .to_lowercase();
+      words.iter().all(|word| haystack.contains(word.as_str()))
+    })
+    .collect()
+}
+
+pub struct ShortcutSheet {
+  editor: WeakEntity<Editor>,
+  focus_handle: FocusHandle,
+  query: String,
+}
+
+/// Open the cheat sheet and give it focus
+pub(crate) fn open(editor: &mut Editor, window: &mut Window, cx: &mut Context<Editor>) {
+  let editor_handle = cx.weak_entity();
+  let sheet = cx.new(|cx| ShortcutSheet {
+    editor: editor_handle,
+    focus_handle: cx.focus_handle(),
+    query: String::new(),
+  });
+  window.focus(&sheet.focus_handle(cx), cx);
+  editor.shortcut_sheet = Some(sheet);
+  cx.notify();
+}
+
+/// Close the cheat sheet, returning focus to the editor if it had it
+pub(crate) fn close(editor: &mut Editor, window: &mut Window, cx: &mut Context<Editor>) {
+  let Some(sheet) = editor.shortcut_sheet.take() else {
+    return;
+  };
+  if sheet.focus_handle(cx).is_focused(window) {
+    window.focus(&editor.focus_handle, cx);
+  }
+  cx.notify();
+}
+
+impl ShortcutSheet {
+  fn set_query(&mut self, query: String, cx: &mut Context<Self>) {
+    self.query = query;
+    cx.notify();
+  }
+
+  fn dismiss(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+    let _ = self.editor.update(cx, |editor, cx| {
+      escape::close_topmost(editor, window, cx);
+    });
+  }
+
+  fn key_down(&mut self, event: &KeyDownEvent, window: &mut Window, cx: &mut Context<Self>) {
+    let keystroke = &event.keystroke;
+    if keystroke.key == "escape" {
+      self.dismiss(window, cx);
+    } else if let Some(key_char) = keystroke.key_char.as_ref()
+      && !keystroke.modifiers.platform
+      && !keystroke.modifiers.control
+    {
+      let query = format!("{}{}", self.query, key_char);
+      self.set_query(query, cx);
+    } else {
+      return;
+    }
+    cx.stop_propagation();
+  }
+}
+
+impl Focusable for ShortcutSheet {
+  fn focus_handle(&self, _: &App) -> FocusHandle {
+    self.focus_handle.clone()
+  }
+}
+
+impl Render for ShortcutSheet {
+  fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+    let Some(editor) = self.editor.upgrade() else {
+      return div().id("shortcut-sheet");
+    };
+    let editor = editor.read(cx);
+    let theme = editor.theme.clone();
+    let shortcuts = filter(shortcuts(editor, cx), &self.query);
+    let query = if self.query.is_empty() {
+      "Filter shortcuts".to_string()
+    } else {
+      self.query.clone()
+    };
+
+    let mut rows = Vec::new();
+    let mut category = None;
+    for shortcut in shortcuts {
+      if category.as_ref() != Some(&shortcut.category) {
+        rows.push(
+          div()
+            .px_1()
+            .pt_1()
+            .text_color(theme.line_number())
+            .child(shortcut.category.clone()),
+        );
+        category = Some(shortcut.category.clone());
+      }
+      rows.push(
+        div()
+          .px_1()
+          .flex()
+          .justify_between()
+          .child(shortcut.label)
+          .child(shortcut.keystrokes),
+      );
+    }
+
+    div()
+      .id("shortcut-sheet")
+      .key_context("ShortcutSheet")
+      .track_focus(&self.focus_handle)
+      .on_action(cx.listener(|this, _: &Backspace, _, cx| {
+        let mut query = this.query.clone();
+        query.pop();
+        this.set_query(query, cx);
+      }))
+      .on_key_down(cx.listener(Self::key_down))
+      .w(px(420.0))
+      .max_h(px(480.0))
+      .overflow_y_scroll()
+      .flex()
+      .flex_col()
+      .p_1()
+      .rounded_md()
+      .border_1()
+      .border_color(theme.line_number())
+      .bg(theme.gutter_background())
+      .text_size(px(12.0))
+      .child(div().px_1().pb_1().child(query))
+      .children(rows)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    actions::{InsertUnicode, SelectWordLeft, ZoomIn},
+    editor::tests::EditorTestContext,
+  };
+  use gpui::{KeyBinding, TestAppContext};
+
+  fn shortcut(category: &str, label: &str, keystrokes: &str) -> Shortcut {
+    Shortcut {
+      category: category.into(),
+      label: label.into(),
+      keystrokes: keystrokes.into(),
+    }
+  }
+
+  #[test]
+  fn test_humanize() {
+    assert_eq!(humanize("editor::SelectWordLeft"), "Select Word Left");
+    assert_eq!(humanize("Undo"), "Undo");
+  }
+
+  #[test]
+  fn test_filter() {
+    let all = vec![
+      shortcut("Edit", "Copy", "cmd-c"),
+      shortcut("View", "Zoom In", "cmd-="),
+      shortcut(OTHER, "Select Word Left", "shift-alt-left"),
+    ];
+    assert_eq!(filter(all.clone(), ""), all);
+    assert_eq!(filter(all.clone(), "zoom"), [all[1].clone()]);
+    assert_eq!(filter(all.clone(), "EDIT cmd-c"), [all[0].clone()]);
+    assert_eq!(filter(all.clone(), "other alt"), [all[2].clone()]);
+    assert!(filter(all, "nothing").is_empty());
+  }
+
+  #[gpui::test]
+  fn test_shortcuts_follow_keymap(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "");
+    ctx.cx.update(|cx| {
+      cx.bind_keys([
+        KeyBinding::new("shift-alt-left", SelectWordLeft, None),
+        KeyBinding::new("alt-=", ZoomIn, None),
+        KeyBinding::new("ctrl-u", InsertUnicode, None),
+      ])
+    });
+    let listed = ctx
+      .editor
+      .read_with(&ctx.cx, |editor, cx| shortcuts(editor, cx));
+    assert_eq!(
+      listed,
+      [
+        shortcut("Edit", "Insert Unicode Character…", "ctrl-u"),
+        shortcut("View", "Zoom In", "alt-="),
+        shortcut(OTHER, "Select Word Left", "shift-alt-left"),
+      ]
+    );
+
+    // Rebinding the keys replaces what the sheet lists for them
+    ctx
+      .cx
+      .update(|cx| cx.bind_keys([KeyBinding::new("alt-=", InsertUnicode, None)]));
+    let listed = ctx
+      .editor
+      .read_with(&ctx.cx, |editor, cx| shortcuts(editor, cx));
+    assert_eq!(
+      listed,
+      [
+        shortcut("Edit", "Insert Unicode Character…", "ctrl-u"),
+        shortcut("Edit", "Insert Unicode Character…", "alt-="),
+        shortcut(OTHER, "Select Word Left", "shift-alt-left"),
+      ]
+    );
+  }
+}
@@ -0,0 +1,352 @@
+//! Tail mode for documents backed by a growing log file
+//!
+//! The file is polled for new complete lines, which are appended to the
+//! document outside of undo history. A file truncated or rotated since the
+//! last poll is read again from the start in place of the document's
+//! contents. Lines matching configured patterns are
+//! highlighted with decorations, and the view follows the end of the file
+//! unless the user has scrolled up.
+
+use std::{
+  fs::File,
+  io::{Read, Seek, SeekFrom},
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+use gpui::{Context, Hsla, Task, red, yellow};
+
+use crate::{
+  decorations::{DecorationId, DecorationStyle},
+  editor::Editor,
+};
+
+/// Delay between checks of the file for new content
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Lines containing `needle` get a `color` background
+#[derive(Clone, Debug)]
+pub struct TailPattern {
+  pub needle: String,
+  pub color: Hsla,
+}
+
+impl TailPattern {
+  pub fn new(needle: impl Into<String>, color: Hsla) -> Self {
+    Self {
+      needle: needle.into(),
+      color,
+    }
+  }
+}
+
+pub fn default_tail_patterns() -> Vec<TailPattern> {
+  let mut error = red();
+  error.a = 0.25;
+  let mut warning = yellow();
+  warning.a = 0.2;
+  vec![
+    TailPattern::new("ERROR", error),
+    TailPattern::new("WARN", warning),
+  ]
+}
+
+pub struct TailMode {
+  path: PathBuf,
+  // Bytes of the file already appended to the document
+  read_offset: u64,
+  patterns: Vec<TailPattern>,
+  decorations: Vec<DecorationId>,
+  _poll_task: Task<()>,
+}
+
+impl TailMode {
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+}
+
+/// Output read from the file since the last poll
+#[derive(Debug, PartialEq, Eq)]
+enum TailRead {
+  /// Complete lines appended after what was read, and the offset past them
+  Appended(u64, String),
+  /// The file got shorter, so it was truncated or replaced; its complete
+  /// lines from the start, and the offset past them
+  Restarted(u64, String),
+}
+
+impl TailRead {
+  fn read_offset(&self) -> u64 {
+    match self {
+      TailRead::Appended(offset, _) | TailRead::Restarted(offset, _) => *offset,
+    }
+  }
+}
+
+/// Start following `path`, assuming the document holds its first `read_offset` bytes
+pub(crate) fn start(
+  editor: &mut Editor,
+  path: PathBuf,
+  read_offset: u64,
+  patterns: Vec<TailPattern>,
+  cx: &mut Context<Editor>,
+) {
+  stop(editor, cx);
+
+  let poll_task = cx.spawn({
+    let path = path.clone();
+    async move |this, cx| {
+      loop {
+        cx.background_executor().timer(POLL_INTERVAL).await;
+
+        let Ok(offset) = this.read_with(cx, |editor, _| {
+          editor.tail.as_ref().map_or(0, |tail| tail.read_offset)
+        }) else {
+          break;
+        };
+        let file_path = path.clone();
+        let chunk = cx
+          .background_executor()
+          .spawn(async move { read_new_lines(&file_path, offset) })
+          .await;
+
+        match chunk {
+          Ok(Some(read)) => {
+            let result = this.update(cx, |editor, cx| {
+              if let Some(tail) = editor.tail.as_mut() {
+                tail.read_offset = read.read_offset();
+              }
+              match read {
+                TailRead::Appended(_, text) => append_output(editor, &text, cx),
+                TailRead::Restarted(_, text) => replace_output(editor, &text, cx),
+              }
+            });
+            if result.is_err() {
+              break;
+            }
+          }
+          Ok(None) => {}
+          Err(err) => eprintln!("Failed to tail {}: {}", path.display(), err),
+        }
+      }
+    }
+  });
+
+  editor.tail = Some(TailMode {
+    path,
+    read_offset,
+    patterns,
+    decorations: Vec::new(),
+    _poll_task: poll_task,
+  });
+  highlight_lines(editor, 0, cx);
+}
+
+pub(crate) fn stop(editor: &mut Editor, cx: &mut Context<Editor>) {
+  if let Some(tail) = editor.tail.take() {
+    for id in tail.decorations {
+      editor.decorations.remove(id);
+    }
+    cx.notify();
+  }
+}
+
+/// Read complete lines appended after `offset`, none if there are none
+///
+/// A file shorter than `offset` was truncated or rotated, it is read again
+/// from the start.
+fn read_new_lines(path: &Path, offset: u64) -> Result<Option<TailRead>, String> {
+  let mut file = File::open(path).map_err(|err| err.to_string())?;
+  let len = file.metadata().map_err(|err| err.to_string())?.len();
+  let restarted = len < offset;
+  let offset = if restarted { 0 } else { offset };
+  if len == offset && !restarted {
+    return Ok(None);
+  }
+
+  file
+    .seek(SeekFrom::Start(offset))
+    .map_err(|err| err.to_string())?;
+  let mut bytes = Vec::new();
+  file
+    .read_to_end(&mut bytes)
+    .map_err(|err| err.to_string())?;
+
+  let complete = complete_lines_len(&bytes);
+  let text = String::from_utf8_lossy(&bytes[..complete]).into_owned();
+  let read_offset = offset + complete as u64;
+  Ok(if restarted {
+    Some(TailRead::Restarted(read_offset, text))
+  } else if complete == 0 {
+    None
+  } else {
+    Some(TailRead::Appended(read_offset, text))
+  })
+}
+
+/// Length of the prefix made of complete lines, a partial last line waits for more data
+fn complete_lines_len(bytes: &[u8]) -> usize {
+  bytes
+    .iter()
+    .rposition(|&byte| byte == b'\n')
+    .map_or(0, |idx| idx + 1)
+}
+
+/// Append new log output, keeping the view at the bottom if it already was
+pub(crate) fn append_output(editor: &mut Editor, text: &str, cx: &mut Context<Editor>) {
+  let was_at_bottom = editor.is_scrolled_to_bottom(cx);
+  let start = editor.document.read(cx).len();
+  let first_new_line = editor.document.read(cx).char_to_line(start);
+
+  editor.document.update(cx, |doc, cx| doc.append(text, cx));
+  editor.invalidate_lines_from(first_new_line);
+  highlight_lines(editor, first_new_line, cx);
+
+  if was_at_bottom {
+    editor.scroll_to_bottom(cx);
+  }
+  cx.notify();
+}
+
+/// Show `text` in place of the output, after the file was truncated or rotated
+pub(crate) fn replace_output(editor: &mut Editor, text: &str, cx: &mut Context<Editor>) {
+  let was_at_bottom = editor.is_scrolled_to_bottom(cx);
+  if let Some(tail) = editor.tail.as_mut() {
+    for id in tail.decorations.drain(..) {
+      editor.decorations.remove(id);
+    }
+  }
+
+  editor.document.update(cx, |doc, cx| doc.reset(text, cx));
+  let len = editor.document.read(cx).len();
+  editor.selected_range = editor.selected_range.start.min(len)..editor.selected_range.end.min(len);
+  editor.invalidate_lines_from(0);
+  highlight_lines(editor, 0, cx);
+
+  if was_at_bottom {
+    editor.scroll_to_bottom(cx);
+  }
+  cx.notify();
+}
+
+/// Decorate lines from `first_line` on that match a tail pattern
+fn highlight_lines(editor: &mut Editor, first_line: usize, cx: &mut Context<Editor>) {
+  let Some(tail) = editor.tail.as_mut() else {
+    return;
+  };
+
+  let document = editor.document.read(cx);
+  for line_idx in first_line..document.len_lines() {
+    let Some(line) = document.line_content(line_idx) else {
+      continue;
+    };
+    let Some(pattern) = tail
+      .patterns
+      .iter()
+      .find(|pattern| line.contains(pattern.needle.as_str()))
+    else {
+      continue;
+    };
+    let Some(range) = document.line_range(line_idx) else {
+      continue;
+    };
+
+    let id = editor.decorations.insert(
+      document.anchor_range(range),
+      DecorationStyle {
+        background: Some(pattern.color),
+        ..Default::default()
+      },
+    );
+    tail.decorations.push(id);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+  use std::io::Write;
+
+  #[test]
+  fn test_complete_lines_len() {
+    assert_eq!(complete_lines_len(b"one\ntwo\npart"), 8);
+    assert_eq!(complete_lines_len(b"partial"), 0);
+    assert_eq!(complete_lines_len(b""), 0);
+  }
+
+  #[test]
+  fn test_read_new_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tail.log");
+    let mut file = File::create(&path).unwrap();
+    file.write_all(b"first\nsecond\npar").unwrap();
+
+    assert_eq!(
+      read_new_lines(&path, 6).unwrap(),
+      Some(TailRead::Appended(13, "second\n".to_string()))
+    );
+    assert_eq!(read_new_lines(&path, 13).unwrap(), None);
+
+    // Truncated files are read again from the start
+    assert_eq!(
+      read_new_lines(&path, 100).unwrap(),
+      Some(TailRead::Restarted(13, "first\nsecond\n".to_string()))
+    );
+    File::create(&path).unwrap();
+    assert_eq!(
+      read_new_lines(&path, 13).unwrap(),
+      Some(TailRead::Restarted(0, String::new()))
+    );
+  }
+
+  #[gpui::test]
+  fn test_append_output_highlights_matches(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "boot\n");
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.tail = Some(TailMode {
+        path: PathBuf::from("test.log"),
+        read_offset: 5,
+        patterns: default_tail_patterns(),
+        decorations: Vec::new(),
+        _poll_task: Task::ready(()),
+      });
+
+      append_output(editor, "INFO ready\nERROR failed\nWARN slow\n", cx);
+      assert_eq!(editor.tail.as_ref().unwrap().decorations.len(), 2);
+
+      stop(editor, cx);
+      assert!(editor.decorations.is_empty());
+    });
+
+    assert_eq!(ctx.text(), "boot\nINFO ready\nERROR failed\nWARN slow\n");
+  }
+
+  #[gpui::test]
+  fn test_replace_output_after_rotation(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "ERROR old\nboot\n");
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.tail = Some(TailMode {
+        path: PathBuf::from("test.log"),
+        read_offset: 15,
+        patterns: default_tail_patterns(),
+        decorations: Vec::new(),
+        _poll_task: Task::ready(()),
+      });
+      highlight_lines(editor, 0, cx);
+      let old = editor.tail.as_ref().unwrap().decorations.clone();
+      assert_eq!(old.len(), 1);
+
+      // The old output and its decorations are gone
+      replace_output(editor, "WARN new\n", cx);
+      assert_eq!(editor.tail.as_ref().unwrap().decorations.len(), 1);
+      assert!(!editor.decorations.remove(old[0]));
+    });
+
+    assert_eq!(ctx.text(), "WARN new\n");
+  }
+}
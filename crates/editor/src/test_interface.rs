@@ -0,0 +1,118 @@
+//! Driving an editor from end-to-end tests of downstream projects
+//!
+//! Enabled by the `test-interface` feature. A [`TestInterface`] opens an
+//! editor in a test window, injects key events through the app's keymap and
+//! input handling, and reports what the last frame painted: the cursor and
+//! selection rectangles in window coordinates and the lines drawn.
+
+use gpui::{AppContext, Bounds, Entity, Pixels, TestAppContext, VisualTestContext, WindowHandle};
+
+use crate::{document::Document, editor::Editor};
+
+/// What the editor element painted in its last frame
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PaintedFrame {
+  /// Cursor rectangle, also while blinking hides it; none when scrolled out
+  pub cursor: Option<Bounds<Pixels>>,
+  /// One rectangle per selected row
+  pub selections: Vec<Bounds<Pixels>>,
+  /// Indexes of the lines drawn, top to bottom
+  pub lines: Vec<usize>,
+}
+
+pub struct TestInterface {
+  pub cx: VisualTestContext,
+  pub editor: Entity<Editor>,
+}
+
+impl TestInterface {
+  /// Open a focused editor showing `text` in a new test window
+  ///
+  /// Keystrokes are dispatched through the bindings of `cx`, so bind the
+  /// editor actions a test uses first, as the app does at startup.
+  pub fn new(cx: &mut TestAppContext, text: &str) -> Self {
+    let window: WindowHandle<Editor> = cx.add_window(|window, cx| {
+      let document = cx.new(|cx| Document::new(text, None, cx));
+      let editor = Editor::with_document(document, cx);
+      window.focus(&editor.focus_handle, cx);
+      editor
+    });
+    let editor = window
+      .update(cx, |_, _, cx| cx.entity())
+      .expect("test window was just opened");
+    let mut cx = VisualTestContext::from_window(window.into(), cx);
+    cx.run_until_parked();
+    Self { cx, editor }
+  }
+
+  /// Type keystrokes separated by spaces, e.g. `"cmd-a backspace"`
+  pub fn simulate_keystrokes(&mut self, keystrokes: &str) {
+    self.cx.simulate_keystrokes(keystrokes);
+    self.cx.run_until_parked();
+  }
+
+  /// Enter `text` through the input handler, as the platform does for typing
+  pub fn simulate_input(&mut self, text: &str) {
+    self.cx.simulate_input(text);
+    self.cx.run_until_parked();
+  }
+
+  pub fn text(&mut self) -> String {
+    self.editor.read_with(&self.cx, |editor, cx| {
+      let doc = editor.document.read(cx);
+      doc.slice_to_string(0..doc.len())
+    })
+  }
+
+  /// The last frame, after drawing any pending changes
+  pub fn painted(&mut self) -> PaintedFrame {
+    self.cx.run_until_parked();
+    self
+      .editor
+      .read_with(&self.cx, |editor, _| editor.painted.clone())
+      .unwrap_or_default()
+  }
+
+  pub fn cursor_bounds(&mut self) -> Option<Bounds<Pixels>> {
+    self.painted().cursor
+  }
+
+  pub fn selection_bounds(&mut self) -> Vec<Bounds<Pixels>> {
+    self.painted().selections
+  }
+
+  pub fn painted_lines(&mut self) -> Vec<usize> {
+    self.painted().lines
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::actions::{Left, SelectAll};
+  use gpui::KeyBinding;
+
+  #[gpui::test]
+  fn test_drive_editor(cx: &mut TestAppContext) {
+    cx.update(|cx| {
+      cx.bind_keys([
+        KeyBinding::new("left", Left, None),
+        KeyBinding::new("cmd-a", SelectAll, None),
+      ])
+    });
+    let mut editor = TestInterface::new(cx, "one\ntwo\nthree");
+    assert_eq!(editor.painted_lines(), [0, 1, 2]);
+
+    editor.simulate_input("zero ");
+    assert_eq!(editor.text(), "zero one\ntwo\nthree");
+    let after_input = editor.cursor_bounds().unwrap();
+    editor.simulate_keystrokes("left left");
+    let moved = editor.cursor_bounds().unwrap();
+    assert!(moved.origin.x < after_input.origin.x);
+    assert_eq!(moved.origin.y, after_input.origin.y);
+    assert!(editor.selection_bounds().is_empty());
+
+    editor.simulate_keystrokes("cmd-a");
+    assert_eq!(editor.selection_bounds().len(), 3);
+  }
+}
@@ -0,0 +1,448 @@
+//! Gutter run buttons for Rust `#[test]` functions
+//!
+//! Test functions are found with tree-sitter after edits settle, decorated with
+//! a run icon, and clicking the icon runs `cargo test -- --exact <path>` in the
+//! background, from the workspace, for the package the file belongs to.
+
+use std::{
+  collections::HashMap,
+  ops::Range,
+  path::{Path, PathBuf},
+  process::Command,
+  time::Duration,
+};
+
+use buffer::Anchor;
+use gpui::{App, Context, Hsla, SharedString, Task, green, red, yellow};
+use syntax::runnables::{RustTest, find_rust_tests};
+
+use crate::{
+  decorations::{DecorationId, DecorationStyle, GutterIcon},
+  editor::Editor,
+  trust,
+};
+
+/// Delay before re-scanning the document for tests after an edit
+const REFRESH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TestStatus {
+  Idle,
+  Running,
+  Passed,
+  Failed,
+  /// The run matched no test, e.g. the file isn't part of a package target
+  NotRun,
+}
+
+struct TestRunnable {
+  name: String,
+  range: Range<Anchor>,
+  decoration: DecorationId,
+  status: TestStatus,
+}
+
+#[derive(Default)]
+pub struct TestRunner {
+  runnables: Vec<TestRunnable>,
+  // Document version the runnables were computed for
+  scanned_version: Option<usize>,
+  refresh_task: Option<Task<()>>,
+  // Runs in progress by test name, until they finish or the test goes away
+  running: HashMap<String, Task<()>>,
+}
+
+impl TestRunner {
+  pub fn status(&self, name: &str) -> Option<TestStatus> {
+    self
+      .runnables
+      .iter()
+      .find(|runnable| runnable.name == name)
+      .map(|runnable| runnable.status)
+  }
+}
+
+/// Re-scan the document for tests once edits have settled
+pub(crate) fn schedule_refresh(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let document = editor.document.read(cx);
+  let version = document.version();
+  if editor.test_runner.scanned_version == Some(version) {
+    return;
+  }
+
+  if document.language_name() != Some("rust") {
+    editor.test_runner.refresh_task = None;
+    if !editor.test_runner.runnables.is_empty() {
+      apply_tests(editor, Vec::new(), version, cx);
+    }
+    return;
+  }
+
+  let snapshot = document.snapshot();
+  editor.test_runner.refresh_task = Some(cx.spawn(async move |this, cx| {
+    cx.background_executor().timer(REFRESH_DEBOUNCE).await;
+
+    let tests = cx
+      .background_executor()
+      .spawn(async move { find_rust_tests(&snapshot.text()) })
+      .await;
+
+    let _ = this.update(cx, |editor, cx| {
+      // Byte offsets are only valid for the text they were computed from
+      if editor.document.read(cx).version() == version {
+        apply_tests(editor, tests, version, cx);
+      }
+    });
+  }));
+}
+
+fn apply_tests(
+  editor: &mut Editor,
+  tests: Vec<RustTest>,
+  version: usize,
+  cx: &mut Context<Editor>,
+) {
+  let previous = std::mem::take(&mut editor.test_runner.runnables);
+  for runnable in &previous {
+    editor.decorations.remove(runnable.decoration);
+  }
+
+  let mut runnables = Vec::with_capacity(tests.len());
+  for test in tests {
+    let (range, idle_color) = {
+      let document = editor.document.read(cx);
+      let start = document.byte_to_char(test.byte_range.start);
+      let end = document.byte_to_char(test.byte_range.end);
+      (
+        document.anchor_range(start..end),
+        editor.theme.line_number(),
+      )
+    };
+
+    // Keep the last result of a test across re-scans
+    let status = previous
+      .iter()
+      .find(|runnable| runnable.name == test.name)
+      .map_or(TestStatus::Idle, |runnable| runnable.status);
+
+    let decoration = editor
+      .decorations
+      .insert(range.clone(), style_for_status(status, idle_color));
+    runnables.push(TestRunnable {
+      name: test.name,
+      range,
+      decoration,
+      status,
+    });
+  }
+
+  // A test that is gone has no decoration left to show how its run went
+  editor
+    .test_runner
+    .running
+    .retain(|name, _| runnables.iter().any(|runnable| runnable.name == *name));
+  editor.test_runner.runnables = runnables;
+  editor.test_runner.scanned_version = Some(version);
+  cx.notify();
+}
+
+/// Name of the test whose first line is `line`
+pub(crate) fn test_at_line(editor: &Editor, line: usize, cx: &App) -> Option<String> {
+  let document = editor.document.read(cx);
+  editor
+    .test_runner
+    .runnables
+    .iter()
+    .find(|runnable| {
+      let start = document.resolve_anchor(&runnable.range.start);
+      document.char_to_line(start) == line
+    })
+    .map(|runnable| runnable.name.clone())
+}
+
+/// Run the test whose first line is `line`; returns false if there is none
+///
+/// Nothing runs while the workspace is restricted.
+pub(crate) fn run_test_at_line(editor: &mut Editor, line: usize, cx: &mut Context<Editor>) -> bool {
+  if trust::is_restricted(editor, cx) {
+    return false;
+  }
+  let Some(name) = test_at_line(editor, line, cx) else {
+    return false;
+  };
+
+  set_status(editor, &name, TestStatus::Running, cx);
+
+  let invocation = TestInvocation::new(editor.file_path(), &name);
+  let workspace = trust::workspace(editor, cx);
+  let task = cx.spawn({
+    let name = name.clone();
+    async move |this, cx| {
+      let status = cx
+        .background_executor()
+        .spawn(async move { invocation.run(workspace) })
+        .await;
+
+      let _ = this.update(cx, |editor, cx| {
+        editor.test_runner.running.remove(&name);
+        set_status(editor, &name, status, cx);
+      });
+    }
+  });
+  editor.test_runner.running.insert(name, task);
+  true
+}
+
+/// How `cargo test` is invoked to run exactly one test
+#[derive(Debug, PartialEq, Eq)]
+struct TestInvocation {
+  /// Manifest of the package the file belongs to
+  manifest: Option<PathBuf>,
+  /// Integration test target, for files under the package's `tests` directory
+  test_target: Option<String>,
+  /// Full path of the test within its target, e.g. `parser::tests::parses_input`
+  filter: String,
+}
+
+impl TestInvocation {
+  /// `name` is the path of the test within `file`, as found by [`find_rust_tests`]
+  fn new(file: Option<&Path>, name: &str) -> Self {
+    let mut invocation = Self {
+      manifest: None,
+      test_target: None,
+      filter: name.to_string(),
+    };
+    let Some(file) = file else {
+      return invocation;
+    };
+    let Some(package) = file
+      .ancestors()
+      .skip(1)
+      .find(|dir| dir.join("Cargo.toml").is_file())
+    else {
+      return invocation;
+    };
+    invocation.manifest = Some(package.join("Cargo.toml"));
+
+    let Ok(relative) = file
+      .with_extension("")
+      .strip_prefix(package)
+      .map(Path::to_path_buf)
+    else {
+      return invocation;
+    };
+    let mut components = relative
+      .iter()
+      .map(|component| component.to_string_lossy().into_owned())
+      .collect::<Vec<_>>();
+    match components.first().map(String::as_str) {
+      Some("src") => {
+        components.remove(0);
+        // Crate roots and `mod.rs` files don't add a module of their own
+        if matches!(
+          components.last().map(String::as_str),
+          Some("lib" | "main" | "mod")
+        ) {
+          components.pop();
+        }
+      }
+      Some("tests") if components.len() == 2 => {
+        invocation.test_target = components.pop();
+        components.clear();
+      }
+      _ => return invocation,
+    }
+    components.push(name.to_string());
+    invocation.filter = components.join("::");
+    invocation
+  }
+
+  fn run(&self, workspace: Option<PathBuf>) -> TestStatus {
+    let mut command = Command::new("cargo");
+    command.arg("test");
+    if let Some(manifest) = &self.manifest {
+      command.arg("--manifest-path").arg(manifest);
+    }
+    if let Some(target) = &self.test_target {
+      command.arg("--test").arg(target);
+    }
+    command.args(["--", "--exact", &self.filter]);
+    if let Some(workspace) = workspace {
+      command.current_dir(workspace);
+    }
+
+    match command.output() {
+      Ok(output) if !output.status.success() => TestStatus::Failed,
+      Ok(output) if passed_count(&String::from_utf8_lossy(&output.stdout)) > 0 => {
+        TestStatus::Passed
+      }
+      Ok(_) => TestStatus::NotRun,
+      Err(_) => TestStatus::Failed,
+    }
+  }
+}
+
+/// Number of passed tests over the `test result:` lines of cargo's output
+fn passed_count(stdout: &str) -> usize {
+  stdout
+    .lines()
+    .filter_map(|line| line.trim().strip_prefix("test result:"))
+    .filter_map(|result| {
+      let passed = result
+        .split(';')
+        .find(|part| part.trim_end().ends_with("passed"))?;
+      passed
+        .split_whitespace()
+        .rev()
+        .nth(1)?
+        .parse::<usize>()
+        .ok()
+    })
+    .sum()
+}
+
+fn set_status(editor: &mut Editor, name: &str, status: TestStatus, cx: &mut Context<Editor>) {
+  let idle_color = editor.theme.line_number();
+  let Some(runnable) = editor
+    .test_runner
+    .runnables
+    .iter_mut()
+    .find(|runnable| runnable.name == name)
+  else {
+    return;
+  };
+
+  editor.decorations.remove(runnable.decoration);
+  runnable.status = status;
+  runnable.decoration = editor
+    .decorations
+    .insert(runnable.range.clone(), style_for_status(status, idle_color));
+  cx.notify();
+}
+
+fn style_for_status(status: TestStatus, idle_color: Hsla) -> DecorationStyle {
+  let (glyph, color) = match status {
+    TestStatus::Idle => ("▶", idle_color),
+    TestStatus::Running => ("●", yellow()),
+    TestStatus::Passed => ("✔", green()),
+    TestStatus::Failed => ("✘", red()),
+    TestStatus::NotRun => ("?", yellow()),
+  };
+
+  DecorationStyle {
+    gutter_icon: Some(GutterIcon {
+      glyph: SharedString::new_static(glyph),
+      color,
+    }),
+    ..Default::default()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[gpui::test]
+  fn test_apply_tests_decorates_runnables(cx: &mut TestAppContext) {
+    let mut ctx =
+      EditorTestContext::with_text(cx.clone(), "#[test]\nfn one() {}\n\n#[test]\nfn two() {}\n");
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      let text = {
+        let document = editor.document.read(cx);
+        document.slice_to_string(0..document.len())
+      };
+      let version = editor.document.read(cx).version();
+      apply_tests(editor, find_rust_tests(&text), version, cx);
+
+      assert_eq!(editor.test_runner.runnables.len(), 2);
+      assert_eq!(editor.test_runner.status("two"), Some(TestStatus::Idle));
+      assert!(!editor.decorations.is_empty());
+    });
+  }
+
+  #[gpui::test]
+  fn test_status_survives_rescan(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "#[test]\nfn one() {}\n");
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      let text = {
+        let document = editor.document.read(cx);
+        document.slice_to_string(0..document.len())
+      };
+      let version = editor.document.read(cx).version();
+      apply_tests(editor, find_rust_tests(&text), version, cx);
+      set_status(editor, "one", TestStatus::Failed, cx);
+
+      apply_tests(editor, find_rust_tests(&text), version, cx);
+      assert_eq!(editor.test_runner.status("one"), Some(TestStatus::Failed));
+    });
+  }
+
+  #[gpui::test]
+  fn test_rescan_drops_runs_of_removed_tests(cx: &mut TestAppContext) {
+    let mut ctx =
+      EditorTestContext::with_text(cx.clone(), "#[test]\nfn one() {}\n\n#[test]\nfn two() {}\n");
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      let text = {
+        let document = editor.document.read(cx);
+        document.slice_to_string(0..document.len())
+      };
+      let version = editor.document.read(cx).version();
+      apply_tests(editor, find_rust_tests(&text), version, cx);
+      for name in ["one", "two"] {
+        editor
+          .test_runner
+          .running
+          .insert(name.to_string(), Task::ready(()));
+      }
+
+      let one = &text[.."#[test]\nfn one() {}\n".len()];
+      apply_tests(editor, find_rust_tests(one), version, cx);
+      assert!(editor.test_runner.running.contains_key("one"));
+      assert!(!editor.test_runner.running.contains_key("two"));
+    });
+  }
+
+  #[test]
+  fn test_invocation_paths() {
+    let dir = tempfile::tempdir().unwrap();
+    let package = dir.path().join("crates/parser");
+    std::fs::create_dir_all(package.join("src/lexer")).unwrap();
+    std::fs::create_dir_all(package.join("tests")).unwrap();
+    std::fs::write(package.join("Cargo.toml"), "[package]\n").unwrap();
+
+    let invocation = |file: &str| TestInvocation::new(Some(&package.join(file)), "tests::one");
+    assert_eq!(invocation("src/lib.rs").filter, "tests::one");
+    assert_eq!(invocation("src/lexer/mod.rs").filter, "lexer::tests::one");
+    assert_eq!(
+      invocation("src/lexer/token.rs").filter,
+      "lexer::token::tests::one"
+    );
+    assert_eq!(
+      invocation("src/lib.rs").manifest,
+      Some(package.join("Cargo.toml"))
+    );
+
+    let integration = invocation("tests/smoke.rs");
+    assert_eq!(integration.test_target.as_deref(), Some("smoke"));
+    assert_eq!(integration.filter, "tests::one");
+
+    let unsaved = TestInvocation::new(None, "tests::one");
+    assert_eq!(unsaved.manifest, None);
+    assert_eq!(unsaved.filter, "tests::one");
+  }
+
+  #[test]
+  fn test_passed_count() {
+    let output = "running 1 test\ntest tests::one ... ok\n\ntest result: ok. 1 passed; 0 failed; 0 ignored; 0 measured; 12 filtered out; finished in 0.00s\n\nrunning 0 tests\n\ntest result: ok. 0 passed; 0 failed; 0 ignored; 0 measured; 3 filtered out; finished in 0.00s\n";
+    assert_eq!(passed_count(output), 1);
+    assert_eq!(
+      passed_count("test result: ok. 0 passed; 0 failed; 0 ignored; 0 measured; 9 filtered out\n"),
+      0
+    );
+  }
+}
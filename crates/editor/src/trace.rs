@@ -0,0 +1,184 @@
+//! Performance traces in the Chrome trace event format
+//!
+//! While a trace is recording, frame timings, line cache statistics and input
+//! events are collected. Stopping writes them to a JSON file that can be
+//! opened in `chrome://tracing` or Perfetto and attached to bug reports.
+
+use std::{
+  fmt::Write as _,
+  path::PathBuf,
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use gpui::Context;
+
+use crate::editor::Editor;
+
+enum TraceEvent {
+  /// A span of work, such as laying out a frame
+  Complete {
+    name: &'static str,
+    start: Duration,
+    duration: Duration,
+  },
+  /// Something that happened at one point in time, such as a key press
+  Instant { name: &'static str, at: Duration },
+  /// Values sampled over time, such as line cache statistics
+  Counter {
+    name: &'static str,
+    at: Duration,
+    values: Vec<(&'static str, usize)>,
+  },
+}
+
+pub(crate) struct PerformanceTrace {
+  started_at: Instant,
+  events: Vec<TraceEvent>,
+}
+
+impl PerformanceTrace {
+  pub fn new(now: Instant) -> Self {
+    Self {
+      started_at: now,
+      events: Vec::new(),
+    }
+  }
+
+  pub fn record_span(&mut self, name: &'static str, start: Instant, end: Instant) {
+    self.events.push(TraceEvent::Complete {
+      name,
+      start: start.saturating_duration_since(self.started_at),
+      duration: end.saturating_duration_since(start),
+    });
+  }
+
+  pub fn record_event(&mut self, name: &'static str, now: Instant) {
+    self.events.push(TraceEvent::Instant {
+      name,
+      at: now.saturating_duration_since(self.started_at),
+    });
+  }
+
+  pub fn record_counter(
+    &mut self,
+    name: &'static str,
+    now: Instant,
+    values: Vec<(&'static str, usize)>,
+  ) {
+    self.events.push(TraceEvent::Counter {
+      name,
+      at: now.saturating_duration_since(self.started_at),
+      values,
+    });
+  }
+
+  /// Serialize the events as a Chrome trace JSON object, timestamps in microseconds
+  pub fn to_json(&self) -> String {
+    let mut events = Vec::with_capacity(self.events.len());
+    for event in &self.events {
+      let mut json = String::new();
+      match event {
+        TraceEvent::Complete {
+          name,
+          start,
+          duration,
+        } => {
+          let _ = write!(
+            json,
+            r#"{{"name":"{}","cat":"editor","ph":"X","ts":{},"dur":{},"pid":1,"tid":1}}"#,
+            name,
+            start.as_micros(),
+            duration.as_micros()
+          );
+        }
+        TraceEvent::Instant { name, at } => {
+          let _ = write!(
+            json,
+            r#"{{"name":"{}","cat":"input","ph":"i","s":"t","ts":{},"pid":1,"tid":1}}"#,
+            name,
+            at.as_micros()
+          );
+        }
+        TraceEvent::Counter { name, at, values } => {
+          let args: Vec<String> = values
+            .iter()
+            .map(|(key, value)| format!(r#""{}":{}"#, key, value))
+            .collect();
+          let _ = write!(
+            json,
+            r#"{{"name":"{}","cat":"cache","ph":"C","ts":{},"pid":1,"tid":1,"args":{{{}}}}}"#,
+            name,
+            at.as_micros(),
+            args.join(",")
+          );
+        }
+      }
+      events.push(json);
+    }
+    format!(
+      "{{\"traceEvents\":[\n{}\n],\"displayTimeUnit\":\"ms\"}}\n",
+      events.join(",\n")
+    )
+  }
+}
+
+pub(crate) fn start(editor: &mut Editor, cx: &mut Context<Editor>) {
+  editor.trace = Some(PerformanceTrace::new(Instant::now()));
+  cx.notify();
+}
+
+/// Stop recording and write the trace to the temp directory, returning its path
+pub(crate) fn stop(
+  editor: &mut Editor,
+  cx: &mut Context<Editor>,
+) -> Result<Option<PathBuf>, String> {
+  let Some(trace) = editor.trace.take() else {
+    return Ok(None);
+  };
+  cx.notify();
+
+  let timestamp = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|elapsed| elapsed.as_secs())
+    .unwrap_or_default();
+  let path = std::env::temp_dir().join(format!("gpui-editor-trace-{}.json", timestamp));
+  std::fs::write(&path, trace.to_json()).map_err(|err| err.to_string())?;
+  Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_to_json() {
+    let start = Instant::now();
+    let mut trace = PerformanceTrace::new(start);
+    trace.record_event("key_down", start + Duration::from_micros(10));
+    trace.record_span(
+      "prepaint",
+      start + Duration::from_micros(20),
+      start + Duration::from_micros(270),
+    );
+    trace.record_counter(
+      "line_cache",
+      start + Duration::from_micros(270),
+      vec![("hits", 40), ("misses", 2)],
+    );
+
+    let json = trace.to_json();
+    assert!(json.starts_with("{\"traceEvents\":["));
+    assert!(json.contains(r#""name":"key_down","cat":"input","ph":"i","s":"t","ts":10"#));
+    assert!(json.contains(r#""name":"prepaint","cat":"editor","ph":"X","ts":20,"dur":250"#));
+    assert!(json.contains(r#""args":{"hits":40,"misses":2}"#));
+  }
+
+  #[test]
+  fn test_empty_trace() {
+    let trace = PerformanceTrace::new(Instant::now());
+    assert_eq!(
+      trace.to_json(),
+      "{\"traceEvents\":[\n\n],\"displayTimeUnit\":\"ms\"}\n"
+    );
+  }
+}
@@ -0,0 +1,271 @@
+//! Encoding and decoding the selection in place
+//!
+//! Each transform replaces the selection, or the run of non-whitespace at the
+//! cursor, with its encoded or decoded form and selects the result. Input a
+//! decoder can't make sense of, or that doesn't decode to UTF-8 text, is
+//! left as it is.
+
+use std::time::Instant;
+
+use gpui::{Context, Window};
+
+use crate::editor::Editor;
+
+const BASE64_ALPHABET: &[u8; 64] =
+  b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Transform {
+  Base64Encode,
+  Base64Decode,
+  UrlEncode,
+  UrlDecode,
+  JsonEscape,
+  JsonUnescape,
+}
+
+impl Transform {
+  /// Undo history label
+  fn label(self) -> &'static str {
+    match self {
+      Transform::Base64Encode => "Base64 Encode",
+      Transform::Base64Decode => "Base64 Decode",
+      Transform::UrlEncode => "URL Encode",
+      Transform::UrlDecode => "URL Decode",
+      Transform::JsonEscape => "JSON Escape",
+      Transform::JsonUnescape => "JSON Unescape",
+    }
+  }
+
+  /// `text` transformed, none when it isn't valid input
+  pub(crate) fn apply(self, text: &str) -> Option<String> {
+    match self {
+      Transform::Base64Encode => Some(base64_encode(text.as_bytes())),
+      Transform::Base64Decode => String::from_utf8(base64_decode(text)?).ok(),
+      Transform::UrlEncode => Some(url_encode(text)),
+      Transform::UrlDecode => url_decode(text),
+      Transform::JsonEscape => {
+        let quoted = serde_json::to_string(text).ok()?;
+        Some(quoted[1..quoted.len() - 1].to_string())
+      }
+      Transform::JsonUnescape => serde_json::from_str(&format!("\"{}\"", text)).ok(),
+    }
+  }
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+  let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+  for chunk in bytes.chunks(3) {
+    let group = chunk.iter().enumerate().fold(0u32, |group, (ix, byte)| {
+      group | ((*byte as u32) << (16 - 8 * ix))
+    });
+    for ix in 0..4 {
+      if ix <= chunk.len() {
+        out.push(BASE64_ALPHABET[((group >> (18 - 6 * ix)) & 0x3f) as usize] as char);
+      } else {
+        out.push('=');
+      }
+    }
+  }
+  out
+}
+
+/// Standard or URL-safe Base64, with or without padding, ignoring whitespace
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+  let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+  let mut group = 0u32;
+  let mut bits = 0;
+  for ch in text
+    .trim_end_matches(|ch: char| ch == '=' || ch.is_whitespace())
+    .chars()
+  {
+    let value = match ch {
+      'A'..='Z' => ch as u32 - 'A' as u32,
+      'a'..='z' => ch as u32 - 'a' as u32 + 26,
+      '0'..='9' => ch as u32 - '0' as u32 + 52,
+      '+' | '-' => 62,
+      '/' | '_' => 63,
+      ch if ch.is_whitespace() => continue,
+      _ => return None,
+    };
+    group = (group << 6) | value;
+    bits += 6;
+    if bits >= 8 {
+      bits -= 8;
+      bytes.push((group >> bits) as u8);
+    }
+  }
+  // A lone trailing character can't hold a whole byte
+  (bits < 6).then_some(bytes)
+}
+
+/// Percent-encode everything but the unreserved characters of RFC 3986
+fn url_encode(text: &str) -> String {
+  let mut out = String::with_capacity(text.len());
+  for byte in text.bytes() {
+    if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+      out.push(byte as char);
+    } else {
+      out.push_str(&format!("%{:02X}", byte));
+    }
+  }
+  out
+}
+
+/// Decode percent escapes, keeping a `%` that doesn't start one as it is
+fn url_decode(text: &str) -> Option<String> {
+  let bytes = text.as_bytes();
+  let mut out = Vec::with_capacity(bytes.len());
+  let mut ix = 0;
+  while ix < bytes.len() {
+    let escaped = (bytes[ix] == b'%')
+      .then(|| text.get(ix + 1..ix + 3))
+      .flatten()
+      .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+    match escaped {
+      Some(byte) => {
+        out.push(byte);
+        ix += 3;
+      }
+      None => {
+        out.push(bytes[ix]);
+        ix += 1;
+      }
+    }
+  }
+  String::from_utf8(out).ok()
+}
+
+/// Transform the selection, or the non-whitespace around the cursor, as one
+/// undoable edit
+pub(crate) fn transform_selection(
+  editor: &mut Editor,
+  transform: Transform,
+  window: &mut Window,
+  cx: &mut Context<Editor>,
+) {
+  let selection_before = editor.selected_range.clone();
+  let document = editor.document.read(cx);
+  let range = if selection_before.is_empty() {
+    let line = document.char_to_line(selection_before.start);
+    let line_start = document.line_to_char(line);
+    let content = document.line_content(line).unwrap_or_default();
+    let chars: Vec<char> = content.chars().collect();
+    let column = selection_before.start - line_start;
+    let start = chars[..column]
+      .iter()
+      .rposition(|ch| ch.is_whitespace())
+      .map_or(0, |ix| ix + 1);
+    let end = chars[column..]
+      .iter()
+      .position(|ch| ch.is_whitespace())
+      .map_or(chars.len(), |ix| column + ix);
+    line_start + start..line_start + end
+  } else {
+    selection_before.clone()
+  };
+  if range.is_empty() {
+    return;
+  }
+  let text = document.slice_to_string(range.clone());
+  let Some(new_text) = transform.apply(&text).filter(|new_text| *new_text != text) else {
+    return;
+  };
+
+  let transaction_id = editor.document.update(cx, |doc, cx| {
+    let id = doc.buffer.transaction(Instant::now(), |buffer, tx| {
+      buffer.replace(tx, range.clone(), &new_text);
+    });
+    doc.schedule_recompute_highlights(cx);
+    cx.notify();
+    id
+  });
+
+  editor.selected_range = range.start..range.start + new_text.chars().count();
+  editor.selection_reversed = false;
+  editor.target_column = None;
+  let selection_after = editor.selected_range.clone();
+  editor.record_transaction(
+    transaction_id,
+    selection_before,
+    selection_after,
+    transform.label(),
+    cx,
+  );
+
+  let line = editor.document.read(cx).char_to_line(range.start);
+  editor.invalidate_lines_from(line);
+  editor.ensure_cursor_visible(window, cx);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::document::Document;
+  use gpui::{AppContext, TestAppContext};
+
+  #[test]
+  fn test_base64() {
+    for (text, encoded) in [
+      ("", ""),
+      ("f", "Zg=="),
+      ("fo", "Zm8="),
+      ("foo", "Zm9v"),
+      ("é!", "w6kh"),
+    ] {
+      assert_eq!(Transform::Base64Encode.apply(text).unwrap(), encoded);
+      assert_eq!(Transform::Base64Decode.apply(encoded).unwrap(), text);
+    }
+    // URL-safe, unpadded and wrapped input decodes too
+    assert_eq!(Transform::Base64Decode.apply("Pz8_\nPw").unwrap(), "????");
+    assert_eq!(Transform::Base64Decode.apply("Zm9v!"), None);
+    assert_eq!(Transform::Base64Decode.apply("Z"), None);
+    // Bytes that aren't UTF-8 text
+    assert_eq!(Transform::Base64Decode.apply("/w=="), None);
+  }
+
+  #[test]
+  fn test_url() {
+    let encoded = Transform::UrlEncode.apply("a b&c=é~").unwrap();
+    assert_eq!(encoded, "a%20b%26c%3D%C3%A9~");
+    assert_eq!(Transform::UrlDecode.apply(&encoded).unwrap(), "a b&c=é~");
+    // Stray percent signs are kept
+    assert_eq!(
+      Transform::UrlDecode.apply("100% %zz %4").unwrap(),
+      "100% %zz %4"
+    );
+  }
+
+  #[test]
+  fn test_json() {
+    let escaped = Transform::JsonEscape.apply("say \"hi\"\n\t\\").unwrap();
+    assert_eq!(escaped, r#"say \"hi\"\n\t\\"#);
+    assert_eq!(
+      Transform::JsonUnescape.apply(&escaped).unwrap(),
+      "say \"hi\"\n\t\\"
+    );
+    assert_eq!(Transform::JsonUnescape.apply(r"\q"), None);
+  }
+
+  #[gpui::test]
+  fn test_transform_text_at_cursor(cx: &mut TestAppContext) {
+    let window = cx.add_window(|_, cx| {
+      let document = cx.new(|cx| Document::new("token: Zm9v end", None, cx));
+      Editor::with_document(document, cx)
+    });
+    window
+      .update(cx, |editor, window, cx| {
+        editor.selected_range = 9..9;
+        transform_selection(editor, Transform::Base64Decode, window, cx);
+        assert_eq!(editor.document.read(cx).text(), "token: foo end");
+        assert_eq!(editor.selected_range, 7..10);
+
+        // Invalid input is left alone
+        editor.selected_range = 0..6;
+        transform_selection(editor, Transform::Base64Decode, window, cx);
+        assert_eq!(editor.document.read(cx).text(), "token: foo end");
+        assert_eq!(editor.document.read(cx).buffer.undo_depth(), 1);
+      })
+      .unwrap();
+  }
+}
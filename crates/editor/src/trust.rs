@@ -0,0 +1,197 @@
+//! Workspace trust
+//!
+//! A workspace is restricted until the user trusts it: nothing from it is
+//! executed, so tests can't be run from the gutter. Git isn't run in it
+//! either, as a repository's config can name programs for git to run, like
+//! an fsmonitor hook or a diff filter. Trusting a directory
+//! also trusts everything below it, and is remembered across sessions in a
+//! file listing one trusted path per line.
+
+use std::path::{Path, PathBuf};
+
+use gpui::{App, Context, Global, PromptLevel, Window};
+
+use crate::{editor::Editor, paths, word_diff, workspace};
+
+#[derive(Debug, Default)]
+pub struct WorkspaceTrust {
+  /// File trust decisions are saved to, none to keep them in memory
+  store: Option<PathBuf>,
+  trusted: Vec<PathBuf>,
+}
+
+impl Global for WorkspaceTrust {}
+
+impl WorkspaceTrust {
+  /// Trust decisions saved in `store`, empty if it doesn't exist yet
+  pub fn load(store: PathBuf) -> Self {
+    let trusted = std::fs::read_to_string(&store)
+      .map(|contents| {
+        contents
+          .lines()
+          .filter(|line| !line.trim().is_empty())
+          .map(PathBuf::from)
+          .collect()
+      })
+      .unwrap_or_default();
+    Self {
+      store: Some(store),
+      trusted,
+    }
+  }
+
+  /// `trusted_workspaces` in the editor's config directory
+  pub fn default_store() -> Option<PathBuf> {
+    Some(paths::config_dir()?.join("trusted_workspaces"))
+  }
+
+  pub fn is_trusted(&self, workspace: &Path) -> bool {
+    let workspace = normalize(workspace);
+    self
+      .trusted
+      .iter()
+      .any(|trusted| workspace.starts_with(trusted))
+  }
+
+  /// Trust `workspace` and its subdirectories, saving the decision
+  pub fn trust(&mut self, workspace: &Path) -> Result<(), String> {
+    if self.is_trusted(workspace) {
+      return Ok(());
+    }
+    let workspace = normalize(workspace);
+    // A parent replaces the subdirectories trusted before it
+    self
+      .trusted
+      .retain(|trusted| !trusted.starts_with(&workspace));
+    self.trusted.push(workspace);
+    self.save()
+  }
+
+  fn save(&self) -> Result<(), String> {
+    let Some(store) = &self.store else {
+      return Ok(());
+    };
+    let contents: String = self
+      .trusted
+      .iter()
+      .map(|path| format!("{}\n", path.display()))
+      .collect();
+    store
+      .parent()
+      .map_or(Ok(()), std::fs::create_dir_all)
+      .and_then(|_| std::fs::write(store, contents))
+      .map_err(|err| err.to_string())
+  }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+  path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Workspace root holding the editor's file, else the file's directory, or
+/// the working directory tasks run in
+pub(crate) fn workspace(editor: &Editor, cx: &App) -> Option<PathBuf> {
+  if let Some(root) = workspace::root_for_editor(editor, cx) {
+    return Some(root);
+  }
+  match editor.file_path() {
+    Some(path) => path.parent().map(Path::to_path_buf),
+    None => std::env::current_dir().ok(),
+  }
+}
+
+pub(crate) fn is_restricted(editor: &Editor, cx: &App) -> bool {
+  workspace(editor, cx).is_none_or(|workspace| !is_trusted(&workspace, cx))
+}
+
+/// Whether `path` is in a trusted workspace
+pub(crate) fn is_trusted(path: &Path, cx: &App) -> bool {
+  cx.try_global::<WorkspaceTrust>()
+    .is_some_and(|trust| trust.is_trusted(path))
+}
+
+pub(crate) fn trust_workspace(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let Some(workspace) = workspace(editor, cx) else {
+    return;
+  };
+  if let Err(err) = cx.default_global::<WorkspaceTrust>().trust(&workspace) {
+    eprintln!("Failed to save trust for {}: {}", workspace.display(), err);
+  }
+  // Every editor in the workspace leaves restricted mode
+  word_diff::reload_head(editor, cx);
+  cx.refresh_windows();
+}
+
+/// Ask whether to trust the workspace before running something from it
+pub(crate) fn request_trust(editor: &mut Editor, window: &mut Window, cx: &mut Context<Editor>) {
+  let Some(workspace) = workspace(editor, cx) else {
+    return;
+  };
+  let answer = window.prompt(
+    PromptLevel::Warning,
+    &format!("Trust {}?", workspace.display()),
+    Some("Restricted mode doesn't run tests or other code from this workspace."),
+    &["Trust", "Cancel"],
+    cx,
+  );
+  cx.spawn_in(window, async move |this, cx| {
+    if answer.await == Ok(0) {
+      let _ = this.update(cx, |editor, cx| trust_workspace(editor, cx));
+    }
+  })
+  .detach();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[test]
+  fn test_trust_covers_subdirectories() {
+    let mut trust = WorkspaceTrust::default();
+    assert!(!trust.is_trusted(Path::new("/work/project")));
+
+    trust.trust(Path::new("/work/project/crates")).unwrap();
+    trust.trust(Path::new("/work/project")).unwrap();
+    assert_eq!(trust.trusted, [PathBuf::from("/work/project")]);
+
+    assert!(trust.is_trusted(Path::new("/work/project/crates/editor")));
+    assert!(!trust.is_trusted(Path::new("/work/project-fork")));
+    assert!(!trust.is_trusted(Path::new("/work")));
+  }
+
+  #[test]
+  fn test_trust_persists() {
+    // Canonical already, so paths compare equal whether they exist or not
+    let temp = tempfile::tempdir_in(std::env::temp_dir().canonicalize().unwrap()).unwrap();
+    let dir = temp.path();
+    let store = dir.join("config").join("trusted_workspaces");
+    let workspace = dir.join("project");
+    std::fs::create_dir_all(&workspace).unwrap();
+
+    let mut trust = WorkspaceTrust::load(store.clone());
+    assert!(!trust.is_trusted(&workspace));
+    trust.trust(&workspace).unwrap();
+
+    let reloaded = WorkspaceTrust::load(store);
+    assert!(reloaded.is_trusted(&workspace));
+    assert!(reloaded.is_trusted(&workspace.join("src")));
+    assert!(!reloaded.is_trusted(dir));
+  }
+
+  #[gpui::test]
+  fn test_editor_starts_restricted(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "fn main() {}");
+
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      assert!(is_restricted(editor, cx));
+      assert!(editor.status_items(cx).contains(&"Restricted Mode".into()));
+
+      trust_workspace(editor, cx);
+      assert!(!is_restricted(editor, cx));
+      assert!(!editor.status_items(cx).contains(&"Restricted Mode".into()));
+    });
+  }
+}
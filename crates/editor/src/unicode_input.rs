@@ -0,0 +1,255 @@
+//! Inserting Unicode characters by code point or from a palette of symbols
+//!
+//! The palette takes keyboard focus while it is open. Typing `U+2192` picks a
+//! character by code point, any other text searches the names of common
+//! symbols; Enter inserts the selected one at the cursor as one transaction.
+
+use gpui::{
+  App, Context, FocusHandle, Focusable, KeyDownEvent, WeakEntity, Window, div, prelude::*, px,
+};
+
+use crate::{
+  actions::{Backspace, Down, Enter, Up},
+  describe::CodePoint,
+  editor::Editor,
+  escape,
+};
+
+/// Matches shown at once
+const MAX_VISIBLE_MATCHES: usize = 12;
+
+const ARROWS: &[char] = &['←', '→', '↑', '↓', '↔', '↕', '⇐', '⇒', '⇔', '↩', '↪', '⟶'];
+const MATH: &[char] = &[
+  '±', '×', '÷', '≈', '≠', '≤', '≥', '∞', '√', '∑', '∏', '∫', '∂', '∆', '∇', '∈', '∉', '⊂', '⊆',
+  '∪', '∩', '∀', '∃', '¬', '∧', '∨', '°', 'π', 'λ', 'μ',
+];
+const BOX_DRAWING: &[char] = &[
+  '─', '│', '┌', '┐', '└', '┘', '├', '┤', '┬', '┴', '┼', '═', '║', '╔', '╗', '╚', '╝',
+];
+const TYPOGRAPHY: &[char] = &[
+  '…', '–', '—', '•', '·', '‹', '›', '«', '»', '✓', '✗', '©', '®', '™', '€', '£',
+];
+
+/// Symbols offered by the palette, in the order they are listed
+const SYMBOLS: &[&[char]] = &[ARROWS, MATH, BOX_DRAWING, TYPOGRAPHY];
+
+/// Characters matching `query`, in palette order
+pub(crate) fn matches(query: &str) -> Vec<CodePoint> {
+  let query = query.trim();
+  if let Some(ch) = parse_code_point(query) {
+    return vec![CodePoint::new(ch)];
+  }
+  let needle = query.to_uppercase();
+  SYMBOLS
+    .iter()
+    .flat_map(|group| group.iter())
+    .map(|&ch| CodePoint::new(ch))
+    .filter(|code_point| {
+      needle.is_empty() || code_point.name.contains(&needle) || code_point.ch.to_string() == query
+    })
+    .collect()
+}
+
+/// `U+2192` or `u+2192`, rejecting surrogates and values past U+10FFFF
+fn parse_code_point(query: &str) -> Option<char> {
+  let hex = query
+    .strip_prefix("U+")
+    .or_else(|| query.strip_prefix("u+"))?;
+  if hex.is_empty() || hex.len() > 6 {
+    return None;
+  }
+  u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+}
+
+/// Replace the selection with `text` in a single undoable transaction
+pub(crate) fn insert(editor: &mut Editor, text: &str, cx: &mut Context<Editor>) {
+  editor.insert_text(text, "Insert Character", cx);
+}
+
+pub struct UnicodeInput {
+  editor: WeakEntity<Editor>,
+  focus_handle: FocusHandle,
+  query: String,
+  selected: usize,
+}
+
+/// Open the palette below the cursor and give it focus
+pub(crate) fn open(editor: &mut Editor, window: &mut Window, cx: &mut Context<Editor>) {
+  let editor_handle = cx.weak_entity();
+  let input = cx.new(|cx| UnicodeInput {
+    editor: editor_handle,
+    focus_handle: cx.focus_handle(),
+    query: String::new(),
+    selected: 0,
+  });
+  window.focus(&input.focus_handle(cx), cx);
+  editor.unicode_input = Some(input);
+  cx.notify();
+}
+
+/// Close the palette, returning focus to the editor if it had it
+pub(crate) fn close(editor: &mut Editor, window: &mut Window, cx: &mut Context<Editor>) {
+  let Some(input) = editor.unicode_input.take() else {
+    return;
+  };
+  if input.focus_handle(cx).is_focused(window) {
+    window.focus(&editor.focus_handle, cx);
+  }
+  cx.notify();
+}
+
+impl UnicodeInput {
+  fn set_query(&mut self, query: String, cx: &mut Context<Self>) {
+    self.query = query;
+    self.selected = 0;
+    cx.notify();
+  }
+
+  fn select(&mut self, delta: isize, cx: &mut Context<Self>) {
+    let count = matches(&self.query).len().min(MAX_VISIBLE_MATCHES);
+    if count > 0 {
+      self.selected = (self.selected as isize + delta).rem_euclid(count as isize) as usize;
+      cx.notify();
+    }
+  }
+
+  fn confirm(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+    let text = matches(&self.query)
+      .get(self.selected)
+      .map(|code_point| code_point.ch.to_string());
+    let _ = self.editor.update(cx, |editor, cx| {
+      if let Some(text) = text {
+        insert(editor, &text, cx);
+      }
+      close(editor, window, cx);
+    });
+  }
+
+  fn dismiss(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+    let _ = self.editor.update(cx, |editor, cx| {
+      escape::close_topmost(editor, window, cx);
+    });
+  }
+
+  fn key_down(&mut self, event: &KeyDownEvent, window: &mut Window, cx: &mut Context<Self>) {
+    let keystroke = &event.keystroke;
+    if keystroke.key == "escape" {
+      self.dismiss(window, cx);
+    } else if let Some(key_char) = keystroke.key_char.as_ref()
+      && !keystroke.modifiers.platform
+      && !keystroke.modifiers.control
+    {
+      let query = format!("{}{}", self.query, key_char);
+      self.set_query(query, cx);
+    } else {
+      return;
+    }
+    cx.stop_propagation();
+  }
+}
+
+impl Focusable for UnicodeInput {
+  fn focus_handle(&self, _: &App) -> FocusHandle {
+    self.focus_handle.clone()
+  }
+}
+
+impl Render for UnicodeInput {
+  fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+    let Some(editor) = self.editor.upgrade() else {
+      return div();
+    };
+    let theme = editor.read(cx).theme.clone();
+    let query = if self.query.is_empty() {
+      "U+XXXX or symbol name".to_string()
+    } else {
+      self.query.clone()
+    };
+
+    div()
+      .key_context("UnicodeInput")
+      .track_focus(&self.focus_handle)
+      // The palette's own versions of the editor keys it shadows
+      .on_action(cx.listener(|this, _: &Enter, window, cx| this.confirm(window, cx)))
+      .on_action(cx.listener(|this, _: &Backspace, _, cx| {
+        let mut query = this.query.clone();
+        query.pop();
+        this.set_query(query, cx);
+      }))
+      .on_action(cx.listener(|this, _: &Up, _, cx| this.select(-1, cx)))
+      .on_action(cx.listener(|this, _: &Down, _, cx| this.select(1, cx)))
+      .on_key_down(cx.listener(Self::key_down))
+      .w(px(320.0))
+      .flex()
+      .flex_col()
+      .p_1()
+      .rounded_md()
+      .border_1()
+      .border_color(theme.line_number())
+      .bg(theme.gutter_background())
+      .text_size(px(12.0))
+      .child(div().px_1().pb_1().child(query))
+      .children(
+        matches(&self.query)
+          .into_iter()
+          .take(MAX_VISIBLE_MATCHES)
+          .enumerate()
+          .map(|(ix, code_point)| {
+            div()
+              .px_1()
+              .rounded_sm()
+              .when(ix == self.selected, |el| el.bg(theme.selection()))
+              .child(format!("{}  {}", code_point.ch, code_point.name))
+          }),
+      )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  fn symbols(query: &str) -> Vec<char> {
+    matches(query)
+      .into_iter()
+      .map(|code_point| code_point.ch)
+      .collect()
+  }
+
+  #[test]
+  fn test_code_point_input() {
+    assert_eq!(symbols("U+2192"), ['→']);
+    assert_eq!(symbols("u+1f600"), ['😀']);
+    // Surrogates and values past the last code point aren't characters
+    assert!(symbols("U+D800").is_empty());
+    assert!(symbols("U+110000").is_empty());
+    assert!(symbols("U+").is_empty());
+  }
+
+  #[test]
+  fn test_symbol_search() {
+    assert_eq!(symbols("").len(), SYMBOLS.concat().len());
+    assert_eq!(symbols("double arrow"), ['⇐', '⇒', '⇔']);
+    assert!(
+      symbols("box drawings")
+        .iter()
+        .all(|ch| ('─'..='╿').contains(ch))
+    );
+    assert_eq!(symbols("≠"), ['≠']);
+    assert!(symbols("no such symbol").is_empty());
+  }
+
+  #[gpui::test]
+  fn test_insert_is_one_transaction(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "a b");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.selected_range = 1..2;
+      insert(editor, "→", cx);
+      assert_eq!(editor.selected_range, 2..2);
+      assert_eq!(editor.document.read(cx).buffer.undo_depth(), 1);
+    });
+    assert_eq!(ctx.text(), "a→b");
+  }
+}
@@ -0,0 +1,48 @@
+//! The window title for the open document
+//!
+//! Windows are titled "file — folder", the folder being the one the file is
+//! in, with "(edited)" after it while the document has changes that aren't
+//! on disk. A document without a file is "Untitled".
+
+use std::path::Path;
+
+use gpui::App;
+
+use crate::editor::Editor;
+
+fn title(path: Option<&Path>, edited: bool) -> String {
+  let name = path
+    .and_then(Path::file_name)
+    .map_or("Untitled".into(), |name| name.to_string_lossy());
+  let folder = path
+    .and_then(Path::parent)
+    .and_then(Path::file_name)
+    .map(|folder| folder.to_string_lossy());
+  let mut title = match folder {
+    Some(folder) => format!("{} — {}", name, folder),
+    None => name.into_owned(),
+  };
+  if edited {
+    title.push_str(" (edited)");
+  }
+  title
+}
+
+/// Title of the window showing `editor`
+pub fn window_title(editor: &Editor, cx: &App) -> String {
+  title(editor.file_path(), editor.is_edited(cx))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_title() {
+    let path = Path::new("/home/me/project/src/main.rs");
+    assert_eq!(title(Some(path), false), "main.rs — src");
+    assert_eq!(title(Some(path), true), "main.rs — src (edited)");
+    assert_eq!(title(Some(Path::new("notes.txt")), false), "notes.txt");
+    assert_eq!(title(None, true), "Untitled (edited)");
+  }
+}
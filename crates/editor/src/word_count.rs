@@ -0,0 +1,213 @@
+//! Word count and reading time of prose documents
+//!
+//! Markdown and plain text documents show how many words they hold and how
+//! long they take to read in the status bar, and the same for the selection.
+//! Words are counted per line and kept up to date from the document's edits,
+//! so an edit only has the lines it touched counted again.
+
+use std::ops::Range;
+
+use buffer::{Bias, Edit};
+use gpui::{App, SharedString};
+
+use crate::{document::Document, editor::Editor, markdown};
+
+/// Average silent reading speed
+const WORDS_PER_MINUTE: usize = 230;
+
+#[derive(Default)]
+pub(crate) struct WordCount {
+  /// Words on each line of the document
+  lines: Vec<usize>,
+  total: usize,
+  // Document version the counts are for, none until counted
+  counted_version: Option<usize>,
+}
+
+/// Runs of non-whitespace with a letter or digit in them, so Markdown
+/// markers like `#` and `-` aren't words
+fn count_words(text: &str) -> usize {
+  text
+    .split_whitespace()
+    .filter(|word| word.chars().any(char::is_alphanumeric))
+    .count()
+}
+
+fn is_prose(document: &Document) -> bool {
+  let ext = document.file_extension();
+  markdown::is_markdown(ext) || matches!(ext, None | Some("txt" | "text"))
+}
+
+/// Char range of the current text covering everything `edits` inserted or
+/// removed, none without edits
+pub(crate) fn changed_range(edits: &[Edit]) -> Option<Range<usize>> {
+  edits.iter().fold(None, |range, edit| {
+    let inserted = edit.start..edit.start + edit.new_len;
+    Some(match range {
+      None => inserted,
+      Some(range) => {
+        let start = edit.transform(range.start, Bias::Left);
+        let end = edit.transform(range.end, Bias::Right);
+        start.min(inserted.start)..end.max(inserted.end)
+      }
+    })
+  })
+}
+
+fn line_words(document: &Document, line: usize) -> usize {
+  document
+    .line_content(line)
+    .map_or(0, |content| count_words(&content))
+}
+
+/// Bring the counts up to date with the document
+pub(crate) fn update(editor: &mut Editor, cx: &App) {
+  let document = editor.document.read(cx);
+  let counts = &mut editor.word_count;
+  let version = document.version();
+  if counts.counted_version == Some(version) {
+    return;
+  }
+  if !is_prose(document) {
+    *counts = WordCount::default();
+    return;
+  }
+
+  let line_count = document.len_lines();
+  let changed = counts
+    .counted_version
+    .and_then(|counted| changed_range(document.edits_since(counted)));
+  match changed {
+    Some(range) => {
+      let first = document.char_to_line(range.start);
+      let last = document.char_to_line(range.end);
+      // Lines after the change only moved, by the difference in line count
+      let old_end = (last + 1 + counts.lines.len())
+        .saturating_sub(line_count)
+        .clamp(first, counts.lines.len());
+      let recounted: Vec<usize> = (first..=last)
+        .map(|line| line_words(document, line))
+        .collect();
+      let added: usize = recounted.iter().sum();
+      let removed: usize = counts.lines.splice(first..old_end, recounted).sum();
+      counts.total = counts.total + added - removed;
+    }
+    None => {
+      counts.lines = (0..line_count)
+        .map(|line| line_words(document, line))
+        .collect();
+      counts.total = counts.lines.iter().sum();
+    }
+  }
+  counts.counted_version = Some(version);
+}
+
+/// Forget the counts of the previous document
+pub(crate) fn clear(editor: &mut Editor) {
+  editor.word_count = WordCount::default();
+}
+
+/// Words in the selection, counting the lines fully inside it from the
+/// per-line counts
+fn selection_words(editor: &Editor, document: &Document) -> usize {
+  let range = editor.selected_range.clone();
+  let first = document.char_to_line(range.start);
+  let last = document.char_to_line(range.end);
+  if first == last {
+    return count_words(&document.slice_to_string(range));
+  }
+  let first_line_end = document.line_to_char(first + 1);
+  let last_line_start = document.line_to_char(last);
+  count_words(&document.slice_to_string(range.start..first_line_end))
+    + editor.word_count.lines[first + 1..last]
+      .iter()
+      .sum::<usize>()
+    + count_words(&document.slice_to_string(last_line_start..range.end))
+}
+
+fn describe(words: usize) -> String {
+  let minutes = words.div_ceil(WORDS_PER_MINUTE);
+  let noun = if words == 1 { "word" } else { "words" };
+  format!("{} {}, {} min read", words, noun, minutes)
+}
+
+/// Status bar items for the document and the selection, none until counted
+pub(crate) fn status_items(editor: &Editor, cx: &App) -> Vec<SharedString> {
+  let document = editor.document.read(cx);
+  if editor.word_count.counted_version != Some(document.version()) {
+    return Vec::new();
+  }
+  let mut items = vec![describe(editor.word_count.total).into()];
+  if !editor.selected_range.is_empty() {
+    let selected = selection_words(editor, document);
+    items.push(format!("Selection: {}", describe(selected)).into());
+  }
+  items
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[test]
+  fn test_count_words() {
+    assert_eq!(count_words("# Title\n\n- one two\n---"), 3);
+    assert_eq!(count_words("  it's   1.5x  "), 2);
+  }
+
+  #[test]
+  fn test_changed_range() {
+    assert_eq!(changed_range(&[]), None);
+    let insert = |start, new_len| Edit {
+      start,
+      old_len: 0,
+      new_len,
+    };
+    // An insertion before an earlier one moves it forward
+    assert_eq!(changed_range(&[insert(10, 2), insert(0, 3)]), Some(0..15));
+    let delete = Edit {
+      start: 11,
+      old_len: 4,
+      new_len: 0,
+    };
+    assert_eq!(changed_range(&[insert(10, 2), delete]), Some(10..11));
+  }
+
+  #[gpui::test]
+  fn test_counts_follow_edits(cx: &mut TestAppContext) {
+    let text = "One two three.\n\nFour five\nsix\n";
+    let mut ctx = EditorTestContext::with_text(cx.clone(), text);
+    let edits: [(Range<usize>, &str); 4] = [
+      (4..4, "and a half "),
+      (14..27, " joined"),
+      (0..0, "New\nlines here\n"),
+      (20..40, ""),
+    ];
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      update(editor, cx);
+      assert_eq!(editor.word_count.total, 6);
+
+      for (range, new_text) in edits {
+        editor
+          .document
+          .update(cx, |document, cx| document.replace(range, new_text, cx));
+        update(editor, cx);
+        let document = editor.document.read(cx);
+        let recounted: Vec<usize> = (0..document.len_lines())
+          .map(|line| line_words(document, line))
+          .collect();
+        assert_eq!(editor.word_count.lines, recounted);
+        assert_eq!(editor.word_count.total, recounted.iter().sum::<usize>());
+      }
+    });
+
+    ctx.set_selection(2..9, false);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      let items = status_items(editor, cx);
+      assert_eq!(items.len(), 2);
+      assert_eq!(selection_words(editor, editor.document.read(cx)), 2);
+    });
+  }
+}
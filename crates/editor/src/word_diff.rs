@@ -0,0 +1,350 @@
+//! Underlining what changed on lines modified since the last commit
+//!
+//! The committed version of the file is read from git when word diff is
+//! turned on, another file is opened, or git status sees another commit
+//! checked out. After edits settle, the document is
+//! compared line by line with it; lines replaced in place are marked in the
+//! gutter, and the words that differ from their committed version are
+//! underlined.
+
+use std::{
+  ops::Range,
+  path::{Path, PathBuf},
+  process::Command,
+  sync::Arc,
+  time::Duration,
+};
+
+use buffer::Anchor;
+use gpui::{App, Context, Hsla, Task};
+
+use crate::{
+  decorations::{DecorationId, DecorationStyle},
+  diff::{DiffAlgorithm, LineChanges, line_changes},
+  editor::Editor,
+  git_status, trust,
+};
+
+/// Delay before comparing the document again after an edit
+const REFRESH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+#[derive(Default)]
+pub(crate) struct WordDiff {
+  enabled: bool,
+  /// Committed text of the open file, none when it isn't tracked
+  head: Option<Arc<str>>,
+  /// Commit the committed text was read from, as last seen by git status
+  head_commit: Option<String>,
+  decorations: Vec<DecorationId>,
+  hunks: Vec<Hunk>,
+  // Document version the decorations were computed for
+  scanned_version: Option<usize>,
+  task: Option<Task<()>>,
+}
+
+/// Lines changed since the last commit, as found in the document
+#[derive(Clone, Debug)]
+pub(crate) struct Hunk {
+  /// Char range of the changed lines, empty where lines were deleted
+  pub range: Range<Anchor>,
+  /// Index of the first committed line the hunk replaces
+  pub old_start: usize,
+  /// The committed lines, with their line breaks
+  pub old_text: String,
+}
+
+pub(crate) fn is_enabled(editor: &Editor) -> bool {
+  editor.word_diff.enabled
+}
+
+pub(crate) fn toggle(editor: &mut Editor, cx: &mut Context<Editor>) {
+  editor.word_diff.enabled = !editor.word_diff.enabled;
+  if editor.word_diff.enabled {
+    load_head(editor, cx);
+  } else {
+    clear(editor);
+    cx.notify();
+  }
+}
+
+/// Forget the committed text of the previous file, leaving word diff on
+pub(crate) fn clear(editor: &mut Editor) {
+  remove_decorations(editor);
+  editor.word_diff.head = None;
+  editor.word_diff.head_commit = None;
+  editor.word_diff.scanned_version = None;
+  editor.word_diff.task = None;
+}
+
+/// Read the committed text of the open file, if word diff is on
+pub(crate) fn reload_head(editor: &mut Editor, cx: &mut Context<Editor>) {
+  if editor.word_diff.enabled {
+    load_head(editor, cx);
+  }
+}
+
+/// Re-read the committed text once another commit is checked out
+pub(crate) fn head_changed(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let Some(path) = editor.file_path() else {
+    return;
+  };
+  if editor.word_diff.enabled && editor.word_diff.head_commit != git_status::head_commit(path, cx) {
+    load_head(editor, cx);
+  }
+}
+
+fn remove_decorations(editor: &mut Editor) {
+  for id in editor.word_diff.decorations.drain(..) {
+    editor.decorations.remove(id);
+  }
+  editor.word_diff.hunks.clear();
+}
+
+/// The hunk marked in the gutter next to `line`
+pub(crate) fn hunk_at_line(editor: &Editor, line: usize, cx: &App) -> Option<Hunk> {
+  let document = editor.document.read(cx);
+  editor
+    .word_diff
+    .hunks
+    .iter()
+    .find(|hunk| {
+      let range = document.resolve_anchor_range(&hunk.range);
+      let start = document.char_to_line(range.start);
+      if range.is_empty() {
+        line == start
+      } else {
+        (start..=document.char_to_line(range.end - 1)).contains(&line)
+      }
+    })
+    .cloned()
+}
+
+/// Nothing is read while the workspace is restricted
+fn load_head(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let Some(path) = editor.file_path().map(Path::to_path_buf) else {
+    return;
+  };
+  if trust::is_restricted(editor, cx) {
+    return;
+  }
+  editor.word_diff.head_commit = git_status::head_commit(&path, cx);
+  editor.word_diff.task = Some(cx.spawn(async move |this, cx| {
+    let head = cx
+      .background_executor()
+      .spawn(async move { read_head(&path) })
+      .await;
+    let _ = this.update(cx, |editor, cx| {
+      editor.word_diff.task = None;
+      match head {
+        Ok(Some(head)) => {
+          editor.word_diff.head = Some(Arc::from(head));
+          editor.word_diff.scanned_version = None;
+          schedule_refresh(editor, cx);
+        }
+        Ok(None) => {
+          editor.word_diff.head = None;
+          remove_decorations(editor);
+          cx.notify();
+        }
+        Err(err) => eprintln!("Failed to read the committed file: {}", err),
+      }
+    });
+  }));
+}
+
+/// Text of `path` in the HEAD commit, none if it isn't in a repository or
+/// isn't committed
+fn read_head(path: &Path) -> Result<Option<String>, String> {
+  let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+    return Ok(None);
+  };
+  let dir = if dir.as_os_str().is_empty() {
+    PathBuf::from(".")
+  } else {
+    dir.to_path_buf()
+  };
+  // Plumbing reads the blob as stored, without filters from the repository's config
+  let output = Command::new("git")
+    .arg("-C")
+    .arg(&dir)
+    .arg("cat-file")
+    .arg("blob")
+    .arg(format!("HEAD:./{}", name.to_string_lossy()))
+    .output()
+    .map_err(|err| format!("git: {}", err))?;
+  if !output.status.success() {
+    return Ok(None);
+  }
+  Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+}
+
+/// Compare the document with its committed text once edits have settled
+pub(crate) fn schedule_refresh(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let document = editor.document.read(cx);
+  let version = document.version();
+  let Some(head) = editor.word_diff.head.clone() else {
+    return;
+  };
+  if !editor.word_diff.enabled || editor.word_diff.scanned_version == Some(version) {
+    return;
+  }
+
+  let snapshot = document.snapshot();
+  let algorithm = cx
+    .try_global::<DiffAlgorithm>()
+    .copied()
+    .unwrap_or_default();
+  editor.word_diff.task = Some(cx.spawn(async move |this, cx| {
+    cx.background_executor().timer(REFRESH_DEBOUNCE).await;
+
+    let changes = cx
+      .background_executor()
+      .spawn(async move { line_changes(&head, &snapshot.text(), algorithm) })
+      .await;
+
+    let _ = this.update(cx, |editor, cx| {
+      // Byte offsets are only valid for the text they were computed from
+      if editor.document.read(cx).version() == version {
+        apply_changes(editor, changes, version, cx);
+      }
+    });
+  }));
+}
+
+pub(crate) fn apply_changes(
+  editor: &mut Editor,
+  changes: LineChanges,
+  version: usize,
+  cx: &mut Context<Editor>,
+) {
+  remove_decorations(editor);
+  let document = editor.document.read(cx);
+  let mut ranges = Vec::new();
+  let mut hunks = Vec::new();
+  for hunk in changes.hunks {
+    let line_start = |line: usize| {
+      if line < document.len_lines() {
+        document.line_to_char(line)
+      } else {
+        document.len()
+      }
+    };
+    let range =
+      document.anchor_range(line_start(hunk.new_lines.start)..line_start(hunk.new_lines.end));
+    let color = if hunk.old_lines.is_empty() {
+      added_line_color()
+    } else if hunk.new_lines.is_empty() {
+      deleted_line_color()
+    } else {
+      modified_line_color()
+    };
+    ranges.push((
+      range.clone(),
+      DecorationStyle {
+        gutter_background: Some(color),
+        ..Default::default()
+      },
+    ));
+    hunks.push(Hunk {
+      range,
+      old_start: hunk.old_lines.start,
+      old_text: hunk.old_text,
+    });
+  }
+  for word in changes.changed_words {
+    let range = document.byte_to_char(word.start)..document.byte_to_char(word.end);
+    ranges.push((
+      document.anchor_range(range),
+      DecorationStyle {
+        underline: Some(changed_word_color()),
+        ..Default::default()
+      },
+    ));
+  }
+
+  for (range, style) in ranges {
+    let id = editor.decorations.insert(range, style);
+    editor.word_diff.decorations.push(id);
+  }
+  editor.word_diff.hunks = hunks;
+  editor.word_diff.scanned_version = Some(version);
+  cx.notify();
+}
+
+fn added_line_color() -> Hsla {
+  Hsla {
+    h: 120.0 / 360.0,
+    s: 0.6,
+    l: 0.45,
+    a: 0.35,
+  }
+}
+
+fn deleted_line_color() -> Hsla {
+  Hsla {
+    h: 0.0,
+    s: 0.7,
+    l: 0.5,
+    a: 0.35,
+  }
+}
+
+fn modified_line_color() -> Hsla {
+  Hsla {
+    h: 210.0 / 360.0,
+    s: 0.7,
+    l: 0.55,
+    a: 0.35,
+  }
+}
+
+fn changed_word_color() -> Hsla {
+  Hsla {
+    h: 210.0 / 360.0,
+    s: 0.7,
+    l: 0.55,
+    a: 0.7,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+
+  #[gpui::test]
+  fn test_apply_changes(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "héllo wörld\n");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.word_diff.enabled = true;
+      let changes = line_changes("héllo world\n", "héllo wörld\n", DiffAlgorithm::Myers);
+      apply_changes(editor, changes, 0, cx);
+      assert_eq!(editor.word_diff.decorations.len(), 2);
+      let hunk = hunk_at_line(editor, 0, cx).unwrap();
+      assert_eq!(
+        (hunk.old_start, hunk.old_text.as_str()),
+        (0, "héllo world\n")
+      );
+      assert!(hunk_at_line(editor, 1, cx).is_none());
+
+      toggle(editor, cx);
+      assert!(editor.decorations.is_empty());
+    });
+  }
+
+  #[gpui::test]
+  fn test_git_waits_for_trust(cx: &mut TestAppContext) {
+    let temp = tempfile::tempdir().unwrap();
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "fn main() {}\n");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.file_path = Some(temp.path().join("main.rs"));
+      toggle(editor, cx);
+      assert!(is_enabled(editor));
+      assert!(editor.word_diff.task.is_none());
+
+      trust::trust_workspace(editor, cx);
+      assert!(editor.word_diff.task.is_some());
+    });
+  }
+}
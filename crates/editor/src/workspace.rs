@@ -0,0 +1,284 @@
+//! Workspace root folders
+//!
+//! A workspace holds one or more root folders, kept in a global shared by
+//! every editor. Project search covers the files of all roots, and paths are
+//! shown relative to the root holding them, prefixed with the root's name
+//! once there are several. A root can override settings for the files below
+//! it in `.gpui-editor/settings.json`.
+
+use std::path::{Path, PathBuf};
+
+use gpui::{App, Context, Global, PathPromptOptions, Window};
+use serde_json::Value;
+
+use crate::{editor::Editor, project_search::ProjectReplace, search::SearchQuery};
+
+/// Settings file of a root, relative to it
+const SETTINGS_FILE: &str = ".gpui-editor/settings.json";
+
+/// Settings a root overrides for its files
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RootSettings {
+  pub wrap_column: Option<usize>,
+}
+
+impl RootSettings {
+  /// Settings from a JSON object; unknown keys are ignored
+  pub fn parse(json: &str) -> Result<Self, String> {
+    let value: Value = serde_json::from_str(json).map_err(|err| err.to_string())?;
+    let object = value.as_object().ok_or("settings must be an object")?;
+    let wrap_column = match object.get("wrap_column") {
+      None | Some(Value::Null) => None,
+      Some(column) => Some(
+        column
+          .as_u64()
+          .filter(|&column| column > 0)
+          .ok_or("wrap_column must be a positive integer")? as usize,
+      ),
+    };
+    Ok(Self { wrap_column })
+  }
+
+  /// Settings of the root at `root`, the defaults if it has none
+  fn load(root: &Path) -> Result<Self, String> {
+    match std::fs::read_to_string(root.join(SETTINGS_FILE)) {
+      Ok(json) => Self::parse(&json).map_err(|err| format!("{}: {}", SETTINGS_FILE, err)),
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+      Err(err) => Err(err.to_string()),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorkspaceRoot {
+  pub path: PathBuf,
+  pub settings: RootSettings,
+}
+
+impl WorkspaceRoot {
+  /// Name of the folder, shown before the paths below it
+  pub fn name(&self) -> String {
+    self.path.file_name().map_or_else(
+      || self.path.display().to_string(),
+      |name| name.to_string_lossy().into_owned(),
+    )
+  }
+}
+
+#[derive(Debug, Default)]
+pub struct Workspace {
+  roots: Vec<WorkspaceRoot>,
+}
+
+impl Global for Workspace {}
+
+impl Workspace {
+  pub fn roots(&self) -> &[WorkspaceRoot] {
+    &self.roots
+  }
+
+  /// Add `path` as a root, reading its settings
+  pub fn add_folder(&mut self, path: &Path) -> Result<(), String> {
+    let path = path
+      .canonicalize()
+      .map_err(|err| format!("{}: {}", path.display(), err))?;
+    if !path.is_dir() {
+      return Err(format!("{}: not a folder", path.display()));
+    }
+    if self.roots.iter().any(|root| root.path == path) {
+      return Ok(());
+    }
+    let settings =
+      RootSettings::load(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    self.roots.push(WorkspaceRoot { path, settings });
+    Ok(())
+  }
+
+  /// Remove the root at `path`, returning whether it was one
+  pub fn remove_folder(&mut self, path: &Path) -> bool {
+    let len = self.roots.len();
+    self.roots.retain(|root| root.path != path);
+    self.roots.len() != len
+  }
+
+  /// The innermost root containing `path`
+  pub fn root_for(&self, path: &Path) -> Option<&WorkspaceRoot> {
+    self
+      .roots
+      .iter()
+      .filter(|root| path.starts_with(&root.path))
+      .max_by_key(|root| root.path.components().count())
+  }
+
+  /// `path` relative to its root, prefixed with the root's name when the
+  /// workspace has several; the full path outside of the roots
+  pub fn display_path(&self, path: &Path) -> String {
+    let Some(root) = self.root_for(path) else {
+      return path.display().to_string();
+    };
+    let relative = path.strip_prefix(&root.path).unwrap_or(path);
+    if self.roots.len() > 1 {
+      Path::new(&root.name()).join(relative).display().to_string()
+    } else {
+      relative.display().to_string()
+    }
+  }
+
+  /// Find `query` in the text files of every root
+  pub fn search(&self, query: SearchQuery, replacement: impl Into<String>) -> ProjectReplace {
+    let roots: Vec<&Path> = self.roots.iter().map(|root| root.path.as_path()).collect();
+    let mut replace = ProjectReplace::search_roots(&roots, query, replacement);
+    for file in &mut replace.files {
+      file.display_path = self.display_path(&file.path);
+    }
+    replace
+  }
+}
+
+/// Root containing the editor's file
+fn editor_root<'a>(editor: &Editor, workspace: &'a Workspace) -> Option<&'a WorkspaceRoot> {
+  let path = editor.file_path()?;
+  let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+  workspace.root_for(&path)
+}
+
+pub(crate) fn root_for_editor(editor: &Editor, cx: &App) -> Option<PathBuf> {
+  let root = editor_root(editor, cx.try_global::<Workspace>()?)?;
+  Some(root.path.clone())
+}
+
+/// Settings the root of the editor's file overrides
+pub(crate) fn root_settings(editor: &Editor, cx: &App) -> Option<RootSettings> {
+  let root = editor_root(editor, cx.try_global::<Workspace>()?)?;
+  Some(root.settings.clone())
+}
+
+/// Ask for folders and add them as roots
+pub(crate) fn add_folders(window: &mut Window, cx: &mut Context<Editor>) {
+  let paths = cx.prompt_for_paths(PathPromptOptions {
+    files: false,
+    directories: true,
+    multiple: true,
+    prompt: Some("Add to Workspace".into()),
+  });
+  cx.spawn_in(window, async move |_, cx| {
+    let Ok(Ok(Some(paths))) = paths.await else {
+      return;
+    };
+    let _ = cx.update(|_, cx| {
+      let workspace = cx.default_global::<Workspace>();
+      for path in paths {
+        if let Err(err) = workspace.add_folder(&path) {
+          eprintln!("Failed to add {} to the workspace: {}", path.display(), err);
+        }
+      }
+      cx.refresh_windows();
+    });
+  })
+  .detach();
+}
+
+/// Remove the root containing the editor's file
+pub(crate) fn remove_folder(editor: &mut Editor, cx: &mut Context<Editor>) {
+  let Some(root) = root_for_editor(editor, cx) else {
+    return;
+  };
+  if cx.default_global::<Workspace>().remove_folder(&root) {
+    cx.refresh_windows();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::tests::EditorTestContext;
+  use gpui::TestAppContext;
+  use tempfile::TempDir;
+
+  fn temp_roots() -> TempDir {
+    // Canonical, like the roots the workspace stores
+    let temp = tempfile::tempdir_in(std::env::temp_dir().canonicalize().unwrap()).unwrap();
+    let dir = temp.path();
+    std::fs::create_dir_all(dir.join("app/src")).unwrap();
+    std::fs::create_dir_all(dir.join("lib/.gpui-editor")).unwrap();
+    std::fs::write(dir.join("app/src/main.rs"), "fn old() {}\n").unwrap();
+    std::fs::write(dir.join("lib/lib.rs"), "old\n").unwrap();
+    std::fs::write(
+      dir.join("lib/.gpui-editor/settings.json"),
+      r#"{ "wrap_column": 72, "theme": "dark" }"#,
+    )
+    .unwrap();
+    temp
+  }
+
+  #[test]
+  fn test_root_settings() {
+    assert_eq!(
+      RootSettings::parse(r#"{ "wrap_column": 100 }"#),
+      Ok(RootSettings {
+        wrap_column: Some(100)
+      })
+    );
+    assert_eq!(RootSettings::parse("{}"), Ok(RootSettings::default()));
+    assert!(RootSettings::parse(r#"{ "wrap_column": -1 }"#).is_err());
+    assert!(RootSettings::parse("[]").is_err());
+  }
+
+  #[test]
+  fn test_roots() {
+    let temp = temp_roots();
+    let dir = temp.path();
+    let mut workspace = Workspace::default();
+    workspace.add_folder(&dir.join("app")).unwrap();
+    assert!(workspace.add_folder(&dir.join("missing")).is_err());
+
+    let main = dir.join("app/src/main.rs");
+    assert_eq!(workspace.display_path(&main), "src/main.rs");
+
+    workspace.add_folder(&dir.join("lib")).unwrap();
+    workspace.add_folder(&dir.join("lib")).unwrap();
+    assert_eq!(workspace.roots().len(), 2);
+    assert_eq!(workspace.display_path(&main), "app/src/main.rs");
+    assert_eq!(
+      workspace
+        .root_for(&dir.join("lib/lib.rs"))
+        .unwrap()
+        .settings,
+      RootSettings {
+        wrap_column: Some(72)
+      }
+    );
+
+    let replace = workspace.search(SearchQuery::new("old"), "new");
+    let paths: Vec<_> = replace
+      .files
+      .iter()
+      .map(|file| file.display_path.as_str())
+      .collect();
+    assert_eq!(paths, ["app/src/main.rs", "lib/lib.rs"]);
+
+    assert!(workspace.remove_folder(&dir.join("app")));
+    assert!(!workspace.remove_folder(&dir.join("app")));
+    assert_eq!(workspace.display_path(&dir.join("lib/lib.rs")), "lib.rs");
+  }
+
+  #[gpui::test]
+  fn test_root_for_editor(cx: &mut TestAppContext) {
+    let temp = temp_roots();
+    let dir = temp.path();
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      let workspace = cx.default_global::<Workspace>();
+      workspace.add_folder(&dir.join("app")).unwrap();
+      workspace.add_folder(&dir.join("lib")).unwrap();
+
+      editor.open_file(dir.join("lib/lib.rs"), cx).unwrap();
+      assert_eq!(root_for_editor(editor, cx), Some(dir.join("lib")));
+      assert_eq!(root_settings(editor, cx).unwrap().wrap_column, Some(72));
+
+      remove_folder(editor, cx);
+      assert_eq!(root_for_editor(editor, cx), None);
+      assert_eq!(cx.global::<Workspace>().roots().len(), 1);
+    });
+  }
+}
@@ -0,0 +1,853 @@
+//! Rows of the text area, from the display map
+//!
+//! The display map of the document decides what every row shows: lines
+//! hidden by folds are left out, the fold placeholder and the virtual text
+//! of decorations are inlays after the end of their line, elastic tabstops
+//! pad cells with inlays before their tabs, and with soft wrap on a line
+//! that doesn't fit is shown on as many rows as it needs, split every
+//! `column` characters, so the text never scrolls sideways. Rows then
+//! replace lines wherever the view counts them: scrolling, the viewport, the
+//! gutter, popups and moving up and down. The column is how many characters
+//! fit across the text area on the last frame.
+//!
+//! After an edit the map is spliced, measuring only the lines the edit
+//! touched; it is built again when a fold or an inlay away from the edit
+//! changed. A new width only recounts the lines long enough to wrap
+//! differently. Hex mode and large files have no display map, so they are
+//! never wrapped, folded or padded, and show their virtual text after the
+//! line.
+
+use std::ops::Range;
+
+use gpui::{App, Hsla, Pixels, ShapedLine, px};
+
+use crate::{
+  display_map::{DisplayMap, DisplayPoint, FOLD_PLACEHOLDER, Inlay},
+  document::Document,
+  editor::Editor,
+  elastic_tabs, large_file,
+  word_count::changed_range,
+};
+
+/// Gap between the end of a line and its virtual text
+const VIRTUAL_TEXT_GAP: &str = "  ";
+
+#[derive(Default)]
+pub(crate) struct WrapMap {
+  /// Characters that fit across the text area, as of the last frame
+  column: Option<usize>,
+  /// Rows of the document at `column`, with the version and folded lines
+  /// they were counted with
+  rows: Option<(usize, Vec<Range<usize>>, DisplayMap)>,
+}
+
+impl WrapMap {
+  /// Count the rows again, for another document
+  pub(crate) fn invalidate(&mut self) {
+    self.rows = None;
+  }
+}
+
+pub(crate) fn is_enabled(editor: &Editor) -> bool {
+  editor.soft_wrap && is_mapped(editor)
+}
+
+/// Whether rows come from the display map, which hex mode and large files
+/// do without
+fn is_mapped(editor: &Editor) -> bool {
+  !editor.hex_mode && !large_file::is_degraded(editor)
+}
+
+/// Wrap after `column` characters from now on
+pub(crate) fn set_column(editor: &mut Editor, column: Option<usize>, cx: &App) {
+  editor.wrap_map.column = column.filter(|&column| column > 0);
+  refresh(editor, cx);
+}
+
+/// Virtual text of the decorations on `lines`, as inlays after the end of
+/// the last line of each
+fn virtual_text_inlays(editor: &Editor, lines: Range<usize>, document: &Document) -> Vec<Inlay> {
+  let mut inlays: Vec<Inlay> = editor
+    .decorations
+    .resolve_in_lines(lines.clone(), document)
+    .into_iter()
+    .filter_map(|decoration| {
+      let virtual_text = decoration.style.virtual_text.as_ref()?;
+      let line = decoration.lines.end - 1;
+      if !lines.contains(&line) {
+        return None;
+      }
+      let len = document
+        .line_content(line)
+        .map_or(0, |content| content.chars().count());
+      Some(Inlay {
+        offset: document.line_to_char(line) + len,
+        text: format!("{VIRTUAL_TEXT_GAP}{}", virtual_text.text),
+        color: Some(virtual_text.color),
+      })
+    })
+    .collect();
+  inlays.sort_by_key(|inlay| inlay.offset);
+  inlays
+}
+
+/// Bring the rows up to date with the document, the column, the folds, the
+/// decorations and the setting
+pub(crate) fn refresh(editor: &mut Editor, cx: &App) {
+  if !is_mapped(editor) {
+    editor.wrap_map.rows = None;
+    return;
+  }
+  let column = editor.wrap_map.column.filter(|_| is_enabled(editor));
+  let folds = editor.folded_lines(cx);
+  let mut inlays = elastic_tabs::inlays(editor, cx).to_vec();
+  let document = editor.document.read(cx);
+  let version = document.version();
+  inlays.extend(virtual_text_inlays(
+    editor,
+    0..document.len_lines(),
+    document,
+  ));
+  inlays.sort_by_key(|inlay| inlay.offset);
+  let rows = &mut editor.wrap_map.rows;
+  let up_to_date = match rows {
+    Some((counted, folded, map)) if *counted == version => {
+      *folded == folds && map.inlays() == inlays
+    }
+    Some((counted, folded, map)) => splice(map, *counted, folded, &folds, &inlays, document),
+    None => false,
+  };
+  if !up_to_date {
+    *rows = None;
+  }
+  match rows {
+    Some((counted, folded, map)) => {
+      *counted = version;
+      *folded = folds;
+      map.set_wrap_column(column);
+    }
+    None => {
+      let map = DisplayMap::new(
+        &line_ranges(document, 0..document.len_lines()),
+        &folds,
+        &inlays,
+        column,
+      );
+      *rows = Some((version, folds, map));
+    }
+  }
+}
+
+/// Splice the lines edited since `version` into `map`, false if it has to
+/// be built again
+fn splice(
+  map: &mut DisplayMap,
+  version: usize,
+  old_folds: &[Range<usize>],
+  folds: &[Range<usize>],
+  inlays: &[Inlay],
+  document: &Document,
+) -> bool {
+  let edits = document.edits_since(version);
+  let Some(changed) = changed_range(edits) else {
+    return false;
+  };
+  let first = document.char_to_line(changed.start);
+  let last = document.char_to_line(changed.end);
+  // Lines after the change only moved, by the difference in line count
+  let line_delta = document.len_lines() as isize - map.line_count() as isize;
+  let Some(old_end) = (last + 1)
+    .checked_add_signed(-line_delta)
+    .filter(|&end| end > first && end <= map.line_count())
+  else {
+    return false;
+  };
+  // Folds after the edit follow their lines, any other change of the folds
+  // has the map built again
+  let moved: Vec<Range<usize>> = old_folds
+    .iter()
+    .map(|fold| {
+      if fold.start >= old_end {
+        fold.start.wrapping_add_signed(line_delta)..fold.end.wrapping_add_signed(line_delta)
+      } else {
+        fold.clone()
+      }
+    })
+    .collect();
+  if moved != folds {
+    return false;
+  }
+  let char_delta = edits
+    .iter()
+    .map(|edit| edit.new_len as isize - edit.old_len as isize)
+    .sum();
+  map.splice(
+    first..old_end,
+    first..last + 1,
+    &line_ranges(document, first..last + 1),
+    char_delta,
+    inlays,
+  )
+}
+
+/// Char ranges of `lines` of `document`, without line breaks
+pub(crate) fn line_ranges(document: &Document, lines: Range<usize>) -> Vec<Range<usize>> {
+  lines
+    .map(|line| {
+      let start = document.line_to_char(line);
+      let len = document
+        .line_content(line)
+        .map_or(0, |content| content.chars().count());
+      start..start + len
+    })
+    .collect()
+}
+
+/// The rows of the document when they are up to date
+pub(crate) fn rows(editor: &Editor, cx: &App) -> Option<&DisplayMap> {
+  let (version, _, map) = editor.wrap_map.rows.as_ref()?;
+  (is_mapped(editor) && *version == editor.document.read(cx).version()).then_some(map)
+}
+
+/// Rows of text, as many as lines unless they are wrapped or folded
+pub(crate) fn row_count(editor: &Editor, cx: &App) -> usize {
+  rows(editor, cx).map_or_else(
+    || editor.document.read(cx).len_lines(),
+    DisplayMap::row_count,
+  )
+}
+
+/// Row showing `offset`, and the offset the row starts at
+pub(crate) fn row_at(editor: &Editor, offset: usize, cx: &App) -> (usize, usize) {
+  match rows(editor, cx) {
+    Some(map) => {
+      let row = map.to_display_point(offset).row;
+      (row, map.to_offset(DisplayPoint::new(row, 0)))
+    }
+    None => {
+      let document = editor.document.read(cx);
+      let line = document.char_to_line(offset);
+      (line, document.line_to_char(line))
+    }
+  }
+}
+
+/// Rows showing buffer line `line`, the row of the fold hiding it if it is hidden
+pub(crate) fn rows_for_line(editor: &Editor, line: usize, cx: &App) -> Range<usize> {
+  let Some(map) = rows(editor, cx) else {
+    return line..line + 1;
+  };
+  map.rows_for_line(line).unwrap_or_else(|| {
+    let document = editor.document.read(cx);
+    let line = line.min(document.len_lines().saturating_sub(1));
+    let row = map.to_display_point(document.line_to_char(line)).row;
+    row..row + 1
+  })
+}
+
+/// Buffer line shown on `row`
+pub(crate) fn line_at_row(editor: &Editor, row: usize, cx: &App) -> usize {
+  match rows(editor, cx) {
+    Some(map) => map
+      .buffer_line(row.min(map.row_count().saturating_sub(1)))
+      .unwrap_or(0),
+    None => row,
+  }
+}
+
+/// Layout of rows `rows` of the text
+pub(crate) fn layout(editor: &Editor, rows: Range<usize>, cx: &App) -> RowLayout {
+  let document = editor.document.read(cx);
+  match self::rows(editor, cx) {
+    Some(map) => RowLayout::mapped(map, rows, document),
+    None => {
+      let inlays = virtual_text_inlays(editor, rows.clone(), document);
+      RowLayout::unmapped(rows, &inlays, document)
+    }
+  }
+}
+
+/// Part of an inlay or of a fold placeholder shown on a row
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct RowInlay {
+  /// Byte of the line the inlay sits before
+  pub byte: usize,
+  /// What of the inlay's text is on the row
+  pub text: String,
+  /// Color of the text, the text color if none
+  pub color: Option<Hsla>,
+  /// Whether it is the placeholder of a fold
+  pub placeholder: bool,
+  /// Whether the inlay started on an earlier row
+  continued: bool,
+}
+
+impl RowInlay {
+  fn width(&self, char_width: Pixels) -> Pixels {
+    char_width * self.text.chars().count() as f32
+  }
+}
+
+/// Rows of a line in the viewport
+#[derive(Clone, Debug, PartialEq)]
+struct LineRows {
+  line: usize,
+  first_row: usize,
+  /// Byte each row starts at, the first at 0
+  starts: Vec<usize>,
+  /// Inlays on each row, left to right
+  inlays: Vec<Vec<RowInlay>>,
+  /// Length of the line in bytes
+  len: usize,
+}
+
+impl LineRows {
+  /// Index of the row showing the glyph at byte `byte`
+  fn glyph_row(&self, byte: usize) -> usize {
+    self
+      .starts
+      .partition_point(|&start| start <= byte)
+      .saturating_sub(1)
+  }
+}
+
+/// Where the lines in the viewport are shown, row by row, with the inlays
+/// between their text
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct RowLayout {
+  /// Rows in the viewport
+  pub rows: Range<usize>,
+  lines: Vec<LineRows>,
+}
+
+impl RowLayout {
+  /// One row per line, with `lines` in the viewport and `inlays` after the
+  /// end of their line
+  pub(crate) fn unmapped(lines: Range<usize>, inlays: &[Inlay], document: &Document) -> Self {
+    let rows = lines.clone();
+    let lines = lines
+      .map(|line| {
+        let len = document
+          .line_content(line)
+          .map_or(0, |content| content.len());
+        let line_inlays = inlays
+          .iter()
+          .filter(|inlay| document.char_to_line(inlay.offset) == line)
+          .map(|inlay| RowInlay {
+            byte: len,
+            text: inlay.text.clone(),
+            color: inlay.color,
+            placeholder: false,
+            continued: false,
+          })
+          .collect();
+        LineRows {
+          line,
+          first_row: line,
+          starts: vec![0],
+          inlays: vec![line_inlays],
+          len,
+        }
+      })
+      .collect();
+    Self { rows, lines }
+  }
+
+  /// Rows `rows` of `map`, with every row of the lines they show
+  pub(crate) fn mapped(map: &DisplayMap, rows: Range<usize>, document: &Document) -> Self {
+    let mut lines = Vec::new();
+    if let (Some(first), Some(last)) = (
+      map.buffer_line(rows.start),
+      rows.end.checked_sub(1).and_then(|row| map.buffer_line(row)),
+    ) {
+      for line in first..=last {
+        let Some(line_rows) = map.rows_for_line(line) else {
+          continue;
+        };
+        let line_start = document.line_to_char(line);
+        let content = document.line_content(line).unwrap_or_default();
+        let byte_for_column = |column: usize| {
+          content
+            .char_indices()
+            .nth(column)
+            .map_or(content.len(), |(byte, _)| byte)
+        };
+        let mut starts = Vec::with_capacity(line_rows.len());
+        let mut inlays = Vec::with_capacity(line_rows.len());
+        for row in line_rows.clone() {
+          let column = map.to_offset(DisplayPoint::new(row, 0)) - line_start;
+          starts.push(byte_for_column(column));
+          let row_inlays = map
+            .inlays_in_row(row)
+            .into_iter()
+            .map(|chunk| {
+              let text = match chunk.inlay {
+                Some(ix) => map.inlays()[ix].text.as_str(),
+                None => FOLD_PLACEHOLDER,
+              };
+              RowInlay {
+                byte: byte_for_column(chunk.offset - line_start),
+                text: text
+                  .chars()
+                  .skip(chunk.chars.start)
+                  .take(chunk.chars.len())
+                  .collect(),
+                color: chunk.inlay.and_then(|ix| map.inlays()[ix].color),
+                placeholder: chunk.inlay.is_none(),
+                continued: chunk.chars.start > 0,
+              }
+            })
+            .collect();
+          inlays.push(row_inlays);
+        }
+        lines.push(LineRows {
+          line,
+          first_row: line_rows.start,
+          starts,
+          inlays,
+          len: content.len(),
+        });
+      }
+    }
+    Self { rows, lines }
+  }
+
+  /// Buffer lines with rows in the viewport
+  pub(crate) fn lines(&self) -> Range<usize> {
+    match (self.lines.first(), self.lines.last()) {
+      (Some(first), Some(last)) => first.line..last.line + 1,
+      _ => 0..0,
+    }
+  }
+
+  fn line(&self, line: usize) -> Option<&LineRows> {
+    let ix = self
+      .lines
+      .binary_search_by_key(&line, |rows| rows.line)
+      .ok()?;
+    Some(&self.lines[ix])
+  }
+
+  /// The rows of the line shown on `row`, and the index of `row` among them
+  fn row(&self, row: usize) -> Option<(&LineRows, usize)> {
+    let ix = self
+      .lines
+      .partition_point(|rows| rows.first_row <= row)
+      .checked_sub(1)?;
+    let rows = &self.lines[ix];
+    let ix = row - rows.first_row;
+    (ix < rows.starts.len()).then_some((rows, ix))
+  }
+
+  /// Whether `line` is shown, not hidden by a fold
+  pub(crate) fn is_shown(&self, line: usize) -> bool {
+    self.line(line).is_some()
+  }
+
+  /// Whether `line` is shown on more than one row
+  pub(crate) fn is_wrapped(&self, line: usize) -> bool {
+    self.line(line).is_some_and(|rows| rows.starts.len() > 1)
+  }
+
+  /// Whether inlays are shown on the rows of `line`
+  pub(crate) fn has_inlays(&self, line: usize) -> bool {
+    self
+      .line(line)
+      .is_some_and(|rows| rows.inlays.iter().any(|inlays| !inlays.is_empty()))
+  }
+
+  /// Row the caret at byte `byte` of `line` is on, and the byte it starts at
+  ///
+  /// A byte where a row ends is at the start of the next, except the end of
+  /// the line. The caret sits before the inlays at its byte, so it is on the
+  /// row where the first of them starts.
+  pub(crate) fn row_of(&self, line: usize, byte: usize) -> Option<(usize, usize)> {
+    let rows = self.line(line)?;
+    let last = rows.glyph_row(byte);
+    let ix = (0..=last)
+      .find(|&ix| {
+        rows.inlays[ix]
+          .iter()
+          .any(|inlay| inlay.byte == byte && !inlay.continued)
+      })
+      .unwrap_or(last);
+    Some((rows.first_row + ix, rows.starts[ix]))
+  }
+
+  /// Rows of `line` in the viewport, with the bytes each one shows
+  pub(crate) fn line_rows(&self, line: usize) -> Vec<(usize, Range<usize>)> {
+    let Some(rows) = self.line(line) else {
+      return Vec::new();
+    };
+    rows
+      .starts
+      .iter()
+      .enumerate()
+      .map(|(ix, &start)| {
+        let end = rows.starts.get(ix + 1).copied().unwrap_or(rows.len);
+        (rows.first_row + ix, start..end)
+      })
+      .filter(|(row, _)| self.rows.contains(row))
+      .collect()
+  }
+
+  /// Parts of `bytes` of `line` on each of its rows in the viewport, an
+  /// empty range on the row `row_of` puts it on
+  pub(crate) fn spans(&self, line: usize, bytes: Range<usize>) -> Vec<(usize, Range<usize>)> {
+    if bytes.is_empty() {
+      return self
+        .row_of(line, bytes.start)
+        .filter(|(row, _)| self.rows.contains(row))
+        .map(|(row, _)| (row, bytes))
+        .into_iter()
+        .collect();
+    }
+    self
+      .line_rows(line)
+      .into_iter()
+      .filter_map(|(row, range)| {
+        let start = bytes.start.max(range.start);
+        let end = bytes.end.min(range.end);
+        (start < end).then_some((row, start..end))
+      })
+      .collect()
+  }
+
+  /// The line shown on `row` and the bytes of it on the row
+  pub(crate) fn at_row(&self, row: usize) -> Option<(usize, Range<usize>)> {
+    let (rows, ix) = self.row(row)?;
+    let end = rows.starts.get(ix + 1).copied().unwrap_or(rows.len);
+    Some((rows.line, rows.starts[ix]..end))
+  }
+
+  /// Inlays shown on `row`, left to right
+  pub(crate) fn inlays(&self, row: usize) -> &[RowInlay] {
+    match self.row(row) {
+      Some((rows, ix)) => &rows.inlays[ix],
+      None => &[],
+    }
+  }
+
+  /// X of byte `byte` of the line shown on `row`, from the start of the row,
+  /// past the inlays at the byte or not; inlays are `char_width` per char
+  fn x_in_row(
+    &self,
+    row: usize,
+    byte: usize,
+    past_inlays: bool,
+    shaped: &ShapedLine,
+    char_width: Pixels,
+  ) -> Pixels {
+    let Some((rows, ix)) = self.row(row) else {
+      return px(0.);
+    };
+    let inlays_width = rows.inlays[ix]
+      .iter()
+      .filter(|inlay| inlay.byte < byte || (past_inlays && inlay.byte == byte))
+      .map(|inlay| inlay.width(char_width))
+      .fold(px(0.), |total, width| total + width);
+    shaped.x_for_index(byte) - shaped.x_for_index(rows.starts[ix]) + inlays_width
+  }
+
+  /// Row of the caret at byte `byte` of `line` and its x from the start of
+  /// the row
+  pub(crate) fn caret_x(
+    &self,
+    line: usize,
+    byte: usize,
+    shaped: &ShapedLine,
+    char_width: Pixels,
+  ) -> Option<(usize, Pixels)> {
+    let (row, _) = self.row_of(line, byte)?;
+    Some((row, self.x_in_row(row, byte, false, shaped, char_width)))
+  }
+
+  /// Row of the glyph at byte `byte` of `line` and its x from the start of
+  /// the row
+  pub(crate) fn glyph_x(
+    &self,
+    line: usize,
+    byte: usize,
+    shaped: &ShapedLine,
+    char_width: Pixels,
+  ) -> Option<(usize, Pixels)> {
+    let rows = self.line(line)?;
+    let row = rows.first_row + rows.glyph_row(byte);
+    Some((row, self.x_in_row(row, byte, true, shaped, char_width)))
+  }
+
+  /// Parts of `bytes` of `line` on each of its rows in the viewport, from
+  /// the start of their glyphs to the caret after them, an empty range
+  /// where the caret is
+  pub(crate) fn span_xs(
+    &self,
+    line: usize,
+    bytes: Range<usize>,
+    shaped: &ShapedLine,
+    char_width: Pixels,
+  ) -> Vec<(usize, Range<Pixels>)> {
+    self
+      .spans(line, bytes)
+      .into_iter()
+      .map(|(row, bytes)| (row, self.row_span_x(row, bytes, shaped, char_width)))
+      .collect()
+  }
+
+  /// From the start of the glyphs of `bytes` on `row` to the caret after
+  /// them, from the start of the row
+  pub(crate) fn row_span_x(
+    &self,
+    row: usize,
+    bytes: Range<usize>,
+    shaped: &ShapedLine,
+    char_width: Pixels,
+  ) -> Range<Pixels> {
+    let end = self.x_in_row(row, bytes.end, false, shaped, char_width);
+    let start = if bytes.is_empty() {
+      end
+    } else {
+      self.x_in_row(row, bytes.start, true, shaped, char_width)
+    };
+    start..end
+  }
+
+  /// X of each inlay on `row`, from the start of the row
+  pub(crate) fn inlay_xs(
+    &self,
+    row: usize,
+    shaped: &ShapedLine,
+    char_width: Pixels,
+  ) -> Vec<Pixels> {
+    let Some((rows, ix)) = self.row(row) else {
+      return Vec::new();
+    };
+    let row_x = shaped.x_for_index(rows.starts[ix]);
+    let mut inlays_width = px(0.);
+    rows.inlays[ix]
+      .iter()
+      .map(|inlay| {
+        let x = shaped.x_for_index(inlay.byte) - row_x + inlays_width;
+        inlays_width += inlay.width(char_width);
+        x
+      })
+      .collect()
+  }
+
+  /// Runs of the text of `row` between its inlays, with the x each starts
+  /// at from the start of the row
+  pub(crate) fn text_pieces(
+    &self,
+    row: usize,
+    shaped: &ShapedLine,
+    char_width: Pixels,
+  ) -> Vec<(Range<usize>, Pixels)> {
+    let Some((line, bytes)) = self.at_row(row) else {
+      return Vec::new();
+    };
+    let Some(rows) = self.line(line) else {
+      return Vec::new();
+    };
+    let row_x = shaped.x_for_index(bytes.start);
+    let mut pieces = Vec::new();
+    let mut start = bytes.start;
+    let mut inlays_width = px(0.);
+    for inlay in &rows.inlays[row - rows.first_row] {
+      if inlay.byte > start {
+        pieces.push((
+          start..inlay.byte,
+          shaped.x_for_index(start) - row_x + inlays_width,
+        ));
+        start = inlay.byte;
+      }
+      inlays_width += inlay.width(char_width);
+    }
+    if bytes.end > start {
+      pieces.push((
+        start..bytes.end,
+        shaped.x_for_index(start) - row_x + inlays_width,
+      ));
+    }
+    pieces
+  }
+
+  /// Byte of the line shown on `row` closest to `x` from the start of the
+  /// row, within the bytes of the row; an inlay maps to the byte it sits
+  /// before
+  pub(crate) fn index_for_x(
+    &self,
+    row: usize,
+    x: Pixels,
+    shaped: &ShapedLine,
+    char_width: Pixels,
+  ) -> Option<usize> {
+    let (_, bytes) = self.at_row(row)?;
+    let row_x = shaped.x_for_index(bytes.start);
+    let mut start = bytes.start;
+    let mut inlays_width = px(0.);
+    for (inlay, inlay_x) in self
+      .inlays(row)
+      .iter()
+      .zip(self.inlay_xs(row, shaped, char_width))
+    {
+      if x < inlay_x {
+        break;
+      }
+      inlays_width += inlay.width(char_width);
+      if x < inlay_x + inlay.width(char_width) {
+        return Some(inlay.byte);
+      }
+      start = inlay.byte;
+    }
+    let index = shaped.closest_index_for_x(x - inlays_width + row_x);
+    Some(index.clamp(start, bytes.end))
+  }
+
+  /// Top of `row`, for a viewport whose first row is at `top`
+  pub(crate) fn row_top(&self, row: usize, top: Pixels, line_height: Pixels) -> Pixels {
+    top + line_height * (row as f32 - self.rows.start as f32)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    decorations::{DecorationStyle, VirtualText},
+    editor::tests::EditorTestContext,
+  };
+  use gpui::TestAppContext;
+
+  #[gpui::test]
+  fn test_rows_follow_edits_and_width(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "abcdefgh\nij");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      set_column(editor, Some(3), cx);
+      // Not wrapped until turned on
+      assert_eq!(row_count(editor, cx), 2);
+
+      editor.soft_wrap = true;
+      refresh(editor, cx);
+      assert_eq!(row_count(editor, cx), 4);
+      assert_eq!(row_at(editor, 7, cx), (2, 6));
+      assert_eq!(rows_for_line(editor, 1, cx), 3..4);
+      assert_eq!(line_at_row(editor, 2, cx), 0);
+
+      // Stale after an edit until refreshed
+      editor.move_to(11, cx);
+      editor.insert_text("klmn", "Insert", cx);
+      assert!(rows(editor, cx).is_none());
+      refresh(editor, cx);
+      assert_eq!(row_count(editor, cx), 5);
+
+      set_column(editor, Some(4), cx);
+      assert_eq!(row_count(editor, cx), 4);
+    });
+  }
+
+  #[gpui::test]
+  fn test_folds_hide_rows(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "fn a() {\n  b;\n  c;\n}\nd\n");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.move_to(7, cx);
+      crate::folds::toggle_bracket_region(editor, cx);
+      // Unwrapped, the lines inside the brackets are hidden
+      assert_eq!(row_count(editor, cx), 4);
+      assert_eq!(line_at_row(editor, 1, cx), 3);
+      assert_eq!(rows_for_line(editor, 2, cx), 0..1);
+      let layout = layout(editor, 0..4, cx);
+      assert!(!layout.is_shown(1));
+      assert!(layout.is_shown(3));
+
+      crate::folds::toggle_bracket_region(editor, cx);
+      assert_eq!(row_count(editor, cx), 6);
+    });
+  }
+
+  #[gpui::test]
+  fn test_row_layout(cx: &mut TestAppContext) {
+    let ctx = EditorTestContext::with_text(cx.clone(), "héllo wörld\nab");
+    ctx.cx.update(|cx| {
+      let document = ctx.editor.read(cx).document.read(cx);
+      let lines = [0..11, 12..14];
+      let map = DisplayMap::new(&lines, &[], &[], Some(4));
+      assert_eq!(map.row_count(), 4);
+
+      // The last row of the long line and the short line
+      let layout = RowLayout::mapped(&map, 2..4, document);
+      assert_eq!(layout.lines(), 0..2);
+      assert_eq!(layout.line_rows(0), [(2, 10..13)]);
+      assert_eq!(layout.at_row(3), Some((1, 0..2)));
+      assert_eq!(layout.at_row(2), Some((0, 10..13)));
+      // "ö" is two bytes, so the rows start at bytes 0, 5 and 10
+      assert_eq!(layout.row_of(0, 5), Some((1, 5)));
+      assert_eq!(layout.row_of(0, 13), Some((2, 10)));
+      assert_eq!(layout.spans(0, 3..12), [(2, 10..12)]);
+      assert_eq!(layout.spans(0, 10..10), [(2, 10..10)]);
+
+      let layout = RowLayout::unmapped(0..2, &[], document);
+      assert_eq!(layout.line_rows(0), [(0, 0..13)]);
+      assert_eq!(layout.at_row(1), Some((1, 0..2)));
+    });
+  }
+
+  #[gpui::test]
+  fn test_edits_are_spliced(cx: &mut TestAppContext) {
+    let text = "fn a() {\n  b;\n}\nlonger line\nfn c() {\n  d;\n}\n";
+    let mut ctx = EditorTestContext::with_text(cx.clone(), text);
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      editor.soft_wrap = true;
+      editor.move_to(text.find("{\n  d").unwrap(), cx);
+      crate::folds::toggle_bracket_region(editor, cx);
+      set_column(editor, Some(5), cx);
+      for (offset, text) in [(0, "x"), (10, "\nnew line\n"), (3, ""), (20, "ab")] {
+        editor.move_to(offset, cx);
+        editor.insert_text(text, "Typing", cx);
+        refresh(editor, cx);
+        let document = editor.document.read(cx);
+        let lines = line_ranges(document, 0..document.len_lines());
+        let built = DisplayMap::new(&lines, &editor.folded_lines(cx), &[], Some(5));
+        assert_eq!(rows(editor, cx), Some(&built));
+      }
+    });
+  }
+
+  #[gpui::test]
+  fn test_virtual_text_takes_rows(cx: &mut TestAppContext) {
+    let mut ctx = EditorTestContext::with_text(cx.clone(), "abc\nd");
+    ctx.editor.update(&mut ctx.cx, |editor, cx| {
+      let range = editor.document.read(cx).anchor_range(0..1);
+      editor.decorations.insert(
+        range,
+        DecorationStyle {
+          virtual_text: Some(VirtualText {
+            text: "hint".into(),
+            color: gpui::red(),
+          }),
+          ..Default::default()
+        },
+      );
+      editor.soft_wrap = true;
+      set_column(editor, Some(4), cx);
+      // "abc  hint" is 9 columns
+      assert_eq!(row_count(editor, cx), 4);
+      assert_eq!(rows_for_line(editor, 1, cx), 3..4);
+
+      let layout = layout(editor, 0..4, cx);
+      assert!(layout.has_inlays(0));
+      assert_eq!(layout.line_rows(0), [(0, 0..3), (1, 3..3), (2, 3..3)]);
+      let texts: Vec<_> = (0..3)
+        .map(|row| {
+          layout
+            .inlays(row)
+            .iter()
+            .map(|inlay| inlay.text.clone())
+            .collect::<Vec<_>>()
+        })
+        .collect();
+      assert_eq!(texts, [vec![" "], vec![" hin"], vec!["t"]]);
+      // The caret at the end of the line is before the virtual text
+      assert_eq!(layout.row_of(0, 3), Some((0, 0)));
+      assert_eq!(layout.at_row(2), Some((0, 3..3)));
+    });
+  }
+}
@@ -10,6 +10,48 @@ const INITIAL_WINDOW_HEIGHT: f32 = 800.0;
 struct EditorExample {
   editor: Entity<Editor>,
   focus_handle: FocusHandle,
+  panels: Panels,
+  // Undo and redo items of the menu bar as last set
+  history_labels: (String, String),
+}
+
+impl EditorExample {
+  fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+    let editor = cx.new(Editor::new);
+    let mut panels = Panels::default();
+    panels.register(PanelKind::Editor, editor.focus_handle(cx));
+    cx.observe_in(&editor, window, |this, editor, window, cx| {
+      let labels = history_labels(editor.read(cx), cx);
+      if labels != this.history_labels {
+        cx.set_menus(app_menus(editor.read(cx), cx));
+        this.history_labels = labels;
+      }
+      update_title(editor.read(cx), window, cx);
+    })
+    .detach();
+    update_title(editor.read(cx), window, cx);
+    let history_labels = history_labels(editor.read(cx), cx);
+    Self {
+      editor,
+      focus_handle: cx.focus_handle(),
+      panels,
+      history_labels,
+    }
+  }
+
+  fn focus_editor(&mut self, _: &FocusEditor, window: &mut Window, cx: &mut Context<Self>) {
+    self
+      .editor
+      .update(cx, |editor, cx| editor.dismiss_overlays(window, cx));
+    self.panels.focus(PanelKind::Editor, window, cx);
+  }
+}
+
+/// Title the window for the editor's document, marking it edited in the
+/// title bar too
+fn update_title(editor: &Editor, window: &mut Window, cx: &App) {
+  window.set_window_title(&window_title(editor, cx));
+  window.set_window_edited(editor.is_edited(cx));
 }
 
 impl Focusable for EditorExample {
@@ -19,14 +61,26 @@ impl Focusable for EditorExample {
 }
 
 impl Render for EditorExample {
-  fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+  fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+    let editor_focused = self.panels.focused(window, cx) == Some(PanelKind::Editor);
     div()
       .bg(rgb(0xaaaaaa))
       .track_focus(&self.focus_handle(cx))
+      .on_action(cx.listener(Self::focus_editor))
+      .on_action(cx.listener(|this, _: &FocusNextPanel, window, cx| {
+        this.panels.focus_next(window, cx);
+      }))
+      // No file tree or terminal panel is registered in this window yet
+      .on_action(cx.listener(|this, _: &FocusFileTree, window, cx| {
+        this.panels.focus(PanelKind::FileTree, window, cx);
+      }))
+      .on_action(cx.listener(|this, _: &FocusTerminal, window, cx| {
+        this.panels.focus(PanelKind::Terminal, window, cx);
+      }))
       .flex()
       .flex_col()
       .size_full()
-      .child(self.editor.clone())
+      .child(focus_ring(div().size_full(), editor_focused).child(self.editor.clone()))
   }
 }
 
@@ -38,14 +92,32 @@ fn main() {
       cx,
     );
 
+    if let Some(store) = WorkspaceTrust::default_store() {
+      cx.set_global(WorkspaceTrust::load(store));
+    }
+    cx.set_global(CrashRecovery::in_config_dir());
+    cx.set_global(FoldSettings::in_config_dir());
+    load_plugins(cx);
+    if let Ok(dir) = std::env::current_dir()
+      && let Err(err) = cx.default_global::<Workspace>().add_folder(&dir)
+    {
+      eprintln!("Failed to open {} as a workspace: {}", dir.display(), err);
+    }
+    watch_git_status(cx);
+
     cx.bind_keys([
       KeyBinding::new("enter", Enter, None),
+      KeyBinding::new("tab", Indent, None),
+      KeyBinding::new("shift-tab", Outdent, None),
+      KeyBinding::new("cmd-/", ToggleComment, None),
       KeyBinding::new("backspace", Backspace, None),
       KeyBinding::new("alt-backspace", BackspaceWord, None),
       KeyBinding::new("cmd-backspace", BackspaceAll, None),
       KeyBinding::new("delete", Delete, None),
       KeyBinding::new("up", Up, None),
       KeyBinding::new("down", Down, None),
+      KeyBinding::new("pageup", PageUp, None),
+      KeyBinding::new("pagedown", PageDown, None),
       KeyBinding::new("left", Left, None),
       KeyBinding::new("alt-left", AltLeft, None),
       KeyBinding::new("cmd-left", CmdLeft, None),
@@ -56,6 +128,8 @@ fn main() {
       KeyBinding::new("cmd-down", CmdDown, None),
       KeyBinding::new("shift-up", SelectUp, None),
       KeyBinding::new("shift-down", SelectDown, None),
+      KeyBinding::new("shift-pageup", SelectPageUp, None),
+      KeyBinding::new("shift-pagedown", SelectPageDown, None),
       KeyBinding::new("shift-cmd-left", SelectCmdLeft, None),
       KeyBinding::new("shift-cmd-right", SelectCmdRight, None),
       KeyBinding::new("shift-cmd-up", SelectCmdUp, None),
@@ -65,13 +139,77 @@ fn main() {
       KeyBinding::new("shift-right", SelectRight, None),
       KeyBinding::new("shift-alt-right", SelectWordRight, None),
       KeyBinding::new("cmd-a", SelectAll, None),
+      KeyBinding::new("cmd-d", SelectNextOccurrence, None),
       KeyBinding::new("cmd-v", Paste, None),
       KeyBinding::new("cmd-c", Copy, None),
       KeyBinding::new("cmd-x", Cut, None),
+      KeyBinding::new("alt-cmd-shift-c", CopyAppend, None),
+      KeyBinding::new("alt-cmd-shift-x", CutAppend, None),
       KeyBinding::new("cmd-z", Undo, None),
       KeyBinding::new("cmd-shift-z", Redo, None),
+      KeyBinding::new("alt-q", ReflowParagraph, None),
+      KeyBinding::new("cmd-shift-d", DuplicateLine, None),
+      KeyBinding::new("cmd-shift-k", DeleteLine, None),
+      KeyBinding::new("alt-up", MoveLineUp, None),
+      KeyBinding::new("alt-down", MoveLineDown, None),
+      KeyBinding::new("alt-cmd-c", ToggleCoverageOverlay, None),
+      KeyBinding::new("alt-cmd-t", StartPerformanceTrace, None),
+      KeyBinding::new("alt-cmd-shift-t", StopPerformanceTrace, None),
+      KeyBinding::new("alt-cmd-o", SwitchToAlternateFile, None),
+      KeyBinding::new("alt-cmd-shift-w", TrustWorkspace, None),
+      KeyBinding::new("alt-cmd-i", DescribeCharacter, None),
+      KeyBinding::new("alt-cmd-u", InsertUnicode, None),
+      KeyBinding::new("alt-cmd-shift-u", RemoveSuspiciousUnicode, None),
+      KeyBinding::new("alt-cmd-shift-e", EscapeSuspiciousUnicode, None),
+      KeyBinding::new("cmd-=", ZoomIn, None),
+      KeyBinding::new("cmd--", ZoomOut, None),
+      KeyBinding::new("cmd-0", ZoomToFit, None),
+      KeyBinding::new("f5", StartDebugging, None),
+      KeyBinding::new("shift-f5", StopDebugging, None),
+      KeyBinding::new("f10", StepOver, None),
+      KeyBinding::new("f11", StepInto, None),
+      KeyBinding::new("shift-f11", StepOut, None),
+      KeyBinding::new("alt-cmd-shift-k", ToggleCodeCells, None),
+      KeyBinding::new("alt-cmd-enter", RunCell, None),
+      KeyBinding::new("alt-cmd-.", StopCell, None),
+      KeyBinding::new("alt-cmd-k", ClearCellOutputs, None),
+      KeyBinding::new("alt-cmd-j", JumpToWord, None),
+      KeyBinding::new("alt-cmd-shift-l", ToggleDarkMode, None),
+      KeyBinding::new("alt-cmd-shift-a", UseSystemTheme, None),
+      KeyBinding::new("alt-cmd-f", ToggleFocusMode, None),
+      KeyBinding::new("alt-z", ToggleSoftWrap, None),
+      KeyBinding::new("alt-cmd-shift-d", ToggleWordDiff, None),
+      KeyBinding::new("alt-cmd-shift-o", AddFolderToWorkspace, None),
+      KeyBinding::new("alt-cmd-shift-r", RemoveFolder, None),
+      KeyBinding::new("alt-cmd-]", NextConflict, None),
+      KeyBinding::new("alt-cmd-[", PreviousConflict, None),
+      KeyBinding::new("alt-cmd-shift-n", CopyWithLineNumbers, None),
+      KeyBinding::new("alt-cmd-shift-p", CopyPermalink, None),
+      KeyBinding::new("alt-cmd-shift-v", ToggleFormatOnPaste, None),
+      KeyBinding::new("cmd-k cmd-s", ShowKeyboardShortcuts, None),
+      KeyBinding::new("cmd-shift-p", ShowCommandPalette, None),
+      KeyBinding::new("ctrl-cmd-[", FoldBracketRegion, None),
+      KeyBinding::new("alt-cmd-m", ToggleSelectionLock, None),
+      KeyBinding::new("ctrl-x ctrl-x", SwapSelectionEnds, None),
+      KeyBinding::new("alt-cmd-n", NewScratchBuffer, None),
+      KeyBinding::new("cmd-o", Open, None),
+      KeyBinding::new("cmd-s", Save, None),
+      KeyBinding::new("cmd-shift-s", SaveAs, None),
+      KeyBinding::new("cmd-f", Find, None),
+      KeyBinding::new("cmd-shift-h", Replace, None),
+      KeyBinding::new("cmd-g", FindNext, None),
+      KeyBinding::new("cmd-shift-g", FindPrevious, None),
+      KeyBinding::new("alt-cmd-shift-j", FormatJson, None),
+      KeyBinding::new("alt-cmd-shift-m", MinifyJson, None),
+      KeyBinding::new("f6", FocusNextPanel, None),
+      KeyBinding::new("escape", FocusEditor, None),
+      KeyBinding::new("escape", Cancel, Some("Editor")),
+      KeyBinding::new("cmd-shift-e", FocusFileTree, None),
+      KeyBinding::new("ctrl-`", FocusTerminal, None),
       KeyBinding::new("home", Home, None),
       KeyBinding::new("end", End, None),
+      KeyBinding::new("shift-home", SelectHome, None),
+      KeyBinding::new("shift-end", SelectEnd, None),
       KeyBinding::new("ctrl-cmd-space", ShowCharacterPalette, None),
     ]);
 
@@ -81,18 +219,23 @@ fn main() {
           window_bounds: Some(WindowBounds::Windowed(bounds)),
           ..Default::default()
         },
-        |_, cx| {
-          cx.new(|cx| EditorExample {
-            editor: cx.new(Editor::new),
-            focus_handle: cx.focus_handle(),
-          })
-        },
+        |window, cx| cx.new(|cx| EditorExample::new(window, cx)),
       )
       .unwrap();
 
     window
       .update(cx, |view, window, cx| {
+        view.editor.update(cx, |editor, cx| {
+          editor.follow_system_appearance(window, cx);
+          // A file named on the command line replaces the empty document
+          if let Some(path) = std::env::args_os().nth(1)
+            && let Err(err) = editor.open_file(path.into(), cx)
+          {
+            eprintln!("Failed to open the file: {}", err);
+          }
+        });
         window.focus(&view.editor.focus_handle(cx), cx);
+        cx.set_menus(app_menus(view.editor.read(cx), cx));
         cx.activate(true);
       })
       .unwrap();
@@ -0,0 +1,365 @@
+//! WASM plugins
+//!
+//! A plugin is a WebAssembly module run by an interpreter that gives it no
+//! access to the system: the only functions it can import are the editor's
+//! host API below, and each call into it runs on a fuel budget with its
+//! memory capped, so a plugin stuck in a loop or allocating without end
+//! fails instead of hanging the editor. A plugin never holds the document: a
+//! command runs against a snapshot of its text and hands back the edits and
+//! decorations it made for the editor to apply, so commands can run on any
+//! thread.
+//!
+//! The host API is imported from the `editor` module. Offsets are bytes of
+//! the UTF-8 text of the snapshot, pointers point into the plugin's exported
+//! `memory`:
+//!
+//! - `register_command(name_ptr, name_len) -> id`, only while in `init`
+//! - `text_len() -> len`
+//! - `read_text(start, ptr, len) -> copied`, copying at most `len` bytes
+//! - `replace(start, end, text_ptr, text_len)`
+//! - `decorate(start, end, rgba, label_ptr, label_len)`, underlining the
+//!   range in `rgba` with the label after it unless empty
+//!
+//! Besides `memory`, a plugin exports `run_command(id)` and optionally
+//! `init()`, called once when it's loaded.
+
+use std::ops::Range;
+
+use wasmi::{
+  Caller, Config, Engine, Error, Extern, Instance, Linker, Memory, Module, Store, StoreLimits,
+  StoreLimitsBuilder,
+};
+
+/// Instructions, roughly, a call into a plugin may execute
+const FUEL_PER_CALL: u64 = 100_000_000;
+/// Largest linear memory a plugin may grow
+const MAX_MEMORY: usize = 16 * 1024 * 1024;
+
+/// Change a command made to the snapshot it ran against
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Effect {
+  Replace {
+    range: Range<usize>,
+    text: String,
+  },
+  Decorate {
+    range: Range<usize>,
+    /// `0xRRGGBBAA`
+    color: u32,
+    label: Option<String>,
+  },
+}
+
+struct Host {
+  limits: StoreLimits,
+  /// Commands can only be registered while `init` runs
+  initializing: bool,
+  commands: Vec<String>,
+  text: String,
+  effects: Vec<Effect>,
+}
+
+pub struct Plugin {
+  name: String,
+  store: Store<Host>,
+  instance: Instance,
+}
+
+fn memory(caller: &Caller<'_, Host>) -> Result<Memory, Error> {
+  match caller.get_export("memory") {
+    Some(Extern::Memory(memory)) => Ok(memory),
+    _ => Err(Error::new("the plugin exports no memory")),
+  }
+}
+
+fn read_string(caller: &Caller<'_, Host>, ptr: u32, len: u32) -> Result<String, Error> {
+  let mut bytes = vec![0; len as usize];
+  memory(caller)?
+    .read(caller, ptr as usize, &mut bytes)
+    .map_err(|_| Error::new("string out of bounds of the plugin's memory"))?;
+  String::from_utf8(bytes).map_err(|_| Error::new("string isn't UTF-8"))
+}
+
+/// `start..end` of the snapshot, checked to lie on character boundaries
+fn text_range(host: &Host, start: u32, end: u32) -> Result<Range<usize>, Error> {
+  let range = start as usize..end as usize;
+  if range.start > range.end
+    || !host.text.is_char_boundary(range.start)
+    || !host.text.is_char_boundary(range.end)
+  {
+    return Err(Error::new(format!("{:?} isn't a range of the text", range)));
+  }
+  Ok(range)
+}
+
+fn linker(engine: &Engine) -> Result<Linker<Host>, Error> {
+  let mut linker = Linker::new(engine);
+  linker.func_wrap(
+    "editor",
+    "register_command",
+    |caller: Caller<'_, Host>, ptr: u32, len: u32| -> Result<u32, Error> {
+      if !caller.data().initializing {
+        return Err(Error::new("commands can only be registered in init"));
+      }
+      let name = read_string(&caller, ptr, len)?;
+      let mut caller = caller;
+      let commands = &mut caller.data_mut().commands;
+      commands.push(name);
+      Ok(commands.len() as u32 - 1)
+    },
+  )?;
+  linker.func_wrap("editor", "text_len", |caller: Caller<'_, Host>| {
+    caller.data().text.len() as u32
+  })?;
+  linker.func_wrap(
+    "editor",
+    "read_text",
+    |mut caller: Caller<'_, Host>, start: u32, ptr: u32, len: u32| -> Result<u32, Error> {
+      let text = &caller.data().text;
+      let start = (start as usize).min(text.len());
+      let end = (start + len as usize).min(text.len());
+      let bytes = text.as_bytes()[start..end].to_vec();
+      memory(&caller)?
+        .write(&mut caller, ptr as usize, &bytes)
+        .map_err(|_| Error::new("read_text out of bounds of the plugin's memory"))?;
+      Ok(bytes.len() as u32)
+    },
+  )?;
+  linker.func_wrap(
+    "editor",
+    "replace",
+    |mut caller: Caller<'_, Host>, start: u32, end: u32, ptr: u32, len: u32| -> Result<(), Error> {
+      let range = text_range(caller.data(), start, end)?;
+      let text = read_string(&caller, ptr, len)?;
+      let effects = &mut caller.data_mut().effects;
+      // Edits are all against the snapshot, so they can't overlap
+      let overlaps = effects.iter().any(|effect| match effect {
+        Effect::Replace { range: other, .. } => {
+          range.start < other.end && other.start < range.end || range == *other
+        }
+        Effect::Decorate { .. } => false,
+      });
+      if overlaps {
+        return Err(Error::new(format!("{:?} overlaps an earlier edit", range)));
+      }
+      effects.push(Effect::Replace { range, text });
+      Ok(())
+    },
+  )?;
+  linker.func_wrap(
+    "editor",
+    "decorate",
+    |mut caller: Caller<'_, Host>,
+     start: u32,
+     end: u32,
+     color: u32,
+     ptr: u32,
+     len: u32|
+     -> Result<(), Error> {
+      let range = text_range(caller.data(), start, end)?;
+      let label = read_string(&caller, ptr, len)?;
+      caller.data_mut().effects.push(Effect::Decorate {
+        range,
+        color,
+        label: (!label.is_empty()).then_some(label),
+      });
+      Ok(())
+    },
+  )?;
+  Ok(linker)
+}
+
+impl Plugin {
+  /// Compile `wasm` and run its `init`
+  pub fn load(name: impl Into<String>, wasm: &[u8]) -> Result<Self, String> {
+    let name = name.into();
+    let fail = |err: Error| format!("Failed to load plugin {}: {}", name, err);
+
+    let mut config = Config::default();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config);
+    let module = Module::new(&engine, wasm).map_err(fail)?;
+    let mut store = Store::new(
+      &engine,
+      Host {
+        limits: StoreLimitsBuilder::new()
+          .memory_size(MAX_MEMORY)
+          .instances(1)
+          .build(),
+        initializing: true,
+        commands: Vec::new(),
+        text: String::new(),
+        effects: Vec::new(),
+      },
+    );
+    store.limiter(|host| &mut host.limits);
+    store.set_fuel(FUEL_PER_CALL).map_err(fail)?;
+    let instance = linker(&engine)
+      .and_then(|linker| linker.instantiate_and_start(&mut store, &module))
+      .map_err(fail)?;
+    if let Ok(init) = instance.get_typed_func::<(), ()>(&store, "init") {
+      init.call(&mut store, ()).map_err(fail)?;
+    }
+    store.data_mut().initializing = false;
+    Ok(Self {
+      name,
+      store,
+      instance,
+    })
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// Names of the commands the plugin registered, indexed by id
+  pub fn commands(&self) -> &[String] {
+    &self.store.data().commands
+  }
+
+  /// Run command `id` against `text`, returning what it changed in order,
+  /// or nothing if it failed
+  pub fn run(&mut self, id: usize, text: String) -> Result<Vec<Effect>, String> {
+    let fail = |err: Error| format!("Plugin {} failed: {}", self.name, err);
+    if id >= self.commands().len() {
+      return Err(format!("Plugin {} has no command {}", self.name, id));
+    }
+    let run_command = self
+      .instance
+      .get_typed_func::<u32, ()>(&self.store, "run_command")
+      .map_err(fail)?;
+    let host = self.store.data_mut();
+    host.text = text;
+    host.effects.clear();
+    self.store.set_fuel(FUEL_PER_CALL).map_err(fail)?;
+    let result = run_command.call(&mut self.store, id as u32);
+    let host = self.store.data_mut();
+    host.text = String::new();
+    let effects = std::mem::take(&mut host.effects);
+    result.map_err(fail)?;
+    Ok(effects)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Plugin with an "Upper first" command uppercasing the first byte, and a
+  /// "Mark" command underlining the first line
+  const SAMPLE: &str = r#"
+    (module
+      (import "editor" "register_command" (func $register (param i32 i32) (result i32)))
+      (import "editor" "text_len" (func $text_len (result i32)))
+      (import "editor" "read_text" (func $read (param i32 i32 i32) (result i32)))
+      (import "editor" "replace" (func $replace (param i32 i32 i32 i32)))
+      (import "editor" "decorate" (func $decorate (param i32 i32 i32 i32 i32)))
+      (memory (export "memory") 1)
+      (data (i32.const 0) "Upper firstMarkTODO")
+      (func (export "init")
+        (drop (call $register (i32.const 0) (i32.const 11)))
+        (drop (call $register (i32.const 11) (i32.const 4))))
+      (func (export "run_command") (param $id i32)
+        (local $len i32)
+        (if (i32.eqz (local.get $id))
+          (then
+            (if (i32.eqz (call $read (i32.const 0) (i32.const 100) (i32.const 1)))
+              (then (return)))
+            (i32.store8 (i32.const 100) (i32.sub (i32.load8_u (i32.const 100)) (i32.const 32)))
+            (call $replace (i32.const 0) (i32.const 1) (i32.const 100) (i32.const 1)))
+          (else
+            (local.set $len (call $text_len))
+            (call $decorate (i32.const 0) (local.get $len) (i32.const 0xff0000ff)
+              (i32.const 15) (i32.const 4))))))
+  "#;
+
+  fn load(wat: &str) -> Result<Plugin, String> {
+    Plugin::load("sample", &wat::parse_str(wat).unwrap())
+  }
+
+  #[test]
+  fn test_commands_edit_and_decorate() {
+    let mut plugin = load(SAMPLE).unwrap();
+    assert_eq!(plugin.commands(), ["Upper first", "Mark"]);
+    assert_eq!(
+      plugin.run(0, "abc".into()),
+      Ok(vec![Effect::Replace {
+        range: 0..1,
+        text: "A".into(),
+      }])
+    );
+    assert_eq!(plugin.run(0, String::new()), Ok(vec![]));
+    assert_eq!(
+      plugin.run(1, "héllo".into()),
+      Ok(vec![Effect::Decorate {
+        range: 0..6,
+        color: 0xff0000ff,
+        label: Some("TODO".into()),
+      }])
+    );
+    assert!(plugin.run(2, "abc".into()).is_err());
+  }
+
+  #[test]
+  fn test_edits_are_checked() {
+    // Splitting the é, then overlapping edits
+    let wat = r#"
+      (module
+        (import "editor" "register_command" (func $register (param i32 i32) (result i32)))
+        (import "editor" "replace" (func $replace (param i32 i32 i32 i32)))
+        (memory (export "memory") 1)
+        (func (export "init")
+          (drop (call $register (i32.const 0) (i32.const 0)))
+          (drop (call $register (i32.const 0) (i32.const 0))))
+        (func (export "run_command") (param $id i32)
+          (if (i32.eqz (local.get $id))
+            (then (call $replace (i32.const 2) (i32.const 3) (i32.const 0) (i32.const 0)))
+            (else
+              (call $replace (i32.const 0) (i32.const 3) (i32.const 0) (i32.const 0))
+              (call $replace (i32.const 1) (i32.const 1) (i32.const 0) (i32.const 0))))))
+    "#;
+    let mut plugin = load(wat).unwrap();
+    assert!(plugin.run(0, "hé".into()).is_err());
+    assert!(plugin.run(1, "hé".into()).is_err());
+  }
+
+  #[test]
+  fn test_runaway_plugins_are_stopped() {
+    let wat = r#"
+      (module
+        (import "editor" "register_command" (func $register (param i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (func (export "init") (drop (call $register (i32.const 0) (i32.const 0))))
+        (func (export "run_command") (param i32)
+          (if (i32.eqz (local.get 0))
+            (then (loop $forever (br $forever)))
+            (else (drop (memory.grow (i32.const 1024)))
+              (if (i32.eq (memory.size) (i32.const 1)) (then (unreachable)))))))
+    "#;
+    let mut plugin = load(wat).unwrap();
+    assert!(plugin.run(0, String::new()).is_err());
+    // Growing past the cap fails, so the plugin traps
+    assert!(plugin.run(1, String::new()).is_err());
+  }
+
+  #[test]
+  fn test_commands_are_registered_in_init() {
+    let wat = r#"
+      (module
+        (import "editor" "register_command" (func $register (param i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (func (export "init") (drop (call $register (i32.const 0) (i32.const 0))))
+        (func (export "run_command") (param i32)
+          (drop (call $register (i32.const 0) (i32.const 0)))))
+    "#;
+    let mut plugin = load(wat).unwrap();
+    assert!(plugin.run(0, String::new()).is_err());
+    assert_eq!(plugin.commands().len(), 1);
+    // Only the editor's host API can be imported
+    let wasi = r#"
+      (module (import "wasi_snapshot_preview1" "fd_write"
+        (func (param i32 i32 i32 i32) (result i32))))
+    "#;
+    assert!(load(wasi).is_err());
+  }
+}
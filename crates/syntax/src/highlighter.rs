@@ -1,9 +1,33 @@
-use crate::theme::TokenType;
-use std::ops::Range;
-use tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, Highlighter};
+use crate::{pairs::BracketPair, theme::TokenType};
+use std::{cmp::Reverse, collections::HashSet, ops::Range};
+use tree_sitter::{InputEdit, Language, Node, Parser, QueryCursor, StreamingIterator, Tree};
+use tree_sitter_highlight::HighlightConfiguration;
+
+/// Names the highlight queries' captures are matched against, in the order
+/// of the token types they map to
+pub const HIGHLIGHT_NAMES: &[&str] = &[
+  "keyword",
+  "keyword.control",
+  "function",
+  "function.method",
+  "function.macro",
+  "type",
+  "type.builtin",
+  "string",
+  "string.escape",
+  "number",
+  "comment",
+  "variable",
+  "property",
+  "constant",
+  "operator",
+  "punctuation.bracket",
+  "attribute",
+  "lifetime",
+];
 
 /// Highlight span with token type
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct HighlightSpan {
   pub byte_range: Range<usize>,
   pub token_type: TokenType,
@@ -14,62 +38,219 @@ pub struct LanguageConfig {
   pub name: &'static str,
   pub highlight_config: HighlightConfiguration,
   pub extensions: &'static [&'static str],
+  /// Brackets and quotes closed as they are typed
+  pub bracket_pairs: &'static [BracketPair],
+  /// Comment tokens, for toggling comments
+  pub comments: CommentTokens,
+}
+
+impl LanguageConfig {
+  /// Configuration for `grammar` highlighted with `highlight_query`, whose
+  /// captures are matched against `HIGHLIGHT_NAMES`; it has no bracket pairs
+  /// or comment tokens until they are set
+  pub fn new(
+    name: &'static str,
+    grammar: Language,
+    highlight_query: &str,
+    extensions: &'static [&'static str],
+  ) -> Result<Self, String> {
+    let mut highlight_config = HighlightConfiguration::new(grammar, name, highlight_query, "", "")
+      .map_err(|e| format!("Invalid highlight query for {}: {}", name, e))?;
+    highlight_config.configure(HIGHLIGHT_NAMES);
+    Ok(Self {
+      name,
+      highlight_config,
+      extensions,
+      bracket_pairs: &[],
+      comments: CommentTokens {
+        line: None,
+        block: None,
+      },
+    })
+  }
+}
+
+/// Tokens that start a comment in a language
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CommentTokens {
+  /// Starts a comment running to the end of the line, like `//`
+  pub line: Option<&'static str>,
+  /// Opens and closes a comment that can span lines, like `/*` and `*/`
+  pub block: Option<(&'static str, &'static str)>,
+}
+
+/// Text a highlighter reads a piece at a time, so that a rope is parsed and
+/// queried without copying it into one string
+pub trait TextChunks {
+  fn len_bytes(&self) -> usize;
+
+  /// Bytes from `byte` to the end of the piece holding it, empty at the end
+  fn chunk_at_byte(&self, byte: usize) -> &[u8];
+
+  /// Pieces holding the bytes in `range`, cut to it
+  fn chunks_in(&self, range: Range<usize>) -> impl Iterator<Item = &[u8]>;
+}
+
+impl TextChunks for str {
+  fn len_bytes(&self) -> usize {
+    self.len()
+  }
+
+  fn chunk_at_byte(&self, byte: usize) -> &[u8] {
+    self.as_bytes().get(byte..).unwrap_or_default()
+  }
+
+  fn chunks_in(&self, range: Range<usize>) -> impl Iterator<Item = &[u8]> {
+    std::iter::once(&self.as_bytes()[range])
+  }
 }
 
 /// Syntax highlighting manager
+///
+/// It keeps the tree of the text it last parsed. Edits made to the text since
+/// are applied to the tree with `edit`, so the next `update` reparses only
+/// what they touched and reports where the syntax changed.
+#[derive(Clone)]
 pub struct SyntaxHighlighter {
-  highlighter: Highlighter,
   pub config: &'static LanguageConfig,
+  tree: Option<Tree>,
 }
 
 impl SyntaxHighlighter {
   pub fn new(config: &'static LanguageConfig) -> Self {
-    Self {
-      highlighter: Highlighter::new(),
-      config,
-    }
+    Self { config, tree: None }
   }
 
   /// Highlight complete text
   /// Returns Ok(highlights) or Err if parsing fails
   pub fn highlight_text(&mut self, text: &str) -> Result<Vec<HighlightSpan>, String> {
-    let events = self
-      .highlighter
-      .highlight(&self.config.highlight_config, text.as_bytes(), None, |_| {
-        None
-      })
-      .map_err(|e| format!("Highlight failed: {}", e))?;
-
-    let mut highlights = Vec::new();
-    let mut highlight_stack = Vec::new();
-
-    for event in events {
-      match event.map_err(|e| format!("Event error: {}", e))? {
-        HighlightEvent::Source { start, end } => {
-          if let Some(&highlight_idx) = highlight_stack.last() {
-            highlights.push(HighlightSpan {
-              byte_range: start..end,
-              token_type: map_highlight_index_to_token_type(highlight_idx),
-            });
-          }
-        }
-        HighlightEvent::HighlightStart(idx) => {
-          highlight_stack.push(idx.0);
-        }
-        HighlightEvent::HighlightEnd => {
-          highlight_stack.pop();
-        }
+    self.tree = None;
+    self.update(text)?;
+    Ok(self.highlight_range(text, 0..text.len()))
+  }
+
+  /// Apply an edit made to the text since it was last parsed
+  pub fn edit(&mut self, edit: &InputEdit) {
+    if let Some(tree) = &mut self.tree {
+      tree.edit(edit);
+    }
+  }
+
+  /// Parse `text`, reusing the tree of the last parse as edited since, and
+  /// return the byte ranges whose syntax changed; a first parse changes all
+  pub fn update(&mut self, text: &(impl TextChunks + ?Sized)) -> Result<Vec<Range<usize>>, String> {
+    let mut parser = Parser::new();
+    parser
+      .set_language(&self.config.highlight_config.language)
+      .map_err(|e| format!("Parser setup failed: {}", e))?;
+    let tree = parser
+      .parse_with_options(
+        &mut |byte, _| text.chunk_at_byte(byte),
+        self.tree.as_ref(),
+        None,
+      )
+      .ok_or_else(|| "Parse failed".to_string())?;
+    let changed = match &self.tree {
+      Some(old) => old
+        .changed_ranges(&tree)
+        .map(|range| range.start_byte..range.end_byte)
+        .collect(),
+      None => vec![0..text.len_bytes()],
+    };
+    self.tree = Some(tree);
+    Ok(changed)
+  }
+
+  /// Highlights of the bytes of `text` in `range`, which the last `update`
+  /// parsed; spans crossing the range are cut at its ends
+  pub fn highlight_range(
+    &self,
+    text: &(impl TextChunks + ?Sized),
+    range: Range<usize>,
+  ) -> Vec<HighlightSpan> {
+    let Some(tree) = &self.tree else {
+      return Vec::new();
+    };
+    let query = &self.config.highlight_config.query;
+    let highlights: Vec<Option<usize>> = query
+      .capture_names()
+      .iter()
+      .map(|name| recognized_name(HIGHLIGHT_NAMES, name))
+      .collect();
+
+    // Highlighted nodes, each taking the first pattern that captures it
+    let mut seen = HashSet::new();
+    let mut nodes = Vec::new();
+    let mut cursor = QueryCursor::new();
+    cursor.set_byte_range(range.clone());
+    let mut captures = cursor.captures(query, tree.root_node(), |node: Node| {
+      text.chunks_in(node.byte_range())
+    });
+    while let Some((found, index)) = captures.next() {
+      let capture = found.captures[*index];
+      let Some(highlight) = highlights[capture.index as usize] else {
+        continue;
+      };
+      let bytes = capture.node.byte_range();
+      if seen.insert(bytes.clone()) {
+        nodes.push((bytes, highlight));
       }
     }
 
-    Ok(highlights)
+    // Nodes nest, so an inner one is highlighted over the one around it
+    nodes.sort_by_key(|(bytes, _)| (bytes.start, Reverse(bytes.end)));
+    let mut spans = Vec::new();
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut at = range.start;
+    for (bytes, highlight) in nodes {
+      push_spans(&mut spans, &mut open, &mut at, bytes.start);
+      open.push((bytes.end, highlight));
+    }
+    push_spans(&mut spans, &mut open, &mut at, range.end);
+    spans
+  }
+}
+
+/// Index of the name in `names` that best matches a capture: the one with
+/// the most parts, all of which are parts of the capture name
+fn recognized_name(names: &[&str], capture: &str) -> Option<usize> {
+  let parts: Vec<&str> = capture.split('.').collect();
+  names
+    .iter()
+    .enumerate()
+    .filter(|(_, name)| name.split('.').all(|part| parts.contains(&part)))
+    .max_by_key(|(index, name)| (name.split('.').count(), Reverse(*index)))
+    .map(|(index, _)| index)
+}
+
+/// Push spans for the bytes from `at` to `end` under the innermost of the
+/// `open` nodes, as their end and highlight, moving `at` to `end`
+fn push_spans(
+  spans: &mut Vec<HighlightSpan>,
+  open: &mut Vec<(usize, usize)>,
+  at: &mut usize,
+  end: usize,
+) {
+  while *at < end {
+    while open.last().is_some_and(|(node_end, _)| *node_end <= *at) {
+      open.pop();
+    }
+    let Some(&(node_end, highlight)) = open.last() else {
+      *at = end;
+      return;
+    };
+    let span_end = node_end.min(end);
+    spans.push(HighlightSpan {
+      byte_range: *at..span_end,
+      token_type: map_highlight_index_to_token_type(highlight),
+    });
+    *at = span_end;
   }
 }
 
 /// Map highlight index to TokenType
 fn map_highlight_index_to_token_type(idx: usize) -> TokenType {
-  // Indices correspond to the order in highlight_names of HighlightConfiguration
-  // See languages/rust.rs for the list of names
+  // Indices correspond to the order of HIGHLIGHT_NAMES
   match idx {
     0 => TokenType::Keyword,             // keyword
     1 => TokenType::KeywordControl,      // keyword.control
@@ -97,6 +278,7 @@ fn map_highlight_index_to_token_type(idx: usize) -> TokenType {
 mod tests {
   use super::*;
   use crate::languages::rust::RUST_CONFIG;
+  use tree_sitter::Point;
 
   #[test]
   fn test_highlight_simple_rust() {
@@ -169,6 +351,94 @@ mod tests {
     assert!(result.is_ok() || result.is_err());
   }
 
+  #[test]
+  fn test_update_after_edit() {
+    let mut highlighter = SyntaxHighlighter::new(&RUST_CONFIG);
+    let text = "fn a() {}\nfn b() {}\n";
+    assert_eq!(highlighter.update(text).unwrap(), vec![0..text.len()]);
+
+    let edited = "/* x */ fn a() {}\nfn b() {}\n";
+    highlighter.edit(&InputEdit {
+      start_byte: 0,
+      old_end_byte: 0,
+      new_end_byte: 8,
+      start_position: Point::new(0, 0),
+      old_end_position: Point::new(0, 0),
+      new_end_position: Point::new(0, 8),
+    });
+    assert!(!highlighter.update(edited).unwrap().is_empty());
+
+    let mut fresh = SyntaxHighlighter::new(&RUST_CONFIG);
+    fresh.update(edited).unwrap();
+    assert_eq!(
+      highlighter.highlight_range(edited, 0..edited.len()),
+      fresh.highlight_range(edited, 0..edited.len())
+    );
+  }
+
+  #[test]
+  fn test_highlight_range() {
+    let mut highlighter = SyntaxHighlighter::new(&RUST_CONFIG);
+    let text = "let s = \"hello\";";
+    let all = highlighter.highlight_text(text).unwrap();
+    let string = all
+      .iter()
+      .find(|h| h.token_type == TokenType::String)
+      .unwrap();
+    assert_eq!(string.byte_range, 8..15);
+
+    // Cut at the ends of the range
+    let spans = highlighter.highlight_range(text, 10..12);
+    assert_eq!(
+      spans,
+      vec![HighlightSpan {
+        byte_range: 10..12,
+        token_type: TokenType::String,
+      }]
+    );
+  }
+
+  /// Text in pieces of a few bytes
+  struct Pieces(String);
+
+  impl TextChunks for Pieces {
+    fn len_bytes(&self) -> usize {
+      self.0.len()
+    }
+
+    fn chunk_at_byte(&self, byte: usize) -> &[u8] {
+      let bytes = self.0.as_bytes();
+      &bytes[byte.min(bytes.len())..(byte + 3).min(bytes.len())]
+    }
+
+    fn chunks_in(&self, range: Range<usize>) -> impl Iterator<Item = &[u8]> {
+      self.0.as_bytes()[range].chunks(3)
+    }
+  }
+
+  #[test]
+  fn test_highlight_chunks() {
+    let text = "fn main() {\n  let s = \"hello\"; // greet\n}\n";
+    let mut highlighter = SyntaxHighlighter::new(&RUST_CONFIG);
+    let expected = highlighter.highlight_text(text).unwrap();
+
+    let pieces = Pieces(text.to_string());
+    let mut highlighter = SyntaxHighlighter::new(&RUST_CONFIG);
+    highlighter.update(&pieces).unwrap();
+    assert_eq!(
+      highlighter.highlight_range(&pieces, 0..text.len()),
+      expected
+    );
+  }
+
+  #[test]
+  fn test_recognized_name() {
+    let names = ["function", "function.method", "type"];
+    assert_eq!(recognized_name(&names, "function.method.call"), Some(1));
+    assert_eq!(recognized_name(&names, "function.builtin"), Some(0));
+    assert_eq!(recognized_name(&names, "variable"), None);
+  }
+
   #[test]
   fn test_map_highlight_indices() {
     // Verify that all indices map correctly
@@ -0,0 +1,57 @@
+use crate::{
+  highlighter::{CommentTokens, LanguageConfig},
+  pairs::BracketPair,
+};
+use once_cell::sync::Lazy;
+
+/// Nodes where brackets and quotes are text rather than code
+const NOT_CODE: &[&str] = &[
+  "interpreted_string_literal",
+  "raw_string_literal",
+  "rune_literal",
+  "comment",
+];
+
+const BRACKET_PAIRS: &[BracketPair] = &[
+  BracketPair {
+    open: "(",
+    close: ")",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "[",
+    close: "]",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "{",
+    close: "}",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "\"",
+    close: "\"",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "`",
+    close: "`",
+    disabled_in: NOT_CODE,
+  },
+];
+
+pub static GO_CONFIG: Lazy<LanguageConfig> = Lazy::new(|| {
+  let mut config = LanguageConfig::new(
+    "go",
+    tree_sitter_go::LANGUAGE.into(),
+    include_str!("../tree-sitter-queries/go-highlights.scm"),
+    &["go"],
+  )
+  .expect("Failed to create Go highlight config");
+  config.bracket_pairs = BRACKET_PAIRS;
+  config.comments = CommentTokens {
+    line: Some("//"),
+    block: Some(("/*", "*/")),
+  };
+  config
+});
@@ -0,0 +1,42 @@
+use crate::{
+  highlighter::{CommentTokens, LanguageConfig},
+  pairs::BracketPair,
+};
+use once_cell::sync::Lazy;
+
+/// Nodes where brackets and quotes are text rather than code
+const NOT_CODE: &[&str] = &["string"];
+
+const BRACKET_PAIRS: &[BracketPair] = &[
+  BracketPair {
+    open: "[",
+    close: "]",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "{",
+    close: "}",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "\"",
+    close: "\"",
+    disabled_in: NOT_CODE,
+  },
+];
+
+pub static JSON_CONFIG: Lazy<LanguageConfig> = Lazy::new(|| {
+  let mut config = LanguageConfig::new(
+    "json",
+    tree_sitter_json::LANGUAGE.into(),
+    include_str!("../tree-sitter-queries/json-highlights.scm"),
+    &["json"],
+  )
+  .expect("Failed to create JSON highlight config");
+  config.bracket_pairs = BRACKET_PAIRS;
+  config.comments = CommentTokens {
+    line: None,
+    block: None,
+  };
+  config
+});
@@ -0,0 +1,42 @@
+use crate::{
+  highlighter::{CommentTokens, LanguageConfig},
+  pairs::BracketPair,
+};
+use once_cell::sync::Lazy;
+
+/// Nodes where brackets are text rather than markup
+const NOT_CODE: &[&str] = &["fenced_code_block", "indented_code_block"];
+
+const BRACKET_PAIRS: &[BracketPair] = &[
+  BracketPair {
+    open: "(",
+    close: ")",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "[",
+    close: "]",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "`",
+    close: "`",
+    disabled_in: NOT_CODE,
+  },
+];
+
+pub static MARKDOWN_CONFIG: Lazy<LanguageConfig> = Lazy::new(|| {
+  let mut config = LanguageConfig::new(
+    "markdown",
+    tree_sitter_md::LANGUAGE.into(),
+    include_str!("../tree-sitter-queries/markdown-highlights.scm"),
+    &["md"],
+  )
+  .expect("Failed to create Markdown highlight config");
+  config.bracket_pairs = BRACKET_PAIRS;
+  config.comments = CommentTokens {
+    line: None,
+    block: Some(("<!--", "-->")),
+  };
+  config
+});
@@ -1,19 +1,84 @@
+pub mod go;
+pub mod json;
+pub mod markdown;
+pub mod python;
 pub mod rust;
+pub mod toml;
 pub mod typescript;
 
-use crate::highlighter::LanguageConfig;
+use crate::{highlighter::LanguageConfig, registry::LanguageRegistry};
 
+/// Language of files with `extension`, from the global registry
 pub fn detect_language_config(extension: &str) -> Option<&'static LanguageConfig> {
-  match extension {
-    "rs" => Some(&*rust::RUST_CONFIG),
-    "ts" | "tsx" | "js" | "jsx" => Some(&*typescript::TYPESCRIPT_CONFIG),
-    _ => None,
+  LanguageRegistry::global().by_extension(extension)
+}
+
+/// Language named by the first line of a file without extension: a shebang's
+/// interpreter, or a mode in an Emacs (`-*- mode: rust -*-`) or Vim
+/// (`vim: ft=rust`, `vim: set filetype=rust:`) modeline
+pub fn detect_from_first_line(line: &str) -> Option<&'static LanguageConfig> {
+  let name = shebang_interpreter(line)
+    .or_else(|| emacs_mode(line))
+    .or_else(|| vim_filetype(line))?;
+  let extension = match name.to_ascii_lowercase().as_str() {
+    "rust" | "rs" | "rust-script" | "cargo" => "rs",
+    "typescript" | "ts" | "javascript" | "js" | "node" | "nodejs" | "deno" | "bun" | "ts-node"
+    | "tsx" => "ts",
+    "python" | "py" => "py",
+    "go" | "golang" => "go",
+    "json" => "json",
+    "toml" => "toml",
+    "markdown" | "md" => "md",
+    // Registered languages are named by their own name
+    _ => return LanguageRegistry::global().by_name(name),
+  };
+  detect_language_config(extension)
+}
+
+/// Program of `#!/usr/bin/node`, or the one `env` runs, skipping its options
+fn shebang_interpreter(line: &str) -> Option<&str> {
+  let mut words = line.strip_prefix("#!")?.split_whitespace();
+  let program = words.next()?.rsplit('/').next()?;
+  let program = if program == "env" {
+    words.find(|word| !word.starts_with('-'))?
+  } else {
+    program
+  };
+  // Versioned interpreters like `node18`
+  Some(program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.'))
+}
+
+/// `rust` of `-*- mode: rust -*-`, or of `-*- rust -*-`
+fn emacs_mode(line: &str) -> Option<&str> {
+  let start = line.find("-*-")? + 3;
+  let end = start + line[start..].find("-*-")?;
+  let vars = line[start..end].trim();
+  if !vars.contains(':') {
+    return Some(vars);
   }
+  vars.split(';').find_map(|var| {
+    let (key, value) = var.split_once(':')?;
+    (key.trim().eq_ignore_ascii_case("mode")).then(|| value.trim())
+  })
+}
+
+/// `rust` of `vim: ft=rust` or `vim: set filetype=rust:`
+fn vim_filetype(line: &str) -> Option<&str> {
+  let start = line.find("vim:").or_else(|| line.find("vi:"))?;
+  line[start..]
+    .split([' ', ':', '\t'])
+    .find_map(|option| {
+      option
+        .strip_prefix("ft=")
+        .or_else(|| option.strip_prefix("filetype="))
+    })
+    .filter(|name| !name.is_empty())
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::{SyntaxHighlighter, TokenType};
 
   #[test]
   fn test_detect_rust() {
@@ -38,6 +103,26 @@ mod tests {
     assert!(detect_language_config("").is_none());
   }
 
+  #[test]
+  fn test_detect_from_first_line() {
+    let name = |line| detect_from_first_line(line).map(|config| config.name);
+    assert_eq!(name("#!/usr/bin/env node"), Some("typescript"));
+    assert_eq!(name("#!/usr/bin/env -S deno run"), Some("typescript"));
+    assert_eq!(
+      name("#!/usr/local/bin/node18 --harmony"),
+      Some("typescript")
+    );
+    assert_eq!(name("#!/usr/bin/env rust-script"), Some("rust"));
+    assert_eq!(name("// -*- mode: rust; tab-width: 4 -*-"), Some("rust"));
+    assert_eq!(name("// -*- javascript -*-"), Some("typescript"));
+    assert_eq!(name("/* vim: set filetype=rust: */"), Some("rust"));
+    assert_eq!(name("// vim: ts=2 ft=javascript"), Some("typescript"));
+    assert_eq!(name("#!/usr/bin/env python3"), Some("python"));
+    assert_eq!(name("<!-- vim: ft=markdown -->"), Some("markdown"));
+    assert_eq!(name("#!/usr/bin/env ruby"), None);
+    assert_eq!(name("fn main() {}"), None);
+  }
+
   #[test]
   fn test_rust_config_has_correct_name() {
     let config = detect_language_config("rs").unwrap();
@@ -49,4 +134,80 @@ mod tests {
     let config = detect_language_config("ts").unwrap();
     assert_eq!(config.name, "typescript");
   }
+
+  #[test]
+  fn test_detect_more_languages() {
+    let name = |extension| detect_language_config(extension).map(|config| config.name);
+    assert_eq!(name("py"), Some("python"));
+    assert_eq!(name("go"), Some("go"));
+    assert_eq!(name("json"), Some("json"));
+    assert_eq!(name("toml"), Some("toml"));
+    assert_eq!(name("md"), Some("markdown"));
+  }
+
+  #[test]
+  fn test_more_languages_highlight() {
+    let cases = [
+      (
+        "py",
+        "def f():\n    return 'hi'  # note\n",
+        &[
+          TokenType::Keyword,
+          TokenType::KeywordControl,
+          TokenType::String,
+          TokenType::Comment,
+        ][..],
+      ),
+      (
+        "go",
+        "package main\n\nfunc f() string { return \"hi\" } // note\n",
+        &[
+          TokenType::Keyword,
+          TokenType::KeywordControl,
+          TokenType::String,
+          TokenType::Comment,
+        ],
+      ),
+      (
+        "json",
+        "{\"a\": \"hi\", \"b\": true} // note\n",
+        &[
+          TokenType::Property,
+          TokenType::String,
+          TokenType::Constant,
+          TokenType::Comment,
+        ],
+      ),
+      (
+        "toml",
+        "# note\n[table]\nkey = \"hi\"\nflag = true\n",
+        &[
+          TokenType::Type,
+          TokenType::Property,
+          TokenType::String,
+          TokenType::Comment,
+        ],
+      ),
+      (
+        "md",
+        "# Title\n\n<!-- note -->\n\n```rust\ncode\n```\n",
+        &[
+          TokenType::Keyword,
+          TokenType::Type,
+          TokenType::String,
+          TokenType::Comment,
+        ],
+      ),
+    ];
+    for (extension, text, expected) in cases {
+      let config = detect_language_config(extension).unwrap();
+      let spans = SyntaxHighlighter::new(config).highlight_text(text).unwrap();
+      for token_type in expected {
+        assert!(
+          spans.iter().any(|span| span.token_type == *token_type),
+          "no {token_type:?} in {extension}"
+        );
+      }
+    }
+  }
 }
@@ -0,0 +1,52 @@
+use crate::{
+  highlighter::{CommentTokens, LanguageConfig},
+  pairs::BracketPair,
+};
+use once_cell::sync::Lazy;
+
+/// Nodes where brackets and quotes are text rather than code
+const NOT_CODE: &[&str] = &["string", "comment"];
+
+const BRACKET_PAIRS: &[BracketPair] = &[
+  BracketPair {
+    open: "(",
+    close: ")",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "[",
+    close: "]",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "{",
+    close: "}",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "\"",
+    close: "\"",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "'",
+    close: "'",
+    disabled_in: NOT_CODE,
+  },
+];
+
+pub static PYTHON_CONFIG: Lazy<LanguageConfig> = Lazy::new(|| {
+  let mut config = LanguageConfig::new(
+    "python",
+    tree_sitter_python::LANGUAGE.into(),
+    include_str!("../tree-sitter-queries/python-highlights.scm"),
+    &["py"],
+  )
+  .expect("Failed to create Python highlight config");
+  config.bracket_pairs = BRACKET_PAIRS;
+  config.comments = CommentTokens {
+    line: Some("#"),
+    block: None,
+  };
+  config
+});
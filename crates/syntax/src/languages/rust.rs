@@ -1,46 +1,54 @@
-use crate::highlighter::LanguageConfig;
+use crate::{
+  highlighter::{CommentTokens, LanguageConfig},
+  pairs::BracketPair,
+};
 use once_cell::sync::Lazy;
-use tree_sitter_highlight::HighlightConfiguration;
 
-pub static RUST_CONFIG: Lazy<LanguageConfig> = Lazy::new(|| {
-  let language = tree_sitter_rust::LANGUAGE.into();
-  let query_source = include_str!("../tree-sitter-queries/rust-highlights.scm");
+/// Nodes where brackets and quotes are text rather than code
+const NOT_CODE: &[&str] = &[
+  "string_literal",
+  "raw_string_literal",
+  "char_literal",
+  "line_comment",
+  "block_comment",
+];
 
-  let highlight_names = vec![
-    "keyword",
-    "keyword.control",
-    "function",
-    "function.method",
-    "function.macro",
-    "type",
-    "type.builtin",
-    "string",
-    "string.escape",
-    "number",
-    "comment",
-    "variable",
-    "property",
-    "constant",
-    "operator",
-    "punctuation.bracket",
-    "attribute",
-    "lifetime",
-  ];
+/// No `'` pair, since it starts lifetimes as often as chars
+const BRACKET_PAIRS: &[BracketPair] = &[
+  BracketPair {
+    open: "(",
+    close: ")",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "[",
+    close: "]",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "{",
+    close: "}",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "\"",
+    close: "\"",
+    disabled_in: NOT_CODE,
+  },
+];
 
-  let mut config = HighlightConfiguration::new(
-    language,
+pub static RUST_CONFIG: Lazy<LanguageConfig> = Lazy::new(|| {
+  let mut config = LanguageConfig::new(
     "rust",
-    query_source,
-    "", // injections query
-    "", // locals query
+    tree_sitter_rust::LANGUAGE.into(),
+    include_str!("../tree-sitter-queries/rust-highlights.scm"),
+    &["rs"],
   )
   .expect("Failed to create Rust highlight config");
-
-  config.configure(&highlight_names);
-
-  LanguageConfig {
-    name: "rust",
-    highlight_config: config,
-    extensions: &["rs"],
-  }
+  config.bracket_pairs = BRACKET_PAIRS;
+  config.comments = CommentTokens {
+    line: Some("//"),
+    block: Some(("/*", "*/")),
+  };
+  config
 });
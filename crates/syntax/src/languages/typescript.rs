@@ -1,37 +1,57 @@
-use crate::highlighter::LanguageConfig;
+use crate::{
+  highlighter::{CommentTokens, LanguageConfig},
+  pairs::BracketPair,
+};
 use once_cell::sync::Lazy;
-use tree_sitter_highlight::HighlightConfiguration;
 
-pub static TYPESCRIPT_CONFIG: Lazy<LanguageConfig> = Lazy::new(|| {
-  let language = tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into();
-  let query_source = include_str!("../tree-sitter-queries/typescript-highlights.scm");
-
-  let highlight_names = vec![
-    "keyword",
-    "keyword.control",
-    "function",
-    "function.method",
-    "type",
-    "type.builtin",
-    "string",
-    "string.escape",
-    "number",
-    "comment",
-    "variable",
-    "property",
-    "constant",
-    "operator",
-    "punctuation.bracket",
-  ];
+/// Nodes where brackets and quotes are text rather than code
+const NOT_CODE: &[&str] = &["string", "template_string", "comment", "regex"];
 
-  let mut config = HighlightConfiguration::new(language, "typescript", query_source, "", "")
-    .expect("Failed to create TypeScript highlight config");
+const BRACKET_PAIRS: &[BracketPair] = &[
+  BracketPair {
+    open: "(",
+    close: ")",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "[",
+    close: "]",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "{",
+    close: "}",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "\"",
+    close: "\"",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "'",
+    close: "'",
+    disabled_in: NOT_CODE,
+  },
+  BracketPair {
+    open: "`",
+    close: "`",
+    disabled_in: NOT_CODE,
+  },
+];
 
-  config.configure(&highlight_names);
-
-  LanguageConfig {
-    name: "typescript",
-    highlight_config: config,
-    extensions: &["ts", "tsx", "js", "jsx"],
-  }
+pub static TYPESCRIPT_CONFIG: Lazy<LanguageConfig> = Lazy::new(|| {
+  let mut config = LanguageConfig::new(
+    "typescript",
+    tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+    include_str!("../tree-sitter-queries/typescript-highlights.scm"),
+    &["ts", "tsx", "js", "jsx"],
+  )
+  .expect("Failed to create TypeScript highlight config");
+  config.bracket_pairs = BRACKET_PAIRS;
+  config.comments = CommentTokens {
+    line: Some("//"),
+    block: Some(("/*", "*/")),
+  };
+  config
 });
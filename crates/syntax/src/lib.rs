@@ -1,6 +1,16 @@
 mod highlighter;
 pub mod languages;
+pub mod pairs;
+mod registry;
+pub mod runnables;
+pub mod scopes;
 mod theme;
+mod theme_file;
 
-pub use highlighter::{HighlightSpan, LanguageConfig, SyntaxHighlighter};
+pub use highlighter::{
+  CommentTokens, HIGHLIGHT_NAMES, HighlightSpan, LanguageConfig, SyntaxHighlighter, TextChunks,
+};
+pub use pairs::BracketPair;
+pub use registry::LanguageRegistry;
 pub use theme::{SyntaxTheme, Theme, TokenType};
+pub use tree_sitter::{InputEdit, Language, Point};
@@ -0,0 +1,130 @@
+//! Brackets and quotes that are closed as they are opened
+//!
+//! Each language lists its pairs in its `LanguageConfig`, with the syntax
+//! nodes inside which a pair isn't closed, like quotes in comments. Rust
+//! leaves `'` out for its lifetimes, TypeScript adds backticks.
+
+use tree_sitter::{Language, Parser};
+
+use crate::highlighter::LanguageConfig;
+
+/// Text closed by inserting its closing text after the cursor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BracketPair {
+  pub open: &'static str,
+  pub close: &'static str,
+  /// Kinds of syntax nodes inside which typing `open` inserts only itself
+  pub disabled_in: &'static [&'static str],
+}
+
+/// The pair whose opening text ends `before` followed by `typed`, the
+/// longest if several do, so `"""` wins over `"`
+fn matching_pair<'a>(
+  pairs: &'a [BracketPair],
+  before: &str,
+  typed: &str,
+) -> Option<&'a BracketPair> {
+  pairs
+    .iter()
+    .filter(|pair| {
+      pair
+        .open
+        .strip_suffix(typed)
+        .is_some_and(|rest| before.ends_with(rest))
+    })
+    .max_by_key(|pair| pair.open.len())
+}
+
+/// Kinds of the syntax nodes strictly containing byte `offset` of `text`,
+/// innermost first
+fn enclosing_kinds(grammar: &Language, text: &str, offset: usize) -> Vec<&'static str> {
+  let mut parser = Parser::new();
+  if parser.set_language(grammar).is_err() {
+    return Vec::new();
+  }
+  let Some(tree) = parser.parse(text, None) else {
+    return Vec::new();
+  };
+
+  let mut kinds = Vec::new();
+  let mut node = tree.root_node().descendant_for_byte_range(offset, offset);
+  while let Some(current) = node {
+    // A node ending at the cursor, like a string just closed, is left
+    if current.start_byte() < offset && offset < current.end_byte() {
+      kinds.push(current.kind());
+    }
+    node = current.parent();
+  }
+  kinds
+}
+
+/// Text to insert after the cursor when `typed` is inserted at byte
+/// `offset` of `text`, if it opens one of the language's pairs there
+pub fn closing_pair(
+  config: &LanguageConfig,
+  text: &str,
+  offset: usize,
+  typed: &str,
+) -> Option<&'static str> {
+  let pair = matching_pair(config.bracket_pairs, &text[..offset], typed)?;
+  if !pair.disabled_in.is_empty() {
+    let kinds = enclosing_kinds(&config.highlight_config.language, text, offset);
+    if kinds.iter().any(|kind| pair.disabled_in.contains(kind)) {
+      return None;
+    }
+  }
+  Some(pair.close)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::languages::detect_language_config;
+
+  #[test]
+  fn test_matching_pair() {
+    let pairs = [
+      BracketPair {
+        open: "\"",
+        close: "\"",
+        disabled_in: &[],
+      },
+      BracketPair {
+        open: "\"\"\"",
+        close: "\"\"\"",
+        disabled_in: &[],
+      },
+    ];
+    assert_eq!(matching_pair(&pairs, "x = ", "\"").unwrap().open, "\"");
+    assert_eq!(
+      matching_pair(&pairs, "x = \"\"", "\"").unwrap().open,
+      "\"\"\""
+    );
+    assert!(matching_pair(&pairs, "x = ", "(").is_none());
+  }
+
+  #[test]
+  fn test_rust_pairs() {
+    let rust = detect_language_config("rs").unwrap();
+    let text = "fn f<'a>(s: &'a str) { let x = \"ab\"; // c\n}";
+    let at = |needle: &str| text.find(needle).unwrap();
+
+    assert_eq!(closing_pair(rust, text, at(" let"), "("), Some(")"));
+    assert_eq!(closing_pair(rust, text, at(" let"), "\""), Some("\""));
+    // Lifetimes make a quote pair more annoying than useful
+    assert_eq!(closing_pair(rust, text, at("a>"), "'"), None);
+    assert_eq!(closing_pair(rust, text, at("b\""), "("), None);
+    assert_eq!(closing_pair(rust, text, at(" c"), "\""), None);
+    // Right after a string's closing quote is code again
+    assert_eq!(closing_pair(rust, text, at(";"), "["), Some("]"));
+  }
+
+  #[test]
+  fn test_typescript_pairs() {
+    let typescript = detect_language_config("ts").unwrap();
+    let text = "const s = 'a';\n";
+    assert_eq!(closing_pair(typescript, text, 10, "`"), Some("`"));
+    assert_eq!(closing_pair(typescript, text, 10, "'"), Some("'"));
+    assert_eq!(closing_pair(typescript, text, 12, "{"), None);
+  }
+}
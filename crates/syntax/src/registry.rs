@@ -0,0 +1,154 @@
+//! Languages the editor knows
+//!
+//! The built-in languages are in the global registry from its first use, and
+//! more can be registered at runtime, like a downstream crate's own
+//! tree-sitter grammar. A registered language is leaked so that it lives as
+//! long as the built-in ones, which highlighters hold on to.
+
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use once_cell::sync::Lazy;
+
+use crate::{
+  highlighter::LanguageConfig,
+  languages::{go, json, markdown, python, rust, toml, typescript},
+};
+
+static GLOBAL: Lazy<RwLock<LanguageRegistry>> =
+  Lazy::new(|| RwLock::new(LanguageRegistry::with_builtin()));
+
+/// Languages by name and file extension
+#[derive(Clone, Default)]
+pub struct LanguageRegistry {
+  languages: Vec<&'static LanguageConfig>,
+}
+
+impl LanguageRegistry {
+  /// A registry without languages
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// A registry with the built-in languages
+  pub fn with_builtin() -> Self {
+    let mut registry = Self::new();
+    registry.register_static(&rust::RUST_CONFIG);
+    registry.register_static(&typescript::TYPESCRIPT_CONFIG);
+    registry.register_static(&python::PYTHON_CONFIG);
+    registry.register_static(&go::GO_CONFIG);
+    registry.register_static(&json::JSON_CONFIG);
+    registry.register_static(&toml::TOML_CONFIG);
+    registry.register_static(&markdown::MARKDOWN_CONFIG);
+    registry
+  }
+
+  /// The registry documents detect their language from
+  pub fn global() -> RwLockReadGuard<'static, LanguageRegistry> {
+    GLOBAL.read().unwrap_or_else(PoisonError::into_inner)
+  }
+
+  /// The global registry, to register languages in
+  pub fn global_mut() -> RwLockWriteGuard<'static, LanguageRegistry> {
+    GLOBAL.write().unwrap_or_else(PoisonError::into_inner)
+  }
+
+  /// Add `config` in place of a language with the same name
+  pub fn register(&mut self, config: LanguageConfig) -> &'static LanguageConfig {
+    let config = Box::leak(Box::new(config));
+    self.register_static(config);
+    config
+  }
+
+  /// Add a language that already lives for the whole program
+  pub fn register_static(&mut self, config: &'static LanguageConfig) {
+    self
+      .languages
+      .retain(|language| language.name != config.name);
+    self.languages.push(config);
+  }
+
+  pub fn by_name(&self, name: &str) -> Option<&'static LanguageConfig> {
+    self
+      .languages
+      .iter()
+      .copied()
+      .find(|language| language.name.eq_ignore_ascii_case(name))
+  }
+
+  /// Language of files with `extension`, the last registered if several
+  /// claim it
+  pub fn by_extension(&self, extension: &str) -> Option<&'static LanguageConfig> {
+    self
+      .languages
+      .iter()
+      .rev()
+      .copied()
+      .find(|language| language.extensions.contains(&extension))
+  }
+
+  /// Registered languages, in the order they were registered
+  pub fn languages(&self) -> impl Iterator<Item = &'static LanguageConfig> + '_ {
+    self.languages.iter().copied()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{SyntaxHighlighter, TokenType, languages::detect_language_config};
+
+  fn comments_only(name: &'static str, extensions: &'static [&'static str]) -> LanguageConfig {
+    LanguageConfig::new(
+      name,
+      tree_sitter_rust::LANGUAGE.into(),
+      "(line_comment) @comment",
+      extensions,
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn test_register_language() {
+    let mut registry = LanguageRegistry::with_builtin();
+    assert!(registry.by_extension("rsc").is_none());
+
+    let config = registry.register(comments_only("rust-comments", &["rsc"]));
+    assert_eq!(
+      registry.by_extension("rsc").map(|c| c.name),
+      Some(config.name)
+    );
+    assert!(registry.by_name("Rust-Comments").is_some());
+
+    let spans = SyntaxHighlighter::new(config)
+      .highlight_text("fn f() {} // note")
+      .unwrap();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].token_type, TokenType::Comment);
+
+    // Registering the same name again replaces the language
+    registry.register(comments_only("rust-comments", &["rsd"]));
+    assert!(registry.by_extension("rsc").is_none());
+    assert_eq!(registry.languages().count(), 8);
+  }
+
+  #[test]
+  fn test_detect_registered_language() {
+    assert!(detect_language_config("rsx-test").is_none());
+    LanguageRegistry::global_mut().register(comments_only("rsx-test", &["rsx-test"]));
+    assert_eq!(
+      detect_language_config("rsx-test").map(|config| config.name),
+      Some("rsx-test")
+    );
+  }
+
+  #[test]
+  fn test_invalid_query() {
+    let result = LanguageConfig::new(
+      "broken",
+      tree_sitter_rust::LANGUAGE.into(),
+      "(no_such_node) @comment",
+      &[],
+    );
+    assert!(result.is_err());
+  }
+}
@@ -0,0 +1,146 @@
+use std::ops::Range;
+use tree_sitter::{Node, Parser};
+
+/// A test function found in Rust source
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RustTest {
+  /// Path of the test relative to its file, e.g. `tests::parses_input`
+  pub name: String,
+  /// Byte range of the test function, including its attributes
+  pub byte_range: Range<usize>,
+}
+
+/// Find all functions marked `#[test]` (or `#[some_crate::test]`) in Rust source
+pub fn find_rust_tests(text: &str) -> Vec<RustTest> {
+  let mut parser = Parser::new();
+  if parser
+    .set_language(&tree_sitter_rust::LANGUAGE.into())
+    .is_err()
+  {
+    return Vec::new();
+  }
+
+  let Some(tree) = parser.parse(text, None) else {
+    return Vec::new();
+  };
+
+  let mut tests = Vec::new();
+  collect_tests(
+    tree.root_node(),
+    text.as_bytes(),
+    &mut Vec::new(),
+    &mut tests,
+  );
+  tests
+}
+
+fn collect_tests(
+  node: Node,
+  source: &[u8],
+  module_path: &mut Vec<String>,
+  tests: &mut Vec<RustTest>,
+) {
+  let mut cursor = node.walk();
+  for child in node.named_children(&mut cursor) {
+    match child.kind() {
+      "function_item" => {
+        let Some(start) = test_attributes_start(child, source) else {
+          continue;
+        };
+        let Some(name) = child
+          .child_by_field_name("name")
+          .and_then(|name| name.utf8_text(source).ok())
+        else {
+          continue;
+        };
+
+        let mut path = module_path.clone();
+        path.push(name.to_string());
+        tests.push(RustTest {
+          name: path.join("::"),
+          byte_range: start..child.end_byte(),
+        });
+      }
+      "mod_item" => {
+        let name = child
+          .child_by_field_name("name")
+          .and_then(|name| name.utf8_text(source).ok());
+        if let (Some(name), Some(body)) = (name, child.child_by_field_name("body")) {
+          module_path.push(name.to_string());
+          collect_tests(body, source, module_path, tests);
+          module_path.pop();
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+/// Start of the attribute list of a function, if one of its attributes marks it as a test
+fn test_attributes_start(function: Node, source: &[u8]) -> Option<usize> {
+  let mut start = function.start_byte();
+  let mut is_test = false;
+
+  let mut sibling = function.prev_named_sibling();
+  while let Some(node) = sibling {
+    match node.kind() {
+      "attribute_item" => {
+        is_test |= is_test_attribute(node.utf8_text(source).unwrap_or_default());
+        start = node.start_byte();
+      }
+      "line_comment" | "block_comment" => {}
+      _ => break,
+    }
+    sibling = node.prev_named_sibling();
+  }
+
+  is_test.then_some(start)
+}
+
+fn is_test_attribute(text: &str) -> bool {
+  let inner = text.trim_start_matches("#[").trim_end_matches(']');
+  let path = inner.split('(').next().unwrap_or_default().trim();
+  path == "test" || path.ends_with("::test")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_find_top_level_test() {
+    let source = "#[test]\nfn it_works() {\n  assert!(true);\n}\n";
+    let tests = find_rust_tests(source);
+
+    assert_eq!(tests.len(), 1);
+    assert_eq!(tests[0].name, "it_works");
+    assert_eq!(tests[0].byte_range.start, 0);
+  }
+
+  #[test]
+  fn test_find_tests_in_module() {
+    let source = "fn helper() {}\n\n#[cfg(test)]\nmod tests {\n  #[gpui::test]\n  fn first() {}\n\n  #[test]\n  #[ignore]\n  fn second() {}\n}\n";
+    let names: Vec<_> = find_rust_tests(source)
+      .into_iter()
+      .map(|test| test.name)
+      .collect();
+
+    assert_eq!(names, vec!["tests::first", "tests::second"]);
+  }
+
+  #[test]
+  fn test_ignores_plain_functions() {
+    let source = "#[inline]\nfn not_a_test() {}\n#[derive(Debug)]\nstruct Foo;\n";
+    assert!(find_rust_tests(source).is_empty());
+  }
+
+  #[test]
+  fn test_is_test_attribute() {
+    assert!(is_test_attribute("#[test]"));
+    assert!(is_test_attribute(
+      "#[tokio::test(flavor = \"multi_thread\")]"
+    ));
+    assert!(!is_test_attribute("#[cfg(test)]"));
+    assert!(!is_test_attribute("#[testing]"));
+  }
+}
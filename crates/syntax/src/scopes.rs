@@ -0,0 +1,124 @@
+use std::ops::Range;
+use tree_sitter::{Language, Node, Parser};
+
+/// Functions, closures and type bodies, for Rust
+const RUST_SCOPES: &[&str] = &[
+  "function_item",
+  "closure_expression",
+  "impl_item",
+  "trait_item",
+  "mod_item",
+  "struct_item",
+  "enum_item",
+];
+
+/// Functions, methods and classes, for TypeScript and JavaScript
+const TYPESCRIPT_SCOPES: &[&str] = &[
+  "function_declaration",
+  "function_expression",
+  "generator_function_declaration",
+  "arrow_function",
+  "method_definition",
+  "class_declaration",
+  "interface_declaration",
+];
+
+/// Byte ranges of the scopes in `text`: every top-level item, and every
+/// function or type body nested in one, outer scopes before inner ones
+///
+/// `language` is a language name as in `LanguageConfig::name`; an unknown
+/// language has no scopes.
+pub fn find_scopes(text: &str, language: &str) -> Vec<Range<usize>> {
+  let (grammar, kinds): (Language, _) = match language {
+    "rust" => (tree_sitter_rust::LANGUAGE.into(), RUST_SCOPES),
+    "typescript" => (
+      tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+      TYPESCRIPT_SCOPES,
+    ),
+    _ => return Vec::new(),
+  };
+  let mut parser = Parser::new();
+  if parser.set_language(&grammar).is_err() {
+    return Vec::new();
+  }
+  let Some(tree) = parser.parse(text, None) else {
+    return Vec::new();
+  };
+
+  let mut scopes = Vec::new();
+  let root = tree.root_node();
+  let mut cursor = root.walk();
+  for item in root.named_children(&mut cursor) {
+    scopes.push(item.byte_range());
+    collect_scopes(item, kinds, &mut scopes);
+  }
+  scopes
+}
+
+fn collect_scopes(node: Node, kinds: &[&str], scopes: &mut Vec<Range<usize>>) {
+  let mut cursor = node.walk();
+  for child in node.named_children(&mut cursor) {
+    if kinds.contains(&child.kind()) {
+      scopes.push(child.byte_range());
+    }
+    collect_scopes(child, kinds, scopes);
+  }
+}
+
+/// The innermost of `scopes` containing `offset`
+pub fn innermost_scope(scopes: &[Range<usize>], offset: usize) -> Option<Range<usize>> {
+  scopes
+    .iter()
+    .filter(|scope| scope.start <= offset && offset <= scope.end)
+    .min_by_key(|scope| scope.len())
+    .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_rust_scopes() {
+    let source = "use std::fmt;\n\nfn outer() {\n  let f = |x| x + 1;\n}\n\nimpl Foo {\n  fn method(&self) {}\n}\n";
+    let scopes = find_scopes(source, "rust");
+    let texts: Vec<_> = scopes.iter().map(|scope| &source[scope.clone()]).collect();
+    assert_eq!(
+      texts,
+      [
+        "use std::fmt;",
+        "fn outer() {\n  let f = |x| x + 1;\n}",
+        "|x| x + 1",
+        "impl Foo {\n  fn method(&self) {}\n}",
+        "fn method(&self) {}",
+      ]
+    );
+
+    let in_closure = source.find("x + 1").unwrap();
+    assert_eq!(
+      innermost_scope(&scopes, in_closure).map(|scope| &source[scope]),
+      Some("|x| x + 1")
+    );
+    let in_outer = source.find("let f").unwrap();
+    assert_eq!(
+      innermost_scope(&scopes, in_outer).map(|scope| &source[scope]),
+      Some("fn outer() {\n  let f = |x| x + 1;\n}")
+    );
+    assert_eq!(
+      innermost_scope(&scopes, source.find("\n\nfn").unwrap() + 1),
+      None
+    );
+  }
+
+  #[test]
+  fn test_typescript_scopes() {
+    let source = "class A {\n  run() {\n    return [1].map((x) => x);\n  }\n}\n";
+    let scopes = find_scopes(source, "typescript");
+    let in_arrow = source.find("=> x").unwrap();
+    assert_eq!(
+      innermost_scope(&scopes, in_arrow).map(|scope| &source[scope]),
+      Some("(x) => x")
+    );
+    assert!(find_scopes(source, "markdown").is_empty());
+  }
+}
@@ -1,14 +1,17 @@
-use gpui::Hsla;
+use gpui::{Hsla, black, white};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Theme {
   pub is_dark: bool,
+  /// Syntax colors loaded from a theme file, the defaults of the mode if none
+  pub(crate) syntax: Option<SyntaxTheme>,
 }
 
 impl Theme {
   pub fn new(is_dark_mode: bool) -> Self {
     Self {
       is_dark: is_dark_mode,
+      syntax: None,
     }
   }
 
@@ -20,18 +23,31 @@ impl Theme {
     Self::new(false)
   }
 
+  /// Switch to the default theme of the other mode
   pub fn toggle(&mut self) {
-    self.is_dark = !self.is_dark;
+    *self = Self::new(!self.is_dark);
   }
 
   pub fn syntax(&self) -> SyntaxTheme {
-    if self.is_dark {
+    if let Some(syntax) = &self.syntax {
+      syntax.clone()
+    } else if self.is_dark {
       SyntaxTheme::default_dark()
     } else {
       SyntaxTheme::default_light()
     }
   }
 
+  /// Background of the text area
+  pub fn background(&self) -> Hsla {
+    if self.is_dark { black() } else { white() }
+  }
+
+  /// Color of text without a syntax color
+  pub fn foreground(&self) -> Hsla {
+    if self.is_dark { white() } else { black() }
+  }
+
   pub fn cursor(&self) -> Hsla {
     Hsla {
       h: 210.0 / 360.0,
@@ -94,6 +110,54 @@ impl Theme {
       }
     }
   }
+
+  /// Faint color for glyphs standing in for invisible characters
+  pub fn invisible(&self) -> Hsla {
+    if self.is_dark {
+      Hsla {
+        h: 0.0,
+        s: 0.0,
+        l: 0.40,
+        a: 0.8,
+      }
+    } else {
+      Hsla {
+        h: 0.0,
+        s: 0.0,
+        l: 0.70,
+        a: 0.8,
+      }
+    }
+  }
+
+  /// Background of the matches of a search
+  pub fn search_match(&self) -> Hsla {
+    Hsla {
+      h: 45.0 / 360.0,
+      s: 1.0,
+      l: 0.5,
+      a: if self.is_dark { 0.3 } else { 0.4 },
+    }
+  }
+
+  /// Scrollbar thumbs, a grey that shows over text in either mode
+  pub fn scrollbar_thumb(&self) -> Hsla {
+    Hsla {
+      h: 0.0,
+      s: 0.0,
+      l: 0.5,
+      a: 0.5,
+    }
+  }
+
+  pub fn warning(&self) -> Hsla {
+    Hsla {
+      h: 0.0,
+      s: 0.85,
+      l: 0.55,
+      a: 1.0,
+    }
+  }
 }
 
 impl Default for Theme {
@@ -137,7 +201,7 @@ pub enum TokenType {
 }
 
 /// Syntax highlighting theme
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SyntaxTheme {
   pub keyword: Hsla,
   pub keyword_control: Hsla,
@@ -171,6 +235,77 @@ pub struct SyntaxTheme {
   pub embedded: Hsla,
 }
 
+impl TokenType {
+  pub const ALL: [TokenType; 30] = [
+    TokenType::Keyword,
+    TokenType::KeywordControl,
+    TokenType::Function,
+    TokenType::FunctionMethod,
+    TokenType::FunctionSpecial,
+    TokenType::Type,
+    TokenType::TypeBuiltin,
+    TokenType::TypeInterface,
+    TokenType::TypeClass,
+    TokenType::String,
+    TokenType::StringEscape,
+    TokenType::StringRegex,
+    TokenType::Number,
+    TokenType::Boolean,
+    TokenType::Comment,
+    TokenType::CommentDoc,
+    TokenType::Operator,
+    TokenType::Variable,
+    TokenType::VariableSpecial,
+    TokenType::VariableParameter,
+    TokenType::Property,
+    TokenType::Constant,
+    TokenType::ConstantBuiltin,
+    TokenType::Punctuation,
+    TokenType::PunctuationBracket,
+    TokenType::PunctuationDelimiter,
+    TokenType::PunctuationSpecial,
+    TokenType::Attribute,
+    TokenType::Lifetime,
+    TokenType::Embedded,
+  ];
+
+  /// Name of the scope theme files give this token's color under
+  pub fn scope(self) -> &'static str {
+    match self {
+      TokenType::Keyword => "keyword",
+      TokenType::KeywordControl => "keyword.control",
+      TokenType::Function => "function",
+      TokenType::FunctionMethod => "function.method",
+      TokenType::FunctionSpecial => "function.special",
+      TokenType::Type => "type",
+      TokenType::TypeBuiltin => "type.builtin",
+      TokenType::TypeInterface => "type.interface",
+      TokenType::TypeClass => "type.class",
+      TokenType::String => "string",
+      TokenType::StringEscape => "string.escape",
+      TokenType::StringRegex => "string.regex",
+      TokenType::Number => "number",
+      TokenType::Boolean => "boolean",
+      TokenType::Comment => "comment",
+      TokenType::CommentDoc => "comment.doc",
+      TokenType::Operator => "operator",
+      TokenType::Variable => "variable",
+      TokenType::VariableSpecial => "variable.special",
+      TokenType::VariableParameter => "variable.parameter",
+      TokenType::Property => "property",
+      TokenType::Constant => "constant",
+      TokenType::ConstantBuiltin => "constant.builtin",
+      TokenType::Punctuation => "punctuation",
+      TokenType::PunctuationBracket => "punctuation.bracket",
+      TokenType::PunctuationDelimiter => "punctuation.delimiter",
+      TokenType::PunctuationSpecial => "punctuation.special",
+      TokenType::Attribute => "attribute",
+      TokenType::Lifetime => "lifetime",
+      TokenType::Embedded => "embedded",
+    }
+  }
+}
+
 impl SyntaxTheme {
   pub fn color_for_token(&self, token_type: TokenType) -> Hsla {
     match token_type {
@@ -206,6 +341,41 @@ impl SyntaxTheme {
       TokenType::Embedded => self.embedded,
     }
   }
+
+  pub(crate) fn color_for_token_mut(&mut self, token_type: TokenType) -> &mut Hsla {
+    match token_type {
+      TokenType::Keyword => &mut self.keyword,
+      TokenType::KeywordControl => &mut self.keyword_control,
+      TokenType::Function => &mut self.function,
+      TokenType::FunctionMethod => &mut self.function_method,
+      TokenType::FunctionSpecial => &mut self.function_special,
+      TokenType::Type => &mut self.type_name,
+      TokenType::TypeBuiltin => &mut self.type_builtin,
+      TokenType::TypeInterface => &mut self.type_interface,
+      TokenType::TypeClass => &mut self.type_class,
+      TokenType::String => &mut self.string,
+      TokenType::StringEscape => &mut self.string_escape,
+      TokenType::StringRegex => &mut self.string_regex,
+      TokenType::Number => &mut self.number,
+      TokenType::Boolean => &mut self.boolean,
+      TokenType::Comment => &mut self.comment,
+      TokenType::CommentDoc => &mut self.comment_doc,
+      TokenType::Operator => &mut self.operator,
+      TokenType::Variable => &mut self.variable,
+      TokenType::VariableSpecial => &mut self.variable_special,
+      TokenType::VariableParameter => &mut self.variable_parameter,
+      TokenType::Property => &mut self.property,
+      TokenType::Constant => &mut self.constant,
+      TokenType::ConstantBuiltin => &mut self.constant_builtin,
+      TokenType::Punctuation => &mut self.punctuation,
+      TokenType::PunctuationBracket => &mut self.punctuation_bracket,
+      TokenType::PunctuationDelimiter => &mut self.punctuation_delimiter,
+      TokenType::PunctuationSpecial => &mut self.punctuation_special,
+      TokenType::Attribute => &mut self.attribute,
+      TokenType::Lifetime => &mut self.lifetime,
+      TokenType::Embedded => &mut self.embedded,
+    }
+  }
 }
 
 impl SyntaxTheme {
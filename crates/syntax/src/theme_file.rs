@@ -0,0 +1,202 @@
+//! Loading themes from TOML or JSON files
+//!
+//! A theme file names its appearance and maps syntax scopes to hex colors:
+//!
+//! ```toml
+//! appearance = "dark"
+//!
+//! [syntax]
+//! keyword = "#c678dd"
+//! "function.method" = "#61afefcc"
+//! ```
+//!
+//! A scope missing from the file takes the color of its nearest parent scope in the file
+//! (`function.method` falls back to `function`), then the default theme's color.
+
+use std::path::Path;
+
+use gpui::Hsla;
+
+use crate::theme::{Theme, TokenType};
+
+impl Theme {
+  /// Load a theme from a `.toml` or `.json` file
+  pub fn load_from_path(path: &Path) -> Result<Self, String> {
+    let source = std::fs::read_to_string(path)
+      .map_err(|e| format!("Failed to read theme {}: {}", path.display(), e))?;
+    let theme = match path.extension().and_then(|ext| ext.to_str()) {
+      Some("toml") => Self::from_toml(&source),
+      Some("json") => Self::from_json(&source),
+      _ => return Err(format!("Unsupported theme file: {}", path.display())),
+    };
+    theme.map_err(|e| format!("Invalid theme {}: {}", path.display(), e))
+  }
+
+  pub fn from_toml(source: &str) -> Result<Self, String> {
+    let value: toml::Table = toml::from_str(source).map_err(|e| e.to_string())?;
+    let appearance = match value.get("appearance") {
+      Some(appearance) => Some(appearance.as_str().ok_or("appearance must be a string")?),
+      None => None,
+    };
+    let mut scopes = Vec::new();
+    if let Some(syntax) = value.get("syntax") {
+      let syntax = syntax.as_table().ok_or("syntax must be a table")?;
+      for (scope, color) in syntax {
+        let color = color
+          .as_str()
+          .ok_or_else(|| format!("{} must be a color string", scope))?;
+        scopes.push((scope.as_str(), color));
+      }
+    }
+    build_theme(appearance, &scopes)
+  }
+
+  pub fn from_json(source: &str) -> Result<Self, String> {
+    let value: serde_json::Value = serde_json::from_str(source).map_err(|e| e.to_string())?;
+    let value = value.as_object().ok_or("theme must be an object")?;
+    let appearance = match value.get("appearance") {
+      Some(appearance) => Some(appearance.as_str().ok_or("appearance must be a string")?),
+      None => None,
+    };
+    let mut scopes = Vec::new();
+    if let Some(syntax) = value.get("syntax") {
+      let syntax = syntax.as_object().ok_or("syntax must be an object")?;
+      for (scope, color) in syntax {
+        let color = color
+          .as_str()
+          .ok_or_else(|| format!("{} must be a color string", scope))?;
+        scopes.push((scope.as_str(), color));
+      }
+    }
+    build_theme(appearance, &scopes)
+  }
+}
+
+fn build_theme(appearance: Option<&str>, scopes: &[(&str, &str)]) -> Result<Theme, String> {
+  let mut theme = match appearance {
+    Some("dark") | None => Theme::dark(),
+    Some("light") => Theme::light(),
+    Some(other) => return Err(format!("Unknown appearance: {}", other)),
+  };
+
+  let mut colors = Vec::with_capacity(scopes.len());
+  for &(scope, color) in scopes {
+    if !TokenType::ALL.iter().any(|token| token.scope() == scope) {
+      return Err(format!("Unknown scope: {}", scope));
+    }
+    colors.push((scope, parse_color(color)?));
+  }
+
+  let mut syntax = theme.syntax();
+  for token in TokenType::ALL {
+    if let Some(color) = resolve_scope(token.scope(), &colors) {
+      *syntax.color_for_token_mut(token) = color;
+    }
+  }
+  theme.syntax = Some(syntax);
+  Ok(theme)
+}
+
+/// Color of `scope` or its nearest parent scope among `colors`
+fn resolve_scope(mut scope: &str, colors: &[(&str, Hsla)]) -> Option<Hsla> {
+  loop {
+    if let Some((_, color)) = colors.iter().find(|(name, _)| *name == scope) {
+      return Some(*color);
+    }
+    scope = &scope[..scope.rfind('.')?];
+  }
+}
+
+/// Parse `#rrggbb` or `#rrggbbaa`
+fn parse_color(color: &str) -> Result<Hsla, String> {
+  let invalid = || format!("Invalid color: {}", color);
+  let hex = color.strip_prefix('#').ok_or_else(invalid)?;
+  if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+    return Err(invalid());
+  }
+  let value = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+  let rgba = match hex.len() {
+    6 => (value << 8) | 0xff,
+    8 => value,
+    _ => return Err(invalid()),
+  };
+  Ok(gpui::rgba(rgba).into())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::theme::SyntaxTheme;
+
+  #[test]
+  fn test_theme_from_toml() {
+    let theme = Theme::from_toml(
+      r##"
+appearance = "light"
+
+[syntax]
+keyword = "#ff0000"
+"string.escape" = "#00ff0080"
+"##,
+    )
+    .unwrap();
+
+    assert!(!theme.is_dark);
+    let syntax = theme.syntax();
+    assert_eq!(syntax.keyword, gpui::rgba(0xff0000ff).into());
+    assert_eq!(syntax.string_escape, gpui::rgba(0x00ff0080).into());
+    assert_eq!(syntax.number, SyntaxTheme::default_light().number);
+  }
+
+  #[test]
+  fn test_theme_from_json() {
+    let theme =
+      Theme::from_json(r##"{ "appearance": "dark", "syntax": { "comment": "#123456" } }"##)
+        .unwrap();
+
+    assert!(theme.is_dark);
+    assert_eq!(theme.syntax().comment, gpui::rgba(0x123456ff).into());
+    assert_eq!(theme.syntax().keyword, SyntaxTheme::default_dark().keyword);
+  }
+
+  #[test]
+  fn test_missing_scope_falls_back_to_parent() {
+    let theme = Theme::from_toml("[syntax]\nfunction = \"#abcdef\"\n").unwrap();
+    let syntax = theme.syntax();
+
+    assert_eq!(syntax.function_method, gpui::rgba(0xabcdefff).into());
+    assert_eq!(syntax.function_special, gpui::rgba(0xabcdefff).into());
+    assert_eq!(
+      syntax.type_builtin,
+      SyntaxTheme::default_dark().type_builtin
+    );
+  }
+
+  #[test]
+  fn test_invalid_theme() {
+    assert!(Theme::from_toml("[syntax]\nkeyword = \"red\"\n").is_err());
+    assert!(Theme::from_toml("[syntax]\nkeyword = \"#ff00\"\n").is_err());
+    assert!(Theme::from_toml("[syntax]\nnot_a_scope = \"#ff0000\"\n").is_err());
+    assert!(Theme::from_json(r#"{ "appearance": "sepia" }"#).is_err());
+    assert!(Theme::from_json("{").is_err());
+  }
+
+  #[test]
+  fn test_load_from_path() {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path();
+    let path = dir.join("theme.toml");
+    std::fs::write(
+      &path,
+      "appearance = \"light\"\n[syntax]\nkeyword = \"#010203\"\n",
+    )
+    .unwrap();
+
+    let theme = Theme::load_from_path(&path).unwrap();
+    assert!(!theme.is_dark);
+    assert_eq!(theme.syntax().keyword, gpui::rgba(0x010203ff).into());
+
+    assert!(Theme::load_from_path(&dir.join("missing.json")).is_err());
+    assert!(Theme::load_from_path(&dir.join("theme.yaml")).is_err());
+  }
+}
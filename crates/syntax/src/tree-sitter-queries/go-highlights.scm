@@ -0,0 +1,89 @@
+; Functions
+
+(function_declaration
+  name: (identifier) @function)
+
+(method_declaration
+  name: (field_identifier) @function.method)
+
+(call_expression
+  function: (selector_expression
+    field: (field_identifier) @function.method))
+
+(call_expression
+  function: (identifier) @function)
+
+; Types and properties
+
+(type_identifier) @type
+
+(field_identifier) @property
+
+; Literals
+
+[
+  (true)
+  (false)
+  (nil)
+  (iota)
+] @constant
+
+[
+  (int_literal)
+  (float_literal)
+  (imaginary_literal)
+] @number
+
+(escape_sequence) @string.escape
+
+[
+  (interpreted_string_literal)
+  (raw_string_literal)
+  (rune_literal)
+] @string
+
+(comment) @comment
+
+; Keywords
+
+[
+  "if"
+  "else"
+  "for"
+  "range"
+  "switch"
+  "case"
+  "default"
+  "select"
+  "break"
+  "continue"
+  "fallthrough"
+  "goto"
+  "return"
+  "go"
+  "defer"
+] @keyword.control
+
+[
+  "package"
+  "import"
+  "func"
+  "type"
+  "struct"
+  "interface"
+  "map"
+  "chan"
+  "const"
+  "var"
+] @keyword
+
+; Punctuation
+
+[
+  "("
+  ")"
+  "["
+  "]"
+  "{"
+  "}"
+] @punctuation.bracket
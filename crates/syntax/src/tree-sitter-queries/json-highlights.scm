@@ -0,0 +1,23 @@
+(pair
+  key: (string) @property)
+
+(escape_sequence) @string.escape
+
+(string) @string
+
+(number) @number
+
+[
+  (true)
+  (false)
+  (null)
+] @constant
+
+(comment) @comment
+
+[
+  "["
+  "]"
+  "{"
+  "}"
+] @punctuation.bracket
@@ -0,0 +1,31 @@
+; Block structure only, inline text is left plain
+
+[
+  (atx_heading)
+  (setext_heading)
+] @keyword
+
+(info_string) @type
+
+[
+  (fenced_code_block)
+  (indented_code_block)
+] @string
+
+[
+  (block_quote)
+  (html_block)
+] @comment
+
+[
+  (list_marker_minus)
+  (list_marker_plus)
+  (list_marker_star)
+  (list_marker_dot)
+  (list_marker_parenthesis)
+  (thematic_break)
+] @operator
+
+(link_label) @property
+
+(link_destination) @string
@@ -0,0 +1,96 @@
+; Functions
+
+(decorator) @attribute
+
+(function_definition
+  name: (identifier) @function)
+
+(call
+  function: (attribute
+    attribute: (identifier) @function.method))
+
+(call
+  function: (identifier) @function)
+
+; Types
+
+(class_definition
+  name: (identifier) @type)
+
+(type
+  (identifier) @type)
+
+; Properties
+
+(attribute
+  attribute: (identifier) @property)
+
+; Literals
+
+[
+  (true)
+  (false)
+  (none)
+] @constant
+
+[
+  (integer)
+  (float)
+] @number
+
+(escape_sequence) @string.escape
+
+(string) @string
+
+(comment) @comment
+
+; Keywords
+
+[
+  "if"
+  "elif"
+  "else"
+  "for"
+  "while"
+  "try"
+  "except"
+  "finally"
+  "raise"
+  "return"
+  "yield"
+  "break"
+  "continue"
+  "pass"
+  "with"
+] @keyword.control
+
+[
+  "def"
+  "class"
+  "lambda"
+  "import"
+  "from"
+  "as"
+  "global"
+  "nonlocal"
+  "assert"
+  "del"
+  "in"
+  "is"
+  "not"
+  "and"
+  "or"
+  "async"
+  "await"
+] @keyword
+
+; Punctuation
+
+[
+  "("
+  ")"
+  "["
+  "]"
+  "{"
+  "}"
+] @punctuation.bracket
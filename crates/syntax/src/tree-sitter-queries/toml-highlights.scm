@@ -0,0 +1,46 @@
+; Table headers
+
+(table
+  (bare_key) @type)
+
+(table
+  (dotted_key
+    (bare_key) @type))
+
+(table_array_element
+  (bare_key) @type)
+
+; Keys
+
+[
+  (bare_key)
+  (quoted_key)
+] @property
+
+; Values
+
+(string) @string
+
+[
+  (integer)
+  (float)
+] @number
+
+[
+  (boolean)
+  (offset_date_time)
+  (local_date_time)
+  (local_date)
+  (local_time)
+] @constant
+
+(comment) @comment
+
+"=" @operator
+
+[
+  "["
+  "]"
+  "{"
+  "}"
+] @punctuation.bracket